@@ -0,0 +1,267 @@
+// Render benchmarks for large datasets
+//
+// `cargo bench` runs every case a few times and prints the median; a case slower
+// than its budget fails the run. Data comes from the generators below instead of
+// checked-in fixtures. Pass a substring to run only matching cases:
+// `cargo bench -- line`.
+
+use gramgraph::csv_reader::{self, ColumnStore, CsvData, CsvOptions};
+use gramgraph::data::PlotData;
+use gramgraph::graph::Canvas;
+use gramgraph::{compiler, parser, render, resolve, scale, transform, OutputFormat, RenderOptions};
+use std::hint::black_box;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+const RUNS: usize = 5;
+
+/// Deterministic pseudo-random numbers in [0, 1), so every run draws the same data
+struct Noise(u64);
+
+impl Noise {
+    fn next(&mut self) -> f64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Columns `x` (row index), `y` (a noisy random walk), and `g` (one of `groups`
+/// labels, cycling), as CSV text
+fn series_csv(rows: usize, groups: usize) -> String {
+    let mut noise = Noise(rows as u64);
+    let mut csv = String::with_capacity(rows * 24);
+    csv.push_str("x,y,g\n");
+    let mut y = 0.0;
+    for i in 0..rows {
+        y += noise.next() - 0.5;
+        csv.push_str(&format!("{},{:.4},g{}\n", i, y, i % groups.max(1)));
+    }
+    csv
+}
+
+fn load(csv: &str) -> CsvData {
+    csv_reader::read_csv(csv.as_bytes(), CsvOptions::default()).unwrap()
+}
+
+struct Case {
+    name: &'static str,
+    budget: Duration,
+    /// Builds one run's input (untimed) and returns the timed part
+    prepare: Box<dyn Fn() -> Box<dyn FnOnce()>>,
+}
+
+fn render_case(name: &'static str, budget_ms: u64, csv: String, dsl: &'static str) -> Case {
+    let data = load(&csv);
+    Case {
+        name,
+        budget: Duration::from_millis(budget_ms),
+        prepare: Box::new(move || {
+            let data = data.clone();
+            Box::new(move || {
+                let options = RenderOptions {
+                    format: OutputFormat::Png,
+                    ..RenderOptions::default()
+                };
+                black_box(render(dsl, data, options).unwrap());
+            })
+        }),
+    }
+}
+
+/// Times only compiling and drawing `dsl`, with resolution, transformation, and
+/// scaling done while preparing, so point binning's share isn't lost in CSV parsing
+fn draw_case(
+    name: &'static str,
+    budget_ms: u64,
+    format: OutputFormat,
+    data: Rc<CsvData>,
+    dsl: &'static str,
+) -> Case {
+    Case {
+        name,
+        budget: Duration::from_millis(budget_ms),
+        prepare: Box::new(move || {
+            let options = RenderOptions {
+                format: format.clone(),
+                ..RenderOptions::default()
+            };
+            let spec = parser::parse_plot_spec(dsl).unwrap().1;
+            let data = PlotData::from_csv(CsvData::clone(&data));
+            let resolved = resolve::resolve_plot_aesthetics(&spec, &data).unwrap();
+            let render_data = transform::apply_transformations(&resolved, &data, &options).unwrap();
+            let scales = scale::build_scales(&render_data, &resolved).unwrap();
+            Box::new(move || {
+                let scene =
+                    compiler::compile_geometry(render_data, scales, &resolved, &options).unwrap();
+                black_box(Canvas::execute(scene, &options).unwrap());
+            })
+        }),
+    }
+}
+
+/// Twenty layers over one dataset. A panel's chart context, mesh, and axes are built
+/// once however many layers it has, so each layer past the first adds only its own
+/// drawing; `main` prints the cost per extra layer next to `layers_1_png`
+const TWENTY_LAYERS: &str = "aes(x: x, y: y) | line(alpha: 0.5) | point(size: 1) | line(alpha: 0.5) | point(size: 1) | line(alpha: 0.5) | point(size: 1) | line(alpha: 0.5) | point(size: 1) | line(alpha: 0.5) | point(size: 1) | line(alpha: 0.5) | point(size: 1) | line(alpha: 0.5) | point(size: 1) | line(alpha: 0.5) | point(size: 1) | line(alpha: 0.5) | point(size: 1) | line(alpha: 0.5) | point(size: 1)";
+
+fn cases() -> Vec<Case> {
+    let load_csv = series_csv(500_000, 10);
+    let walk = Rc::new(load(&series_csv(1_000_000, 1)));
+    let small_walk = Rc::new(load(&series_csv(10_000, 1)));
+    vec![
+        Case {
+            name: "csv_load_500k",
+            budget: Duration::from_millis(600),
+            prepare: Box::new(move || {
+                let csv = load_csv.clone();
+                Box::new(move || {
+                    let data = load(&csv);
+                    black_box(ColumnStore::new(&data.headers, &data.rows));
+                })
+            }),
+        },
+        render_case(
+            "line_100k",
+            300,
+            series_csv(100_000, 1),
+            "aes(x: x, y: y) | line()",
+        ),
+        render_case(
+            "line_1m",
+            1000,
+            series_csv(1_000_000, 1),
+            "aes(x: x, y: y) | line()",
+        ),
+        render_case(
+            "scatter_10_groups_x_100k",
+            2500,
+            series_csv(1_000_000, 10),
+            "aes(x: x, y: y, color: g) | point(size: 2)",
+        ),
+        draw_case(
+            "points_1m_exact_png",
+            1500,
+            OutputFormat::Png,
+            walk.clone(),
+            "aes(x: x, y: y) | point(size: 2, fast: false)",
+        ),
+        draw_case(
+            "points_1m_binned_png",
+            150,
+            OutputFormat::Png,
+            walk.clone(),
+            "aes(x: x, y: y) | point(size: 2)",
+        ),
+        draw_case(
+            "points_1m_exact_svg",
+            1500,
+            OutputFormat::Svg,
+            walk.clone(),
+            "aes(x: x, y: y) | point(size: 2, fast: false)",
+        ),
+        draw_case(
+            "points_1m_binned_svg",
+            150,
+            OutputFormat::Svg,
+            walk,
+            "aes(x: x, y: y) | point(size: 2)",
+        ),
+        draw_case(
+            "layers_1_png",
+            150,
+            OutputFormat::Png,
+            small_walk.clone(),
+            "aes(x: x, y: y) | line(alpha: 0.5)",
+        ),
+        draw_case(
+            "layers_20_png",
+            400,
+            OutputFormat::Png,
+            small_walk,
+            TWENTY_LAYERS,
+        ),
+        render_case(
+            "bar_count_10k_categories",
+            500,
+            series_csv(100_000, 10_000),
+            "aes(x: g) | bar(stat: \"count\")",
+        ),
+        render_case(
+            "facet_16_panels",
+            600,
+            series_csv(160_000, 16),
+            "aes(x: x, y: y) | line() | facet_wrap(by: g, ncol: 4)",
+        ),
+    ]
+}
+
+fn main() {
+    // `cargo bench` passes `--bench`; any other argument filters cases by name
+    let filter: Option<String> = std::env::args().skip(1).find(|arg| !arg.starts_with('-'));
+    let mut over_budget = Vec::new();
+    let mut medians: Vec<(&str, Duration)> = Vec::new();
+
+    for case in cases() {
+        if filter
+            .as_ref()
+            .is_some_and(|f| !case.name.contains(f.as_str()))
+        {
+            continue;
+        }
+        let mut times: Vec<Duration> = (0..RUNS)
+            .map(|_| {
+                let run = (case.prepare)();
+                let start = Instant::now();
+                run();
+                start.elapsed()
+            })
+            .collect();
+        times.sort();
+        let median = times[RUNS / 2];
+        medians.push((case.name, median));
+        let verdict = if median > case.budget {
+            over_budget.push(case.name);
+            "OVER BUDGET"
+        } else {
+            "ok"
+        };
+        println!(
+            "{:<28} median {:>8.1} ms  (min {:>8.1} ms, budget {} ms)  {}",
+            case.name,
+            median.as_secs_f64() * 1000.0,
+            times[0].as_secs_f64() * 1000.0,
+            case.budget.as_millis(),
+            verdict
+        );
+    }
+
+    let median_of = |name: &str| medians.iter().find(|(n, _)| *n == name).map(|(_, t)| *t);
+    for format in ["png", "svg"] {
+        let exact = median_of(&format!("points_1m_exact_{}", format));
+        let binned = median_of(&format!("points_1m_binned_{}", format));
+        if let (Some(exact), Some(binned)) = (exact, binned) {
+            println!(
+                "point binning speedup ({}): {:.1}x",
+                format,
+                exact.as_secs_f64() / binned.as_secs_f64()
+            );
+        }
+    }
+
+    if let (Some(one), Some(twenty)) = (median_of("layers_1_png"), median_of("layers_20_png")) {
+        println!(
+            "20 layers vs 1: {:.1}x the time, {:.1} ms per extra layer",
+            twenty.as_secs_f64() / one.as_secs_f64(),
+            (twenty.as_secs_f64() - one.as_secs_f64()) * 1000.0 / 19.0
+        );
+    }
+
+    if !over_budget.is_empty() {
+        eprintln!("Over budget: {}", over_budget.join(", "));
+        std::process::exit(1);
+    }
+}
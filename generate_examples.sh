@@ -9,6 +9,18 @@ echo "Generating example images..."
 echo "Generating line_grouped.svg..."
 cat examples/timeseries.csv | cargo run -- 'aes(x: time, y: value, color: series) | line() | point() | theme_minimal()' --format svg > examples/line_grouped.svg
 
+# Line Types
+echo "Generating linetype.svg..."
+cat examples/regional_sales.csv | cargo run -- 'aes(x: time, y: sales, linetype: region) | line(color: "black", width: 2) | facet_wrap(by: product) | labs(title: "Sales by Region") | theme_minimal()' --format svg > examples/linetype.svg
+
+# Group Without Styling
+echo "Generating group_lines.svg..."
+cat examples/sensor_readings.csv | cargo run -- 'aes(x: hour, y: temp, color: site, group: sensor) | line(alpha: 0.5) | labs(title: "Hourly Temperature per Sensor") | theme_minimal()' --format svg > examples/group_lines.svg
+
+# Thumbnail (small canvas layout)
+echo "Generating thumbnail.svg..."
+cat examples/timeseries.csv | cargo run -- 'aes(x: time, y: value, color: series) | line() | labs(title: "Thumbnail", x: "Time", y: "Value") | theme_minimal()' --width 320 --height 200 --format svg > examples/thumbnail.svg
+
 # Datetime Scale
 echo "Generating weather_datetime.svg..."
 cat examples/weather_hourly.csv | cargo run -- 'aes(x: time, y: temp) | line() | point() | theme_minimal() | scale_x_datetime(interval: "20h", format: "%b %-d %H:%M")' --format svg > examples/weather_datetime.svg
@@ -17,18 +29,74 @@ cat examples/weather_hourly.csv | cargo run -- 'aes(x: time, y: temp) | line() |
 echo "Generating scatter.svg..."
 cat examples/demographics.csv | cargo run -- 'aes(x: height, y: weight, color: gender) | point(size: 5) | theme_minimal()' --format svg > examples/scatter.svg
 
+# Text Labels
+echo "Generating text_labels.svg..."
+cat examples/countries.csv | cargo run -- 'aes(x: gdp, y: life_exp, color: continent) | point(size: 5) | text(label: country, size: 11, nudge_y: 1.2) | labs(title: "Labeled Scatter Plot", x: "GDP per capita (k USD)", y: "Life expectancy") | theme_minimal() | theme(legend_position: "lower-right")' --format svg > examples/text_labels.svg
+
 # Shape and Alpha Mappings
 echo "Generating shape_alpha.svg..."
 cat examples/demographics.csv | cargo run -- 'aes(x: height, y: weight, shape: gender, alpha: gender) | point(size: 7, color: "steelblue") | labs(title: "Shape and Alpha Mapping", x: "Height (cm)", y: "Weight (kg)") | theme_minimal()' --format svg > examples/shape_alpha.svg
 
+# Outlined Points
+echo "Generating point_outline.svg..."
+cat examples/countries.csv | cargo run -- 'aes(x: gdp, y: life_exp, color: continent, shape: continent) | point(size: 7, fill: "white", stroke: 2.5) | labs(title: "Outlined Points", x: "GDP per capita (k USD)", y: "Life expectancy") | theme_minimal() | theme(legend_position: "lower-right")' --format svg > examples/point_outline.svg
+
+# Color and Shape from Different Columns
+echo "Generating color_shape.svg..."
+cat examples/regional_sales.csv | cargo run -- 'aes(x: time, y: sales, color: region, shape: product) | line() | point(size: 6) | labs(title: "Sales by Region and Product", x: "Month", y: "Sales") | theme_minimal() | theme(legend_position: "lower-right")' --format svg > examples/color_shape.svg
+
+# Named Palettes
+echo "Generating palette.svg..."
+cat examples/regional_sales.csv | cargo run -- 'aes(x: time, y: sales, color: region) | line(width: 2) | point(size: 5) | scale_color(palette: "okabe_ito") | facet_wrap(by: product) | labs(title: "Colorblind-Safe Palette", x: "Month", y: "Sales") | theme_minimal()' --format svg > examples/palette.svg
+
+# Missing Values
+echo "Generating missing_values.svg..."
+cat examples/gappy_sales.csv | cargo run -- 'aes(x: month, y: sales, color: region) | line(width: 2) | point(size: 5) | labs(title: "Sales with Missing Months", x: "Month", y: "Sales") | theme_minimal()' --format svg > examples/missing_values.svg
+
+# Manual Colors
+echo "Generating color_manual.svg..."
+cat examples/regional_sales.csv | cargo run -- 'aes(x: time, y: sales, color: region) | line() | point() | scale_color_manual(values: "North" = "#1b9e77", "South" = "#d95f02", "East" = "#7570b3", "West" = "#e7298a") | facet_wrap(by: product) | labs(title: "Manual Colors by Region") | theme_minimal()' --format svg > examples/color_manual.svg
+
+# Continuous Color Gradient
+echo "Generating continuous_color.svg..."
+cat examples/measurements.csv | cargo run -- 'aes(x: elapsed, y: temperature, color: pressure) | point(size: 6) | scale_color(low: "blue", high: "red") | labs(title: "Temperature Colored by Pressure", x: "Elapsed Time (hrs)", y: "Temperature (C)") | theme_minimal()' --format svg > examples/continuous_color.svg
+
+# Continuous Size (Bubble Chart)
+echo "Generating bubble.svg..."
+cat examples/countries.csv | cargo run -- 'aes(x: gdp, y: life_exp, size: population, color: continent) | point(alpha: 0.7) | labs(title: "Population Bubbles", x: "GDP per capita (k USD)", y: "Life expectancy") | theme_minimal()' --format svg > examples/bubble.svg
+
+# Recency Fade
+echo "Generating point_fade.svg..."
+cat examples/weather_hourly.csv | cargo run -- 'aes(x: time, y: temp) | line(color: "gray80") | point(size: 6, color: "steelblue", fade_by: time) | labs(title: "Recency Fade", x: "Time", y: "Temperature") | theme_minimal() | scale_x_datetime(interval: "20h", format: "%b %-d %H:%M")' --format svg > examples/point_fade.svg
+
+# Layer Filters
+echo "Generating layer_filter.svg..."
+cat examples/countries.csv | cargo run -- 'aes(x: gdp, y: life_exp) | point(size: 5, color: "#CCCCCC") | point(filter: continent == "Europe", size: 5, color: "#D62728") | text(filter: continent == "Europe" && gdp > 50, label: country, size: 11, nudge_y: 1.2) | labs(title: "European Countries", x: "GDP per capita (k USD)", y: "Life expectancy") | theme_minimal()' --format svg > examples/layer_filter.svg
+
+# Jittered Points
+echo "Generating point_jitter.svg..."
+cat examples/ratings.csv | cargo run -- 'aes(x: product, y: rating) | boxplot(alpha: 0.25) | jitter(width: 0.4, size: 4, alpha: 0.6, color: "#4682B4") | labs(title: "Jittered Ratings", x: "Plan", y: "Rating") | theme_minimal()' --format svg > examples/point_jitter.svg
+
 # Dodged Bar Chart
 echo "Generating bar_dodge.svg..."
 cat examples/financials.csv | cargo run -- 'aes(x: quarter, y: amount, color: type) | bar(position: "dodge") | theme_minimal()' --format svg > examples/bar_dodge.svg
 
+# Dodged Points and Lines
+echo "Generating point_dodge.svg..."
+cat examples/financials.csv | cargo run -- 'aes(x: quarter, y: amount, color: type) | bar(position: "dodge", alpha: 0.4) | line(position: "dodge") | point(position: "dodge", size: 6) | theme_minimal()' --format svg > examples/point_dodge.svg
+
 # Stacked Bar Chart
 echo "Generating bar_stack.svg..."
 cat examples/financials.csv | cargo run -- 'aes(x: quarter, y: amount, color: type) | bar(position: "stack") | theme_minimal()' --format svg > examples/bar_stack.svg
 
+# Stack Order
+echo "Generating bar_stack_order.svg..."
+cat examples/browser_share.csv | cargo run -- 'aes(x: quarter, y: share, color: browser) | bar(position: "stack") | scale_color(levels: "Chrome", "Safari", "Edge", "Firefox", "Other") | labs(title: "Browser Share", y: "Share (%)") | theme_minimal()' --format svg > examples/bar_stack_order.svg
+
+# Diverging Stacked Bar Chart
+echo "Generating bar_stack_diverging.svg..."
+cat examples/cashflow.csv | cargo run -- 'aes(x: month, y: amount, color: flow) | bar(position: "stack") | hline(yintercept: 0, color: "black") | theme_minimal()' --format svg > examples/bar_stack_diverging.svg
+
 # Triple Dodged Bar Chart
 echo "Generating bar_triple_dodge.svg..."
 cat examples/financials_triple.csv | cargo run -- 'aes(x: quarter, y: amount, color: type) | bar(position: "dodge") | theme_minimal()' --format svg > examples/bar_triple_dodge.svg
@@ -37,10 +105,58 @@ cat examples/financials_triple.csv | cargo run -- 'aes(x: quarter, y: amount, co
 echo "Generating bar_triple_stack.svg..."
 cat examples/financials_triple.csv | cargo run -- 'aes(x: quarter, y: amount, color: type) | bar(position: "stack") | theme_minimal()' --format svg > examples/bar_triple_stack.svg
 
+# Filled Stacked Bar Chart
+echo "Generating bar_fill.svg..."
+cat examples/financials_triple.csv | cargo run -- 'aes(x: quarter, y: amount, color: type) | bar(position: "fill") | scale_y(format: "percent") | labs(title: "Quarterly Mix", y: "Share") | theme_minimal()' --format svg > examples/bar_fill.svg
+
+# Bars on a Numeric Axis
+echo "Generating bar_continuous.svg..."
+cat examples/annual_rainfall.csv | cargo run -- 'aes(x: year, y: rainfall) | bar(x_continuous: true, color: "#9ecae1") | line(stat: "rolling_mean", window: 5, color: "#08519c", width: 2) | labs(title: "Annual Rainfall", subtitle: "5-year rolling mean", x: "Year", y: "Rainfall (mm)") | theme_minimal()' --format svg > examples/bar_continuous.svg
+
+# Bar Outlines
+echo "Generating bar_border.svg..."
+cat examples/financials_triple.csv | cargo run -- 'aes(x: quarter, y: amount, color: type) | bar(position: "stack", border: "black", border_width: 1.5) | scale_color(palette: "pastel") | labs(title: "Quarterly Spend", subtitle: "Pastel fills with black outlines", x: "Quarter", y: "Amount") | theme_minimal()' --format svg > examples/bar_border.svg
+
+# Sorted Bars
+echo "Generating sort_pareto.svg..."
+cat examples/countries.csv | cargo run -- 'aes(x: country, y: gdp) | sort(by: gdp, desc: true) | bar(color: "#4682B4") | labs(title: "GDP per Capita, Highest First", x: "Country", y: "GDP per capita (k USD)") | theme_minimal()' --format svg > examples/sort_pareto.svg
+
+# Derived Columns
+echo "Generating mutate.svg..."
+cat examples/countries.csv | cargo run -- 'mutate(gdp_total: gdp * population / 1000) | sort(by: gdp_total, desc: true) | aes(x: country, y: gdp_total) | bar(color: "#2E8B57") | scale_x(rotate: 45) | labs(title: "Total GDP", x: "Country", y: "GDP (trillion USD)") | theme_minimal()' --format svg > examples/mutate.svg
+
+# Pivot Longer
+echo "Generating pivot_longer.svg..."
+cat examples/wide_sales.csv | cargo run -- 'pivot_longer(cols: north, south, east, names_to: region, values_to: sales) | aes(x: month, y: sales, color: region) | line(width: 2) | point(size: 5) | labs(title: "Monthly Sales from a Wide Table", x: "Month", y: "Sales") | theme_minimal() | theme(legend_position: "lower-right")' --format svg > examples/pivot_longer.svg
+
+# Summarize Stage
+echo "Generating summarize.svg..."
+cat examples/regional_sales.csv | cargo run -- 'aes(x: time, y: total, color: region) | summarize(by: [time, region], y: sum(sales), as: total) | line(width: 2) | point(size: 5) | labs(title: "Total Sales by Region", x: "Month", y: "Sales (all products)") | theme_minimal() | theme(legend_position: "lower-right")' --format svg > examples/summarize.svg
+
+# Summarized Bar Chart
+echo "Generating bar_stat_mean.svg..."
+cat examples/regional_sales.csv | cargo run -- 'aes(x: region, y: sales, color: product) | bar(position: "dodge", stat: "mean") | labs(title: "Mean Sales per Period", x: "Region", y: "Sales") | theme_minimal()' --format svg > examples/bar_stat_mean.svg
+
+# Count Bar Chart
+echo "Generating bar_count.svg..."
+cat examples/ratings.csv | cargo run -- 'aes(x: product, color: rating) | bar(stat: "count", position: "stack") | labs(title: "Ratings per Plan", x: "Plan") | theme_minimal()' --format svg > examples/bar_count.svg
+
 # Faceted Plot with Color Grouping
 echo "Generating facets.svg..."
 cat examples/regional_sales.csv | cargo run -- 'aes(x: time, y: sales, color: product) | line() | facet_wrap(by: region) | theme_minimal()' --format svg > examples/facets.svg
 
+# Facets of a Binned Numeric Column
+echo "Generating facet_bins.svg..."
+cat examples/countries.csv | cargo run -- 'aes(x: gdp, y: life_exp, color: continent) | point(size: 5) | facet_wrap(by: population, bins: 3, cut: "quantile", ncol: 3) | labs(title: "Wealth and Health by Population Tercile", x: "GDP per Capita", y: "Life Expectancy") | theme_minimal()' --format svg > examples/facet_bins.svg
+
+# Facet Grid
+echo "Generating facet_grid.svg..."
+cat examples/regional_sales.csv | cargo run -- 'aes(x: time, y: sales) | line(color: "steelblue", width: 2) | facet_grid(rows: product, cols: region, scales: "free_y") | theme_minimal()' --format svg > examples/facet_grid.svg
+
+# Facet Labels
+echo "Generating facet_labels.svg..."
+cat examples/regional_sales.csv | cargo run -- 'aes(x: time, y: sales, color: product) | line(width: 2) | facet_grid(rows: product, cols: region, labeller: "value", label_size: 12) | labs(title: "Sales by Region and Product") | theme_minimal()' --format svg > examples/facet_labels.svg
+
 # --- New Examples ---
 
 # Histogram with Theme
@@ -83,6 +199,10 @@ cat examples/timeseries.csv | cargo run -- 'aes(x: time, y: value, color: series
 echo "Generating abline_segment.svg..."
 cat examples/demographics.csv | cargo run -- 'aes(x: height, y: weight, color: gender) | point(alpha: 0.55, size: 5) | abline(slope: 1, intercept: -100, color: "gray30", width: 2, label: "Reference trend") | segment(x: 160, y: 55, xend: 185, yend: 85, color: "red", width: 2, label: "Manual segment") | labs(title: "Abline and Segment", x: "Height (cm)", y: "Weight (kg)") | theme_minimal() | theme(legend_position: "bottom")' --format svg > examples/abline_segment.svg
 
+# Segments from Data
+echo "Generating segment_arrows.svg..."
+cat examples/renewables.csv | cargo run -- 'mutate(start: 2015, end: 2023) | aes(x: start, y: share_2015, color: country) | segment(xend: end, yend: share_2023, arrow: true, width: 2) | point(size: 4) | labs(title: "Renewable Share of Energy, 2015 to 2023", x: "Year", y: "Share (%)") | theme_minimal() | theme(legend_position: "upper-left")' --format svg > examples/segment_arrows.svg
+
 # Line Range
 echo "Generating linerange.svg..."
 cat examples/intervals.csv | cargo run -- 'aes(x: time, y: estimate, ymin: lower, ymax: upper, color: series) | linerange(width: 2, alpha: 0.75) | point(size: 4) | labs(title: "Line Range Intervals", x: "Time", y: "Estimate") | theme_minimal()' --format svg > examples/linerange.svg
@@ -107,6 +227,10 @@ cat examples/demographics.csv | cargo run -- 'aes(x: height, y: weight) | point(
 echo "Generating smooth_loess.svg..."
 cat examples/demographics.csv | cargo run -- 'aes(x: height, y: weight) | point(alpha: 0.35) | smooth(method: "loess", span: 0.65, color: "red", width: 3) | labs(title: "Height vs Weight", subtitle: "LOESS Fit") | theme_minimal()' --format svg > examples/smooth_loess.svg
 
+# Category Order
+echo "Generating category_order.svg..."
+cat examples/countries.csv | cargo run -- 'aes(x: country, y: gdp) | bar(color: "steelblue") | labs(title: "GDP per Capita, Highest First", x: "Country", y: "USD (thousands)") | theme_minimal() | scale_x_discrete(order: "value")' --format svg > examples/category_order.svg
+
 # Reverse Scale (Note: scales come LAST in parsing order)
 echo "Generating scale_reverse.svg..."
 cat examples/timeseries.csv | cargo run -- 'aes(x: time, y: value, color: series) | line() | labs(title: "Reverse Time Axis") | theme_minimal() | scale_x_reverse()' --format svg > examples/scale_reverse.svg
@@ -119,6 +243,10 @@ cat examples/scales.csv | cargo run -- 'aes(x: x, y: value) | line(color: "steel
 echo "Generating scale_sqrt.svg..."
 cat examples/scales.csv | cargo run -- 'aes(x: x, y: value) | line(color: "purple", width: 2) | point(shape: "diamond", size: 6, color: "purple") | labs(title: "Square Root X Scale", x: "Input", y: "Value") | theme_minimal() | scale_x_sqrt()' --format svg > examples/scale_sqrt.svg
 
+# Rolling Mean
+echo "Generating rolling_mean.svg..."
+cat examples/daily_visits.csv | cargo run -- 'aes(x: date, y: visits) | line(color: "#4682B4", alpha: 0.35) | line(stat: "rolling_mean", window: 7, color: "#4682B4", width: 2) | scale_x_datetime(format: "%b %-d") | labs(title: "Daily Visits", subtitle: "7-day rolling mean", x: "Date", y: "Visits") | theme_minimal()' --format svg > examples/rolling_mean.svg
+
 # Boxplot
 echo "Generating boxplot.svg..."
 cat examples/demographics.csv | cargo run -- 'aes(x: gender, y: height, color: gender) | boxplot() | theme_minimal()' --format svg > examples/boxplot.svg
@@ -135,14 +263,30 @@ cat examples/distribution.csv | cargo run -- 'aes(x: value) | density() | labs(t
 echo "Generating density_grouped.svg..."
 cat examples/demographics.csv | cargo run -- 'aes(x: height, color: gender) | density(alpha: 0.4) | labs(title: "Height Distribution by Gender", x: "Height (cm)", y: "Density") | theme_minimal()' --format svg > examples/density_grouped.svg
 
+# Density Curves without Fill
+echo "Generating density_outline.svg..."
+cat examples/demographics.csv | cargo run -- 'aes(x: height, color: gender) | density(fill: false, bw: 4) | labs(title: "Height Distribution by Gender", x: "Height (cm)", y: "Density") | theme_minimal()' --format svg > examples/density_outline.svg
+
+# Donut Chart
+echo "Generating donut.svg..."
+cat examples/energy_mix.csv | cargo run -- 'aes(x: source, y: share) | pie(donut: 0.5, other: 0.03) | labs(title: "Electricity Mix")' --format svg > examples/donut.svg
+
 # Heatmap (Categorical)
 echo "Generating heatmap.svg..."
 cat examples/heatmap_data.csv | cargo run -- 'aes(x: x, y: y, fill: value) | heatmap() | labs(title: "Weekly Activity Heatmap", x: "Day", y: "Time of Day") | theme_minimal()' --format svg > examples/heatmap.svg
 
+# Tile Grid (numeric x, categorical y)
+echo "Generating tile.svg..."
+cat examples/activity_tiles.csv | cargo run -- 'aes(x: hour, y: weekday, fill: count) | tile() | labs(title: "Activity by Hour and Weekday", x: "Hour", y: "Weekday") | theme_minimal()' --format svg > examples/tile.svg
+
 # Nice Ticks (irregular data with clean axis labels)
 echo "Generating nice_ticks.svg..."
 cat examples/measurements.csv | cargo run -- 'aes(x: elapsed, y: temperature) | point(color: "steelblue", size: 4) | line(color: "steelblue", alpha: 0.5) | labs(title: "Sensor Readings", subtitle: "Nice ticks from irregular sample times", x: "Elapsed Time (hrs)", y: "Temperature (C)") | theme_minimal()' --format svg > examples/nice_ticks.svg
 
+# Axis Padding
+echo "Generating axis_expand.svg..."
+cat examples/measurements.csv | cargo run -- 'aes(x: elapsed, y: temperature) | point(color: "steelblue", size: 4) | line(color: "steelblue", alpha: 0.5) | scale_x(expand: 0.02) | labs(title: "Sensor Readings", subtitle: "X axis padded 2% past the data", x: "Elapsed Time (hrs)", y: "Temperature (C)") | theme_minimal()' --format svg > examples/axis_expand.svg
+
 # --- Theme Examples ---
 
 # Custom Theme with Element Functions
@@ -162,6 +306,12 @@ echo "Generating theme_light.svg..."
 cat examples/timeseries.csv | cargo run -- 'aes(x: time, y: value, color: series) | line(width: 2) | point(size: 4) | labs(title: "Light Theme Example") | theme_minimal() | theme_light()' --format svg > examples/theme_light.svg
 
 # Void Theme Example
+echo "Generating theme_shorthand.svg..."
+cat examples/demographics.csv | cargo run -- 'aes(x: height, y: weight, color: gender) | point(size: 5) | labs(title: "Theme Shorthands") | theme_minimal() | theme(background: "#FAF7F0", grid: "none", font_size: 14, font: "serif")' --format svg > examples/theme_shorthand.svg
+
+echo "Generating grid_lines.svg..."
+cat examples/timeseries.csv | cargo run -- 'aes(x: time, y: value, color: series) | line(width: 2) | labs(title: "Grid Lines") | theme_minimal() | theme(grid_minor: true, grid_color: "#999999", grid_linetype: "dashed")' --format svg > examples/grid_lines.svg
+
 echo "Generating theme_void.svg..."
 cat examples/demographics.csv | cargo run -- 'aes(x: height, y: weight, color: gender) | point(size: 5) | labs(title: "Void Theme Example") | theme_minimal() | theme_void()' --format svg > examples/theme_void.svg
 
@@ -187,9 +337,13 @@ cat examples/financials.csv | cargo run -- 'aes(x: quarter, y: amount, color: ty
 echo "Generating axis_no_ticks.svg..."
 cat examples/timeseries.csv | cargo run -- 'aes(x: time, y: value, color: series) | line() | labs(title: "Clean Look - No Ticks") | theme_minimal() | theme(axis_ticks: element_blank())' --format svg > examples/axis_no_ticks.svg
 
+# Formatted and truncated tick labels
+echo "Generating axis_format.svg..."
+cat examples/metro_areas.csv | cargo run -- 'aes(x: metro, y: population) | bar(color: "#4682B4") | labs(title: "Largest US Metro Areas", x: "Metro Area", y: "Population") | theme_minimal() | scale_x(rotate: 90, max_label_len: 16) | scale_y(format: "si")' --format svg > examples/axis_format.svg
+
 # Combined: Bold, Rotated, Custom Color
 echo "Generating axis_styled.svg..."
-cat examples/regional_sales.csv | cargo run -- 'aes(x: region, y: sales, color: product) | bar(position: "dodge") | labs(title: "Fully Styled Axes") | theme_minimal() | theme(axis_text: element_text(face: "bold", angle: 90, color: "#2E86AB", size: 11), axis_line: element_line(color: "#333333", width: 2))' --format svg > examples/axis_styled.svg
+cat examples/regional_sales.csv | cargo run -- 'aes(x: region, y: sales, color: product) | bar(position: "dodge", stat: "sum") | labs(title: "Fully Styled Axes") | theme_minimal() | theme(axis_text: element_text(face: "bold", angle: 90, color: "#2E86AB", size: 11), axis_line: element_line(color: "#333333", width: 2))' --format svg > examples/axis_styled.svg
 
 # --- Variable Injection Examples ---
 
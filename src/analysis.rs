@@ -0,0 +1,157 @@
+// X-axis analysis: whether a layer's x values sit on a categorical, continuous, or datetime axis
+//
+// The decision takes the geometry first and the data second. Bars (without
+// `x_continuous`), boxplots, violins, and pies always place x as categories, a
+// datetime scale always parses it, and every other layer is continuous exactly
+// when each of its x values is a number by `csv_reader::numeric_value`, the same
+// rule the column store uses to type a column.
+
+use crate::csv_reader::numeric_value;
+use crate::parser::ast::{AxisScale, Layer, ScaleType};
+use serde::Serialize;
+
+/// How a layer's x values are placed along the axis
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum XAxisKind {
+    /// One evenly spaced slot per distinct value
+    Categorical,
+    /// Numbers placed by value
+    Continuous,
+    /// Dates and times parsed by `scale_x_datetime()`
+    DateTime,
+}
+
+/// The x axis a layer needs for the given x values (after any stat has run).
+///
+/// A datetime x scale wins, then the geometry (`Layer::requires_categorical_x`),
+/// then the data: continuous when every value is numeric, categorical otherwise.
+/// Missing cells are dropped before values reach here unless `--strict` keeps
+/// them, in which case an empty cell is text like any other. A layer with no x
+/// values is continuous.
+///
+/// ```
+/// use gramgraph::analysis::{x_axis_kind, XAxisKind};
+/// use gramgraph::parser::parse_plot_spec;
+///
+/// let (_, spec) = parse_plot_spec("aes(x: a, y: b) | line()").unwrap();
+/// let line = &spec.layers[0];
+/// assert_eq!(x_axis_kind(line, None, ["1", "2.5", "1e3"]), XAxisKind::Continuous);
+/// assert_eq!(x_axis_kind(line, None, ["1", "two"]), XAxisKind::Categorical);
+///
+/// let (_, spec) = parse_plot_spec("aes(x: a, y: b) | bar()").unwrap();
+/// let bar = &spec.layers[0];
+/// assert_eq!(x_axis_kind(bar, None, ["1", "2"]), XAxisKind::Categorical);
+/// ```
+pub fn x_axis_kind<'a, I>(layer: &Layer, x_scale: Option<&AxisScale>, values: I) -> XAxisKind
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    if x_scale.is_some_and(|scale| matches!(scale.scale_type, ScaleType::DateTime)) {
+        XAxisKind::DateTime
+    } else if layer.requires_categorical_x() || !all_numeric(values) {
+        XAxisKind::Categorical
+    } else {
+        XAxisKind::Continuous
+    }
+}
+
+/// True when every value is a number by `csv_reader::numeric_value`
+pub fn all_numeric<'a, I>(values: I) -> bool
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    values
+        .into_iter()
+        .all(|value| numeric_value(value).is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_plot_spec;
+
+    fn layer(dsl: &str) -> Layer {
+        parse_plot_spec(dsl).unwrap().1.layers.remove(0)
+    }
+
+    fn line() -> Layer {
+        layer("aes(x: a, y: b) | line()")
+    }
+
+    #[test]
+    fn numbers_make_a_continuous_axis() {
+        let line = line();
+        assert_eq!(
+            x_axis_kind(&line, None, ["1", "-2.5", " 3 ", "+4"]),
+            XAxisKind::Continuous
+        );
+        // Scientific notation is a number
+        assert_eq!(
+            x_axis_kind(&line, None, ["1e3", "2.5E-2", "-1e+2"]),
+            XAxisKind::Continuous
+        );
+        // So is a leading zero, though "007" then sits at 7
+        assert_eq!(
+            x_axis_kind(&line, None, ["007", "010"]),
+            XAxisKind::Continuous
+        );
+        assert_eq!(x_axis_kind(&line, None, []), XAxisKind::Continuous);
+    }
+
+    #[test]
+    fn any_text_value_makes_a_categorical_axis() {
+        let line = line();
+        assert_eq!(
+            x_axis_kind(&line, None, ["1", "2", "three"]),
+            XAxisKind::Categorical
+        );
+        // Thousands separators aren't parsed, so "1,234" is a label
+        assert_eq!(
+            x_axis_kind(&line, None, ["999", "1,234"]),
+            XAxisKind::Categorical
+        );
+        // An empty cell kept by --strict is text too
+        assert_eq!(x_axis_kind(&line, None, ["1", ""]), XAxisKind::Categorical);
+        // Non-finite numbers can't be placed by value
+        assert_eq!(
+            x_axis_kind(&line, None, ["1", "inf"]),
+            XAxisKind::Categorical
+        );
+    }
+
+    #[test]
+    fn geometry_forces_categories_before_the_data() {
+        for dsl in [
+            "aes(x: a, y: b) | bar()",
+            "aes(x: a, y: b) | boxplot()",
+            "aes(x: a, y: b) | violin()",
+            "aes(x: a, y: b) | pie()",
+        ] {
+            let layer = layer(dsl);
+            assert!(layer.requires_categorical_x(), "{dsl}");
+            assert_eq!(
+                x_axis_kind(&layer, None, ["1", "2"]),
+                XAxisKind::Categorical,
+                "{dsl}"
+            );
+        }
+
+        // `x_continuous` hands a bar's x back to the data
+        let bar = layer("aes(x: a, y: b) | bar(x_continuous: true)");
+        assert!(!bar.requires_categorical_x());
+        assert_eq!(x_axis_kind(&bar, None, ["1", "2"]), XAxisKind::Continuous);
+        assert!(!line().requires_categorical_x());
+    }
+
+    #[test]
+    fn datetime_scale_overrides_geometry_and_data() {
+        let (_, spec) = parse_plot_spec("aes(x: a, y: b) | bar() | scale_x_datetime()").unwrap();
+        let scale = spec.x_scale.as_ref();
+        assert_eq!(
+            x_axis_kind(&spec.layers[0], scale, ["2024-01-01", "2024-02-01"]),
+            XAxisKind::DateTime
+        );
+        assert!(spec.requires_categorical_x());
+    }
+}
@@ -0,0 +1,761 @@
+// Character-grid backend for previewing plots in a terminal
+//
+// A scene is drawn onto a `width` x `height` grid of characters: lines as Braille
+// dots (2x4 per character), points as `•`, rects as block columns (with eighth
+// blocks at the top edge), polygons as `░`, and text as itself. Every panel gets
+// its share of the grid, with an axis and min/max tick labels on each side. With
+// color on, each cell is wrapped in a 24-bit ANSI escape of its command's color.
+
+use crate::backend::{RenderBackend, SceneRecorder};
+use crate::datetime::format_datetime_tick;
+use crate::graph::format_axis_tick;
+use crate::ir::{DrawCommand, PanelScene, Scale, SceneGraph};
+use crate::theme_resolve::parse_color;
+use anyhow::Result;
+
+/// Default grid size, a classic terminal
+pub const DEFAULT_WIDTH: usize = 80;
+pub const DEFAULT_HEIGHT: usize = 24;
+
+/// plotters' `BLUE`, the image outputs' color for shapes without one
+const DEFAULT_COLOR: (u8, u8, u8) = (0, 0, 255);
+
+/// Lower eighth blocks, indexed by eighths covered (1-7)
+const LOWER_BLOCKS: [char; 8] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇'];
+
+/// Braille dot bits by [row][column] within a character's 2x4 dot cell
+const BRAILLE_DOTS: [[u8; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+/// Renders scenes as text for terminals (`--format ascii`)
+#[derive(Debug)]
+pub struct AsciiBackend {
+    recorder: SceneRecorder,
+    width: usize,
+    height: usize,
+    color: bool,
+}
+
+impl AsciiBackend {
+    /// A `width` x `height` character grid, in plain text
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            recorder: SceneRecorder::default(),
+            width,
+            height,
+            color: false,
+        }
+    }
+
+    /// Color cells with ANSI escapes
+    pub fn color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+}
+
+impl Default for AsciiBackend {
+    fn default() -> Self {
+        Self::new(DEFAULT_WIDTH, DEFAULT_HEIGHT)
+    }
+}
+
+impl RenderBackend for AsciiBackend {
+    fn begin_scene(&mut self, scene: &SceneGraph) -> Result<()> {
+        self.recorder.begin_scene(scene);
+        Ok(())
+    }
+
+    fn begin_panel(&mut self, panel: &PanelScene) -> Result<()> {
+        self.recorder.begin_panel(panel)
+    }
+
+    fn draw(&mut self, command: &DrawCommand) -> Result<()> {
+        self.recorder.draw(command)
+    }
+
+    fn finish(&mut self) -> Result<Vec<u8>> {
+        let scene = self.recorder.take_scene()?;
+        Ok(render_text(&scene, self.width, self.height, self.color).into_bytes())
+    }
+}
+
+/// Draw `scene` onto a `width` x `height` character grid, one line per row.
+/// Trailing blanks are trimmed; with `color`, escapes wrap colored cells.
+pub fn render_text(scene: &SceneGraph, width: usize, height: usize, color: bool) -> String {
+    let mut grid = Grid::new(width, height);
+    let mut top = 0;
+    if let Some(title) = &scene.labels.title {
+        grid.centered(0, width, 0, title, None);
+        top = 1;
+    }
+
+    let legend = legend_entries(scene);
+    let bottom = if legend.is_empty() {
+        height
+    } else {
+        height.saturating_sub(1)
+    };
+
+    let nrow = scene.nrow.max(1);
+    let ncol = scene.ncol.max(1);
+    let rows = bottom.saturating_sub(top);
+    for panel in &scene.panels {
+        let region = Region {
+            left: panel.col * width / ncol,
+            right: (panel.col + 1) * width / ncol,
+            top: top + panel.row * rows / nrow,
+            bottom: top + (panel.row + 1) * rows / nrow,
+        };
+        draw_panel(&mut grid, panel, region);
+    }
+
+    if !legend.is_empty() {
+        let mut col = 0;
+        for (glyph, label, rgb) in legend {
+            grid.put(col, bottom, Glyph::Char(glyph), rgb);
+            grid.text(col as isize + 2, bottom, &label, None);
+            col += label.chars().count() + 5;
+        }
+    }
+
+    grid.render(color)
+}
+
+type Rgb = (u8, u8, u8);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Glyph {
+    Blank,
+    Braille(u8),
+    Char(char),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Cell {
+    glyph: Glyph,
+    color: Option<Rgb>,
+}
+
+struct Grid {
+    width: usize,
+    height: usize,
+    cells: Vec<Cell>,
+}
+
+impl Grid {
+    fn new(width: usize, height: usize) -> Self {
+        let blank = Cell {
+            glyph: Glyph::Blank,
+            color: None,
+        };
+        Self {
+            width,
+            height,
+            cells: vec![blank; width * height],
+        }
+    }
+
+    fn put(&mut self, col: usize, row: usize, glyph: Glyph, color: Option<Rgb>) {
+        if col < self.width && row < self.height {
+            self.cells[row * self.width + col] = Cell { glyph, color };
+        }
+    }
+
+    /// Set one Braille dot; dots join any already in the cell, but replace other glyphs
+    fn dot(&mut self, col: usize, row: usize, bit: u8, color: Option<Rgb>) {
+        if col >= self.width || row >= self.height {
+            return;
+        }
+        let cell = &mut self.cells[row * self.width + col];
+        let dots = match cell.glyph {
+            Glyph::Braille(dots) => dots,
+            _ => 0,
+        };
+        *cell = Cell {
+            glyph: Glyph::Braille(dots | bit),
+            color,
+        };
+    }
+
+    /// Write `text` from `col` on, clipped at the grid edges
+    fn text(&mut self, col: isize, row: usize, text: &str, color: Option<Rgb>) {
+        for (i, c) in text.chars().enumerate() {
+            let col = col + i as isize;
+            if col >= 0 {
+                self.put(col as usize, row, Glyph::Char(c), color);
+            }
+        }
+    }
+
+    /// Write `text` centered between columns `left` and `right`, cut to fit
+    fn centered(&mut self, left: usize, right: usize, row: usize, text: &str, color: Option<Rgb>) {
+        let span = right.saturating_sub(left);
+        let text: String = text.chars().take(span).collect();
+        let start = left + (span - text.chars().count()) / 2;
+        self.text(start as isize, row, &text, color);
+    }
+
+    fn render(&self, color: bool) -> String {
+        let mut out = String::new();
+        for row in self.cells.chunks(self.width.max(1)).take(self.height) {
+            let used = row
+                .iter()
+                .rposition(|cell| cell.glyph != Glyph::Blank)
+                .map_or(0, |last| last + 1);
+            let mut current: Option<Rgb> = None;
+            for cell in &row[..used] {
+                let cell_color = if cell.glyph == Glyph::Blank {
+                    current
+                } else {
+                    cell.color
+                };
+                if color && cell_color != current {
+                    match cell_color {
+                        Some((r, g, b)) => out.push_str(&format!("\x1b[38;2;{};{};{}m", r, g, b)),
+                        None => out.push_str("\x1b[0m"),
+                    }
+                    current = cell_color;
+                }
+                out.push(match cell.glyph {
+                    Glyph::Blank => ' ',
+                    Glyph::Braille(dots) => char::from_u32(0x2800 + dots as u32).unwrap_or(' '),
+                    Glyph::Char(c) => c,
+                });
+            }
+            if current.is_some() {
+                out.push_str("\x1b[0m");
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// A panel's share of the grid: columns `left..right`, rows `top..bottom`
+#[derive(Debug, Clone, Copy)]
+struct Region {
+    left: usize,
+    right: usize,
+    top: usize,
+    bottom: usize,
+}
+
+/// The cells data is drawn in, and the coordinate ranges they span
+struct PlotArea<'a> {
+    left: usize,
+    top: usize,
+    cols: usize,
+    rows: usize,
+    x: &'a Scale,
+    y: &'a Scale,
+}
+
+impl PlotArea<'_> {
+    /// Column position (in cells, fractional) of an x coordinate
+    fn col(&self, x: f64) -> f64 {
+        fraction(x, self.x.range) * self.cols as f64
+    }
+
+    /// Row position (in cells from the top, fractional) of a y coordinate
+    fn row(&self, y: f64) -> f64 {
+        (1.0 - fraction(y, self.y.range)) * self.rows as f64
+    }
+
+    /// The cell holding a fractional position, if it is inside the area (the far
+    /// edges included, so the range ends land in the last column and row)
+    fn cell(&self, col: f64, row: f64) -> Option<(usize, usize)> {
+        let (cols, rows) = (self.cols as f64, self.rows as f64);
+        let inside = (0.0..=cols).contains(&col) && (0.0..=rows).contains(&row);
+        inside.then(|| {
+            (
+                self.left + (col as usize).min(self.cols - 1),
+                self.top + (row as usize).min(self.rows - 1),
+            )
+        })
+    }
+}
+
+fn fraction(value: f64, (start, end): (f64, f64)) -> f64 {
+    if end != start {
+        (value - start) / (end - start)
+    } else {
+        0.5
+    }
+}
+
+fn draw_panel(grid: &mut Grid, panel: &PanelScene, region: Region) {
+    let mut top = region.top;
+    if let Some(title) = &panel.title {
+        grid.centered(region.left, region.right, top, title, None);
+        top += 1;
+    }
+
+    let area = if panel.polar {
+        PlotArea {
+            left: region.left,
+            top,
+            cols: region.right.saturating_sub(region.left),
+            rows: region.bottom.saturating_sub(top),
+            x: &panel.x_scale,
+            y: &panel.y_scale,
+        }
+    } else {
+        let y_labels = axis_extremes(&panel.y_scale);
+        let label_width = y_labels
+            .iter()
+            .map(|(_, label)| label.chars().count())
+            .max()
+            .unwrap_or(0)
+            .min((region.right - region.left) / 3);
+        // Axis line and tick labels below the plot, then the axis title
+        let below = 2 + panel.x_label.is_some() as usize;
+        let area = PlotArea {
+            left: region.left + label_width + 1,
+            top,
+            cols: region.right.saturating_sub(region.left + label_width + 1),
+            rows: region.bottom.saturating_sub(top + below),
+            x: &panel.x_scale,
+            y: &panel.y_scale,
+        };
+        if area.cols == 0 || area.rows == 0 {
+            return;
+        }
+        draw_axes(grid, &area, &y_labels, label_width);
+        if let Some(label) = &panel.x_label {
+            grid.centered(
+                area.left,
+                region.right,
+                area.top + area.rows + 2,
+                label,
+                None,
+            );
+        }
+        area
+    };
+    if area.cols == 0 || area.rows == 0 {
+        return;
+    }
+
+    for command in &panel.commands {
+        draw_command(grid, &area, command);
+    }
+}
+
+/// The axis lines, with the y labels right-aligned against the left axis and the
+/// x labels under the bottom one. A label that would overlap the first is dropped.
+fn draw_axes(grid: &mut Grid, area: &PlotArea, y_labels: &[(f64, String)], label_width: usize) {
+    let axis_col = area.left - 1;
+    let axis_row = area.top + area.rows;
+    for row in area.top..axis_row {
+        grid.put(axis_col, row, Glyph::Char('│'), None);
+    }
+    grid.put(axis_col, axis_row, Glyph::Char('└'), None);
+    for col in area.left..area.left + area.cols {
+        grid.put(col, axis_row, Glyph::Char('─'), None);
+    }
+
+    let mut used_row = None;
+    for (value, label) in y_labels {
+        let row = (area.row(*value) as usize).min(area.rows - 1) + area.top;
+        if used_row == Some(row) {
+            continue;
+        }
+        used_row = Some(row);
+        let label: String = label.chars().take(label_width).collect();
+        let start = axis_col - label.chars().count();
+        grid.text(start as isize, row, &label, None);
+    }
+
+    let right = area.left + area.cols;
+    let mut used_until = None;
+    for (value, label) in axis_extremes(area.x) {
+        let len = label.chars().count();
+        let center = area.left as isize + area.col(value) as isize;
+        let start = (center - len as isize / 2)
+            .min(right as isize - len as isize)
+            .max(area.left as isize - 1);
+        if used_until.is_some_and(|end| start <= end) {
+            continue;
+        }
+        used_until = Some(start + len as isize);
+        grid.text(start, axis_row + 1, &label, None);
+    }
+}
+
+/// The lowest and highest labeled positions on an axis: the first and last
+/// category, or the outer tick marks (the range ends when there are none)
+fn axis_extremes(scale: &Scale) -> Vec<(f64, String)> {
+    let values = if scale.is_categorical {
+        match scale.categories.len() {
+            0 => vec![],
+            n => vec![0.0, (n - 1) as f64],
+        }
+    } else {
+        match (scale.tick_positions.first(), scale.tick_positions.last()) {
+            (Some(first), Some(last)) => vec![*first, *last],
+            _ => vec![scale.range.0, scale.range.1],
+        }
+    };
+    let mut labels: Vec<(f64, String)> = values
+        .into_iter()
+        .map(|value| {
+            let label = if scale.is_categorical {
+                scale
+                    .labels
+                    .category(&scale.categories[value as usize])
+                    .into_owned()
+            } else if let Some(datetime) = &scale.datetime {
+                format_datetime_tick(value, &datetime.label_format)
+            } else {
+                format_axis_tick(value, scale)
+            };
+            (value, label)
+        })
+        .collect();
+    labels.dedup_by(|a, b| a.1 == b.1);
+    labels
+}
+
+/// The command's color, or plotters' blue like the image outputs (text without a
+/// color keeps the terminal's own)
+fn rgb(color: &Option<String>, text: bool) -> Option<Rgb> {
+    match color.as_deref().and_then(parse_color) {
+        Some(color) => Some((color.0, color.1, color.2)),
+        None if text => None,
+        None => Some(DEFAULT_COLOR),
+    }
+}
+
+fn draw_command(grid: &mut Grid, area: &PlotArea, command: &DrawCommand) {
+    match command {
+        DrawCommand::DrawLine { points, style, .. } => {
+            let color = rgb(&style.color, false);
+            let dots: Vec<(f64, f64)> = points
+                .iter()
+                .map(|&(x, y)| (area.col(x) * 2.0, area.row(y) * 4.0))
+                .collect();
+            if let [only] = dots.as_slice() {
+                draw_segment(grid, area, *only, *only, color);
+            }
+            for pair in dots.windows(2) {
+                draw_segment(grid, area, pair[0], pair[1], color);
+            }
+        }
+        DrawCommand::DrawPoint { points, style, .. } => {
+            let color = rgb(&style.color, false);
+            for &(x, y) in points {
+                if let Some((col, row)) = area.cell(area.col(x), area.row(y)) {
+                    grid.put(col, row, Glyph::Char('•'), color);
+                }
+            }
+        }
+        DrawCommand::DrawRect { tl, br, style, .. } => {
+            draw_rect(grid, area, *tl, *br, rgb(&style.color, false));
+        }
+        DrawCommand::DrawPolygon { points, style, .. } => {
+            let cells: Vec<(f64, f64)> = points
+                .iter()
+                .map(|&(x, y)| (area.col(x), area.row(y)))
+                .collect();
+            fill_polygon(grid, area, &cells, rgb(&style.color, false));
+        }
+        DrawCommand::DrawText {
+            position,
+            text,
+            style,
+            ..
+        } => {
+            let (col, row) = (area.col(position.0), area.row(position.1));
+            if let Some((col, row)) = area.cell(col, row) {
+                let start = col as isize - text.chars().count() as isize / 2;
+                grid.text(start, row, text, rgb(&style.color, true));
+            }
+        }
+    }
+}
+
+/// Set the Braille dots along a segment between two dot positions, clipped to the area
+fn draw_segment(
+    grid: &mut Grid,
+    area: &PlotArea,
+    a: (f64, f64),
+    b: (f64, f64),
+    color: Option<Rgb>,
+) {
+    let bounds = (area.cols as f64 * 2.0, area.rows as f64 * 4.0);
+    let Some((a, b)) = clip_segment(a, b, bounds) else {
+        return;
+    };
+    let steps = (b.0 - a.0).abs().max((b.1 - a.1).abs()).ceil().max(1.0) as usize;
+    for i in 0..=steps {
+        let t = i as f64 / steps as f64;
+        let x = (a.0 + (b.0 - a.0) * t).clamp(0.0, bounds.0 - 1.0) as usize;
+        let y = (a.1 + (b.1 - a.1) * t).clamp(0.0, bounds.1 - 1.0) as usize;
+        grid.dot(
+            area.left + x / 2,
+            area.top + y / 4,
+            BRAILLE_DOTS[y % 4][x % 2],
+            color,
+        );
+    }
+}
+
+/// Liang-Barsky clipping of a segment to `[0, w] x [0, h]`; None when it misses
+fn clip_segment(
+    a: (f64, f64),
+    b: (f64, f64),
+    (w, h): (f64, f64),
+) -> Option<((f64, f64), (f64, f64))> {
+    if ![a.0, a.1, b.0, b.1].iter().all(|v| v.is_finite()) {
+        return None;
+    }
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let (mut t0, mut t1) = (0.0_f64, 1.0_f64);
+    for (p, q) in [(-dx, a.0), (dx, w - a.0), (-dy, a.1), (dy, h - a.1)] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else {
+            let t = q / p;
+            if p < 0.0 {
+                t0 = t0.max(t);
+            } else {
+                t1 = t1.min(t);
+            }
+        }
+    }
+    (t0 <= t1).then_some((
+        (a.0 + dx * t0, a.1 + dy * t0),
+        (a.0 + dx * t1, a.1 + dy * t1),
+    ))
+}
+
+/// Fill the columns whose centers a rect covers (the nearest one for a thin rect),
+/// in eighths of a row at the top edge and in halves at the bottom
+fn draw_rect(grid: &mut Grid, area: &PlotArea, tl: (f64, f64), br: (f64, f64), color: Option<Rgb>) {
+    let (left, right) = {
+        let (a, b) = (area.col(tl.0), area.col(br.0));
+        (a.min(b), a.max(b))
+    };
+    let (upper, lower) = {
+        let (a, b) = (area.row(tl.1) * 8.0, area.row(br.1) * 8.0);
+        let max = area.rows as f64 * 8.0;
+        (a.min(b).clamp(0.0, max), a.max(b).clamp(0.0, max))
+    };
+    if !(left.is_finite() && right.is_finite() && upper.is_finite() && lower.is_finite()) {
+        return;
+    }
+
+    let first = (left - 0.5).ceil().max(0.0) as usize;
+    let last = ((right - 0.5).floor() as isize).min(area.cols as isize - 1);
+    let cols = if (first as isize) <= last {
+        first..last as usize + 1
+    } else {
+        let middle = (left + right) / 2.0;
+        if !(0.0..area.cols as f64).contains(&middle) {
+            return;
+        }
+        middle as usize..middle as usize + 1
+    };
+
+    for row in 0..area.rows {
+        let (cell_top, cell_bottom) = (row as f64 * 8.0, row as f64 * 8.0 + 8.0);
+        let covered = (lower.min(cell_bottom) - upper.max(cell_top)).round() as usize;
+        let glyph = match covered {
+            0 => continue,
+            8.. => '█',
+            k if lower >= cell_bottom - 0.5 => LOWER_BLOCKS[k],
+            // A sliver at the top of a cell is left to whatever is below it
+            k if upper <= cell_top + 0.5 => match k {
+                4.. => '▀',
+                _ => continue,
+            },
+            _ => '■',
+        };
+        for col in cols.clone() {
+            grid.put(area.left + col, area.top + row, Glyph::Char(glyph), color);
+        }
+    }
+}
+
+/// Shade the cells whose centers fall inside a polygon (even-odd rule); `points`
+/// are fractional cell positions
+fn fill_polygon(grid: &mut Grid, area: &PlotArea, points: &[(f64, f64)], color: Option<Rgb>) {
+    if points.len() < 3 || !points.iter().all(|(c, r)| c.is_finite() && r.is_finite()) {
+        return;
+    }
+    let (mut min_row, mut max_row) = (f64::INFINITY, f64::NEG_INFINITY);
+    for &(_, row) in points {
+        min_row = min_row.min(row);
+        max_row = max_row.max(row);
+    }
+    let first = (min_row - 0.5).ceil().max(0.0) as usize;
+    let last = ((max_row - 0.5).floor()).min(area.rows as f64 - 1.0);
+    if last < first as f64 {
+        return;
+    }
+
+    for row in first..=last as usize {
+        let y = row as f64 + 0.5;
+        // Where the polygon's edges cross this row's center line, left to right
+        let mut crossings: Vec<f64> = points
+            .iter()
+            .zip(points.iter().cycle().skip(1))
+            .filter(|(a, b)| (a.1 <= y) != (b.1 <= y))
+            .map(|(a, b)| a.0 + (y - a.1) / (b.1 - a.1) * (b.0 - a.0))
+            .collect();
+        crossings.sort_by(f64::total_cmp);
+        for span in crossings.chunks_exact(2) {
+            let start = (span[0] - 0.5).ceil().max(0.0) as usize;
+            let end = ((span[1] - 0.5).floor()).min(area.cols as f64 - 1.0);
+            if end < start as f64 {
+                continue;
+            }
+            for col in start..=end as usize {
+                grid.put(area.left + col, area.top + row, Glyph::Char('░'), color);
+            }
+        }
+    }
+}
+
+/// One legend key per label, in first-drawn order, with its command's glyph and color
+fn legend_entries(scene: &SceneGraph) -> Vec<(char, String, Option<Rgb>)> {
+    let mut entries: Vec<(char, String, Option<Rgb>)> = Vec::new();
+    for command in scene.panels.iter().flat_map(|panel| &panel.commands) {
+        let (glyph, legend, color) = match command {
+            DrawCommand::DrawLine { legend, style, .. } => ('─', legend, rgb(&style.color, false)),
+            DrawCommand::DrawPoint { legend, style, .. } => ('•', legend, rgb(&style.color, false)),
+            DrawCommand::DrawRect { legend, style, .. } => ('█', legend, rgb(&style.color, false)),
+            DrawCommand::DrawPolygon { legend, style, .. } => {
+                ('░', legend, rgb(&style.color, false))
+            }
+            DrawCommand::DrawText { legend, style, .. } => ('•', legend, rgb(&style.color, true)),
+        };
+        if let Some(label) = legend.as_ref().filter(|label| !label.is_empty()) {
+            if !entries.iter().any(|(_, existing, _)| existing == label) {
+                entries.push((glyph, label.clone(), color));
+            }
+        }
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::render_scene;
+    use crate::csv_reader::{self, CsvOptions};
+    use crate::{render, OutputFormat, RenderOptions};
+
+    fn ascii(dsl: &str, csv: &str, width: usize, height: usize, color: bool) -> String {
+        let data = csv_reader::read_csv(csv.as_bytes(), CsvOptions::default()).unwrap();
+        let options = RenderOptions {
+            format: OutputFormat::Ascii,
+            ascii_width: width,
+            ascii_height: height,
+            ascii_color: color,
+            ..RenderOptions::default()
+        };
+        String::from_utf8(render(dsl, data, options).unwrap()).unwrap()
+    }
+
+    const SALES: &str = "x,y\n1,10\n2,14\n3,9\n4,20\n5,17\n";
+
+    #[test]
+    fn line_chart_snapshot() {
+        let text = ascii(
+            r#"aes(x: x, y: y) | line() | point() | labs(title: "Sales", x: "Day")"#,
+            SALES,
+            30,
+            10,
+            false,
+        );
+        assert_eq!(
+            text,
+            "            Sales
+20│                  ⡠⠊•⠉⠒⠒⠤⠤•
+  │     ⢀•⡀        ⡠⠊
+  │⣀⠤⠔⠒⠉⠁ ⠈⠉⠒⠤⣀⡀ ⡠⠊
+  │•           ⠈•
+  │
+ 0│
+  └───────────────────────────
+   1                         5
+               Day
+"
+        );
+    }
+
+    #[test]
+    fn bar_chart_snapshot() {
+        let text = ascii(
+            "aes(x: c, y: v, color: g) | bar()",
+            "c,v,g\na,3,u\nb,7,u\nc,5,v\nd,1,v\n",
+            30,
+            10,
+            false,
+        );
+        assert_eq!(
+            text,
+            "7│        █████
+ │        █████
+ │        █████  █████
+ │        █████  █████
+ │ █████  █████  █████
+ │ █████  █████  █████
+0│ █████  █████  █████  █████
+ └────────────────────────────
+     a                    d
+█ u   █ v
+"
+        );
+    }
+
+    #[test]
+    fn color_wraps_cells_in_ansi_escapes() {
+        let dsl = r#"aes(x: x, y: y) | point(color: "red")"#;
+        let plain = ascii(dsl, SALES, 30, 10, false);
+        assert!(!plain.contains('\x1b'));
+
+        let colored = ascii(dsl, SALES, 30, 10, true);
+        assert!(colored.contains("\x1b[38;2;255;0;0m•\x1b[0m"));
+        // Stripping the escapes gives back the plain text
+        let stripped = colored
+            .replace("\x1b[38;2;255;0;0m", "")
+            .replace("\x1b[0m", "");
+        assert_eq!(stripped, plain);
+    }
+
+    #[test]
+    fn backend_matches_canvas_output() {
+        let data = csv_reader::read_csv(SALES.as_bytes(), CsvOptions::default()).unwrap();
+        let (_, spec) = crate::parser::parse_plot_spec("aes(x: x, y: y) | line()").unwrap();
+        let data = crate::data::PlotData::from_csv(data);
+        let resolved = crate::resolve::resolve_plot_aesthetics(&spec, &data).unwrap();
+        let options = RenderOptions::default();
+        let render_data =
+            crate::transform::apply_transformations(&resolved, &data, &options).unwrap();
+        let scales = crate::scale::build_scales(&render_data, &resolved).unwrap();
+        let scene =
+            crate::compiler::compile_geometry(render_data, scales, &resolved, &options).unwrap();
+
+        let text = render_scene(&scene, &mut AsciiBackend::new(40, 12)).unwrap();
+        assert_eq!(
+            String::from_utf8(text).unwrap(),
+            render_text(&scene, 40, 12, false)
+        );
+        assert_eq!(render_text(&scene, 40, 12, false).lines().count(), 12);
+    }
+
+    #[test]
+    fn segments_clip_to_the_dot_grid() {
+        let bounds = (10.0, 8.0);
+        assert_eq!(
+            clip_segment((-5.0, 4.0), (15.0, 4.0), bounds),
+            Some(((0.0, 4.0), (10.0, 4.0)))
+        );
+        assert_eq!(clip_segment((-5.0, -1.0), (-1.0, -5.0), bounds), None);
+        assert_eq!(clip_segment((f64::NAN, 0.0), (1.0, 1.0), bounds), None);
+    }
+}
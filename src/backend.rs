@@ -0,0 +1,362 @@
+// Pluggable rendering backends for compiled scenes
+//
+// `render_scene` walks a SceneGraph and feeds it to a `RenderBackend` one panel and
+// one command at a time, so downstream crates can render the same scene to other
+// targets (terminal, GPU, custom vector formats) without going through plotters.
+
+use crate::graph::Canvas;
+use crate::ir::{DrawCommand, PanelScene, SceneGraph};
+use crate::{OutputFormat, RenderOptions};
+use anyhow::{anyhow, Result};
+
+/// A target that executes draw commands.
+///
+/// `render_scene` calls `begin_scene` once, then `begin_panel` for each panel followed
+/// by `draw` for each of that panel's commands (in paint order), and finally `finish`.
+/// Commands are in the panel's data coordinates; map them with the panel's `x_scale`
+/// and `y_scale`. Legend entries are the `legend` fields of the commands.
+pub trait RenderBackend {
+    /// Canvas size, plot labels, and theme; called before any panel
+    fn begin_scene(&mut self, _scene: &SceneGraph) -> Result<()> {
+        Ok(())
+    }
+
+    /// Start a panel; its axes, title, and colorbar are on `panel`
+    fn begin_panel(&mut self, panel: &PanelScene) -> Result<()>;
+
+    /// Draw one command in the current panel
+    fn draw(&mut self, command: &DrawCommand) -> Result<()>;
+
+    /// Produce the rendered output
+    fn finish(&mut self) -> Result<Vec<u8>>;
+}
+
+/// Execute a compiled scene against a backend and return its output
+pub fn render_scene(scene: &SceneGraph, backend: &mut dyn RenderBackend) -> Result<Vec<u8>> {
+    backend.begin_scene(scene)?;
+    for panel in &scene.panels {
+        backend.begin_panel(panel)?;
+        for command in &panel.commands {
+            backend.draw(command)?;
+        }
+    }
+    backend.finish()
+}
+
+/// Renders with plotters to PNG (supersampled, like the CLI)
+#[derive(Debug, Default)]
+pub struct PlottersPngBackend {
+    recorder: SceneRecorder,
+}
+
+impl PlottersPngBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl RenderBackend for PlottersPngBackend {
+    fn begin_scene(&mut self, scene: &SceneGraph) -> Result<()> {
+        self.recorder.begin_scene(scene);
+        Ok(())
+    }
+
+    fn begin_panel(&mut self, panel: &PanelScene) -> Result<()> {
+        self.recorder.begin_panel(panel)
+    }
+
+    fn draw(&mut self, command: &DrawCommand) -> Result<()> {
+        self.recorder.draw(command)
+    }
+
+    fn finish(&mut self) -> Result<Vec<u8>> {
+        self.recorder.finish(OutputFormat::Png)
+    }
+}
+
+/// Renders with plotters to an SVG document
+#[derive(Debug, Default)]
+pub struct PlottersSvgBackend {
+    recorder: SceneRecorder,
+}
+
+impl PlottersSvgBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl RenderBackend for PlottersSvgBackend {
+    fn begin_scene(&mut self, scene: &SceneGraph) -> Result<()> {
+        self.recorder.begin_scene(scene);
+        Ok(())
+    }
+
+    fn begin_panel(&mut self, panel: &PanelScene) -> Result<()> {
+        self.recorder.begin_panel(panel)
+    }
+
+    fn draw(&mut self, command: &DrawCommand) -> Result<()> {
+        self.recorder.draw(command)
+    }
+
+    fn finish(&mut self) -> Result<Vec<u8>> {
+        self.recorder.finish(OutputFormat::Svg)
+    }
+}
+
+/// Renders with plotters to raw 8-bit RGBA pixels, the ones the PNG output encodes.
+/// The buffer is row-major and `begin_scene`'s `width` by `height` pixels.
+#[derive(Debug, Default)]
+pub struct PlottersRgbaBackend {
+    recorder: SceneRecorder,
+}
+
+impl PlottersRgbaBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl RenderBackend for PlottersRgbaBackend {
+    fn begin_scene(&mut self, scene: &SceneGraph) -> Result<()> {
+        self.recorder.begin_scene(scene);
+        Ok(())
+    }
+
+    fn begin_panel(&mut self, panel: &PanelScene) -> Result<()> {
+        self.recorder.begin_panel(panel)
+    }
+
+    fn draw(&mut self, command: &DrawCommand) -> Result<()> {
+        self.recorder.draw(command)
+    }
+
+    fn finish(&mut self) -> Result<Vec<u8>> {
+        let (pixels, _, _) = Canvas::render_rgba(self.recorder.take_scene()?)?;
+        Ok(pixels)
+    }
+}
+
+/// Rebuilds the scene from the command stream. Plotters lays out axes and legends
+/// per panel from all of the panel's commands at once, so drawing waits for `finish`.
+#[derive(Debug, Default)]
+pub(crate) struct SceneRecorder {
+    scene: Option<SceneGraph>,
+}
+
+impl SceneRecorder {
+    pub(crate) fn begin_scene(&mut self, scene: &SceneGraph) {
+        self.scene = Some(SceneGraph {
+            width: scene.width,
+            height: scene.height,
+            panels: Vec::with_capacity(scene.panels.len()),
+            labels: scene.labels.clone(),
+            theme: scene.theme.clone(),
+            facet_label_size: scene.facet_label_size,
+            nrow: scene.nrow,
+            ncol: scene.ncol,
+        });
+    }
+
+    pub(crate) fn begin_panel(&mut self, panel: &PanelScene) -> Result<()> {
+        let scene = self.scene_mut()?;
+        scene.panels.push(PanelScene {
+            row: panel.row,
+            col: panel.col,
+            title: panel.title.clone(),
+            row_title: panel.row_title.clone(),
+            x_label: panel.x_label.clone(),
+            y_label: panel.y_label.clone(),
+            x_scale: panel.x_scale.clone(),
+            y_scale: panel.y_scale.clone(),
+            commands: Vec::with_capacity(panel.commands.len()),
+            colorbar: panel.colorbar.clone(),
+            show_x_tick_labels: panel.show_x_tick_labels,
+            show_y_tick_labels: panel.show_y_tick_labels,
+            polar: false,
+            aspect_ratio: panel.aspect_ratio,
+            underlay: panel.underlay,
+        });
+        Ok(())
+    }
+
+    pub(crate) fn draw(&mut self, command: &DrawCommand) -> Result<()> {
+        self.scene_mut()?
+            .panels
+            .last_mut()
+            .ok_or_else(|| anyhow!("draw called before begin_panel"))?
+            .commands
+            .push(command.clone());
+        Ok(())
+    }
+
+    fn finish(&mut self, format: OutputFormat) -> Result<Vec<u8>> {
+        let scene = self.take_scene()?;
+        let options = RenderOptions {
+            width: scene.width,
+            height: scene.height,
+            format,
+            ..RenderOptions::default()
+        };
+        Canvas::execute(scene, &options)
+    }
+
+    pub(crate) fn take_scene(&mut self) -> Result<SceneGraph> {
+        self.scene
+            .take()
+            .ok_or_else(|| anyhow!("finish called before begin_scene"))
+    }
+
+    fn scene_mut(&mut self) -> Result<&mut SceneGraph> {
+        self.scene
+            .as_mut()
+            .ok_or_else(|| anyhow!("begin_panel called before begin_scene"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::PlotData;
+    use crate::parser::parse_plot_spec;
+    use crate::{compiler, resolve, scale, transform};
+
+    fn compile(dsl: &str, csv: &str) -> SceneGraph {
+        let mut lines = csv.lines();
+        let headers = lines
+            .next()
+            .unwrap()
+            .split(',')
+            .map(str::to_string)
+            .collect();
+        let rows = lines
+            .map(|line| line.split(',').map(str::to_string).collect())
+            .collect();
+        let data = PlotData { headers, rows };
+        let (_, spec) = parse_plot_spec(dsl).unwrap();
+        let options = RenderOptions::default();
+        let resolved = resolve::resolve_plot_aesthetics(&spec, &data).unwrap();
+        let render_data =
+            transform::apply_transformations(&resolved, &data, &crate::RenderOptions::default())
+                .unwrap();
+        let scales = scale::build_scales(&render_data, &resolved).unwrap();
+        compiler::compile_geometry(render_data, scales, &resolved, &options).unwrap()
+    }
+
+    #[derive(Default)]
+    struct CountingBackend {
+        scenes: usize,
+        panels: Vec<Option<String>>,
+        lines: usize,
+        points: usize,
+        rects: usize,
+        legends: Vec<String>,
+    }
+
+    impl RenderBackend for CountingBackend {
+        fn begin_scene(&mut self, _scene: &SceneGraph) -> Result<()> {
+            self.scenes += 1;
+            Ok(())
+        }
+
+        fn begin_panel(&mut self, panel: &PanelScene) -> Result<()> {
+            self.panels.push(panel.title.clone());
+            Ok(())
+        }
+
+        fn draw(&mut self, command: &DrawCommand) -> Result<()> {
+            let legend = match command {
+                DrawCommand::DrawLine { legend, .. } => {
+                    self.lines += 1;
+                    legend
+                }
+                DrawCommand::DrawPoint { legend, .. } => {
+                    self.points += 1;
+                    legend
+                }
+                DrawCommand::DrawRect { legend, .. } => {
+                    self.rects += 1;
+                    legend
+                }
+                DrawCommand::DrawPolygon { legend, .. } | DrawCommand::DrawText { legend, .. } => {
+                    legend
+                }
+            };
+            if let Some(label) = legend {
+                if !self.legends.contains(label) {
+                    self.legends.push(label.clone());
+                }
+            }
+            Ok(())
+        }
+
+        fn finish(&mut self) -> Result<Vec<u8>> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[test]
+    fn backend_receives_every_command() {
+        let scene = compile(
+            "aes(x: x, y: y, color: g) | line() | point()",
+            "x,y,g\n1,10,a\n2,20,a\n1,15,b\n2,25,b\n",
+        );
+        let mut backend = CountingBackend::default();
+        render_scene(&scene, &mut backend).unwrap();
+        assert_eq!(backend.scenes, 1);
+        assert_eq!(backend.panels, vec![None]);
+        // One line and one point command per color group
+        assert_eq!(backend.lines, 2);
+        assert_eq!(backend.points, 2);
+        assert_eq!(backend.rects, 0);
+        assert_eq!(backend.legends, vec!["a".to_string(), "b".to_string()]);
+    }
+
+    #[test]
+    fn backend_receives_panels_in_order() {
+        let scene = compile(
+            "aes(x: x, y: y) | bar() | facet_wrap(by: g)",
+            "x,y,g\nA,10,p\nB,20,p\nA,15,q\n",
+        );
+        let mut backend = CountingBackend::default();
+        render_scene(&scene, &mut backend).unwrap();
+        assert_eq!(
+            backend.panels,
+            vec![Some("g = p".to_string()), Some("g = q".to_string())]
+        );
+        assert_eq!(backend.rects, 3);
+    }
+
+    #[test]
+    fn plotters_backends_match_canvas_output() {
+        let scene = compile(
+            "aes(x: x, y: y, color: g) | line() | point()",
+            "x,y,g\n1,10,a\n2,20,a\n1,15,b\n2,25,b\n",
+        );
+
+        let svg = render_scene(&scene, &mut PlottersSvgBackend::new()).unwrap();
+        let options = RenderOptions {
+            format: OutputFormat::Svg,
+            ..RenderOptions::default()
+        };
+        let direct = Canvas::execute(scene.clone(), &options).unwrap();
+        assert_eq!(svg, direct);
+
+        let png = render_scene(&scene, &mut PlottersPngBackend::new()).unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+
+        let rgba = render_scene(&scene, &mut PlottersRgbaBackend::new()).unwrap();
+        assert_eq!(rgba.len(), (scene.width * scene.height * 4) as usize);
+        let (direct, _, _) = Canvas::render_rgba(scene).unwrap();
+        assert_eq!(rgba, direct);
+    }
+
+    #[test]
+    fn recorder_rejects_out_of_order_calls() {
+        let scene = compile("aes(x: x, y: y) | line()", "x,y\n1,2\n2,3\n");
+        let mut backend = PlottersPngBackend::new();
+        assert!(backend.begin_panel(&scene.panels[0]).is_err());
+        assert!(backend.finish().is_err());
+    }
+}
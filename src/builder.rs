@@ -0,0 +1,814 @@
+// Typed builder for PlotSpec, for library users who don't want to format DSL strings
+//
+// The builder produces exactly the AST the parser would, so a built spec and its DSL
+// equivalent render identically.
+
+use crate::parser::ast::{
+    AestheticValue, Aesthetics, BarLayer, BarPosition, ColumnRef, Facet, FacetGrid, FacetLabeller,
+    FacetScales, Labels, Layer, LineLayer, LinePosition, PlotSpec, PointLayer, PointPosition, Stat,
+};
+use anyhow::{bail, Result};
+
+impl PlotSpec {
+    /// Start building a plot specification in code.
+    ///
+    /// ```
+    /// use gramgraph::{LineOptions, PlotSpec, PointOptions};
+    ///
+    /// let spec = PlotSpec::builder()
+    ///     .aes("time", "temp")
+    ///     .color("region")
+    ///     .line(LineOptions::new().width(2.0))
+    ///     .point(PointOptions::new().size(4.0))
+    ///     .facet_wrap("site", 2)
+    ///     .title("Temperature by site")
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!(spec.layers.len(), 2);
+    /// ```
+    pub fn builder() -> PlotSpecBuilder {
+        PlotSpecBuilder::default()
+    }
+}
+
+/// Builds a `PlotSpec`; equivalent to `aes(...) | geom(...) | labs(...) | facet_wrap(...)`
+#[derive(Debug, Clone, Default)]
+pub struct PlotSpecBuilder {
+    x: Option<String>,
+    y: Option<String>,
+    color: Option<String>,
+    size: Option<String>,
+    shape: Option<String>,
+    alpha: Option<String>,
+    group: Option<String>,
+    layers: Vec<Layer>,
+    labels: Option<Labels>,
+    facet: Option<Facet>,
+    facet_grid: Option<FacetGrid>,
+}
+
+impl PlotSpecBuilder {
+    /// Global x and y mappings, like `aes(x: ..., y: ...)`
+    pub fn aes(mut self, x: impl Into<String>, y: impl Into<String>) -> Self {
+        self.x = Some(x.into());
+        self.y = Some(y.into());
+        self
+    }
+
+    /// Global x mapping only, for layers that compute y (e.g. counted bars)
+    pub fn aes_x(mut self, x: impl Into<String>) -> Self {
+        self.x = Some(x.into());
+        self.y = None;
+        self
+    }
+
+    /// Global color grouping column, like `aes(color: ...)`
+    pub fn color(mut self, column: impl Into<String>) -> Self {
+        self.color = Some(column.into());
+        self
+    }
+
+    /// Global size mapping column, like `aes(size: ...)`
+    pub fn size(mut self, column: impl Into<String>) -> Self {
+        self.size = Some(column.into());
+        self
+    }
+
+    /// Global shape mapping column, like `aes(shape: ...)`
+    pub fn shape(mut self, column: impl Into<String>) -> Self {
+        self.shape = Some(column.into());
+        self
+    }
+
+    /// Global alpha mapping column, like `aes(alpha: ...)`
+    pub fn alpha(mut self, column: impl Into<String>) -> Self {
+        self.alpha = Some(column.into());
+        self
+    }
+
+    /// Global grouping column that styles nothing, like `aes(group: ...)`
+    pub fn group(mut self, column: impl Into<String>) -> Self {
+        self.group = Some(column.into());
+        self
+    }
+
+    /// Add a `line(...)` layer
+    pub fn line(mut self, options: LineOptions) -> Self {
+        self.layers.push(Layer::Line(options.layer));
+        self
+    }
+
+    /// Add a `point(...)` layer
+    pub fn point(mut self, options: PointOptions) -> Self {
+        self.layers.push(Layer::Point(options.layer));
+        self
+    }
+
+    /// Add a `bar(...)` layer
+    pub fn bar(mut self, options: BarOptions) -> Self {
+        self.layers.push(Layer::Bar(options.layer));
+        self
+    }
+
+    /// Plot title, like `labs(title: ...)`
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.labels_mut().title = Some(title.into());
+        self
+    }
+
+    /// Plot subtitle, like `labs(subtitle: ...)`
+    pub fn subtitle(mut self, subtitle: impl Into<String>) -> Self {
+        self.labels_mut().subtitle = Some(subtitle.into());
+        self
+    }
+
+    /// X axis title, like `labs(x: ...)`
+    pub fn x_label(mut self, label: impl Into<String>) -> Self {
+        self.labels_mut().x = Some(label.into());
+        self
+    }
+
+    /// Y axis title, like `labs(y: ...)`
+    pub fn y_label(mut self, label: impl Into<String>) -> Self {
+        self.labels_mut().y = Some(label.into());
+        self
+    }
+
+    /// Caption below the plot, like `labs(caption: ...)`
+    pub fn caption(mut self, caption: impl Into<String>) -> Self {
+        self.labels_mut().caption = Some(caption.into());
+        self
+    }
+
+    /// One panel per value of `by`, laid out in `ncol` columns
+    /// (pass `None` for the automatic layout), like `facet_wrap(by: ..., ncol: ...)`
+    pub fn facet_wrap(mut self, by: impl Into<String>, ncol: impl Into<Option<usize>>) -> Self {
+        let scales = self.take_facet_scales();
+        self.facet = Some(Facet {
+            by: by.into(),
+            ncol: ncol.into(),
+            nrow: None,
+            scales,
+            bins: None,
+            cut: Default::default(),
+            labeller: Default::default(),
+            label_size: None,
+        });
+        self
+    }
+
+    /// One panel per combination of the `rows` and `cols` columns' values, like
+    /// `facet_grid(rows: ..., cols: ...)`; replaces any `facet_wrap`
+    pub fn facet_grid(mut self, rows: Option<&str>, cols: Option<&str>) -> Self {
+        let scales = self.take_facet_scales();
+        self.facet_grid = Some(FacetGrid {
+            rows: rows.map(str::to_string),
+            cols: cols.map(str::to_string),
+            scales,
+            labeller: Default::default(),
+            label_size: None,
+        });
+        self
+    }
+
+    /// Axis sharing across facets, like `facet_wrap(scales: ...)`; requires `facet_wrap`
+    /// or `facet_grid`
+    pub fn facet_scales(mut self, scales: FacetScales) -> Self {
+        if let Some(facet) = &mut self.facet {
+            facet.scales = scales.clone();
+        }
+        if let Some(grid) = &mut self.facet_grid {
+            grid.scales = scales;
+        }
+        self
+    }
+
+    /// Panel caption format, like `facet_wrap(labeller: ...)`; requires `facet_wrap` or
+    /// `facet_grid`
+    pub fn facet_labeller(mut self, labeller: FacetLabeller) -> Self {
+        if let Some(facet) = &mut self.facet {
+            facet.labeller = labeller.clone();
+        }
+        if let Some(grid) = &mut self.facet_grid {
+            grid.labeller = labeller;
+        }
+        self
+    }
+
+    /// Panel caption font size in pixels, like `facet_wrap(label_size: ...)`; requires
+    /// `facet_wrap` or `facet_grid`
+    pub fn facet_label_size(mut self, size: f64) -> Self {
+        if let Some(facet) = &mut self.facet {
+            facet.label_size = Some(size);
+        }
+        if let Some(grid) = &mut self.facet_grid {
+            grid.label_size = Some(size);
+        }
+        self
+    }
+
+    /// Clear the current facet (wrap or grid), keeping its scales for the new one
+    fn take_facet_scales(&mut self) -> FacetScales {
+        let wrap = self.facet.take().map(|f| f.scales);
+        let grid = self.facet_grid.take().map(|g| g.scales);
+        wrap.or(grid).unwrap_or_default()
+    }
+
+    /// Lay facets out in `nrow` rows instead of `ncol` columns, like
+    /// `facet_wrap(nrow: ...)`; requires `facet_wrap` with no `ncol`
+    pub fn facet_nrow(mut self, nrow: usize) -> Self {
+        if let Some(facet) = &mut self.facet {
+            facet.nrow = Some(nrow);
+        }
+        self
+    }
+
+    /// Validate and produce the spec
+    pub fn build(self) -> Result<PlotSpec> {
+        if self.layers.is_empty() {
+            bail!("A plot needs at least one layer (add line(), point(), or bar())");
+        }
+        let has_mappings = self.y.is_some()
+            || self.color.is_some()
+            || self.size.is_some()
+            || self.shape.is_some()
+            || self.alpha.is_some();
+        if self.x.is_none() && has_mappings {
+            bail!("Global mappings need an x column; call aes() or aes_x() first");
+        }
+        let columns = [
+            &self.x,
+            &self.y,
+            &self.color,
+            &self.size,
+            &self.shape,
+            &self.alpha,
+        ];
+        if columns.iter().any(|c| c.as_deref() == Some("")) {
+            bail!("Column names in aes() must not be empty");
+        }
+        if let Some(facet) = &self.facet {
+            if facet.by.is_empty() {
+                bail!("facet_wrap needs a column to facet by");
+            }
+            if facet.ncol == Some(0) || facet.nrow == Some(0) {
+                bail!("facet_wrap ncol and nrow must be at least 1");
+            }
+            if facet.ncol.is_some() && facet.nrow.is_some() {
+                bail!("facet_wrap takes ncol or nrow, not both");
+            }
+        }
+        if let Some(grid) = &self.facet_grid {
+            let columns = [&grid.rows, &grid.cols];
+            if columns.iter().all(|c| c.is_none()) {
+                bail!("facet_grid needs a rows or cols column");
+            }
+            if columns.iter().any(|c| c.as_deref() == Some("")) {
+                bail!("facet_grid column names must not be empty");
+            }
+        }
+
+        let aesthetics = self.x.map(|x| Aesthetics {
+            x: x.into(),
+            y: self.y.map(Into::into),
+            y_columns: Vec::new(),
+            color: self.color.map(Into::into),
+            size: self.size.map(Into::into),
+            shape: self.shape.map(Into::into),
+            alpha: self.alpha.map(Into::into),
+            ymin: None,
+            ymax: None,
+            fill: None,
+            linetype: None,
+            group: self.group.map(Into::into),
+        });
+
+        Ok(PlotSpec {
+            aesthetics,
+            layers: self.layers,
+            labels: self.labels,
+            facet: self.facet,
+            facet_grid: self.facet_grid,
+            coord: None,
+            theme: None,
+            x_scale: None,
+            y_scale: None,
+            color_scale: None,
+            color_manual: None,
+            stages: Vec::new(),
+            annotations: Vec::new(),
+            shades: Vec::new(),
+        })
+    }
+
+    fn labels_mut(&mut self) -> &mut Labels {
+        self.labels.get_or_insert_with(Labels::default)
+    }
+}
+
+/// Options for a `line(...)` layer
+#[derive(Debug, Clone, Default)]
+pub struct LineOptions {
+    layer: LineLayer,
+}
+
+impl LineOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Override the global x column for this layer
+    pub fn x(mut self, column: impl Into<String>) -> Self {
+        self.layer.x = Some(ColumnRef::Name(column.into()));
+        self
+    }
+
+    /// Override the global y column for this layer
+    pub fn y(mut self, column: impl Into<String>) -> Self {
+        self.layer.y = Some(ColumnRef::Name(column.into()));
+        self
+    }
+
+    /// Fixed line color (named color or hex), like `line(color: "red")`
+    pub fn color(mut self, color: impl Into<String>) -> Self {
+        self.layer.color = Some(AestheticValue::Fixed(color.into()));
+        self
+    }
+
+    /// Color grouping column, like `line(color: region)`
+    pub fn color_by(mut self, column: impl Into<String>) -> Self {
+        self.layer.color = Some(AestheticValue::Mapped(ColumnRef::Name(column.into())));
+        self
+    }
+
+    /// Fixed stroke width, like `line(width: 2)`
+    pub fn width(mut self, width: f64) -> Self {
+        self.layer.width = Some(AestheticValue::Fixed(width));
+        self
+    }
+
+    /// Fixed opacity from 0.0 to 1.0, like `line(alpha: 0.5)`
+    pub fn alpha(mut self, alpha: f64) -> Self {
+        self.layer.alpha = Some(AestheticValue::Fixed(alpha));
+        self
+    }
+
+    /// Fixed dash pattern (`solid`, `dashed`, `dotted`, `dotdash`), like `line(linetype: "dashed")`
+    pub fn linetype(mut self, linetype: impl Into<String>) -> Self {
+        self.layer.linetype = Some(AestheticValue::Fixed(linetype.into()));
+        self
+    }
+
+    /// Linetype grouping column, like `line(linetype: region)`
+    pub fn linetype_by(mut self, column: impl Into<String>) -> Self {
+        self.layer.linetype = Some(AestheticValue::Mapped(ColumnRef::Name(column.into())));
+        self
+    }
+
+    /// Column splitting rows into separate, identically styled lines, like `line(group: id)`
+    pub fn group(mut self, column: impl Into<String>) -> Self {
+        self.layer.group = Some(ColumnRef::Name(column.into()));
+        self
+    }
+
+    /// Offset grouped lines within categorical x slots, like `line(position: "dodge")`
+    pub fn position(mut self, position: LinePosition) -> Self {
+        self.layer.position = position;
+        self
+    }
+
+    /// Whether to skip points that share a pixel column with their neighbours (on by
+    /// default); `false` is `line(simplify: false)`
+    pub fn simplify(mut self, simplify: bool) -> Self {
+        self.layer.simplify = simplify;
+        self
+    }
+
+    /// Draw a statistic instead of the raw series, like
+    /// `line(stat: "rolling_mean", window: 7)` (`Stat::RollingMean`)
+    pub fn stat(mut self, stat: Stat) -> Self {
+        self.layer.stat = stat;
+        self
+    }
+}
+
+/// Options for a `point(...)` layer
+#[derive(Debug, Clone, Default)]
+pub struct PointOptions {
+    layer: PointLayer,
+}
+
+impl PointOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Override the global x column for this layer
+    pub fn x(mut self, column: impl Into<String>) -> Self {
+        self.layer.x = Some(ColumnRef::Name(column.into()));
+        self
+    }
+
+    /// Override the global y column for this layer
+    pub fn y(mut self, column: impl Into<String>) -> Self {
+        self.layer.y = Some(ColumnRef::Name(column.into()));
+        self
+    }
+
+    /// Fixed point color, like `point(color: "blue")`
+    pub fn color(mut self, color: impl Into<String>) -> Self {
+        self.layer.color = Some(AestheticValue::Fixed(color.into()));
+        self
+    }
+
+    /// Color grouping column, like `point(color: region)`
+    pub fn color_by(mut self, column: impl Into<String>) -> Self {
+        self.layer.color = Some(AestheticValue::Mapped(ColumnRef::Name(column.into())));
+        self
+    }
+
+    /// Fixed point size, like `point(size: 4)`
+    pub fn size(mut self, size: f64) -> Self {
+        self.layer.size = Some(AestheticValue::Fixed(size));
+        self
+    }
+
+    /// Size mapping column, like `point(size: population)`
+    pub fn size_by(mut self, column: impl Into<String>) -> Self {
+        self.layer.size = Some(AestheticValue::Mapped(ColumnRef::Name(column.into())));
+        self
+    }
+
+    /// Fixed marker shape (`circle`, `square`, `triangle`, ...), like `point(shape: "square")`
+    pub fn shape(mut self, shape: impl Into<String>) -> Self {
+        self.layer.shape = Some(AestheticValue::Fixed(shape.into()));
+        self
+    }
+
+    /// Shape grouping column, like `point(shape: species)`
+    pub fn shape_by(mut self, column: impl Into<String>) -> Self {
+        self.layer.shape = Some(AestheticValue::Mapped(ColumnRef::Name(column.into())));
+        self
+    }
+
+    /// Fixed opacity from 0.0 to 1.0, like `point(alpha: 0.5)`
+    pub fn alpha(mut self, alpha: f64) -> Self {
+        self.layer.alpha = Some(AestheticValue::Fixed(alpha));
+        self
+    }
+
+    /// How overplotted points are spread out, like `point(position: "jitter")`
+    pub fn position(mut self, position: PointPosition) -> Self {
+        self.layer.position = position;
+        self
+    }
+
+    /// Jitter span in x units, like `point(jitter_width: 0.2)`
+    pub fn jitter_width(mut self, width: f64) -> Self {
+        self.layer.jitter_width = Some(width);
+        self
+    }
+
+    /// Fixed fill inside an outline drawn in the point color, like `point(fill: "white")`
+    pub fn fill(mut self, color: impl Into<String>) -> Self {
+        self.layer.fill = Some(color.into());
+        self
+    }
+
+    /// Outline width in pixels, like `point(stroke: 1.5)`
+    pub fn stroke(mut self, width: f64) -> Self {
+        self.layer.stroke = Some(width);
+        self
+    }
+
+    /// Whether a layer over `RenderOptions.max_exact_points` may be binned onto the
+    /// pixel grid (on by default); `false` is `point(fast: false)`
+    pub fn fast(mut self, fast: bool) -> Self {
+        self.layer.fast = fast;
+        self
+    }
+}
+
+/// Options for a `bar(...)` layer
+#[derive(Debug, Clone, Default)]
+pub struct BarOptions {
+    layer: BarLayer,
+}
+
+impl BarOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Override the global x column for this layer
+    pub fn x(mut self, column: impl Into<String>) -> Self {
+        self.layer.x = Some(ColumnRef::Name(column.into()));
+        self
+    }
+
+    /// Override the global y column for this layer
+    pub fn y(mut self, column: impl Into<String>) -> Self {
+        self.layer.y = Some(ColumnRef::Name(column.into()));
+        self
+    }
+
+    /// Fixed fill color, like `bar(color: "steelblue")`
+    pub fn color(mut self, color: impl Into<String>) -> Self {
+        self.layer.color = Some(AestheticValue::Fixed(color.into()));
+        self
+    }
+
+    /// Color grouping column, like `bar(color: type)`
+    pub fn color_by(mut self, column: impl Into<String>) -> Self {
+        self.layer.color = Some(AestheticValue::Mapped(ColumnRef::Name(column.into())));
+        self
+    }
+
+    /// Bar width relative to the category spacing (0.0-1.0), or in x units with
+    /// `x_continuous`, like `bar(width: 0.5)`
+    pub fn width(mut self, width: f64) -> Self {
+        self.layer.width = Some(AestheticValue::Fixed(width));
+        self
+    }
+
+    /// Fixed opacity from 0.0 to 1.0, like `bar(alpha: 0.5)`
+    pub fn alpha(mut self, alpha: f64) -> Self {
+        self.layer.alpha = Some(AestheticValue::Fixed(alpha));
+        self
+    }
+
+    /// How grouped bars share an x position, like `bar(position: "dodge")` or `"fill"`
+    pub fn position(mut self, position: BarPosition) -> Self {
+        self.layer.position = position;
+        self
+    }
+
+    /// Outline color drawn around each bar, like `bar(border: "black")`
+    pub fn border(mut self, color: impl Into<String>) -> Self {
+        self.layer.border = Some(color.into());
+        self
+    }
+
+    /// Outline width in pixels, like `bar(border_width: 2)`
+    pub fn border_width(mut self, width: f64) -> Self {
+        self.layer.border_width = Some(width);
+        self
+    }
+
+    /// Center bars on a numeric x axis instead of category slots, like
+    /// `bar(x_continuous: true)`; `width` is then in x units
+    pub fn x_continuous(mut self, continuous: bool) -> Self {
+        self.layer.x_continuous = continuous;
+        self
+    }
+
+    /// How rows with the same x combine, like `bar(stat: "sum")` (`Stat::Summary`)
+    /// or `bar(stat: "count")` (`Stat::Count`)
+    pub fn stat(mut self, stat: Stat) -> Self {
+        self.layer.stat = stat;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::PlotData;
+    use crate::parser::parse_plot_spec;
+    use crate::{runtime, RenderOptions};
+
+    fn parse(dsl: &str) -> PlotSpec {
+        parse_plot_spec(dsl).unwrap().1
+    }
+
+    fn data(csv: &str) -> PlotData {
+        let mut lines = csv.lines();
+        let headers = lines
+            .next()
+            .unwrap()
+            .split(',')
+            .map(str::to_string)
+            .collect();
+        let rows = lines
+            .map(|line| line.split(',').map(str::to_string).collect())
+            .collect();
+        PlotData { headers, rows }
+    }
+
+    fn render(spec: PlotSpec, csv: &str) -> Vec<u8> {
+        runtime::render_plot(spec, data(csv), RenderOptions::default()).unwrap()
+    }
+
+    const SITES: &str = "time,temp,region,site\n1,10,north,a\n2,12,north,a\n1,8,south,a\n2,9,south,a\n1,14,north,b\n2,15,north,b\n1,11,south,b\n2,13,south,b\n";
+
+    #[test]
+    fn builds_the_parsed_ast() {
+        let built = PlotSpec::builder()
+            .aes("time", "temp")
+            .color("region")
+            .line(LineOptions::new().width(2.0))
+            .point(PointOptions::new().size(4.0).color("black").alpha(0.5))
+            .facet_wrap("site", 2)
+            .title("Temps")
+            .y_label("Celsius")
+            .build()
+            .unwrap();
+        let parsed = parse(
+            r#"aes(x: time, y: temp, color: region) | line(width: 2) | point(size: 4, color: "black", alpha: 0.5) | facet_wrap(by: site, ncol: 2) | labs(title: "Temps", y: "Celsius")"#,
+        );
+        assert_eq!(built, parsed);
+    }
+
+    #[test]
+    fn builds_mapped_and_positioned_layers() {
+        let built = PlotSpec::builder()
+            .aes("x", "y")
+            .bar(BarOptions::new().color_by("g").position(BarPosition::Dodge))
+            .point(PointOptions::new().y("z").size_by("s").shape("square"))
+            .facet_wrap("f", None)
+            .facet_scales(FacetScales::FreeY)
+            .facet_nrow(2)
+            .build()
+            .unwrap();
+        let parsed = parse(
+            r#"aes(x: x, y: y) | bar(color: g, position: "dodge") | point(y: z, size: s, shape: "square") | facet_wrap(by: f, nrow: 2, scales: "free_y")"#,
+        );
+        assert_eq!(built, parsed);
+
+        let built = PlotSpec::builder()
+            .aes("g", "y")
+            .point(
+                PointOptions::new()
+                    .position(PointPosition::Jitter)
+                    .jitter_width(0.2),
+            )
+            .build()
+            .unwrap();
+        assert_eq!(built, parse("aes(x: g, y: y) | jitter(width: 0.2)"));
+
+        let built = PlotSpec::builder()
+            .aes("g", "y")
+            .color("c")
+            .line(LineOptions::new().position(LinePosition::Dodge))
+            .point(PointOptions::new().position(PointPosition::Dodge))
+            .build()
+            .unwrap();
+        let parsed = parse(
+            r#"aes(x: g, y: y, color: c) | line(position: "dodge") | point(position: "dodge")"#,
+        );
+        assert_eq!(built, parsed);
+
+        let built = PlotSpec::builder()
+            .aes("x", "y")
+            .line(LineOptions::new())
+            .facet_wrap("f", None)
+            .facet_grid(Some("r"), Some("c"))
+            .facet_scales(FacetScales::Free)
+            .build()
+            .unwrap();
+        let parsed =
+            parse(r#"aes(x: x, y: y) | line() | facet_grid(rows: r, cols: c, scales: "free")"#);
+        assert_eq!(built, parsed);
+    }
+
+    #[test]
+    fn builds_line_linetypes() {
+        let built = PlotSpec::builder()
+            .aes("x", "y")
+            .line(LineOptions::new().linetype_by("g"))
+            .line(LineOptions::new().y("z").linetype("dashed"))
+            .build()
+            .unwrap();
+        let parsed =
+            parse(r#"aes(x: x, y: y) | line(linetype: g) | line(y: z, linetype: "dashed")"#);
+        assert_eq!(built, parsed);
+    }
+
+    #[test]
+    fn builds_line_simplify() {
+        let built = PlotSpec::builder()
+            .aes("x", "y")
+            .line(LineOptions::new().simplify(false))
+            .build()
+            .unwrap();
+        assert_eq!(built, parse("aes(x: x, y: y) | line(simplify: false)"));
+    }
+
+    #[test]
+    fn builds_point_fast() {
+        let built = PlotSpec::builder()
+            .aes("x", "y")
+            .point(PointOptions::new().fast(false))
+            .build()
+            .unwrap();
+        assert_eq!(built, parse("aes(x: x, y: y) | point(fast: false)"));
+    }
+
+    #[test]
+    fn builds_group_columns() {
+        let built = PlotSpec::builder()
+            .aes("x", "y")
+            .group("id")
+            .line(LineOptions::new())
+            .line(LineOptions::new().y("z").group("run"))
+            .build()
+            .unwrap();
+        let parsed = parse("aes(x: x, y: y, group: id) | line() | line(y: z, group: run)");
+        assert_eq!(built, parsed);
+    }
+
+    #[test]
+    fn builder_spec_renders_identically_to_parsed_spec() {
+        let built = PlotSpec::builder()
+            .aes("time", "temp")
+            .color("region")
+            .line(LineOptions::new().width(2.0))
+            .point(PointOptions::new().size(4.0).fill("white").stroke(1.5))
+            .facet_wrap("site", 2)
+            .title("Temps")
+            .build()
+            .unwrap();
+        let parsed = parse(
+            r#"aes(x: time, y: temp, color: region) | line(width: 2) | point(size: 4, fill: "white", stroke: 1.5) | facet_wrap(by: site, ncol: 2) | labs(title: "Temps")"#,
+        );
+        assert_eq!(render(built, SITES), render(parsed, SITES));
+
+        let built = PlotSpec::builder()
+            .aes("region", "temp")
+            .color("site")
+            .bar(
+                BarOptions::new()
+                    .position(BarPosition::Stack)
+                    .stat(Stat::Summary {
+                        fun: crate::parser::ast::Aggregate::Mean,
+                    })
+                    .border("black")
+                    .border_width(2.0),
+            )
+            .build()
+            .unwrap();
+        let parsed = parse(
+            r#"aes(x: region, y: temp, color: site) | bar(position: "stack", stat: "mean", border: "black", border_width: 2)"#,
+        );
+        assert_eq!(render(built, SITES), render(parsed, SITES));
+
+        let built = PlotSpec::builder()
+            .aes("time", "temp")
+            .color("region")
+            .line(LineOptions::new().alpha(0.3))
+            .line(LineOptions::new().width(2.0).stat(Stat::RollingMean {
+                window: 2,
+                min_periods: Some(1),
+            }))
+            .build()
+            .unwrap();
+        let parsed = parse(
+            r#"aes(x: time, y: temp, color: region) | line(alpha: 0.3) | line(stat: "rolling_mean", window: 2, min_periods: 1, width: 2)"#,
+        );
+        assert_eq!(render(built, SITES), render(parsed, SITES));
+    }
+
+    #[test]
+    fn build_validates_the_spec() {
+        let err = PlotSpec::builder().aes("x", "y").build().unwrap_err();
+        assert!(err.to_string().contains("at least one layer"));
+
+        let err = PlotSpec::builder()
+            .color("g")
+            .line(LineOptions::new())
+            .build()
+            .unwrap_err();
+        assert!(err.to_string().contains("need an x column"));
+
+        let err = PlotSpec::builder()
+            .aes("x", "y")
+            .line(LineOptions::new())
+            .facet_wrap("g", 0)
+            .build()
+            .unwrap_err();
+        assert!(err.to_string().contains("ncol and nrow must be at least 1"));
+
+        let err = PlotSpec::builder()
+            .aes("x", "y")
+            .line(LineOptions::new())
+            .facet_wrap("g", 2)
+            .facet_nrow(1)
+            .build()
+            .unwrap_err();
+        assert!(err.to_string().contains("ncol or nrow, not both"));
+
+        let err = PlotSpec::builder()
+            .aes("x", "y")
+            .line(LineOptions::new())
+            .facet_grid(None, None)
+            .build()
+            .unwrap_err();
+        assert!(err.to_string().contains("needs a rows or cols column"));
+
+        // Layer-level x without global aes is allowed, as in the DSL
+        assert!(PlotSpec::builder()
+            .line(LineOptions::new().x("a").y("b"))
+            .build()
+            .is_ok());
+    }
+}
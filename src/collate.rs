@@ -0,0 +1,159 @@
+// Ordering for category, facet, and group labels
+//
+// Labels sort the same way on every machine: numerically when every label is a
+// number, and otherwise in natural order, where runs of digits compare by value ("run2" < "run10") and
+// everything else compares by Unicode code point (no locale, no case folding).
+
+use serde::Deserialize;
+use std::cmp::Ordering;
+
+/// How sorted labels are ordered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LabelOrder {
+    /// Numbers by value, other labels naturally (default)
+    #[default]
+    Natural,
+    /// Plain string order, where "10" sorts before "9"
+    Lexical,
+}
+
+impl LabelOrder {
+    /// Sort `labels` in place
+    pub fn sort(self, labels: &mut [String]) {
+        match self {
+            LabelOrder::Lexical => labels.sort(),
+            LabelOrder::Natural => {
+                if let Some(values) = labels
+                    .iter()
+                    .map(|s| s.trim().parse::<f64>().ok())
+                    .collect::<Option<Vec<f64>>>()
+                {
+                    let mut keyed: Vec<(f64, String)> =
+                        values.into_iter().zip(labels.iter().cloned()).collect();
+                    keyed.sort_by(|a, b| a.0.total_cmp(&b.0).then_with(|| natural_cmp(&a.1, &b.1)));
+                    for (slot, (_, label)) in labels.iter_mut().zip(keyed) {
+                        *slot = label;
+                    }
+                } else {
+                    labels.sort_by(|a, b| natural_cmp(a, b));
+                }
+            }
+        }
+    }
+
+    /// Compare two labels without the rest of their set, as a tie-breaker
+    pub fn cmp(self, a: &str, b: &str) -> Ordering {
+        match self {
+            LabelOrder::Lexical => a.cmp(b),
+            LabelOrder::Natural => natural_cmp(a, b),
+        }
+    }
+}
+
+/// Natural order: digit runs compare by value, other text by code point. Labels that
+/// differ only in leading zeros fall back to plain string order, so the order is total.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a_rest, mut b_rest) = (a, b);
+    loop {
+        match (a_rest.chars().next(), b_rest.chars().next()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let (a_digits, a_tail) = split_digits(a_rest);
+                let (b_digits, b_tail) = split_digits(b_rest);
+                let a_value = a_digits.trim_start_matches('0');
+                let b_value = b_digits.trim_start_matches('0');
+                let ordering = a_value
+                    .len()
+                    .cmp(&b_value.len())
+                    .then_with(|| a_value.cmp(b_value));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                (a_rest, b_rest) = (a_tail, b_tail);
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(&y);
+                }
+                (a_rest, b_rest) = (&a_rest[x.len_utf8()..], &b_rest[y.len_utf8()..]);
+            }
+        }
+    }
+}
+
+fn split_digits(s: &str) -> (&str, &str) {
+    let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    s.split_at(end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(order: LabelOrder, labels: &[&str]) -> Vec<String> {
+        let mut labels: Vec<String> = labels.iter().map(|s| s.to_string()).collect();
+        order.sort(&mut labels);
+        labels
+    }
+
+    #[test]
+    fn numeric_labels_sort_by_value() {
+        assert_eq!(
+            sorted(LabelOrder::Natural, &["10", "2", "1", "-3", "2.5"]),
+            ["-3", "1", "2", "2.5", "10"]
+        );
+        // The same value written two ways keeps a fixed order
+        assert_eq!(sorted(LabelOrder::Natural, &["1.0", "1"]), ["1", "1.0"]);
+        assert_eq!(
+            sorted(LabelOrder::Lexical, &["10", "2", "1"]),
+            ["1", "10", "2"]
+        );
+    }
+
+    #[test]
+    fn mixed_labels_sort_naturally() {
+        assert_eq!(
+            sorted(
+                LabelOrder::Natural,
+                &["run10", "run2", "run1", "Run3", "10", "x"]
+            ),
+            ["10", "Run3", "run1", "run2", "run10", "x"]
+        );
+        assert_eq!(
+            sorted(LabelOrder::Natural, &["item 007", "item 7", "item 10"]),
+            ["item 007", "item 7", "item 10"]
+        );
+        assert_eq!(
+            sorted(LabelOrder::Lexical, &["run10", "run2"]),
+            ["run10", "run2"]
+        );
+    }
+
+    #[test]
+    fn version_labels_sort_by_component() {
+        assert_eq!(
+            sorted(
+                LabelOrder::Natural,
+                &["1.10.0", "1.2.0", "1.9.3", "1.2.0-rc1"]
+            ),
+            ["1.2.0", "1.2.0-rc1", "1.9.3", "1.10.0"]
+        );
+        assert_eq!(
+            sorted(LabelOrder::Natural, &["v2", "v10", "v1.5"]),
+            ["v1.5", "v2", "v10"]
+        );
+    }
+
+    #[test]
+    fn natural_cmp_is_a_total_order() {
+        assert_eq!(natural_cmp("a1", "a1"), Ordering::Equal);
+        assert_eq!(natural_cmp("a01", "a1"), Ordering::Less);
+        assert_eq!(natural_cmp("a", "a1"), Ordering::Less);
+        assert_eq!(natural_cmp("é2", "é10"), Ordering::Less);
+        assert_eq!(LabelOrder::Natural.cmp("b9", "b10"), Ordering::Less);
+        assert_eq!(LabelOrder::Lexical.cmp("b9", "b10"), Ordering::Greater);
+    }
+}
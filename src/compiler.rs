@@ -1,43 +1,51 @@
-use crate::graph::{BarStyle, BoxplotStyle, LineStyle, PointStyle, RibbonStyle};
+use crate::collate::LabelOrder;
+use crate::graph::{BarStyle, BoxplotStyle, LabelStyle, LineStyle, PointStyle, RibbonStyle};
 use crate::ir::{
-    DrawCommand, PanelScales, PanelScene, RenderData, RenderStyle, ResolvedSpec, Scale,
-    ScaleSystem, SceneGraph,
+    ColorBar, DrawCommand, GroupAesthetic, PanelAnnotation, PanelScales, PanelScene, PanelShade,
+    RenderData, RenderStyle, ResolvedSpec, Scale, ScaleSystem, SceneGraph,
+};
+use crate::palette::{ColorGradient, ColorPalette, SizePalette};
+use crate::parser::ast::{
+    Annotation, BarPosition, ColorScale, FacetScales, Layer, LineInterpolation, LinePosition,
+    PointPosition, Shade, Stat,
 };
-use crate::parser::ast::{BarPosition, Layer, LineInterpolation};
 use crate::RenderOptions;
 use anyhow::{anyhow, Result};
 
 use std::collections::{HashMap, HashSet};
 
-// =============================================================================
-// Heatmap Color Mapping
-// =============================================================================
+/// Number of gradient samples used to draw a colorbar strip
+const COLORBAR_STEPS: usize = 32;
+
+/// Target number of intervals between reference bubbles in a continuous size legend
+const SIZE_LEGEND_INTERVALS: usize = 2;
 
-/// Map a normalized value (0.0-1.0) to a viridis-inspired color gradient
-/// Goes from dark purple (low) → blue → teal → green → yellow (high)
-fn value_to_heatmap_color(t: f64) -> String {
-    // Simplified viridis colormap with 5 control points
-    let colors: [(f64, f64, f64); 5] = [
-        (68.0, 1.0, 84.0),    // Dark purple (t=0.0)
-        (59.0, 82.0, 139.0),  // Blue (t=0.25)
-        (33.0, 145.0, 140.0), // Teal (t=0.5)
-        (94.0, 201.0, 98.0),  // Green (t=0.75)
-        (253.0, 231.0, 37.0), // Yellow (t=1.0)
-    ];
-
-    let t = t.clamp(0.0, 1.0);
-    let segment = (t * 4.0).min(3.999);
-    let idx = segment.floor() as usize;
-    let frac = segment - idx as f64;
-
-    let (r1, g1, b1) = colors[idx];
-    let (r2, g2, b2) = colors[idx + 1];
-
-    let r = (r1 + (r2 - r1) * frac).round() as u8;
-    let g = (g1 + (g2 - g1) * frac).round() as u8;
-    let b = (b1 + (b2 - b1) * frac).round() as u8;
-
-    format!("#{:02x}{:02x}{:02x}", r, g, b)
+/// Opacity past which a binned cell is drawn as if it held no more points
+const BINNED_ALPHA_SATURATION: f64 = 0.99;
+
+/// Font size the renderer draws text at when none is set
+const DEFAULT_TEXT_SIZE: f64 = 11.0;
+
+/// Build the continuous color gradient described by `scale_color(low:, high:)`
+fn color_scale_gradient(scale: &ColorScale) -> Result<ColorGradient> {
+    let endpoint = |name: &str| {
+        crate::theme_resolve::parse_color(name)
+            .map(|c| (c.0, c.1, c.2))
+            .ok_or_else(|| anyhow!("Unknown color '{}' in scale_color", name))
+    };
+    Ok(ColorPalette::gradient(
+        endpoint(&scale.low)?,
+        endpoint(&scale.high)?,
+    ))
+}
+
+/// Normalize a value into 0.0-1.0 within a (min, max) domain (0.5 for a zero-width domain)
+fn normalize_in_domain(value: f64, (min, max): (f64, f64)) -> f64 {
+    if max > min {
+        ((value - min) / (max - min)).clamp(0.0, 1.0)
+    } else {
+        0.5
+    }
 }
 
 // =============================================================================
@@ -57,6 +65,7 @@ struct BoxplotGeometry {
 }
 
 /// Calculates boxplot primitive geometry for a single boxplot
+#[allow(clippy::too_many_arguments)]
 fn compute_boxplot_geometry(
     x: f64,
     width: f64,
@@ -105,6 +114,9 @@ fn boxplot_component_styles(style: &BoxplotStyle) -> (LineStyle, BarStyle, LineS
         color: style.color.clone(),
         width: Some(2.0),
         alpha: style.alpha,
+        linetype: None,
+        arrow: false,
+        simplify: false,
     };
 
     // Box fill
@@ -112,6 +124,8 @@ fn boxplot_component_styles(style: &BoxplotStyle) -> (LineStyle, BarStyle, LineS
         color: style.color.clone(),
         alpha: style.alpha,
         width: None,
+        border: None,
+        border_width: None,
     };
 
     // Median line - white for contrast
@@ -119,6 +133,9 @@ fn boxplot_component_styles(style: &BoxplotStyle) -> (LineStyle, BarStyle, LineS
         color: Some("white".to_string()),
         width: Some(2.0),
         alpha: Some(0.9),
+        linetype: None,
+        arrow: false,
+        simplify: false,
     };
 
     // Outliers - use outlier-specific style or fallback to main color
@@ -127,6 +144,8 @@ fn boxplot_component_styles(style: &BoxplotStyle) -> (LineStyle, BarStyle, LineS
         size: style.outlier_size,
         shape: style.outlier_shape.clone(),
         alpha: style.alpha,
+        fill: None,
+        stroke_width: None,
     };
 
     (whisker_style, box_style, median_style, outlier_style)
@@ -207,6 +226,246 @@ fn transform_visual_points(
         .collect()
 }
 
+/// A `shade()` band's rectangle. Sides are placed in the axes' transformed space so a
+/// missing one can sit at the axis limit, and a category side covers its whole slot.
+fn shade_command(
+    shade: &Shade,
+    band: &PanelShade,
+    scales: &PanelScales,
+    is_flipped: bool,
+) -> Result<DrawCommand> {
+    let span = |low: Option<f64>, high: Option<f64>, scale: &Scale, axis: &str| {
+        let (start, end) = (
+            scale.range.0.min(scale.range.1),
+            scale.range.0.max(scale.range.1),
+        );
+        let pad = if scale.is_categorical { 0.5 } else { 0.0 };
+        let side = |value: Option<f64>, pad: f64, limit: f64| match value {
+            Some(value) => transform_axis_value(value, scale, axis).map(|v| v + pad),
+            None => Ok(limit),
+        };
+        Ok::<_, anyhow::Error>((
+            side(low, -pad, start)?.max(start),
+            side(high, pad, end)?.min(end),
+        ))
+    };
+    let x = span(band.xmin, band.xmax, &scales.x, "x")?;
+    let y = span(shade.ymin, shade.ymax, &scales.y, "y")?;
+    let (x, y) = if is_flipped { (y, x) } else { (x, y) };
+    Ok(DrawCommand::DrawRect {
+        tl: (x.0, y.1),
+        br: (x.1, y.0),
+        style: BarStyle {
+            color: Some(shade.color.clone().unwrap_or_else(|| "gray".to_string())),
+            alpha: Some(shade.alpha.unwrap_or(0.2)),
+            ..Default::default()
+        },
+        legend: None,
+    })
+}
+
+/// An `annotate()` note's text, with its point and arrow when asked for. A note that
+/// doesn't widen the axes (`expand: false`) is dropped when its anchor falls outside
+/// them, rather than drawn over the axis.
+fn annotation_commands(
+    note: &Annotation,
+    placed: &PanelAnnotation,
+    scales: &PanelScales,
+    is_flipped: bool,
+    (panel_width, panel_height): (usize, usize),
+) -> Result<Vec<DrawCommand>> {
+    let within = |value: f64, scale: &Scale| {
+        let (low, high) = (
+            scale.range.0.min(scale.range.1),
+            scale.range.0.max(scale.range.1),
+        );
+        (low..=high).contains(&value)
+    };
+    let (ax, ay) = (
+        transform_axis_value(placed.x, &scales.x, "x")?,
+        transform_axis_value(placed.y, &scales.y, "y")?,
+    );
+    let inside = within(ax, &scales.x) && within(ay, &scales.y);
+    if !note.expand && !inside {
+        return Ok(Vec::new());
+    }
+
+    let anchor = transform_data_point(placed.x, placed.y, scales, is_flipped)?;
+    let position = transform_data_point(
+        placed.x + note.nudge_x,
+        placed.y + note.nudge_y,
+        scales,
+        is_flipped,
+    )?;
+    let mut commands = Vec::new();
+    if note.point {
+        commands.push(DrawCommand::DrawPoint {
+            points: vec![anchor],
+            style: PointStyle {
+                color: note.color.clone(),
+                ..Default::default()
+            },
+            legend: None,
+        });
+    }
+    if note.arrow {
+        // Start the arrow at the edge of the text's box, estimated in pixels from the
+        // font size, so it points away from the words rather than through them
+        let (across, up) = if is_flipped {
+            (&scales.y, &scales.x)
+        } else {
+            (&scales.x, &scales.y)
+        };
+        let pixels = |scale: &Scale, extent: usize| extent as f64 / (scale.range.1 - scale.range.0);
+        let dx = (anchor.0 - position.0) * pixels(across, panel_width);
+        let dy = (anchor.1 - position.1) * pixels(up, panel_height);
+        let size = note.size.unwrap_or(DEFAULT_TEXT_SIZE);
+        let half_width = 0.3 * size * note.text.chars().count() as f64;
+        let half_height = 0.6 * size;
+        let clearance = (half_width / dx.abs()).min(half_height / dy.abs());
+        if clearance < 1.0 {
+            let start = (
+                position.0 + (anchor.0 - position.0) * clearance,
+                position.1 + (anchor.1 - position.1) * clearance,
+            );
+            commands.push(DrawCommand::DrawLine {
+                points: vec![start, anchor],
+                style: LineStyle {
+                    color: note.color.clone(),
+                    arrow: true,
+                    ..Default::default()
+                },
+                legend: None,
+            });
+        }
+    }
+    commands.push(DrawCommand::DrawText {
+        position,
+        text: note.text.clone(),
+        style: LabelStyle {
+            color: note.color.clone(),
+            size: note.size,
+            alpha: None,
+        },
+        legend: None,
+    });
+    Ok(commands)
+}
+
+/// A grid over a panel's coordinate ranges, one cell per pixel of the panel's share of
+/// the output
+struct PixelGrid {
+    x: (f64, f64),
+    y: (f64, f64),
+    cols: usize,
+    rows: usize,
+}
+
+impl PixelGrid {
+    /// The cell holding `point`; None outside the ranges (or if one has no width)
+    fn cell(&self, (x, y): (f64, f64)) -> Option<usize> {
+        let fraction = |v: f64, (lo, hi): (f64, f64)| (v - lo) / (hi - lo);
+        let (fx, fy) = (fraction(x, self.x), fraction(y, self.y));
+        if !(0.0..=1.0).contains(&fx) || !(0.0..=1.0).contains(&fy) {
+            return None;
+        }
+        let col = ((fx * self.cols as f64) as usize).min(self.cols - 1);
+        let row = ((fy * self.rows as f64) as usize).min(self.rows - 1);
+        Some(row * self.cols + col)
+    }
+}
+
+/// Collapse the points sharing a grid cell into the first of them, with how many
+/// landed there, in order of each cell's first point. Points off the grid are kept one
+/// by one.
+fn bin_points(points: &[(f64, f64)], grid: &PixelGrid) -> Vec<((f64, f64), usize)> {
+    let mut cells = vec![usize::MAX; grid.cols * grid.rows];
+    let mut bins: Vec<((f64, f64), usize)> = Vec::new();
+    for &point in points {
+        match grid.cell(point) {
+            Some(cell) if cells[cell] != usize::MAX => bins[cells[cell]].1 += 1,
+            Some(cell) => {
+                cells[cell] = bins.len();
+                bins.push((point, 1));
+            }
+            None => bins.push((point, 1)),
+        }
+    }
+    bins
+}
+
+/// One group of a binned point layer: each occupied cell is drawn once, at the opacity
+/// its stacked points would have reached (1 - (1 - alpha)^n), lightest first. The legend
+/// entry rides on an empty command with the layer's own style.
+fn binned_point_commands(
+    points: &[(f64, f64)],
+    style: &PointStyle,
+    legend: Option<String>,
+    grid: &PixelGrid,
+) -> Vec<DrawCommand> {
+    let alpha = style.alpha.unwrap_or(1.0);
+    let levels = if alpha > 0.0 && alpha < 1.0 {
+        ((1.0 - BINNED_ALPHA_SATURATION).ln() / (1.0 - alpha).ln())
+            .ceil()
+            .max(1.0) as usize
+    } else {
+        1
+    };
+    let mut by_count: Vec<Vec<(f64, f64)>> = vec![Vec::new(); levels];
+    for (point, count) in bin_points(points, grid) {
+        by_count[count.min(levels) - 1].push(point);
+    }
+
+    let mut commands: Vec<DrawCommand> = by_count
+        .into_iter()
+        .enumerate()
+        .filter(|(_, points)| !points.is_empty())
+        .map(|(i, points)| {
+            let mut level_style = style.clone();
+            if levels > 1 {
+                level_style.alpha = Some(1.0 - (1.0 - alpha).powi(i as i32 + 1));
+            }
+            DrawCommand::DrawPoint {
+                points,
+                style: level_style,
+                legend: None,
+            }
+        })
+        .collect();
+    if legend.is_some() {
+        commands.push(DrawCommand::DrawPoint {
+            points: vec![],
+            style: style.clone(),
+            legend,
+        });
+    }
+    commands
+}
+
+/// Indices of `xs` in ascending order (stable, so tied x keep their data order);
+/// filled bands trace their edges in this order so unsorted input doesn't fold
+/// the polygon over itself
+fn x_order(xs: &[f64]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..xs.len()).collect();
+    order.sort_by(|&a, &b| xs[a].total_cmp(&xs[b]));
+    order
+}
+
+/// Split points into runs, starting a new run at each index in `breaks`
+fn split_at_breaks(points: Vec<(f64, f64)>, breaks: &[usize]) -> Vec<Vec<(f64, f64)>> {
+    if breaks.is_empty() {
+        return vec![points];
+    }
+    let mut runs = vec![Vec::new()];
+    for (i, point) in points.into_iter().enumerate() {
+        if breaks.contains(&i) {
+            runs.push(Vec::new());
+        }
+        runs.last_mut().unwrap().push(point);
+    }
+    runs
+}
+
 fn expand_line_points(
     points: Vec<(f64, f64)>,
     interpolation: LineInterpolation,
@@ -260,10 +519,48 @@ fn reference_line_label(layer: &Layer) -> Option<&str> {
     }
 }
 
+/// "count" when every layer's y is a row count (`bar(stat: "count")`, histograms,
+/// frequency polygons), so charts without a y column still title their value axis
+fn count_axis_label(spec: &ResolvedSpec) -> Option<String> {
+    let all_counts = !spec.layers.is_empty()
+        && spec
+            .layers
+            .iter()
+            .all(|layer| matches!(layer.original_layer.stat(), Stat::Count | Stat::Bin { .. }));
+    all_counts.then(|| "count".to_string())
+}
+
+/// Fraction of a category slot a bar (or a dodged set of bars) fills by default
+pub(crate) const DEFAULT_BAR_WIDTH: f64 = 0.8;
+
 fn x_occupancy_key(x: f64) -> i64 {
     (x * 1_000_000.0).round() as i64
 }
 
+/// Slot width and x offset for `group_idx` among the groups occupying `x`, splitting
+/// `width_ratio` of the category slot evenly; undodged layers have no occupancy and
+/// keep the full width at offset zero
+fn dodge_slot(
+    x_occupancy: &HashMap<i64, Vec<usize>>,
+    x: f64,
+    group_idx: usize,
+    width_ratio: f64,
+) -> (f64, f64) {
+    let rank = x_occupancy.get(&x_occupancy_key(x)).and_then(|occupants| {
+        Some((
+            occupants.iter().position(|&g| g == group_idx)?,
+            occupants.len(),
+        ))
+    });
+    match rank {
+        Some((rank, num_at_x)) => {
+            let slot = width_ratio / num_at_x as f64;
+            (slot, (rank as f64 - (num_at_x as f64 - 1.0) / 2.0) * slot)
+        }
+        None => (width_ratio, 0.0),
+    }
+}
+
 fn rug_side_enabled(sides: &str, short: char, name: &str) -> bool {
     let sides = sides.to_ascii_lowercase();
     sides.contains(short) || sides.split_whitespace().any(|part| part == name)
@@ -306,6 +603,280 @@ fn abline_points(
     ])
 }
 
+/// A legend entry seen while compiling a panel
+struct LegendEntry {
+    layer: usize,
+    /// The layer's legend section: one per grouping column when it groups by several
+    section: usize,
+    /// A continuous size reference bubble (already in value order) rather than a group
+    bubble: bool,
+    /// Position of the label among the layer's group levels (`usize::MAX` if absent)
+    rank: usize,
+    swatch: DrawCommand,
+}
+
+/// Outline of a pie slice on the unit circle: the outer arc from `start` to `end`
+/// (radians clockwise from 12 o'clock), then back along the inner arc at radius
+/// `inner`, or through the centre for a solid pie
+fn wedge_points(start: f64, end: f64, inner: f64) -> Vec<(f64, f64)> {
+    // About one segment per degree keeps large arcs smooth
+    let steps = ((end - start).to_degrees().ceil() as usize).max(1);
+    let arc = |radius: f64| {
+        (0..=steps).map(move |i| {
+            let angle = start + (end - start) * i as f64 / steps as f64;
+            (radius * angle.sin(), radius * angle.cos())
+        })
+    };
+    let mut points: Vec<(f64, f64)> = arc(1.0).collect();
+    if inner > 0.0 {
+        let inner_arc: Vec<(f64, f64)> = arc(inner).collect();
+        points.extend(inner_arc.into_iter().rev());
+    } else {
+        points.push((0.0, 0.0));
+    }
+    points
+}
+
+/// A command that draws nothing but carries `command`'s legend entry and style
+fn legend_swatch(command: &DrawCommand) -> Option<DrawCommand> {
+    let swatch = match command {
+        DrawCommand::DrawLine { style, legend, .. } => DrawCommand::DrawLine {
+            points: vec![],
+            style: style.clone(),
+            legend: Some(legend.clone()?),
+        },
+        DrawCommand::DrawPoint { style, legend, .. } => DrawCommand::DrawPoint {
+            points: vec![],
+            style: style.clone(),
+            legend: Some(legend.clone()?),
+        },
+        // An outlined rect keeps its key as an empty rect so the outline shows
+        DrawCommand::DrawRect { style, legend, .. } if style.border.is_some() => {
+            DrawCommand::DrawRect {
+                tl: (0.0, 0.0),
+                br: (0.0, 0.0),
+                style: style.clone(),
+                legend: Some(legend.clone()?),
+            }
+        }
+        // A polygon's legend key is the same filled square as a rect's
+        DrawCommand::DrawRect { style, legend, .. } => DrawCommand::DrawPolygon {
+            points: vec![],
+            style: RibbonStyle {
+                color: style.color.clone(),
+                alpha: Some(style.alpha.unwrap_or(1.0)),
+            },
+            legend: Some(legend.clone()?),
+        },
+        DrawCommand::DrawPolygon { style, legend, .. } => DrawCommand::DrawPolygon {
+            points: vec![],
+            style: style.clone(),
+            legend: Some(legend.clone()?),
+        },
+        DrawCommand::DrawText {
+            position,
+            style,
+            legend,
+            ..
+        } => DrawCommand::DrawText {
+            position: *position,
+            text: String::new(),
+            style: style.clone(),
+            legend: Some(legend.clone()?),
+        },
+    };
+    Some(swatch)
+}
+
+/// Replace per-panel legends with one legend on the first panel listing every
+/// group in the figure, including groups that first panel lacks. Groups keep
+/// layer order, then the sorted key order their palette was assigned in.
+fn merge_facet_legends(
+    panels: &mut [PanelScene],
+    mut entries: Vec<LegendEntry>,
+    label_order: LabelOrder,
+) {
+    if entries.is_empty() {
+        return;
+    }
+    for command in panels
+        .iter_mut()
+        .flat_map(|panel| panel.commands.iter_mut())
+    {
+        *legend_mut(command) = None;
+    }
+
+    // Stable, so bubbles keep their value order and duplicates their panel order
+    entries.sort_by(|a, b| {
+        (a.layer, a.section, a.bubble, a.rank)
+            .cmp(&(b.layer, b.section, b.bubble, b.rank))
+            .then_with(|| match a.bubble {
+                true => std::cmp::Ordering::Equal,
+                false => label_order.cmp(legend_label(&a.swatch), legend_label(&b.swatch)),
+            })
+    });
+    let mut seen: HashSet<String> = HashSet::new();
+    let first = &mut panels[0].commands;
+    for entry in entries {
+        if seen.insert(legend_label(&entry.swatch).to_string()) {
+            first.push(entry.swatch);
+        }
+    }
+}
+
+fn legend_label(command: &DrawCommand) -> &str {
+    match command {
+        DrawCommand::DrawLine { legend, .. }
+        | DrawCommand::DrawPoint { legend, .. }
+        | DrawCommand::DrawRect { legend, .. }
+        | DrawCommand::DrawPolygon { legend, .. }
+        | DrawCommand::DrawText { legend, .. } => legend.as_deref().unwrap_or_default(),
+    }
+}
+
+fn legend_mut(command: &mut DrawCommand) -> &mut Option<String> {
+    match command {
+        DrawCommand::DrawLine { legend, .. }
+        | DrawCommand::DrawPoint { legend, .. }
+        | DrawCommand::DrawRect { legend, .. }
+        | DrawCommand::DrawPolygon { legend, .. }
+        | DrawCommand::DrawText { legend, .. } => legend,
+    }
+}
+
+/// Whether `swatch`'s primitive shows `aesthetic` (lines have no shape, ...)
+fn swatch_shows(swatch: &DrawCommand, aesthetic: GroupAesthetic) -> bool {
+    matches!(
+        (swatch, aesthetic),
+        (_, GroupAesthetic::Color | GroupAesthetic::Alpha)
+            | (
+                DrawCommand::DrawLine { .. },
+                GroupAesthetic::Size | GroupAesthetic::Linetype
+            )
+            | (
+                DrawCommand::DrawPoint { .. },
+                GroupAesthetic::Size | GroupAesthetic::Shape
+            )
+    )
+}
+
+/// Take the per-group legend entries off a layer grouped by several columns and
+/// build one section per column instead: an entry per value (in `levels` order),
+/// styled like the first group with that value, with the aesthetics the other columns
+/// map reset so each swatch stands for its own column alone. A column whose
+/// aesthetics the layer can't show gets no section, leaving its labels to a layer
+/// that can.
+fn legend_sections(
+    commands: &mut [DrawCommand],
+    key_parts: &HashMap<String, Vec<String>>,
+    group_aesthetics: &[Vec<GroupAesthetic>],
+    levels: &[Vec<String>],
+) -> Vec<Vec<DrawCommand>> {
+    let mut swatches: Vec<(&Vec<String>, DrawCommand)> = Vec::new();
+    for command in commands.iter_mut() {
+        let Some(parts) = legend_mut(command)
+            .as_ref()
+            .and_then(|key| key_parts.get(key))
+        else {
+            continue;
+        };
+        if let Some(swatch) = legend_swatch(command) {
+            swatches.push((parts, swatch));
+        }
+        *legend_mut(command) = None;
+    }
+
+    let Some((_, first)) = swatches.first() else {
+        return Vec::new();
+    };
+    (0..group_aesthetics.len())
+        .filter(|&col| {
+            group_aesthetics[col]
+                .iter()
+                .any(|&aesthetic| swatch_shows(first, aesthetic))
+        })
+        .map(|col| {
+            let reset: Vec<GroupAesthetic> = group_aesthetics
+                .iter()
+                .enumerate()
+                .filter(|&(other, _)| other != col)
+                .flat_map(|(_, aesthetics)| aesthetics.iter().copied())
+                .filter(|aesthetic| !group_aesthetics[col].contains(aesthetic))
+                .collect();
+            levels[col]
+                .iter()
+                .filter_map(|value| {
+                    let (_, swatch) = swatches.iter().find(|(parts, _)| &parts[col] == value)?;
+                    Some(section_swatch(swatch.clone(), value, &reset))
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// `swatch` relabeled with `label`, with the `reset` aesthetics back at their
+/// defaults (gray for color, like size reference bubbles)
+fn section_swatch(mut swatch: DrawCommand, label: &str, reset: &[GroupAesthetic]) -> DrawCommand {
+    let resets = |aesthetic| reset.contains(&aesthetic);
+    let gray = || Some("gray".to_string());
+    match &mut swatch {
+        DrawCommand::DrawLine { style, .. } => {
+            if resets(GroupAesthetic::Color) {
+                style.color = gray();
+            }
+            if resets(GroupAesthetic::Size) {
+                style.width = None;
+            }
+            if resets(GroupAesthetic::Linetype) {
+                style.linetype = None;
+            }
+            if resets(GroupAesthetic::Alpha) {
+                style.alpha = None;
+            }
+        }
+        DrawCommand::DrawPoint { style, .. } => {
+            if resets(GroupAesthetic::Color) {
+                style.color = gray();
+            }
+            if resets(GroupAesthetic::Size) {
+                style.size = None;
+            }
+            if resets(GroupAesthetic::Shape) {
+                style.shape = None;
+            }
+            if resets(GroupAesthetic::Alpha) {
+                style.alpha = None;
+            }
+        }
+        DrawCommand::DrawRect { style, .. } => {
+            if resets(GroupAesthetic::Color) {
+                style.color = gray();
+            }
+            if resets(GroupAesthetic::Alpha) {
+                style.alpha = None;
+            }
+        }
+        DrawCommand::DrawPolygon { style, .. } => {
+            if resets(GroupAesthetic::Color) {
+                style.color = gray();
+            }
+            if resets(GroupAesthetic::Alpha) {
+                style.alpha = None;
+            }
+        }
+        DrawCommand::DrawText { style, .. } => {
+            if resets(GroupAesthetic::Color) {
+                style.color = gray();
+            }
+            if resets(GroupAesthetic::Alpha) {
+                style.alpha = None;
+            }
+        }
+    }
+    *legend_mut(&mut swatch) = Some(label.to_string());
+    swatch
+}
+
 /// Compile data and scales into a SceneGraph of drawing commands
 pub fn compile_geometry(
     data: RenderData,
@@ -315,29 +886,118 @@ pub fn compile_geometry(
 ) -> Result<SceneGraph> {
     let mut panels = Vec::new();
     let is_flipped = matches!(spec.coord, Some(crate::parser::ast::CoordSystem::Flip));
+    let n_panels = data.panels.len();
+    // Count-only plots title the count axis (horizontal when flipped) unless labs() does
+    let (x_label, y_label) = match count_axis_label(spec) {
+        Some(label) if is_flipped => (spec.labels.x.clone().or(Some(label)), spec.labels.y.clone()),
+        Some(label) => (spec.labels.x.clone(), spec.labels.y.clone().or(Some(label))),
+        None => (spec.labels.x.clone(), spec.labels.y.clone()),
+    };
+    // Shared (fixed) axes only label the outer panels, like ggplot2; free ones label each
+    let (free_x, free_y) = match spec.facet.as_ref().map(|f| &f.scales) {
+        Some(FacetScales::FreeX) => (true, false),
+        Some(FacetScales::FreeY) => (false, true),
+        Some(FacetScales::Free) => (true, true),
+        Some(FacetScales::Fixed) | None => (false, false),
+    };
+    // The flipped horizontal axis shows y
+    let (mut free_horizontal, mut free_vertical) = if is_flipped {
+        (free_y, free_x)
+    } else {
+        (free_x, free_y)
+    };
+    // A grid frees x per column and y per row, so unflipped axes always line up
+    // along the outer panels
+    let grid = spec.facet.as_ref().and_then(|f| f.grid.as_ref());
+    if grid.is_some() && !is_flipped {
+        (free_horizontal, free_vertical) = (false, false);
+    }
+    // Every panel's legend entries, for the single legend of a faceted figure
+    let mut legend_entries: Vec<LegendEntry> = Vec::new();
+    let aspect_ratio = match spec.coord {
+        Some(crate::parser::ast::CoordSystem::Fixed { ratio }) => Some(ratio),
+        _ => None,
+    };
+    // Pie slices are drawn on the unit circle, without axes
+    let polar = spec
+        .layers
+        .iter()
+        .any(|layer| matches!(layer.original_layer, Layer::Pie(_)));
+
+    // Point layers with more points (over every panel) than `max_exact_points` are
+    // binned onto each panel's pixel grid
+    let binned_layers: Vec<bool> = spec
+        .layers
+        .iter()
+        .enumerate()
+        .map(|(i, layer)| {
+            let total: usize = data
+                .panels
+                .iter()
+                .flat_map(|panel| &panel.layers[i].groups)
+                .map(|group| group.x.len())
+                .sum();
+            matches!(&layer.original_layer, Layer::Point(p) if p.fast)
+                && options.max_exact_points > 0
+                && total > options.max_exact_points
+        })
+        .collect();
+    let (panel_width, panel_height) = (
+        (options.width as usize / data.facet_layout.ncol.max(1)).max(1),
+        (options.height as usize / data.facet_layout.nrow.max(1)).max(1),
+    );
 
     // Iterate panels (zipped with scales)
-    for (panel_data, panel_scales) in data.panels.into_iter().zip(scales.panels.into_iter()) {
+    for (panel_data, panel_scales) in data.panels.into_iter().zip(scales.panels) {
         let mut commands = Vec::new();
         let mut emitted_legend_keys: HashSet<String> = HashSet::new();
+        let mut colorbar: Option<ColorBar> = None;
 
         // Iterate layers
-        for (layer_idx, layer_data) in panel_data.layers.into_iter().enumerate() {
+        for (layer_idx, mut layer_data) in panel_data.layers.into_iter().enumerate() {
+            let layer_start = commands.len();
             // Retrieve original layer spec for metadata (position, etc.)
             let layer_spec = &spec.layers[layer_idx];
 
             // Determine if this layer has a meaningful grouping aesthetic
+            // (continuous color is shown by a colorbar, continuous size by reference bubbles)
             let layer_aes = &spec.layers[layer_idx].aesthetics;
-            let has_grouping = layer_aes.color.is_some()
-                || layer_aes.size.is_some()
+            let has_grouping = (layer_aes.color.is_some() && layer_data.color_domain.is_none())
+                || (layer_aes.size.is_some() && layer_data.size_domain.is_none())
                 || layer_aes.shape.is_some()
+                || layer_aes.linetype.is_some()
                 || layer_aes.alpha.is_some();
 
+            let color_gradient = match layer_data.color_domain {
+                Some((min, max)) => {
+                    let gradient = color_scale_gradient(&spec.color_scale)?;
+                    if colorbar.is_none() {
+                        colorbar = Some(ColorBar {
+                            title: layer_aes.color.clone(),
+                            min,
+                            max,
+                            colors: gradient.sample(COLORBAR_STEPS),
+                        });
+                    }
+                    Some(gradient)
+                }
+                None => None,
+            };
+
             // Handle Positioning Logic
-            let (_is_bar, position) = match &layer_spec.original_layer {
+            // Points and lines only dodge on categorical x; on a continuous axis there
+            // are no category slots to share, so the request is a no-op
+            let categorical_x = layer_data.groups.iter().all(|g| g.x_categories.is_some());
+            let (is_bar, position) = match &layer_spec.original_layer {
                 Layer::Bar(b) => (true, b.position.clone()),
                 Layer::Boxplot(_) => (true, BarPosition::Dodge),
                 Layer::Violin(_) => (true, BarPosition::Dodge),
+                Layer::Point(p) if p.position == PointPosition::Dodge && categorical_x => {
+                    (false, BarPosition::Dodge)
+                }
+                Layer::Line(l) if l.position == LinePosition::Dodge && categorical_x => {
+                    (false, BarPosition::Dodge)
+                }
                 _ => (false, BarPosition::Identity),
             };
 
@@ -359,20 +1019,55 @@ pub fn compile_geometry(
                     groups_at_x.sort();
                     groups_at_x.dedup(); // Handle multiple points per group at same X (if any)
                 }
+
+                // Dodged points and lines move to the centre of their group's slot, using
+                // the default bar width so they line up with dodged bars
+                if !is_bar {
+                    for (g_idx, group) in layer_data.groups.iter_mut().enumerate() {
+                        for x in &mut group.x {
+                            *x += dodge_slot(&x_occupancy, *x, g_idx, DEFAULT_BAR_WIDTH).1;
+                        }
+                    }
+                }
             }
 
+            // A layer grouped by several columns gets a legend section per column
+            // instead of an entry per group
+            let key_parts: Option<HashMap<String, Vec<String>>> =
+                (layer_data.group_aesthetics.len() > 1).then(|| {
+                    layer_data
+                        .groups
+                        .iter()
+                        .map(|group| (group.key.clone(), group.key_parts.clone()))
+                        .collect()
+                });
+
+            let size_palette = SizePalette::default_range();
+            // Reference bubbles reuse the first group's styling so they match the points
+            let size_legend = match (layer_data.size_domain, layer_data.groups.first()) {
+                (Some(domain), Some(group)) => match &group.style {
+                    RenderStyle::Point(style) => Some((domain, style.clone())),
+                    _ => None,
+                },
+                _ => None,
+            };
+
             for (group_idx, group) in layer_data.groups.into_iter().enumerate() {
                 match &group.style {
                     RenderStyle::Line(style) => {
-                        let points = match &layer_spec.original_layer {
-                            Layer::HLine(_) => hline_points(group.y[0], &panel_scales, is_flipped)?,
-                            Layer::VLine(_) => vline_points(group.x[0], &panel_scales, is_flipped)?,
-                            Layer::AbLine(abline) => abline_points(
+                        let segments = match &layer_spec.original_layer {
+                            Layer::HLine(_) => {
+                                vec![hline_points(group.y[0], &panel_scales, is_flipped)?]
+                            }
+                            Layer::VLine(_) => {
+                                vec![vline_points(group.x[0], &panel_scales, is_flipped)?]
+                            }
+                            Layer::AbLine(abline) => vec![abline_points(
                                 abline.slope,
                                 abline.intercept,
                                 &panel_scales,
                                 is_flipped,
-                            )?,
+                            )?],
                             _ => {
                                 let raw_points: Vec<(f64, f64)> = group
                                     .x
@@ -380,38 +1075,40 @@ pub fn compile_geometry(
                                     .zip(group.y.iter())
                                     .map(|(&x, &y)| (x, y))
                                     .collect();
-                                let raw_points = expand_line_points(
-                                    raw_points,
-                                    line_interpolation(&layer_spec.original_layer),
-                                );
-                                raw_points
+                                // Skipped (missing) rows break the line instead of bridging the gap
+                                split_at_breaks(raw_points, &group.line_breaks)
                                     .into_iter()
-                                    .map(|(x, y)| {
-                                        transform_data_point(x, y, &panel_scales, is_flipped)
+                                    .map(|run| {
+                                        expand_line_points(
+                                            run,
+                                            line_interpolation(&layer_spec.original_layer),
+                                        )
+                                        .into_iter()
+                                        .map(|(x, y)| {
+                                            transform_data_point(x, y, &panel_scales, is_flipped)
+                                        })
+                                        .collect::<Result<Vec<_>>>()
                                     })
                                     .collect::<Result<Vec<_>>>()?
                             }
                         };
-                        let legend = if matches!(
-                            &layer_spec.original_layer,
-                            Layer::HLine(_)
-                                | Layer::VLine(_)
-                                | Layer::AbLine(_)
-                                | Layer::Segment(_)
-                        ) {
+                        let legend = if has_grouping && !layer_spec.original_layer.is_reference() {
+                            Some(group.key.clone())
+                                .filter(|key| emitted_legend_keys.insert(key.clone()))
+                        } else {
+                            // Reference lines (and ungrouped data segments) show their label:
                             reference_line_label(&layer_spec.original_layer)
                                 .filter(|label| emitted_legend_keys.insert((*label).to_string()))
                                 .map(ToString::to_string)
-                        } else if has_grouping && emitted_legend_keys.insert(group.key.clone()) {
-                            Some(group.key.clone())
-                        } else {
-                            None
                         };
-                        commands.push(DrawCommand::DrawLine {
-                            points,
-                            style: style.clone(),
-                            legend,
-                        });
+                        let mut legend = legend;
+                        for points in segments {
+                            commands.push(DrawCommand::DrawLine {
+                                points,
+                                style: style.clone(),
+                                legend: legend.take(),
+                            });
+                        }
                     }
                     RenderStyle::LineRange(style) => {
                         for i in 0..group.x.len() {
@@ -723,6 +1420,74 @@ pub fn compile_geometry(
                             });
                         }
                     }
+                    RenderStyle::Point(style)
+                        if !group.point_alphas.is_empty()
+                            || !group.color_values.is_empty()
+                            || !group.size_values.is_empty() =>
+                    {
+                        // Recency fade / continuous color / continuous size: one DrawPoint per
+                        // point so each carries its own alpha, color and size. Faint points are
+                        // emitted first so emphasized ones land on top (otherwise large bubbles
+                        // go first so small ones stay visible), and the single legend entry is
+                        // attached to the last point drawn.
+                        let mut order: Vec<usize> = (0..group.x.len()).collect();
+                        if !group.point_alphas.is_empty() {
+                            order.sort_by(|&a, &b| {
+                                group.point_alphas[a]
+                                    .partial_cmp(&group.point_alphas[b])
+                                    .unwrap_or(std::cmp::Ordering::Equal)
+                            });
+                        } else if !group.size_values.is_empty() {
+                            order.sort_by(|&a, &b| {
+                                group.size_values[b]
+                                    .partial_cmp(&group.size_values[a])
+                                    .unwrap_or(std::cmp::Ordering::Equal)
+                            });
+                        }
+                        let legend_idx = order.last().copied();
+                        let mut legend =
+                            if has_grouping && emitted_legend_keys.insert(group.key.clone()) {
+                                Some(group.key.clone())
+                            } else {
+                                None
+                            };
+                        let base_alpha = style.alpha.unwrap_or(1.0);
+
+                        for i in order {
+                            let mut point_style = style.clone();
+                            if let Some(&point_alpha) = group.point_alphas.get(i) {
+                                point_style.alpha = Some(base_alpha * point_alpha);
+                            }
+                            if let (Some(gradient), Some(domain), Some(&value)) = (
+                                &color_gradient,
+                                layer_data.color_domain,
+                                group.color_values.get(i),
+                            ) {
+                                point_style.color =
+                                    Some(gradient.color_at(normalize_in_domain(value, domain)));
+                            }
+                            if let (Some(domain), Some(&value)) =
+                                (layer_data.size_domain, group.size_values.get(i))
+                            {
+                                point_style.size =
+                                    Some(size_palette.size_at(normalize_in_domain(value, domain)));
+                            }
+                            commands.push(DrawCommand::DrawPoint {
+                                points: vec![transform_data_point(
+                                    group.x[i],
+                                    group.y[i],
+                                    &panel_scales,
+                                    is_flipped,
+                                )?],
+                                style: point_style,
+                                legend: if Some(i) == legend_idx {
+                                    legend.take()
+                                } else {
+                                    None
+                                },
+                            });
+                        }
+                    }
                     RenderStyle::Point(style) => {
                         let points: Vec<(f64, f64)> = group
                             .x
@@ -730,19 +1495,35 @@ pub fn compile_geometry(
                             .zip(group.y.iter())
                             .map(|(&x, &y)| transform_data_point(x, y, &panel_scales, is_flipped))
                             .collect::<Result<Vec<_>>>()?;
-                        commands.push(DrawCommand::DrawPoint {
-                            points,
-                            style: style.clone(),
-                            legend: if has_grouping && emitted_legend_keys.insert(group.key.clone())
-                            {
+                        let legend =
+                            if has_grouping && emitted_legend_keys.insert(group.key.clone()) {
                                 Some(group.key.clone())
                             } else {
                                 None
-                            },
-                        });
+                            };
+                        if binned_layers[layer_idx] {
+                            let (x_scale, y_scale) = if is_flipped {
+                                (&panel_scales.y, &panel_scales.x)
+                            } else {
+                                (&panel_scales.x, &panel_scales.y)
+                            };
+                            let grid = PixelGrid {
+                                x: x_scale.range,
+                                y: y_scale.range,
+                                cols: panel_width,
+                                rows: panel_height,
+                            };
+                            commands.extend(binned_point_commands(&points, style, legend, &grid));
+                        } else {
+                            commands.push(DrawCommand::DrawPoint {
+                                points,
+                                style: style.clone(),
+                                legend,
+                            });
+                        }
                     }
                     RenderStyle::Bar(style) => {
-                        let bar_width_ratio = style.width.unwrap_or(0.8);
+                        let bar_width_ratio = style.width.unwrap_or(DEFAULT_BAR_WIDTH);
 
                         for i in 0..group.x.len() {
                             let x_center = group.x[i];
@@ -750,26 +1531,8 @@ pub fn compile_geometry(
                             let y_bottom = group.y_start[i];
 
                             // Calculate Dodge Offset for this specific point
-                            let (slot_width, x_offset) = if matches!(position, BarPosition::Dodge) {
-                                let key = x_occupancy_key(x_center);
-                                if let Some(occupants) = x_occupancy.get(&key) {
-                                    let num_at_x = occupants.len();
-                                    if let Some(rank) =
-                                        occupants.iter().position(|&g| g == group_idx)
-                                    {
-                                        let slot = bar_width_ratio / num_at_x as f64;
-                                        let offset =
-                                            (rank as f64 - (num_at_x as f64 - 1.0) / 2.0) * slot;
-                                        (slot, offset)
-                                    } else {
-                                        (bar_width_ratio, 0.0) // Should not happen
-                                    }
-                                } else {
-                                    (bar_width_ratio, 0.0)
-                                }
-                            } else {
-                                (bar_width_ratio, 0.0)
-                            };
+                            let (slot_width, x_offset) =
+                                dodge_slot(&x_occupancy, x_center, group_idx, bar_width_ratio);
 
                             let x_final = x_center + x_offset;
                             let half_width = slot_width / 2.0;
@@ -804,26 +1567,8 @@ pub fn compile_geometry(
                             let x_center = group.x[i];
 
                             // Calculate Dodge Offset for this specific point
-                            let (slot_width, x_offset) = if matches!(position, BarPosition::Dodge) {
-                                let key = x_occupancy_key(x_center);
-                                if let Some(occupants) = x_occupancy.get(&key) {
-                                    let num_at_x = occupants.len();
-                                    if let Some(rank) =
-                                        occupants.iter().position(|&g| g == group_idx)
-                                    {
-                                        let slot = width_ratio / num_at_x as f64;
-                                        let offset =
-                                            (rank as f64 - (num_at_x as f64 - 1.0) / 2.0) * slot;
-                                        (slot, offset)
-                                    } else {
-                                        (width_ratio, 0.0)
-                                    }
-                                } else {
-                                    (width_ratio, 0.0)
-                                }
-                            } else {
-                                (width_ratio, 0.0)
-                            };
+                            let (slot_width, x_offset) =
+                                dodge_slot(&x_occupancy, x_center, group_idx, width_ratio);
 
                             let x_final = x_center + x_offset;
                             let is_vertical = !is_flipped;
@@ -935,18 +1680,19 @@ pub fn compile_geometry(
                         }
                     }
                     RenderStyle::Ribbon(style) => {
-                        // Construct Polygon: Trace y_max forward, then y_min backward
+                        // Construct Polygon: Trace y_max forward along x, then y_min backward
+                        let order = x_order(&group.x);
                         let mut points = Vec::with_capacity(group.x.len() * 2);
 
                         // Forward pass: y_max
-                        for i in 0..group.x.len() {
+                        for &i in &order {
                             let x = group.x[i];
                             let y = group.y_max[i];
                             points.push(transform_data_point(x, y, &panel_scales, is_flipped)?);
                         }
 
                         // Backward pass: y_min
-                        for i in (0..group.x.len()).rev() {
+                        for &i in order.iter().rev() {
                             let x = group.x[i];
                             let y = group.y_min[i];
                             points.push(transform_data_point(x, y, &panel_scales, is_flipped)?);
@@ -964,15 +1710,16 @@ pub fn compile_geometry(
                         });
                     }
                     RenderStyle::Area(style) => {
+                        let order = x_order(&group.x);
                         let mut points = Vec::with_capacity(group.x.len() * 2);
 
-                        for i in 0..group.x.len() {
+                        for &i in &order {
                             let x = group.x[i];
                             let y = group.y[i];
                             points.push(transform_data_point(x, y, &panel_scales, is_flipped)?);
                         }
 
-                        for i in (0..group.x.len()).rev() {
+                        for &i in order.iter().rev() {
                             let x = group.x[i];
                             let y = group.y_start[i];
                             points.push(transform_data_point(x, y, &panel_scales, is_flipped)?);
@@ -990,48 +1737,56 @@ pub fn compile_geometry(
                         });
                     }
                     RenderStyle::Density(style) => {
-                        // Density: filled area + outline line
-                        // Build polygon from (x, 0) to (x, density)
-                        let mut polygon_points = Vec::with_capacity(group.x.len() * 2);
-
-                        // Forward pass: top of density curve
-                        for i in 0..group.x.len() {
-                            let x = group.x[i];
-                            let y = group.y[i];
-                            polygon_points.push(transform_data_point(
-                                x,
-                                y,
-                                &panel_scales,
-                                is_flipped,
-                            )?);
-                        }
-
-                        // Backward pass: baseline (y = 0)
-                        for i in (0..group.x.len()).rev() {
-                            let x = group.x[i];
-                            let y = group.y_start[i]; // 0.0
-                            polygon_points.push(transform_data_point(
-                                x,
-                                y,
-                                &panel_scales,
-                                is_flipped,
-                            )?);
-                        }
-
-                        // Draw filled area
-                        commands.push(DrawCommand::DrawPolygon {
-                            points: polygon_points,
-                            style: RibbonStyle {
-                                color: style.color.clone(),
-                                alpha: style.alpha.or(Some(0.3)),
-                            },
-                            legend: if has_grouping && emitted_legend_keys.insert(group.key.clone())
-                            {
+                        // Density: filled area (unless fill: false) + outline line; the
+                        // legend entry goes on whichever is drawn first
+                        let legend =
+                            if has_grouping && emitted_legend_keys.insert(group.key.clone()) {
                                 Some(group.key.clone())
                             } else {
                                 None
-                            },
-                        });
+                            };
+
+                        // Draw filled area
+                        let outline_legend = if style.fill {
+                            // Build polygon from (x, 0) to (x, density)
+                            let mut polygon_points = Vec::with_capacity(group.x.len() * 2);
+
+                            // Forward pass: top of density curve
+                            for i in 0..group.x.len() {
+                                let x = group.x[i];
+                                let y = group.y[i];
+                                polygon_points.push(transform_data_point(
+                                    x,
+                                    y,
+                                    &panel_scales,
+                                    is_flipped,
+                                )?);
+                            }
+
+                            // Backward pass: baseline (y = 0)
+                            for i in (0..group.x.len()).rev() {
+                                let x = group.x[i];
+                                let y = group.y_start[i]; // 0.0
+                                polygon_points.push(transform_data_point(
+                                    x,
+                                    y,
+                                    &panel_scales,
+                                    is_flipped,
+                                )?);
+                            }
+
+                            commands.push(DrawCommand::DrawPolygon {
+                                points: polygon_points,
+                                style: RibbonStyle {
+                                    color: style.color.clone(),
+                                    alpha: style.alpha.or(Some(0.3)),
+                                },
+                                legend,
+                            });
+                            None
+                        } else {
+                            legend
+                        };
 
                         // Draw outline
                         let line_points: Vec<(f64, f64)> = group
@@ -1045,7 +1800,74 @@ pub fn compile_geometry(
                             style: LineStyle {
                                 color: style.color.clone(),
                                 width: Some(2.0),
-                                alpha: Some(1.0),
+                                // Without a fill, alpha applies to the curve itself
+                                alpha: if style.fill {
+                                    Some(1.0)
+                                } else {
+                                    style.alpha.or(Some(1.0))
+                                },
+                                linetype: None,
+                                arrow: false,
+                                simplify: false,
+                            },
+                            legend: outline_legend,
+                        });
+                    }
+                    RenderStyle::Text {
+                        style,
+                        nudge_x,
+                        nudge_y,
+                    } => {
+                        // Text: one DrawText per labeled point, nudged in data units
+                        for (i, text) in group.labels.iter().enumerate() {
+                            let position = transform_data_point(
+                                group.x[i] + nudge_x,
+                                group.y[i] + nudge_y,
+                                &panel_scales,
+                                is_flipped,
+                            )?;
+                            commands.push(DrawCommand::DrawText {
+                                position,
+                                text: text.clone(),
+                                style: style.clone(),
+                                legend: if i == 0
+                                    && has_grouping
+                                    && emitted_legend_keys.insert(group.key.clone())
+                                {
+                                    Some(group.key.clone())
+                                } else {
+                                    None
+                                },
+                            });
+                        }
+                    }
+                    RenderStyle::Pie {
+                        style,
+                        start_angle,
+                        end_angle,
+                        inner_radius,
+                    } => {
+                        // Each slice is its own legend entry, in slice order, and a thin
+                        // white edge parts it from its neighbours
+                        let points = wedge_points(*start_angle, *end_angle, *inner_radius);
+                        let mut edge = points.clone();
+                        edge.push(points[0]);
+                        commands.push(DrawCommand::DrawPolygon {
+                            points,
+                            style: style.clone(),
+                            legend: emitted_legend_keys
+                                .insert(group.key.clone())
+                                .then(|| group.key.clone()),
+                        });
+                        commands.push(DrawCommand::DrawLine {
+                            points: edge,
+                            style: LineStyle {
+                                color: Some("white".to_string()),
+                                width: Some(1.5),
+                                alpha: None,
+                                linetype: None,
+                                arrow: false,
+                                simplify: false,
                             },
                             legend: None,
                         });
@@ -1061,6 +1883,28 @@ pub fn compile_geometry(
                         } else {
                             1.0
                         };
+                        let gradient = ColorGradient::viridis();
+
+                        // One colorbar per panel; widen it if several tile groups share the panel
+                        match colorbar.as_mut() {
+                            Some(bar) => {
+                                bar.min = bar.min.min(val_min);
+                                bar.max = bar.max.max(val_max);
+                            }
+                            None => {
+                                colorbar = Some(ColorBar {
+                                    title: Some(
+                                        layer_aes
+                                            .fill
+                                            .clone()
+                                            .unwrap_or_else(|| "count".to_string()),
+                                    ),
+                                    min: val_min,
+                                    max: val_max,
+                                    colors: gradient.sample(COLORBAR_STEPS),
+                                });
+                            }
+                        }
 
                         for i in 0..group.x.len() {
                             let x_center = group.x[i];
@@ -1071,7 +1915,7 @@ pub fn compile_geometry(
                             let t = ((fill_val - val_min) / val_range).clamp(0.0, 1.0);
 
                             // Map to viridis-like color gradient
-                            let color_str = value_to_heatmap_color(t);
+                            let color_str = gradient.color_at(t);
 
                             let half_w = cell_w / 2.0;
                             let half_h = cell_h / 2.0;
@@ -1089,6 +1933,8 @@ pub fn compile_geometry(
                                     color: Some(color_str),
                                     alpha: style.alpha.or(Some(1.0)),
                                     width: None,
+                                    border: None,
+                                    border_width: None,
                                 },
                                 legend: None,
                             });
@@ -1102,26 +1948,8 @@ pub fn compile_geometry(
                             let x_center = group.x[i];
 
                             // Calculate Dodge Offset (same as boxplot)
-                            let (slot_width, x_offset) = if matches!(position, BarPosition::Dodge) {
-                                let key = x_occupancy_key(x_center);
-                                if let Some(occupants) = x_occupancy.get(&key) {
-                                    let num_at_x = occupants.len();
-                                    if let Some(rank) =
-                                        occupants.iter().position(|&g| g == group_idx)
-                                    {
-                                        let slot = width_ratio / num_at_x as f64;
-                                        let offset =
-                                            (rank as f64 - (num_at_x as f64 - 1.0) / 2.0) * slot;
-                                        (slot, offset)
-                                    } else {
-                                        (width_ratio, 0.0)
-                                    }
-                                } else {
-                                    (width_ratio, 0.0)
-                                }
-                            } else {
-                                (width_ratio, 0.0)
-                            };
+                            let (slot_width, x_offset) =
+                                dodge_slot(&x_occupancy, x_center, group_idx, width_ratio);
 
                             let x_final = x_center + x_offset;
                             let half_width = slot_width / 2.0;
@@ -1265,6 +2093,9 @@ pub fn compile_geometry(
                                         color: Some("white".to_string()),
                                         width: Some(1.5),
                                         alpha: Some(0.9),
+                                        linetype: None,
+                                        arrow: false,
+                                        simplify: false,
                                     },
                                     legend: None,
                                 });
@@ -1273,45 +2104,194 @@ pub fn compile_geometry(
                     }
                 }
             }
+
+            let mut section_starts = Vec::new();
+            if let Some(key_parts) = key_parts {
+                let sections = legend_sections(
+                    &mut commands[layer_start..],
+                    &key_parts,
+                    &layer_data.group_aesthetics,
+                    &layer_data.group_levels,
+                );
+                // Combined keys never label an entry, so later layers may use them
+                for key in key_parts.keys() {
+                    emitted_legend_keys.remove(key);
+                }
+                for section in sections {
+                    section_starts.push(commands.len());
+                    commands.extend(
+                        section.into_iter().filter(|swatch| {
+                            emitted_legend_keys.insert(legend_label(swatch).into())
+                        }),
+                    );
+                }
+            }
+
+            // Continuous size legend: reference bubbles at nice values within the domain.
+            // A DrawPoint with no points only contributes its legend entry.
+            let bubbles_start = commands.len();
+            if let Some((domain, style)) = size_legend {
+                let mut values =
+                    crate::scale::nice_ticks_within(domain.0, domain.1, SIZE_LEGEND_INTERVALS);
+                if values.len() < 2 {
+                    values = vec![domain.0, domain.1];
+                }
+                for value in values {
+                    let label = crate::scale::format_nice_number(value);
+                    if !emitted_legend_keys.insert(label.clone()) {
+                        continue;
+                    }
+                    let mut bubble_style = style.clone();
+                    bubble_style.size =
+                        Some(size_palette.size_at(normalize_in_domain(value, domain)));
+                    // Mapped colors vary per point, so neutral bubbles stand for size alone
+                    if layer_aes.color.is_some() {
+                        bubble_style.color = Some("gray".to_string());
+                    }
+                    commands.push(DrawCommand::DrawPoint {
+                        points: vec![],
+                        style: bubble_style,
+                        legend: Some(label),
+                    });
+                }
+            }
+
+            for (i, command) in commands[layer_start..].iter().enumerate() {
+                if let Some(swatch) = legend_swatch(command) {
+                    let label = legend_label(&swatch);
+                    let rank = layer_data
+                        .group_levels
+                        .iter()
+                        .flatten()
+                        .position(|level| level == label)
+                        .unwrap_or(usize::MAX);
+                    legend_entries.push(LegendEntry {
+                        layer: layer_idx,
+                        section: section_starts
+                            .iter()
+                            .filter(|&&start| start <= layer_start + i)
+                            .count(),
+                        bubble: layer_start + i >= bubbles_start,
+                        rank,
+                        swatch,
+                    });
+                }
+            }
+        }
+
+        // A facet whose rows were all dropped (or a facet_grid combination with none)
+        // keeps its title and axes, and says why it is blank
+        if commands.is_empty() && n_panels > 1 {
+            let (across, up) = if is_flipped {
+                (&panel_scales.y, &panel_scales.x)
+            } else {
+                (&panel_scales.x, &panel_scales.y)
+            };
+            let middle = |scale: &Scale| (scale.range.0 + scale.range.1) / 2.0;
+            commands.push(DrawCommand::DrawText {
+                position: (middle(across), middle(up)),
+                text: "No data".to_string(),
+                style: LabelStyle {
+                    color: Some("#808080".to_string()),
+                    size: Some(13.0),
+                    alpha: None,
+                },
+                legend: None,
+            });
         }
 
-        // Determine Panel Title
-        let title = data
-            .facet_layout
-            .panel_titles
-            .get(panel_data.index)
-            .cloned()
-            .filter(|s| !s.is_empty())
-            .map(|s| format!("{} = {}", spec.facet.as_ref().unwrap().col, s));
+        // shade() bands go under everything, even the grid (see `PanelScene::underlay`)
+        let underlay = panel_data
+            .shades
+            .iter()
+            .map(|band| shade_command(&spec.shades[band.index], band, &panel_scales, is_flipped))
+            .collect::<Result<Vec<_>>>()?;
+        let underlay_len = underlay.len();
+        commands.splice(0..0, underlay);
+
+        // annotate() notes sit over every layer
+        for placed in &panel_data.annotations {
+            commands.extend(annotation_commands(
+                &spec.annotations[placed.index],
+                placed,
+                &panel_scales,
+                is_flipped,
+                (panel_width, panel_height),
+            )?);
+        }
 
         // Determine Row/Col
         let row = panel_data.index / data.facet_layout.ncol;
         let col = panel_data.index % data.facet_layout.ncol;
 
+        // Determine Panel Title; a grid labels columns along the top row and rows
+        // down the right-hand column
+        let facet_label = |titles: &[String], column: Option<&String>| {
+            titles
+                .get(panel_data.index)
+                .filter(|s| !s.is_empty())
+                .zip(column)
+                .zip(spec.facet.as_ref())
+                .map(|((s, column), facet)| facet.labeller.label(column, s))
+        };
+        let (title, row_title) = match grid {
+            Some(grid) => (
+                facet_label(&data.facet_layout.panel_titles, grid.cols.as_ref())
+                    .filter(|_| row == 0),
+                facet_label(&data.facet_layout.row_titles, grid.rows.as_ref())
+                    .filter(|_| col + 1 == data.facet_layout.ncol),
+            ),
+            None => (
+                facet_label(
+                    &data.facet_layout.panel_titles,
+                    spec.facet.as_ref().map(|f| &f.col),
+                ),
+                None,
+            ),
+        };
+
         let (x_scale, y_scale) = if is_flipped {
             (panel_scales.y, panel_scales.x)
         } else {
             (panel_scales.x, panel_scales.y)
         };
 
+        // A panel with no panel beneath it (bottom row, or above a gap in a short
+        // last row) keeps its horizontal tick labels
+        let has_panel_below = panel_data.index + data.facet_layout.ncol < n_panels;
+
         panels.push(PanelScene {
             row,
             col,
             title,
-            x_label: spec.labels.x.clone(),
-            y_label: spec.labels.y.clone(),
+            row_title,
+            x_label: x_label.clone(),
+            y_label: y_label.clone(),
             x_scale,
             y_scale,
             commands,
+            colorbar,
+            show_x_tick_labels: free_horizontal || !has_panel_below,
+            show_y_tick_labels: free_vertical || col == 0,
+            polar,
+            aspect_ratio,
+            underlay: underlay_len,
         });
     }
 
+    if panels.len() > 1 {
+        merge_facet_legends(&mut panels, legend_entries, options.label_order);
+    }
+
     Ok(SceneGraph {
         width: options.width,
         height: options.height,
         panels,
         labels: spec.labels.clone(),
         theme: spec.theme.clone(),
+        facet_label_size: spec.facet.as_ref().and_then(|f| f.label_size),
+        nrow: data.facet_layout.nrow,
+        ncol: data.facet_layout.ncol,
     })
 }
 
@@ -1329,9 +2309,12 @@ mod tests {
         let render_data = RenderData {
             panels: vec![PanelData {
                 index: 0,
+                annotations: Vec::new(),
+                shades: Vec::new(),
                 layers: vec![LayerData {
                     groups: vec![GroupData {
                         key: "A".to_string(),
+                        key_parts: vec!["A".to_string()],
                         x: vec![0.0, 1.0],
                         y: vec![10.0, 20.0],
                         y_start: vec![0.0, 0.0],
@@ -1350,15 +2333,31 @@ mod tests {
                         heatmap_cell_height: 0.0,
                         x_categories: None,
                         y_categories: None,
+                        labels: vec![],
+                        point_alphas: vec![],
+                        color_values: vec![],
+                        size_values: vec![],
+                        line_breaks: vec![],
                         style: RenderStyle::Line(LineStyle::default()),
                     }],
+                    color_domain: None,
+                    size_domain: None,
+                    skipped_rows: 0,
+                    filter_rejected_rows: 0,
+                    filter_matched_rows: None,
+                    group_aesthetics: vec![vec![GroupAesthetic::Color]],
+                    group_levels: vec![vec!["A".to_string()]],
                 }],
             }],
             facet_layout: FacetLayout {
                 nrow: 1,
                 ncol: 1,
                 panel_titles: vec![],
+                row_titles: vec![],
+                panel_rows: vec![],
             },
+            filter_rejected_rows: 0,
+            row_limits: Vec::new(),
         };
 
         let scales = ScaleSystem {
@@ -1371,6 +2370,7 @@ mod tests {
                     tick_positions: vec![],
                     datetime: None,
                     transform: AxisTransform::Linear,
+                    labels: Default::default(),
                 },
                 y: Scale {
                     domain: (0.0, 20.0),
@@ -1380,6 +2380,7 @@ mod tests {
                     tick_positions: vec![],
                     datetime: None,
                     transform: AxisTransform::Linear,
+                    labels: Default::default(),
                 },
             }],
         };
@@ -1392,11 +2393,16 @@ mod tests {
                     y_col: Some("y".to_string()),
                     ymin_col: None,
                     ymax_col: None,
+                    xend_col: None,
+                    yend_col: None,
                     color: None,
                     size: None,
                     shape: None,
                     alpha: None,
                     fill: None,
+                    linetype: None,
+                    group: Vec::new(),
+                    label: None,
                 },
             }],
             facet: None,
@@ -1405,6 +2411,11 @@ mod tests {
             theme: crate::parser::ast::Theme::default(),
             x_scale_spec: None,
             y_scale_spec: None,
+            color_scale: crate::parser::ast::ColorScale::default(),
+            color_manual: None,
+            stages: Vec::new(),
+            annotations: Vec::new(),
+            shades: Vec::new(),
         };
 
         (render_data, scales, spec)
@@ -1427,4 +2438,1062 @@ mod tests {
             panic!("Expected DrawLine");
         }
     }
+
+    #[test]
+    fn test_compile_line_breaks_at_gaps() {
+        let (mut data, scales, spec) = make_test_data();
+        let group = &mut data.panels[0].layers[0].groups[0];
+        group.x = vec![0.0, 0.25, 0.5, 1.0];
+        group.y = vec![10.0, 12.0, 15.0, 20.0];
+        group.line_breaks = vec![2];
+        let scene = compile_geometry(data, scales, &spec, &RenderOptions::default()).unwrap();
+
+        let lines: Vec<usize> = scene.panels[0]
+            .commands
+            .iter()
+            .filter_map(|cmd| match cmd {
+                DrawCommand::DrawLine { points, .. } => Some(points.len()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(lines, vec![2, 2]);
+    }
+
+    fn compile_dsl(dsl: &str, csv: &str) -> SceneGraph {
+        compile_dsl_with(dsl, csv, &RenderOptions::default())
+    }
+
+    fn compile_dsl_with(dsl: &str, csv: &str, options: &RenderOptions) -> SceneGraph {
+        let mut lines = csv.lines();
+        let headers = lines
+            .next()
+            .unwrap()
+            .split(',')
+            .map(str::to_string)
+            .collect();
+        let rows = lines
+            .map(|line| line.split(',').map(str::to_string).collect())
+            .collect();
+        let data = crate::data::PlotData { headers, rows };
+        let (_, spec) = crate::parser::parse_plot_spec(dsl).unwrap();
+        let resolved = crate::resolve::resolve_plot_aesthetics(&spec, &data).unwrap();
+        let render_data =
+            crate::transform::apply_transformations(&resolved, &data, options).unwrap();
+        let scales = crate::scale::build_scales(&render_data, &resolved).unwrap();
+        compile_geometry(render_data, scales, &resolved, options).unwrap()
+    }
+
+    #[test]
+    fn test_compile_y_list_draws_a_line_per_column() {
+        let scene = compile_dsl(
+            "aes(x: time, y: [temp, humidity, pressure]) | line()",
+            "time,temp,humidity,pressure\n1,20,50,30\n2,22,55,31\n3,21,52,29\n",
+        );
+        let lines: Vec<_> = scene.panels[0]
+            .commands
+            .iter()
+            .filter_map(|cmd| match cmd {
+                DrawCommand::DrawLine {
+                    points,
+                    style,
+                    legend,
+                } => Some((points.len(), style.color.clone(), legend.clone())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines.iter().all(|(points, _, _)| *points == 3));
+        // One legend entry per column, in the order listed, each its own palette color
+        let legends: Vec<_> = lines.iter().map(|(_, _, legend)| legend.clone()).collect();
+        assert_eq!(
+            legends,
+            vec![
+                Some("temp".to_string()),
+                Some("humidity".to_string()),
+                Some("pressure".to_string())
+            ]
+        );
+        assert!(lines[0].1 != lines[1].1 && lines[1].1 != lines[2].1);
+    }
+
+    #[test]
+    fn test_compile_y_list_leaves_the_columns_to_other_layers() {
+        // The data's own value column survives the pivot, and line(y: temp) draws once
+        let scene = compile_dsl(
+            "aes(x: time, y: [temp, humidity]) | point() | line(y: temp) | text(y: value, label: value)",
+            "time,temp,humidity,value\n1,20,50,9\n2,22,55,8\n3,21,52,7\n",
+        );
+        let commands = &scene.panels[0].commands;
+        let lines: Vec<usize> = commands
+            .iter()
+            .filter_map(|cmd| match cmd {
+                DrawCommand::DrawLine { points, .. } => Some(points.len()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(lines, vec![3]);
+        let points: usize = commands
+            .iter()
+            .filter_map(|cmd| match cmd {
+                DrawCommand::DrawPoint { points, .. } => Some(points.len()),
+                _ => None,
+            })
+            .sum();
+        assert_eq!(points, 6);
+        let labels = commands
+            .iter()
+            .filter(|cmd| matches!(cmd, DrawCommand::DrawText { .. }))
+            .count();
+        assert_eq!(labels, 3);
+    }
+
+    #[test]
+    fn test_compile_pie_draws_a_labeled_wedge_per_slice() {
+        let scene = compile_dsl(
+            "aes(x: fruit, y: n) | pie(donut: 0.5)",
+            "fruit,n\napple,3\npear,1\n",
+        );
+        let commands = &scene.panels[0].commands;
+        assert!(scene.panels[0].polar);
+        // Each slice is a filled polygon followed by a white edge around it
+        assert_eq!(commands.len(), 4);
+        let polygons: Vec<_> = commands
+            .iter()
+            .filter_map(|cmd| match cmd {
+                DrawCommand::DrawPolygon { points, legend, .. } => Some((points, legend.clone())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            polygons
+                .iter()
+                .map(|(_, legend)| legend.clone())
+                .collect::<Vec<Option<String>>>(),
+            vec![Some("apple".to_string()), Some("pear".to_string())]
+        );
+        assert!(matches!(commands[1], DrawCommand::DrawLine { .. }));
+        // Apple starts at 12 o'clock on the outer edge and ends at 9 o'clock
+        let apple: &Vec<(f64, f64)> = polygons[0].0;
+        assert!((apple[0].0).abs() < 1e-9 && (apple[0].1 - 1.0).abs() < 1e-9);
+        assert!(apple
+            .iter()
+            .any(|&(x, y)| (x + 1.0).abs() < 1e-9 && y.abs() < 1e-9));
+        // The hole leaves nothing inside half the radius
+        assert!(apple
+            .iter()
+            .all(|&(x, y)| (x * x + y * y).sqrt() > 0.5 - 1e-9));
+    }
+
+    #[test]
+    fn test_compile_mapped_segments_draw_one_line_per_row() {
+        let scene = compile_dsl(
+            "aes(x: x0, y: y0) | segment(xend: x1, yend: y1, color: g, arrow: true)",
+            "x0,y0,x1,y1,g\n0,0,1,1,a\n1,0,2,2,a\n0,3,1,4,b\n",
+        );
+        let lines: Vec<(usize, bool, Option<String>)> = scene.panels[0]
+            .commands
+            .iter()
+            .filter_map(|cmd| match cmd {
+                DrawCommand::DrawLine {
+                    points,
+                    style,
+                    legend,
+                } => Some((points.len(), style.arrow, legend.clone())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                (2, true, Some("a".to_string())),
+                (2, true, None),
+                (2, true, Some("b".to_string())),
+            ]
+        );
+
+        // An ungrouped data segment keeps its label:
+        let scene = compile_dsl(
+            r#"segment(x: x0, y: y0, xend: x1, yend: y1, label: "Moves")"#,
+            "x0,y0,x1,y1\n0,0,1,1\n1,0,2,2\n",
+        );
+        assert_eq!(
+            legends(&scene.panels[0]),
+            vec![("Moves".to_string(), false)]
+        );
+    }
+
+    #[test]
+    fn test_compile_ribbon_traces_edges_in_x_order() {
+        // Rows arrive out of x order; the ribbon sits under the line in layer order
+        let scene = compile_dsl(
+            "aes(x: x) | ribbon(ymin: lo, ymax: hi, alpha: 0.2) | line(y: y)",
+            "x,y,lo,hi\n3,5,4,6\n1,2,1,3\n2,3,2,5\n",
+        );
+        let commands = &scene.panels[0].commands;
+        let DrawCommand::DrawPolygon { points, .. } = &commands[0] else {
+            panic!("Expected the ribbon first, got {:?}", commands[0]);
+        };
+        assert!(matches!(commands[1], DrawCommand::DrawLine { .. }));
+
+        let xs: Vec<f64> = points.iter().map(|p| p.0).collect();
+        let upper = &xs[..3];
+        let lower = &xs[3..];
+        assert!(upper.windows(2).all(|w| w[0] < w[1]), "{:?}", xs);
+        assert!(lower.windows(2).all(|w| w[0] > w[1]), "{:?}", xs);
+    }
+
+    fn legends(panel: &PanelScene) -> Vec<(String, bool)> {
+        panel
+            .commands
+            .iter()
+            .filter_map(|cmd| match cmd {
+                DrawCommand::DrawLine { points, legend, .. }
+                | DrawCommand::DrawPoint { points, legend, .. }
+                | DrawCommand::DrawPolygon { points, legend, .. } => {
+                    legend.clone().map(|l| (l, points.is_empty()))
+                }
+                DrawCommand::DrawRect { legend, .. } => legend.clone().map(|l| (l, false)),
+                DrawCommand::DrawText { legend, .. } => legend.clone().map(|l| (l, true)),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_compile_facets_share_one_legend() {
+        // "c" only appears in the second panel, "a" only in the first
+        let scene = compile_dsl(
+            "aes(x: x, y: y, color: g) | line() | point() | facet_wrap(by: f)",
+            "x,y,g,f\n1,1,b,p\n2,2,b,p\n1,3,a,p\n1,2,c,q\n2,1,c,q\n1,5,b,q\n",
+        );
+        assert_eq!(scene.panels.len(), 2);
+        // Every group, in key order, as drawing-free swatches on the first panel
+        assert_eq!(
+            legends(&scene.panels[0]),
+            vec![
+                ("a".to_string(), true),
+                ("b".to_string(), true),
+                ("c".to_string(), true)
+            ]
+        );
+        assert!(legends(&scene.panels[1]).is_empty());
+
+        // A single panel keeps its entries on the drawn series
+        let scene = compile_dsl(
+            "aes(x: x, y: y, color: g) | line()",
+            "x,y,g\n1,1,b\n2,2,b\n1,3,a\n2,3,a\n",
+        );
+        assert_eq!(
+            legends(&scene.panels[0]),
+            vec![("a".to_string(), false), ("b".to_string(), false)]
+        );
+    }
+
+    #[test]
+    fn test_compile_bar_border_reaches_rects_and_legends() {
+        let scene = compile_dsl(
+            r#"aes(x: x, y: y, color: g) | bar(border: "black", border_width: 2) | facet_wrap(by: f)"#,
+            "x,y,g,f\nA,1,a,p\nB,2,b,p\nA,3,c,q\n",
+        );
+        let rects: Vec<(bool, Option<String>, Option<f64>, bool)> = scene
+            .panels
+            .iter()
+            .flat_map(|panel| panel.commands.iter())
+            .filter_map(|cmd| match cmd {
+                DrawCommand::DrawRect {
+                    tl,
+                    br,
+                    style,
+                    legend,
+                } => Some((
+                    tl == br,
+                    style.border.clone(),
+                    style.border_width,
+                    legend.is_some(),
+                )),
+                _ => None,
+            })
+            .collect();
+        let border = (Some("black".to_string()), Some(2.0));
+        // Three bars, then the merged legend's three outlined swatches
+        assert_eq!(rects.len(), 6);
+        assert!(rects.iter().all(|r| (r.1.clone(), r.2) == border));
+        assert_eq!(rects.iter().filter(|r| r.0 && r.3).count(), 3);
+    }
+
+    #[test]
+    fn test_compile_fixed_color_overrides_inherited_mapping() {
+        let csv = "x,y,depth,site\n1,1,10,a\n2,2,20,a\n3,3,30,b\n4,4,40,b\n";
+        let points = |scene: &SceneGraph| -> Vec<(Option<String>, Option<f64>, bool)> {
+            scene.panels[0]
+                .commands
+                .iter()
+                .filter_map(|cmd| match cmd {
+                    DrawCommand::DrawPoint {
+                        points,
+                        style,
+                        legend,
+                        ..
+                    } if !points.is_empty() => {
+                        Some((style.color.clone(), style.size, legend.is_some()))
+                    }
+                    _ => None,
+                })
+                .collect()
+        };
+
+        // A continuous color is overridden too, so there is no gradient
+        let scene = compile_dsl(
+            r#"aes(x: x, y: y, color: depth) | point(color: "red")"#,
+            csv,
+        );
+        let drawn = points(&scene);
+        assert_eq!(drawn.len(), 4);
+        assert!(drawn
+            .iter()
+            .all(|(color, ..)| color.as_deref() == Some("red")));
+        assert!(legends(&scene.panels[0]).is_empty());
+
+        // Lines keep one series per site in the fixed color; the legend comes from the
+        // points, which map site to size in red
+        let scene = compile_dsl(
+            r#"aes(x: x, y: y, color: site) | line(color: "red") | point(color: "red", size: site)"#,
+            csv,
+        );
+        let lines: Vec<Option<String>> = scene.panels[0]
+            .commands
+            .iter()
+            .filter_map(|cmd| match cmd {
+                DrawCommand::DrawLine { points, style, .. } if !points.is_empty() => {
+                    Some(style.color.clone())
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(lines, vec![Some("red".to_string()); 2]);
+        let drawn = points(&scene);
+        assert!(drawn
+            .iter()
+            .all(|(color, ..)| color.as_deref() == Some("red")));
+        let sizes: HashSet<_> = drawn
+            .iter()
+            .map(|(_, size, _)| size.map(f64::to_bits))
+            .collect();
+        assert_eq!(sizes.len(), 2);
+        let labels: Vec<String> = legends(&scene.panels[0])
+            .into_iter()
+            .map(|(l, _)| l)
+            .collect();
+        assert_eq!(labels, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_compile_color_levels_order_dodges_and_legends() {
+        let csv = "x,y,g,k\nA,1,Other,u\nA,2,gamma,v\nA,4,beta,u\nB,8,gamma,v\nB,3,beta,v\n";
+        let labels = |scene: &SceneGraph| -> Vec<String> {
+            legends(&scene.panels[0])
+                .into_iter()
+                .map(|(l, _)| l)
+                .collect()
+        };
+        let levels = r#"scale_color(levels: "gamma", "beta", "Other")"#;
+
+        // Dodge slots run left to right in level order
+        let scene = compile_dsl(
+            &format!(
+                r#"aes(x: x, y: y, color: g) | bar(position: "dodge") | {}"#,
+                levels
+            ),
+            csv,
+        );
+        let mut lefts: Vec<(f64, &str)> = scene.panels[0]
+            .commands
+            .iter()
+            .filter_map(|cmd| match cmd {
+                DrawCommand::DrawRect {
+                    tl,
+                    legend: Some(l),
+                    ..
+                } => Some((tl.0, l.as_str())),
+                _ => None,
+            })
+            .collect();
+        lefts.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let order: Vec<&str> = lefts.iter().map(|(_, l)| *l).collect();
+        assert_eq!(order, vec!["gamma", "beta", "Other"]);
+        assert_eq!(labels(&scene), vec!["gamma", "beta", "Other"]);
+
+        // Facet legends merge in the same order
+        let scene = compile_dsl(
+            &format!(
+                "aes(x: x, y: y, color: g) | point() | facet_wrap(by: x) | {}",
+                levels
+            ),
+            csv,
+        );
+        assert_eq!(labels(&scene), vec!["gamma", "beta", "Other"]);
+
+        // With a second grouping column, only the color section is reordered
+        let scene = compile_dsl(
+            r#"aes(x: x, y: y, color: g, shape: k) | point() | scale_color(order: "appearance")"#,
+            csv,
+        );
+        assert_eq!(labels(&scene), vec!["Other", "gamma", "beta", "u", "v"]);
+    }
+
+    #[test]
+    fn test_compile_group_draws_lines_without_legend() {
+        let mut csv = String::from("time,value,sensor,kind\n");
+        for sensor in 0..50 {
+            for time in 0..4 {
+                let kind = if sensor < 25 { "low" } else { "high" };
+                csv.push_str(&format!(
+                    "{},{},s{},{}\n",
+                    time,
+                    sensor * time,
+                    sensor,
+                    kind
+                ));
+            }
+        }
+        let lines = |scene: &SceneGraph| -> Vec<Option<String>> {
+            scene.panels[0]
+                .commands
+                .iter()
+                .filter_map(|cmd| match cmd {
+                    DrawCommand::DrawLine { points, style, .. } if !points.is_empty() => {
+                        Some(style.color.clone())
+                    }
+                    _ => None,
+                })
+                .collect()
+        };
+
+        let scene = compile_dsl("aes(x: time, y: value, group: sensor) | line()", &csv);
+        let colors = lines(&scene);
+        assert_eq!(colors.len(), 50);
+        assert!(colors.iter().all(|color| *color == colors[0]));
+        assert!(legends(&scene.panels[0]).is_empty());
+
+        // The legend shows the styling column only, once per level
+        let scene = compile_dsl(
+            "aes(x: time, y: value, color: kind, group: sensor) | line()",
+            &csv,
+        );
+        assert_eq!(lines(&scene).len(), 50);
+        let labels: Vec<String> = legends(&scene.panels[0])
+            .into_iter()
+            .map(|(l, _)| l)
+            .collect();
+        assert_eq!(labels, vec!["high", "low"]);
+    }
+
+    #[test]
+    fn test_compile_legend_has_a_section_per_grouping_column() {
+        let csv =
+            "a,b,region,product\n1,2,north,widget\n2,3,north,gadget\n1,5,south,widget\n2,6,south,gadget\n";
+        let scene = compile_dsl(
+            "aes(x: a, y: b, color: region, shape: product) | point()",
+            csv,
+        );
+        let commands = &scene.panels[0].commands;
+        let swatches: Vec<(String, Option<String>, Option<String>)> = commands
+            .iter()
+            .filter_map(|cmd| match cmd {
+                DrawCommand::DrawPoint { style, legend, .. } => legend
+                    .clone()
+                    .map(|l| (l, style.color.clone(), style.shape.clone())),
+                _ => None,
+            })
+            .collect();
+        let labels: Vec<&str> = swatches.iter().map(|(l, ..)| l.as_str()).collect();
+        assert_eq!(labels, vec!["north", "south", "gadget", "widget"]);
+        // Region swatches keep their color at the default shape; product swatches keep
+        // their shape in gray
+        assert!(swatches[..2]
+            .iter()
+            .all(|(_, c, s)| c.as_deref() != Some("gray") && s.is_none()));
+        assert!(swatches[2..]
+            .iter()
+            .all(|(_, c, s)| c.as_deref() == Some("gray") && s.is_some()));
+        // The groups themselves draw without entries
+        assert_eq!(
+            legends(&scene.panels[0])
+                .iter()
+                .filter(|(_, empty)| !empty)
+                .count(),
+            0
+        );
+
+        // Facets keep the sections apart rather than sorting all labels together
+        let scene = compile_dsl(
+            "aes(x: a, y: b, color: region, shape: product) | point() | facet_wrap(by: region)",
+            csv,
+        );
+        let labels: Vec<String> = legends(&scene.panels[0])
+            .into_iter()
+            .map(|(l, _)| l)
+            .collect();
+        assert_eq!(labels, vec!["north", "south", "gadget", "widget"]);
+
+        // Lines can't show shape, so the products' entries come from the points
+        let scene = compile_dsl(
+            "aes(x: a, y: b, color: region, shape: product) | line() | point()",
+            csv,
+        );
+        let entries: Vec<(&str, bool)> = scene.panels[0]
+            .commands
+            .iter()
+            .filter_map(|cmd| match cmd {
+                DrawCommand::DrawLine { legend, .. } => legend.as_deref().map(|l| (l, true)),
+                DrawCommand::DrawPoint { legend, .. } => legend.as_deref().map(|l| (l, false)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            entries,
+            vec![
+                ("north", true),
+                ("south", true),
+                ("gadget", false),
+                ("widget", false)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compile_facet_tick_labels_follow_shared_axes() {
+        let csv = "x,y,f\n1,1,a\n2,2,b\n3,3,c\n";
+        let flags = |facet: &str| -> Vec<(bool, bool)> {
+            compile_dsl(&format!("aes(x: x, y: y) | point() | {}", facet), csv)
+                .panels
+                .iter()
+                .map(|p| (p.show_x_tick_labels, p.show_y_tick_labels))
+                .collect()
+        };
+        // a b
+        // c
+        // "b" has no panel below it, so it keeps its x labels
+        assert_eq!(
+            flags("facet_wrap(by: f, ncol: 2)"),
+            vec![(false, true), (true, false), (true, true)]
+        );
+        assert_eq!(
+            flags(r#"facet_wrap(by: f, ncol: 2, scales: "free_x")"#),
+            vec![(true, true), (true, false), (true, true)]
+        );
+        assert_eq!(
+            flags(r#"facet_wrap(by: f, ncol: 2, scales: "free")"#),
+            vec![(true, true); 3]
+        );
+        // Flipped, the free data x axis is the vertical one
+        assert_eq!(
+            flags(r#"facet_wrap(by: f, ncol: 2, scales: "free_x") | coord_flip()"#),
+            vec![(false, true), (true, true), (true, true)]
+        );
+    }
+
+    #[test]
+    fn test_compile_facet_grid_labels_outer_strips() {
+        let csv = "x,y,r,c\n1,1,lo,a\n2,2,lo,b\n1,3,hi,a\n";
+        let scene = compile_dsl(
+            r#"aes(x: x, y: y) | point() | facet_grid(rows: r, cols: c, scales: "free")"#,
+            csv,
+        );
+        let strips: Vec<_> = scene
+            .panels
+            .iter()
+            .map(|p| (p.row, p.col, p.title.as_deref(), p.row_title.as_deref()))
+            .collect();
+        // Column labels along the top, row labels down the right; (hi, b) has no data
+        // but keeps its place in the grid
+        assert_eq!(
+            strips,
+            vec![
+                (0, 0, Some("c = a"), None),
+                (0, 1, Some("c = b"), Some("r = hi")),
+                (1, 0, None, None),
+                (1, 1, None, Some("r = lo")),
+            ]
+        );
+        // Grid columns share x and rows share y, so even free scales only label the
+        // outer panels
+        let flags: Vec<_> = scene
+            .panels
+            .iter()
+            .map(|p| (p.show_x_tick_labels, p.show_y_tick_labels))
+            .collect();
+        assert_eq!(
+            flags,
+            vec![(false, true), (false, false), (true, true), (true, false)]
+        );
+    }
+
+    #[test]
+    fn test_compile_empty_facet_keeps_its_panel() {
+        // Facet "b" has rows, but none with a usable y
+        let csv = "x,y,g\n1,2,a\n2,4,a\n1,NA,b\n2,,b\n1,5,c\n2,3,c\n";
+        let scene = compile_dsl(
+            r#"aes(x: x, y: y) | line() | point() | facet_wrap(by: g, ncol: 4, scales: "free_y")"#,
+            csv,
+        );
+        assert_eq!(scene.panels.len(), 3);
+        // The grid keeps the requested four columns; the fourth cell has no panel
+        assert_eq!((scene.nrow, scene.ncol), (1, 4));
+
+        let empty = &scene.panels[1];
+        assert_eq!(empty.title.as_deref(), Some("g = b"));
+        assert_eq!(empty.x_scale.range, scene.panels[0].x_scale.range);
+        match empty.commands.as_slice() {
+            [DrawCommand::DrawText {
+                position,
+                text,
+                legend: None,
+                ..
+            }] => {
+                assert_eq!(text, "No data");
+                let middle = |s: &Scale| (s.range.0 + s.range.1) / 2.0;
+                assert_eq!(*position, (middle(&empty.x_scale), middle(&empty.y_scale)));
+            }
+            other => panic!("expected only the note, got {:?}", other),
+        }
+        // Panels with data get no note
+        assert!(!scene.panels[0]
+            .commands
+            .iter()
+            .any(|c| matches!(c, DrawCommand::DrawText { text, .. } if text == "No data")));
+    }
+
+    #[test]
+    fn test_compile_facet_labellers() {
+        let csv = "x,y,region,year\n1,1,North,2023\n2,2,South,2024\n";
+        let titles = |facet: &str| -> Vec<(Option<String>, Option<String>)> {
+            compile_dsl(&format!("aes(x: x, y: y) | point() | {}", facet), csv)
+                .panels
+                .into_iter()
+                .map(|p| (p.title, p.row_title))
+                .collect()
+        };
+        let wrap = |labeller: &str| -> Vec<String> {
+            titles(&format!("facet_wrap(by: region{})", labeller))
+                .into_iter()
+                .filter_map(|(title, _)| title)
+                .collect()
+        };
+        assert_eq!(wrap(""), ["region = North", "region = South"]);
+        assert_eq!(wrap(r#", labeller: "value""#), ["North", "South"]);
+        assert_eq!(
+            wrap(r#", labeller: "both""#),
+            ["region: North", "region: South"]
+        );
+        assert_eq!(
+            wrap(r#", labeller: "Region {value}""#),
+            ["Region North", "Region South"]
+        );
+        assert_eq!(
+            wrap(r#", labeller: "{value} ({column})""#),
+            ["North (region)", "South (region)"]
+        );
+
+        // The grid labels both strips the same way
+        let grid = titles(r#"facet_grid(rows: region, cols: year, labeller: "value")"#);
+        assert_eq!(grid[0], (Some("2023".to_string()), None));
+        assert_eq!(
+            grid[1],
+            (Some("2024".to_string()), Some("North".to_string()))
+        );
+        assert_eq!(grid[3].1.as_deref(), Some("South"));
+
+        // label_size reaches the renderer
+        let scene = compile_dsl(
+            "aes(x: x, y: y) | point() | facet_wrap(by: region, label_size: 11)",
+            csv,
+        );
+        assert_eq!(scene.facet_label_size, Some(11.0));
+    }
+
+    #[test]
+    fn test_compile_reversed_axes_keep_data_coordinates() {
+        let csv = "x,y,g\n1,10,a\n2,30,a\n3,20,a\n1,5,b\n2,15,b\n3,-8,b\n";
+        for layers in [
+            "line()",
+            "point()",
+            r#"bar(position: "stack")"#,
+            r#"bar(position: "dodge")"#,
+        ] {
+            let dsl = format!("aes(x: x, y: y, color: g) | {layers}");
+            let plain = compile_dsl(&dsl, csv);
+            let reversed = compile_dsl(&format!("{dsl} | scale_y(reverse: true)"), csv);
+            let (plain, reversed) = (&plain.panels[0], &reversed.panels[0]);
+            let (lo, hi) = plain.y_scale.range;
+            assert_eq!(reversed.y_scale.range, (hi, lo), "{layers}");
+            assert_eq!(
+                reversed.y_scale.tick_positions, plain.y_scale.tick_positions,
+                "{layers}"
+            );
+            // Bars still run from zero to their value, and stacks keep their order;
+            // only the axis direction flips
+            assert_eq!(
+                format!("{:?}", reversed.commands),
+                format!("{:?}", plain.commands),
+                "{layers}"
+            );
+        }
+
+        // Fixed facets share the reversed axis
+        let scene = compile_dsl(
+            "aes(x: x, y: y) | point() | facet_wrap(by: g) | scale_x(reverse: true)",
+            csv,
+        );
+        assert!(scene
+            .panels
+            .iter()
+            .all(|p| p.x_scale.range == scene.panels[0].x_scale.range
+                && p.x_scale.range.0 > p.x_scale.range.1));
+    }
+
+    fn point_xs(panel: &PanelScene) -> Vec<f64> {
+        panel
+            .commands
+            .iter()
+            .filter_map(|c| match c {
+                DrawCommand::DrawPoint { points, .. } => Some(points.iter().map(|p| p.0)),
+                _ => None,
+            })
+            .flatten()
+            .collect()
+    }
+
+    #[test]
+    fn test_compile_dodged_points_line_up_with_dodged_bars() {
+        let csv = "q,v,g\nQ1,10,a\nQ1,30,b\nQ2,5,a\nQ2,8,b\n";
+        let scene = compile_dsl(
+            r#"aes(x: q, y: v, color: g) | bar(position: "dodge") | point(position: "dodge")"#,
+            csv,
+        );
+        let panel = &scene.panels[0];
+        let bar_centers: Vec<f64> = panel
+            .commands
+            .iter()
+            .filter_map(|c| match c {
+                DrawCommand::DrawRect { tl, br, .. } => Some((tl.0 + br.0) / 2.0),
+                _ => None,
+            })
+            .collect();
+        let points = point_xs(panel);
+        assert_eq!(points.len(), 4);
+        for (point, bar) in points.iter().zip(&bar_centers) {
+            assert!((point - bar).abs() < 1e-9, "{} vs {}", point, bar);
+        }
+        // Groups sit on either side of the category centre: 0 -/+ 0.2
+        assert!((points[0] + 0.2).abs() < 1e-9 && (points[2] - 0.2).abs() < 1e-9);
+
+        // Dodged lines follow the same offsets
+        let scene = compile_dsl(
+            r#"aes(x: q, y: v, color: g) | line(position: "dodge")"#,
+            csv,
+        );
+        let line_xs: Vec<f64> = scene.panels[0]
+            .commands
+            .iter()
+            .filter_map(|c| match c {
+                DrawCommand::DrawLine { points, .. } => Some(points.iter().map(|p| p.0)),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+        assert_eq!(line_xs.len(), 4);
+        assert!((line_xs[0] + 0.2).abs() < 1e-9 && (line_xs[3] - 1.2).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_compile_dodge_is_a_no_op_on_continuous_x() {
+        let csv = "x,y,g\n1,10,a\n1,30,b\n2,5,a\n2,8,b\n";
+        let dodged = compile_dsl(
+            r#"aes(x: x, y: y, color: g) | point(position: "dodge")"#,
+            csv,
+        );
+        let plain = compile_dsl("aes(x: x, y: y, color: g) | point()", csv);
+        assert_eq!(point_xs(&dodged.panels[0]), point_xs(&plain.panels[0]));
+    }
+
+    #[test]
+    fn test_compile_count_plots_title_the_count_axis() {
+        let csv = "x,g\nA,p\nA,q\nB,p\n";
+        let labels = |dsl: &str| {
+            let scene = compile_dsl(dsl, csv);
+            (
+                scene.panels[0].x_label.clone(),
+                scene.panels[0].y_label.clone(),
+            )
+        };
+        assert_eq!(
+            labels(r#"aes(x: x) | bar(stat: "count")"#),
+            (None, Some("count".to_string()))
+        );
+        assert_eq!(
+            labels(r#"aes(x: x, color: g) | bar(stat: "count", position: "dodge") | coord_flip()"#),
+            (Some("count".to_string()), None)
+        );
+        assert_eq!(
+            labels(r#"aes(x: x) | bar(stat: "count") | labs(y: "Rows")"#),
+            (None, Some("Rows".to_string()))
+        );
+        // Layers with real y values keep the axis untitled
+        let csv = "x,y\nA,1\nB,2\n";
+        let scene = compile_dsl(r#"aes(x: x, y: y) | bar()"#, csv);
+        assert_eq!(scene.panels[0].y_label, None);
+    }
+
+    #[test]
+    fn test_bin_points_keeps_the_first_point_per_cell() {
+        let grid = PixelGrid {
+            x: (0.0, 10.0),
+            y: (0.0, 10.0),
+            cols: 10,
+            rows: 10,
+        };
+        let points = [
+            (0.1, 0.1),
+            (5.5, 5.5),
+            (0.5, 0.9),
+            (20.0, 1.0),
+            (0.2, 0.2),
+            (10.0, 10.0),
+        ];
+        assert_eq!(
+            bin_points(&points, &grid),
+            vec![
+                ((0.1, 0.1), 3),
+                ((5.5, 5.5), 1),
+                ((20.0, 1.0), 1),
+                ((10.0, 10.0), 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_binned_points_shade_by_count() {
+        let grid = PixelGrid {
+            x: (0.0, 10.0),
+            y: (0.0, 10.0),
+            cols: 10,
+            rows: 10,
+        };
+        let style = PointStyle {
+            alpha: Some(0.5),
+            ..Default::default()
+        };
+        let points = [(1.5, 1.5), (7.1, 7.1), (7.2, 7.2), (7.3, 7.3)];
+        let drawn: Vec<_> = binned_point_commands(&points, &style, Some("a".to_string()), &grid)
+            .into_iter()
+            .map(|command| match command {
+                DrawCommand::DrawPoint {
+                    points,
+                    style,
+                    legend,
+                } => (points, style.alpha, legend),
+                other => panic!("Expected DrawPoint, got {:?}", other),
+            })
+            .collect();
+        // Three stacked half-transparent points reach 0.875; the legend keeps 0.5
+        assert_eq!(
+            drawn,
+            vec![
+                (vec![(1.5, 1.5)], Some(0.5), None),
+                (vec![(7.1, 7.1)], Some(0.875), None),
+                (vec![], Some(0.5), Some("a".to_string())),
+            ]
+        );
+
+        // Opaque points need no shading
+        let commands = binned_point_commands(&points, &PointStyle::default(), None, &grid);
+        assert_eq!(commands.len(), 1);
+    }
+
+    #[test]
+    fn test_compile_bins_point_layers_over_max_exact_points() {
+        let mut csv = String::from("x,y\n");
+        for i in 0..40 {
+            // Two clusters, each narrower than a pixel
+            let base = if i % 2 == 0 { 0.0 } else { 100.0 };
+            csv.push_str(&format!("{},{}\n", base + i as f64 * 1e-4, base));
+        }
+        let drawn = |dsl: &str, max_exact_points: usize| -> usize {
+            let options = RenderOptions {
+                max_exact_points,
+                ..RenderOptions::default()
+            };
+            compile_dsl_with(dsl, &csv, &options).panels[0]
+                .commands
+                .iter()
+                .map(|command| match command {
+                    DrawCommand::DrawPoint { points, .. } => points.len(),
+                    _ => 0,
+                })
+                .sum()
+        };
+
+        assert_eq!(drawn("aes(x: x, y: y) | point()", 10), 2);
+        assert_eq!(drawn("aes(x: x, y: y) | jitter(width: 0)", 10), 2);
+        // Small layers, `fast: false`, and a 0 threshold draw every point
+        assert_eq!(drawn("aes(x: x, y: y) | point()", 40), 40);
+        assert_eq!(drawn("aes(x: x, y: y) | point(fast: false)", 10), 40);
+        assert_eq!(drawn("aes(x: x, y: y) | point()", 0), 40);
+    }
+
+    fn annotation_texts(scene: &SceneGraph) -> Vec<Vec<(String, (f64, f64))>> {
+        scene
+            .panels
+            .iter()
+            .map(|panel| {
+                panel
+                    .commands
+                    .iter()
+                    .filter_map(|command| match command {
+                        DrawCommand::DrawText { text, position, .. } => {
+                            Some((text.clone(), *position))
+                        }
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_compile_annotation_expands_or_clips() {
+        let csv = "x,y\n1,10\n2,20\n3,15\n";
+        let scene = compile_dsl(
+            r#"aes(x: x, y: y) | line() | annotate(x: 2, y: 95, text: "policy change", color: "red")"#,
+            csv,
+        );
+        let panel = &scene.panels[0];
+        assert!(panel.y_scale.range.1 >= 95.0);
+        // Drawn last, over the line
+        assert!(matches!(
+            panel.commands.last(),
+            Some(DrawCommand::DrawText { text, position: (x, y), style, .. })
+                if text == "policy change" && *x == 2.0 && *y == 95.0
+                    && style.color.as_deref() == Some("red")
+        ));
+
+        // expand: false leaves the axes to the data and drops a note past them
+        let scene = compile_dsl(
+            r#"aes(x: x, y: y) | line() | annotate(x: 2, y: 95, text: "off", expand: false) | annotate(x: 2, y: 12, text: "on", expand: false)"#,
+            csv,
+        );
+        assert!(scene.panels[0].y_scale.range.1 < 95.0);
+        assert_eq!(
+            annotation_texts(&scene)[0],
+            vec![("on".to_string(), (2.0, 12.0))]
+        );
+    }
+
+    #[test]
+    fn test_compile_annotation_point_and_arrow() {
+        let scene = compile_dsl(
+            r#"aes(x: x, y: y) | line() | annotate(x: 2, y: 20, text: "peak", point: true, arrow: true, nudge_y: 10)"#,
+            "x,y\n1,10\n2,20\n3,15\n",
+        );
+        let commands = &scene.panels[0].commands;
+        let n = commands.len();
+        assert!(matches!(
+            &commands[n - 3],
+            DrawCommand::DrawPoint { points, .. } if points == &vec![(2.0, 20.0)]
+        ));
+        // The arrow leaves the text's box and ends on the point
+        match &commands[n - 2] {
+            DrawCommand::DrawLine { points, style, .. } => {
+                assert!(style.arrow);
+                assert_eq!(points.len(), 2);
+                assert_eq!(points[0].0, 2.0);
+                assert!(points[0].1 < 30.0 && points[0].1 > 20.0);
+                assert_eq!(points[1], (2.0, 20.0));
+            }
+            other => panic!("expected an arrow, got {other:?}"),
+        }
+        assert!(matches!(
+            &commands[n - 1],
+            DrawCommand::DrawText { position, .. } if *position == (2.0, 30.0)
+        ));
+    }
+
+    #[test]
+    fn test_compile_annotation_on_categories_and_panels() {
+        let csv = "cat,value,region\nA,1,EU\nB,2,EU\nA,3,US\nC,4,US\n";
+        let scene = compile_dsl(
+            r#"aes(x: cat, y: value) | bar() | annotate(x: "B", y: 2.5, text: "new") | annotate(x: "C", y: 1, text: "us", panel: "US") | facet_wrap(by: region)"#,
+            csv,
+        );
+        let texts = annotation_texts(&scene);
+        assert_eq!(texts[0], vec![("new".to_string(), (1.0, 2.5))]);
+        assert_eq!(
+            texts[1],
+            vec![
+                ("new".to_string(), (1.0, 2.5)),
+                ("us".to_string(), (2.0, 1.0))
+            ]
+        );
+    }
+
+    fn shade_rects(scene: &SceneGraph, panel: usize) -> Vec<((f64, f64), (f64, f64))> {
+        let panel = &scene.panels[panel];
+        panel.commands[..panel.underlay]
+            .iter()
+            .map(|command| match command {
+                DrawCommand::DrawRect { tl, br, .. } => (*tl, *br),
+                other => panic!("expected a shade rect, got {other:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_compile_shade_runs_missing_sides_to_the_axes() {
+        let scene = compile_dsl(
+            r#"aes(x: x, y: y) | line() | shade(xmin: 2, xmax: 3) | shade(ymin: 18, color: "red", alpha: 0.5)"#,
+            "x,y\n1,10\n2,20\n4,15\n",
+        );
+        let panel = &scene.panels[0];
+        assert_eq!(panel.underlay, 2);
+        let (x, y) = (panel.x_scale.range, panel.y_scale.range);
+        assert_eq!(
+            shade_rects(&scene, 0),
+            vec![((2.0, y.1), (3.0, y.0)), ((x.0, y.1), (x.1, 18.0))]
+        );
+        assert!(matches!(
+            &panel.commands[0],
+            DrawCommand::DrawRect { style, .. }
+                if style.color.as_deref() == Some("gray") && style.alpha == Some(0.2)
+        ));
+        // The layers follow the bands
+        assert!(matches!(&panel.commands[2], DrawCommand::DrawLine { .. }));
+
+        // A band's given sides widen the axes; flipped, x runs up the plot
+        let scene = compile_dsl(
+            "aes(x: x, y: y) | line() | shade(xmin: 6, ymax: 30) | coord_flip()",
+            "x,y\n1,10\n2,20\n4,15\n",
+        );
+        let panel = &scene.panels[0];
+        let (across, up) = (panel.x_scale.range, panel.y_scale.range);
+        assert!(across.1 >= 30.0 && up.1 >= 6.0);
+        assert_eq!(
+            shade_rects(&scene, 0),
+            vec![((across.0, up.1), (30.0, 6.0))]
+        );
+    }
+
+    #[test]
+    fn test_compile_shade_covers_whole_categories_on_chosen_panels() {
+        let csv = "cat,value,region\nA,1,EU\nB,2,EU\nA,3,US\nD,4,US\n";
+        let scene = compile_dsl(
+            r#"aes(x: cat, y: value) | bar() | shade(xmin: "B", xmax: "D", panel: "US") | facet_wrap(by: region, scales: "free_x")"#,
+            csv,
+        );
+        assert_eq!(scene.panels[0].underlay, 0);
+        // US keeps B on its axis for the band's edge
+        let us = &scene.panels[1];
+        assert_eq!(us.x_scale.categories, vec!["A", "B", "D"]);
+        let y = us.y_scale.range;
+        assert_eq!(shade_rects(&scene, 1), vec![((0.5, y.1), (2.5, y.0))]);
+    }
 }
@@ -1,6 +1,7 @@
+use crate::error::GramGraphError;
 use anyhow::{anyhow, Context, Result};
 use csv::ReaderBuilder;
-use std::io;
+use std::io::Read;
 
 #[derive(Debug, Clone)]
 pub struct CsvData {
@@ -8,37 +9,520 @@ pub struct CsvData {
     pub rows: Vec<Vec<String>>,
 }
 
+impl CsvData {
+    /// Build data from named columns (e.g. query results), checking every column has
+    /// the same number of values
+    pub fn from_columns(columns: Vec<(String, Vec<String>)>) -> Result<CsvData> {
+        let Some((first_name, first_values)) = columns.first() else {
+            return Err(anyhow!("Data must have at least one column"));
+        };
+        let len = first_values.len();
+        if let Some((name, values)) = columns.iter().find(|(_, values)| values.len() != len) {
+            return Err(anyhow!(
+                "Column '{}' has {} values, expected {} (the length of column '{}')",
+                name,
+                values.len(),
+                len,
+                first_name
+            ));
+        }
+
+        let headers = columns.iter().map(|(name, _)| name.clone()).collect();
+        let mut rows = vec![Vec::with_capacity(columns.len()); len];
+        for (_, values) in columns {
+            for (row, value) in rows.iter_mut().zip(values) {
+                row.push(value);
+            }
+        }
+        Ok(CsvData { headers, rows })
+    }
+}
+
 pub enum ColumnSelector {
     Index(usize),
     Name(String),
 }
 
-pub fn read_csv_from_stdin() -> Result<CsvData> {
+/// Format of the data on the input stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InputFormat {
+    #[default]
+    Csv,
+    /// Array of objects or newline-delimited objects (see `json_reader`)
+    Json,
+}
+
+/// How numbers are written in the input's cells
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NumberStyle {
+    /// Plain `f64` syntax only ("1234.5")
+    #[default]
+    Plain,
+    /// A point decimal with comma or space thousands ("1,234.5", "1 234.5")
+    Grouped,
+    /// A comma decimal with point or space thousands ("3,14", "1.234,5", "1 234,5")
+    DecimalComma,
+}
+
+/// Characters besides the style's own separator that may group thousands: a space,
+/// a no-break space, and the narrow no-break space French exports use
+const SPACE_SEPARATORS: [char; 3] = [' ', '\u{a0}', '\u{202f}'];
+
+impl NumberStyle {
+    /// A cell written in this style as plain `f64` syntax, or `None` when it isn't a
+    /// separated number or needs no rewriting. Every thousands group must have three
+    /// digits and use one separator, so "1.5", "12,34", and "1,234 567" are left alone.
+    pub fn normalize(self, cell: &str) -> Option<String> {
+        let (decimal, thousands) = match self {
+            NumberStyle::Plain => return None,
+            NumberStyle::Grouped => ('.', ','),
+            NumberStyle::DecimalComma => (',', '.'),
+        };
+        let cell = cell.trim();
+        let (sign, digits) = match cell.strip_prefix(['-', '+']) {
+            Some(rest) => (&cell[..1], rest),
+            None => ("", cell),
+        };
+        let (int, frac) = match digits.split_once(decimal) {
+            Some((int, frac)) => (int, Some(frac)),
+            None => (digits, None),
+        };
+        let all_digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+        if frac.is_some_and(|frac| !all_digits(frac)) {
+            return None;
+        }
+
+        let separator = int
+            .chars()
+            .find(|&c| c == thousands || SPACE_SEPARATORS.contains(&c));
+        let int = match separator {
+            Some(separator) => {
+                let mut groups = int.split(separator);
+                let first = groups.next().unwrap_or_default();
+                if !all_digits(first) || first.len() > 3 {
+                    return None;
+                }
+                let mut joined = first.to_string();
+                for group in groups {
+                    if group.len() != 3 || !all_digits(group) {
+                        return None;
+                    }
+                    joined.push_str(group);
+                }
+                joined
+            }
+            // Nothing to rewrite unless the decimal separator isn't a point
+            None if all_digits(int) && frac.is_some() && decimal != '.' => int.to_string(),
+            None => return None,
+        };
+        Some(match frac {
+            Some(frac) => format!("{sign}{int}.{frac}"),
+            None => format!("{sign}{int}"),
+        })
+    }
+}
+
+/// Options controlling how input data is read
+#[derive(Debug, Clone, Copy)]
+pub struct CsvOptions {
+    /// Data format; `delimiter` and `has_header` only apply to CSV
+    pub format: InputFormat,
+    /// Field delimiter; `None` detects tab vs comma from the first line
+    pub delimiter: Option<u8>,
+    /// When false, the first line is data and headers are synthesized as `c0, c1, ...`
+    pub has_header: bool,
+    /// Separators to strip from numeric columns (see `normalize_numbers`)
+    pub numbers: NumberStyle,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {
+            format: InputFormat::Csv,
+            delimiter: None,
+            has_header: true,
+            numbers: NumberStyle::Plain,
+        }
+    }
+}
+
+/// Read input data in the configured format
+pub fn read_input(input: impl Read, options: CsvOptions) -> Result<CsvData> {
+    let mut data = match options.format {
+        InputFormat::Csv => read_csv(input, options)?,
+        InputFormat::Json => crate::json_reader::read_json(input)?,
+    };
+    normalize_numbers(&mut data, options.numbers);
+    Ok(data)
+}
+
+/// Rewrite numbers written with separators as plain `f64` syntax, so the column store
+/// and every stat parse them. A column is only rewritten when each of its cells is
+/// missing, already a plain number, or normalized by `style`; one other cell (a code
+/// like "A-1.234", a label) leaves the whole column as text.
+pub fn normalize_numbers(data: &mut CsvData, style: NumberStyle) {
+    if style == NumberStyle::Plain {
+        return;
+    }
+    for idx in 0..data.headers.len() {
+        let mut rewritten = Vec::new();
+        let numeric = data.rows.iter().enumerate().all(|(row_idx, row)| {
+            let cell = row.get(idx).map(String::as_str).unwrap_or_default();
+            match style.normalize(cell) {
+                Some(value) => {
+                    rewritten.push((row_idx, value));
+                    true
+                }
+                None => numeric_value(cell).is_some() || is_missing(cell),
+            }
+        });
+        if numeric {
+            for (row_idx, value) in rewritten {
+                data.rows[row_idx][idx] = value;
+            }
+        }
+    }
+}
+
+/// Read delimited text.
+/// An explicit delimiter always wins; otherwise it is detected from the first line.
+pub fn read_csv(mut input: impl Read, options: CsvOptions) -> Result<CsvData> {
+    let mut content = Vec::new();
+    input
+        .read_to_end(&mut content)
+        .context("Failed to read CSV input")?;
+
+    let delimiter = options
+        .delimiter
+        .unwrap_or_else(|| detect_delimiter(&content));
     let mut reader = ReaderBuilder::new()
-        .has_headers(true)
-        .from_reader(io::stdin());
+        .has_headers(options.has_header)
+        .delimiter(delimiter)
+        .from_reader(content.as_slice());
 
-    let headers = reader
-        .headers()
-        .context("Failed to read CSV headers")?
-        .iter()
-        .map(|s| s.to_string())
-        .collect();
+    let mut headers: Vec<String> = if options.has_header {
+        reader
+            .headers()
+            .context("Failed to read CSV headers")?
+            .iter()
+            .map(|s| s.to_string())
+            .collect()
+    } else {
+        Vec::new()
+    };
 
     let mut rows = Vec::new();
-    for result in reader.records() {
-        let record = result.context("Failed to read CSV record")?;
+    for (row_idx, result) in reader.records().enumerate() {
+        let record = result.map_err(|e| match e.kind() {
+            csv::ErrorKind::UnequalLengths {
+                expected_len, len, ..
+            } => anyhow!(
+                "Failed to read CSV record: row {} has {} fields, expected {} ({})",
+                row_idx + 1,
+                len,
+                expected_len,
+                headers.join(", ")
+            ),
+            _ => anyhow!(e).context("Failed to read CSV record"),
+        })?;
+        if headers.is_empty() && !options.has_header {
+            headers = synthesized_headers(record.len());
+        }
         let row: Vec<String> = record.iter().map(|s| s.to_string()).collect();
         rows.push(row);
     }
 
     if rows.is_empty() {
-        return Err(anyhow!("CSV must contain at least one data row"));
+        return Err(GramGraphError::EmptyData.into());
     }
 
     Ok(CsvData { headers, rows })
 }
 
+/// A unit written into numeric cells, stripped by `clean_unit_columns`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellUnit {
+    /// A trailing `%`; the value is divided by 100 ("45%" is 0.45)
+    Percent,
+    /// A single leading `$`, `€`, or `£` ("$1,299.00" is 1299)
+    Currency,
+}
+
+/// Symbols a currency cell may start with
+pub const CURRENCY_SYMBOLS: [char; 3] = ['$', '€', '£'];
+
+impl CellUnit {
+    /// The unit named by `scale_x(parse: ...)` / `scale_y(parse: ...)`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "percent" => Some(CellUnit::Percent),
+            "currency" => Some(CellUnit::Currency),
+            _ => None,
+        }
+    }
+
+    /// A cell's value and the mark that carried the unit ('%' or the currency
+    /// symbol), or `None` when the cell isn't a number with this unit. With
+    /// `lenient`, a bare number counts too (a percent still divided by 100) and the mark
+    /// is `None`. Amounts may group thousands with commas ("$1,299.00", "1,250%").
+    pub fn parse(self, cell: &str, lenient: bool) -> Option<(f64, Option<char>)> {
+        let cell = cell.trim();
+        let amount = |text: &str| {
+            let grouped = NumberStyle::Grouped.normalize(text);
+            numeric_value(grouped.as_deref().unwrap_or(text))
+        };
+        match self {
+            CellUnit::Percent => {
+                let (text, mark) = match cell.strip_suffix('%') {
+                    Some(text) => (text.trim_end(), Some('%')),
+                    None if lenient => (cell, None),
+                    None => return None,
+                };
+                Some((amount(text)? / 100.0, mark))
+            }
+            CellUnit::Currency => {
+                // The sign may come before or after the symbol: "-$5", "$-5"
+                let (sign, rest) = match cell.strip_prefix('-') {
+                    Some(rest) => (-1.0, rest),
+                    None => (1.0, cell),
+                };
+                let (text, mark) = match rest.strip_prefix(CURRENCY_SYMBOLS) {
+                    Some(text) => (text, rest.chars().next()),
+                    None if lenient => (rest, None),
+                    None => return None,
+                };
+                if sign < 0.0 && text.starts_with(['-', '+']) {
+                    return None;
+                }
+                Some((sign * amount(text)?, mark))
+            }
+        }
+    }
+}
+
+/// Rewrite percent and currency columns as plain numbers and return each column's
+/// unit. A column is cleaned when every non-missing cell carries the same mark ("45%",
+/// or always "$"), so text like "100% cotton" or a mix of "45%" and "50" is left alone.
+/// A column in `hints` is cleaned leniently (see `CellUnit::parse`), unless a cell is
+/// no number at all.
+pub fn clean_unit_columns(
+    headers: &[String],
+    rows: &mut [Vec<String>],
+    hints: &[(usize, CellUnit)],
+) -> Vec<Option<CellUnit>> {
+    (0..headers.len())
+        .map(|idx| {
+            let hint = hints
+                .iter()
+                .find(|(col, _)| *col == idx)
+                .map(|(_, unit)| *unit);
+            let units = match hint {
+                Some(unit) => vec![unit],
+                None => vec![CellUnit::Percent, CellUnit::Currency],
+            };
+            units.into_iter().find_map(|unit| {
+                let values = unit_values(rows, idx, unit, hint.is_some())?;
+                for (row, value) in values {
+                    rows[row][idx] = value.to_string();
+                }
+                Some(unit)
+            })
+        })
+        .collect()
+}
+
+/// A column's values with `unit` stripped, by row, if every non-missing cell parses
+/// (with one mark throughout, unless `lenient`) and at least one does
+fn unit_values(
+    rows: &[Vec<String>],
+    idx: usize,
+    unit: CellUnit,
+    lenient: bool,
+) -> Option<Vec<(usize, f64)>> {
+    let mut values = Vec::new();
+    let mut marks = None;
+    for (row, cells) in rows.iter().enumerate() {
+        let Some(cell) = cells.get(idx) else {
+            continue;
+        };
+        if is_missing(cell) {
+            continue;
+        }
+        let (value, mark) = unit.parse(cell, lenient)?;
+        if !lenient && *marks.get_or_insert(mark) != mark {
+            return None;
+        }
+        values.push((row, value));
+    }
+    (!values.is_empty()).then_some(values)
+}
+
+/// Cell values treated as missing (compared case-insensitively after trimming)
+pub const NA_TOKENS: &[&str] = &["", "NA", "NaN", "null"];
+
+/// True when a cell holds one of the `NA_TOKENS` or a non-finite number ("inf",
+/// "-Infinity"), which no scale can place
+pub fn is_missing(value: &str) -> bool {
+    let value = value.trim();
+    NA_TOKENS
+        .iter()
+        .any(|token| value.eq_ignore_ascii_case(token))
+        || is_non_finite(value)
+}
+
+/// A cell's value when it is a finite number once trimmed: "007" is 7 and "1e3"
+/// is 1000, while "1,234", "", and "inf" are not numbers
+pub fn numeric_value(value: &str) -> Option<f64> {
+    value
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|value| value.is_finite())
+}
+
+/// True when a cell parses as a number that isn't finite: NaN, an infinity, or a
+/// literal too large for f64 ("1e999")
+pub fn is_non_finite(value: &str) -> bool {
+    value.trim().parse::<f64>().is_ok_and(|v| !v.is_finite())
+}
+
+/// A column's cells, typed once per render so numeric aesthetics don't re-parse
+/// the same strings for every layer and facet
+#[derive(Debug, Clone, PartialEq)]
+pub enum Column {
+    /// Every non-missing cell is a number; missing cells hold NaN
+    Numeric(Vec<f64>),
+    /// Some non-missing cell is not a number; values are read from the rows
+    Text,
+}
+
+/// Typed columns inferred from string rows, indexed like the headers
+#[derive(Debug, Clone)]
+pub struct ColumnStore {
+    columns: Vec<Column>,
+}
+
+impl ColumnStore {
+    /// Infer each column's type: numeric unless a non-missing cell fails to parse.
+    /// Cells past the end of a short row count as missing.
+    pub fn new(headers: &[String], rows: &[Vec<String>]) -> Self {
+        let mut numeric: Vec<Option<Vec<f64>>> = (0..headers.len())
+            .map(|_| Some(Vec::with_capacity(rows.len())))
+            .collect();
+        for row in rows {
+            for (idx, column) in numeric.iter_mut().enumerate() {
+                let Some(values) = column else {
+                    continue;
+                };
+                let cell = row.get(idx).map(String::as_str).unwrap_or_default();
+                match numeric_value(cell) {
+                    Some(value) => values.push(value),
+                    None if is_missing(cell) => values.push(f64::NAN),
+                    None => *column = None,
+                }
+            }
+        }
+        let columns = numeric
+            .into_iter()
+            .map(|values| values.map_or(Column::Text, Column::Numeric))
+            .collect();
+        Self { columns }
+    }
+
+    /// The typed column at a header index
+    pub fn column(&self, idx: usize) -> &Column {
+        &self.columns[idx]
+    }
+}
+
+/// Positional column names for headerless input: `c0, c1, c2, ...`
+pub fn synthesized_headers(count: usize) -> Vec<String> {
+    (0..count).map(|i| format!("c{}", i)).collect()
+}
+
+/// A header `disambiguate_headers` renamed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenamedHeader {
+    /// Zero-based position of the column
+    pub index: usize,
+    /// The header as read
+    pub name: String,
+    /// The earlier header it repeats (equal ignoring case)
+    pub first: String,
+    /// The name the column now goes by
+    pub renamed: String,
+}
+
+/// Give each header that repeats an earlier one, ignoring case as column lookups do, a
+/// numbered suffix: `value, value, Value` becomes `value, value_2, Value_3`. A suffix
+/// already taken by another header is skipped. Returns the renamed headers.
+pub fn disambiguate_headers(headers: &mut [String]) -> Vec<RenamedHeader> {
+    let original = headers.to_vec();
+    let mut renamed = Vec::new();
+    for index in 0..headers.len() {
+        let name = &original[index];
+        let mut earlier = original[..index]
+            .iter()
+            .filter(|h| h.eq_ignore_ascii_case(name));
+        let Some(first) = earlier.next() else {
+            continue;
+        };
+        let mut n = earlier.count() + 2;
+        let candidate = loop {
+            let candidate = format!("{}_{}", name, n);
+            if !headers.iter().any(|h| h.eq_ignore_ascii_case(&candidate)) {
+                break candidate;
+            }
+            n += 1;
+        };
+        headers[index] = candidate.clone();
+        renamed.push(RenamedHeader {
+            index,
+            name: name.clone(),
+            first: first.clone(),
+            renamed: candidate,
+        });
+    }
+    renamed
+}
+
+/// Tab when the header row contains tabs but no commas, comma otherwise
+pub fn detect_delimiter(content: &[u8]) -> u8 {
+    let header = content.split(|&b| b == b'\n').next().unwrap_or_default();
+    if header.contains(&b'\t') && !header.contains(&b',') {
+        b'\t'
+    } else {
+        b','
+    }
+}
+
+/// Parse a `--delimiter` value: a single ASCII character, or `\t` / `tab` for tabs.
+/// Quotes and line breaks are rejected since the CSV layer reserves them.
+pub fn parse_delimiter(s: &str) -> std::result::Result<u8, String> {
+    let delimiter = match s {
+        "\\t" | "tab" => b'\t',
+        _ => {
+            let mut chars = s.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if c.is_ascii() => c as u8,
+                _ => {
+                    return Err(format!(
+                        "delimiter must be a single ASCII character or \\t, got `{}`",
+                        s
+                    ))
+                }
+            }
+        }
+    };
+    if matches!(delimiter, b'"' | b'\n' | b'\r') {
+        return Err(format!(
+            "`{}` cannot be used as a delimiter",
+            (delimiter as char).escape_default()
+        ));
+    }
+    Ok(delimiter)
+}
+
 pub fn parse_column_selector(input: &str) -> ColumnSelector {
     match input.parse::<usize>() {
         Ok(index) => ColumnSelector::Index(index),
@@ -78,9 +562,10 @@ pub fn extract_column(data: &CsvData, selector: ColumnSelector) -> Result<(Strin
     for (row_idx, row) in data.rows.iter().enumerate() {
         if column_index >= row.len() {
             return Err(anyhow!(
-                "Row {} has only {} columns, expected at least {}",
+                "Row {} has only {} columns, missing column '{}' (column {})",
                 row_idx + 1,
                 row.len(),
+                column_name,
                 column_index + 1
             ));
         }
@@ -94,6 +579,14 @@ pub fn extract_column(data: &CsvData, selector: ColumnSelector) -> Result<(Strin
                 row_idx + 1
             )
         })?;
+        if !value.is_finite() {
+            return Err(anyhow!(
+                "Non-finite value '{}' in column '{}' at row {}",
+                value_str,
+                column_name,
+                row_idx + 1
+            ));
+        }
         values.push(value);
     }
 
@@ -135,9 +628,10 @@ pub fn extract_column_as_string(
     for (row_idx, row) in data.rows.iter().enumerate() {
         if column_index >= row.len() {
             return Err(anyhow!(
-                "Row {} has only {} columns, expected at least {}",
+                "Row {} has only {} columns, missing column '{}' (column {})",
                 row_idx + 1,
                 row.len(),
+                column_name,
                 column_index + 1
             ));
         }
@@ -154,28 +648,7 @@ mod tests {
 
     /// Helper function to create CsvData from string
     fn csv_from_string(content: &str) -> Result<CsvData> {
-        let cursor = Cursor::new(content);
-        let mut reader = ReaderBuilder::new().has_headers(true).from_reader(cursor);
-
-        let headers = reader
-            .headers()
-            .context("Failed to read CSV headers")?
-            .iter()
-            .map(|s| s.to_string())
-            .collect();
-
-        let mut rows = Vec::new();
-        for result in reader.records() {
-            let record = result.context("Failed to read CSV record")?;
-            let row: Vec<String> = record.iter().map(|s| s.to_string()).collect();
-            rows.push(row);
-        }
-
-        if rows.is_empty() {
-            return Err(anyhow!("CSV must contain at least one data row"));
-        }
-
-        Ok(CsvData { headers, rows })
+        read_csv(Cursor::new(content), CsvOptions::default())
     }
 
     // parse_column_selector tests (2 tests)
@@ -291,6 +764,30 @@ mod tests {
         assert!(result.unwrap_err().to_string().contains("Failed to parse"));
     }
 
+    #[test]
+    fn test_extract_column_non_finite_value() {
+        for cell in ["inf", "-inf", "NaN", "1e999"] {
+            let csv = csv_from_string(&format!("x,y\n1,10\n2,{}", cell)).unwrap();
+            let err = extract_column(&csv, ColumnSelector::Name("y".to_string())).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!("Non-finite value '{}' in column 'y' at row 2", cell)
+            );
+        }
+    }
+
+    #[test]
+    fn test_non_finite_cells_count_as_missing() {
+        for cell in ["inf", " -inf", "+Infinity", "nan", "1e999"] {
+            assert!(is_non_finite(cell), "{}", cell);
+            assert!(is_missing(cell), "{}", cell);
+        }
+        for cell in ["1e300", "-0", "info", "Region A", "NA"] {
+            assert!(!is_non_finite(cell), "{}", cell);
+        }
+        assert!(!is_missing("info"));
+    }
+
     #[test]
     fn test_extract_column_short_row() {
         // CSV reader validates row length during parsing
@@ -311,7 +808,7 @@ mod tests {
             .contains("at least one data row"));
     }
 
-    // read_csv_from_stdin tests (6 tests using csv_from_string helper)
+    // read_csv tests (6 tests using csv_from_string helper)
 
     #[test]
     fn test_read_csv_basic() {
@@ -348,12 +845,44 @@ mod tests {
 
     #[test]
     fn test_read_csv_duplicate_headers() {
-        // CSV crate allows duplicate headers, just reads them as-is
+        // CSV crate allows duplicate headers, just reads them as-is; rendering renames
+        // the repeats with disambiguate_headers
         let csv = csv_from_string("value,value,other\n1,2,3\n4,5,6").unwrap();
         assert_eq!(csv.headers, vec!["value", "value", "other"]);
         assert_eq!(csv.rows.len(), 2);
     }
 
+    #[test]
+    fn test_disambiguate_headers() {
+        let mut headers = ["value", "value", "other", "Value", "value_2"].map(String::from);
+        let renamed = disambiguate_headers(&mut headers);
+        assert_eq!(
+            headers,
+            ["value", "value_3", "other", "Value_4", "value_2"].map(String::from)
+        );
+        assert_eq!(
+            renamed,
+            vec![
+                RenamedHeader {
+                    index: 1,
+                    name: "value".into(),
+                    first: "value".into(),
+                    renamed: "value_3".into(),
+                },
+                RenamedHeader {
+                    index: 3,
+                    name: "Value".into(),
+                    first: "value".into(),
+                    renamed: "Value_4".into(),
+                },
+            ]
+        );
+
+        let mut headers = ["a", "b"].map(String::from);
+        assert!(disambiguate_headers(&mut headers).is_empty());
+        assert_eq!(headers, ["a", "b"].map(String::from));
+    }
+
     #[test]
     fn test_read_csv_malformed() {
         // Unclosed quote
@@ -361,4 +890,261 @@ mod tests {
         // CSV crate may handle this differently, just check it doesn't panic
         let _ = result;
     }
+
+    #[test]
+    fn test_read_csv_detects_tabs() {
+        let csv = csv_from_string("a\tb\n1\t2\n").unwrap();
+        assert_eq!(csv.headers, vec!["a", "b"]);
+        assert_eq!(csv.rows[0], vec!["1", "2"]);
+
+        // A comma in the header row keeps comma parsing
+        let csv = csv_from_string("a,b\tc\n1,2\t3\n").unwrap();
+        assert_eq!(csv.headers, vec!["a", "b\tc"]);
+    }
+
+    #[test]
+    fn test_read_csv_explicit_delimiter_wins() {
+        let semicolon = CsvOptions {
+            delimiter: Some(b';'),
+            ..CsvOptions::default()
+        };
+        let csv = read_csv(Cursor::new("a;b\tc\n1;2\t3\n"), semicolon).unwrap();
+        assert_eq!(csv.headers, vec!["a", "b\tc"]);
+        assert_eq!(csv.rows[0], vec!["1", "2\t3"]);
+
+        let pipe = CsvOptions {
+            delimiter: Some(b'|'),
+            ..CsvOptions::default()
+        };
+        let csv = read_csv(Cursor::new("a|b\n\"x|y\"|2\n"), pipe).unwrap();
+        assert_eq!(csv.rows[0], vec!["x|y", "2"]);
+    }
+
+    #[test]
+    fn test_parse_delimiter() {
+        assert_eq!(parse_delimiter(";"), Ok(b';'));
+        assert_eq!(parse_delimiter("|"), Ok(b'|'));
+        assert_eq!(parse_delimiter("\\t"), Ok(b'\t'));
+        assert_eq!(parse_delimiter("\t"), Ok(b'\t'));
+        assert_eq!(parse_delimiter("tab"), Ok(b'\t'));
+        assert!(parse_delimiter("").is_err());
+        assert!(parse_delimiter(";;").is_err());
+        assert!(parse_delimiter("é").is_err());
+        assert!(parse_delimiter("\"").is_err());
+    }
+
+    #[test]
+    fn test_read_csv_without_header() {
+        let options = CsvOptions {
+            has_header: false,
+            ..CsvOptions::default()
+        };
+        let csv = read_csv(Cursor::new("1,10,a\n2,20,b\n"), options).unwrap();
+        assert_eq!(csv.headers, vec!["c0", "c1", "c2"]);
+        assert_eq!(csv.rows.len(), 2);
+        assert_eq!(csv.rows[0], vec!["1", "10", "a"]);
+
+        let err = read_csv(Cursor::new("1,10\n2\n"), options).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("row 2 has 1 fields, expected 2 (c0, c1)"));
+    }
+
+    #[test]
+    fn test_from_columns() {
+        let data = CsvData::from_columns(vec![
+            ("x".to_string(), vec!["1".to_string(), "2".to_string()]),
+            ("y".to_string(), vec!["10".to_string(), "20".to_string()]),
+        ])
+        .unwrap();
+        assert_eq!(data.headers, vec!["x", "y"]);
+        assert_eq!(data.rows, vec![vec!["1", "10"], vec!["2", "20"]]);
+
+        let err = CsvData::from_columns(vec![
+            ("x".to_string(), vec!["1".to_string(), "2".to_string()]),
+            ("y".to_string(), vec!["10".to_string()]),
+        ])
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Column 'y' has 1 values, expected 2 (the length of column 'x')"
+        );
+
+        assert!(CsvData::from_columns(vec![]).is_err());
+    }
+
+    #[test]
+    fn test_column_store_infers_types() {
+        let headers: Vec<String> = ["n", "gappy", "t", "mixed"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let rows: Vec<Vec<String>> = [
+            ["1", "2.5", "a", "1"],
+            [" 2 ", "NA", "b", "x"],
+            ["-3e2", "", "c", "3"],
+        ]
+        .iter()
+        .map(|row| row.iter().map(|s| s.to_string()).collect())
+        .collect();
+        let store = ColumnStore::new(&headers, &rows);
+
+        assert_eq!(store.column(0), &Column::Numeric(vec![1.0, 2.0, -300.0]));
+        // Missing cells don't make a column text; they hold NaN
+        match store.column(1) {
+            Column::Numeric(values) => {
+                assert_eq!(values[0], 2.5);
+                assert!(values[1].is_nan() && values[2].is_nan());
+            }
+            Column::Text => panic!("missing cells should not make a column text"),
+        }
+        assert_eq!(store.column(2), &Column::Text);
+        // One bad cell is enough
+        assert_eq!(store.column(3), &Column::Text);
+
+        // Infinities are missing too, never stored as values
+        let rows = vec![vec!["inf".to_string()], vec!["4".to_string()]];
+        match ColumnStore::new(&headers[..1], &rows).column(0) {
+            Column::Numeric(values) => assert!(values[0].is_nan() && values[1] == 4.0),
+            Column::Text => panic!("an infinity should not make a column text"),
+        }
+    }
+
+    #[test]
+    fn test_normalize_german_and_french_numbers() {
+        let style = NumberStyle::DecimalComma;
+        assert_eq!(style.normalize("3,14").as_deref(), Some("3.14"));
+        assert_eq!(style.normalize("1.234,56").as_deref(), Some("1234.56"));
+        assert_eq!(style.normalize("-1.234.567").as_deref(), Some("-1234567"));
+        assert_eq!(style.normalize("1 234,5").as_deref(), Some("1234.5"));
+        assert_eq!(style.normalize("1\u{202f}234,5").as_deref(), Some("1234.5"));
+        assert_eq!(style.normalize("12\u{a0}345").as_deref(), Some("12345"));
+        // Already plain, or not a separated number
+        for cell in [
+            "42",
+            "1.5",
+            "12.34,5",
+            "1.234 567",
+            "1,2,3",
+            "v1,2",
+            "",
+            "NA",
+        ] {
+            assert_eq!(style.normalize(cell), None, "{cell}");
+        }
+
+        let style = NumberStyle::Grouped;
+        assert_eq!(style.normalize("1,234").as_deref(), Some("1234"));
+        assert_eq!(
+            style.normalize("1,234,567.25").as_deref(),
+            Some("1234567.25")
+        );
+        assert_eq!(style.normalize("1 234").as_deref(), Some("1234"));
+        for cell in ["1234.5", "12,34", "1,2345", ",123", "1,234.5.6"] {
+            assert_eq!(style.normalize(cell), None, "{cell}");
+        }
+        assert_eq!(NumberStyle::Plain.normalize("1,234"), None);
+    }
+
+    #[test]
+    fn test_normalize_numbers_rewrites_only_numeric_columns() {
+        let mut data = CsvData {
+            headers: vec!["price".into(), "code".into(), "plain".into()],
+            rows: vec![
+                vec!["1.234,5".into(), "1.234".into(), "2.5".into()],
+                vec!["3,14".into(), "A-7".into(), "3".into()],
+                vec!["".into(), "9.876".into(), "NA".into()],
+                vec!["7".into()],
+            ],
+        };
+        normalize_numbers(&mut data, NumberStyle::DecimalComma);
+        assert_eq!(data.rows[0], vec!["1234.5", "1.234", "2.5"]);
+        assert_eq!(data.rows[1], vec!["3.14", "A-7", "3"]);
+        assert_eq!(data.rows[2], vec!["", "9.876", "NA"]);
+        assert_eq!(data.rows[3], vec!["7"]);
+
+        let store = ColumnStore::new(&data.headers, &data.rows);
+        assert!(matches!(store.column(0), Column::Numeric(_)));
+        assert_eq!(store.column(1), &Column::Text);
+    }
+
+    #[test]
+    fn test_cell_unit_parse() {
+        let percent = |cell| CellUnit::Percent.parse(cell, false).map(|(v, _)| v);
+        assert_eq!(percent("45%"), Some(0.45));
+        assert_eq!(percent(" -12.5 %"), Some(-0.125));
+        assert_eq!(percent("1,250%"), Some(12.5));
+        for cell in ["45", "%", "100% cotton", "45%%", "1,25%"] {
+            assert_eq!(percent(cell), None, "{cell}");
+        }
+        assert_eq!(CellUnit::Percent.parse("45", true), Some((0.45, None)));
+
+        let currency = |cell| CellUnit::Currency.parse(cell, false);
+        assert_eq!(currency("$1,299.00"), Some((1299.0, Some('$'))));
+        assert_eq!(currency("€12.5"), Some((12.5, Some('€'))));
+        assert_eq!(currency("-£5"), Some((-5.0, Some('£'))));
+        assert_eq!(currency("$-5"), Some((-5.0, Some('$'))));
+        for cell in ["1299", "$", "$ten", "-$-5", "5$", "$$5", "US$5"] {
+            assert_eq!(currency(cell), None, "{cell}");
+        }
+        assert_eq!(
+            CellUnit::Currency.parse("1,299", true),
+            Some((1299.0, None))
+        );
+        assert_eq!(CellUnit::from_name("currency"), Some(CellUnit::Currency));
+        assert_eq!(CellUnit::from_name("percent"), Some(CellUnit::Percent));
+        assert_eq!(CellUnit::from_name("money"), None);
+    }
+
+    #[test]
+    fn test_clean_unit_columns() {
+        let headers: Vec<String> = ["share", "price", "fabric", "mixed", "euro", "plain"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let mut rows: Vec<Vec<String>> = [
+            ["45%", "$1,299.00", "100% cotton", "45%", "€5", "1"],
+            ["NA", "$0.99", "50% wool", "50", "$5", "2"],
+            ["7%", "", "20%", "12%", "€6", "3"],
+        ]
+        .iter()
+        .map(|row| row.iter().map(|s| s.to_string()).collect())
+        .collect();
+
+        let units = clean_unit_columns(&headers, &mut rows, &[]);
+        assert_eq!(
+            units,
+            vec![
+                Some(CellUnit::Percent),
+                Some(CellUnit::Currency),
+                None,
+                None,
+                None,
+                None
+            ]
+        );
+        assert_eq!(rows[0][..2], ["0.45", "1299"]);
+        assert_eq!(rows[1][..2], ["NA", "0.99"]);
+        assert_eq!(rows[2][..2], ["0.07", ""]);
+        // Text that merely contains a percent, mixed marks, and bare numbers stay put
+        assert_eq!(rows[0][2..], ["100% cotton", "45%", "€5", "1"]);
+        assert_eq!(rows[1][2..], ["50% wool", "50", "$5", "2"]);
+
+        // A hint reads bare numbers in the unit, and never rewrites text
+        let units = clean_unit_columns(
+            &headers,
+            &mut rows,
+            &[
+                (2, CellUnit::Percent),
+                (3, CellUnit::Percent),
+                (4, CellUnit::Currency),
+            ],
+        );
+        assert_eq!(
+            units[2..5],
+            [None, Some(CellUnit::Percent), Some(CellUnit::Currency)]
+        );
+        assert_eq!(rows[0][2..5], ["100% cotton", "0.45", "5"]);
+        assert_eq!(rows[1][2..5], ["50% wool", "0.5", "5"]);
+    }
 }
@@ -0,0 +1,260 @@
+// Structured failures for library callers
+//
+// The public entry points in `runtime` return `Result<_, GramGraphError>`, so callers
+// match on the variant instead of reading the message. Inside the pipeline, stages
+// still return `anyhow::Result` and raise these as plain errors; `From<anyhow::Error>`
+// turns a failure into this type at the library surface, and only the CLI turns it
+// back into anyhow to print with its context.
+
+use std::fmt;
+
+/// A failure callers can match on instead of reading the message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GramGraphError {
+    /// The DSL didn't parse. `offset` is the byte offset of the component that failed;
+    /// `line` and `column` are 1-based, and `expected` says what was wrong there.
+    ParseError {
+        offset: usize,
+        line: usize,
+        column: usize,
+        expected: String,
+        /// The DSL line holding `offset`, shown under the message with a caret
+        source_line: String,
+    },
+    /// A column the plot references isn't in the data. `also_missing` lists any
+    /// further missing columns, reported in the same error.
+    ColumnNotFound {
+        name: String,
+        also_missing: Vec<String>,
+        available: Vec<String>,
+    },
+    /// A cell that must hold a number doesn't. `aesthetic` is the DSL name the
+    /// column is mapped to (`y`, `color`, `size`, ...); `row` is 1-based.
+    NonNumericValue {
+        aesthetic: String,
+        column: String,
+        row: usize,
+        value: String,
+    },
+    /// The input has a header but no data rows
+    EmptyData,
+    /// Drawing or encoding the output failed, or (from `From<anyhow::Error>`) any
+    /// other failure; the message includes the cause
+    RenderError(String),
+}
+
+impl fmt::Display for GramGraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GramGraphError::ParseError {
+                line,
+                column,
+                expected,
+                source_line,
+                ..
+            } => write!(
+                f,
+                "Parse error at line {}, column {}: {}\n  {}\n  {}^",
+                line,
+                column,
+                expected,
+                source_line,
+                " ".repeat(column - 1)
+            ),
+            GramGraphError::ColumnNotFound {
+                name,
+                also_missing,
+                available,
+            } => {
+                let described: Vec<String> = std::iter::once(name)
+                    .chain(also_missing)
+                    .map(|col| {
+                        match crate::suggest::closest_match(
+                            col,
+                            available.iter().map(String::as_str),
+                        ) {
+                            Some(suggestion) => {
+                                format!("'{}' (did you mean '{}'?)", col, suggestion)
+                            }
+                            None => format!("'{}'", col),
+                        }
+                    })
+                    .collect();
+                write!(
+                    f,
+                    "Unknown column{} {}. Available columns: {}",
+                    if also_missing.is_empty() { "" } else { "s" },
+                    described.join(", "),
+                    available.join(", ")
+                )
+            }
+            GramGraphError::NonNumericValue {
+                aesthetic,
+                column,
+                row,
+                value,
+            } => write!(
+                f,
+                "Failed to parse {} value '{}' in column '{}' (row {})",
+                aesthetic, value, column, row
+            ),
+            GramGraphError::EmptyData => write!(f, "Input must contain at least one data row"),
+            GramGraphError::RenderError(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for GramGraphError {}
+
+/// `err` itself when it is a structured failure, else a `RenderError` carrying the
+/// whole message chain. A structured failure under added context (`Layer 2 data:
+/// ...`) becomes a `RenderError` too, so the message keeps that context.
+impl From<anyhow::Error> for GramGraphError {
+    fn from(err: anyhow::Error) -> Self {
+        match err.downcast_ref::<GramGraphError>() {
+            Some(structured) if err.chain().count() == 1 => structured.clone(),
+            _ => GramGraphError::RenderError(format!("{:#}", err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csv_reader::{read_csv, CsvData, CsvOptions};
+    use crate::{render, OutputFormat, RenderOptions};
+    use std::io::Cursor;
+
+    fn data() -> CsvData {
+        CsvData::from_columns(vec![
+            ("day".to_string(), vec!["1".to_string(), "2".to_string()]),
+            (
+                "sales".to_string(),
+                vec!["10".to_string(), "oops".to_string()],
+            ),
+        ])
+        .unwrap()
+    }
+
+    fn render_error(dsl: &str, data: CsvData) -> GramGraphError {
+        let options = RenderOptions {
+            format: OutputFormat::Svg,
+            ..RenderOptions::default()
+        };
+        render(dsl, data, options).unwrap_err()
+    }
+
+    #[test]
+    fn test_parse_error_variant() {
+        let err = render_error("aes(x: day, y: sales) | lin()", data());
+        let GramGraphError::ParseError {
+            offset,
+            line,
+            column,
+            ..
+        } = err
+        else {
+            panic!("expected a parse error, got {err:?}");
+        };
+        assert_eq!((offset, line, column), (24, 1, 25));
+        assert!(err
+            .to_string()
+            .starts_with("Parse error at line 1, column 25"));
+    }
+
+    #[test]
+    fn test_column_not_found_variant() {
+        let err = render_error("aes(x: day, y: sale) | line()", data());
+        assert_eq!(
+            err,
+            GramGraphError::ColumnNotFound {
+                name: "sale".to_string(),
+                also_missing: vec![],
+                available: vec!["day".to_string(), "sales".to_string()],
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "Unknown column 'sale' (did you mean 'sales'?). Available columns: day, sales"
+        );
+    }
+
+    #[test]
+    fn test_non_numeric_value_variant() {
+        let err = render_error("aes(x: day, y: sales) | line()", data());
+        assert_eq!(
+            err,
+            GramGraphError::NonNumericValue {
+                aesthetic: "y".to_string(),
+                column: "sales".to_string(),
+                row: 2,
+                value: "oops".to_string(),
+            }
+        );
+
+        // Other numeric aesthetics name themselves
+        let err = render_error("aes(x: day, y: day) | point(fade_by: sales)", data());
+        assert!(matches!(
+            &err,
+            GramGraphError::NonNumericValue { aesthetic, row: 2, .. } if aesthetic == "fade_by"
+        ));
+        assert_eq!(
+            err.to_string(),
+            "Failed to parse fade_by value 'oops' in column 'sales' (row 2)"
+        );
+        let err = render_error(
+            "aes(x: day, y: day) | segment(xend: day, yend: sales)",
+            data(),
+        );
+        assert!(matches!(
+            &err,
+            GramGraphError::NonNumericValue { aesthetic, .. } if aesthetic == "yend"
+        ));
+    }
+
+    #[test]
+    fn test_empty_data_variant() {
+        let err = read_csv(Cursor::new("day,sales\n"), CsvOptions::default()).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<GramGraphError>(),
+            Some(&GramGraphError::EmptyData)
+        );
+
+        let empty = CsvData {
+            headers: vec!["day".to_string()],
+            rows: vec![],
+        };
+        assert_eq!(
+            render_error("aes(x: day, y: day) | line()", empty),
+            GramGraphError::EmptyData
+        );
+    }
+
+    #[test]
+    fn test_unstructured_failures_keep_their_message() {
+        let err = GramGraphError::from(anyhow::anyhow!("boom").context("Failed to render plot"));
+        assert_eq!(
+            err,
+            GramGraphError::RenderError("Failed to render plot: boom".to_string())
+        );
+        let err = GramGraphError::from(
+            anyhow::Error::new(GramGraphError::EmptyData).context("Layer 2 data: \"old\""),
+        );
+        assert_eq!(
+            err,
+            GramGraphError::RenderError(
+                "Layer 2 data: \"old\": Input must contain at least one data row".to_string()
+            )
+        );
+
+        // Failures of the render itself name the phase
+        let err = render_error(
+            "aes(x: day, y: day) | line() | facet_wrap(by: day, ncol: 0)",
+            data(),
+        );
+        assert!(
+            matches!(&err, GramGraphError::RenderError(message) if message == "Failed to render plot: facet_wrap() ncol and nrow must be at least 1"),
+            "{err:?}"
+        );
+    }
+}
@@ -1,20 +1,62 @@
 use crate::datetime::format_datetime_tick;
-use crate::ir::{AxisTransform, DrawCommand, PanelScene, SceneGraph};
-use crate::theme_resolve::{parse_color as resolve_color, FontFace, ResolvedTheme};
+use crate::ir::{ColorBar, DrawCommand, PanelScene, Scale, SceneGraph};
+use crate::theme_resolve::{parse_color as resolve_color, FontFace, LineType, ResolvedTheme};
 use crate::{OutputFormat, RenderOptions};
 use anyhow::{Context, Result};
 use image::{imageops::FilterType, ImageEncoder, RgbImage};
-use plotters::coord::ranged1d::{KeyPointHint, NoDefaultFormatting, Ranged, ValueFormatter};
+use plotters::coord::ranged1d::{
+    BoldPoints, KeyPointHint, NoDefaultFormatting, Ranged, ValueFormatter,
+};
 use plotters::coord::types::RangedCoordf64;
+use plotters::element::{Drawable, PointCollection};
 use plotters::prelude::*;
 use plotters::style::{
     text_anchor::{HPos, Pos, VPos},
     FontStyle, FontTransform,
 };
+use plotters_backend::{BackendColor, BackendCoord, BackendTextStyle, DrawingErrorKind};
+use std::borrow::Cow;
 use std::ops::Range;
 
 const PNG_SUPERSAMPLING_SCALE: u32 = 2;
 
+/// Canvases smaller than this (in either dimension) scale fonts, margins,
+/// markers and label areas down proportionally
+const COMPACT_LAYOUT_SIZE: f64 = 500.0;
+/// Below this, tick labels and axis titles are dropped so the data keeps the space
+const THUMBNAIL_SIZE: f64 = 250.0;
+/// Smallest layout scale applied to compact canvases
+const MIN_LAYOUT_SCALE: f64 = 0.5;
+/// Font size of panel captions and facet_grid row strips (at 1x), unless the facet
+/// sets `label_size`
+const PANEL_TITLE_SIZE: f64 = 15.0;
+/// Smallest plot title drawn over a facet grid, relative to the panel captions
+const FACET_TITLE_RATIO: f64 = 1.2;
+
+/// Size-dependent layout adjustments, computed from the output dimensions
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct CanvasLayout {
+    /// Multiplier applied to every theme size and fixed layout constant
+    scale: f64,
+    /// False for thumbnails: no tick labels or axis titles
+    show_axis_text: bool,
+}
+
+impl CanvasLayout {
+    fn for_size(width: u32, height: u32) -> Self {
+        let smallest = width.min(height) as f64;
+        let scale = if smallest < COMPACT_LAYOUT_SIZE {
+            (smallest / COMPACT_LAYOUT_SIZE).max(MIN_LAYOUT_SCALE)
+        } else {
+            1.0
+        };
+        CanvasLayout {
+            scale,
+            show_axis_text: smallest >= THUMBNAIL_SIZE,
+        }
+    }
+}
+
 struct FixedKeyPointCoord {
     inner: RangedCoordf64,
     key_points: Vec<f64>,
@@ -62,31 +104,46 @@ impl ValueFormatter<f64> for FixedKeyPointCoord {
 
 /// Style configuration for line layers
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LineStyle {
     pub color: Option<String>,
     pub width: Option<f64>,
     pub alpha: Option<f64>,
+    /// Dash pattern: "solid" (default), "dashed", "dotted", or "dotdash"
+    pub linetype: Option<String>,
+    /// Draw an arrowhead at the last point of each line
+    pub arrow: bool,
+    /// Draw only the first, lowest, highest, and last point of each run that lands
+    /// in one pixel column (`line(simplify:)`)
+    pub simplify: bool,
 }
 
 /// Style configuration for point layers
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PointStyle {
     pub color: Option<String>,
     pub size: Option<f64>,
     pub shape: Option<String>,
     pub alpha: Option<f64>,
+    pub fill: Option<String>, // Fixed fill for an outlined glyph, whose outline is `color`
+    pub stroke_width: Option<f64>, // Outline width in pixels; alone, `color` fills and the outline is black
 }
 
 /// Style configuration for bar layers
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BarStyle {
     pub color: Option<String>,
     pub alpha: Option<f64>,
     pub width: Option<f64>,
+    pub border: Option<String>,    // Outline color; None draws no outline
+    pub border_width: Option<f64>, // Outline width in pixels (default 1)
 }
 
 /// Style configuration for ribbon layers
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RibbonStyle {
     pub color: Option<String>,
     pub alpha: Option<f64>,
@@ -94,6 +151,7 @@ pub struct RibbonStyle {
 
 /// Style configuration for boxplot layers
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BoxplotStyle {
     pub color: Option<String>,
     pub width: Option<f64>,
@@ -105,6 +163,7 @@ pub struct BoxplotStyle {
 
 /// Style configuration for violin layers
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ViolinStyle {
     pub color: Option<String>,
     pub width: Option<f64>,
@@ -114,29 +173,63 @@ pub struct ViolinStyle {
 
 /// Style configuration for density layers
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DensityStyle {
     pub color: Option<String>,
     pub alpha: Option<f64>,
+    pub fill: bool,
+}
+
+/// Style configuration for text label layers
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LabelStyle {
+    pub color: Option<String>,
+    pub size: Option<f64>,
+    pub alpha: Option<f64>,
 }
 
 /// Style configuration for heatmap layers
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct HeatmapStyle {
     pub alpha: Option<f64>,
     pub value_min: f64,
     pub value_max: f64,
 }
 
-fn scale_u32(value: u32, pixel_scale: u32) -> u32 {
-    value.saturating_mul(pixel_scale.max(1))
+fn scale_u32(value: u32, pixel_scale: f64) -> u32 {
+    (value as f64 * pixel_scale).round() as u32
+}
+
+fn scale_i32(value: i32, pixel_scale: f64) -> i32 {
+    (value as f64 * pixel_scale).round() as i32
 }
 
-fn scale_i32(value: i32, pixel_scale: u32) -> i32 {
-    value.saturating_mul(pixel_scale.max(1) as i32)
+fn scale_f64(value: f64, pixel_scale: f64) -> f64 {
+    value * pixel_scale
 }
 
-fn scale_f64(value: f64, pixel_scale: u32) -> f64 {
-    value * pixel_scale.max(1) as f64
+/// Top, bottom, left, and right strips `width` pixels thick, centered on the edges of
+/// the rectangle between two opposite corners. Filled strips keep the width in SVG
+/// output (which drops a rect's stroke width) and keep corners square.
+fn border_strips(a: (i32, i32), b: (i32, i32), width: u32) -> [[(i32, i32); 2]; 4] {
+    let (left, right) = (a.0.min(b.0), a.0.max(b.0));
+    let (top, bottom) = (a.1.min(b.1), a.1.max(b.1));
+    let inner = width as i32 / 2;
+    let outer = width as i32 - inner;
+    [
+        [(left - outer, top - outer), (right + outer, top + inner)],
+        [
+            (left - outer, bottom - inner),
+            (right + outer, bottom + outer),
+        ],
+        [(left - outer, top - outer), (left + inner, bottom + outer)],
+        [
+            (right - inner, top - outer),
+            (right + outer, bottom + outer),
+        ],
+    ]
 }
 
 fn to_stroke_width(value: f64) -> u32 {
@@ -155,8 +248,8 @@ fn to_marker_size(value: f64) -> i32 {
     }
 }
 
-fn scale_resolved_theme(theme: &ResolvedTheme, pixel_scale: u32) -> ResolvedTheme {
-    if pixel_scale <= 1 {
+fn scale_resolved_theme(theme: &ResolvedTheme, pixel_scale: f64) -> ResolvedTheme {
+    if pixel_scale == 1.0 {
         return theme.clone();
     }
 
@@ -166,16 +259,18 @@ fn scale_resolved_theme(theme: &ResolvedTheme, pixel_scale: u32) -> ResolvedThem
     scaled.legend_text.size = scale_f64(scaled.legend_text.size, pixel_scale);
     scaled.legend_margin = scale_f64(scaled.legend_margin, pixel_scale);
     scaled.legend_key_size = scale_f64(scaled.legend_key_size, pixel_scale);
+    scaled.margin = scaled.margin.map(|margin| margin * pixel_scale);
 
     for line in [
         scaled.panel_grid_major.as_mut(),
         scaled.panel_grid_minor.as_mut(),
         scaled.axis_line.as_mut(),
         scaled.axis_ticks.as_mut(),
-    ] {
-        if let Some(line) = line {
-            line.width = scale_f64(line.width, pixel_scale);
-        }
+    ]
+    .into_iter()
+    .flatten()
+    {
+        line.width = scale_f64(line.width, pixel_scale);
     }
 
     scaled.plot_background.border_width =
@@ -192,11 +287,11 @@ fn scale_resolved_theme(theme: &ResolvedTheme, pixel_scale: u32) -> ResolvedThem
 /// Convert angle to plotters FontTransform (90-degree increments only)
 fn angle_to_font_transform(angle: f64) -> FontTransform {
     let normalized = ((angle % 360.0) + 360.0) % 360.0;
-    if normalized >= 315.0 || normalized < 45.0 {
+    if !(45.0..315.0).contains(&normalized) {
         FontTransform::None
-    } else if normalized >= 45.0 && normalized < 135.0 {
+    } else if (45.0..135.0).contains(&normalized) {
         FontTransform::Rotate90
-    } else if normalized >= 135.0 && normalized < 225.0 {
+    } else if (135.0..225.0).contains(&normalized) {
         FontTransform::Rotate180
     } else {
         FontTransform::Rotate270
@@ -280,19 +375,137 @@ fn build_legend_text_style<'a>(theme: &'a ResolvedTheme) -> TextStyle<'a> {
     .color(&theme.legend_text.color)
 }
 
+/// Approximate advance width (in ems) of a character the font cannot measure.
+/// East Asian wide characters and emoji take a full em; joiners and combining marks take none.
+fn fallback_char_width(c: char) -> f64 {
+    match c as u32 {
+        0x0300..=0x036F | 0x200B..=0x200F | 0xFE00..=0xFE0F => 0.0,
+        0x1100..=0x115F
+        | 0x2E80..=0xA4CF
+        | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFF60
+        | 0xFFE0..=0xFFE6
+        | 0x1F300..=0x1FAFF
+        | 0x20000..=0x3FFFD => 1.0,
+        _ => 0.6,
+    }
+}
+
 fn estimate_text_size<DB: DrawingBackend>(
     area: &DrawingArea<DB, plotters::coord::Shift>,
     text: &str,
     style: &TextStyle,
     font_size: f64,
 ) -> (u32, u32) {
-    area.estimate_text_size(text, style).unwrap_or_else(|_| {
-        let width = ((text.chars().count() as f64) * font_size * 0.6)
-            .ceil()
-            .max(1.0) as u32;
+    let fallback = || {
+        let ems: f64 = text.chars().map(fallback_char_width).sum();
+        let width = (ems * font_size).ceil().max(1.0) as u32;
         let height = (font_size * 1.3).ceil().max(1.0) as u32;
         (width, height)
-    })
+    };
+
+    match area.estimate_text_size(text, style) {
+        Ok(size) if text.is_ascii() => size,
+        // The bundled font often lacks CJK/emoji/RTL glyphs, so a measurement can come back
+        // narrower than what a viewer with fallback fonts will draw; never size below the estimate.
+        Ok((width, height)) => {
+            let (fallback_width, fallback_height) = fallback();
+            (width.max(fallback_width), height.max(fallback_height))
+        }
+        Err(_) => fallback(),
+    }
+}
+
+/// Shrink a font size until `text` fits within `max_width` pixels (never below 8px at 1x).
+fn fit_font_size<DB: DrawingBackend>(
+    area: &DrawingArea<DB, plotters::coord::Shift>,
+    text: &str,
+    family: &str,
+    font_size: f64,
+    max_width: u32,
+    pixel_scale: f64,
+) -> f64 {
+    let style = TextStyle::from((family, font_size).into_font());
+    let (width, _) = estimate_text_size(area, text, &style, font_size);
+    if width <= max_width || width == 0 {
+        return font_size;
+    }
+    (font_size * max_width as f64 / width as f64).max(scale_f64(8.0, pixel_scale))
+}
+
+/// `text` cut short with a trailing `…` so it fits within `max_width` pixels
+fn ellipsize<DB: DrawingBackend>(
+    area: &DrawingArea<DB, plotters::coord::Shift>,
+    text: &str,
+    style: &TextStyle,
+    font_size: f64,
+    max_width: u32,
+) -> String {
+    if estimate_text_size(area, text, style, font_size).0 <= max_width {
+        return text.to_string();
+    }
+    let mut chars: Vec<char> = text.chars().collect();
+    while !chars.is_empty() {
+        chars.pop();
+        let shortened = format!("{}…", chars.iter().collect::<String>().trim_end());
+        if estimate_text_size(area, &shortened, style, font_size).0 <= max_width {
+            return shortened;
+        }
+    }
+    "…".to_string()
+}
+
+/// `text` broken into lines that fit within `max_width` pixels: at its own `\n`s, at
+/// spaces where it can, and between characters for a word wider than a whole line
+fn wrap_text<DB: DrawingBackend>(
+    area: &DrawingArea<DB, plotters::coord::Shift>,
+    text: &str,
+    style: &TextStyle,
+    font_size: f64,
+    max_width: u32,
+) -> Vec<String> {
+    text.split('\n')
+        .flat_map(|paragraph| wrap_paragraph(area, paragraph, style, font_size, max_width))
+        .collect()
+}
+
+fn wrap_paragraph<DB: DrawingBackend>(
+    area: &DrawingArea<DB, plotters::coord::Shift>,
+    text: &str,
+    style: &TextStyle,
+    font_size: f64,
+    max_width: u32,
+) -> Vec<String> {
+    let fits = |line: &str| estimate_text_size(area, line, style, font_size).0 <= max_width;
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let joined = if line.is_empty() {
+            word.to_string()
+        } else {
+            format!("{} {}", line, word)
+        };
+        if fits(&joined) {
+            line = joined;
+            continue;
+        }
+        if !line.is_empty() {
+            lines.push(std::mem::take(&mut line));
+        }
+        for c in word.chars() {
+            line.push(c);
+            if !fits(&line) && line.chars().count() > 1 {
+                line.pop();
+                lines.push(std::mem::replace(&mut line, c.to_string()));
+            }
+        }
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
 }
 
 fn max_text_dimensions<DB: DrawingBackend, I, S>(
@@ -317,33 +530,46 @@ fn blank_tick_label(_value: &f64) -> String {
     String::new()
 }
 
-fn format_axis_tick(value: f64, transform: AxisTransform) -> String {
-    crate::scale::format_nice_number(transform.invert(value))
+pub(crate) fn format_axis_tick(value: f64, scale: &Scale) -> String {
+    let value = scale.transform.invert(value);
+    match &scale.labels.format {
+        Some(format) => format.format(value),
+        None => crate::scale::format_nice_number(value),
+    }
+}
+
+/// Display text for each category, shortened per the scale's `max_label_len`
+fn category_labels(scale: &Scale) -> Vec<String> {
+    scale
+        .categories
+        .iter()
+        .map(|c| scale.labels.category(c).into_owned())
+        .collect()
 }
 
 fn estimate_numeric_tick_label_width<DB: DrawingBackend>(
     area: &DrawingArea<DB, plotters::coord::Shift>,
-    tick_positions: &[f64],
-    range: (f64, f64),
-    transform: AxisTransform,
+    scale: &Scale,
     style: &TextStyle,
     font_size: f64,
 ) -> u32 {
-    let labels: Vec<String> = if !tick_positions.is_empty() {
-        tick_positions
+    let range = scale.range;
+    let labels: Vec<String> = if !scale.tick_positions.is_empty() {
+        scale
+            .tick_positions
             .iter()
-            .map(|v| format_axis_tick(*v, transform))
+            .map(|v| format_axis_tick(*v, scale))
             .collect()
     } else {
         let coord: RangedCoordf64 = (range.0..range.1).into();
         let mut lbls: Vec<String> = coord
             .key_points(11)
             .into_iter()
-            .map(|value| format_axis_tick(value, transform))
+            .map(|value| format_axis_tick(value, scale))
             .collect();
         if lbls.is_empty() {
-            lbls.push(format_axis_tick(range.0, transform));
-            lbls.push(format_axis_tick(range.1, transform));
+            lbls.push(format_axis_tick(range.0, scale));
+            lbls.push(format_axis_tick(range.1, scale));
         }
         lbls
     };
@@ -353,7 +579,7 @@ fn estimate_numeric_tick_label_width<DB: DrawingBackend>(
     max_width
 }
 
-fn effective_tick_label_gap(theme: &ResolvedTheme, pixel_scale: u32) -> u32 {
+fn effective_tick_label_gap(theme: &ResolvedTheme, pixel_scale: f64) -> u32 {
     // Plotters' default tick size is 5px, which yields a 10px label gap.
     if theme.axis_ticks.is_some() || theme.axis_line.is_none() {
         scale_u32(10, pixel_scale)
@@ -368,6 +594,8 @@ struct AxisLayout {
     y_label_area_size: u32,
     x_tick_gap: u32,
     y_tick_gap: u32,
+    show_x_tick_labels: bool,
+    show_y_tick_labels: bool,
     manual_rotated_x_labels: bool,
     max_y_label_width: u32,
     y_desc_width: u32,
@@ -381,38 +609,66 @@ fn calculate_axis_layout<DB: DrawingBackend>(
     theme: &ResolvedTheme,
     y_axis_style: &TextStyle,
     axis_desc_style: &TextStyle,
-    pixel_scale: u32,
+    pixel_scale: f64,
+    show_tick_labels: bool,
 ) -> AxisLayout {
     let font_size = theme.axis_text.size.max(1.0);
+    let outer_padding: u32 = scale_u32(4, pixel_scale);
+
+    if !show_tick_labels {
+        // Thumbnails: axis lines only, the data gets the rest of the panel
+        return AxisLayout {
+            x_label_area_size: outer_padding,
+            y_label_area_size: outer_padding,
+            x_tick_gap: 0,
+            y_tick_gap: 0,
+            show_x_tick_labels: false,
+            show_y_tick_labels: false,
+            manual_rotated_x_labels: false,
+            max_y_label_width: 0,
+            y_desc_width: 0,
+            y_label_to_desc_gap: 0,
+            outer_padding,
+        };
+    }
+
+    let normalized_angle = ((theme.axis_text.angle % 360.0) + 360.0) % 360.0;
+    let rotated_x_labels =
+        (45.0..135.0).contains(&normalized_angle) || (225.0..315.0).contains(&normalized_angle);
+    // Rotated numeric labels stand on end, so their width sets the axis height
+    let rotated_numeric_x =
+        rotated_x_labels && !panel.x_scale.is_categorical && panel.x_scale.datetime.is_none();
 
     let (max_x_label_width, max_x_label_height) = if panel.x_scale.is_categorical {
         max_text_dimensions(
             area,
-            panel.x_scale.categories.iter().map(String::as_str),
+            category_labels(&panel.x_scale),
             y_axis_style,
             font_size,
         )
+    } else if rotated_numeric_x {
+        let width =
+            estimate_numeric_tick_label_width(area, &panel.x_scale, y_axis_style, font_size);
+        (width, font_size.ceil() as u32)
     } else {
         (0, 0)
     };
 
-    let max_y_label_width = if panel.y_scale.is_categorical {
+    let show_x_tick_labels = panel.show_x_tick_labels;
+    let show_y_tick_labels = panel.show_y_tick_labels;
+
+    let max_y_label_width = if !show_y_tick_labels {
+        0
+    } else if panel.y_scale.is_categorical {
         let (max_width, _) = max_text_dimensions(
             area,
-            panel.y_scale.categories.iter().map(String::as_str),
+            category_labels(&panel.y_scale),
             y_axis_style,
             font_size,
         );
         max_width
     } else {
-        estimate_numeric_tick_label_width(
-            area,
-            &panel.y_scale.tick_positions,
-            panel.y_scale.range,
-            panel.y_scale.transform,
-            y_axis_style,
-            font_size,
-        )
+        estimate_numeric_tick_label_width(area, &panel.y_scale, y_axis_style, font_size)
     };
 
     let (_, x_desc_height) = panel
@@ -430,10 +686,9 @@ fn calculate_axis_layout<DB: DrawingBackend>(
         })
         .unwrap_or(0);
 
-    let normalized_angle = ((theme.axis_text.angle % 360.0) + 360.0) % 360.0;
-    let rotated_x_labels =
-        (45.0..135.0).contains(&normalized_angle) || (225.0..315.0).contains(&normalized_angle);
-    let manual_rotated_x_labels = rotated_x_labels && panel.x_scale.is_categorical;
+    let manual_rotated_x_labels = (panel.x_scale.is_categorical || rotated_numeric_x)
+        && rotated_x_labels
+        && show_x_tick_labels;
     let x_label_vertical_extent = if rotated_x_labels {
         max_x_label_width
     } else {
@@ -449,18 +704,19 @@ fn calculate_axis_layout<DB: DrawingBackend>(
     let y_tick_gap: u32 = base_tick_gap;
     let x_label_to_desc_gap: u32 = scale_u32(6, pixel_scale);
     let y_label_to_desc_gap: u32 = scale_u32(16, pixel_scale);
-    let outer_padding: u32 = scale_u32(4, pixel_scale);
 
-    let x_tick_block = if panel.x_scale.is_categorical {
+    let x_tick_block = if panel.x_scale.is_categorical || rotated_numeric_x {
         x_tick_gap.saturating_add(x_label_vertical_extent)
     } else {
         x_tick_gap.saturating_add(font_size.ceil() as u32)
     };
 
+    // Numeric labels are right-aligned against the ticks, so the widest one sets the
+    // room left of the axis too
     let y_tick_block = if panel.y_scale.is_categorical {
         y_tick_gap.saturating_add(max_y_label_width)
     } else {
-        y_tick_gap.saturating_add(font_size.ceil() as u32)
+        y_tick_gap.saturating_add(max_y_label_width.max(font_size.ceil() as u32))
     };
 
     let x_desc_block = if panel.x_label.is_some() {
@@ -475,20 +731,36 @@ fn calculate_axis_layout<DB: DrawingBackend>(
         0
     };
 
-    let x_label_area_size = x_tick_block
-        .saturating_add(x_desc_block)
-        .saturating_add(outer_padding)
-        .max(scale_u32(30, pixel_scale));
-    let y_label_area_size = y_tick_block
-        .saturating_add(y_desc_block)
-        .saturating_add(outer_padding)
-        .max(scale_u32(40, pixel_scale));
+    // Unlabeled (interior facet) axes only need room for the tick marks
+    let tick_mark_block = scale_u32(5, pixel_scale);
+    let x_label_area_size = if show_x_tick_labels {
+        x_tick_block
+            .saturating_add(x_desc_block)
+            .saturating_add(outer_padding)
+            .max(scale_u32(30, pixel_scale))
+    } else {
+        tick_mark_block
+            .saturating_add(x_desc_block)
+            .saturating_add(outer_padding)
+    };
+    let y_label_area_size = if show_y_tick_labels {
+        y_tick_block
+            .saturating_add(y_desc_block)
+            .saturating_add(outer_padding)
+            .max(scale_u32(40, pixel_scale))
+    } else {
+        tick_mark_block
+            .saturating_add(y_desc_block)
+            .saturating_add(outer_padding)
+    };
 
     AxisLayout {
         x_label_area_size,
         y_label_area_size,
         x_tick_gap,
         y_tick_gap,
+        show_x_tick_labels,
+        show_y_tick_labels,
         manual_rotated_x_labels,
         max_y_label_width,
         y_desc_width,
@@ -525,10 +797,25 @@ where
     };
     let label_style = x_axis_style.clone().pos(anchor);
 
-    for (idx, label) in panel.x_scale.categories.iter().enumerate() {
+    let ticks: Vec<(f64, String)> = if panel.x_scale.is_categorical {
+        category_labels(&panel.x_scale)
+            .into_iter()
+            .enumerate()
+            .map(|(idx, label)| (idx as f64, label))
+            .collect()
+    } else {
+        panel
+            .x_scale
+            .tick_positions
+            .iter()
+            .map(|&t| (t, format_axis_tick(t, &panel.x_scale)))
+            .collect()
+    };
+
+    for (x, label) in &ticks {
         let (abs_x, _) = chart
             .plotting_area()
-            .map_coordinate(&(idx as f64, panel.y_scale.range.0));
+            .map_coordinate(&(*x, panel.y_scale.range.0));
         let rel_x = abs_x - panel_x.start;
         let rel_y = plot_bottom + axis_layout.x_tick_gap as i32;
         area.draw_text(label, &label_style, (rel_x, rel_y))
@@ -579,137 +866,850 @@ where
     Ok(())
 }
 
-fn datetime_tick_values(panel: &PanelScene) -> Option<Vec<f64>> {
-    let datetime = panel.x_scale.datetime.as_ref()?;
-    let interval = datetime.interval_seconds?;
-    if interval <= 0.0 || !interval.is_finite() {
-        return None;
+/// Major grid lines the plotters mesh can't draw: dashed or dotted ones, and the
+/// separators `grid_separators` puts between category slots
+struct ManualGrid {
+    style: Option<ShapeStyle>,
+    /// Dash lengths in pixels; None for solid lines
+    pattern: Option<Vec<f64>>,
+    x_separators: bool,
+    y_separators: bool,
+}
+
+impl ManualGrid {
+    fn new(panel: &PanelScene, theme: &ResolvedTheme) -> Self {
+        let Some(major) = &theme.panel_grid_major else {
+            return ManualGrid {
+                style: None,
+                pattern: None,
+                x_separators: false,
+                y_separators: false,
+            };
+        };
+        let stroke_width = to_stroke_width(major.width);
+        let linetype = match major.linetype {
+            LineType::Solid => None,
+            LineType::Dashed => Some("dashed"),
+            LineType::Dotted => Some("dotted"),
+        };
+        let pattern = dash_pattern(linetype).map(|pattern| {
+            let unit = stroke_width.max(1) as f64;
+            pattern.iter().map(|len| len * unit).collect()
+        });
+        ManualGrid {
+            style: Some(major.color.stroke_width(stroke_width)),
+            pattern,
+            x_separators: theme.grid_separators && panel.x_scale.is_categorical,
+            y_separators: theme.grid_separators && panel.y_scale.is_categorical,
+        }
     }
+}
 
-    let (mut start, mut end) = panel.x_scale.domain;
-    if start > end {
-        std::mem::swap(&mut start, &mut end);
+/// Draw the `ManualGrid` lines over the mesh, at the same bold key points the mesh
+/// used (`x_label_count`/`y_label_count`) or at the category slot edges
+fn draw_manual_grid<DB, X, Y>(
+    chart: &mut ChartContext<'_, DB, Cartesian2d<X, Y>>,
+    panel: &PanelScene,
+    grid: &ManualGrid,
+    x_label_count: usize,
+    y_label_count: usize,
+) -> Result<()>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+    X: Ranged<ValueType = f64>,
+    Y: Ranged<ValueType = f64>,
+{
+    let Some(style) = grid.style else {
+        return Ok(());
+    };
+    let slot_edges = |scale: &Scale| -> Vec<f64> {
+        (1..scale.categories.len())
+            .map(|k| k as f64 - 0.5)
+            .collect()
+    };
+    let dashed = grid.pattern.is_some();
+    let coord = chart.as_coord_spec();
+    let x_lines = if grid.x_separators {
+        slot_edges(&panel.x_scale)
+    } else if dashed {
+        coord.x_spec().key_points(BoldPoints(x_label_count))
+    } else {
+        Vec::new()
+    };
+    let y_lines = if grid.y_separators {
+        slot_edges(&panel.y_scale)
+    } else if dashed {
+        coord.y_spec().key_points(BoldPoints(y_label_count))
+    } else {
+        Vec::new()
+    };
+
+    let (x_range, y_range) = (chart.x_range(), chart.y_range());
+    let mut lines: Vec<Vec<(f64, f64)>> = Vec::new();
+    lines.extend(
+        x_lines
+            .into_iter()
+            .map(|x| vec![(x, y_range.start), (x, y_range.end)]),
+    );
+    lines.extend(
+        y_lines
+            .into_iter()
+            .map(|y| vec![(x_range.start, y), (x_range.end, y)]),
+    );
+    if let Some(pattern) = &grid.pattern {
+        lines = lines
+            .iter()
+            .flat_map(|line| {
+                dash_polyline(
+                    line,
+                    |p| {
+                        let (x, y) = chart.backend_coord(&p);
+                        (x as f64, y as f64)
+                    },
+                    pattern,
+                )
+            })
+            .collect();
     }
+    chart
+        .draw_series(lines.into_iter().map(|line| PathElement::new(line, style)))
+        .context("Failed to draw grid lines")?;
 
-    if !start.is_finite() || !end.is_finite() {
-        return None;
+    Ok(())
+}
+
+/// Indices of the points a polyline needs once mapped to pixels. Within each run of
+/// consecutive points in the same pixel column only the first, lowest, highest, and
+/// last can change what is drawn, so a million-point series shrinks to about four
+/// points per column without a visible difference.
+fn pixel_column_extremes(pixels: &[BackendCoord]) -> Vec<usize> {
+    let mut keep = Vec::new();
+    let mut start = 0;
+    while start < pixels.len() {
+        let column = pixels[start].0;
+        let mut end = start + 1;
+        let (mut low, mut high) = (start, start);
+        while end < pixels.len() && pixels[end].0 == column {
+            if pixels[end].1 < pixels[low].1 {
+                low = end;
+            }
+            if pixels[end].1 > pixels[high].1 {
+                high = end;
+            }
+            end += 1;
+        }
+        let mut picks = [start, low, high, end - 1];
+        picks.sort_unstable();
+        for i in picks {
+            if keep.last() != Some(&i) {
+                keep.push(i);
+            }
+        }
+        start = end;
     }
+    keep
+}
 
-    if (end - start).abs() <= f64::EPSILON {
-        return Some(vec![start]);
+/// On/off dash lengths in multiples of the stroke width; None for a solid line
+fn dash_pattern(linetype: Option<&str>) -> Option<&'static [f64]> {
+    match linetype?.to_lowercase().as_str() {
+        "dashed" => Some(&[4.0, 2.0]),
+        "dotted" => Some(&[1.0, 1.5]),
+        "dotdash" => Some(&[1.0, 1.5, 4.0, 1.5]),
+        _ => None,
     }
+}
 
-    let mut ticks = Vec::new();
-    let mut current = start;
-    let max_ticks = 1_000;
-    while current <= end + interval * 1e-9 && ticks.len() < max_ticks {
-        ticks.push(current);
-        current += interval;
+/// Split a polyline into the "on" spans of a dash pattern. Lengths are measured
+/// after `to_pixel`, so dashes look the same on both axes whatever the data ranges;
+/// span points are interpolated in the input coordinates.
+fn dash_polyline<P>(points: &[(f64, f64)], to_pixel: P, pattern: &[f64]) -> Vec<Vec<(f64, f64)>>
+where
+    P: Fn((f64, f64)) -> (f64, f64),
+{
+    let mut spans = Vec::new();
+    let mut current: Vec<(f64, f64)> = Vec::new();
+    // Even indices of the pattern draw, odd ones skip
+    let mut dash = 0;
+    let mut remaining = pattern[0];
+    for pair in points.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        let (pa, pb) = (to_pixel(a), to_pixel(b));
+        let length = (pb.0 - pa.0).hypot(pb.1 - pa.1);
+        let along = |distance: f64| {
+            let t = if length > 0.0 { distance / length } else { 0.0 };
+            (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t)
+        };
+
+        if dash % 2 == 0 && current.is_empty() {
+            current.push(a);
+        }
+        let mut travelled = 0.0;
+        while length - travelled > remaining {
+            travelled += remaining;
+            current.push(along(travelled));
+            if dash % 2 == 0 {
+                spans.push(std::mem::take(&mut current));
+            }
+            dash = (dash + 1) % pattern.len();
+            remaining = pattern[dash];
+        }
+        if length.is_finite() {
+            remaining -= length - travelled;
+        }
+        if dash % 2 == 0 {
+            current.push(b);
+        } else {
+            current.clear();
+        }
     }
+    if current.len() > 1 {
+        spans.push(current);
+    }
+    spans
+}
 
-    Some(ticks)
+/// Corners of an arrowhead pointing at the tip of the pixel-space segment
+/// `from -> tip`, as offsets from the tip: the tip itself, then the two barbs
+/// `length` pixels back. None when the segment has no direction.
+fn arrowhead(from: (f64, f64), tip: (f64, f64), length: f64) -> Option<Vec<BackendCoord>> {
+    let (dx, dy) = (tip.0 - from.0, tip.1 - from.1);
+    let norm = dx.hypot(dy);
+    if norm == 0.0 || !norm.is_finite() {
+        return None;
+    }
+    let (ux, uy) = (dx / norm, dy / norm);
+    // Barbs 30 degrees either side of the shaft
+    let half_width = length * 0.58;
+    let barb = |side: f64| {
+        (
+            (-ux * length - uy * half_width * side).round() as i32,
+            (-uy * length + ux * half_width * side).round() as i32,
+        )
+    };
+    Some(vec![(0, 0), barb(1.0), barb(-1.0)])
 }
 
-/// The Rendering Backend
-pub struct Canvas;
+/// The scales' ranges widened about their centres so a data unit spans as many
+/// pixels across as it does up, in a plotting area `width` x `height` pixels
+fn equal_unit_ranges(
+    x_scale: &Scale,
+    y_scale: &Scale,
+    (width, height): (u32, u32),
+) -> (Range<f64>, Range<f64>) {
+    let (width, height) = (width.max(1) as f64, height.max(1) as f64);
+    let x_span = (x_scale.range.1 - x_scale.range.0).abs();
+    let y_span = (y_scale.range.1 - y_scale.range.0).abs();
+    // The axis with the most data per pixel sets the units for both
+    let per_pixel = (x_span / width).max(y_span / height);
+    let widen = |(lo, hi): (f64, f64), pixels: f64| {
+        let (centre, half) = ((lo + hi) / 2.0, per_pixel * pixels / 2.0);
+        (centre - half)..(centre + half)
+    };
+    (widen(x_scale.range, width), widen(y_scale.range, height))
+}
 
-impl Canvas {
-    /// Execute the SceneGraph and produce a byte vector (PNG or SVG)
-    pub fn execute(scene: SceneGraph, options: &RenderOptions) -> Result<Vec<u8>> {
-        match options.format {
-            OutputFormat::Png => Self::render_png(scene, options),
-            OutputFormat::Svg => Self::render_svg(scene, options),
+/// What `Canvas::panel_frame` reserves around a panel's plotting area
+struct PanelFrame {
+    axis_layout: AxisLayout,
+    margin: u32,
+    /// The facet title, shrunk and ellipsized to the panel width
+    title: String,
+    title_size: f64,
+    has_caption: bool,
+    /// The plotting area's size in pixels, before coord_fixed() shrinks it
+    plot_size: (u32, u32),
+}
+
+/// A coord_fixed() plotting area: its size, and its offset from the top left of
+/// the room `PanelFrame::plot_size` leaves for it
+#[derive(Debug, Clone, Copy)]
+struct FixedPlacement {
+    size: (u32, u32),
+    lead: (u32, u32),
+}
+
+/// The theme a panel draws with: scale_x(rotate: ...) takes the place of the
+/// theme's x tick label angle
+fn panel_theme<'a>(theme: &'a ResolvedTheme, panel: &PanelScene) -> Cow<'a, ResolvedTheme> {
+    match panel.x_scale.labels.angle {
+        Some(angle) => {
+            let mut rotated = theme.clone();
+            rotated.axis_text.angle = angle;
+            Cow::Owned(rotated)
         }
+        None => Cow::Borrowed(theme),
     }
+}
 
-    fn render_png(scene: SceneGraph, _options: &RenderOptions) -> Result<Vec<u8>> {
-        let target_width = scene.width;
-        let target_height = scene.height;
-        let width = target_width
-            .checked_mul(PNG_SUPERSAMPLING_SCALE)
-            .context("PNG width overflow during supersampling")?;
-        let height = target_height
-            .checked_mul(PNG_SUPERSAMPLING_SCALE)
-            .context("PNG height overflow during supersampling")?;
-        let mut buffer = vec![0u8; (width * height * 3) as usize];
-        let mut supersampled_scene = scene;
-        supersampled_scene.width = width;
-        supersampled_scene.height = height;
+/// The largest plotting area within `width` x `height` pixels that spends `ratio`
+/// times as many pixels on a y unit as on an x unit
+fn fixed_aspect_size(
+    x_scale: &Scale,
+    y_scale: &Scale,
+    (width, height): (u32, u32),
+    ratio: f64,
+) -> (u32, u32) {
+    let x_span = (x_scale.range.1 - x_scale.range.0).abs();
+    let y_span = (y_scale.range.1 - y_scale.range.0).abs();
+    let aspect = ratio * y_span / x_span;
+    if !(aspect.is_finite() && aspect > 0.0) {
+        return (width, height);
+    }
+    if height as f64 > width as f64 * aspect {
+        (width, (width as f64 * aspect).round() as u32)
+    } else {
+        (
+            (height as f64 / aspect).round().min(width as f64) as u32,
+            height,
+        )
+    }
+}
 
-        {
-            let root = BitMapBackend::with_buffer(&mut buffer, (width, height)).into_drawing_area();
-            Self::draw_scene(&root, &supersampled_scene, PNG_SUPERSAMPLING_SCALE)?;
-        }
+/// Several disjoint polylines drawn as one element, so a dashed legend key is a
+/// single legend entry
+struct MultiPath {
+    spans: Vec<Vec<BackendCoord>>,
+    style: ShapeStyle,
+}
 
-        let image = RgbImage::from_raw(width, height, buffer)
-            .context("Failed to build supersampled PNG image")?;
-        let downsampled =
-            image::imageops::resize(&image, target_width, target_height, FilterType::Lanczos3);
-        let downsampled_buffer = downsampled.into_raw();
+impl MultiPath {
+    /// A horizontal legend key from `start`, `length` pixels long
+    fn legend_key(
+        start: BackendCoord,
+        length: i32,
+        pattern: Option<&[f64]>,
+        style: ShapeStyle,
+    ) -> Self {
+        let end = (start.0 + length, start.1);
+        let spans = match pattern {
+            Some(pattern) => dash_polyline(
+                &[
+                    (start.0 as f64, start.1 as f64),
+                    (end.0 as f64, end.1 as f64),
+                ],
+                |p| p,
+                pattern,
+            )
+            .into_iter()
+            .map(|span| {
+                span.into_iter()
+                    .map(|(x, y)| (x.round() as i32, y.round() as i32))
+                    .collect()
+            })
+            .collect(),
+            None => vec![vec![start, end]],
+        };
+        MultiPath { spans, style }
+    }
+}
 
-        // Encode as PNG
-        let mut png_bytes = Vec::new();
-        {
-            let encoder = image::codecs::png::PngEncoder::new(&mut png_bytes);
-            encoder
-                .write_image(
-                    &downsampled_buffer,
-                    target_width,
-                    target_height,
-                    image::ColorType::Rgb8,
-                )
-                .context("Failed to encode PNG")?;
-        }
+impl<'a> PointCollection<'a, BackendCoord> for &'a MultiPath {
+    type Point = &'a BackendCoord;
+    type IntoIter = std::iter::Flatten<std::slice::Iter<'a, Vec<BackendCoord>>>;
 
-        Ok(png_bytes)
+    fn point_iter(self) -> Self::IntoIter {
+        self.spans.iter().flatten()
     }
+}
 
-    fn render_svg(scene: SceneGraph, _options: &RenderOptions) -> Result<Vec<u8>> {
-        let mut buffer = String::new();
-        {
-            let root = SVGBackend::with_string(&mut buffer, (scene.width, scene.height))
-                .into_drawing_area();
-            Self::draw_scene(&root, &scene, 1)?;
+impl<DB: DrawingBackend> Drawable<DB> for MultiPath {
+    fn draw<I: Iterator<Item = BackendCoord>>(
+        &self,
+        mut points: I,
+        backend: &mut DB,
+        _: (u32, u32),
+    ) -> Result<(), DrawingErrorKind<DB::ErrorType>> {
+        for span in &self.spans {
+            backend.draw_path(points.by_ref().take(span.len()), &self.style)?;
         }
-        Ok(buffer.into_bytes())
+        Ok(())
     }
+}
 
-    fn draw_scene<DB: DrawingBackend>(
-        root: &DrawingArea<DB, plotters::coord::Shift>,
-        scene: &SceneGraph,
-        pixel_scale: u32,
-    ) -> Result<()>
-    where
-        DB::ErrorType: 'static,
-    {
-        // Resolve theme once at the start
-        let resolved_theme = scale_resolved_theme(&scene.theme.resolve(), pixel_scale);
-
-        // Fill background with resolved theme color
-        root.fill(&resolved_theme.plot_background.fill)
-            .context("Failed to fill background")?;
+/// A filled point glyph with an outline `width` pixels wide just outside it, in pixel
+/// offsets from the point. Polygon outlines are one quad per edge, so a translucent
+/// outline doesn't double up at the corners.
+#[derive(Clone)]
+struct OutlinedMarker {
+    outline: MarkerOutline,
+    fill: ShapeStyle,
+    stroke: ShapeStyle,
+}
 
-        // Calculate header height for title + subtitle
-        let title_size = resolved_theme.plot_title.size;
-        let has_title = scene.labels.title.is_some();
-        let has_subtitle = scene.labels.subtitle.is_some();
-        let has_caption = scene.labels.caption.is_some();
+#[derive(Clone)]
+enum MarkerOutline {
+    Circle { radius: u32, width: u32 },
+    // The glyph's corners, then the same corners pushed out by the outline width
+    Polygon(Vec<BackendCoord>),
+}
 
-        let header_height: u32 = if has_title || has_subtitle {
-            let mut h = scale_u32(5, pixel_scale); // top padding
-            if has_title {
-                h += title_size as u32 + scale_u32(5, pixel_scale);
-            }
-            if has_subtitle {
-                h += (title_size * 0.7) as u32 + scale_u32(5, pixel_scale);
-            }
+impl OutlinedMarker {
+    fn new(shape: &str, size: i32, width: u32, fill: ShapeStyle, stroke: RGBAColor) -> Self {
+        let polygon = |corners: Vec<BackendCoord>, inradius: f64| {
+            let grow = 1.0 + width as f64 / inradius.max(1.0);
+            let outer: Vec<BackendCoord> = corners
+                .iter()
+                .map(|&(x, y)| {
+                    (
+                        (x as f64 * grow).round() as i32,
+                        (y as f64 * grow).round() as i32,
+                    )
+                })
+                .collect();
+            MarkerOutline::Polygon(corners.into_iter().chain(outer).collect())
+        };
+        let outline = match shape {
+            "square" => polygon(
+                vec![(-size, -size), (size, -size), (size, size), (-size, size)],
+                size as f64,
+            ),
+            // Corners where plotters' TriangleMarker puts them
+            "triangle" => polygon(
+                [-90.0f64, -210.0, -330.0]
+                    .iter()
+                    .map(|deg| {
+                        let rad = deg.to_radians();
+                        (
+                            (rad.cos() * size as f64).ceil() as i32,
+                            (rad.sin() * size as f64).ceil() as i32,
+                        )
+                    })
+                    .collect(),
+                size as f64 / 2.0,
+            ),
+            "diamond" => polygon(
+                vec![(0, -size), (size, 0), (0, size), (-size, 0)],
+                size as f64 / std::f64::consts::SQRT_2,
+            ),
+            _ => MarkerOutline::Circle {
+                radius: size.max(0) as u32,
+                width,
+            },
+        };
+        OutlinedMarker {
+            outline,
+            fill,
+            stroke: stroke.stroke_width(width),
+        }
+    }
+}
+
+impl<'a> PointCollection<'a, BackendCoord> for &'a OutlinedMarker {
+    type Point = &'a BackendCoord;
+    type IntoIter = std::slice::Iter<'a, BackendCoord>;
+
+    fn point_iter(self) -> Self::IntoIter {
+        match &self.outline {
+            MarkerOutline::Circle { .. } => std::slice::from_ref(&(0, 0)).iter(),
+            MarkerOutline::Polygon(corners) => corners.iter(),
+        }
+    }
+}
+
+impl<DB: DrawingBackend> Drawable<DB> for OutlinedMarker {
+    fn draw<I: Iterator<Item = BackendCoord>>(
+        &self,
+        points: I,
+        backend: &mut DB,
+        _: (u32, u32),
+    ) -> Result<(), DrawingErrorKind<DB::ErrorType>> {
+        let points: Vec<BackendCoord> = points.collect();
+        match &self.outline {
+            MarkerOutline::Circle { radius, width } => {
+                let Some(&center) = points.first() else {
+                    return Ok(());
+                };
+                backend.draw_circle(center, *radius, &self.fill, true)?;
+                if *width > 0 {
+                    // The stroke straddles its circle, so center it half a width out
+                    backend.draw_circle(center, radius + width / 2, &self.stroke, false)?;
+                }
+            }
+            MarkerOutline::Polygon(_) => {
+                let (inner, outer) = points.split_at(points.len() / 2);
+                backend.fill_polygon(inner.iter().copied(), &self.fill)?;
+                if self.stroke.stroke_width > 0 {
+                    for i in 0..inner.len() {
+                        let j = (i + 1) % inner.len();
+                        backend.fill_polygon(
+                            [inner[i], inner[j], outer[j], outer[i]],
+                            &self.stroke.color,
+                        )?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A bitmap backend that draws every piece of text as a solid box in the text's
+/// color, sized from `fallback_char_width` instead of a font. Layout measures
+/// text through the same estimate, so the picture doesn't depend on which fonts
+/// the machine has (`RenderOptions.placeholder_text`)
+struct PlaceholderText<DB>(DB);
+
+impl<DB> PlaceholderText<DB> {
+    fn text_size<S: BackendTextStyle>(text: &str, style: &S) -> (u32, u32) {
+        let ems: f64 = text.chars().map(fallback_char_width).sum();
+        let size = style.size();
+        ((ems * size).ceil() as u32, (size * 0.7).ceil() as u32)
+    }
+}
+
+impl<DB: DrawingBackend> DrawingBackend for PlaceholderText<DB> {
+    type ErrorType = DB::ErrorType;
+
+    fn get_size(&self) -> (u32, u32) {
+        self.0.get_size()
+    }
+
+    fn ensure_prepared(&mut self) -> Result<(), DrawingErrorKind<Self::ErrorType>> {
+        self.0.ensure_prepared()
+    }
+
+    fn present(&mut self) -> Result<(), DrawingErrorKind<Self::ErrorType>> {
+        self.0.present()
+    }
+
+    fn draw_pixel(
+        &mut self,
+        point: BackendCoord,
+        color: BackendColor,
+    ) -> Result<(), DrawingErrorKind<Self::ErrorType>> {
+        self.0.draw_pixel(point, color)
+    }
+
+    fn draw_line<S: plotters_backend::BackendStyle>(
+        &mut self,
+        from: BackendCoord,
+        to: BackendCoord,
+        style: &S,
+    ) -> Result<(), DrawingErrorKind<Self::ErrorType>> {
+        self.0.draw_line(from, to, style)
+    }
+
+    fn draw_rect<S: plotters_backend::BackendStyle>(
+        &mut self,
+        upper_left: BackendCoord,
+        bottom_right: BackendCoord,
+        style: &S,
+        fill: bool,
+    ) -> Result<(), DrawingErrorKind<Self::ErrorType>> {
+        self.0.draw_rect(upper_left, bottom_right, style, fill)
+    }
+
+    fn draw_path<S: plotters_backend::BackendStyle, I: IntoIterator<Item = BackendCoord>>(
+        &mut self,
+        path: I,
+        style: &S,
+    ) -> Result<(), DrawingErrorKind<Self::ErrorType>> {
+        self.0.draw_path(path, style)
+    }
+
+    fn draw_circle<S: plotters_backend::BackendStyle>(
+        &mut self,
+        center: BackendCoord,
+        radius: u32,
+        style: &S,
+        fill: bool,
+    ) -> Result<(), DrawingErrorKind<Self::ErrorType>> {
+        self.0.draw_circle(center, radius, style, fill)
+    }
+
+    fn fill_polygon<S: plotters_backend::BackendStyle, I: IntoIterator<Item = BackendCoord>>(
+        &mut self,
+        vert: I,
+        style: &S,
+    ) -> Result<(), DrawingErrorKind<Self::ErrorType>> {
+        self.0.fill_polygon(vert, style)
+    }
+
+    fn draw_text<S: BackendTextStyle>(
+        &mut self,
+        text: &str,
+        style: &S,
+        pos: BackendCoord,
+    ) -> Result<(), DrawingErrorKind<Self::ErrorType>> {
+        let (width, height) = Self::text_size(text, style);
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+        let (width, height) = (width as i32, height as i32);
+        let dx = match style.anchor().h_pos {
+            HPos::Left => 0,
+            HPos::Right => -width,
+            HPos::Center => -width / 2,
+        };
+        let dy = match style.anchor().v_pos {
+            VPos::Top => 0,
+            VPos::Center => -height / 2,
+            VPos::Bottom => -height,
+        };
+        // Rotate the box's corners about the anchor the way glyphs would be
+        let transform = style.transform();
+        let a = transform.transform(dx, dy);
+        let b = transform.transform(dx + width, dy + height);
+        self.0.draw_rect(
+            (pos.0 + a.0.min(b.0), pos.1 + a.1.min(b.1)),
+            (pos.0 + a.0.max(b.0), pos.1 + a.1.max(b.1)),
+            &style.color(),
+            true,
+        )
+    }
+
+    fn estimate_text_size<S: BackendTextStyle>(
+        &self,
+        text: &str,
+        style: &S,
+    ) -> Result<(u32, u32), DrawingErrorKind<Self::ErrorType>> {
+        Ok(Self::text_size(text, style))
+    }
+
+    fn blit_bitmap(
+        &mut self,
+        pos: BackendCoord,
+        (iw, ih): (u32, u32),
+        src: &[u8],
+    ) -> Result<(), DrawingErrorKind<Self::ErrorType>> {
+        self.0.blit_bitmap(pos, (iw, ih), src)
+    }
+}
+
+fn datetime_tick_values(panel: &PanelScene) -> Option<Vec<f64>> {
+    let datetime = panel.x_scale.datetime.as_ref()?;
+    let interval = datetime.interval_seconds?;
+    if interval <= 0.0 || !interval.is_finite() {
+        return None;
+    }
+
+    let (mut start, mut end) = panel.x_scale.domain;
+    if start > end {
+        std::mem::swap(&mut start, &mut end);
+    }
+
+    if !start.is_finite() || !end.is_finite() {
+        return None;
+    }
+
+    if (end - start).abs() <= f64::EPSILON {
+        return Some(vec![start]);
+    }
+
+    let mut ticks = Vec::new();
+    let mut current = start;
+    let max_ticks = 1_000;
+    while current <= end + interval * 1e-9 && ticks.len() < max_ticks {
+        ticks.push(current);
+        current += interval;
+    }
+
+    Some(ticks)
+}
+
+/// The Rendering Backend
+pub struct Canvas;
+
+impl Canvas {
+    /// Execute the SceneGraph and produce a byte vector (PNG, SVG, or terminal text)
+    pub fn execute(scene: SceneGraph, options: &RenderOptions) -> Result<Vec<u8>> {
+        match options.format {
+            OutputFormat::Png => Self::render_png(scene, options),
+            OutputFormat::Svg => Self::render_svg(scene, options),
+            OutputFormat::Ascii => Ok(crate::ascii::render_text(
+                &scene,
+                options.ascii_width,
+                options.ascii_height,
+                options.ascii_color,
+            )
+            .into_bytes()),
+        }
+    }
+
+    /// Rasterize the SceneGraph like the PNG output, returning unencoded 8-bit RGBA
+    /// pixels (row-major, always opaque) with the width and height
+    pub fn render_rgba(scene: SceneGraph) -> Result<(Vec<u8>, u32, u32)> {
+        let image = Self::rasterize(scene, false)?;
+        let (width, height) = image.dimensions();
+        let rgba = image::DynamicImage::ImageRgb8(image).into_rgba8();
+        Ok((rgba.into_raw(), width, height))
+    }
+
+    fn render_png(scene: SceneGraph, options: &RenderOptions) -> Result<Vec<u8>> {
+        let image = Self::rasterize(scene, options.placeholder_text)?;
+        let mut png_bytes = Vec::new();
+        image::codecs::png::PngEncoder::new(&mut png_bytes)
+            .write_image(
+                image.as_raw(),
+                image.width(),
+                image.height(),
+                image::ColorType::Rgb8,
+            )
+            .context("Failed to encode PNG")?;
+        Ok(png_bytes)
+    }
+
+    /// Draw the scene supersampled and downscale it to the output size; both the
+    /// PNG and RGBA outputs come from here. `placeholder_text` draws text as boxes
+    /// (see `PlaceholderText`)
+    fn rasterize(scene: SceneGraph, placeholder_text: bool) -> Result<RgbImage> {
+        let target_width = scene.width;
+        let target_height = scene.height;
+        let width = target_width
+            .checked_mul(PNG_SUPERSAMPLING_SCALE)
+            .context("PNG width overflow during supersampling")?;
+        let height = target_height
+            .checked_mul(PNG_SUPERSAMPLING_SCALE)
+            .context("PNG height overflow during supersampling")?;
+        let mut buffer = vec![0u8; (width * height * 3) as usize];
+        let mut supersampled_scene = scene;
+        supersampled_scene.width = width;
+        supersampled_scene.height = height;
+
+        {
+            let backend = BitMapBackend::with_buffer(&mut buffer, (width, height));
+            if placeholder_text {
+                let root = PlaceholderText(backend).into_drawing_area();
+                Self::draw_scene(&root, &supersampled_scene, PNG_SUPERSAMPLING_SCALE, true)?;
+            } else {
+                let root = backend.into_drawing_area();
+                Self::draw_scene(&root, &supersampled_scene, PNG_SUPERSAMPLING_SCALE, true)?;
+            }
+        }
+
+        let image = RgbImage::from_raw(width, height, buffer)
+            .context("Failed to build supersampled PNG image")?;
+        Ok(image::imageops::resize(
+            &image,
+            target_width,
+            target_height,
+            FilterType::Lanczos3,
+        ))
+    }
+
+    fn render_svg(scene: SceneGraph, _options: &RenderOptions) -> Result<Vec<u8>> {
+        let mut buffer = String::new();
+        {
+            let root = SVGBackend::with_string(&mut buffer, (scene.width, scene.height))
+                .into_drawing_area();
+            Self::draw_scene(&root, &scene, 1, false)?;
+        }
+        Ok(buffer.into_bytes())
+    }
+
+    fn draw_scene<DB: DrawingBackend>(
+        root: &DrawingArea<DB, plotters::coord::Shift>,
+        scene: &SceneGraph,
+        supersampling: u32,
+        raster: bool,
+    ) -> Result<()>
+    where
+        DB::ErrorType: 'static,
+    {
+        // Layout adapts to the output size, not the supersampled buffer
+        let layout =
+            CanvasLayout::for_size(scene.width / supersampling, scene.height / supersampling);
+        let pixel_scale = supersampling as f64 * layout.scale;
+
+        // Resolve theme once at the start
+        let resolved_theme = scale_resolved_theme(&scene.theme.resolve(), pixel_scale);
+
+        // Fill background with resolved theme color
+        root.fill(&resolved_theme.plot_background.fill)
+            .context("Failed to fill background")?;
+
+        // Calculate header height for title + subtitle. Over a facet grid the title
+        // speaks for every panel, so it is centered and never smaller than the panel
+        // captions, shrinking only when it would run past the plot's edges
+        let faceted = scene.panels.len() > 1;
+        let caption_size = scale_f64(
+            scene.facet_label_size.unwrap_or(PANEL_TITLE_SIZE),
+            pixel_scale,
+        );
+        let (root_width, _) = root.dim_in_pixel();
+        let title_size = match &scene.labels.title {
+            Some(title) if faceted => fit_font_size(
+                root,
+                title,
+                &resolved_theme.plot_title.family,
+                resolved_theme
+                    .plot_title
+                    .size
+                    .max(caption_size * FACET_TITLE_RATIO),
+                root_width.saturating_sub(scale_u32(20, pixel_scale)),
+                pixel_scale,
+            ),
+            _ => resolved_theme.plot_title.size,
+        };
+        let has_title = scene.labels.title.is_some();
+        let has_subtitle = scene.labels.subtitle.is_some();
+        let has_caption = scene.labels.caption.is_some();
+
+        // Title, subtitle, and caption wrap at word boundaries to fit the canvas
+        let header_width = root_width.saturating_sub(scale_u32(20, pixel_scale));
+        let title_family = resolved_theme.plot_title.family.as_str();
+        let title_font = TextStyle::from((title_family, title_size).into_font());
+        let title_lines = scene
+            .labels
+            .title
+            .as_ref()
+            .map(|title| wrap_text(root, title, &title_font, title_size, header_width))
+            .unwrap_or_default();
+        let subtitle_size = (title_size * 0.7).max(10.0);
+        let subtitle_lines = scene
+            .labels
+            .subtitle
+            .as_ref()
+            .map(|subtitle| {
+                let font = TextStyle::from((title_family, subtitle_size).into_font());
+                wrap_text(root, subtitle, &font, subtitle_size, header_width)
+            })
+            .unwrap_or_default();
+        let footer_size = scale_f64(11.0, pixel_scale);
+        let caption_lines = scene
+            .labels
+            .caption
+            .as_ref()
+            .map(|caption| {
+                let font = TextStyle::from(("sans-serif", footer_size).into_font());
+                let width = root_width.saturating_sub(scale_u32(30, pixel_scale));
+                wrap_text(root, caption, &font, footer_size, width)
+            })
+            .unwrap_or_default();
+        let line_gap = scale_u32(2, pixel_scale);
+        let stacked =
+            |lines: usize, height: u32| (height + line_gap) * lines.max(1) as u32 - line_gap;
+
+        // Measure the title so tall glyphs (CJK, emoji) are not clipped by the header
+        let title_height = title_lines
+            .iter()
+            .map(|line| {
+                let (_, height) = estimate_text_size(root, line, &title_font, title_size);
+                height.max(title_size as u32)
+            })
+            .max()
+            .unwrap_or(0);
+        let subtitle_height = (title_size * 0.7) as u32;
+
+        let header_height: u32 = if has_title || has_subtitle {
+            let mut h = scale_u32(5, pixel_scale); // top padding
+            if has_title {
+                h += stacked(title_lines.len(), title_height) + scale_u32(5, pixel_scale);
+            }
+            if has_subtitle {
+                h += stacked(subtitle_lines.len(), subtitle_height) + scale_u32(5, pixel_scale);
+            }
             h + scale_u32(5, pixel_scale) // bottom padding
         } else {
             0
         };
 
+        let caption_line_height = (footer_size * 1.3) as u32;
         let caption_height: u32 = if has_caption {
             scale_u32(30, pixel_scale)
+                + caption_line_height * caption_lines.len().saturating_sub(1) as u32
         } else {
             0
         };
@@ -726,95 +1726,192 @@ impl Canvas {
         // Draw title and subtitle in header area
         if has_title || has_subtitle {
             let mut y_offset = scale_i32(8, pixel_scale);
+            let (header_x, header_pos) = if faceted {
+                (root_width as i32 / 2, Pos::new(HPos::Center, VPos::Top))
+            } else {
+                (scale_i32(10, pixel_scale), Pos::new(HPos::Left, VPos::Top))
+            };
 
-            if let Some(title) = &scene.labels.title {
-                let title_style = TextStyle::from(
-                    (
-                        resolved_theme.plot_title.family.as_str(),
-                        resolved_theme.plot_title.size as i32,
-                    )
-                        .into_font(),
-                )
-                .color(&resolved_theme.plot_title.color);
-                header_area.draw_text(
-                    title,
-                    &title_style,
-                    (scale_i32(10, pixel_scale), y_offset),
-                )?;
-                y_offset += title_size as i32 + scale_i32(4, pixel_scale);
+            if has_title {
+                let title_style = TextStyle::from((title_family, title_size as i32).into_font())
+                    .color(&resolved_theme.plot_title.color)
+                    .pos(header_pos);
+                for (i, line) in title_lines.iter().enumerate() {
+                    if i > 0 {
+                        y_offset += (title_height + line_gap) as i32;
+                    }
+                    header_area.draw_text(line, &title_style, (header_x, y_offset))?;
+                }
+                y_offset += title_height as i32 + scale_i32(4, pixel_scale);
             }
 
-            if let Some(subtitle) = &scene.labels.subtitle {
-                let subtitle_size = (title_size * 0.7).max(10.0);
-                let subtitle_style = TextStyle::from(
-                    (
-                        resolved_theme.plot_title.family.as_str(),
-                        subtitle_size as i32,
-                    )
-                        .into_font(),
-                )
-                .color(&resolved_theme.axis_text.color);
-                header_area.draw_text(
-                    subtitle,
-                    &subtitle_style,
-                    (scale_i32(10, pixel_scale), y_offset),
-                )?;
+            if has_subtitle {
+                let subtitle_style =
+                    TextStyle::from((title_family, subtitle_size as i32).into_font())
+                        .color(&resolved_theme.axis_text.color)
+                        .pos(header_pos);
+                for line in &subtitle_lines {
+                    header_area.draw_text(line, &subtitle_style, (header_x, y_offset))?;
+                    y_offset += (subtitle_height + line_gap) as i32;
+                }
             }
         }
 
         // Draw caption in footer area (right-aligned, muted)
-        if let Some(caption) = &scene.labels.caption {
-            let caption_style =
-                TextStyle::from(("sans-serif", scale_i32(11, pixel_scale)).into_font())
-                    .color(&resolved_theme.axis_text.color)
-                    .pos(Pos::new(HPos::Right, VPos::Center));
+        if has_caption {
+            let caption_style = TextStyle::from(("sans-serif", footer_size as i32).into_font())
+                .color(&resolved_theme.axis_text.color)
+                .pos(Pos::new(HPos::Right, VPos::Center));
             let (w, _h) = footer_area.dim_in_pixel();
-            footer_area.draw_text(
-                caption,
-                &caption_style,
-                (
-                    (w as i32) - scale_i32(15, pixel_scale),
-                    scale_i32(10, pixel_scale),
-                ),
-            )?;
+            for (i, line) in caption_lines.iter().enumerate() {
+                footer_area.draw_text(
+                    line,
+                    &caption_style,
+                    (
+                        (w as i32) - scale_i32(15, pixel_scale),
+                        scale_i32(10, pixel_scale) + (caption_line_height * i as u32) as i32,
+                    ),
+                )?;
+            }
         }
 
         // Determine Grid Layout
         let max_row = scene.panels.iter().map(|p| p.row).max().unwrap_or(0);
         let max_col = scene.panels.iter().map(|p| p.col).max().unwrap_or(0);
 
-        let rows = max_row + 1;
-        let cols = max_col + 1;
+        // An explicit ncol/nrow can leave trailing cells empty; they stay background
+        let rows = scene.nrow.max(max_row + 1);
+        let cols = scene.ncol.max(max_col + 1);
+
+        // facet_grid row labels get a strip down the right edge, so the last column's
+        // panels keep the same width as the others
+        let (main_area, row_strips) = if scene.panels.iter().any(|p| p.row_title.is_some()) {
+            let (w, _) = main_area.dim_in_pixel();
+            let (grid_area, strip_area) =
+                main_area.split_horizontally(w.saturating_sub(scale_u32(26, pixel_scale)));
+            (grid_area, Some(strip_area.split_evenly((rows, 1))))
+        } else {
+            (main_area, None)
+        };
 
         let areas = main_area.split_evenly((rows, cols));
 
-        for panel in &scene.panels {
-            let area_idx = panel.row * cols + panel.col;
-            if area_idx >= areas.len() {
-                continue;
+        if let Some(strips) = &row_strips {
+            let strip_font = ("sans-serif", caption_size);
+            let strip_style = TextStyle::from(strip_font.into_font())
+                .transform(FontTransform::Rotate90)
+                .pos(Pos::new(HPos::Center, VPos::Center));
+            for panel in &scene.panels {
+                if let (Some(row_title), Some(strip)) = (&panel.row_title, strips.get(panel.row)) {
+                    let (w, h) = strip.dim_in_pixel();
+                    let row_title = ellipsize(
+                        strip,
+                        row_title,
+                        &TextStyle::from(strip_font.into_font()),
+                        caption_size,
+                        h.saturating_sub(scale_u32(10, pixel_scale)),
+                    );
+                    strip
+                        .draw_text(&row_title, &strip_style, (w as i32 / 2, h as i32 / 2))
+                        .context("Failed to draw facet row label")?;
+                }
             }
+        }
 
-            let area = &areas[area_idx];
-            Canvas::draw_panel(area, panel, &resolved_theme, pixel_scale)?;
+        // Thumbnails draw without axis titles
+        let panels: Vec<(&DrawingArea<DB, plotters::coord::Shift>, Cow<PanelScene>)> = scene
+            .panels
+            .iter()
+            .filter_map(|panel| {
+                let area = areas.get(panel.row * cols + panel.col)?;
+                if layout.show_axis_text {
+                    return Some((area, Cow::Borrowed(panel)));
+                }
+                let mut thumbnail = panel.clone();
+                thumbnail.x_label = None;
+                thumbnail.y_label = None;
+                Some((area, Cow::Owned(thumbnail)))
+            })
+            .collect();
+
+        // coord_fixed() fits each plotting area to its ranges; panels sharing their
+        // ranges then take the smallest fit at the same offset, so the grid's rows
+        // and columns line up
+        let mut fits = Vec::with_capacity(panels.len());
+        for (area, panel) in &panels {
+            let fit = match panel.aspect_ratio {
+                Some(ratio) if !panel.polar => {
+                    let theme = panel_theme(&resolved_theme, panel);
+                    let frame = Canvas::panel_frame(
+                        area,
+                        panel,
+                        &theme,
+                        caption_size,
+                        pixel_scale,
+                        layout,
+                    )?;
+                    let size =
+                        fixed_aspect_size(&panel.x_scale, &panel.y_scale, frame.plot_size, ratio);
+                    Some((size, frame.plot_size))
+                }
+                _ => None,
+            };
+            fits.push(fit);
+        }
+        let shared_ranges = panels.windows(2).all(|pair| {
+            pair[0].1.x_scale.range == pair[1].1.x_scale.range
+                && pair[0].1.y_scale.range == pair[1].1.y_scale.range
+        });
+        let smallest = |a: (u32, u32), b: (u32, u32)| (a.0.min(b.0), a.1.min(b.1));
+        let shared = fits
+            .iter()
+            .flatten()
+            .copied()
+            .reduce(|(size_a, room_a), (size_b, room_b)| {
+                (smallest(size_a, size_b), smallest(room_a, room_b))
+            })
+            .filter(|_| shared_ranges);
+        let fixed_sizes: Vec<Option<FixedPlacement>> = fits
+            .into_iter()
+            .map(|fit| {
+                let (size, room) = shared.or(fit)?;
+                Some(FixedPlacement {
+                    size,
+                    lead: ((room.0 - size.0) / 2, (room.1 - size.1) / 2),
+                })
+            })
+            .collect();
+
+        for ((area, panel), fixed_size) in panels.iter().zip(fixed_sizes) {
+            Canvas::draw_panel(
+                area,
+                panel,
+                &resolved_theme,
+                caption_size,
+                pixel_scale,
+                raster,
+                layout,
+                fixed_size,
+            )?;
         }
 
         root.present().context("Failed to present drawing")?;
         Ok(())
     }
 
-    fn draw_panel<DB: DrawingBackend>(
+    /// The margin, caption, and label areas a panel reserves around its plotting area
+    fn panel_frame<DB: DrawingBackend>(
         area: &DrawingArea<DB, plotters::coord::Shift>,
         panel: &PanelScene,
         theme: &ResolvedTheme,
-        pixel_scale: u32,
-    ) -> Result<()>
+        caption_size: f64,
+        pixel_scale: f64,
+        layout: CanvasLayout,
+    ) -> Result<PanelFrame>
     where
         <DB as plotters::prelude::DrawingBackend>::ErrorType: 'static,
     {
-        let x_range = panel.x_scale.range.0..panel.x_scale.range.1;
-        let y_range = panel.y_scale.range.0..panel.y_scale.range.1;
-
-        let (x_axis_style, y_axis_style, axis_desc_style) = build_axis_text_styles(theme);
+        let (_, y_axis_style, axis_desc_style) = build_axis_text_styles(theme);
         let axis_layout = calculate_axis_layout(
             area,
             panel,
@@ -822,16 +1919,142 @@ impl Canvas {
             &y_axis_style,
             &axis_desc_style,
             pixel_scale,
+            layout.show_axis_text,
+        );
+
+        // Thumbnails have no axis text to make room for, so the default margin tightens too
+        let margin = match theme.margin {
+            Some(margin) => margin.round() as u32,
+            None => scale_u32(if layout.show_axis_text { 15 } else { 5 }, pixel_scale),
+        };
+        let panel_title = panel.title.clone().unwrap_or_default();
+        // Long facet titles shrink (to at most three quarters of the caption size) to fit
+        // the panel, then lose their tail to an ellipsis rather than spill into neighbors
+        let title_width = area.dim_in_pixel().0.saturating_sub(margin * 2);
+        let title_size = fit_font_size(
+            area,
+            &panel_title,
+            "sans-serif",
+            caption_size,
+            title_width,
+            pixel_scale,
+        )
+        .max(caption_size * 0.75);
+        let panel_title = ellipsize(
+            area,
+            &panel_title,
+            &TextStyle::from(("sans-serif", title_size).into_font()),
+            title_size,
+            title_width,
         );
+        // An empty caption still reserves a line of height; compact canvases can't spare it
+        let has_caption = !panel_title.is_empty() || layout.scale == 1.0;
+
+        let (width, height) = area.dim_in_pixel();
+        let caption_height = if has_caption {
+            let style = TextStyle::from(("sans-serif", title_size).into_font());
+            // The band `DrawingArea::titled` takes off the top
+            let (_, text_h) = area.estimate_text_size(&panel_title, &style)?;
+            text_h + 2 * (text_h / 2).min(5)
+        } else {
+            0
+        };
+        let plot_size = (
+            width.saturating_sub(2 * margin + axis_layout.y_label_area_size),
+            height.saturating_sub(2 * margin + caption_height + axis_layout.x_label_area_size),
+        );
+
+        Ok(PanelFrame {
+            axis_layout,
+            margin,
+            title: panel_title,
+            title_size,
+            has_caption,
+            plot_size,
+        })
+    }
+
+    /// Draw one panel; `fixed_size` places the plotting area under coord_fixed()
+    #[allow(clippy::too_many_arguments)]
+    fn draw_panel<DB: DrawingBackend>(
+        area: &DrawingArea<DB, plotters::coord::Shift>,
+        panel: &PanelScene,
+        theme: &ResolvedTheme,
+        caption_size: f64,
+        pixel_scale: f64,
+        raster: bool,
+        layout: CanvasLayout,
+        fixed_size: Option<FixedPlacement>,
+    ) -> Result<()>
+    where
+        <DB as plotters::prelude::DrawingBackend>::ErrorType: 'static,
+    {
+        let x_range = panel.x_scale.range.0..panel.x_scale.range.1;
+        let y_range = panel.y_scale.range.0..panel.y_scale.range.1;
+
+        let theme = panel_theme(theme, panel);
+        let theme = theme.as_ref();
+        let (x_axis_style, y_axis_style, axis_desc_style) = build_axis_text_styles(theme);
+        let PanelFrame {
+            axis_layout,
+            margin,
+            title: panel_title,
+            title_size,
+            has_caption,
+            plot_size,
+        } = Self::panel_frame(area, panel, theme, caption_size, pixel_scale, layout)?;
+
+        if panel.polar {
+            // The same margin and caption, but no label areas; the plotting area's
+            // pixel size must be known up front to give both axes equal units
+            let plot_area = area.margin(margin, margin, margin, margin);
+            let plot_area = if has_caption {
+                plot_area
+                    .titled(&panel_title, ("sans-serif", title_size))
+                    .context("Failed to draw panel title")?
+            } else {
+                plot_area
+            };
+            let (x_range, y_range) =
+                equal_unit_ranges(&panel.x_scale, &panel.y_scale, plot_area.dim_in_pixel());
+            let mut chart = ChartBuilder::on(&plot_area)
+                .build_cartesian_2d(x_range, y_range)
+                .context("Failed to build chart")?;
+            return Self::draw_panel_contents(
+                area,
+                panel,
+                theme,
+                pixel_scale,
+                raster,
+                axis_layout,
+                &x_axis_style,
+                &y_axis_style,
+                &axis_desc_style,
+                &mut chart,
+            );
+        }
+
+        // coord_fixed() pads the panel, shrinking the plotting area left inside the
+        // margin, caption, and label areas to the fitted size
+        let fixed_area;
+        let area = match fixed_size {
+            Some(FixedPlacement { size, lead }) => {
+                let (left, top) = (lead.0.min(plot_size.0), lead.1.min(plot_size.1));
+                let right = plot_size.0.saturating_sub(size.0 + left);
+                let bottom = plot_size.1.saturating_sub(size.1 + top);
+                fixed_area = area.margin(top, bottom, left, right);
+                &fixed_area
+            }
+            None => area,
+        };
 
         let mut chart_builder = ChartBuilder::on(area);
 
+        chart_builder.margin(margin);
+        if has_caption {
+            chart_builder.caption(panel_title, ("sans-serif", title_size));
+        }
         chart_builder
-            .margin(scale_u32(15, pixel_scale))
-            .caption(
-                panel.title.clone().unwrap_or_default(),
-                ("sans-serif", scale_i32(15, pixel_scale)),
-            )
             .x_label_area_size(axis_layout.x_label_area_size)
             .y_label_area_size(axis_layout.y_label_area_size);
 
@@ -854,6 +2077,7 @@ impl Canvas {
                     panel,
                     theme,
                     pixel_scale,
+                    raster,
                     axis_layout,
                     &x_axis_style,
                     &y_axis_style,
@@ -870,6 +2094,7 @@ impl Canvas {
                     panel,
                     theme,
                     pixel_scale,
+                    raster,
                     axis_layout,
                     &x_axis_style,
                     &y_axis_style,
@@ -886,6 +2111,7 @@ impl Canvas {
                     panel,
                     theme,
                     pixel_scale,
+                    raster,
                     axis_layout,
                     &x_axis_style,
                     &y_axis_style,
@@ -902,6 +2128,7 @@ impl Canvas {
                     panel,
                     theme,
                     pixel_scale,
+                    raster,
                     axis_layout,
                     &x_axis_style,
                     &y_axis_style,
@@ -914,12 +2141,13 @@ impl Canvas {
         Ok(())
     }
 
+    /// Mesh, axis lines, tick labels, and axis titles
     #[allow(clippy::too_many_arguments)]
-    fn draw_panel_contents<'a, DB, X, Y>(
+    fn draw_axes<'a, DB, X, Y>(
         area: &DrawingArea<DB, plotters::coord::Shift>,
         panel: &PanelScene,
         theme: &ResolvedTheme,
-        pixel_scale: u32,
+        pixel_scale: f64,
         axis_layout: AxisLayout,
         x_axis_style: &TextStyle,
         y_axis_style: &TextStyle,
@@ -942,10 +2170,15 @@ impl Canvas {
         mesh.axis_desc_style(axis_desc_style.clone());
 
         let default_tick_mark_size = scale_i32(5, pixel_scale);
+        let manual_grid = ManualGrid::new(panel, theme);
 
         if theme.has_customization {
             // Major Grid
             match &theme.panel_grid_major {
+                // Dashed lines are drawn after the mesh, so the mesh's own stay invisible
+                Some(_) if manual_grid.pattern.is_some() => {
+                    mesh.bold_line_style(RGBColor(255, 255, 255).mix(0.0));
+                }
                 Some(grid_style) => {
                     let grid_color = grid_style
                         .color
@@ -967,11 +2200,24 @@ impl Canvas {
                     mesh.light_line_style(grid_color);
                 }
                 None => {
-                    // Blank - make transparent
-                    mesh.light_line_style(RGBColor(255, 255, 255).mix(0.0));
+                    // Blank - skip the minor lines entirely
+                    mesh.max_light_lines(0);
                 }
             }
 
+            // Both grids blank: draw no mesh lines at all (tick labels are kept)
+            if theme.panel_grid_major.is_none() && theme.panel_grid_minor.is_none() {
+                mesh.disable_mesh();
+            }
+
+            // Separated category axes get their lines from draw_manual_grid
+            if manual_grid.x_separators {
+                mesh.disable_x_mesh();
+            }
+            if manual_grid.y_separators {
+                mesh.disable_y_mesh();
+            }
+
             // Axis line styling
             match &theme.axis_line {
                 Some(axis_style) => {
@@ -999,7 +2245,7 @@ impl Canvas {
             } else {
                 mesh.set_all_tick_mark_size(default_tick_mark_size);
             }
-        } else if pixel_scale > 1 {
+        } else if pixel_scale != 1.0 {
             mesh.set_all_tick_mark_size(default_tick_mark_size);
         }
 
@@ -1008,7 +2254,7 @@ impl Canvas {
         }
 
         // Custom X Labels if categorical
-        let categories_x = panel.x_scale.categories.clone();
+        let categories_x = category_labels(&panel.x_scale);
         let formatter_x = move |v: &f64| {
             // Check if value is integer (within epsilon)
             if (v - v.round()).abs() > 1e-6 {
@@ -1031,42 +2277,45 @@ impl Canvas {
         let formatter_datetime = |v: &f64| format_datetime_tick(*v, &datetime_label_format);
 
         // Nice tick formatters for numeric axes
-        let x_ticks = panel.x_scale.tick_positions.clone();
-        let x_transform = panel.x_scale.transform;
+        let x_scale = &panel.x_scale;
         let nice_formatter_x = move |v: &f64| {
             // Snap to nearest precomputed tick if close enough
-            for t in &x_ticks {
+            for t in &x_scale.tick_positions {
                 if (v - t).abs() < (v.abs().max(t.abs())) * 1e-6 + 1e-12 {
-                    return format_axis_tick(*t, x_transform);
+                    return format_axis_tick(*t, x_scale);
                 }
             }
             String::new()
         };
 
-        let y_ticks = panel.y_scale.tick_positions.clone();
-        let y_transform = panel.y_scale.transform;
+        let y_scale = &panel.y_scale;
         let nice_formatter_y = move |v: &f64| {
-            for t in &y_ticks {
+            for t in &y_scale.tick_positions {
                 if (v - t).abs() < (v.abs().max(t.abs())) * 1e-6 + 1e-12 {
-                    return format_axis_tick(*t, y_transform);
+                    return format_axis_tick(*t, y_scale);
                 }
             }
             String::new()
         };
 
-        if panel.x_scale.is_categorical && !axis_layout.manual_rotated_x_labels {
+        // Bold key points per axis, plotters' default unless a tick count is set below
+        let (mut x_label_count, mut y_label_count) = (11, 11);
+        if !axis_layout.show_x_tick_labels {
+            mesh.x_label_formatter(&blank_tick_label);
+        } else if panel.x_scale.is_categorical && !axis_layout.manual_rotated_x_labels {
             mesh.x_label_formatter(&formatter_x);
         } else if axis_layout.manual_rotated_x_labels {
             mesh.x_label_formatter(&blank_tick_label);
         } else if panel.x_scale.datetime.is_some() {
             mesh.x_label_formatter(&formatter_datetime);
         } else if !panel.x_scale.tick_positions.is_empty() {
-            mesh.x_labels(panel.x_scale.tick_positions.len());
+            x_label_count = panel.x_scale.tick_positions.len();
+            mesh.x_labels(x_label_count);
             mesh.x_label_formatter(&nice_formatter_x);
         }
 
         // Custom Y Labels if categorical (e.g. coord_flip)
-        let categories_y = panel.y_scale.categories.clone();
+        let categories_y = category_labels(&panel.y_scale);
         let formatter_y = move |v: &f64| {
             if (v - v.round()).abs() > 1e-6 {
                 return "".to_string();
@@ -1079,14 +2328,20 @@ impl Canvas {
             }
         };
 
-        if panel.y_scale.is_categorical {
+        if !axis_layout.show_y_tick_labels {
+            mesh.y_label_formatter(&blank_tick_label);
+        } else if panel.y_scale.is_categorical {
             mesh.y_label_formatter(&formatter_y);
         } else if !panel.y_scale.tick_positions.is_empty() {
-            mesh.y_labels(panel.y_scale.tick_positions.len());
+            y_label_count = panel.y_scale.tick_positions.len();
+            mesh.y_labels(y_label_count);
             mesh.y_label_formatter(&nice_formatter_y);
         }
 
         mesh.draw().context("Failed to draw mesh")?;
+        if theme.has_customization {
+            draw_manual_grid(chart, panel, &manual_grid, x_label_count, y_label_count)?;
+        }
 
         draw_manual_rotated_x_tick_labels(
             area,
@@ -1098,32 +2353,139 @@ impl Canvas {
         )?;
         draw_manual_y_axis_desc(area, chart, panel, axis_layout, axis_desc_style)?;
 
-        // Draw Commands
-        for cmd in &panel.commands {
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn draw_panel_contents<'a, DB, X, Y>(
+        area: &DrawingArea<DB, plotters::coord::Shift>,
+        panel: &PanelScene,
+        theme: &ResolvedTheme,
+        pixel_scale: f64,
+        raster: bool,
+        axis_layout: AxisLayout,
+        x_axis_style: &TextStyle,
+        y_axis_style: &TextStyle,
+        axis_desc_style: &TextStyle,
+        chart: &mut ChartContext<'a, DB, Cartesian2d<X, Y>>,
+    ) -> Result<()>
+    where
+        DB: DrawingBackend + 'a,
+        DB::ErrorType: 'static,
+        X: Ranged<ValueType = f64> + ValueFormatter<f64>,
+        Y: Ranged<ValueType = f64> + ValueFormatter<f64>,
+    {
+        // Draw Commands; the mesh and axes go over the `underlay` commands (shade bands)
+        let underlay = panel.underlay.min(panel.commands.len());
+        for i in 0..=panel.commands.len() {
+            if i == underlay && !panel.polar {
+                Self::draw_axes(
+                    area,
+                    panel,
+                    theme,
+                    pixel_scale,
+                    axis_layout,
+                    x_axis_style,
+                    y_axis_style,
+                    axis_desc_style,
+                    chart,
+                )?;
+            }
+            let Some(cmd) = panel.commands.get(i) else {
+                break;
+            };
             match cmd {
                 DrawCommand::DrawLine {
                     points,
                     style,
                     legend,
                 } => {
+                    let mut points: Cow<[(f64, f64)]> = Cow::Borrowed(points);
+                    if style.simplify {
+                        let pixels: Vec<BackendCoord> =
+                            points.iter().map(|p| chart.backend_coord(p)).collect();
+                        let keep = pixel_column_extremes(&pixels);
+                        if keep.len() < points.len() {
+                            points = Cow::Owned(keep.into_iter().map(|i| points[i]).collect());
+                        }
+                    }
+                    let simplified = matches!(points, Cow::Owned(_));
+                    let points = points.as_ref();
                     let color = parse_color(&style.color, BLUE);
                     let stroke_width =
                         to_stroke_width(scale_f64(style.width.unwrap_or(2.0), pixel_scale));
                     let alpha = style.alpha.unwrap_or(1.0);
                     let color_style = color.mix(alpha).stroke_width(stroke_width);
+                    // Dash lengths grow with the stroke so thick dotted lines stay dotted
+                    let pattern: Option<Vec<f64>> =
+                        dash_pattern(style.linetype.as_deref()).map(|pattern| {
+                            let unit = stroke_width.max(1) as f64;
+                            pattern.iter().map(|len| len * unit).collect()
+                        });
 
-                    let series = chart
-                        .draw_series(LineSeries::new(points.iter().cloned(), color_style))
-                        .context("Failed to draw line")?;
+                    let series = match &pattern {
+                        Some(pattern) => {
+                            let spans = dash_polyline(
+                                points,
+                                |p| {
+                                    let (x, y) = chart.backend_coord(&p);
+                                    (x as f64, y as f64)
+                                },
+                                pattern,
+                            );
+                            chart.draw_series(
+                                spans
+                                    .into_iter()
+                                    .map(|span| PathElement::new(span, color_style)),
+                            )
+                        }
+                        // A bitmap fills a wide stroke as one even-odd polygon, where a
+                        // simplified line doubling back within a pixel column would cancel
+                        // itself out; one element per segment keeps every stroke
+                        None if simplified && raster => chart.draw_series(
+                            points
+                                .windows(2)
+                                .map(|pair| PathElement::new(pair.to_vec(), color_style)),
+                        ),
+                        None => {
+                            chart.draw_series(LineSeries::new(points.iter().cloned(), color_style))
+                        }
+                    }
+                    .context("Failed to draw line")?;
 
                     if let Some(label) = legend {
                         series.label(label).legend(move |(x, y)| {
-                            PathElement::new(
-                                vec![(x, y), (x + scale_i32(20, pixel_scale), y)],
+                            MultiPath::legend_key(
+                                (x, y),
+                                scale_i32(20, pixel_scale),
+                                pattern.as_deref(),
                                 color.mix(alpha).stroke_width(stroke_width),
                             )
                         });
                     }
+
+                    if style.arrow {
+                        // Sized in pixels, growing with the stroke like the dashes
+                        let length = scale_f64(10.0, pixel_scale).max(3.0 * stroke_width as f64);
+                        let pixel = |p: &(f64, f64)| {
+                            let (x, y) = chart.backend_coord(p);
+                            (x as f64, y as f64)
+                        };
+                        let head = points.last().and_then(|tip| {
+                            // Walk back past repeated points for the shaft direction
+                            let from = points.iter().rev().find(|p| pixel(p) != pixel(tip))?;
+                            arrowhead(pixel(from), pixel(tip), length)
+                                .map(|corners| (*tip, corners))
+                        });
+                        if let Some((tip, corners)) = head {
+                            chart
+                                .draw_series(std::iter::once(
+                                    EmptyElement::at(tip)
+                                        + Polygon::new(corners, color.mix(alpha).filled()),
+                                ))
+                                .context("Failed to draw arrowhead")?;
+                        }
+                    }
                 }
                 DrawCommand::DrawPoint {
                     points,
@@ -1134,14 +2496,53 @@ impl Canvas {
                     let size = to_marker_size(scale_f64(style.size.unwrap_or(3.0), pixel_scale));
                     let alpha = style.alpha.unwrap_or(1.0);
                     let color_style = color.mix(alpha).filled();
-                    let stroke_style = color
-                        .mix(alpha)
-                        .stroke_width(to_stroke_width(scale_f64(2.0, pixel_scale)));
+                    // `stroke:` thickens the line-drawn shapes, which have no fill to outline
+                    let line_width =
+                        to_stroke_width(scale_f64(style.stroke_width.unwrap_or(2.0), pixel_scale));
+                    let stroke_style = color.mix(alpha).stroke_width(line_width);
                     let shape = style
                         .shape
                         .as_deref()
                         .unwrap_or("circle")
                         .to_ascii_lowercase();
+                    let line_drawn =
+                        matches!(shape.as_str(), "cross" | "plus" | "x" | "xcross" | "star");
+
+                    if !line_drawn && (style.fill.is_some() || style.stroke_width.is_some()) {
+                        // A fixed fill makes `color` the outline; otherwise `color` (mapped
+                        // or fixed) fills and the outline is black
+                        let (fill, outline) = match &style.fill {
+                            Some(_) => (
+                                parse_color(&style.fill, WHITE),
+                                parse_color(&style.color, BLACK),
+                            ),
+                            None => (color, BLACK),
+                        };
+                        let width = to_stroke_width(scale_f64(
+                            style.stroke_width.unwrap_or(1.0),
+                            pixel_scale,
+                        ));
+                        let marker = OutlinedMarker::new(
+                            &shape,
+                            size,
+                            width,
+                            fill.mix(alpha).filled(),
+                            outline.mix(alpha),
+                        );
+                        let series = chart
+                            .draw_series(
+                                points.iter().map(|p| EmptyElement::at(*p) + marker.clone()),
+                            )
+                            .context("Failed to draw points")?;
+
+                        if let Some(label) = legend {
+                            series.label(label).legend(move |(x, y)| {
+                                EmptyElement::at((x + scale_i32(10, pixel_scale), y))
+                                    + marker.clone()
+                            });
+                        }
+                        continue;
+                    }
 
                     match shape.as_str() {
                         "square" => {
@@ -1221,9 +2622,7 @@ impl Canvas {
 
                             if let Some(label) = legend {
                                 series.label(label).legend(move |(x, y)| {
-                                    let legend_style = color
-                                        .mix(alpha)
-                                        .stroke_width(to_stroke_width(scale_f64(2.0, pixel_scale)));
+                                    let legend_style = color.mix(alpha).stroke_width(line_width);
                                     EmptyElement::at((x + scale_i32(10, pixel_scale), y))
                                         + PathElement::new(
                                             vec![(-size, 0), (size, 0)],
@@ -1253,9 +2652,7 @@ impl Canvas {
 
                             if let Some(label) = legend {
                                 series.label(label).legend(move |(x, y)| {
-                                    let legend_style = color
-                                        .mix(alpha)
-                                        .stroke_width(to_stroke_width(scale_f64(2.0, pixel_scale)));
+                                    let legend_style = color.mix(alpha).stroke_width(line_width);
                                     EmptyElement::at((x + scale_i32(10, pixel_scale), y))
                                         + PathElement::new(
                                             vec![(-size, -size), (size, size)],
@@ -1293,9 +2690,7 @@ impl Canvas {
 
                             if let Some(label) = legend {
                                 series.label(label).legend(move |(x, y)| {
-                                    let legend_style = color
-                                        .mix(alpha)
-                                        .stroke_width(to_stroke_width(scale_f64(2.0, pixel_scale)));
+                                    let legend_style = color.mix(alpha).stroke_width(line_width);
                                     EmptyElement::at((x + scale_i32(10, pixel_scale), y))
                                         + PathElement::new(
                                             vec![(-size, 0), (size, 0)],
@@ -1346,24 +2741,61 @@ impl Canvas {
                     let color = parse_color(&style.color, BLUE);
                     let alpha = style.alpha.unwrap_or(1.0);
                     let color_style = color.mix(alpha).filled();
-
+                    // The outline color and its width in whole pixels
+                    let border = style.border.as_ref().map(|border| {
+                        let width = scale_f64(style.border_width.unwrap_or(1.0), pixel_scale);
+                        (
+                            parse_color(&Some(border.clone()), BLACK).filled(),
+                            to_stroke_width(width),
+                        )
+                    });
+
+                    // An empty rect only carries a legend entry
+                    let area = (tl != br).then_some([*tl, *br]);
                     let series = chart
-                        .draw_series(std::iter::once(Rectangle::new([*tl, *br], color_style)))
+                        .draw_series(area.map(|corners| Rectangle::new(corners, color_style)))
                         .context("Failed to draw rect")?;
-
-                    if let Some(label) = legend {
-                        series.label(label).legend(move |(x, y)| {
-                            Rectangle::new(
-                                [
-                                    (x, y - scale_i32(5, pixel_scale)),
-                                    (
-                                        x + scale_i32(15, pixel_scale),
-                                        y + scale_i32(5, pixel_scale),
-                                    ),
-                                ],
-                                color.mix(alpha).filled(),
-                            )
-                        });
+                    let half = scale_i32(5, pixel_scale);
+                    let swatch = [(0, -half), (scale_i32(15, pixel_scale), half)];
+
+                    match (legend, border) {
+                        (Some(label), Some((border_style, width))) => {
+                            let [top, bottom, left, right] =
+                                border_strips(swatch[0], swatch[1], width);
+                            series.label(label).legend(move |(x, y)| {
+                                EmptyElement::at((x, y))
+                                    + Rectangle::new(swatch, color_style)
+                                    + Rectangle::new(top, border_style)
+                                    + Rectangle::new(bottom, border_style)
+                                    + Rectangle::new(left, border_style)
+                                    + Rectangle::new(right, border_style)
+                            });
+                        }
+                        (Some(label), None) => {
+                            series.label(label).legend(move |(x, y)| {
+                                Rectangle::new(
+                                    [(x, y - half), (x + scale_i32(15, pixel_scale), y + half)],
+                                    color_style,
+                                )
+                            });
+                        }
+                        (None, _) => {}
+                    }
+                    if let (Some([tl, br]), Some((border_style, width))) = (area, border) {
+                        // Strips in pixels around the corners, anchored at tl
+                        let origin = chart.backend_coord(&tl);
+                        let far = chart.backend_coord(&br);
+                        let [top, bottom, left, right] =
+                            border_strips((0, 0), (far.0 - origin.0, far.1 - origin.1), width);
+                        chart
+                            .draw_series(std::iter::once(
+                                EmptyElement::at(tl)
+                                    + Rectangle::new(top, border_style)
+                                    + Rectangle::new(bottom, border_style)
+                                    + Rectangle::new(left, border_style)
+                                    + Rectangle::new(right, border_style),
+                            ))
+                            .context("Failed to draw rect border")?;
                     }
                 }
                 DrawCommand::DrawPolygon {
@@ -1376,10 +2808,7 @@ impl Canvas {
                     let color_style = color.mix(alpha).filled();
 
                     let series = chart
-                        .draw_series(std::iter::once(Polygon::new(
-                            points.clone(),
-                            color_style.clone(),
-                        )))
+                        .draw_series(std::iter::once(Polygon::new(points.clone(), color_style)))
                         .context("Failed to draw polygon")?;
 
                     if let Some(label) = legend {
@@ -1392,7 +2821,41 @@ impl Canvas {
                                         y + scale_i32(5, pixel_scale),
                                     ),
                                 ],
-                                color_style.clone(),
+                                color_style,
+                            )
+                        });
+                    }
+                }
+                DrawCommand::DrawText {
+                    position,
+                    text,
+                    style,
+                    legend,
+                } => {
+                    let color = parse_color(&style.color, BLACK);
+                    let alpha = style.alpha.unwrap_or(1.0);
+                    let size = scale_f64(style.size.unwrap_or(11.0), pixel_scale).max(1.0);
+                    let mut text_style = TextStyle::from(("sans-serif", size).into_font())
+                        .pos(Pos::new(HPos::Center, VPos::Center));
+                    text_style.color = color.mix(alpha).to_backend_color();
+
+                    // Labels running past the plot area spill into the margins; pixels
+                    // beyond the canvas are dropped by the backend rather than erroring.
+                    let series = chart
+                        .draw_series(std::iter::once(Text::new(
+                            text.clone(),
+                            *position,
+                            text_style.clone(),
+                        )))
+                        .context("Failed to draw text")?;
+
+                    if let Some(label) = legend {
+                        let legend_style = text_style.pos(Pos::new(HPos::Left, VPos::Center));
+                        series.label(label).legend(move |(x, y)| {
+                            Text::new(
+                                "a",
+                                (x + scale_i32(5, pixel_scale), y),
+                                legend_style.clone(),
                             )
                         });
                     }
@@ -1409,6 +2872,7 @@ impl Canvas {
             DrawCommand::DrawPoint { legend, .. } => legend.is_some(),
             DrawCommand::DrawRect { legend, .. } => legend.is_some(),
             DrawCommand::DrawPolygon { legend, .. } => legend.is_some(),
+            DrawCommand::DrawText { legend, .. } => legend.is_some(),
         });
 
         if has_legend_entries && theme.legend_position != LegendPosition::None {
@@ -1446,6 +2910,145 @@ impl Canvas {
             legend.draw().context("Failed to draw legend")?;
         }
 
+        if let Some(bar) = &panel.colorbar {
+            if theme.legend_position != LegendPosition::None {
+                Self::draw_colorbar(
+                    &chart.plotting_area().strip_coord_spec(),
+                    bar,
+                    theme,
+                    pixel_scale,
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Draw a continuous color guide: title, a vertical gradient strip, and min/mid/max labels.
+    /// Anchored inside the plotting area using the same corner rules as the series legend.
+    fn draw_colorbar<DB>(
+        area: &DrawingArea<DB, plotters::coord::Shift>,
+        bar: &ColorBar,
+        theme: &ResolvedTheme,
+        pixel_scale: f64,
+    ) -> Result<()>
+    where
+        DB: DrawingBackend,
+        DB::ErrorType: 'static,
+    {
+        use crate::parser::ast::LegendPosition;
+
+        if bar.colors.is_empty() {
+            return Ok(());
+        }
+
+        let (area_w, area_h) = area.dim_in_pixel();
+        let text_style = build_legend_text_style(theme);
+        let font_size = theme.legend_text.size;
+        let margin = theme.legend_margin.round() as i32;
+        let padding = scale_i32(6, pixel_scale);
+        let gap = scale_i32(4, pixel_scale);
+
+        let bar_w = (theme.legend_key_size * 0.5).round().max(1.0) as i32;
+        let bar_h = (area_h as f64 * 0.5)
+            .min(scale_f64(120.0, pixel_scale))
+            .round()
+            .max(1.0) as i32;
+
+        let labels = [
+            crate::scale::format_nice_number(bar.max),
+            crate::scale::format_nice_number((bar.min + bar.max) / 2.0),
+            crate::scale::format_nice_number(bar.min),
+        ];
+        let (label_w, _) = max_text_dimensions(area, labels.iter(), &text_style, font_size);
+        let (title_w, title_h) = match &bar.title {
+            Some(title) => estimate_text_size(area, title, &text_style, font_size),
+            None => (0, 0),
+        };
+        let title_block = if title_h > 0 { title_h as i32 + gap } else { 0 };
+
+        let box_w = padding * 2 + (title_w as i32).max(bar_w + gap + label_w as i32);
+        // Half a line of headroom keeps the centered max/min labels inside the box
+        let label_overhang = (font_size * 0.6).round() as i32;
+        let box_h = padding * 2 + title_block + bar_h + label_overhang;
+
+        let (area_w, area_h) = (area_w as i32, area_h as i32);
+        let left = match theme.legend_position {
+            LegendPosition::UpperLeft | LegendPosition::MiddleLeft | LegendPosition::LowerLeft => {
+                margin
+            }
+            LegendPosition::UpperMiddle
+            | LegendPosition::MiddleMiddle
+            | LegendPosition::LowerMiddle => (area_w - box_w) / 2,
+            _ => area_w - box_w - margin,
+        };
+        let top = match theme.legend_position {
+            LegendPosition::MiddleLeft
+            | LegendPosition::MiddleMiddle
+            | LegendPosition::MiddleRight => (area_h - box_h) / 2,
+            LegendPosition::LowerLeft
+            | LegendPosition::LowerMiddle
+            | LegendPosition::LowerRight => area_h - box_h - margin,
+            _ => margin,
+        };
+
+        if let Some(background) = &theme.legend_background {
+            area.draw(&Rectangle::new(
+                [(left, top), (left + box_w, top + box_h)],
+                background.fill.filled(),
+            ))
+            .context("Failed to draw colorbar background")?;
+            if let Some(border_color) = background.border_color {
+                area.draw(&Rectangle::new(
+                    [(left, top), (left + box_w, top + box_h)],
+                    ShapeStyle::from(&border_color)
+                        .stroke_width(to_stroke_width(background.border_width)),
+                ))
+                .context("Failed to draw colorbar border")?;
+            }
+        }
+
+        if let Some(title) = &bar.title {
+            area.draw(&Text::new(
+                title.clone(),
+                (left + padding, top + padding),
+                text_style.pos(Pos::new(HPos::Left, VPos::Top)),
+            ))
+            .context("Failed to draw colorbar title")?;
+        }
+
+        // Gradient strip: low values at the bottom, high values at the top
+        let bar_left = left + padding;
+        let bar_top = top + padding + title_block + label_overhang / 2;
+        let bar_bottom = bar_top + bar_h;
+        let steps = bar.colors.len();
+        for (i, color) in bar.colors.iter().enumerate() {
+            let y0 = bar_bottom - (bar_h as f64 * i as f64 / steps as f64).round() as i32;
+            let y1 = bar_bottom - (bar_h as f64 * (i + 1) as f64 / steps as f64).round() as i32;
+            let fill = resolve_color(color).unwrap_or(BLACK);
+            area.draw(&Rectangle::new(
+                [(bar_left, y1), (bar_left + bar_w, y0)],
+                fill.filled(),
+            ))
+            .context("Failed to draw colorbar")?;
+        }
+        area.draw(&Rectangle::new(
+            [(bar_left, bar_top), (bar_left + bar_w, bar_bottom)],
+            ShapeStyle::from(&RGBColor(128, 128, 128))
+                .stroke_width(to_stroke_width(scale_f64(0.5, pixel_scale))),
+        ))
+        .context("Failed to draw colorbar outline")?;
+
+        let label_x = bar_left + bar_w + gap;
+        let label_style = text_style.pos(Pos::new(HPos::Left, VPos::Center));
+        for (label, y) in labels
+            .iter()
+            .zip([bar_top, (bar_top + bar_bottom) / 2, bar_bottom])
+        {
+            area.draw(&Text::new(label.clone(), (label_x, y), label_style.clone()))
+                .context("Failed to draw colorbar label")?;
+        }
+
         Ok(())
     }
 }
@@ -1460,17 +3063,24 @@ fn parse_color(color_str: &Option<String>, default_color: RGBColor) -> RGBColor
 
 #[cfg(test)]
 mod tests {
-    use super::{build_axis_text_styles, calculate_axis_layout, scale_resolved_theme};
-    use crate::ir::{AxisTransform, DrawCommand, PanelScene, Scale};
-    use crate::parser::ast::Theme;
+    use super::{
+        arrowhead, border_strips, build_axis_text_styles, calculate_axis_layout, dash_pattern,
+        dash_polyline, ellipsize, estimate_text_size, fallback_char_width, fit_font_size,
+        fixed_aspect_size, pixel_column_extremes, scale_resolved_theme, wrap_text, BarStyle,
+        Canvas, CanvasLayout, MarkerOutline, OutlinedMarker, PlaceholderText,
+    };
+    use crate::ir::{AxisTransform, DrawCommand, PanelScene, Scale, SceneGraph};
+    use crate::parser::ast::{Labels, Theme};
+    use crate::RenderOptions;
     use plotters::drawing::IntoDrawingArea;
-    use plotters::prelude::BitMapBackend;
+    use plotters::prelude::{BitMapBackend, Color, IntoFont, TextStyle, BLACK, WHITE};
 
     fn sample_panel() -> PanelScene {
         PanelScene {
             row: 0,
             col: 0,
             title: None,
+            row_title: None,
             x_label: Some("Day".to_string()),
             y_label: Some("Time of Day".to_string()),
             x_scale: Scale {
@@ -1487,6 +3097,7 @@ mod tests {
                 tick_positions: vec![],
                 datetime: None,
                 transform: AxisTransform::Linear,
+                labels: Default::default(),
             },
             y_scale: Scale {
                 domain: (0.0, 2.0),
@@ -1500,8 +3111,15 @@ mod tests {
                 tick_positions: vec![],
                 datetime: None,
                 transform: AxisTransform::Linear,
+                labels: Default::default(),
             },
             commands: Vec::<DrawCommand>::new(),
+            colorbar: None,
+            show_x_tick_labels: true,
+            show_y_tick_labels: true,
+            polar: false,
+            aspect_ratio: None,
+            underlay: 0,
         }
     }
 
@@ -1510,6 +3128,7 @@ mod tests {
             row: 0,
             col: 0,
             title: None,
+            row_title: None,
             x_label: Some("Value".to_string()),
             y_label: Some("Density".to_string()),
             x_scale: Scale {
@@ -1520,6 +3139,7 @@ mod tests {
                 tick_positions: vec![],
                 datetime: None,
                 transform: AxisTransform::Linear,
+                labels: Default::default(),
             },
             y_scale: Scale {
                 domain: (0.0, 0.3),
@@ -1529,8 +3149,15 @@ mod tests {
                 tick_positions: vec![],
                 datetime: None,
                 transform: AxisTransform::Linear,
+                labels: Default::default(),
             },
             commands: Vec::<DrawCommand>::new(),
+            colorbar: None,
+            show_x_tick_labels: true,
+            show_y_tick_labels: true,
+            polar: false,
+            aspect_ratio: None,
+            underlay: 0,
         }
     }
 
@@ -1542,8 +3169,15 @@ mod tests {
         let panel = sample_panel();
 
         let (_, y_axis_style, axis_desc_style) = build_axis_text_styles(&theme);
-        let layout =
-            calculate_axis_layout(&area, &panel, &theme, &y_axis_style, &axis_desc_style, 1);
+        let layout = calculate_axis_layout(
+            &area,
+            &panel,
+            &theme,
+            &y_axis_style,
+            &axis_desc_style,
+            1.0,
+            true,
+        );
 
         assert!(
             layout.x_label_area_size > 30,
@@ -1557,6 +3191,60 @@ mod tests {
         );
     }
 
+    #[test]
+    fn border_strips_straddle_each_edge() {
+        // Corners in either order give the same strips
+        let strips = border_strips((10, 50), (40, 20), 3);
+        assert_eq!(strips, border_strips((40, 20), (10, 50), 3));
+        assert_eq!(
+            strips,
+            [
+                [(8, 18), (42, 21)],
+                [(8, 49), (42, 52)],
+                [(8, 18), (11, 52)],
+                [(39, 18), (42, 52)],
+            ]
+        );
+        let [top, ..] = border_strips((0, 0), (5, 5), 1);
+        assert_eq!(top, [(-1, -1), (6, 0)]);
+    }
+
+    #[test]
+    fn outlined_markers_grow_by_the_stroke_width() {
+        let marker =
+            |shape: &str| OutlinedMarker::new(shape, 4, 2, WHITE.filled(), BLACK.to_rgba()).outline;
+        match marker("square") {
+            MarkerOutline::Polygon(corners) => assert_eq!(
+                corners,
+                [
+                    (-4, -4),
+                    (4, -4),
+                    (4, 4),
+                    (-4, 4),
+                    (-6, -6),
+                    (6, -6),
+                    (6, 6),
+                    (-6, 6)
+                ]
+            ),
+            _ => panic!("Expected a polygon outline"),
+        }
+        // A diamond's edges sit closer to the center than its corners
+        match marker("diamond") {
+            MarkerOutline::Polygon(corners) => {
+                assert_eq!(corners[4..], [(0, -7), (7, 0), (0, 7), (-7, 0)])
+            }
+            _ => panic!("Expected a polygon outline"),
+        }
+        assert!(matches!(
+            marker("circle"),
+            MarkerOutline::Circle {
+                radius: 4,
+                width: 2
+            }
+        ));
+    }
+
     #[test]
     fn rotated_x_labels_get_more_vertical_space() {
         let mut buffer = vec![0u8; 800 * 600 * 3];
@@ -1571,8 +3259,15 @@ mod tests {
         ];
 
         let (_, y_axis_style, axis_desc_style) = build_axis_text_styles(&theme);
-        let layout =
-            calculate_axis_layout(&area, &panel, &theme, &y_axis_style, &axis_desc_style, 1);
+        let layout = calculate_axis_layout(
+            &area,
+            &panel,
+            &theme,
+            &y_axis_style,
+            &axis_desc_style,
+            1.0,
+            true,
+        );
 
         assert!(
             layout.x_label_area_size >= 80,
@@ -1598,8 +3293,15 @@ mod tests {
         let panel = numeric_y_panel();
 
         let (_, y_axis_style, axis_desc_style) = build_axis_text_styles(&theme);
-        let layout =
-            calculate_axis_layout(&area, &panel, &theme, &y_axis_style, &axis_desc_style, 1);
+        let layout = calculate_axis_layout(
+            &area,
+            &panel,
+            &theme,
+            &y_axis_style,
+            &axis_desc_style,
+            1.0,
+            true,
+        );
 
         assert!(
             layout.max_y_label_width > 0,
@@ -1612,10 +3314,119 @@ mod tests {
         );
     }
 
+    #[test]
+    fn wide_numeric_y_labels_clear_the_axis_title() {
+        let mut buffer = vec![0u8; 800 * 600 * 3];
+        let area = BitMapBackend::with_buffer(&mut buffer, (800, 600)).into_drawing_area();
+        let theme = Theme::default().resolve();
+        let mut panel = numeric_y_panel();
+        panel.y_scale.range = (0.0, 350_000_000_000.0);
+        panel.y_scale.tick_positions = vec![0.0, 150_000_000_000.0, 350_000_000_000.0];
+
+        let (_, y_axis_style, axis_desc_style) = build_axis_text_styles(&theme);
+        let layout = calculate_axis_layout(
+            &area,
+            &panel,
+            &theme,
+            &y_axis_style,
+            &axis_desc_style,
+            1.0,
+            true,
+        );
+
+        // Twelve digits, then the gap and the rotated title, inside the label area
+        assert!(
+            layout.max_y_label_width > 60,
+            "{}",
+            layout.max_y_label_width
+        );
+        assert!(
+            layout.y_label_area_size
+                >= layout.y_tick_gap
+                    + layout.max_y_label_width
+                    + layout.y_label_to_desc_gap
+                    + layout.y_desc_width,
+            "{layout:?}"
+        );
+    }
+
+    #[test]
+    fn long_text_wraps_at_word_boundaries_to_fit() {
+        let mut buffer = vec![0u8; 800 * 600 * 3];
+        let area = BitMapBackend::with_buffer(&mut buffer, (800, 600)).into_drawing_area();
+        let style = TextStyle::from(("sans-serif", 14.0).into_font());
+        let title = "Quarterly revenue by region and product line ".repeat(5);
+        let title = title.trim();
+        assert!(title.len() > 200);
+
+        let lines = wrap_text(&area, title, &style, 14.0, 780);
+        assert!(lines.len() > 1);
+        for line in &lines {
+            assert!(
+                estimate_text_size(&area, line, &style, 14.0).0 <= 780,
+                "{line}"
+            );
+            assert_eq!(line.trim(), line);
+        }
+        // Nothing is lost or reordered
+        assert_eq!(lines.join(" "), title);
+
+        // Short text stays on one line, a literal newline always breaks, and a word
+        // wider than a line is split
+        assert_eq!(wrap_text(&area, "Sales", &style, 14.0, 780), vec!["Sales"]);
+        assert_eq!(
+            wrap_text(&area, "Sales\nby region", &style, 14.0, 780),
+            vec!["Sales", "by region"]
+        );
+        let word = "x".repeat(300);
+        let pieces = wrap_text(&area, &word, &style, 14.0, 200);
+        assert!(pieces.len() > 1);
+        assert_eq!(pieces.concat(), word);
+    }
+
+    #[test]
+    fn wide_glyphs_are_never_measured_narrower_than_estimate() {
+        let mut buffer = vec![0u8; 800 * 600 * 3];
+        let area = BitMapBackend::with_buffer(&mut buffer, (800, 600)).into_drawing_area();
+        let style = TextStyle::from(("sans-serif", 10.0).into_font());
+
+        assert_eq!(fallback_char_width('a'), 0.6);
+        assert_eq!(fallback_char_width('東'), 1.0);
+        assert_eq!(fallback_char_width('😀'), 1.0);
+        assert_eq!(fallback_char_width('\u{200D}'), 0.0);
+
+        let (width, height) = estimate_text_size(&area, "東京都😀", &style, 10.0);
+        assert!(width >= 40, "expected >= 4 ems, got {}", width);
+        assert!(height >= 13);
+
+        // Long titles shrink to fit, short ones keep their size
+        let fitted = fit_font_size(
+            &area,
+            &"Long Facet Title ".repeat(10),
+            "sans-serif",
+            15.0,
+            200,
+            1.0,
+        );
+        assert!(fitted < 15.0);
+        assert_eq!(
+            fit_font_size(&area, "A", "sans-serif", 15.0, 200, 1.0),
+            15.0
+        );
+
+        // What still doesn't fit is cut short with an ellipsis
+        let long = "region = ".to_string() + &"Northwest Territories ".repeat(5);
+        let cut = ellipsize(&area, &long, &style, 10.0, 150);
+        assert!(cut.ends_with('…') && cut.len() < long.len(), "{}", cut);
+        assert!(estimate_text_size(&area, &cut, &style, 10.0).0 <= 150);
+        assert!(cut.starts_with("region = North"), "{}", cut);
+        assert_eq!(ellipsize(&area, "North", &style, 10.0, 150), "North");
+    }
+
     #[test]
     fn resolved_theme_scales_for_supersampled_pngs() {
         let theme = Theme::default().resolve();
-        let scaled = scale_resolved_theme(&theme, 2);
+        let scaled = scale_resolved_theme(&theme, 2.0);
 
         assert_eq!(scaled.plot_title.size, theme.plot_title.size * 2.0);
         assert_eq!(scaled.axis_text.size, theme.axis_text.size * 2.0);
@@ -1624,4 +3435,385 @@ mod tests {
             theme.axis_line.as_ref().unwrap().width * 2.0
         );
     }
+
+    #[test]
+    fn placeholder_text_draws_boxes_from_fixed_widths() {
+        let (width, height) = (100, 40);
+        let mut buffer = vec![255u8; width * height * 3];
+        {
+            let backend = BitMapBackend::with_buffer(&mut buffer, (width as u32, height as u32));
+            let area = PlaceholderText(backend).into_drawing_area();
+            let style = TextStyle::from(("sans-serif", 20.0).into_font()).color(&BLACK);
+            // 4 characters of 0.6em at 20px, by 0.7em
+            assert_eq!(area.estimate_text_size("abcd", &style).unwrap(), (48, 14));
+            area.draw_text("abcd", &style, (10, 10)).unwrap();
+        }
+        let pixel = |x: usize, y: usize| &buffer[(y * width + x) * 3..(y * width + x) * 3 + 3];
+        assert_eq!(pixel(30, 15), &[0, 0, 0]);
+        assert_eq!(pixel(70, 15), &[255, 255, 255]);
+        assert_eq!(pixel(30, 30), &[255, 255, 255]);
+    }
+
+    #[test]
+    fn rgba_buffer_holds_the_png_pixels_with_opaque_alpha() {
+        let scene = SceneGraph {
+            width: 240,
+            height: 160,
+            panels: vec![sample_panel()],
+            labels: Labels::default(),
+            theme: Theme::default(),
+            facet_label_size: None,
+            nrow: 1,
+            ncol: 1,
+        };
+        let options = RenderOptions {
+            width: 240,
+            height: 160,
+            ..RenderOptions::default()
+        };
+        let png = Canvas::execute(scene.clone(), &options).unwrap();
+        let (rgba, width, height) = Canvas::render_rgba(scene).unwrap();
+
+        assert_eq!((width, height), (240, 160));
+        assert_eq!(rgba.len(), 240 * 160 * 4);
+        assert_eq!(&rgba[..4], &[255, 255, 255, 255]);
+        assert!(rgba.chunks(4).all(|pixel| pixel[3] == 255));
+        let decoded = image::load_from_memory(&png).unwrap().to_rgba8();
+        assert_eq!(decoded.into_raw(), rgba);
+    }
+
+    /// A panel holding a single magenta rect spanning the full data range, so its
+    /// pixels are the plot area
+    fn unit_rect_scene(width: u32, height: u32) -> SceneGraph {
+        let unit_scale = Scale {
+            domain: (0.0, 1.0),
+            range: (0.0, 1.0),
+            is_categorical: false,
+            categories: vec![],
+            tick_positions: vec![0.0, 0.5, 1.0],
+            datetime: None,
+            transform: AxisTransform::Linear,
+            labels: Default::default(),
+        };
+        SceneGraph {
+            width,
+            height,
+            panels: vec![PanelScene {
+                row: 0,
+                col: 0,
+                title: None,
+                row_title: None,
+                x_label: Some("Horizontal".to_string()),
+                y_label: Some("Vertical".to_string()),
+                x_scale: unit_scale.clone(),
+                y_scale: unit_scale,
+                commands: vec![DrawCommand::DrawRect {
+                    tl: (0.0, 1.0),
+                    br: (1.0, 0.0),
+                    style: BarStyle {
+                        color: Some("#ff00ff".to_string()),
+                        alpha: Some(1.0),
+                        width: None,
+                        border: None,
+                        border_width: None,
+                    },
+                    legend: None,
+                }],
+                colorbar: None,
+                show_x_tick_labels: true,
+                show_y_tick_labels: true,
+                polar: false,
+                aspect_ratio: None,
+                underlay: 0,
+            }],
+            labels: Labels {
+                title: Some("Thumbnail".to_string()),
+                ..Default::default()
+            },
+            theme: Theme::default(),
+            facet_label_size: None,
+            nrow: 1,
+            ncol: 1,
+        }
+    }
+
+    /// The scene's magenta pixels
+    fn magenta_pixels(scene: SceneGraph) -> Vec<(u32, u32)> {
+        let options = RenderOptions {
+            width: scene.width,
+            height: scene.height,
+            ..RenderOptions::default()
+        };
+        let png = Canvas::execute(scene, &options).unwrap();
+        let image = image::load_from_memory(&png).unwrap().to_rgb8();
+        image
+            .enumerate_pixels()
+            .filter(|(_, _, p)| p.0[0] > 200 && p.0[1] < 60 && p.0[2] > 200)
+            .map(|(x, y, _)| (x, y))
+            .collect()
+    }
+
+    /// Fraction of the canvas covered by the plot area
+    fn plot_area_fraction(width: u32, height: u32) -> f64 {
+        let plot_pixels = magenta_pixels(unit_rect_scene(width, height)).len();
+        plot_pixels as f64 / (width * height) as f64
+    }
+
+    /// Left, top, width, and height of the plot area in pixels
+    fn plot_area_bounds(scene: SceneGraph) -> (u32, u32, u32, u32) {
+        let pixels = magenta_pixels(scene);
+        let (left, right) = (
+            pixels.iter().map(|p| p.0).min().unwrap(),
+            pixels.iter().map(|p| p.0).max().unwrap(),
+        );
+        let (top, bottom) = (
+            pixels.iter().map(|p| p.1).min().unwrap(),
+            pixels.iter().map(|p| p.1).max().unwrap(),
+        );
+        (left, top, right - left + 1, bottom - top + 1)
+    }
+
+    #[test]
+    fn fixed_aspect_gives_a_square_extent_a_square_plot_area() {
+        // Unit ranges on both axes; a free aspect stretches them over the canvas
+        let (_, _, width, height) = plot_area_bounds(unit_rect_scene(800, 400));
+        assert!(width > height * 3 / 2, "{}x{}", width, height);
+
+        let mut scene = unit_rect_scene(800, 400);
+        scene.panels[0].aspect_ratio = Some(1.0);
+        let (left, _, width, height) = plot_area_bounds(scene);
+        assert!(width.abs_diff(height) <= 1, "{}x{}", width, height);
+        // The plot area is centred in the room the free aspect gave it
+        assert!(left > 200, "{}", left);
+
+        // ratio: 2 spends twice the pixels on a y unit
+        let mut scene = unit_rect_scene(400, 800);
+        scene.panels[0].aspect_ratio = Some(2.0);
+        let (_, _, width, height) = plot_area_bounds(scene);
+        assert!(height.abs_diff(width * 2) <= 2, "{}x{}", width, height);
+    }
+
+    #[test]
+    fn fixed_aspect_size_shrinks_the_longer_side() {
+        let mut panel = numeric_y_panel();
+        panel.x_scale.range = (0.0, 10.0);
+        panel.y_scale.range = (0.0, 5.0);
+        let size = |area, ratio| fixed_aspect_size(&panel.x_scale, &panel.y_scale, area, ratio);
+        assert_eq!(size((600, 600), 1.0), (600, 300));
+        assert_eq!(size((600, 200), 1.0), (400, 200));
+        assert_eq!(size((600, 600), 2.0), (600, 600));
+        assert_eq!(size((600, 600), 4.0), (300, 600));
+    }
+
+    #[test]
+    fn theme_margin_sets_the_space_around_the_panel() {
+        let (left, top, _, _) = plot_area_bounds(unit_rect_scene(800, 600));
+        let mut scene = unit_rect_scene(800, 600);
+        scene.theme.margin = Some(40.0);
+        let (wide_left, wide_top, _, _) = plot_area_bounds(scene);
+        // 25px more than the default 15 on each side
+        assert_eq!((wide_left - left, wide_top - top), (25, 25));
+    }
+
+    /// Magenta pixels in each quadrant of a 2x2 facet of full-height magenta bars
+    fn facet_plot_areas(scales: &str) -> [usize; 4] {
+        let rows = ["a", "b", "c", "d"]
+            .iter()
+            .map(|f| vec!["A".to_string(), "1000".to_string(), f.to_string()])
+            .collect();
+        let data = crate::data::PlotData::new(vec!["x".into(), "y".into(), "f".into()], rows);
+        let dsl = format!(
+            r##"aes(x: x, y: y) | bar(color: "#ff00ff", width: 1) | facet_wrap(by: f, scales: "{}")"##,
+            scales
+        );
+        let (_, spec) = crate::parser::parse_plot_spec(&dsl).unwrap();
+        let png = crate::runtime::render_plot(spec, data, RenderOptions::default()).unwrap();
+        let image = image::load_from_memory(&png).unwrap().to_rgb8();
+        let (w, h) = image.dimensions();
+        let mut counts = [0; 4];
+        for (x, y, p) in image.enumerate_pixels() {
+            if p.0[0] > 200 && p.0[1] < 60 && p.0[2] > 200 {
+                counts[(y >= h / 2) as usize * 2 + (x >= w / 2) as usize] += 1;
+            }
+        }
+        counts
+    }
+
+    #[test]
+    fn fixed_facets_give_interior_panels_the_label_space() {
+        // Quadrants: [top-left, top-right, bottom-left, bottom-right]
+        let [top_left, top_right, bottom_left, bottom_right] = facet_plot_areas("fixed");
+        // Only the left column has y tick labels and only the bottom row x tick labels
+        assert!(top_right > top_left, "{} vs {}", top_right, top_left);
+        assert!(
+            top_right > bottom_right,
+            "{} vs {}",
+            top_right,
+            bottom_right
+        );
+        assert!(top_left > bottom_left, "{} vs {}", top_left, bottom_left);
+        assert!(
+            top_right as f64 > bottom_left as f64 * 1.1,
+            "{} vs {}",
+            top_right,
+            bottom_left
+        );
+
+        // Free scales keep every panel's labels, so the areas match
+        let free = facet_plot_areas("free");
+        assert!(free.iter().all(|&n| n == free[0]), "{:?}", free);
+    }
+
+    #[test]
+    fn small_canvases_scale_layout_down() {
+        assert_eq!(
+            CanvasLayout::for_size(800, 600),
+            CanvasLayout {
+                scale: 1.0,
+                show_axis_text: true
+            }
+        );
+        let compact = CanvasLayout::for_size(480, 320);
+        assert!(compact.scale < 1.0 && compact.show_axis_text);
+        let thumbnail = CanvasLayout::for_size(320, 200);
+        assert!(thumbnail.scale < compact.scale && !thumbnail.show_axis_text);
+
+        for (width, height) in [(480, 320), (320, 200), (240, 160)] {
+            let fraction = plot_area_fraction(width, height);
+            assert!(
+                fraction >= 0.7,
+                "plot area covers only {:.0}% of a {}x{} canvas",
+                fraction * 100.0,
+                width,
+                height
+            );
+        }
+    }
+
+    fn dashes(linetype: &str, points: &[(f64, f64)]) -> Vec<Vec<(f64, f64)>> {
+        let pattern = dash_pattern(Some(linetype)).unwrap();
+        dash_polyline(points, |p| p, pattern)
+    }
+
+    #[test]
+    fn solid_linetype_has_no_dash_pattern() {
+        assert_eq!(dash_pattern(None), None);
+        assert_eq!(dash_pattern(Some("solid")), None);
+        // Unknown names fall back to solid
+        assert_eq!(dash_pattern(Some("wavy")), None);
+        assert_eq!(dash_pattern(Some("Dashed")), dash_pattern(Some("dashed")));
+    }
+
+    #[test]
+    fn dashed_linetype_alternates_four_on_two_off() {
+        let spans = dashes("dashed", &[(0.0, 0.0), (14.0, 0.0)]);
+        assert_eq!(
+            spans,
+            vec![
+                vec![(0.0, 0.0), (4.0, 0.0)],
+                vec![(6.0, 0.0), (10.0, 0.0)],
+                vec![(12.0, 0.0), (14.0, 0.0)],
+            ]
+        );
+    }
+
+    #[test]
+    fn dotted_linetype_draws_short_dots() {
+        let spans = dashes("dotted", &[(0.0, 0.0), (0.0, 6.0)]);
+        assert_eq!(
+            spans,
+            vec![
+                vec![(0.0, 0.0), (0.0, 1.0)],
+                vec![(0.0, 2.5), (0.0, 3.5)],
+                vec![(0.0, 5.0), (0.0, 6.0)],
+            ]
+        );
+    }
+
+    #[test]
+    fn dotdash_linetype_alternates_dots_and_dashes() {
+        let spans = dashes("dotdash", &[(0.0, 0.0), (10.0, 0.0)]);
+        assert_eq!(
+            spans,
+            vec![
+                vec![(0.0, 0.0), (1.0, 0.0)],
+                vec![(2.5, 0.0), (6.5, 0.0)],
+                vec![(8.0, 0.0), (9.0, 0.0)],
+            ]
+        );
+    }
+
+    #[test]
+    fn dashes_continue_across_vertices() {
+        // The first dash turns the corner at (3, 0) instead of restarting there
+        let spans = dashes("dashed", &[(0.0, 0.0), (3.0, 0.0), (3.0, 5.0)]);
+        assert_eq!(
+            spans,
+            vec![
+                vec![(0.0, 0.0), (3.0, 0.0), (3.0, 1.0)],
+                vec![(3.0, 3.0), (3.0, 5.0)],
+            ]
+        );
+    }
+
+    #[test]
+    fn arrowheads_point_along_the_segment() {
+        // Pointing right (+x): barbs trail to the left, either side of the shaft
+        let corners = arrowhead((0.0, 0.0), (50.0, 0.0), 10.0).unwrap();
+        assert_eq!(corners, vec![(0, 0), (-10, 6), (-10, -6)]);
+
+        // Pointing down in pixel space
+        let corners = arrowhead((5.0, 5.0), (5.0, 25.0), 10.0).unwrap();
+        assert_eq!(corners, vec![(0, 0), (-6, -10), (6, -10)]);
+
+        assert_eq!(arrowhead((1.0, 1.0), (1.0, 1.0), 10.0), None);
+    }
+
+    #[test]
+    fn dashes_are_measured_in_pixels() {
+        // One data unit is ten pixels, so a 4px dash covers 0.4 units
+        let pattern = dash_pattern(Some("dashed")).unwrap();
+        let spans = dash_polyline(&[(0.0, 0.0), (1.0, 0.0)], |(x, y)| (x * 10.0, y), pattern);
+        assert_eq!(spans[0], vec![(0.0, 0.0), (0.4, 0.0)]);
+        assert_eq!(spans.len(), 2);
+    }
+
+    #[test]
+    fn column_extremes_keep_first_lowest_highest_last() {
+        // Column 10 holds five points; 11 and 12 one each
+        let pixels = [
+            (10, 5),
+            (10, 9),
+            (10, 1),
+            (10, 7),
+            (10, 6),
+            (11, 3),
+            (12, 4),
+        ];
+        assert_eq!(pixel_column_extremes(&pixels), vec![0, 1, 2, 4, 5, 6]);
+
+        // Extremes that are also the first or last point are kept once
+        let pixels = [(3, 1), (3, 4), (3, 2), (3, 8)];
+        assert_eq!(pixel_column_extremes(&pixels), vec![0, 3]);
+
+        // A column revisited later is a new run
+        let pixels = [(1, 0), (2, 0), (1, 0)];
+        assert_eq!(pixel_column_extremes(&pixels), vec![0, 1, 2]);
+        assert!(pixel_column_extremes(&[]).is_empty());
+    }
+
+    #[test]
+    fn equal_unit_ranges_keep_circles_round() {
+        let mut panel = numeric_y_panel();
+        panel.x_scale.range = (-1.0, 1.0);
+        panel.y_scale.range = (-1.0, 1.0);
+        // A wide plotting area widens x so both axes spend 200px per unit
+        let (x, y) = super::equal_unit_ranges(&panel.x_scale, &panel.y_scale, (800, 400));
+        assert_eq!(x, -2.0..2.0);
+        assert_eq!(y, -1.0..1.0);
+
+        let (x, y) = super::equal_unit_ranges(&panel.x_scale, &panel.y_scale, (300, 600));
+        assert_eq!(x, -1.0..1.0);
+        assert_eq!(y, -2.0..2.0);
+    }
 }
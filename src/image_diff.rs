@@ -0,0 +1,209 @@
+// Pixel-level image comparison for visual review and golden-image tests
+
+use anyhow::{Context, Result};
+use image::{ImageEncoder, Rgb, RgbImage};
+use std::path::Path;
+
+/// Color for pixels that only exist in one of the two images
+const OUT_OF_BOUNDS_COLOR: Rgb<u8> = Rgb([255, 0, 255]);
+
+/// Result of comparing two images pixel by pixel
+#[derive(Debug, Clone)]
+pub struct DiffResult {
+    /// Dimensions of the first (old) image
+    pub old_dimensions: (u32, u32),
+    /// Dimensions of the second (new) image
+    pub new_dimensions: (u32, u32),
+    /// Number of pixels that differ beyond the tolerance
+    pub mismatched: u64,
+    /// Number of pixels compared (the union of both canvases)
+    pub total: u64,
+    /// Heat image: unchanged pixels dimmed, changed pixels colored by magnitude
+    pub diff_image: RgbImage,
+}
+
+impl DiffResult {
+    /// Percentage of mismatched pixels in 0..=100
+    pub fn mismatch_percent(&self) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            self.mismatched as f64 / self.total as f64 * 100.0
+        }
+    }
+
+    /// True when the two images had different dimensions
+    pub fn dimensions_differ(&self) -> bool {
+        self.old_dimensions != self.new_dimensions
+    }
+}
+
+/// Compare two images pixel by pixel.
+///
+/// A pixel matches when every channel differs by at most `tolerance`.
+/// Images of different sizes are compared over the union of both canvases;
+/// pixels present in only one image always count as mismatched.
+pub fn diff_images(old: &RgbImage, new: &RgbImage, tolerance: u8) -> DiffResult {
+    let width = old.width().max(new.width());
+    let height = old.height().max(new.height());
+    let mut diff_image = RgbImage::new(width, height);
+    let mut mismatched = 0u64;
+
+    for y in 0..height {
+        for x in 0..width {
+            let a = pixel_at(old, x, y);
+            let b = pixel_at(new, x, y);
+            let out = match (a, b) {
+                (Some(a), Some(b)) => {
+                    let delta = channel_delta(a, b);
+                    if delta > tolerance {
+                        mismatched += 1;
+                        heat_color(delta)
+                    } else {
+                        dimmed(a)
+                    }
+                }
+                _ => {
+                    mismatched += 1;
+                    OUT_OF_BOUNDS_COLOR
+                }
+            };
+            diff_image.put_pixel(x, y, out);
+        }
+    }
+
+    DiffResult {
+        old_dimensions: old.dimensions(),
+        new_dimensions: new.dimensions(),
+        mismatched,
+        total: width as u64 * height as u64,
+        diff_image,
+    }
+}
+
+/// Decode two encoded images (PNG or any format `image` supports) and compare them
+pub fn diff_bytes(old: &[u8], new: &[u8], tolerance: u8) -> Result<DiffResult> {
+    let old = image::load_from_memory(old)
+        .context("Failed to decode old image")?
+        .to_rgb8();
+    let new = image::load_from_memory(new)
+        .context("Failed to decode new image")?
+        .to_rgb8();
+    Ok(diff_images(&old, &new, tolerance))
+}
+
+/// Load two image files from disk and compare them
+pub fn diff_files(old: &Path, new: &Path, tolerance: u8) -> Result<DiffResult> {
+    let old = image::open(old)
+        .with_context(|| format!("Failed to open image '{}'", old.display()))?
+        .to_rgb8();
+    let new = image::open(new)
+        .with_context(|| format!("Failed to open image '{}'", new.display()))?
+        .to_rgb8();
+    Ok(diff_images(&old, &new, tolerance))
+}
+
+/// Encode an RGB image (such as `DiffResult::diff_image`) as PNG bytes
+pub fn encode_png(img: &RgbImage) -> Result<Vec<u8>> {
+    let mut png_bytes = Vec::new();
+    image::codecs::png::PngEncoder::new(&mut png_bytes)
+        .write_image(
+            img.as_raw(),
+            img.width(),
+            img.height(),
+            image::ColorType::Rgb8,
+        )
+        .context("Failed to encode PNG")?;
+    Ok(png_bytes)
+}
+
+fn pixel_at(img: &RgbImage, x: u32, y: u32) -> Option<&Rgb<u8>> {
+    if x < img.width() && y < img.height() {
+        Some(img.get_pixel(x, y))
+    } else {
+        None
+    }
+}
+
+/// Largest per-channel absolute difference
+fn channel_delta(a: &Rgb<u8>, b: &Rgb<u8>) -> u8 {
+    a.0.iter()
+        .zip(b.0.iter())
+        .map(|(x, y)| x.abs_diff(*y))
+        .max()
+        .unwrap_or(0)
+}
+
+/// Faded grayscale so unchanged regions give context without drawing the eye
+fn dimmed(p: &Rgb<u8>) -> Rgb<u8> {
+    let [r, g, b] = p.0;
+    let luma = 0.299 * r as f64 + 0.587 * g as f64 + 0.114 * b as f64;
+    let v = (luma * 0.3 + 255.0 * 0.7).round() as u8;
+    Rgb([v, v, v])
+}
+
+/// Yellow for faint changes through to red for full-intensity changes
+fn heat_color(delta: u8) -> Rgb<u8> {
+    let t = delta as f64 / 255.0;
+    let lerp = |a: f64, b: f64| (a + (b - a) * t).round() as u8;
+    Rgb([lerp(255.0, 215.0), lerp(215.0, 25.0), lerp(0.0, 28.0)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(width: u32, height: u32, color: [u8; 3]) -> RgbImage {
+        RgbImage::from_pixel(width, height, Rgb(color))
+    }
+
+    #[test]
+    fn identical_images_have_no_mismatch() {
+        let a = solid(4, 3, [10, 20, 30]);
+        let result = diff_images(&a, &a.clone(), 0);
+        assert_eq!(result.mismatched, 0);
+        assert_eq!(result.total, 12);
+        assert_eq!(result.mismatch_percent(), 0.0);
+        assert!(!result.dimensions_differ());
+    }
+
+    #[test]
+    fn tolerance_is_per_channel() {
+        let a = solid(2, 2, [100, 100, 100]);
+        let b = solid(2, 2, [103, 98, 100]);
+        assert_eq!(diff_images(&a, &b, 3).mismatched, 0);
+        assert_eq!(diff_images(&a, &b, 2).mismatched, 4);
+    }
+
+    #[test]
+    fn changed_pixels_are_highlighted() {
+        let a = solid(2, 1, [255, 255, 255]);
+        let mut b = a.clone();
+        b.put_pixel(1, 0, Rgb([0, 0, 0]));
+        let result = diff_images(&a, &b, 0);
+        assert_eq!(result.mismatched, 1);
+        assert_eq!(result.mismatch_percent(), 50.0);
+        assert_eq!(*result.diff_image.get_pixel(1, 0), heat_color(255));
+        assert_eq!(*result.diff_image.get_pixel(0, 0), Rgb([255, 255, 255]));
+    }
+
+    #[test]
+    fn dimension_mismatch_counts_uncovered_pixels() {
+        let a = solid(2, 2, [0, 0, 0]);
+        let b = solid(3, 2, [0, 0, 0]);
+        let result = diff_images(&a, &b, 0);
+        assert!(result.dimensions_differ());
+        assert_eq!(result.diff_image.dimensions(), (3, 2));
+        assert_eq!(result.total, 6);
+        assert_eq!(result.mismatched, 2);
+        assert_eq!(*result.diff_image.get_pixel(2, 1), OUT_OF_BOUNDS_COLOR);
+    }
+
+    #[test]
+    fn diff_bytes_decodes_png() {
+        let encoded = encode_png(&solid(3, 3, [1, 2, 3])).unwrap();
+        let result = diff_bytes(&encoded, &encoded, 0).unwrap();
+        assert_eq!(result.mismatched, 0);
+        assert!(diff_bytes(b"not an image", &encoded, 0).is_err());
+    }
+}
@@ -0,0 +1,678 @@
+// Describing data and plans without drawing: the CLI's `--list-columns`, `--dry-run`,
+// and `--explain json`
+//
+// `column_summaries` types each column the way rendering will (numeric by
+// `csv_reader::ColumnStore`, dates by `datetime::parse_datetime_value`, text
+// otherwise), and `PlotPlan` is the resolved spec, transformed data, and scales a
+// render would draw. Both print as plain-text tables for a terminal; a plan also
+// serializes as an `Explanation` for programs.
+
+use crate::analysis::XAxisKind;
+use crate::csv_reader::{is_missing, Column, ColumnStore, CsvData};
+use crate::ir::{RenderData, ResolvedLayer, ResolvedSpec, Scale, ScaleSystem};
+use crate::warning::Warning;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+
+/// Columns with at most this many distinct values list their count
+pub const LOW_CARDINALITY: usize = 20;
+
+/// How a column's values are read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnKind {
+    /// Every present cell is a number
+    Numeric,
+    /// Every present cell is a date or datetime `scale_x_datetime()` parses
+    Date,
+    Text,
+}
+
+impl fmt::Display for ColumnKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ColumnKind::Numeric => "numeric",
+            ColumnKind::Date => "date",
+            ColumnKind::Text => "text",
+        })
+    }
+}
+
+/// One row of `--list-columns`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnSummary {
+    pub name: String,
+    pub kind: ColumnKind,
+    /// Distinct present values, when there are at most `LOW_CARDINALITY`
+    pub distinct: Option<usize>,
+    /// Smallest and largest value of a numeric column with any present value
+    pub range: Option<(f64, f64)>,
+}
+
+/// Type and summarize each column, in header order. Missing cells (empty, NA, ...)
+/// are left out of every count.
+///
+/// ```
+/// use gramgraph::csv_reader::CsvData;
+/// use gramgraph::inspect::{column_summaries, ColumnKind};
+///
+/// let data = CsvData::from_columns(vec![
+///     ("day".to_string(), vec!["2024-01-01".to_string(), "2024-01-02".to_string()]),
+///     ("sales".to_string(), vec!["10".to_string(), "NA".to_string()]),
+/// ])
+/// .unwrap();
+/// let columns = column_summaries(&data);
+/// assert_eq!(columns[0].kind, ColumnKind::Date);
+/// assert_eq!(columns[1].range, Some((10.0, 10.0)));
+/// ```
+pub fn column_summaries(data: &CsvData) -> Vec<ColumnSummary> {
+    let store = ColumnStore::new(&data.headers, &data.rows);
+    data.headers
+        .iter()
+        .enumerate()
+        .map(|(idx, name)| {
+            let cells: Vec<&str> = data
+                .rows
+                .iter()
+                .map(|row| row.get(idx).map(String::as_str).unwrap_or_default())
+                .filter(|cell| !is_missing(cell))
+                .collect();
+            let (kind, range) = match store.column(idx) {
+                Column::Numeric(values) => {
+                    let present = values.iter().copied().filter(|v| !v.is_nan());
+                    let range = present.fold(None, |range: Option<(f64, f64)>, v| {
+                        Some(range.map_or((v, v), |(lo, hi)| (lo.min(v), hi.max(v))))
+                    });
+                    (ColumnKind::Numeric, range)
+                }
+                Column::Text
+                    if !cells.is_empty()
+                        && cells
+                            .iter()
+                            .all(|cell| crate::datetime::parse_datetime_value(cell).is_ok()) =>
+                {
+                    (ColumnKind::Date, None)
+                }
+                Column::Text => (ColumnKind::Text, None),
+            };
+            let distinct = cells.iter().collect::<HashSet<_>>().len();
+            ColumnSummary {
+                name: name.clone(),
+                kind,
+                distinct: (distinct <= LOW_CARDINALITY).then_some(distinct),
+                range,
+            }
+        })
+        .collect()
+}
+
+/// The summaries as an aligned table with a header row; `-` marks a blank cell
+///
+/// ```text
+/// column  type     distinct  min  max
+/// day     date     2         -    -
+/// sales   numeric  1         10   10
+/// ```
+pub fn format_column_table(columns: &[ColumnSummary]) -> String {
+    let mut rows = vec![["column", "type", "distinct", "min", "max"].map(String::from)];
+    for column in columns {
+        let (min, max) = column
+            .range
+            .map_or(("-".to_string(), "-".to_string()), |(lo, hi)| {
+                (lo.to_string(), hi.to_string())
+            });
+        let distinct = match column.distinct {
+            Some(count) => count.to_string(),
+            None => format!(">{}", LOW_CARDINALITY),
+        };
+        rows.push([
+            column.name.clone(),
+            column.kind.to_string(),
+            distinct,
+            min,
+            max,
+        ]);
+    }
+    format_table(&rows)
+}
+
+/// Left-aligned columns two spaces apart, without trailing spaces
+fn format_table<const N: usize>(rows: &[[String; N]]) -> String {
+    let mut widths = [0; N];
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let mut out = String::new();
+    for row in rows {
+        let mut line = String::new();
+        for (cell, width) in row.iter().zip(widths) {
+            line.push_str(&format!("{:<width$}  ", cell, width = width));
+        }
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+/// What a render would draw, resolved, transformed, and scaled but not drawn
+/// (`runtime::plan_with_sources`). Displays as the `--dry-run` report.
+#[derive(Debug, Clone)]
+pub struct PlotPlan {
+    pub spec: ResolvedSpec,
+    pub data: RenderData,
+    pub scales: ScaleSystem,
+    /// Rows of the plot's data before any stage
+    pub rows: usize,
+    pub warnings: Vec<Warning>,
+}
+
+impl PlotPlan {
+    /// The distinct groups layer `index` splits into across every panel
+    pub fn group_count(&self, index: usize) -> usize {
+        self.data
+            .panels
+            .iter()
+            .flat_map(|panel| &panel.layers[index].groups)
+            .map(|group| group.key.as_str())
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    /// Whether layer `index` maps color and size to a continuous scale rather than
+    /// grouping by them
+    fn continuous(&self, index: usize) -> (bool, bool) {
+        let layers = || self.data.panels.iter().map(|panel| &panel.layers[index]);
+        (
+            layers().any(|layer| layer.color_domain.is_some()),
+            layers().any(|layer| layer.size_domain.is_some()),
+        )
+    }
+
+    /// Layer `index`'s style columns, split into those on a continuous scale and
+    /// those that group it
+    fn styles(&self, index: usize) -> (Mappings<'_>, Mappings<'_>) {
+        let (continuous_color, continuous_size) = self.continuous(index);
+        styles(&self.spec.layers[index])
+            .into_iter()
+            .partition(|(aesthetic, _)| match *aesthetic {
+                "color" => continuous_color,
+                "size" => continuous_size,
+                _ => false,
+            })
+    }
+
+    /// The plan as the `--explain json` document
+    pub fn explain(&self) -> Explanation {
+        let layers = self
+            .spec
+            .layers
+            .iter()
+            .enumerate()
+            .map(|(i, layer)| {
+                let original = &layer.original_layer;
+                let (continuous, grouping) = if original.is_reference() {
+                    Default::default()
+                } else {
+                    self.styles(i)
+                };
+                let mut columns: BTreeMap<&str, String> = BTreeMap::new();
+                if !original.is_reference() {
+                    for (aesthetic, col) in mappings(layer).into_iter().chain(continuous.clone()) {
+                        columns.insert(aesthetic, col.to_string());
+                    }
+                }
+                let grouping = (!grouping.is_empty()).then(|| {
+                    let mut levels: Vec<String> = Vec::new();
+                    for panel in &self.data.panels {
+                        for group in &panel.layers[i].groups {
+                            if !levels.contains(&group.key) {
+                                levels.push(group.key.clone());
+                            }
+                        }
+                    }
+                    LayerGrouping {
+                        by: grouping
+                            .iter()
+                            .map(|(aesthetic, col)| (*aesthetic, col.to_string()))
+                            .collect(),
+                        groups: levels.len(),
+                        levels,
+                    }
+                });
+                LayerExplanation {
+                    geom: original.name(),
+                    data: original.data().map(String::from),
+                    columns,
+                    continuous: continuous.iter().map(|(aesthetic, _)| *aesthetic).collect(),
+                    grouping,
+                }
+            })
+            .collect();
+
+        let layout = &self.data.facet_layout;
+        let facet = self.spec.facet.as_ref().map(|facet| FacetExplanation {
+            by: match &facet.grid {
+                Some(grid) => [&grid.rows, &grid.cols]
+                    .into_iter()
+                    .flatten()
+                    .cloned()
+                    .collect(),
+                None => vec![facet.col.clone()],
+            },
+            nrow: layout.nrow,
+            ncol: layout.ncol,
+        });
+        let panels = self
+            .scales
+            .panels
+            .iter()
+            .enumerate()
+            .map(|(i, scales)| PanelExplanation {
+                title: self
+                    .spec
+                    .facet
+                    .as_ref()
+                    .and_then(|_| layout.panel_titles.get(i).cloned()),
+                row: layout.row_titles.get(i).cloned(),
+                rows: layout.panel_rows.get(i).copied().unwrap_or(self.rows),
+                x: AxisExplanation::new(&scales.x),
+                y: AxisExplanation::new(&scales.y),
+            })
+            .collect::<Vec<_>>();
+
+        Explanation {
+            rows: self.rows,
+            stages: self.spec.stages.iter().map(|s| s.name()).collect(),
+            x_axis: panels
+                .first()
+                .map_or(XAxisKind::Continuous, |panel| panel.x.kind),
+            layers,
+            facet,
+            panels,
+            warnings: self.warnings.iter().map(|w| w.to_string()).collect(),
+        }
+    }
+
+    /// `explain()` as pretty-printed JSON
+    pub fn to_json(&self) -> anyhow::Result<String> {
+        Ok(serde_json::to_string_pretty(&self.explain())?)
+    }
+}
+
+/// A `PlotPlan` summarized for programs that build plots (`--explain json`)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Explanation {
+    /// Rows of the plot's data before any stage
+    pub rows: usize,
+    pub stages: Vec<&'static str>,
+    /// The first panel's x axis
+    pub x_axis: XAxisKind,
+    pub layers: Vec<LayerExplanation>,
+    /// None without `facet_wrap()`/`facet_grid()`
+    pub facet: Option<FacetExplanation>,
+    /// One per panel; a plot without facets has one
+    pub panels: Vec<PanelExplanation>,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LayerExplanation {
+    /// The DSL function name (`Layer::name`)
+    pub geom: &'static str,
+    /// The `data:` table the layer reads instead of the plot's data
+    pub data: Option<String>,
+    /// Each aesthetic's column, after positions and variables are resolved
+    pub columns: BTreeMap<&'static str, String>,
+    /// Aesthetics among `columns` drawn on a continuous scale
+    pub continuous: Vec<&'static str>,
+    /// None when the layer isn't split into groups
+    pub grouping: Option<LayerGrouping>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LayerGrouping {
+    /// Each grouping aesthetic's column
+    pub by: BTreeMap<&'static str, String>,
+    /// Distinct groups across every panel
+    pub groups: usize,
+    /// The group keys in drawing order
+    pub levels: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FacetExplanation {
+    /// The faceting columns (rows then cols for `facet_grid()`)
+    pub by: Vec<String>,
+    pub nrow: usize,
+    pub ncol: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PanelExplanation {
+    /// The panel's facet value; None without facets
+    pub title: Option<String>,
+    /// The `facet_grid()` row value
+    pub row: Option<String>,
+    /// Rows of the staged data in the panel
+    pub rows: usize,
+    pub x: AxisExplanation,
+    pub y: AxisExplanation,
+}
+
+/// A computed axis. Numeric ends are in data units (seconds on a datetime axis,
+/// slot positions on a categorical one).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AxisExplanation {
+    pub kind: XAxisKind,
+    pub transform: &'static str,
+    pub min: f64,
+    pub max: f64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub categories: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub ticks: Vec<f64>,
+}
+
+impl AxisExplanation {
+    fn new(scale: &Scale) -> Self {
+        let kind = if scale.is_categorical {
+            XAxisKind::Categorical
+        } else if scale.datetime.is_some() {
+            XAxisKind::DateTime
+        } else {
+            XAxisKind::Continuous
+        };
+        let invert = |v: f64| scale.transform.invert(v);
+        AxisExplanation {
+            kind,
+            transform: match scale.transform {
+                crate::ir::AxisTransform::Linear => "linear",
+                crate::ir::AxisTransform::Log10 => "log10",
+                crate::ir::AxisTransform::Sqrt => "sqrt",
+            },
+            min: invert(scale.domain.0),
+            max: invert(scale.domain.1),
+            categories: scale.categories.clone(),
+            ticks: scale.tick_positions.iter().copied().map(invert).collect(),
+        }
+    }
+}
+
+impl fmt::Display for PlotPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Data: {}", count(self.rows, "row"))?;
+        if !self.spec.stages.is_empty() {
+            let stages: Vec<&str> = self.spec.stages.iter().map(|s| s.name()).collect();
+            write!(f, ", then {}", stages.join(", "))?;
+        }
+        writeln!(f)?;
+
+        for (i, layer) in self.spec.layers.iter().enumerate() {
+            write!(f, "Layer {}: {}()", i + 1, layer.original_layer.name())?;
+            if let Some(table) = layer.original_layer.data() {
+                write!(f, " from data \"{}\"", table)?;
+            }
+            // Reference lines read no columns
+            if layer.original_layer.is_reference() {
+                writeln!(f)?;
+                continue;
+            }
+            let (continuous, grouping) = self.styles(i);
+            let mappings: Vec<String> = mappings(layer)
+                .into_iter()
+                .map(|(aesthetic, col)| format!("{} = {}", aesthetic, col))
+                .chain(
+                    continuous
+                        .iter()
+                        .map(|(aesthetic, col)| format!("{} = {} (continuous)", aesthetic, col)),
+                )
+                .collect();
+            let grouping: Vec<String> = grouping
+                .iter()
+                .map(|(aesthetic, col)| format!("{} = {}", aesthetic, col))
+                .collect();
+            if !mappings.is_empty() {
+                write!(f, " {}", mappings.join(", "))?;
+            }
+            if !grouping.is_empty() {
+                write!(
+                    f,
+                    "; grouped by {} ({})",
+                    grouping.join(", "),
+                    count(self.group_count(i), "group")
+                )?;
+            }
+            writeln!(f)?;
+        }
+
+        let panels = self.data.panels.len();
+        let layout = &self.data.facet_layout;
+        match &self.spec.facet {
+            Some(facet) => {
+                let by = match &facet.grid {
+                    Some(grid) => [&grid.rows, &grid.cols]
+                        .into_iter()
+                        .flatten()
+                        .cloned()
+                        .collect::<Vec<_>>()
+                        .join(" x "),
+                    None => facet.col.clone(),
+                };
+                writeln!(
+                    f,
+                    "Facets: {} by {} in a {} x {} grid",
+                    count(panels, "panel"),
+                    by,
+                    layout.nrow,
+                    layout.ncol
+                )
+            }
+            None => writeln!(f, "Facets: none"),
+        }
+    }
+}
+
+/// "1 group", "3 groups"
+fn count(n: usize, noun: &str) -> String {
+    format!("{} {}{}", n, noun, if n == 1 { "" } else { "s" })
+}
+
+/// Columns paired with the aesthetics they map
+type Mappings<'a> = Vec<(&'static str, &'a str)>;
+
+/// The layer's positional and value columns, with their aesthetics
+fn mappings(layer: &ResolvedLayer) -> Mappings<'_> {
+    let aes = &layer.aesthetics;
+    let x = Some(&aes.x_col).filter(|x| !x.is_empty());
+    [
+        ("x", x),
+        ("y", aes.y_col.as_ref()),
+        ("ymin", aes.ymin_col.as_ref()),
+        ("ymax", aes.ymax_col.as_ref()),
+        ("xend", aes.xend_col.as_ref()),
+        ("yend", aes.yend_col.as_ref()),
+        ("fill", aes.fill.as_ref()),
+        ("label", aes.label.as_ref()),
+    ]
+    .into_iter()
+    .filter_map(|(name, col)| col.map(|col| (name, col.as_str())))
+    .collect()
+}
+
+/// Each column that styles or groups the layer, with its aesthetic
+fn styles(layer: &ResolvedLayer) -> Mappings<'_> {
+    let aes = &layer.aesthetics;
+    [
+        ("color", aes.color.as_ref()),
+        ("size", aes.size.as_ref()),
+        ("shape", aes.shape.as_ref()),
+        ("alpha", aes.alpha.as_ref()),
+        ("linetype", aes.linetype.as_ref()),
+    ]
+    .into_iter()
+    .filter_map(|(name, col)| col.map(|col| (name, col.as_str())))
+    .chain(aes.group.iter().map(|col| ("group", col.as_str())))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(columns: &[(&str, &[&str])]) -> CsvData {
+        CsvData::from_columns(
+            columns
+                .iter()
+                .map(|(name, values)| {
+                    (
+                        name.to_string(),
+                        values.iter().map(|v| v.to_string()).collect(),
+                    )
+                })
+                .collect(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_column_summaries() {
+        let ids: Vec<String> = (0..25).map(|i| format!("id{}", i)).collect();
+        let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+        let cycle = |values: &[&'static str]| -> Vec<&'static str> {
+            values.iter().copied().cycle().take(25).collect()
+        };
+        let data = table(&[
+            ("id", &ids),
+            ("sales", &cycle(&["10", "2.5", "NA", "-4"])),
+            ("when", &cycle(&["2024-01-01", "2024-02-01T08:30", ""])),
+            ("region", &cycle(&["north", "south"])),
+        ]);
+        let columns = column_summaries(&data);
+        assert_eq!(
+            columns,
+            vec![
+                ColumnSummary {
+                    name: "id".into(),
+                    kind: ColumnKind::Text,
+                    distinct: None,
+                    range: None,
+                },
+                ColumnSummary {
+                    name: "sales".into(),
+                    kind: ColumnKind::Numeric,
+                    distinct: Some(3),
+                    range: Some((-4.0, 10.0)),
+                },
+                ColumnSummary {
+                    name: "when".into(),
+                    kind: ColumnKind::Date,
+                    distinct: Some(2),
+                    range: None,
+                },
+                ColumnSummary {
+                    name: "region".into(),
+                    kind: ColumnKind::Text,
+                    distinct: Some(2),
+                    range: None,
+                },
+            ]
+        );
+
+        assert_eq!(
+            format_column_table(&columns),
+            "column  type     distinct  min  max\n\
+             id      text     >20       -    -\n\
+             sales   numeric  3         -4   10\n\
+             when    date     2         -    -\n\
+             region  text     2         -    -\n"
+        );
+    }
+
+    #[test]
+    fn test_plot_plan_report() {
+        let data = table(&[
+            ("day", &["1", "2", "3", "1", "2", "3"]),
+            ("sales", &["10", "12", "9", "4", "6", "5"]),
+            ("store", &["A", "A", "A", "B", "B", "B"]),
+            ("region", &["EU", "EU", "US", "US", "EU", "US"]),
+        ]);
+        let plan = crate::runtime::plan_with_sources(
+            "filter(sales > 4) | aes(x: day, y: sales, color: store) | line() | point(color: sales) | hline(yintercept: 8) | facet_wrap(by: region)",
+            data,
+            &|name: &str| anyhow::bail!("no table {}", name),
+            crate::RenderOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(plan.group_count(0), 2);
+        assert_eq!(
+            plan.to_string(),
+            "Data: 6 rows, then filter\n\
+             Layer 1: line() x = day, y = sales; grouped by color = store (2 groups)\n\
+             Layer 2: point() x = day, y = sales, color = sales (continuous)\n\
+             Layer 3: hline()\n\
+             Facets: 2 panels by region in a 1 x 2 grid\n"
+        );
+    }
+
+    #[test]
+    fn test_plot_plan_explanation() {
+        let data = table(&[
+            ("day", &["a", "b", "c", "a", "b", "c"]),
+            ("sales", &["10", "100", "9", "4", "6", "5"]),
+            ("store", &["A", "A", "A", "B", "B", "B"]),
+            ("region", &["EU", "EU", "US", "US", "EU", "US"]),
+        ]);
+        let plan = crate::runtime::plan_with_sources(
+            "aes(x: day, y: sales, color: store) | line() | point(color: sales) | scale_y_sqrt() | facet_wrap(by: region)",
+            data,
+            &|name: &str| anyhow::bail!("no table {}", name),
+            crate::RenderOptions::default(),
+        )
+        .unwrap();
+        let explanation = plan.explain();
+        assert_eq!(explanation.x_axis, XAxisKind::Categorical);
+
+        let lines = &explanation.layers[0];
+        assert_eq!(lines.geom, "line");
+        assert_eq!(lines.columns["x"], "day");
+        let grouping = lines.grouping.as_ref().unwrap();
+        assert_eq!(grouping.by["color"], "store");
+        assert_eq!(
+            (grouping.groups, grouping.levels.clone()),
+            (2, vec!["A".into(), "B".into()])
+        );
+        let points = &explanation.layers[1];
+        assert_eq!(
+            (points.columns["color"].as_str(), points.continuous.clone()),
+            ("sales", vec!["color"])
+        );
+        assert_eq!(points.grouping, None);
+
+        assert_eq!(explanation.facet.as_ref().unwrap().by, ["region"]);
+        let panels: Vec<(Option<&str>, usize)> = explanation
+            .panels
+            .iter()
+            .map(|panel| (panel.title.as_deref(), panel.rows))
+            .collect();
+        assert_eq!(panels, [(Some("EU"), 3), (Some("US"), 3)]);
+        let (x, y) = (&explanation.panels[0].x, &explanation.panels[0].y);
+        assert_eq!(x.categories, ["a", "b", "c"]);
+        // Transformed axes report their ends in data units
+        assert_eq!(y.transform, "sqrt");
+        assert!(y.min <= 4.0 && y.max >= 100.0, "{:?}", y);
+
+        let json: serde_json::Value = serde_json::from_str(&plan.to_json().unwrap()).unwrap();
+        assert_eq!(json["x_axis"], "categorical");
+        assert_eq!(json["layers"][0]["grouping"]["by"]["color"], "store");
+        assert_eq!(json["panels"][1]["rows"], 3);
+    }
+
+    #[test]
+    fn test_all_missing_column_is_numeric_without_a_range() {
+        let columns = column_summaries(&table(&[("x", &["1", "2"]), ("empty", &["", "NA"])]));
+        assert_eq!(columns[1].kind, ColumnKind::Numeric);
+        assert_eq!((columns[1].distinct, columns[1].range), (Some(0), None));
+    }
+}
@@ -1,6 +1,6 @@
 use crate::graph::{
-    BarStyle, BoxplotStyle, DensityStyle, HeatmapStyle, LineStyle, PointStyle, RibbonStyle,
-    ViolinStyle,
+    BarStyle, BoxplotStyle, DensityStyle, HeatmapStyle, LabelStyle, LineStyle, PointStyle,
+    RibbonStyle, ViolinStyle,
 };
 use crate::parser::ast::Layer;
 
@@ -10,6 +10,7 @@ use crate::parser::ast::Layer;
 
 /// Result of resolving aesthetics against the CSV headers (but not data values yet)
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ResolvedSpec {
     pub layers: Vec<ResolvedLayer>,
     pub facet: Option<ResolvedFacet>,
@@ -18,20 +19,32 @@ pub struct ResolvedSpec {
     pub theme: crate::parser::ast::Theme,
     pub x_scale_spec: Option<crate::parser::ast::AxisScale>,
     pub y_scale_spec: Option<crate::parser::ast::AxisScale>,
+    pub color_scale: crate::parser::ast::ColorScale,
+    pub color_manual: Option<crate::parser::ast::ManualColorScale>,
+    /// Data stages, applied to the data before partitioning
+    pub stages: Vec<crate::parser::ast::DataStage>,
+    /// `annotate()` notes, placed on each panel by the transform
+    pub annotations: Vec<crate::parser::ast::Annotation>,
+    pub shades: Vec<crate::parser::ast::Shade>,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ResolvedLayer {
     pub original_layer: Layer,
     pub aesthetics: ResolvedAesthetics,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ResolvedAesthetics {
     pub x_col: String,
     pub y_col: Option<String>,
     pub ymin_col: Option<String>,
     pub ymax_col: Option<String>,
+    // Segment end columns (segments mapped to data)
+    pub xend_col: Option<String>,
+    pub yend_col: Option<String>,
     // Optional grouping columns
     pub color: Option<String>,
     pub size: Option<String>,
@@ -39,14 +52,38 @@ pub struct ResolvedAesthetics {
     pub alpha: Option<String>,
     // Fill column (for heatmap value mapping)
     pub fill: Option<String>,
+    // Linetype grouping column (line layers)
+    pub linetype: Option<String>,
+    // Grouping columns that style nothing: ggplot's `group`, then inherited mappings a
+    // fixed layer value overrides
+    pub group: Vec<String>,
+    // Label column (for text layers)
+    pub label: Option<String>,
     // Fixed values (if not mapped) can be stored here or retrieved from Layer
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ResolvedFacet {
+    /// facet_wrap column (empty for facet_grid)
     pub col: String,
     pub ncol: Option<usize>,
+    pub nrow: Option<usize>,
     pub scales: crate::parser::ast::FacetScales,
+    /// Number of intervals to cut a numeric facet_wrap column into
+    pub bins: Option<usize>,
+    pub cut: crate::parser::ast::FacetCut,
+    /// facet_grid variables; panels form a rows x cols matrix instead of wrapping
+    pub grid: Option<ResolvedFacetGrid>,
+    pub labeller: crate::parser::ast::FacetLabeller,
+    pub label_size: Option<f64>,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ResolvedFacetGrid {
+    pub rows: Option<String>,
+    pub cols: Option<String>,
 }
 
 // =============================================================================
@@ -56,36 +93,112 @@ pub struct ResolvedFacet {
 /// The normalized data ready for scaling and rendering.
 /// It is split into "Panels" (for faceting). If no faceting, there is 1 panel.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RenderData {
     pub panels: Vec<PanelData>,
     pub facet_layout: FacetLayout,
+    /// Rows `filter()` stages rejected because a numeric comparison met a non-numeric cell
+    pub filter_rejected_rows: usize,
+    /// `head()`/`sample()` stages that dropped rows, in pipeline order
+    pub row_limits: Vec<RowLimit>,
+}
+
+/// Row counts around a `head()` or `sample()` stage that shortened the data
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RowLimit {
+    pub stage: String,
+    pub before: usize,
+    pub after: usize,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FacetLayout {
     pub nrow: usize,
     pub ncol: usize,
     pub panel_titles: Vec<String>, // Index matches panels
+    /// facet_grid row values (index matches panels); empty for facet_wrap
+    pub row_titles: Vec<String>,
+    /// Rows of the staged plot data in each panel (index matches panels)
+    pub panel_rows: Vec<usize>,
 }
 
 /// Data for a single plot panel (one facet)
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PanelData {
     pub index: usize,
     pub layers: Vec<LayerData>, // Corresponds 1:1 with ResolvedSpec.layers
+    /// The `annotate()` notes this panel draws, over its layers
+    pub annotations: Vec<PanelAnnotation>,
+    /// The `shade()` bands this panel draws, under the grid
+    pub shades: Vec<PanelShade>,
+}
+
+/// An `annotate()` note placed on a panel, before its nudge
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PanelAnnotation {
+    /// Index into `ResolvedSpec.annotations`
+    pub index: usize,
+    /// Data units: a slot index on a categorical axis, seconds on a datetime one
+    pub x: f64,
+    pub y: f64,
+}
+
+/// A `shade()` band placed on a panel; y sides are the shade's own
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PanelShade {
+    /// Index into `ResolvedSpec.shades`
+    pub index: usize,
+    /// Data units like `PanelAnnotation::x`; a category's slot widens to its edges
+    /// when drawn. None runs to the axis limit.
+    pub xmin: Option<f64>,
+    pub xmax: Option<f64>,
 }
 
 /// Data for a single layer within a panel.
 /// Contains one or more "Groups" (e.g. different colored lines).
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LayerData {
     pub groups: Vec<GroupData>,
+    /// Value range of a continuous (numeric) color mapping; None for categorical color
+    pub color_domain: Option<(f64, f64)>,
+    /// Value range of a continuous (numeric) size mapping; None for categorical size
+    pub size_domain: Option<(f64, f64)>,
+    /// Rows dropped from this layer because a needed cell was missing (NA, empty, ...)
+    pub skipped_rows: usize,
+    /// Rows a layer filter rejected because a numeric comparison met a non-numeric cell
+    pub filter_rejected_rows: usize,
+    /// Rows the layer's `filter:` kept; None when it has no filter
+    pub filter_matched_rows: Option<usize>,
+    /// The aesthetics each grouping column maps, in `GroupData.key_parts` order
+    pub group_aesthetics: Vec<Vec<GroupAesthetic>>,
+    /// Each grouping column's values in palette (and legend) order
+    pub group_levels: Vec<Vec<String>>,
+}
+
+/// An aesthetic whose categorical column splits a layer into groups
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GroupAesthetic {
+    Color,
+    Size,
+    Shape,
+    Linetype,
+    Alpha,
 }
 
 /// The atomic unit of rendering: a set of points sharing the same visual style.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GroupData {
     pub key: String, // Legend key (e.g. "Region A")
+    // The group's value in each grouping column; `key` joins them with ", "
+    pub key_parts: Vec<String>,
 
     // Normalized Geometry Data
     // For Line/Point: x and y are straightforward.
@@ -99,13 +212,13 @@ pub struct GroupData {
     pub y_min: Vec<f64>,
     pub y_max: Vec<f64>,
 
-    // Boxplot statistics
+    // Boxplot statistics (empty for other layers)
     pub y_q1: Vec<f64>,
     pub y_median: Vec<f64>,
     pub y_q3: Vec<f64>,
     pub outliers: Vec<Vec<f64>>,
 
-    // Violin statistics (KDE density curves)
+    // Violin statistics (KDE density curves; empty for other layers)
     pub violin_density: Vec<Vec<f64>>, // Normalized density values (0-1) per x category
     pub violin_density_y: Vec<Vec<f64>>, // Y coordinates for density curve per x category
     pub violin_quantile_values: Vec<Vec<f64>>, // Computed Y values at requested quantiles per x category
@@ -121,11 +234,23 @@ pub struct GroupData {
     // Original category names for y-axis (if categorical, e.g. heatmap)
     pub y_categories: Option<Vec<String>>,
 
+    // Text labels (one per point, for text layers)
+    pub labels: Vec<String>,
+    // Per-point alpha (recency fade); empty when the style alpha applies to every point
+    pub point_alphas: Vec<f64>,
+    // Per-point continuous color values; empty when color is a grouping aesthetic
+    pub color_values: Vec<f64>,
+    // Per-point continuous size values; empty when size is a grouping aesthetic
+    pub size_values: Vec<f64>,
+    // Indices where a new line segment starts because rows with missing values were skipped
+    pub line_breaks: Vec<usize>,
+
     // Resolved Visual Style for this group
     pub style: RenderStyle,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum RenderStyle {
     Line(LineStyle),
     Point(PointStyle),
@@ -156,6 +281,18 @@ pub enum RenderStyle {
     Violin(ViolinStyle),
     Density(DensityStyle),
     Heatmap(HeatmapStyle),
+    Text {
+        style: LabelStyle,
+        nudge_x: f64,
+        nudge_y: f64,
+    },
+    /// One pie slice; angles are radians clockwise from 12 o'clock
+    Pie {
+        style: RibbonStyle,
+        start_angle: f64,
+        end_angle: f64,
+        inner_radius: f64,
+    },
 }
 
 // =============================================================================
@@ -164,18 +301,21 @@ pub enum RenderStyle {
 
 /// Holds the scales for the entire plot (potentially multiple panels)
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ScaleSystem {
     // One scale pair per panel
     pub panels: Vec<PanelScales>,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PanelScales {
     pub x: Scale,
     pub y: Scale,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Scale {
     pub domain: (f64, f64), // Data min/max
     pub range: (f64, f64),  // Pixel/Coordinate min/max
@@ -184,15 +324,157 @@ pub struct Scale {
     pub tick_positions: Vec<f64>, // Precomputed nice tick positions (empty for categorical)
     pub datetime: Option<DateTimeScale>,
     pub transform: AxisTransform,
+    /// Tick label formatting from `scale_x(...)` / `scale_y(...)`
+    pub labels: TickLabels,
+}
+
+/// How a scale's tick labels are drawn
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TickLabels {
+    /// Numeric tick format; None keeps the plain nice-number labels
+    pub format: Option<TickFormat>,
+    /// Tick label angle in degrees, overriding the theme's `axis_text` angle
+    pub angle: Option<f64>,
+    /// Categorical labels longer than this many characters are cut with an ellipsis
+    pub max_len: Option<usize>,
+}
+
+impl TickLabels {
+    pub fn from_spec(spec: Option<&crate::parser::ast::AxisScale>) -> Self {
+        let Some(labels) = spec.map(|s| &s.labels) else {
+            return TickLabels::default();
+        };
+        TickLabels {
+            format: labels.format.as_deref().and_then(TickFormat::parse),
+            angle: labels.rotate,
+            max_len: labels.max_label_len,
+        }
+    }
+
+    /// A categorical label, shortened to `max_len` characters (ellipsis included)
+    pub fn category<'a>(&self, label: &'a str) -> std::borrow::Cow<'a, str> {
+        match self.max_len {
+            Some(max) if label.chars().count() > max => {
+                let kept: String = label.chars().take(max.saturating_sub(1)).collect();
+                format!("{}…", kept).into()
+            }
+            _ => label.into(),
+        }
+    }
+}
+
+/// Numeric tick label formats
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TickFormat {
+    /// Thousands separators: 1,500,000
+    Comma,
+    /// Fractions as percentages: 0.25 -> 25%
+    Percent,
+    /// SI suffixes: 1500000 -> 1.5M
+    Si,
+    /// printf-style fixed decimals, e.g. "$%.2f" or "%d units"
+    Fixed {
+        prefix: String,
+        precision: usize,
+        suffix: String,
+    },
+}
+
+impl TickFormat {
+    /// Parse a format name or printf pattern; unrecognized formats yield None
+    pub fn parse(spec: &str) -> Option<Self> {
+        match spec {
+            "comma" => return Some(TickFormat::Comma),
+            "percent" => return Some(TickFormat::Percent),
+            "si" => return Some(TickFormat::Si),
+            _ => {}
+        }
+
+        let mut prefix = String::new();
+        let mut chars = spec.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                prefix.push(c);
+                continue;
+            }
+            if chars.peek() == Some(&'%') {
+                chars.next();
+                prefix.push('%');
+                continue;
+            }
+            let mut precision = None;
+            if chars.peek() == Some(&'.') {
+                chars.next();
+                let mut digits = String::new();
+                while let Some(d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                    digits.push(*d);
+                    chars.next();
+                }
+                precision = Some(digits.parse().unwrap_or(0));
+            }
+            let precision = match chars.next()? {
+                'f' => precision.unwrap_or(6),
+                'd' if precision.is_none() => 0,
+                _ => return None,
+            };
+            let suffix = chars.collect::<String>().replace("%%", "%");
+            return Some(TickFormat::Fixed {
+                prefix,
+                precision,
+                suffix,
+            });
+        }
+        None
+    }
+
+    pub fn format(&self, value: f64) -> String {
+        use crate::scale::format_nice_number;
+        match self {
+            TickFormat::Comma => {
+                let text = format_nice_number(value);
+                let (sign, rest) = match text.strip_prefix('-') {
+                    Some(rest) => ("-", rest),
+                    None => ("", text.as_str()),
+                };
+                let (int, frac) = rest.split_at(rest.find('.').unwrap_or(rest.len()));
+                let mut grouped = String::new();
+                for (i, c) in int.chars().enumerate() {
+                    if i > 0 && (int.len() - i) % 3 == 0 {
+                        grouped.push(',');
+                    }
+                    grouped.push(c);
+                }
+                format!("{}{}{}", sign, grouped, frac)
+            }
+            TickFormat::Percent => format!("{}%", format_nice_number(value * 100.0)),
+            TickFormat::Si => {
+                const UNITS: [(f64, &str); 4] = [(1e12, "T"), (1e9, "G"), (1e6, "M"), (1e3, "k")];
+                UNITS
+                    .iter()
+                    .find(|(scale, _)| value.abs() >= *scale)
+                    .map(|(scale, unit)| format!("{}{}", format_nice_number(value / scale), unit))
+                    .unwrap_or_else(|| format_nice_number(value))
+            }
+            TickFormat::Fixed {
+                prefix,
+                precision,
+                suffix,
+            } => format!("{}{:.*}{}", prefix, precision, value, suffix),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DateTimeScale {
     pub interval_seconds: Option<f64>,
     pub label_format: String,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AxisTransform {
     Linear,
     Log10,
@@ -224,27 +506,64 @@ impl AxisTransform {
 /// A list of primitive drawing commands.
 /// The Backend just executes these blindly.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SceneGraph {
     pub width: u32,
     pub height: u32,
     pub panels: Vec<PanelScene>,
     pub labels: crate::parser::ast::Labels,
     pub theme: crate::parser::ast::Theme,
+    /// Font size of panel captions and row strips in pixels (15 if None)
+    pub facet_label_size: Option<f64>,
+    /// Facet grid shape; cells past the last panel are left blank
+    pub nrow: usize,
+    pub ncol: usize,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PanelScene {
     pub row: usize,
     pub col: usize,
     pub title: Option<String>,
+    /// facet_grid row label, drawn in a strip on the panel's right edge
+    pub row_title: Option<String>,
     pub x_label: Option<String>,
     pub y_label: Option<String>,
     pub x_scale: Scale, // For drawing axes
     pub y_scale: Scale,
     pub commands: Vec<DrawCommand>,
+    /// Continuous fill guide (heatmap/tile layers); drawn beside the series legend
+    pub colorbar: Option<ColorBar>,
+    /// Tick labels on the horizontal axis; off for facet panels with a panel below
+    /// them that shares the axis
+    pub show_x_tick_labels: bool,
+    /// Tick labels on the vertical axis; off for facet panels right of the first
+    /// column when the axis is shared
+    pub show_y_tick_labels: bool,
+    /// No mesh or axes, and equal units on both axes so circles stay round (pies)
+    pub polar: bool,
+    /// `coord_fixed()` ratio of pixels per y unit to pixels per x unit; the
+    /// renderer shrinks the plotting area, centred, until the ranges fit it
+    pub aspect_ratio: Option<f64>,
+    /// How many of the leading commands are `shade()` bands, which the plotters
+    /// renderer paints before the grid; other backends draw them first like any command
+    pub underlay: usize,
 }
 
+/// A continuous color scale guide: a gradient strip labeled with its value range
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ColorBar {
+    pub title: Option<String>,
+    pub min: f64,
+    pub max: f64,
+    /// Evenly spaced gradient samples, ordered from `min` to `max`
+    pub colors: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DrawCommand {
     DrawLine {
         points: Vec<(f64, f64)>,
@@ -268,4 +587,11 @@ pub enum DrawCommand {
         style: RibbonStyle,
         legend: Option<String>,
     },
+    DrawText {
+        // Anchor point (text is centered on it)
+        position: (f64, f64),
+        text: String,
+        style: LabelStyle,
+        legend: Option<String>,
+    },
 }
@@ -0,0 +1,156 @@
+// JSON input: an array of flat objects or newline-delimited objects, read into CsvData
+
+use crate::csv_reader::CsvData;
+use anyhow::{anyhow, Context, Result};
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+use std::io::Read;
+
+/// Read JSON records: either a top-level array of objects (`[{...}, {...}]`)
+/// or newline-delimited JSON with one object per line.
+pub fn read_json(mut input: impl Read) -> Result<CsvData> {
+    let mut content = String::new();
+    input
+        .read_to_string(&mut content)
+        .context("Failed to read JSON input")?;
+
+    let records: Vec<Value> = if content.trim_start().starts_with('[') {
+        match serde_json::from_str(&content).context("Failed to parse JSON input")? {
+            Value::Array(records) => records,
+            _ => return Err(anyhow!("Input data must be a JSON array of objects")),
+        }
+    } else {
+        content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                serde_json::from_str(line)
+                    .with_context(|| format!("Failed to parse JSON on line {}", i + 1))
+            })
+            .collect::<Result<_>>()?
+    };
+
+    records_to_csv(&records)
+}
+
+/// Convert JSON objects into rows. Headers are the union of keys in order of first
+/// appearance; keys absent from a record become empty (missing) cells.
+pub fn records_to_csv(records: &[Value]) -> Result<CsvData> {
+    if records.is_empty() {
+        return Err(anyhow!("JSON input must contain at least one record"));
+    }
+
+    let objects = records
+        .iter()
+        .enumerate()
+        .map(|(i, record)| {
+            record
+                .as_object()
+                .ok_or_else(|| anyhow!("JSON record {} is not an object", i + 1))
+        })
+        .collect::<Result<Vec<&Map<String, Value>>>>()?;
+
+    let mut seen = HashSet::new();
+    let headers: Vec<String> = objects
+        .iter()
+        .flat_map(|obj| obj.keys())
+        .filter(|key| seen.insert(key.as_str()))
+        .cloned()
+        .collect();
+
+    let rows = objects
+        .iter()
+        .enumerate()
+        .map(|(i, obj)| {
+            headers
+                .iter()
+                .map(|key| match obj.get(key) {
+                    None => Ok(String::new()),
+                    Some(value) => stringify_value(value).ok_or_else(|| {
+                        anyhow!(
+                            "Nested value for key '{}' in JSON record {} is not supported; flatten it first",
+                            key,
+                            i + 1
+                        )
+                    }),
+                })
+                .collect::<Result<Vec<String>>>()
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(CsvData { headers, rows })
+}
+
+/// Scalar JSON values as cell strings; None for arrays and objects.
+/// Integers print without a decimal point and whole floats drop the trailing `.0`,
+/// so `1` and `1.0` become the same category.
+fn stringify_value(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => Some(String::new()),
+        Value::Bool(b) => Some(b.to_string()),
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(match (n.as_i64(), n.as_u64(), n.as_f64()) {
+            (Some(i), _, _) => i.to_string(),
+            (_, Some(u), _) => u.to_string(),
+            (_, _, Some(f)) => f.to_string(),
+            _ => n.to_string(),
+        }),
+        Value::Array(_) | Value::Object(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn reads_array_of_objects() {
+        let json =
+            r#"[{"time": 1, "temp": 20.5, "ok": true}, {"time": 2.0, "temp": 22, "ok": false}]"#;
+        let data = read_json(Cursor::new(json)).unwrap();
+        assert_eq!(data.headers.len(), 3);
+        let col = |name: &str| data.headers.iter().position(|h| h == name).unwrap();
+        assert_eq!(data.rows[0][col("time")], "1");
+        assert_eq!(data.rows[0][col("temp")], "20.5");
+        assert_eq!(data.rows[0][col("ok")], "true");
+        assert_eq!(data.rows[1][col("time")], "2");
+        assert_eq!(data.rows[1][col("ok")], "false");
+    }
+
+    #[test]
+    fn reads_newline_delimited_json() {
+        let json = "{\"x\": 1, \"y\": 10}\n\n{\"x\": 2, \"y\": null, \"g\": \"a\"}\n";
+        let data = read_json(Cursor::new(json)).unwrap();
+        assert_eq!(data.rows.len(), 2);
+        let col = |name: &str| data.headers.iter().position(|h| h == name).unwrap();
+        // Union of keys; absent and null values are empty cells
+        assert_eq!(data.rows[0][col("g")], "");
+        assert_eq!(data.rows[1][col("y")], "");
+        assert_eq!(data.rows[1][col("g")], "a");
+
+        let err = read_json(Cursor::new("{\"x\": 1}\n{oops}\n")).unwrap_err();
+        assert!(err.to_string().contains("line 2"));
+    }
+
+    #[test]
+    fn nested_values_name_the_key() {
+        let json = r#"[{"x": 1, "meta": {"a": 1}}]"#;
+        let err = read_json(Cursor::new(json)).unwrap_err();
+        assert!(err.to_string().contains("key 'meta'"));
+
+        let json = r#"[{"x": [1, 2]}]"#;
+        let err = read_json(Cursor::new(json)).unwrap_err();
+        assert!(err.to_string().contains("key 'x'"));
+    }
+
+    #[test]
+    fn rejects_empty_and_non_object_input() {
+        assert!(read_json(Cursor::new("[]")).is_err());
+        assert!(read_json(Cursor::new("[1, 2]"))
+            .unwrap_err()
+            .to_string()
+            .contains("not an object"));
+    }
+}
@@ -3,19 +3,44 @@
 pub mod csv_reader;
 pub mod data;
 pub mod datetime;
+pub mod error;
 pub mod graph;
+pub mod image_diff;
+pub mod inspect;
+pub mod json_reader;
 pub mod palette;
 pub mod parser;
 pub mod runtime;
+pub mod suggest;
 
 // New Architecture Modules
+pub mod analysis;
+pub mod ascii;
+pub mod backend;
+pub mod builder;
+pub mod collate;
 pub mod compiler;
 pub mod ir;
 pub mod preprocessor;
+pub mod range;
 pub mod resolve;
 pub mod scale;
 pub mod theme_resolve;
 pub mod transform;
+pub mod warning;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use builder::{BarOptions, LineOptions, PlotSpecBuilder, PointOptions};
+pub use error::GramGraphError;
+pub use parser::ast::{
+    BarPosition, FacetLabeller, FacetScales, LinePosition, PlotSpec, PointPosition,
+};
+pub use runtime::{
+    render, render_plot_rgba, render_spec, render_spec_with_report, render_spec_with_sources,
+    render_with_report, render_with_sources, RenderReport, RenderResult,
+};
+pub use warning::{SkipReason, Warning};
 
 use serde::Deserialize;
 
@@ -26,6 +51,9 @@ pub enum OutputFormat {
     Png,
     #[serde(rename = "svg")]
     Svg,
+    /// Text on a character grid for terminals (see `ascii::render_text`)
+    #[serde(rename = "ascii")]
+    Ascii,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -36,6 +64,36 @@ pub struct RenderOptions {
     pub height: u32,
     #[serde(default, rename = "type")]
     pub format: OutputFormat,
+    /// Fail on missing values (empty, NA, NaN, null) instead of skipping those rows
+    #[serde(default)]
+    pub strict: bool,
+    /// Render the components that parse and warn about the rest, instead of failing
+    /// on the first one that doesn't
+    #[serde(default)]
+    pub lenient: bool,
+    /// Most values a styled grouping column (color, shape, ...) may take before rendering
+    /// fails; 0 disables the check
+    #[serde(default = "default_max_groups")]
+    pub max_groups: usize,
+    /// Most points a `point()` layer draws one by one; a bigger layer is binned onto
+    /// the pixel grid unless it sets `fast: false`. 0 never bins
+    #[serde(default = "default_max_exact_points")]
+    pub max_exact_points: usize,
+    /// How facet panels, group keys, and alphabetical categories are sorted
+    #[serde(default)]
+    pub label_order: collate::LabelOrder,
+    /// Character grid size for `OutputFormat::Ascii`
+    #[serde(default = "default_ascii_width")]
+    pub ascii_width: usize,
+    #[serde(default = "default_ascii_height")]
+    pub ascii_height: usize,
+    /// Color `OutputFormat::Ascii` cells with ANSI escapes
+    #[serde(default)]
+    pub ascii_color: bool,
+    /// Draw PNG text as solid boxes sized from fixed character widths instead of
+    /// glyphs, so the image doesn't depend on the installed fonts (golden-image tests)
+    #[serde(default)]
+    pub placeholder_text: bool,
 }
 
 fn default_width() -> u32 {
@@ -44,6 +102,18 @@ fn default_width() -> u32 {
 fn default_height() -> u32 {
     600
 }
+fn default_max_groups() -> usize {
+    50
+}
+fn default_max_exact_points() -> usize {
+    100_000
+}
+fn default_ascii_width() -> usize {
+    ascii::DEFAULT_WIDTH
+}
+fn default_ascii_height() -> usize {
+    ascii::DEFAULT_HEIGHT
+}
 
 impl Default for RenderOptions {
     fn default() -> Self {
@@ -51,6 +121,15 @@ impl Default for RenderOptions {
             width: 800,
             height: 600,
             format: OutputFormat::Png,
+            strict: false,
+            lenient: false,
+            max_groups: default_max_groups(),
+            max_exact_points: default_max_exact_points(),
+            label_order: collate::LabelOrder::Natural,
+            ascii_width: default_ascii_width(),
+            ascii_height: default_ascii_height(),
+            ascii_color: false,
+            placeholder_text: false,
         }
     }
 }
@@ -1,17 +1,53 @@
-use gramgraph::{csv_reader, data::PlotData, parser, runtime, OutputFormat, RenderOptions};
+use gramgraph::{
+    collate::LabelOrder,
+    csv_reader::{self, CsvData, CsvOptions, InputFormat, NumberStyle},
+    image_diff,
+    inspect::{self, PlotPlan},
+    runtime, GramGraphError, OutputFormat, PlotSpec, RenderOptions, RenderReport,
+};
 
-use anyhow::{anyhow, Context, Result};
-use clap::{Parser, ValueEnum};
-use csv::ReaderBuilder;
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand, ValueEnum};
 use std::collections::HashMap;
-use std::io::{self, Read, Write};
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
 
 #[derive(Parser, Debug)]
 #[command(name = "gramgraph")]
 #[command(about = "Generate graphs from CSV data using GramGraph DSL", long_about = None)]
+#[command(
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true,
+    disable_help_subcommand = true
+)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// GramGraph DSL string (e.g., 'chart(x: time, y: temp) | layer_line(color: "red")')
-    dsl: String,
+    #[arg(required_unless_present_any = ["spec_json", "list_columns"])]
+    dsl: Option<String>,
+
+    /// Read the plot spec from a JSON file (see PlotSpec::to_json) instead of the DSL
+    #[arg(long, conflicts_with_all = ["dsl", "defines"])]
+    spec_json: Option<PathBuf>,
+
+    /// Print each column's name, type, distinct count, and range, then exit without plotting
+    #[arg(long, conflicts_with_all = ["dsl", "spec_json", "dry_run"])]
+    list_columns: bool,
+
+    /// Check the plot against the data and print its layers, groups, and facets instead of rendering
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Check the plot like --dry-run and print it in this format; `json` adds each panel's
+    /// rows and axes, and reports errors as JSON too
+    #[arg(long, value_enum, conflicts_with_all = ["dry_run", "list_columns"])]
+    explain: Option<ExplainArg>,
+
+    /// Read data from this file instead of stdin ("-" means stdin)
+    #[arg(short, long)]
+    input: Option<PathBuf>,
 
     /// Output width in pixels
     #[arg(long, default_value_t = 800)]
@@ -21,13 +57,99 @@ struct Args {
     #[arg(long, default_value_t = 600)]
     height: u32,
 
-    /// Output format (png, svg)
+    /// Output format (png, svg, ascii); ascii draws on a character grid for terminals
     #[arg(long, value_enum, default_value_t = FormatArg::Png)]
     format: FormatArg,
 
+    /// Character columns for --format ascii
+    #[arg(long, default_value_t = gramgraph::ascii::DEFAULT_WIDTH)]
+    ascii_width: usize,
+
+    /// Character rows for --format ascii
+    #[arg(long, default_value_t = gramgraph::ascii::DEFAULT_HEIGHT)]
+    ascii_height: usize,
+
+    /// ANSI colors for --format ascii (auto: when stdout is a terminal and NO_COLOR is unset)
+    #[arg(long, value_enum, default_value_t = ColorArg::Auto)]
+    color: ColorArg,
+
     /// Define variables for DSL substitution (e.g., -D x=time -D color=red)
     #[arg(short = 'D', long = "define", value_parser = parse_key_val)]
     defines: Vec<(String, String)>,
+
+    /// Read a layer's data: "name" from this file instead of the path "name" (e.g. --data last=2023.csv)
+    #[arg(long = "data", value_parser = parse_key_val)]
+    data_files: Vec<(String, String)>,
+
+    /// Input data format (csv, json); json accepts an array of objects or one object per line
+    #[arg(long, value_enum, default_value_t = InputFormatArg::Csv)]
+    input_format: InputFormatArg,
+
+    /// Field delimiter, a single character or \t (default: tab if the header row has tabs but no commas, else comma)
+    #[arg(long, value_parser = csv_reader::parse_delimiter)]
+    delimiter: Option<u8>,
+
+    /// Treat the first line as data; columns are named c0, c1, c2, ...
+    #[arg(long)]
+    no_header: bool,
+
+    /// Read numbers with a decimal comma and point or space thousands ("3,14", "1.234,56")
+    #[arg(long, conflicts_with = "thousands_separator")]
+    decimal_comma: bool,
+
+    /// Strip comma or space thousands separators from numbers ("1,234.5", "1 234")
+    #[arg(long)]
+    thousands_separator: bool,
+
+    /// Fail on missing values (empty, NA, NaN, null) instead of skipping those rows
+    #[arg(long)]
+    strict: bool,
+
+    /// Render the pipeline components that parse, warning about the rest, instead of failing
+    #[arg(long)]
+    lenient: bool,
+
+    /// Most distinct values a color, size, shape, linetype, or alpha column may group by (0: no limit)
+    #[arg(long, default_value_t = 50)]
+    max_groups: usize,
+
+    /// Most points a point() layer draws one by one before it is binned onto the pixel
+    /// grid (0: never bin)
+    #[arg(long, default_value_t = 100_000)]
+    max_exact_points: usize,
+
+    /// Sort facet panels, groups, and legends as plain strings ("10" before "9")
+    /// instead of by number, month, and embedded number
+    #[arg(long)]
+    lexical_order: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Compare two rendered images pixel by pixel
+    Diff(DiffArgs),
+}
+
+/// Arguments for `gramgraph diff old.png new.png`
+#[derive(clap::Args, Debug)]
+struct DiffArgs {
+    /// Reference image
+    old: PathBuf,
+
+    /// Image to compare against the reference
+    new: PathBuf,
+
+    /// Write a difference heat image (PNG) to this path
+    #[arg(long)]
+    out: Option<PathBuf>,
+
+    /// Per-channel difference (0-255) still treated as a match
+    #[arg(long, default_value_t = 0)]
+    tolerance: u8,
+
+    /// Maximum mismatch percentage before exiting with a nonzero status
+    #[arg(long, default_value_t = 0.0)]
+    threshold: f64,
 }
 
 /// Helper parser for key=value pairs
@@ -42,6 +164,51 @@ fn parse_key_val(s: &str) -> Result<(String, String), String> {
 enum FormatArg {
     Png,
     Svg,
+    Ascii,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ExplainArg {
+    /// The --dry-run report
+    Text,
+    /// `PlotPlan::to_json`
+    Json,
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+enum ColorArg {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorArg {
+    fn enabled(&self) -> bool {
+        match self {
+            ColorArg::Always => true,
+            ColorArg::Never => false,
+            ColorArg::Auto => {
+                io::stdout().is_terminal()
+                    && std::env::var_os("NO_COLOR").is_none()
+                    && std::env::var("TERM").map_or(true, |term| term != "dumb")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+enum InputFormatArg {
+    Csv,
+    Json,
+}
+
+impl From<InputFormatArg> for InputFormat {
+    fn from(arg: InputFormatArg) -> Self {
+        match arg {
+            InputFormatArg::Csv => InputFormat::Csv,
+            InputFormatArg::Json => InputFormat::Json,
+        }
+    }
 }
 
 impl From<FormatArg> for OutputFormat {
@@ -49,6 +216,7 @@ impl From<FormatArg> for OutputFormat {
         match arg {
             FormatArg::Png => OutputFormat::Png,
             FormatArg::Svg => OutputFormat::Svg,
+            FormatArg::Ascii => OutputFormat::Ascii,
         }
     }
 }
@@ -60,67 +228,272 @@ pub fn process_dsl(
     csv_content: impl Read,
     options: RenderOptions,
     variables: HashMap<String, String>,
+    csv_options: CsvOptions,
+    data_files: &HashMap<String, PathBuf>,
 ) -> Result<Vec<u8>> {
     // 1. Preprocess: Expand variables immediately
     let expanded_dsl = gramgraph::preprocessor::expand_variables(dsl, &variables)
         .context("Failed to expand variables")?;
 
-    // Read CSV
-    let mut reader = ReaderBuilder::new()
-        .has_headers(true)
-        .from_reader(csv_content);
+    // Read the data, then parse and render through the library entry point
+    let csv_data = csv_reader::read_input(csv_content, csv_options)?;
+    let load = |name: &str| load_table(name, data_files, csv_options);
+    runtime::render_with_sources(&expanded_dsl, csv_data, &load, options)
+        .map(print_warnings)
+        .map_err(cli_error("Failed to render plot"))
+}
 
-    let headers = reader
-        .headers()
-        .context("Failed to read CSV headers")?
-        .iter()
-        .map(|s| s.to_string())
-        .collect();
+/// Render a JSON plot spec (as written by `PlotSpec::to_json`) against the data
+pub fn process_spec_json(
+    json: &str,
+    csv_content: impl Read,
+    options: RenderOptions,
+    csv_options: CsvOptions,
+    data_files: &HashMap<String, PathBuf>,
+) -> Result<Vec<u8>> {
+    let plot_spec = PlotSpec::from_json(json)?;
+    let csv_data = csv_reader::read_input(csv_content, csv_options)?;
+    let load = |name: &str| load_table(name, data_files, csv_options);
+    runtime::render_spec_with_sources(plot_spec, csv_data, &load, options)
+        .map(print_warnings)
+        .map_err(cli_error("Failed to render plot"))
+}
+
+/// `process_dsl` without rendering: the plan `--dry-run` and `--explain` print
+pub fn plan_dsl(
+    dsl: &str,
+    csv_content: impl Read,
+    options: RenderOptions,
+    variables: HashMap<String, String>,
+    csv_options: CsvOptions,
+    data_files: &HashMap<String, PathBuf>,
+) -> Result<PlotPlan> {
+    let expanded_dsl = gramgraph::preprocessor::expand_variables(dsl, &variables)
+        .context("Failed to expand variables")?;
+    let csv_data = csv_reader::read_input(csv_content, csv_options)?;
+    let load = |name: &str| load_table(name, data_files, csv_options);
+    runtime::plan_with_sources(&expanded_dsl, csv_data, &load, options)
+        .map_err(cli_error("Failed to plan plot"))
+}
+
+/// `process_spec_json` without rendering (see `plan_dsl`)
+pub fn plan_spec_json(
+    json: &str,
+    csv_content: impl Read,
+    options: RenderOptions,
+    csv_options: CsvOptions,
+    data_files: &HashMap<String, PathBuf>,
+) -> Result<PlotPlan> {
+    let plot_spec = PlotSpec::from_json(json)?;
+    let csv_data = csv_reader::read_input(csv_content, csv_options)?;
+    let load = |name: &str| load_table(name, data_files, csv_options);
+    runtime::plan_spec_with_sources(plot_spec, csv_data, &load, options)
+        .map_err(cli_error("Failed to plan plot"))
+}
+
+/// The `--list-columns` table for the input, with headers renamed as a render would
+pub fn list_columns(csv_content: impl Read, csv_options: CsvOptions) -> Result<String> {
+    let mut csv_data = csv_reader::read_input(csv_content, csv_options)?;
+    let renamed = csv_reader::disambiguate_headers(&mut csv_data.headers);
+    if !renamed.is_empty() {
+        let warning = gramgraph::Warning::DuplicateColumns {
+            table: None,
+            renamed,
+        };
+        eprintln!("Warning: {}", warning);
+    }
+    Ok(inspect::format_column_table(&inspect::column_summaries(
+        &csv_data,
+    )))
+}
 
-    let mut rows = Vec::new();
-    for result in reader.records() {
-        let record = result.context("Failed to read CSV record")?;
-        let row: Vec<String> = record.iter().map(|s| s.to_string()).collect();
-        rows.push(row);
+/// Read the table a layer's `data: "name"` names: the `--data name=path` file, else
+/// `name` itself as a path from the working directory, in the same format as the input
+fn load_table(
+    name: &str,
+    data_files: &HashMap<String, PathBuf>,
+    csv_options: CsvOptions,
+) -> Result<CsvData> {
+    let path = data_files
+        .get(name)
+        .cloned()
+        .unwrap_or_else(|| PathBuf::from(name));
+    let bytes = std::fs::read(&path)
+        .with_context(|| format!("Failed to read data file '{}'", path.display()))?;
+    csv_reader::read_input(bytes.as_slice(), csv_options)
+        .with_context(|| format!("Failed to read data file '{}'", path.display()))
+}
+
+/// A library error as the CLI reports it: the failures the library names (a missing
+/// column, a non-numeric cell, ...) under `phase`, like its `RenderError` messages
+fn cli_error(phase: &'static str) -> impl Fn(GramGraphError) -> anyhow::Error {
+    move |err| match err {
+        GramGraphError::ParseError { .. } | GramGraphError::RenderError(_) => err.into(),
+        _ => anyhow::Error::new(err).context(phase),
     }
+}
 
-    if rows.is_empty() {
-        return Err(anyhow!("CSV must contain at least one data row"));
+/// Print the report's warnings to stderr and hand back the rendered bytes
+fn print_warnings(report: RenderReport) -> Vec<u8> {
+    for warning in &report.warnings {
+        eprintln!("Warning: {}", warning);
     }
+    report.output
+}
 
-    let csv_data = csv_reader::CsvData { headers, rows };
-    let plot_data = PlotData::from_csv(csv_data);
+/// Print the plan's warnings to stderr and hand back its report
+fn print_plan(plan: PlotPlan) -> String {
+    for warning in &plan.warnings {
+        eprintln!("Warning: {}", warning);
+    }
+    plan.to_string()
+}
 
-    // Parse the DSL string
-    let plot_spec = match parser::parse_plot_spec(&expanded_dsl) {
-        Ok((remaining, plot_spec)) => {
-            if !remaining.trim().is_empty() {
-                eprintln!("Warning: unparsed input: '{}'", remaining);
-            }
-            plot_spec
-        }
-        Err(e) => {
-            return Err(anyhow!("Parse error: {:?}", e));
+/// An error as the `--explain json` document: the full message and each cause in turn
+fn error_json(error: &anyhow::Error) -> String {
+    let causes: Vec<String> = error.chain().map(|cause| cause.to_string()).collect();
+    let document = serde_json::json!({
+        "error": {
+            "message": format!("{:#}", error),
+            "causes": causes,
         }
+    });
+    serde_json::to_string_pretty(&document).expect("a JSON value always serializes")
+}
+
+/// Open the data source: stdin when no path (or "-") is given, otherwise the file
+fn open_input(path: Option<&Path>) -> Result<Box<dyn Read>> {
+    let Some(path) = path.filter(|p| *p != Path::new("-")) else {
+        return Ok(Box::new(io::stdin()));
     };
+    // Read up front so missing, unreadable, and directory paths all fail with the path attached
+    let bytes = std::fs::read(path)
+        .with_context(|| format!("Failed to read input file '{}'", path.display()))?;
+    Ok(Box::new(io::Cursor::new(bytes)))
+}
 
-    // Render the plot
-    runtime::render_plot(plot_spec, plot_data, options).context("Failed to render plot")
+/// Compare two images, optionally write the heat image, and report the mismatch.
+/// Returns true when the mismatch is within the threshold.
+fn run_diff(args: DiffArgs) -> Result<bool> {
+    let result = image_diff::diff_files(&args.old, &args.new, args.tolerance)?;
+
+    if let Some(out) = &args.out {
+        let png_bytes = image_diff::encode_png(&result.diff_image)?;
+        std::fs::write(out, png_bytes)
+            .with_context(|| format!("Failed to write diff image '{}'", out.display()))?;
+    }
+
+    if result.dimensions_differ() {
+        let (ow, oh) = result.old_dimensions;
+        let (nw, nh) = result.new_dimensions;
+        println!("Dimensions differ: {}x{} vs {}x{}", ow, oh, nw, nh);
+    }
+    println!(
+        "Mismatch: {:.2}% ({} of {} pixels)",
+        result.mismatch_percent(),
+        result.mismatched,
+        result.total
+    );
+
+    Ok(result.mismatch_percent() <= args.threshold)
 }
 
 fn main() -> Result<()> {
     let args = Args::parse();
 
+    if let Some(Command::Diff(diff_args)) = args.command {
+        if !run_diff(diff_args)? {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     let options = RenderOptions {
         width: args.width,
         height: args.height,
         format: args.format.into(),
+        strict: args.strict,
+        lenient: args.lenient,
+        max_groups: args.max_groups,
+        max_exact_points: args.max_exact_points,
+        label_order: if args.lexical_order {
+            LabelOrder::Lexical
+        } else {
+            LabelOrder::Natural
+        },
+        ascii_width: args.ascii_width,
+        ascii_height: args.ascii_height,
+        ascii_color: args.color.enabled(),
+        placeholder_text: false,
     };
 
     // Convert defines Vec to HashMap
     let variables: HashMap<String, String> = args.defines.into_iter().collect();
+    let data_files: HashMap<String, PathBuf> = args
+        .data_files
+        .into_iter()
+        .map(|(name, path)| (name, PathBuf::from(path)))
+        .collect();
+
+    let csv_options = CsvOptions {
+        format: args.input_format.into(),
+        delimiter: args.delimiter,
+        has_header: !args.no_header,
+        numbers: if args.decimal_comma {
+            NumberStyle::DecimalComma
+        } else if args.thousands_separator {
+            NumberStyle::Grouped
+        } else {
+            NumberStyle::Plain
+        },
+    };
 
-    let bytes = process_dsl(&args.dsl, io::stdin(), options, variables)?;
+    if args.list_columns {
+        let input = open_input(args.input.as_deref())?;
+        print!("{}", list_columns(input, csv_options)?);
+        return Ok(());
+    }
+    let explain = args.explain.or(args.dry_run.then_some(ExplainArg::Text));
+    if let Some(format) = explain {
+        // Everything from opening the input on fails inside the plan, so JSON mode
+        // reports every error the same way
+        let plan = (|| {
+            let input = open_input(args.input.as_deref())?;
+            match (&args.spec_json, &args.dsl) {
+                (Some(path), _) => {
+                    let json = std::fs::read_to_string(path).with_context(|| {
+                        format!("Failed to read spec file '{}'", path.display())
+                    })?;
+                    plan_spec_json(&json, input, options, csv_options, &data_files)
+                }
+                (None, Some(dsl)) => {
+                    plan_dsl(dsl, input, options, variables, csv_options, &data_files)
+                }
+                (None, None) => unreachable!("clap requires a DSL string or --spec-json"),
+            }
+        })();
+        match format {
+            ExplainArg::Text => print!("{}", print_plan(plan?)),
+            ExplainArg::Json => match plan.and_then(|plan| plan.to_json()) {
+                Ok(json) => println!("{}", json),
+                Err(e) => {
+                    println!("{}", error_json(&e));
+                    std::process::exit(1);
+                }
+            },
+        }
+        return Ok(());
+    }
+    let input = open_input(args.input.as_deref())?;
+    let bytes = match (&args.spec_json, &args.dsl) {
+        (Some(path), _) => {
+            let json = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read spec file '{}'", path.display()))?;
+            process_spec_json(&json, input, options, csv_options, &data_files)?
+        }
+        (None, Some(dsl)) => process_dsl(dsl, input, options, variables, csv_options, &data_files)?,
+        (None, None) => unreachable!("clap requires a DSL string, --spec-json, or --list-columns"),
+    };
 
     // Write output to stdout
     let stdout = io::stdout();
@@ -148,6 +521,8 @@ mod tests {
             cursor,
             RenderOptions::default(),
             HashMap::new(),
+            CsvOptions::default(),
+            &HashMap::new(),
         );
         assert!(result.is_ok());
         let png_bytes = result.unwrap();
@@ -164,6 +539,8 @@ mod tests {
             cursor,
             RenderOptions::default(),
             HashMap::new(),
+            CsvOptions::default(),
+            &HashMap::new(),
         );
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("Parse error"));
@@ -178,6 +555,8 @@ mod tests {
             cursor,
             RenderOptions::default(),
             HashMap::new(),
+            CsvOptions::default(),
+            &HashMap::new(),
         );
         assert!(result.is_err());
         assert!(result
@@ -195,6 +574,8 @@ mod tests {
             cursor,
             RenderOptions::default(),
             HashMap::new(),
+            CsvOptions::default(),
+            &HashMap::new(),
         );
         assert!(result.is_err());
         // Error is wrapped with context, so check for the context message
@@ -213,6 +594,8 @@ mod tests {
             cursor,
             RenderOptions::default(),
             HashMap::new(),
+            CsvOptions::default(),
+            &HashMap::new(),
         );
         assert!(result.is_ok());
     }
@@ -226,6 +609,25 @@ mod tests {
             cursor,
             RenderOptions::default(),
             HashMap::new(),
+            CsvOptions::default(),
+            &HashMap::new(),
+        );
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_process_dsl_lenient_renders_the_valid_prefix() {
+        let lenient = RenderOptions {
+            lenient: true,
+            ..RenderOptions::default()
+        };
+        let result = process_dsl(
+            "aes(x: x, y: y) | line() | pont()",
+            Cursor::new("x,y\n1,10\n2,20\n"),
+            lenient,
+            HashMap::new(),
+            CsvOptions::default(),
+            &HashMap::new(),
         );
         assert!(result.is_ok());
     }
@@ -240,6 +642,8 @@ mod tests {
             cursor,
             RenderOptions::default(),
             HashMap::new(),
+            CsvOptions::default(),
+            &HashMap::new(),
         );
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("Parse error"));
@@ -249,7 +653,14 @@ mod tests {
     fn test_process_dsl_empty_input() {
         let csv = "x,y\n1,10\n";
         let cursor = Cursor::new(csv);
-        let result = process_dsl("", cursor, RenderOptions::default(), HashMap::new());
+        let result = process_dsl(
+            "",
+            cursor,
+            RenderOptions::default(),
+            HashMap::new(),
+            CsvOptions::default(),
+            &HashMap::new(),
+        );
         assert!(result.is_err());
     }
 
@@ -262,6 +673,8 @@ mod tests {
             cursor,
             RenderOptions::default(),
             HashMap::new(),
+            CsvOptions::default(),
+            &HashMap::new(),
         );
         assert!(result.is_ok());
     }
@@ -275,6 +688,8 @@ mod tests {
             cursor,
             RenderOptions::default(),
             HashMap::new(),
+            CsvOptions::default(),
+            &HashMap::new(),
         );
         assert!(result.is_ok());
     }
@@ -292,6 +707,8 @@ mod tests {
             cursor,
             RenderOptions::default(),
             vars,
+            CsvOptions::default(),
+            &HashMap::new(),
         );
         assert!(result.is_ok());
     }
@@ -309,6 +726,8 @@ mod tests {
             cursor,
             RenderOptions::default(),
             vars,
+            CsvOptions::default(),
+            &HashMap::new(),
         );
         assert!(result.is_ok());
     }
@@ -323,10 +742,291 @@ mod tests {
             cursor,
             RenderOptions::default(),
             HashMap::new(),
+            CsvOptions::default(),
+            &HashMap::new(),
         );
         assert!(result.is_err());
         // Check the full error chain
         let err_str = format!("{:?}", result.unwrap_err());
         assert!(err_str.contains("Variable '$undefined' not defined"));
     }
+
+    #[test]
+    fn test_process_dsl_delimiters() {
+        let dsl = "aes(x: time, y: temp) | line() | point()";
+        let cases = [
+            ("time\ttemp\n1\t20\n2\t25\n", None),
+            ("time\ttemp\n1\t20\n2\t25\n", Some(b'\t')),
+            ("time;temp\n1;20\n2;25\n", Some(b';')),
+            ("time|temp\n1|20\n2|25\n", Some(b'|')),
+        ];
+        for (csv, delimiter) in cases {
+            let result = process_dsl(
+                dsl,
+                Cursor::new(csv),
+                RenderOptions::default(),
+                HashMap::new(),
+                CsvOptions {
+                    delimiter,
+                    ..CsvOptions::default()
+                },
+                &HashMap::new(),
+            );
+            assert!(result.is_ok(), "{:?}: {:?}", delimiter, result.err());
+        }
+
+        // Without the flag, semicolon input is one column and the lookup fails
+        let result = process_dsl(
+            dsl,
+            Cursor::new("time;temp\n1;20\n"),
+            RenderOptions::default(),
+            HashMap::new(),
+            CsvOptions::default(),
+            &HashMap::new(),
+        );
+        assert!(format!("{:?}", result.unwrap_err()).contains("Unknown columns 'time', 'temp'"));
+    }
+
+    #[test]
+    fn test_process_dsl_no_header() {
+        let csv = "1,10\n2,20\n3,15\n";
+        let no_header = CsvOptions {
+            has_header: false,
+            ..CsvOptions::default()
+        };
+        let result = process_dsl(
+            "aes(x: c0, y: c1) | line()",
+            Cursor::new(csv),
+            RenderOptions::default(),
+            HashMap::new(),
+            no_header,
+            &HashMap::new(),
+        );
+        assert!(result.is_ok(), "{:?}", result.err());
+
+        // Without the flag the first row becomes the header
+        let result = process_dsl(
+            "aes(x: c0, y: c1) | line()",
+            Cursor::new(csv),
+            RenderOptions::default(),
+            HashMap::new(),
+            CsvOptions::default(),
+            &HashMap::new(),
+        );
+        assert!(format!("{:?}", result.unwrap_err()).contains("Unknown columns 'c0'"));
+    }
+
+    #[test]
+    fn test_process_dsl_missing_values() {
+        let csv = "x,y,g\n1,NA,a\n2,20,a\n3,,a\n4,40,a\n5,null,a\n1,5,\n";
+        let result = process_dsl(
+            "aes(x: x, y: y, color: g) | line() | point()",
+            Cursor::new(csv),
+            RenderOptions::default(),
+            HashMap::new(),
+            CsvOptions::default(),
+            &HashMap::new(),
+        );
+        assert!(result.is_ok(), "{:?}", result.err());
+
+        let strict = RenderOptions {
+            strict: true,
+            ..RenderOptions::default()
+        };
+        let result = process_dsl(
+            "aes(x: x, y: y) | line()",
+            Cursor::new(csv),
+            strict,
+            HashMap::new(),
+            CsvOptions::default(),
+            &HashMap::new(),
+        );
+        assert!(format!("{:?}", result.unwrap_err()).contains("Failed to parse y value 'NA'"));
+    }
+
+    #[test]
+    fn test_process_dsl_max_groups() {
+        let csv: String = std::iter::once("x,y,id\n".to_string())
+            .chain((0..60).map(|i| format!("{},{},id{}\n", i, i % 5, i)))
+            .collect();
+        let render = |max_groups: usize| {
+            process_dsl(
+                "aes(x: x, y: y, color: id) | point()",
+                Cursor::new(csv.clone()),
+                RenderOptions {
+                    max_groups,
+                    ..RenderOptions::default()
+                },
+                HashMap::new(),
+                CsvOptions::default(),
+                &HashMap::new(),
+            )
+        };
+        let err = render(50).unwrap_err();
+        assert!(format!("{:#}", err).contains("Column 'id' has 60 distinct values"));
+        assert!(render(60).is_ok());
+        assert!(render(0).is_ok());
+    }
+
+    #[test]
+    fn test_open_input_errors_include_path() {
+        let err = open_input(Some(Path::new("fixtures/does_not_exist.csv")))
+            .err()
+            .unwrap();
+        assert!(format!("{:?}", err).contains("fixtures/does_not_exist.csv"));
+
+        let err = open_input(Some(Path::new("fixtures"))).err().unwrap();
+        assert!(format!("{:?}", err).contains("Failed to read input file 'fixtures'"));
+
+        let mut content = String::new();
+        open_input(Some(Path::new("fixtures/basic.csv")))
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert!(!content.is_empty());
+    }
+
+    #[test]
+    fn test_process_dsl_json_input() {
+        let json =
+            r#"[{"time": 1, "temp": 20.5}, {"time": 2, "temp": 22.0}, {"time": 3, "temp": 21}]"#;
+        let json_options = CsvOptions {
+            format: InputFormat::Json,
+            ..CsvOptions::default()
+        };
+        let result = process_dsl(
+            "aes(x: time, y: temp) | line()",
+            Cursor::new(json),
+            RenderOptions::default(),
+            HashMap::new(),
+            json_options,
+            &HashMap::new(),
+        );
+        assert!(result.is_ok(), "{:?}", result.err());
+    }
+
+    #[test]
+    fn test_list_columns() {
+        let csv = "Day;Sales;Store;Sales\n2024-01-01;1,5;A;2\n2024-01-02;3;B;\n";
+        let options = CsvOptions {
+            delimiter: Some(b';'),
+            numbers: NumberStyle::DecimalComma,
+            ..CsvOptions::default()
+        };
+        assert_eq!(
+            list_columns(Cursor::new(csv), options).unwrap(),
+            "column   type     distinct  min  max\n\
+             Day      date     2         -    -\n\
+             Sales    numeric  2         1.5  3\n\
+             Store    text     2         -    -\n\
+             Sales_2  numeric  1         2    2\n"
+        );
+
+        let no_header = CsvOptions {
+            has_header: false,
+            ..CsvOptions::default()
+        };
+        let table = list_columns(Cursor::new("1,a\n2,b\n"), no_header).unwrap();
+        assert!(
+            table.contains("\nc0      numeric  2         1    2\n"),
+            "{}",
+            table
+        );
+    }
+
+    #[test]
+    fn test_plan_dsl_and_spec_json() {
+        let csv = "x,y,g\n1,10,a\n2,20,b\n3,15,a\n";
+        let dsl = "aes(x: x, y: y, color: g) | line() | facet_wrap(by: g)";
+        let plan = plan_dsl(
+            dsl,
+            Cursor::new(csv),
+            RenderOptions::default(),
+            HashMap::new(),
+            CsvOptions::default(),
+            &HashMap::new(),
+        )
+        .unwrap()
+        .to_string();
+        assert_eq!(
+            plan,
+            "Data: 3 rows\n\
+             Layer 1: line() x = x, y = y; grouped by color = g (2 groups)\n\
+             Facets: 2 panels by g in a 1 x 2 grid\n"
+        );
+        let json = gramgraph::parser::parse_plot_spec(dsl)
+            .unwrap()
+            .1
+            .to_json()
+            .unwrap();
+        let from_json = plan_spec_json(
+            &json,
+            Cursor::new(csv),
+            RenderOptions::default(),
+            CsvOptions::default(),
+            &HashMap::new(),
+        )
+        .unwrap();
+        assert_eq!(from_json.to_string(), plan);
+
+        // Validation still runs: a misspelled column fails the dry run
+        let err = plan_dsl(
+            "aes(x: x, y: why) | line()",
+            Cursor::new(csv),
+            RenderOptions::default(),
+            HashMap::new(),
+            CsvOptions::default(),
+            &HashMap::new(),
+        )
+        .unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("Unknown column 'why'"),
+            "{:#}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_process_spec_json() {
+        let csv = "x,y\n1,10\n2,20\n3,15\n";
+        let svg = RenderOptions {
+            format: OutputFormat::Svg,
+            ..RenderOptions::default()
+        };
+        let dsl = r#"aes(x: x, y: y) | line(color: "red") | labs(title: "Spec")"#;
+        let json = gramgraph::parser::parse_plot_spec(dsl)
+            .unwrap()
+            .1
+            .to_json()
+            .unwrap();
+
+        let from_json = process_spec_json(
+            &json,
+            Cursor::new(csv),
+            svg.clone(),
+            CsvOptions::default(),
+            &HashMap::new(),
+        )
+        .unwrap();
+        let from_dsl = process_dsl(
+            dsl,
+            Cursor::new(csv),
+            svg.clone(),
+            HashMap::new(),
+            CsvOptions::default(),
+            &HashMap::new(),
+        )
+        .unwrap();
+        assert_eq!(from_json, from_dsl);
+
+        let err = process_spec_json(
+            "{",
+            Cursor::new(csv),
+            svg,
+            CsvOptions::default(),
+            &HashMap::new(),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("Invalid JSON plot spec"));
+    }
 }
@@ -27,6 +27,129 @@ impl ColorPalette {
         }
     }
 
+    /// ColorBrewer Set2: muted qualitative colors
+    pub fn set2() -> Self {
+        ColorPalette {
+            colors: vec![
+                "#66c2a5".to_string(),
+                "#fc8d62".to_string(),
+                "#8da0cb".to_string(),
+                "#e78ac3".to_string(),
+                "#a6d854".to_string(),
+                "#ffd92f".to_string(),
+                "#e5c494".to_string(),
+                "#b3b3b3".to_string(),
+            ],
+        }
+    }
+
+    /// ColorBrewer Dark2: saturated qualitative colors
+    pub fn dark2() -> Self {
+        ColorPalette {
+            colors: vec![
+                "#1b9e77".to_string(),
+                "#d95f02".to_string(),
+                "#7570b3".to_string(),
+                "#e7298a".to_string(),
+                "#66a61e".to_string(),
+                "#e6ab02".to_string(),
+                "#a6761d".to_string(),
+                "#666666".to_string(),
+            ],
+        }
+    }
+
+    /// ColorBrewer Pastel1: light qualitative colors
+    pub fn pastel() -> Self {
+        ColorPalette {
+            colors: vec![
+                "#fbb4ae".to_string(),
+                "#b3cde3".to_string(),
+                "#ccebc5".to_string(),
+                "#decbe4".to_string(),
+                "#fed9a6".to_string(),
+                "#ffffcc".to_string(),
+                "#e5d8bd".to_string(),
+                "#fddaec".to_string(),
+                "#f2f2f2".to_string(),
+            ],
+        }
+    }
+
+    /// Tableau 20: ten hues, each with a light variant
+    pub fn tableau20() -> Self {
+        ColorPalette {
+            colors: vec![
+                "#1f77b4".to_string(),
+                "#aec7e8".to_string(),
+                "#ff7f0e".to_string(),
+                "#ffbb78".to_string(),
+                "#2ca02c".to_string(),
+                "#98df8a".to_string(),
+                "#d62728".to_string(),
+                "#ff9896".to_string(),
+                "#9467bd".to_string(),
+                "#c5b0d5".to_string(),
+                "#8c564b".to_string(),
+                "#c49c94".to_string(),
+                "#e377c2".to_string(),
+                "#f7b6d2".to_string(),
+                "#7f7f7f".to_string(),
+                "#c7c7c7".to_string(),
+                "#bcbd22".to_string(),
+                "#dbdb8d".to_string(),
+                "#17becf".to_string(),
+                "#9edae5".to_string(),
+            ],
+        }
+    }
+
+    /// Okabe-Ito: distinguishable under common color vision deficiencies
+    pub fn okabe_ito() -> Self {
+        ColorPalette {
+            colors: vec![
+                "#e69f00".to_string(),
+                "#56b4e9".to_string(),
+                "#009e73".to_string(),
+                "#f0e442".to_string(),
+                "#0072b2".to_string(),
+                "#d55e00".to_string(),
+                "#cc79a7".to_string(),
+                "#000000".to_string(),
+            ],
+        }
+    }
+
+    /// Names accepted by `ColorPalette::named` (and `scale_color(palette: ...)`)
+    pub fn names() -> &'static [&'static str] {
+        &[
+            "category10",
+            "set2",
+            "dark2",
+            "pastel",
+            "tableau20",
+            "okabe_ito",
+        ]
+    }
+
+    /// Look up a palette by name; `colorblind` is an alias for `okabe_ito`
+    pub fn named(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "category10" => Some(Self::category10()),
+            "set2" => Some(Self::set2()),
+            "dark2" => Some(Self::dark2()),
+            "pastel" => Some(Self::pastel()),
+            "tableau20" => Some(Self::tableau20()),
+            "okabe_ito" | "colorblind" => Some(Self::okabe_ito()),
+            _ => None,
+        }
+    }
+
+    /// Create a continuous two-color gradient with linear RGB interpolation
+    pub fn gradient(low: (u8, u8, u8), high: (u8, u8, u8)) -> ColorGradient {
+        ColorGradient::new(low, high)
+    }
+
     /// Get color for a specific index (wraps around if index > palette size)
     pub fn get_color(&self, index: usize) -> String {
         self.colors[index % self.colors.len()].clone()
@@ -41,6 +164,93 @@ impl ColorPalette {
             .map(|(i, key)| (key.clone(), self.get_color(i)))
             .collect()
     }
+
+    /// Assign colors with manual overrides.
+    /// Groups matching a `named` key take that color, the rest take `ordered` colors in
+    /// turn, and any groups left over keep this palette's color for their position.
+    pub fn assign_manual_colors(
+        &self,
+        group_keys: &[String],
+        named: &[(String, String)],
+        ordered: &[String],
+    ) -> HashMap<String, String> {
+        let mut ordered = ordered.iter();
+        group_keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| {
+                let color = named
+                    .iter()
+                    .find(|(name, _)| name == key)
+                    .map(|(_, color)| color)
+                    .or_else(|| ordered.next())
+                    .cloned()
+                    .unwrap_or_else(|| self.get_color(i));
+                (key.clone(), color)
+            })
+            .collect()
+    }
+}
+
+/// Continuous color gradient for numeric data
+/// Interpolates linearly between evenly spaced RGB control points.
+pub struct ColorGradient {
+    stops: Vec<(u8, u8, u8)>,
+}
+
+impl ColorGradient {
+    /// Create a two-color gradient from `low` to `high`
+    pub fn new(low: (u8, u8, u8), high: (u8, u8, u8)) -> Self {
+        ColorGradient {
+            stops: vec![low, high],
+        }
+    }
+
+    /// Simplified viridis colormap with 5 control points
+    /// Goes from dark purple (low) → blue → teal → green → yellow (high)
+    pub fn viridis() -> Self {
+        ColorGradient {
+            stops: vec![
+                (68, 1, 84),    // Dark purple (t=0.0)
+                (59, 82, 139),  // Blue (t=0.25)
+                (33, 145, 140), // Teal (t=0.5)
+                (94, 201, 98),  // Green (t=0.75)
+                (253, 231, 37), // Yellow (t=1.0)
+            ],
+        }
+    }
+
+    /// Map a normalized value (0.0-1.0) to a hex color string
+    pub fn color_at(&self, t: f64) -> String {
+        let segments = (self.stops.len() - 1).max(1);
+        let t = t.clamp(0.0, 1.0);
+        let segment = (t * segments as f64).min(segments as f64 - 0.001);
+        let idx = (segment.floor() as usize).min(self.stops.len() - 1);
+        let frac = segment - idx as f64;
+
+        let (r1, g1, b1) = self.stops[idx];
+        let (r2, g2, b2) = self.stops[(idx + 1).min(self.stops.len() - 1)];
+
+        let lerp = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * frac).round() as u8;
+
+        format!(
+            "#{:02x}{:02x}{:02x}",
+            lerp(r1, r2),
+            lerp(g1, g2),
+            lerp(b1, b2)
+        )
+    }
+
+    /// Sample `n` evenly spaced colors from low to high (used for colorbars)
+    pub fn sample(&self, n: usize) -> Vec<String> {
+        match n {
+            0 => vec![],
+            1 => vec![self.color_at(0.5)],
+            _ => (0..n)
+                .map(|i| self.color_at(i as f64 / (n - 1) as f64))
+                .collect(),
+        }
+    }
 }
 
 /// Size palette for categorical or continuous size mapping
@@ -60,6 +270,15 @@ impl SizePalette {
         SizePalette::new(3.0, 15.0)
     }
 
+    /// Size for a continuous value normalized to 0..=1.
+    /// Interpolates marker area rather than radius so bubble area tracks the value.
+    pub fn size_at(&self, t: f64) -> f64 {
+        let t = t.clamp(0.0, 1.0);
+        let min_area = self.min_size * self.min_size;
+        let max_area = self.max_size * self.max_size;
+        (min_area + (max_area - min_area) * t).sqrt()
+    }
+
     /// Assign discrete sizes to a list of group keys
     /// Sizes are evenly distributed between min and max
     pub fn assign_sizes(&self, group_keys: &[String]) -> HashMap<String, f64> {
@@ -169,6 +388,39 @@ impl ShapePalette {
     }
 }
 
+/// Linetype palette for categorical linetype mapping
+pub struct LinetypePalette {
+    linetypes: Vec<String>,
+}
+
+impl LinetypePalette {
+    /// Create a palette with the supported dash patterns
+    pub fn default_linetypes() -> Self {
+        LinetypePalette {
+            linetypes: vec![
+                "solid".to_string(),
+                "dashed".to_string(),
+                "dotted".to_string(),
+                "dotdash".to_string(),
+            ],
+        }
+    }
+
+    /// Get linetype for a specific index (wraps around)
+    pub fn get_linetype(&self, index: usize) -> String {
+        self.linetypes[index % self.linetypes.len()].clone()
+    }
+
+    /// Assign linetypes to a list of group keys
+    pub fn assign_linetypes(&self, group_keys: &[String]) -> HashMap<String, String> {
+        group_keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| (key.clone(), self.get_linetype(i)))
+            .collect()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -196,6 +448,92 @@ mod tests {
         assert_eq!(colors.len(), 3);
     }
 
+    #[test]
+    fn test_color_gradient_endpoints() {
+        let gradient = ColorGradient::new((0, 0, 255), (255, 0, 0));
+        assert_eq!(gradient.color_at(0.0), "#0000ff");
+        assert_eq!(gradient.color_at(1.0), "#ff0000");
+        assert_eq!(gradient.color_at(0.5), "#800080");
+        // Out-of-range values clamp to the endpoints
+        assert_eq!(gradient.color_at(-1.0), "#0000ff");
+        assert_eq!(gradient.color_at(2.0), "#ff0000");
+    }
+
+    #[test]
+    fn test_color_palette_gradient() {
+        let gradient = ColorPalette::gradient((0, 0, 0), (200, 100, 50));
+        assert_eq!(gradient.color_at(0.0), "#000000");
+        assert_eq!(gradient.color_at(0.5), "#643219");
+        assert_eq!(gradient.color_at(1.0), "#c86432");
+    }
+
+    #[test]
+    fn test_switching_palettes_changes_color_map() {
+        let keys = vec!["A".to_string(), "B".to_string(), "C".to_string()];
+        let default_map = ColorPalette::category10().assign_colors(&keys);
+        let set2_map = ColorPalette::named("set2").unwrap().assign_colors(&keys);
+
+        assert_ne!(default_map, set2_map);
+        assert_eq!(set2_map["A"], "#66c2a5");
+        assert_eq!(set2_map["C"], "#8da0cb");
+        for name in ColorPalette::names() {
+            assert!(
+                ColorPalette::named(name).is_some(),
+                "{} should resolve",
+                name
+            );
+        }
+        assert!(ColorPalette::named("colorblind").is_some());
+        assert!(ColorPalette::named("nope").is_none());
+    }
+
+    #[test]
+    fn test_color_palette_manual_overrides() {
+        let palette = ColorPalette::category10();
+        let keys = vec![
+            "East".to_string(),
+            "North".to_string(),
+            "South".to_string(),
+            "West".to_string(),
+        ];
+        let named = vec![("South".to_string(), "red".to_string())];
+        let ordered = vec!["blue".to_string(), "green".to_string()];
+        let colors = palette.assign_manual_colors(&keys, &named, &ordered);
+
+        assert_eq!(colors["South"], "red");
+        assert_eq!(colors["East"], "blue");
+        assert_eq!(colors["North"], "green");
+        // Not covered by the manual scale: default palette color for its position
+        assert_eq!(colors["West"], palette.get_color(3));
+    }
+
+    #[test]
+    fn test_size_palette_size_at_scales_area() {
+        let palette = SizePalette::new(3.0, 15.0);
+        assert_eq!(palette.size_at(0.0), 3.0);
+        assert_eq!(palette.size_at(1.0), 15.0);
+        // Halfway in value is halfway in area, not radius
+        let mid = palette.size_at(0.5);
+        assert!((mid * mid - (9.0 + 225.0) / 2.0).abs() < 1e-9);
+        assert_eq!(palette.size_at(2.0), 15.0);
+    }
+
+    #[test]
+    fn test_color_gradient_viridis_stops() {
+        let gradient = ColorGradient::viridis();
+        assert_eq!(gradient.color_at(0.0), "#440154");
+        assert_eq!(gradient.color_at(0.5), "#21918c");
+        assert_eq!(gradient.color_at(1.0), "#fde725");
+    }
+
+    #[test]
+    fn test_color_gradient_sample() {
+        let gradient = ColorGradient::new((0, 0, 0), (255, 255, 255));
+        let colors = gradient.sample(3);
+        assert_eq!(colors, vec!["#000000", "#808080", "#ffffff"]);
+        assert!(gradient.sample(0).is_empty());
+    }
+
     #[test]
     fn test_size_palette_default_range() {
         let palette = SizePalette::default_range();
@@ -272,4 +610,24 @@ mod tests {
         assert_eq!(shapes.get("A"), Some(&"circle".to_string()));
         assert_eq!(shapes.get("B"), Some(&"square".to_string()));
     }
+
+    #[test]
+    fn test_linetype_palette_default_linetypes() {
+        let palette = LinetypePalette::default_linetypes();
+        assert_eq!(palette.get_linetype(0), "solid");
+        assert_eq!(palette.get_linetype(1), "dashed");
+        assert_eq!(palette.get_linetype(3), "dotdash");
+        // Wraps around
+        assert_eq!(palette.get_linetype(4), "solid");
+    }
+
+    #[test]
+    fn test_linetype_palette_assign_linetypes() {
+        let palette = LinetypePalette::default_linetypes();
+        let groups = vec!["A".to_string(), "B".to_string(), "C".to_string()];
+        let linetypes = palette.assign_linetypes(&groups);
+        assert_eq!(linetypes.get("A"), Some(&"solid".to_string()));
+        assert_eq!(linetypes.get("B"), Some(&"dashed".to_string()));
+        assert_eq!(linetypes.get("C"), Some(&"dotted".to_string()));
+    }
 }
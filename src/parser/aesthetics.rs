@@ -1,11 +1,19 @@
 // Aesthetics parser for Grammar of Graphics DSL
 
-use super::ast::Aesthetics;
-use super::lexer::{identifier, ws};
-use nom::{bytes::complete::tag, character::complete::char, multi::separated_list0, IResult};
+use super::ast::{Aesthetics, ColumnRef};
+use super::lexer::{column_ref, identifier, ws};
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::char,
+    combinator::map,
+    multi::{separated_list0, separated_list1},
+    sequence::delimited,
+    IResult,
+};
 
 /// Parse aesthetics specification
-/// Format: aes(x: col, y: col[, color: col2][, size: col3][, shape: col4][, alpha: col5])
+/// Format: aes(x: col, y: col | [col, ...][, color: col2][, size: col3][, shape: col4][, alpha: col5][, linetype: col6][, group: col7])
 pub fn parse_aesthetics(input: &str) -> IResult<&str, Aesthetics> {
     let (input, _) = ws(tag("aes"))(input)?;
     let (input, _) = ws(char('('))(input)?;
@@ -25,8 +33,26 @@ pub fn parse_aesthetics(input: &str) -> IResult<&str, Aesthetics> {
     let mut ymin = None;
     let mut ymax = None;
     let mut fill = None;
+    let mut linetype = None;
+    let mut group = None;
+
+    let mut y_columns = Vec::new();
 
     for (key, value) in args {
+        let value = match (key.as_str(), value) {
+            ("y", AesValue::List(columns)) => {
+                y_columns = columns;
+                continue;
+            }
+            (_, AesValue::Column(column)) => column,
+            // Only y takes a list of columns
+            (_, AesValue::List(_)) => {
+                return Err(nom::Err::Failure(nom::error::Error::new(
+                    input,
+                    nom::error::ErrorKind::Verify,
+                )))
+            }
+        };
         match key.as_str() {
             "x" => x = Some(value),
             "y" => y = Some(value),
@@ -37,7 +63,15 @@ pub fn parse_aesthetics(input: &str) -> IResult<&str, Aesthetics> {
             "ymin" => ymin = Some(value),
             "ymax" => ymax = Some(value),
             "fill" => fill = Some(value),
-            _ => {} // Ignore unknown keys
+            "linetype" => linetype = Some(value),
+            "group" => group = Some(value),
+            // Unknown keys fail instead of being ignored (see arguments::validate_arguments)
+            _ => {
+                return Err(nom::Err::Failure(nom::error::Error::new(
+                    input,
+                    nom::error::ErrorKind::Verify,
+                )))
+            }
         }
     }
 
@@ -53,6 +87,7 @@ pub fn parse_aesthetics(input: &str) -> IResult<&str, Aesthetics> {
         Aesthetics {
             x,
             y,
+            y_columns,
             color,
             size,
             shape,
@@ -60,19 +95,37 @@ pub fn parse_aesthetics(input: &str) -> IResult<&str, Aesthetics> {
             ymin,
             ymax,
             fill,
+            linetype,
+            group,
         },
     ))
 }
 
 /// Parse a single aesthetic argument (key: value)
-/// Values are identifiers (column names)
-fn parse_aesthetic_argument(input: &str) -> IResult<&str, (String, String)> {
+/// Values are column names, bare or quoted (`y: "Sales (USD)"`), zero-based column
+/// positions (`x: 0`), or a bracketed list of them (`y: [temp, humidity]`)
+fn parse_aesthetic_argument(input: &str) -> IResult<&str, (String, AesValue)> {
     let (input, key) = ws(identifier)(input)?;
     let (input, _) = ws(char(':'))(input)?;
-    let (input, value) = ws(identifier)(input)?;
+    let (input, value) = alt((
+        map(
+            delimited(
+                ws(char('[')),
+                separated_list1(ws(char(',')), ws(column_ref)),
+                ws(char(']')),
+            ),
+            AesValue::List,
+        ),
+        map(ws(column_ref), AesValue::Column),
+    ))(input)?;
     Ok((input, (key, value)))
 }
 
+enum AesValue {
+    Column(ColumnRef),
+    List(Vec<ColumnRef>),
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -83,7 +136,7 @@ mod tests {
         assert!(result.is_ok());
         let (_, aes) = result.unwrap();
         assert_eq!(aes.x, "time");
-        assert_eq!(aes.y, Some("temp".to_string()));
+        assert_eq!(aes.y, Some("temp".into()));
     }
 
     #[test]
@@ -92,7 +145,33 @@ mod tests {
         assert!(result.is_ok());
         let (_, aes) = result.unwrap();
         assert_eq!(aes.x, "time");
-        assert_eq!(aes.y, Some("temp".to_string()));
+        assert_eq!(aes.y, Some("temp".into()));
+    }
+
+    #[test]
+    fn test_parse_aesthetics_linetype() {
+        let (_, aes) = parse_aesthetics("aes(x: time, y: temp, linetype: site)").unwrap();
+        assert_eq!(aes.linetype, Some("site".into()));
+        assert_eq!(aes.color, None);
+    }
+
+    #[test]
+    fn test_parse_aesthetics_group() {
+        let (_, aes) = parse_aesthetics("aes(x: time, y: value, group: sensor_id)").unwrap();
+        assert_eq!(aes.group, Some("sensor_id".into()));
+        assert_eq!(aes.color, None);
+    }
+
+    #[test]
+    fn test_parse_aesthetics_y_list() {
+        let (_, aes) =
+            parse_aesthetics("aes(x: time, y: [temp, `rel humidity`, pressure])").unwrap();
+        assert_eq!(aes.y, None);
+        assert_eq!(aes.y_columns, vec!["temp", "rel humidity", "pressure"]);
+
+        // Only y takes a list, and the list can't be empty
+        assert!(parse_aesthetics("aes(x: time, color: [a, b])").is_err());
+        assert!(parse_aesthetics("aes(x: time, y: [])").is_err());
     }
 
     #[test]
@@ -126,4 +205,28 @@ mod tests {
         let (_, aes) = result.unwrap();
         assert_eq!(aes.x, "value");
     }
+
+    #[test]
+    fn test_parse_aesthetics_unknown_key() {
+        assert!(parse_aesthetics("aes(x: time, colour: series)").is_err());
+    }
+
+    #[test]
+    fn test_parse_aesthetics_quoted_columns() {
+        let (_, aes) =
+            parse_aesthetics(r#"aes(x: `Date (UTC)`, y: "Sales (USD)", color: "store-id")"#)
+                .unwrap();
+        assert_eq!(aes.x, "Date (UTC)");
+        assert_eq!(aes.y, Some("Sales (USD)".into()));
+        assert_eq!(aes.color, Some("store-id".into()));
+    }
+
+    #[test]
+    fn test_parse_aesthetics_column_indices() {
+        let (_, aes) = parse_aesthetics("aes(x: 0, y: [1, rate], color: `2`)").unwrap();
+        assert_eq!(aes.x, ColumnRef::Index(0));
+        assert_eq!(aes.y_columns, vec![ColumnRef::Index(1), "rate".into()]);
+        // Backticks name a header that happens to be a number
+        assert_eq!(aes.color, Some("2".into()));
+    }
 }
@@ -0,0 +1,227 @@
+use crate::parser::ast::{Annotation, AnnotationX, Shade};
+use crate::parser::lexer::{bool_literal, number_literal, string_literal, ws};
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::char,
+    combinator::map,
+    error::{Error, ErrorKind},
+    multi::separated_list0,
+    sequence::preceded,
+    IResult,
+};
+
+enum AnnotateArg {
+    Position(&'static str, AnnotationX),
+    Number(&'static str, f64),
+    Text(&'static str, String),
+    Flag(&'static str, bool),
+}
+
+fn number<'a>(key: &'static str) -> impl FnMut(&'a str) -> IResult<&'a str, AnnotateArg> {
+    map(preceded(ws(tag(key)), ws(number_literal)), move |n| {
+        AnnotateArg::Number(key, n)
+    })
+}
+
+fn text<'a>(key: &'static str) -> impl FnMut(&'a str) -> IResult<&'a str, AnnotateArg> {
+    map(preceded(ws(tag(key)), ws(string_literal)), move |s| {
+        AnnotateArg::Text(key, s)
+    })
+}
+
+fn flag<'a>(key: &'static str) -> impl FnMut(&'a str) -> IResult<&'a str, AnnotateArg> {
+    map(preceded(ws(tag(key)), ws(bool_literal)), move |b| {
+        AnnotateArg::Flag(key, b)
+    })
+}
+
+/// A number, or a quoted category name or datetime
+fn position<'a>(key: &'static str) -> impl FnMut(&'a str) -> IResult<&'a str, AnnotateArg> {
+    map(
+        preceded(
+            ws(tag(key)),
+            alt((
+                map(ws(number_literal), AnnotationX::Number),
+                map(ws(string_literal), AnnotationX::Text),
+            )),
+        ),
+        move |x| AnnotateArg::Position(key, x),
+    )
+}
+
+/// Parse a fixed note at data coordinates
+/// Format: annotate(x: 2020, y: 95, text: "policy change", color: "red", ...)
+/// - x: required, a number or a quoted category name / datetime
+/// - y, text: required
+pub fn parse_annotate(input: &str) -> IResult<&str, Annotation> {
+    let (input, _) = ws(tag("annotate"))(input)?;
+    let (input, _) = ws(char('('))(input)?;
+
+    let (input, args) = separated_list0(
+        ws(char(',')),
+        alt((
+            position("x:"),
+            number("y:"),
+            text("text:"),
+            text("color:"),
+            number("size:"),
+            number("nudge_x:"),
+            number("nudge_y:"),
+            flag("point:"),
+            flag("arrow:"),
+            flag("expand:"),
+            text("panel:"),
+        )),
+    )(input)?;
+
+    let (input, _) = ws(char(')'))(input)?;
+
+    let mut annotation = Annotation::default();
+    let (mut has_x, mut has_y, mut has_text) = (false, false, false);
+    for arg in args {
+        match arg {
+            AnnotateArg::Position(_, x) => {
+                annotation.x = x;
+                has_x = true;
+            }
+            AnnotateArg::Number("y:", y) => {
+                annotation.y = y;
+                has_y = true;
+            }
+            AnnotateArg::Number("size:", s) => annotation.size = Some(s),
+            AnnotateArg::Number("nudge_x:", n) => annotation.nudge_x = n,
+            AnnotateArg::Number("nudge_y:", n) => annotation.nudge_y = n,
+            AnnotateArg::Text("text:", t) => {
+                annotation.text = t;
+                has_text = true;
+            }
+            AnnotateArg::Text("color:", c) => annotation.color = Some(c),
+            AnnotateArg::Text("panel:", p) => annotation.panel = Some(p),
+            AnnotateArg::Flag("point:", b) => annotation.point = b,
+            AnnotateArg::Flag("arrow:", b) => annotation.arrow = b,
+            AnnotateArg::Flag("expand:", b) => annotation.expand = b,
+            _ => {}
+        }
+    }
+
+    // x:, y:, and text: are required
+    if !(has_x && has_y && has_text) {
+        return Err(nom::Err::Failure(Error::new(input, ErrorKind::Verify)));
+    }
+
+    Ok((input, annotation))
+}
+
+/// Parse a background band
+/// Format: shade(xmin: 2008, xmax: 2009.5, color: "gray", alpha: 0.2, ...)
+/// - xmin, xmax: a number or a quoted category name / datetime
+/// - ymin, ymax: numbers
+/// - at least one side is required; the others run to the axis limits
+pub fn parse_shade(input: &str) -> IResult<&str, Shade> {
+    let (input, _) = ws(tag("shade"))(input)?;
+    let (input, _) = ws(char('('))(input)?;
+
+    let (input, args) = separated_list0(
+        ws(char(',')),
+        alt((
+            position("xmin:"),
+            position("xmax:"),
+            number("ymin:"),
+            number("ymax:"),
+            text("color:"),
+            number("alpha:"),
+            text("panel:"),
+        )),
+    )(input)?;
+
+    let (input, _) = ws(char(')'))(input)?;
+
+    let mut shade = Shade::default();
+    for arg in args {
+        match arg {
+            AnnotateArg::Position("xmin:", x) => shade.xmin = Some(x),
+            AnnotateArg::Position("xmax:", x) => shade.xmax = Some(x),
+            AnnotateArg::Number("ymin:", y) => shade.ymin = Some(y),
+            AnnotateArg::Number("ymax:", y) => shade.ymax = Some(y),
+            AnnotateArg::Number("alpha:", a) => shade.alpha = Some(a),
+            AnnotateArg::Text("color:", c) => shade.color = Some(c),
+            AnnotateArg::Text("panel:", p) => shade.panel = Some(p),
+            _ => {}
+        }
+    }
+
+    let bounded = shade.xmin.is_some()
+        || shade.xmax.is_some()
+        || shade.ymin.is_some()
+        || shade.ymax.is_some();
+    if !bounded {
+        return Err(nom::Err::Failure(Error::new(input, ErrorKind::Verify)));
+    }
+
+    Ok((input, shade))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_annotate() {
+        let (_, note) =
+            parse_annotate(r#"annotate(x: 2020, y: 95, text: "policy change", color: "red")"#)
+                .unwrap();
+        assert_eq!(note.x, AnnotationX::Number(2020.0));
+        assert_eq!(note.y, 95.0);
+        assert_eq!(note.text, "policy change");
+        assert_eq!(note.color.as_deref(), Some("red"));
+        assert!(note.expand && !note.point && !note.arrow);
+        assert_eq!(note.panel, None);
+
+        let (_, note) = parse_annotate(
+            r#"annotate(x: "B", y: 3, text: "peak", point: true, arrow: true, nudge_y: 1.5, nudge_x: -0.2, size: 14, expand: false, panel: "EU")"#,
+        )
+        .unwrap();
+        assert_eq!(note.x, AnnotationX::Text("B".to_string()));
+        assert!(note.point && note.arrow && !note.expand);
+        assert_eq!((note.nudge_x, note.nudge_y), (-0.2, 1.5));
+        assert_eq!(note.size, Some(14.0));
+        assert_eq!(note.panel.as_deref(), Some("EU"));
+    }
+
+    #[test]
+    fn test_parse_annotate_requires_position_and_text() {
+        for dsl in [
+            r#"annotate(y: 1, text: "a")"#,
+            r#"annotate(x: 1, text: "a")"#,
+            "annotate(x: 1, y: 2)",
+        ] {
+            assert!(
+                matches!(parse_annotate(dsl), Err(nom::Err::Failure(_))),
+                "{dsl}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_shade() {
+        let (_, shade) =
+            parse_shade(r#"shade(xmin: 2008, xmax: 2009.5, color: "gray", alpha: 0.2)"#).unwrap();
+        assert_eq!(shade.xmin, Some(AnnotationX::Number(2008.0)));
+        assert_eq!(shade.xmax, Some(AnnotationX::Number(2009.5)));
+        assert_eq!((shade.ymin, shade.ymax), (None, None));
+        assert_eq!(shade.color.as_deref(), Some("gray"));
+        assert_eq!(shade.alpha, Some(0.2));
+
+        let (_, shade) = parse_shade(r#"shade(ymin: 95, xmin: "B", panel: "EU")"#).unwrap();
+        assert_eq!(shade.xmin, Some(AnnotationX::Text("B".to_string())));
+        assert_eq!(shade.ymin, Some(95.0));
+        assert_eq!(shade.panel.as_deref(), Some("EU"));
+
+        // A band needs at least one side
+        assert!(matches!(
+            parse_shade(r#"shade(color: "red")"#),
+            Err(nom::Err::Failure(_))
+        ));
+    }
+}
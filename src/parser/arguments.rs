@@ -0,0 +1,611 @@
+// Argument-name validation for DSL function calls
+//
+// The nom parsers match each argument with a fixed `tag("key:")`, so a misspelled
+// key (`colour:`, `widht:`) either fails with an opaque error or is silently ignored.
+// This pass scans every `name(...)` call, captures its `key:` names generically, and
+// reports unknown keys together with the keys that function accepts. The same scan
+// finds deprecated spellings, which are reported as warnings instead of by the parser.
+// String arguments that take one of a fixed set of values are checked here too, so an
+// unknown value is named along with the accepted ones; the parsers refuse it as well.
+
+use crate::suggest::closest_match;
+use crate::warning::Warning;
+use anyhow::{anyhow, Result};
+
+/// Argument names accepted by each DSL function; functions not listed are not checked
+pub fn allowed_arguments(function: &str) -> Option<&'static [&'static str]> {
+    let args: &'static [&'static str] = match function {
+        "aes" => &[
+            "x", "y", "color", "size", "shape", "alpha", "ymin", "ymax", "fill", "linetype",
+            "group",
+        ],
+        "facet_wrap" => &[
+            "by",
+            "ncol",
+            "nrow",
+            "scales",
+            "bins",
+            "cut",
+            "labeller",
+            "label_size",
+        ],
+        "facet_grid" => &["rows", "cols", "scales", "labeller", "label_size"],
+        "labs" => &["title", "subtitle", "x", "y", "caption"],
+        "annotate" => &[
+            "x", "y", "text", "color", "size", "nudge_x", "nudge_y", "point", "arrow", "expand",
+            "panel",
+        ],
+        "coord_fixed" => &["ratio"],
+        "shade" => &["xmin", "xmax", "ymin", "ymax", "color", "alpha", "panel"],
+        "line" => &[
+            "x",
+            "y",
+            "color",
+            "width",
+            "alpha",
+            "linetype",
+            "group",
+            "position",
+            "stat",
+            "window",
+            "min_periods",
+            "simplify",
+            "filter",
+            "data",
+        ],
+        "step" => &[
+            "x",
+            "y",
+            "direction",
+            "color",
+            "width",
+            "alpha",
+            "filter",
+            "data",
+        ],
+        "area" => &["x", "y", "color", "alpha", "baseline", "filter", "data"],
+        "rug" => &[
+            "x", "y", "sides", "length", "color", "width", "alpha", "filter", "data",
+        ],
+        "spike" => &[
+            "x", "y", "baseline", "color", "width", "alpha", "filter", "data",
+        ],
+        "linerange" => &[
+            "x", "ymin", "ymax", "color", "width", "alpha", "filter", "data",
+        ],
+        "errorbar" => &[
+            "x",
+            "ymin",
+            "ymax",
+            "color",
+            "linewidth",
+            "width",
+            "alpha",
+            "filter",
+            "data",
+        ],
+        "pointrange" => &[
+            "x", "ymin", "ymax", "y", "color", "width", "size", "shape", "alpha", "filter", "data",
+        ],
+        "crossbar" => &[
+            "x",
+            "ymin",
+            "ymax",
+            "y",
+            "color",
+            "width",
+            "linewidth",
+            "alpha",
+            "filter",
+            "data",
+        ],
+        "hline" => &["yintercept", "color", "width", "alpha", "label"],
+        "vline" => &["xintercept", "color", "width", "alpha", "label"],
+        "abline" => &["slope", "intercept", "color", "width", "alpha", "label"],
+        "segment" => &[
+            "x", "y", "xend", "yend", "color", "width", "alpha", "label", "arrow",
+        ],
+        "text" => &[
+            "x", "y", "label", "color", "size", "alpha", "nudge_x", "nudge_y", "filter", "data",
+        ],
+        "point" => &[
+            "x",
+            "y",
+            "color",
+            "size",
+            "shape",
+            "alpha",
+            "fade_by",
+            "fade",
+            "position",
+            "jitter_width",
+            "fill",
+            "stroke",
+            "fast",
+            "filter",
+            "data",
+        ],
+        "jitter" => &[
+            "x", "y", "color", "size", "shape", "alpha", "fade_by", "fade", "width", "fill",
+            "stroke", "fast", "filter", "data",
+        ],
+        "bar" => &[
+            "x",
+            "y",
+            "color",
+            "width",
+            "alpha",
+            "position",
+            "stat",
+            "x_continuous",
+            "border",
+            "border_width",
+            "filter",
+            "data",
+        ],
+        "ribbon" => &["x", "ymin", "ymax", "color", "alpha", "filter", "data"],
+        "histogram" => &["bins", "filter", "data"],
+        "freqpoly" => &["x", "bins", "color", "width", "alpha", "filter", "data"],
+        "smooth" => &[
+            "x", "y", "method", "span", "samples", "color", "width", "alpha", "filter", "data",
+        ],
+        "boxplot" => &[
+            "x",
+            "y",
+            "color",
+            "width",
+            "alpha",
+            "outlier_color",
+            "outlier_size",
+            "outlier_shape",
+            "filter",
+            "data",
+        ],
+        "violin" => &[
+            "x",
+            "y",
+            "color",
+            "width",
+            "alpha",
+            "draw_quantiles",
+            "filter",
+            "data",
+        ],
+        "density" => &["x", "color", "alpha", "bw", "fill", "filter", "data"],
+        "pie" => &["x", "y", "alpha", "donut", "other", "filter", "data"],
+        "heatmap" => &["x", "y", "fill", "bins", "alpha", "filter", "data"],
+        "tile" => &["x", "y", "fill", "alpha", "filter", "data"],
+        "summarize" | "summarise" => &["by", "y", "as"],
+        "sort" => &["by", "desc"],
+        "head" => &["n"],
+        "sample" => &["n", "seed"],
+        "pivot_longer" => &["cols", "names_to", "values_to"],
+        "scale_x_datetime" => &["interval", "format"],
+        "scale_x_discrete" => &["order"],
+        "scale_x" => &[
+            "rotate",
+            "format",
+            "max_label_len",
+            "expand",
+            "parse",
+            "reverse",
+            "ticks",
+            "breaks",
+        ],
+        "scale_y" => &[
+            "rotate",
+            "format",
+            "max_label_len",
+            "expand",
+            "parse",
+            "reverse",
+            "ticks",
+            "breaks",
+        ],
+        "scale_color" | "scale_colour" => &["low", "high", "palette", "order", "levels"],
+        "scale_color_manual" | "scale_colour_manual" => &["values"],
+        "theme" => &[
+            "line",
+            "rect",
+            "text",
+            "plot_background",
+            "plot_title",
+            "panel_background",
+            "panel_grid_major",
+            "panel_grid_minor",
+            "axis_text",
+            "axis_line",
+            "axis_ticks",
+            "legend_position",
+            "legend_background",
+            "legend_text",
+            "legend_margin",
+            "legend_key_size",
+            "margin",
+            "preset",
+            "background",
+            "grid",
+            "grid_minor",
+            "grid_color",
+            "grid_width",
+            "grid_linetype",
+            "grid_separators",
+            "font_size",
+            "font",
+        ],
+        "element_text" => &["size", "color", "family", "face", "angle", "hjust", "vjust"],
+        "element_line" => &["color", "width", "linetype"],
+        "element_rect" => &["fill", "color", "width"],
+        _ => return None,
+    };
+    Some(args)
+}
+
+/// The dash patterns `graph::dash_pattern` draws
+const LINETYPES: &[&str] = &["solid", "dashed", "dotted", "dotdash"];
+
+/// Values accepted by string arguments that take one of a fixed set; other string
+/// arguments are not checked
+pub fn allowed_values(function: &str, key: &str) -> Option<&'static [&'static str]> {
+    let values: &'static [&'static str] = match (function, key) {
+        ("bar", "stat") => &["identity", "sum", "mean", "min", "max", "count"],
+        ("theme", "grid") => &["none", "major", "both"],
+        ("facet_wrap", "cut") => &["width", "quantile"],
+        ("theme", "preset") => &["minimal", "dark", "classic", "light", "void"],
+        ("line" | "element_line", "linetype") | ("theme", "grid_linetype") => LINETYPES,
+        _ => return None,
+    };
+    Some(values)
+}
+
+/// Whether `function(key: "value")` is accepted: always, unless `allowed_values` limits it
+pub fn is_allowed_value(function: &str, key: &str, value: &str) -> bool {
+    allowed_values(function, key).is_none_or(|values| values.contains(&value))
+}
+
+/// Check every known function call in the DSL for unknown argument names.
+/// Unknown functions, positional arguments, and syntax errors are left to the parser.
+pub fn validate_arguments(dsl: &str) -> Result<()> {
+    for_each_call(dsl, |name, inner| match allowed_arguments(name) {
+        Some(allowed) => check_call(name, inner, allowed),
+        None => Ok(()),
+    })
+}
+
+/// Arguments written in a spelling that still parses but is deprecated
+pub fn deprecated_arguments(dsl: &str) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    let _ = for_each_call(dsl, |name, inner| {
+        if name != "facet_wrap" && name != "facet_grid" {
+            return Ok(());
+        }
+        for arg in split_top_level(inner) {
+            if leading_key(arg) != Some("ncol") {
+                continue;
+            }
+            let value = arg.split_once(':').map_or("", |(_, value)| value).trim();
+            if let Some(n) = value
+                .strip_prefix("Some")
+                .map(str::trim_start)
+                .and_then(|rest| rest.strip_prefix('('))
+                .and_then(|rest| rest.strip_suffix(')'))
+            {
+                let n = n.trim();
+                warnings.push(Warning::Deprecated {
+                    message: format!(
+                        "{}(ncol: Some({})) is deprecated; write ncol: {}",
+                        name, n, n
+                    ),
+                });
+            }
+        }
+        Ok(())
+    });
+    warnings
+}
+
+/// Call `f(name, inner)` for every `name(...)` call, nested ones included, with the
+/// text between its parentheses; stops at the first error
+fn for_each_call(dsl: &str, mut f: impl FnMut(&str, &str) -> Result<()>) -> Result<()> {
+    let chars: Vec<(usize, char)> = dsl.char_indices().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let (start, c) = chars[i];
+        if is_quote(c) {
+            i = skip_string(&chars, i);
+            continue;
+        }
+        if !is_ident_start(c) {
+            i += 1;
+            continue;
+        }
+
+        let mut end = i;
+        while end < chars.len() && is_ident_char(chars[end].1) {
+            end += 1;
+        }
+        let name = &dsl[start..byte_at(&chars, end, dsl.len())];
+        let mut next = end;
+        while next < chars.len() && chars[next].1.is_whitespace() {
+            next += 1;
+        }
+        if next < chars.len() && chars[next].1 == '(' {
+            let close = matching_paren(&chars, next);
+            let inner = &dsl[chars[next].0 + 1..byte_at(&chars, close, dsl.len())];
+            f(name, inner)?;
+        }
+        // Continue inside the argument list so nested calls are checked too
+        i = end;
+    }
+    Ok(())
+}
+
+fn check_call(name: &str, inner: &str, allowed: &[&str]) -> Result<()> {
+    for arg in split_top_level(inner) {
+        let Some(key) = leading_key(arg) else {
+            continue;
+        };
+        if !allowed.contains(&key) {
+            let hint = closest_match(key, allowed.iter().copied())
+                .map(|s| format!(" (did you mean '{}'?)", s))
+                .unwrap_or_default();
+            return Err(anyhow!(
+                "Unknown argument '{}' for {}(){}. {}() accepts: {}",
+                key,
+                name,
+                hint,
+                name,
+                allowed.join(", ")
+            ));
+        }
+        let value = arg.split_once(':').map_or("", |(_, value)| value).trim();
+        let Some(value) = value
+            .strip_prefix('"')
+            .and_then(|value| value.strip_suffix('"'))
+        else {
+            continue;
+        };
+        if let Some(values) = allowed_values(name, key).filter(|values| !values.contains(&value)) {
+            return Err(anyhow!(
+                "Unknown {} '{}' for {}(). Use one of: {}",
+                key,
+                value,
+                name,
+                values.join(", ")
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Split an argument list on commas that are outside strings and nested calls
+fn split_top_level(inner: &str) -> Vec<&str> {
+    let mut args = Vec::new();
+    let mut depth = 0usize;
+    let mut quote = None;
+    let mut escaped = false;
+    let mut last = 0;
+    for (i, c) in inner.char_indices() {
+        let in_string = quote.is_some();
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' if quote == Some('"') => escaped = true,
+            c if is_quote(c) && quote.is_none_or(|q| q == c) => {
+                quote = if in_string { None } else { Some(c) }
+            }
+            '(' | '[' if !in_string => depth += 1,
+            ')' | ']' if !in_string => depth = depth.saturating_sub(1),
+            ',' if !in_string && depth == 0 => {
+                args.push(&inner[last..i]);
+                last = i + 1;
+            }
+            _ => {}
+        }
+    }
+    args.push(&inner[last..]);
+    args
+}
+
+/// The `key` of a `key: value` argument, if the argument has that shape
+fn leading_key(arg: &str) -> Option<&str> {
+    let arg = arg.trim_start();
+    let end = arg
+        .char_indices()
+        .find(|&(_, c)| !is_ident_char(c))
+        .map(|(i, _)| i)
+        .unwrap_or(arg.len());
+    if end == 0 || !arg.starts_with(is_ident_start) {
+        return None;
+    }
+    arg[end..]
+        .trim_start()
+        .starts_with(':')
+        .then(|| &arg[..end])
+}
+
+/// Double quotes delimit strings and quoted columns; backticks quote columns
+fn is_quote(c: char) -> bool {
+    c == '"' || c == '`'
+}
+
+fn is_ident_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn byte_at(chars: &[(usize, char)], idx: usize, len: usize) -> usize {
+    chars.get(idx).map(|&(b, _)| b).unwrap_or(len)
+}
+
+/// Index just past a string literal (or backtick-quoted column) starting at `i`;
+/// backslash escapes are honoured inside double-quoted strings
+fn skip_string(chars: &[(usize, char)], i: usize) -> usize {
+    let quote = chars[i].1;
+    let mut j = i + 1;
+    while j < chars.len() && chars[j].1 != quote {
+        if quote == '"' && chars[j].1 == '\\' {
+            j += 1;
+        }
+        j += 1;
+    }
+    j + 1
+}
+
+/// Index of the `)` matching the `(` at `open` (or the end of input if unclosed)
+fn matching_paren(chars: &[(usize, char)], open: usize) -> usize {
+    let mut depth = 0usize;
+    let mut j = open;
+    while j < chars.len() {
+        match chars[j].1 {
+            c if is_quote(c) => {
+                j = skip_string(chars, j);
+                continue;
+            }
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return j;
+                }
+            }
+            _ => {}
+        }
+        j += 1;
+    }
+    chars.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_deprecated_ncol_spelling() {
+        let warnings =
+            deprecated_arguments(r#"aes(x: a) | point() | facet_wrap(by: g, ncol: Some( 3 ))"#);
+        assert_eq!(
+            warnings,
+            vec![Warning::Deprecated {
+                message: "facet_wrap(ncol: Some(3)) is deprecated; write ncol: 3".to_string()
+            }]
+        );
+        assert!(deprecated_arguments("facet_wrap(by: g, ncol: 3)").is_empty());
+        assert!(deprecated_arguments(r#"labs(title: "ncol: Some(2)")"#).is_empty());
+    }
+
+    fn error_for(dsl: &str) -> String {
+        validate_arguments(dsl).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn accepts_known_arguments() {
+        assert!(validate_arguments(
+            r#"aes(x: a, y: b, color: c) | line(color: "red", width: 2) | point(size: 3) | facet_wrap(by: c, ncol: 2) | labs(title: "x: y(z)")"#
+        )
+        .is_ok());
+        assert!(validate_arguments(
+            r##"aes(x: a, y: b) | line() | theme(axis_text: element_text(size: 12, color: "#333")) | scale_color_manual(values: "a" = "red")"##
+        )
+        .is_ok());
+        // Unknown functions and positional arguments are left to the parser
+        assert!(validate_arguments("aes(x: a) | pont(colour: 1) | xlim(0, 10)").is_ok());
+    }
+
+    #[test]
+    fn rejects_misspelled_geom_arguments() {
+        let err = error_for(r#"aes(x: a, y: b) | line(colour: "red")"#);
+        assert!(err.contains("Unknown argument 'colour' for line() (did you mean 'color'?)"));
+        assert!(err.contains("line() accepts: x, y, color, width, alpha, linetype"));
+
+        let err = error_for("aes(x: a, y: b) | line(width: 2, widht: 3)");
+        assert!(err.contains("'widht'") && err.contains("did you mean 'width'?"));
+
+        let err = error_for("aes(x: a, y: b) | point(sise: 3)");
+        assert!(err.contains("'sise'") && err.contains("did you mean 'size'?"));
+    }
+
+    #[test]
+    fn rejects_unknown_values_of_fixed_choice_arguments() {
+        assert!(validate_arguments(r#"aes(x: a, y: b) | bar(stat: "mean")"#).is_ok());
+        assert_eq!(
+            error_for(r#"aes(x: a, y: b) | bar(stat: "median")"#),
+            "Unknown stat 'median' for bar(). Use one of: identity, sum, mean, min, max, count"
+        );
+        assert_eq!(
+            error_for(r#"aes(x: a, y: b) | line() | theme(grid: "minor")"#),
+            "Unknown grid 'minor' for theme(). Use one of: none, major, both"
+        );
+        assert_eq!(
+            error_for(r#"aes(x: a, y: b) | line() | theme(preset: "bogus")"#),
+            "Unknown preset 'bogus' for theme(). Use one of: minimal, dark, classic, light, void"
+        );
+        assert_eq!(
+            error_for(r#"aes(x: a, y: b) | line() | theme(grid_linetype: "wavy")"#),
+            "Unknown grid_linetype 'wavy' for theme(). Use one of: solid, dashed, dotted, dotdash"
+        );
+        assert_eq!(
+            error_for(r#"aes(x: a, y: b) | line(linetype: "wavy")"#),
+            "Unknown linetype 'wavy' for line(). Use one of: solid, dashed, dotted, dotdash"
+        );
+        assert!(error_for(
+            r#"aes(x: a, y: b) | line() | theme(axis_line: element_line(linetype: "wavy"))"#
+        )
+        .starts_with("Unknown linetype 'wavy' for element_line()"));
+        assert_eq!(
+            error_for(r#"aes(x: a, y: b) | line() | facet_wrap(by: a, bins: 3, cut: "weird")"#),
+            "Unknown cut 'weird' for facet_wrap(). Use one of: width, quantile"
+        );
+        assert!(validate_arguments(r#"aes(x: a, y: b) | line(linetype: `kind`)"#).is_ok());
+    }
+
+    #[test]
+    fn rejects_unknown_aes_and_facet_arguments() {
+        let err = error_for("aes(x: a, y: b, colour: c) | line()");
+        assert!(err.contains("Unknown argument 'colour' for aes()"));
+
+        let err = error_for("aes(x: a, y: b) | line() | facet_wrap(by: c, nclo: 2)");
+        assert!(err.contains("'nclo' for facet_wrap() (did you mean 'ncol'?)"));
+    }
+
+    #[test]
+    fn skips_quoted_column_names() {
+        assert!(validate_arguments(
+            r#"aes(x: `Date (UTC)`, y: "Sales, USD") | line(color: `a:b`) | facet_wrap(by: `x(y`)"#
+        )
+        .is_ok());
+        let err = error_for("aes(x: `a, b`, y: c) | line(colr: `d)`)");
+        assert!(err.contains("'colr' for line()"));
+    }
+
+    #[test]
+    fn skips_escaped_quotes_in_strings() {
+        assert!(validate_arguments(
+            r#"aes(x: a, y: b) | line() | labs(title: "The \"best, (x: y\" chart")"#
+        )
+        .is_ok());
+        let err = error_for(r#"aes(x: a, y: b) | line(color: "\"", widht: 2)"#);
+        assert!(err.contains("'widht' for line()"));
+    }
+
+    #[test]
+    fn accepts_filter_on_data_layers_only() {
+        assert!(validate_arguments(
+            r#"aes(x: a, y: b) | point(filter: g == "x" || (b >= 1 && b < 2)) | bar(filter: a != "y")"#
+        )
+        .is_ok());
+        let err = error_for("aes(x: a, y: b) | line() | hline(yintercept: 1, filter: b > 1)");
+        assert!(err.contains("'filter' for hline()"));
+    }
+
+    #[test]
+    fn checks_nested_calls() {
+        let err = error_for("aes(x: a) | line() | theme(axis_text: element_text(sise: 3))");
+        assert!(err.contains("'sise' for element_text()"));
+    }
+}
@@ -0,0 +1,239 @@
+// Arithmetic expressions for `mutate()`: `revenue - cost`, `log10(pop) * 2`,
+// `(high + low) / 2`
+//
+// `*` and `/` bind tighter than `+` and `-`, all four are left-associative, and
+// parentheses group. Operands are numbers, columns (bare or backtick-quoted), unary
+// minus, and the one-argument functions abs, log, log10, exp, and sqrt.
+
+use super::ast::{ArithExpr, ArithFunction, ArithOp};
+use super::lexer::{mapped_column, number_literal, ws};
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::char,
+    combinator::{map, value},
+    error::{Error, ErrorKind},
+    multi::many0,
+    sequence::{delimited, pair, preceded},
+    IResult,
+};
+
+/// Deepest nesting of parentheses, calls, and unary minus accepted, so pathological
+/// input fails to parse instead of overflowing the stack
+const MAX_DEPTH: usize = 64;
+
+/// Parse an arithmetic expression
+pub fn arith_expr(input: &str) -> IResult<&str, ArithExpr> {
+    sum(input, 0)
+}
+
+fn sum(input: &str, depth: usize) -> IResult<&str, ArithExpr> {
+    let (input, first) = term(input, depth)?;
+    let (input, rest) = many0(pair(
+        ws(alt((
+            value(ArithOp::Add, char('+')),
+            value(ArithOp::Sub, char('-')),
+        ))),
+        |i| term(i, depth),
+    ))(input)?;
+    Ok((input, fold_binary(first, rest)))
+}
+
+fn term(input: &str, depth: usize) -> IResult<&str, ArithExpr> {
+    let (input, first) = unary(input, depth)?;
+    let (input, rest) = many0(pair(
+        ws(alt((
+            value(ArithOp::Mul, char('*')),
+            value(ArithOp::Div, char('/')),
+        ))),
+        |i| unary(i, depth),
+    ))(input)?;
+    Ok((input, fold_binary(first, rest)))
+}
+
+fn fold_binary(first: ArithExpr, rest: Vec<(ArithOp, ArithExpr)>) -> ArithExpr {
+    rest.into_iter()
+        .fold(first, |left, (op, right)| ArithExpr::Binary {
+            op,
+            left: Box::new(left),
+            right: Box::new(right),
+        })
+}
+
+fn unary(input: &str, depth: usize) -> IResult<&str, ArithExpr> {
+    if depth > MAX_DEPTH {
+        return Err(nom::Err::Failure(Error::new(input, ErrorKind::TooLarge)));
+    }
+    alt((
+        map(
+            preceded(ws(char('-')), |i| unary(i, depth + 1)),
+            |inner| match inner {
+                ArithExpr::Number(n) => ArithExpr::Number(-n),
+                inner => ArithExpr::Neg(Box::new(inner)),
+            },
+        ),
+        |i| atom(i, depth),
+    ))(input)
+}
+
+fn atom(input: &str, depth: usize) -> IResult<&str, ArithExpr> {
+    // Calls before columns so `log(x)` isn't read as a column named `log`; columns
+    // before numbers so a column like `inf` isn't read as a float
+    ws(alt((
+        delimited(char('('), |i| sum(i, depth + 1), ws(char(')'))),
+        |i| call(i, depth),
+        map(mapped_column, ArithExpr::Column),
+        map(number_literal, ArithExpr::Number),
+    )))(input)
+}
+
+fn call(input: &str, depth: usize) -> IResult<&str, ArithExpr> {
+    let (input, function) = alt((
+        value(ArithFunction::Abs, tag("abs")),
+        // `log10` before `log`
+        value(ArithFunction::Log10, tag("log10")),
+        value(ArithFunction::Log, tag("log")),
+        value(ArithFunction::Exp, tag("exp")),
+        value(ArithFunction::Sqrt, tag("sqrt")),
+    ))(input)?;
+    let (input, arg) = delimited(ws(char('(')), |i| sum(i, depth + 1), ws(char(')')))(input)?;
+    Ok((
+        input,
+        ArithExpr::Call {
+            function,
+            arg: Box::new(arg),
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn col(name: &str) -> ArithExpr {
+        ArithExpr::Column(name.to_string())
+    }
+
+    fn binary(op: ArithOp, left: ArithExpr, right: ArithExpr) -> ArithExpr {
+        ArithExpr::Binary {
+            op,
+            left: Box::new(left),
+            right: Box::new(right),
+        }
+    }
+
+    #[test]
+    fn parses_operands() {
+        assert_eq!(arith_expr("revenue").unwrap(), ("", col("revenue")));
+        assert_eq!(arith_expr("2.5").unwrap(), ("", ArithExpr::Number(2.5)));
+        assert_eq!(arith_expr("`Cost ($)`").unwrap().1, col("Cost ($)"));
+        // Identifiers that happen to start like a float are columns
+        assert_eq!(arith_expr("info").unwrap().1, col("info"));
+        assert_eq!(arith_expr("-3").unwrap().1, ArithExpr::Number(-3.0));
+        assert_eq!(
+            arith_expr("-x").unwrap().1,
+            ArithExpr::Neg(Box::new(col("x")))
+        );
+    }
+
+    #[test]
+    fn multiplication_binds_tighter() {
+        let (rest, expr) = arith_expr("a + b * 2").unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(
+            expr,
+            binary(
+                ArithOp::Add,
+                col("a"),
+                binary(ArithOp::Mul, col("b"), ArithExpr::Number(2.0))
+            )
+        );
+
+        let (_, expr) = arith_expr("(a + b) * 2").unwrap();
+        assert_eq!(
+            expr,
+            binary(
+                ArithOp::Mul,
+                binary(ArithOp::Add, col("a"), col("b")),
+                ArithExpr::Number(2.0)
+            )
+        );
+    }
+
+    #[test]
+    fn operators_are_left_associative() {
+        let (_, expr) = arith_expr("a - b - c").unwrap();
+        assert_eq!(
+            expr,
+            binary(
+                ArithOp::Sub,
+                binary(ArithOp::Sub, col("a"), col("b")),
+                col("c")
+            )
+        );
+
+        let (_, expr) = arith_expr("a/b/2").unwrap();
+        assert_eq!(
+            expr,
+            binary(
+                ArithOp::Div,
+                binary(ArithOp::Div, col("a"), col("b")),
+                ArithExpr::Number(2.0)
+            )
+        );
+    }
+
+    #[test]
+    fn parses_function_calls() {
+        let (_, expr) = arith_expr("log10(pop) * 2").unwrap();
+        assert_eq!(
+            expr,
+            binary(
+                ArithOp::Mul,
+                ArithExpr::Call {
+                    function: ArithFunction::Log10,
+                    arg: Box::new(col("pop")),
+                },
+                ArithExpr::Number(2.0)
+            )
+        );
+
+        let (_, expr) = arith_expr("abs(high - low)").unwrap();
+        assert!(matches!(
+            expr,
+            ArithExpr::Call {
+                function: ArithFunction::Abs,
+                ..
+            }
+        ));
+        assert_eq!(expr.columns(), vec!["high", "low"]);
+
+        // Without parentheses a function name is just a column
+        assert_eq!(
+            arith_expr("logins + 1").unwrap().1.columns(),
+            vec!["logins"]
+        );
+        assert_eq!(arith_expr("exp").unwrap().1, col("exp"));
+    }
+
+    #[test]
+    fn rejects_incomplete_expressions() {
+        assert!(arith_expr("").is_err());
+        assert!(arith_expr("* 2").is_err());
+        assert!(arith_expr("(a + b").is_err());
+        // Trailing input is left unparsed for the caller to reject
+        let (rest, _) = arith_expr("a +").unwrap();
+        assert_eq!(rest.trim(), "+");
+        let (rest, _) = arith_expr("log()").unwrap();
+        assert_eq!(rest, "()");
+    }
+
+    #[test]
+    fn rejects_runaway_nesting() {
+        let parens = |depth| format!("{}a{}", "(".repeat(depth), ")".repeat(depth));
+        assert!(arith_expr(&parens(MAX_DEPTH)).is_ok());
+        assert!(arith_expr(&parens(100_000)).is_err());
+        assert!(arith_expr(&"abs(".repeat(100_000)).is_err());
+        assert!(arith_expr(&format!("{}a", "-".repeat(100_000))).is_err());
+    }
+}
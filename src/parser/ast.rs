@@ -1,15 +1,24 @@
 // Abstract Syntax Tree for Grammar of Graphics DSL
 
-#[derive(Debug, Clone, PartialEq)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum CoordSystem {
     Cartesian,
     Flip,
+    /// Pixels per y unit over pixels per x unit; 1 draws both axes to the same scale
+    Fixed {
+        ratio: f64,
+    },
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum LegendPosition {
     UpperLeft,
     UpperMiddle,
+    #[default]
     UpperRight,
     MiddleLeft,
     MiddleMiddle,
@@ -20,16 +29,11 @@ pub enum LegendPosition {
     None,
 }
 
-impl Default for LegendPosition {
-    fn default() -> Self {
-        LegendPosition::UpperRight
-    }
-}
-
 // === Theme Element Primitives ===
 
 /// Line element styling (for axis lines, grid lines, tick marks)
-#[derive(Debug, Clone, PartialEq, Default)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ElementLine {
     pub color: Option<String>,
     pub width: Option<f64>,
@@ -37,7 +41,8 @@ pub struct ElementLine {
 }
 
 /// Rectangle element styling (for backgrounds, borders)
-#[derive(Debug, Clone, PartialEq, Default)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ElementRect {
     pub fill: Option<String>,
     pub color: Option<String>, // Border color
@@ -45,7 +50,8 @@ pub struct ElementRect {
 }
 
 /// Text element styling (for labels, titles)
-#[derive(Debug, Clone, PartialEq, Default)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ElementText {
     pub family: Option<String>,
     pub color: Option<String>,
@@ -57,25 +63,22 @@ pub struct ElementText {
 }
 
 /// Theme element wrapper - can be a specific element type, blank, or inherit from parent
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ThemeElement {
     Line(ElementLine),
     Rect(ElementRect),
     Text(ElementText),
-    Blank,   // Remove this element entirely
+    Blank, // Remove this element entirely
+    #[default]
     Inherit, // Inherit from parent element in hierarchy
 }
 
-impl Default for ThemeElement {
-    fn default() -> Self {
-        ThemeElement::Inherit
-    }
-}
-
 // === Hierarchical Theme ===
 
 /// Complete theme specification with hierarchical element inheritance
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Theme {
     // Root elements (base defaults for each type)
     pub line: ThemeElement,
@@ -102,6 +105,11 @@ pub struct Theme {
     pub legend_text: ThemeElement,
     pub legend_margin: Option<f64>,
     pub legend_key_size: Option<f64>,
+
+    /// Blank space around each panel in pixels
+    pub margin: Option<f64>,
+    /// Draw major grid lines between category slots instead of through them
+    pub grid_separators: Option<bool>,
 }
 
 impl Default for Theme {
@@ -123,30 +131,95 @@ impl Default for Theme {
             legend_text: ThemeElement::Inherit,
             legend_margin: None,
             legend_key_size: None,
+            margin: None,
+            grid_separators: None,
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ScaleType {
     Linear,
     Log10,
     Sqrt,
     Reverse,
+    #[serde(rename = "datetime")]
     DateTime,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DateTimeScaleOptions {
     pub interval: Option<String>,
     pub format: Option<String>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// Order of categories on a categorical x axis
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CategoryOrder {
+    /// First appearance in the data
+    Appearance,
+    /// Alphabetical
+    Alpha,
+    /// Descending by each category's total y
+    Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct AxisScale {
     pub scale_type: ScaleType,
     pub limits: Option<(f64, f64)>, // Custom min/max
     pub datetime: Option<DateTimeScaleOptions>,
+    /// Category order from `scale_x_discrete(order: ...)`. When unset, categories
+    /// keep first-appearance order unless they are all numbers, which sort numerically.
+    pub order: Option<CategoryOrder>,
+    /// Tick label options from `scale_x(...)` / `scale_y(...)`
+    pub labels: AxisLabels,
+}
+
+/// Tick, padding, and direction options: `scale_x(rotate: 90, format: "comma",
+/// max_label_len: 12, ticks: 5)`, `scale_y(expand: 0.05, reverse: true, breaks: 0, 50, 100)`.
+/// They ride along with whatever scale type the axis uses.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AxisLabels {
+    /// Tick label angle in degrees (x axis only); overrides `axis_text` angle
+    pub rotate: Option<f64>,
+    /// Numeric label format: "comma", "percent", "si", or a printf pattern like "%.2f"
+    pub format: Option<String>,
+    /// Categorical labels longer than this many characters end in an ellipsis
+    pub max_label_len: Option<usize>,
+    /// Pad a continuous axis by this fraction of the data span on each side, and
+    /// end it there instead of at nice tick boundaries (`0` fits the data exactly)
+    pub expand: Option<f64>,
+    /// Read the axis columns as "percent" ("45%" or "45" is 0.45) or "currency"
+    /// ("$1,299.00" or "1299" is 1299) even when some cells lack the unit
+    pub parse: Option<String>,
+    /// Run the axis from its largest value to its smallest; `false` undoes a
+    /// `scale_x_reverse()`/`scale_y_reverse()`
+    pub reverse: Option<bool>,
+    /// About how many ticks a continuous axis gets (8 by default)
+    pub ticks: Option<usize>,
+    /// Ticks at exactly these values, in data units; ones outside the axis are dropped
+    pub breaks: Option<Vec<f64>>,
+}
+
+impl AxisLabels {
+    /// Options set here win; unset ones fall back to `earlier`
+    pub fn or(self, earlier: AxisLabels) -> AxisLabels {
+        AxisLabels {
+            rotate: self.rotate.or(earlier.rotate),
+            format: self.format.or(earlier.format),
+            max_label_len: self.max_label_len.or(earlier.max_label_len),
+            expand: self.expand.or(earlier.expand),
+            parse: self.parse.or(earlier.parse),
+            reverse: self.reverse.or(earlier.reverse),
+            ticks: self.ticks.or(earlier.ticks),
+            breaks: self.breaks.or(earlier.breaks),
+        }
+    }
 }
 
 impl Default for AxisScale {
@@ -155,65 +228,265 @@ impl Default for AxisScale {
             scale_type: ScaleType::Linear,
             limits: None,
             datetime: None,
+            order: None,
+            labels: AxisLabels::default(),
+        }
+    }
+}
+
+/// Color scale settings: gradient endpoints used when `color` maps a numeric column,
+/// and the named palette used for grouped (categorical) color assignment
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ColorScale {
+    pub low: String,
+    pub high: String,
+    pub palette: Option<String>,
+    /// Order of the categorical color groups (`"appearance"` or `"alpha"`, the
+    /// default), which sets palette, stack, dodge, and legend order
+    pub order: Option<CategoryOrder>,
+    /// Color groups listed here come first, in this order; the rest follow `order`
+    pub levels: Vec<String>,
+}
+
+impl Default for ColorScale {
+    fn default() -> Self {
+        ColorScale {
+            low: "#132b43".to_string(),
+            high: "#56b1f7".to_string(),
+            palette: None,
+            order: None,
+            levels: Vec::new(),
         }
     }
 }
 
+/// Fixed colors for categorical color groups.
+/// Named entries (`"North" = "blue"`) match a group value exactly; positional entries
+/// are assigned in order to the remaining groups. Groups left over fall back to the
+/// default palette.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ManualColorScale {
+    pub named: Vec<(String, String)>,
+    pub ordered: Vec<String>,
+}
+
 /// Complete plot specification
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PlotSpec {
     pub aesthetics: Option<Aesthetics>,
     pub layers: Vec<Layer>,
     pub labels: Option<Labels>,
     pub facet: Option<Facet>,
+    /// Two-variable facet grid; mutually exclusive with `facet`
+    #[serde(default)]
+    pub facet_grid: Option<FacetGrid>,
     pub coord: Option<CoordSystem>,
     pub theme: Option<Theme>,
     pub x_scale: Option<AxisScale>,
     pub y_scale: Option<AxisScale>,
+    pub color_scale: Option<ColorScale>,
+    pub color_manual: Option<ManualColorScale>,
+    /// Data stages (`filter()`, `summarize()`, `sort()`, ...), applied to the rows in pipeline order
+    #[serde(default)]
+    pub stages: Vec<DataStage>,
+    /// `annotate()` notes, drawn over every layer in pipeline order
+    #[serde(default)]
+    pub annotations: Vec<Annotation>,
+    /// `shade()` regions, drawn under the grid and every layer in pipeline order
+    #[serde(default)]
+    pub shades: Vec<Shade>,
 }
 
 impl PlotSpec {
-    /// Returns true if any layer in the plot requires a categorical x-axis
+    /// Returns true if any layer's geometry requires a categorical x-axis
+    /// (see `Layer::requires_categorical_x`)
     pub fn requires_categorical_x(&self) -> bool {
         self.layers.iter().any(|l| l.requires_categorical_x())
     }
+
+    /// Deserialize a spec from JSON (the format written by `to_json`)
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        let spec: PlotSpec = serde_json::from_str(json)
+            .map_err(|e| anyhow::anyhow!("Invalid JSON plot spec: {}", e))?;
+        if spec.layers.is_empty() {
+            anyhow::bail!("JSON plot spec must contain at least one layer");
+        }
+        Ok(spec)
+    }
+
+    /// Serialize the spec as pretty-printed JSON
+    pub fn to_json(&self) -> anyhow::Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+/// A column as written in the DSL: a header name, or a zero-based position
+/// (`aes(x: 0, y: 1)`). `resolve` replaces positions with the names they point at,
+/// so everything after it sees names only.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ColumnRef {
+    Name(String),
+    Index(usize),
+}
+
+impl ColumnRef {
+    /// The header name, or None for a position not yet resolved
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            ColumnRef::Name(name) => Some(name),
+            ColumnRef::Index(_) => None,
+        }
+    }
+
+    /// Replace a position with the header it points at. Out-of-range positions are
+    /// an error naming the column count.
+    pub fn resolve_index(&mut self, headers: &[String]) -> anyhow::Result<()> {
+        let ColumnRef::Index(index) = *self else {
+            return Ok(());
+        };
+        let Some(header) = headers.get(index) else {
+            let quoted = if headers.iter().any(|h| *h == index.to_string()) {
+                format!("; quote it as `{}` for the column named {}", index, index)
+            } else {
+                String::new()
+            };
+            anyhow::bail!(
+                "Column index {} out of bounds (available columns: {}, numbered from 0){}",
+                index,
+                headers.len(),
+                quoted
+            );
+        };
+        *self = ColumnRef::Name(header.clone());
+        Ok(())
+    }
+}
+
+/// Names print as written; positions print as their number
+impl std::fmt::Display for ColumnRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ColumnRef::Name(name) => f.write_str(name),
+            ColumnRef::Index(index) => write!(f, "{}", index),
+        }
+    }
+}
+
+impl Default for ColumnRef {
+    fn default() -> Self {
+        ColumnRef::Name(String::new())
+    }
+}
+
+impl From<&str> for ColumnRef {
+    fn from(name: &str) -> Self {
+        ColumnRef::Name(name.to_string())
+    }
+}
+
+impl From<String> for ColumnRef {
+    fn from(name: String) -> Self {
+        ColumnRef::Name(name)
+    }
+}
+
+impl PartialEq<str> for ColumnRef {
+    fn eq(&self, other: &str) -> bool {
+        self.name() == Some(other)
+    }
+}
+
+impl PartialEq<&str> for ColumnRef {
+    fn eq(&self, other: &&str) -> bool {
+        self.name() == Some(*other)
+    }
 }
 
 /// Global aesthetic mappings (data columns → visual properties)
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Aesthetics {
-    /// Column name for x-axis
-    pub x: String,
-    /// Column name for y-axis
-    pub y: Option<String>,
-    /// Optional column name for color grouping
-    pub color: Option<String>,
-    /// Optional column name for size grouping
-    pub size: Option<String>,
-    /// Optional column name for shape grouping
-    pub shape: Option<String>,
-    /// Optional column name for alpha grouping
-    pub alpha: Option<String>,
-    /// Optional column name for ymin
-    pub ymin: Option<String>,
-    /// Optional column name for ymax
-    pub ymax: Option<String>,
-    /// Optional column name for fill (heatmap value)
-    pub fill: Option<String>,
+    /// Column for x-axis
+    pub x: ColumnRef,
+    /// Column for y-axis
+    pub y: Option<ColumnRef>,
+    /// Columns listed as `y: [a, b, c]`, each drawn as its own color group
+    /// (`resolve::expand_y_columns`); `y` is None when set
+    #[serde(default)]
+    pub y_columns: Vec<ColumnRef>,
+    /// Optional column for color grouping
+    pub color: Option<ColumnRef>,
+    /// Optional column for size grouping
+    pub size: Option<ColumnRef>,
+    /// Optional column for shape grouping
+    pub shape: Option<ColumnRef>,
+    /// Optional column for alpha grouping
+    pub alpha: Option<ColumnRef>,
+    /// Optional column for ymin
+    pub ymin: Option<ColumnRef>,
+    /// Optional column for ymax
+    pub ymax: Option<ColumnRef>,
+    /// Optional column for fill (heatmap value)
+    pub fill: Option<ColumnRef>,
+    /// Optional column for linetype grouping (line layers)
+    #[serde(default)]
+    pub linetype: Option<ColumnRef>,
+    /// Optional column that splits rows into groups without styling them
+    #[serde(default)]
+    pub group: Option<ColumnRef>,
+}
+
+impl Aesthetics {
+    /// Every column reference, for `ColumnRef::resolve_index`
+    pub fn columns_mut(&mut self) -> impl Iterator<Item = &mut ColumnRef> {
+        std::iter::once(&mut self.x)
+            .chain(self.y_columns.iter_mut())
+            .chain(
+                [
+                    &mut self.y,
+                    &mut self.color,
+                    &mut self.size,
+                    &mut self.shape,
+                    &mut self.alpha,
+                    &mut self.ymin,
+                    &mut self.ymax,
+                    &mut self.fill,
+                    &mut self.linetype,
+                    &mut self.group,
+                ]
+                .into_iter()
+                .flatten(),
+            )
+    }
 }
 
 /// Represents either a fixed literal value or a data-driven column mapping
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum AestheticValue<T> {
     /// Fixed literal value (e.g., line(color: "red"))
     Fixed(T),
-    /// Column name for data-driven mapping (e.g., aes(color: region))
-    Mapped(String),
+    /// Column for data-driven mapping (e.g., aes(color: region))
+    Mapped(ColumnRef),
+}
+
+impl<T> AestheticValue<T> {
+    /// The mapped column, if any
+    pub fn column_mut(value: &mut Option<AestheticValue<T>>) -> Option<&mut ColumnRef> {
+        match value {
+            Some(AestheticValue::Mapped(col)) => Some(col),
+            _ => None,
+        }
+    }
 }
 
 /// Statistical transformation to apply
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Stat {
+    #[default]
     Identity,
     Bin {
         bins: usize,
@@ -234,16 +507,31 @@ pub enum Stat {
     Heatmap {
         bins: Option<usize>,
     },
+    /// Combine the y values of rows sharing an x value
+    Summary {
+        fun: Aggregate,
+    },
+    /// Trailing mean of the last `window` points along x; a point needs at least
+    /// `min_periods` values in its window (default: the full window)
+    RollingMean {
+        window: usize,
+        min_periods: Option<usize>,
+    },
 }
 
-impl Default for Stat {
-    fn default() -> Self {
-        Stat::Identity
-    }
+/// How `bar(stat: ...)` combines the y values of rows with the same x
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Aggregate {
+    Sum,
+    Mean,
+    Min,
+    Max,
 }
 
 /// Individual visualization layer
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Layer {
     Line(LineLayer),
     Point(PointLayer),
@@ -251,25 +539,68 @@ pub enum Layer {
     Area(AreaLayer),
     Rug(RugLayer),
     Spike(SpikeLayer),
+    #[serde(rename = "linerange")]
     LineRange(LineRangeLayer),
+    #[serde(rename = "errorbar")]
     ErrorBar(ErrorBarLayer),
+    #[serde(rename = "pointrange")]
     PointRange(PointRangeLayer),
+    #[serde(rename = "crossbar")]
     CrossBar(CrossBarLayer),
     Ribbon(RibbonLayer),
     Boxplot(BoxplotLayer),
     Violin(ViolinLayer),
     Density(DensityLayer),
     Heatmap(HeatmapLayer),
+    #[serde(rename = "hline")]
     HLine(HLineLayer),
+    #[serde(rename = "vline")]
     VLine(VLineLayer),
+    #[serde(rename = "abline")]
     AbLine(AbLineLayer),
     Segment(SegmentLayer),
+    Text(TextLayer),
+    Pie(PieLayer),
 }
 
 impl Layer {
-    /// Returns true if this layer type requires a categorical x-axis (e.g., Bar charts)
+    /// Returns true if this layer's geometry places x as categories whatever the data:
+    /// bars (unless `x_continuous`), boxplots, violins, and pies. Other layers are
+    /// categorical only for text x values; `analysis::x_axis_kind` decides both.
     pub fn requires_categorical_x(&self) -> bool {
-        matches!(self, Layer::Bar(_) | Layer::Boxplot(_) | Layer::Violin(_))
+        match self {
+            Layer::Bar(bar) => !bar.x_continuous,
+            Layer::Boxplot(_) | Layer::Violin(_) | Layer::Pie(_) => true,
+            _ => false,
+        }
+    }
+
+    /// The layer's DSL function name. Shorthands such as `histogram()`, `step()`, and
+    /// `tile()` build one of these layers and report its name.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Layer::Line(_) => "line",
+            Layer::Point(_) => "point",
+            Layer::Bar(_) => "bar",
+            Layer::Area(_) => "area",
+            Layer::Rug(_) => "rug",
+            Layer::Spike(_) => "spike",
+            Layer::LineRange(_) => "linerange",
+            Layer::ErrorBar(_) => "errorbar",
+            Layer::PointRange(_) => "pointrange",
+            Layer::CrossBar(_) => "crossbar",
+            Layer::Ribbon(_) => "ribbon",
+            Layer::Boxplot(_) => "boxplot",
+            Layer::Violin(_) => "violin",
+            Layer::Density(_) => "density",
+            Layer::Heatmap(_) => "heatmap",
+            Layer::HLine(_) => "hline",
+            Layer::VLine(_) => "vline",
+            Layer::AbLine(_) => "abline",
+            Layer::Segment(_) => "segment",
+            Layer::Text(_) => "text",
+            Layer::Pie(_) => "pie",
+        }
     }
 
     pub fn stat(&self) -> &Stat {
@@ -293,62 +624,705 @@ impl Layer {
             Layer::VLine(v) => &v.stat,
             Layer::AbLine(a) => &a.stat,
             Layer::Segment(s) => &s.stat,
+            Layer::Text(t) => &t.stat,
+            Layer::Pie(p) => &p.stat,
+        }
+    }
+
+    /// Fixed-position annotations (hline, vline, abline, and a segment given as
+    /// numbers) that draw without reading any rows
+    pub fn is_reference(&self) -> bool {
+        match self {
+            Layer::HLine(_) | Layer::VLine(_) | Layer::AbLine(_) => true,
+            Layer::Segment(s) => !s.is_mapped(),
+            _ => false,
         }
     }
+
+    /// The layer's `filter:` row predicate (reference lines have none)
+    pub fn filter(&self) -> Option<&FilterExpr> {
+        match self {
+            Layer::Line(l) => l.filter.as_ref(),
+            Layer::Point(p) => p.filter.as_ref(),
+            Layer::Bar(b) => b.filter.as_ref(),
+            Layer::Area(a) => a.filter.as_ref(),
+            Layer::Rug(r) => r.filter.as_ref(),
+            Layer::Spike(s) => s.filter.as_ref(),
+            Layer::LineRange(l) => l.filter.as_ref(),
+            Layer::ErrorBar(e) => e.filter.as_ref(),
+            Layer::PointRange(p) => p.filter.as_ref(),
+            Layer::CrossBar(c) => c.filter.as_ref(),
+            Layer::Ribbon(r) => r.filter.as_ref(),
+            Layer::Boxplot(b) => b.filter.as_ref(),
+            Layer::Violin(v) => v.filter.as_ref(),
+            Layer::Density(d) => d.filter.as_ref(),
+            Layer::Heatmap(h) => h.filter.as_ref(),
+            Layer::Text(t) => t.filter.as_ref(),
+            Layer::Pie(p) => p.filter.as_ref(),
+            Layer::HLine(_) | Layer::VLine(_) | Layer::AbLine(_) | Layer::Segment(_) => None,
+        }
+    }
+
+    /// The layer's `filter:` slot, for adding to its predicate (reference lines have none)
+    pub fn filter_mut(&mut self) -> Option<&mut Option<FilterExpr>> {
+        match self {
+            Layer::Line(l) => Some(&mut l.filter),
+            Layer::Point(p) => Some(&mut p.filter),
+            Layer::Bar(b) => Some(&mut b.filter),
+            Layer::Area(a) => Some(&mut a.filter),
+            Layer::Rug(r) => Some(&mut r.filter),
+            Layer::Spike(s) => Some(&mut s.filter),
+            Layer::LineRange(l) => Some(&mut l.filter),
+            Layer::ErrorBar(e) => Some(&mut e.filter),
+            Layer::PointRange(p) => Some(&mut p.filter),
+            Layer::CrossBar(c) => Some(&mut c.filter),
+            Layer::Ribbon(r) => Some(&mut r.filter),
+            Layer::Boxplot(b) => Some(&mut b.filter),
+            Layer::Violin(v) => Some(&mut v.filter),
+            Layer::Density(d) => Some(&mut d.filter),
+            Layer::Heatmap(h) => Some(&mut h.filter),
+            Layer::Text(t) => Some(&mut t.filter),
+            Layer::Pie(p) => Some(&mut p.filter),
+            Layer::HLine(_) | Layer::VLine(_) | Layer::AbLine(_) | Layer::Segment(_) => None,
+        }
+    }
+
+    /// The table named by the layer's `data:` argument; None reads the plot's data
+    pub fn data(&self) -> Option<&str> {
+        let data = match self {
+            Layer::Line(l) => &l.data,
+            Layer::Point(p) => &p.data,
+            Layer::Bar(b) => &b.data,
+            Layer::Area(a) => &a.data,
+            Layer::Rug(r) => &r.data,
+            Layer::Spike(s) => &s.data,
+            Layer::LineRange(l) => &l.data,
+            Layer::ErrorBar(e) => &e.data,
+            Layer::PointRange(p) => &p.data,
+            Layer::CrossBar(c) => &c.data,
+            Layer::Ribbon(r) => &r.data,
+            Layer::Boxplot(b) => &b.data,
+            Layer::Violin(v) => &v.data,
+            Layer::Density(d) => &d.data,
+            Layer::Heatmap(h) => &h.data,
+            Layer::Text(t) => &t.data,
+            Layer::Pie(p) => &p.data,
+            Layer::HLine(_) | Layer::VLine(_) | Layer::AbLine(_) | Layer::Segment(_) => {
+                return None
+            }
+        };
+        data.as_deref()
+    }
+
+    /// Every column reference in the layer's own arguments (not its `filter:`), for
+    /// `ColumnRef::resolve_index`
+    pub fn columns_mut(&mut self) -> Vec<&mut ColumnRef> {
+        let refs: Vec<Option<&mut ColumnRef>> = match self {
+            Layer::Line(l) => vec![
+                l.x.as_mut(),
+                l.y.as_mut(),
+                AestheticValue::column_mut(&mut l.color),
+                AestheticValue::column_mut(&mut l.width),
+                AestheticValue::column_mut(&mut l.alpha),
+                AestheticValue::column_mut(&mut l.linetype),
+                l.group.as_mut(),
+            ],
+            Layer::Point(p) => vec![
+                p.x.as_mut(),
+                p.y.as_mut(),
+                AestheticValue::column_mut(&mut p.color),
+                AestheticValue::column_mut(&mut p.size),
+                AestheticValue::column_mut(&mut p.shape),
+                AestheticValue::column_mut(&mut p.alpha),
+                p.fade_by.as_mut(),
+            ],
+            Layer::Bar(b) => vec![
+                b.x.as_mut(),
+                b.y.as_mut(),
+                AestheticValue::column_mut(&mut b.color),
+                AestheticValue::column_mut(&mut b.alpha),
+                AestheticValue::column_mut(&mut b.width),
+            ],
+            Layer::Area(a) => vec![
+                a.x.as_mut(),
+                a.y.as_mut(),
+                AestheticValue::column_mut(&mut a.color),
+                AestheticValue::column_mut(&mut a.alpha),
+            ],
+            Layer::Rug(r) => vec![
+                r.x.as_mut(),
+                r.y.as_mut(),
+                AestheticValue::column_mut(&mut r.color),
+                AestheticValue::column_mut(&mut r.width),
+                AestheticValue::column_mut(&mut r.alpha),
+            ],
+            Layer::Spike(s) => vec![
+                s.x.as_mut(),
+                s.y.as_mut(),
+                AestheticValue::column_mut(&mut s.color),
+                AestheticValue::column_mut(&mut s.width),
+                AestheticValue::column_mut(&mut s.alpha),
+            ],
+            Layer::LineRange(l) => vec![
+                l.x.as_mut(),
+                l.ymin.as_mut(),
+                l.ymax.as_mut(),
+                AestheticValue::column_mut(&mut l.color),
+                AestheticValue::column_mut(&mut l.width),
+                AestheticValue::column_mut(&mut l.alpha),
+            ],
+            Layer::ErrorBar(e) => vec![
+                e.x.as_mut(),
+                e.ymin.as_mut(),
+                e.ymax.as_mut(),
+                AestheticValue::column_mut(&mut e.color),
+                AestheticValue::column_mut(&mut e.line_width),
+                AestheticValue::column_mut(&mut e.alpha),
+            ],
+            Layer::PointRange(p) => vec![
+                p.x.as_mut(),
+                p.y.as_mut(),
+                p.ymin.as_mut(),
+                p.ymax.as_mut(),
+                AestheticValue::column_mut(&mut p.color),
+                AestheticValue::column_mut(&mut p.width),
+                AestheticValue::column_mut(&mut p.alpha),
+                AestheticValue::column_mut(&mut p.size),
+                AestheticValue::column_mut(&mut p.shape),
+            ],
+            Layer::CrossBar(c) => vec![
+                c.x.as_mut(),
+                c.y.as_mut(),
+                c.ymin.as_mut(),
+                c.ymax.as_mut(),
+                AestheticValue::column_mut(&mut c.color),
+                AestheticValue::column_mut(&mut c.alpha),
+                AestheticValue::column_mut(&mut c.line_width),
+            ],
+            Layer::Ribbon(r) => vec![
+                r.x.as_mut(),
+                r.ymin.as_mut(),
+                r.ymax.as_mut(),
+                AestheticValue::column_mut(&mut r.color),
+                AestheticValue::column_mut(&mut r.alpha),
+            ],
+            Layer::Boxplot(b) => vec![
+                b.x.as_mut(),
+                b.y.as_mut(),
+                AestheticValue::column_mut(&mut b.color),
+                AestheticValue::column_mut(&mut b.fill),
+                AestheticValue::column_mut(&mut b.alpha),
+                AestheticValue::column_mut(&mut b.width),
+            ],
+            Layer::Violin(v) => vec![
+                v.x.as_mut(),
+                v.y.as_mut(),
+                AestheticValue::column_mut(&mut v.color),
+                AestheticValue::column_mut(&mut v.alpha),
+                AestheticValue::column_mut(&mut v.width),
+            ],
+            Layer::Density(d) => vec![
+                d.x.as_mut(),
+                AestheticValue::column_mut(&mut d.color),
+                AestheticValue::column_mut(&mut d.alpha),
+            ],
+            Layer::Heatmap(h) => vec![
+                h.x.as_mut(),
+                h.y.as_mut(),
+                AestheticValue::column_mut(&mut h.alpha),
+                h.fill.as_mut(),
+            ],
+            Layer::Text(t) => vec![
+                t.x.as_mut(),
+                t.y.as_mut(),
+                Some(&mut t.label),
+                AestheticValue::column_mut(&mut t.color),
+                AestheticValue::column_mut(&mut t.alpha),
+            ],
+            Layer::Pie(p) => vec![p.x.as_mut(), p.y.as_mut()],
+            Layer::Segment(s) => vec![
+                AestheticValue::column_mut(&mut s.x),
+                AestheticValue::column_mut(&mut s.y),
+                AestheticValue::column_mut(&mut s.xend),
+                AestheticValue::column_mut(&mut s.yend),
+                AestheticValue::column_mut(&mut s.color),
+            ],
+            Layer::HLine(_) | Layer::VLine(_) | Layer::AbLine(_) => Vec::new(),
+        };
+        refs.into_iter().flatten().collect()
+    }
+}
+
+/// Row predicate for `filter:`: column comparisons joined with `&&` / `||`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterExpr {
+    Compare {
+        column: String,
+        op: CompareOp,
+        value: FilterValue,
+    },
+    And(Box<FilterExpr>, Box<FilterExpr>),
+    Or(Box<FilterExpr>, Box<FilterExpr>),
+}
+
+/// Comparison operator in a filter expression
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompareOp {
+    Eq, // ==
+    Ne, // !=
+    Lt, // <
+    Le, // <=
+    Gt, // >
+    Ge, // >=
+}
+
+/// Literal a column is compared against: numbers compare numerically, strings as text
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum FilterValue {
+    Number(f64),
+    Text(String),
+}
+
+impl FilterExpr {
+    /// Every column the expression reads, in order of appearance
+    pub fn columns(&self) -> Vec<&str> {
+        match self {
+            FilterExpr::Compare { column, .. } => vec![column.as_str()],
+            FilterExpr::And(a, b) | FilterExpr::Or(a, b) => {
+                let mut cols = a.columns();
+                cols.extend(b.columns());
+                cols
+            }
+        }
+    }
+}
+
+/// A pipeline stage that reshapes the data before any layer sees it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DataStage {
+    /// `filter(expr)`: keep the rows matching the expression
+    Filter(FilterExpr),
+    /// `summarize(by: ..., y: fn(col))`: one row per group
+    Summarize(Summarize),
+    /// `sort(by: ..., desc: ...)`: stable reorder of the rows
+    Sort(Vec<SortKey>),
+    /// `head(n: ...)`: the first n rows
+    Head(usize),
+    /// `sample(n: ..., seed: ...)`: n rows drawn without replacement
+    Sample(Sample),
+    /// `mutate(name: expr, ...)`: derived columns, computed row by row
+    Mutate(Vec<Mutation>),
+    /// `pivot_longer(cols: ..., names_to: ..., values_to: ...)`: wide columns to key/value rows
+    PivotLonger(PivotLonger),
+}
+
+impl DataStage {
+    /// Every input column the stage reads
+    pub fn columns(&self) -> Vec<&str> {
+        match self {
+            DataStage::Filter(expr) => expr.columns(),
+            DataStage::Summarize(s) => {
+                s.by.iter()
+                    .chain(s.column.as_ref())
+                    .map(String::as_str)
+                    .collect()
+            }
+            DataStage::Sort(keys) => keys.iter().map(|k| k.column.as_str()).collect(),
+            DataStage::Head(_) | DataStage::Sample(_) => Vec::new(),
+            DataStage::Mutate(mutations) => {
+                // Columns defined earlier in the same mutate() aren't inputs
+                let mut cols = Vec::new();
+                for (i, m) in mutations.iter().enumerate() {
+                    let defined = &mutations[..i];
+                    cols.extend(
+                        m.expr.columns().into_iter().filter(|col| {
+                            !defined.iter().any(|d| d.name.eq_ignore_ascii_case(col))
+                        }),
+                    );
+                }
+                cols
+            }
+            DataStage::PivotLonger(p) => p.cols.iter().map(String::as_str).collect(),
+        }
+    }
+
+    /// The stage's DSL function name
+    pub fn name(&self) -> &'static str {
+        match self {
+            DataStage::Filter(_) => "filter",
+            DataStage::Summarize(_) => "summarize",
+            DataStage::Sort(_) => "sort",
+            DataStage::Head(_) => "head",
+            DataStage::Sample(_) => "sample",
+            DataStage::Mutate(_) => "mutate",
+            DataStage::PivotLonger(_) => "pivot_longer",
+        }
+    }
+
+    /// Headers of the stage's output, given its input headers
+    pub fn output_headers(&self, headers: &[String]) -> Vec<String> {
+        match self {
+            DataStage::Filter(_)
+            | DataStage::Sort(_)
+            | DataStage::Head(_)
+            | DataStage::Sample(_) => headers.to_vec(),
+            DataStage::Summarize(s) => {
+                let mut out: Vec<String> =
+                    s.by.iter()
+                        .map(|col| {
+                            headers
+                                .iter()
+                                .find(|h| h.eq_ignore_ascii_case(col))
+                                .unwrap_or(col)
+                                .clone()
+                        })
+                        .collect();
+                out.push(s.output_name());
+                out
+            }
+            DataStage::Mutate(mutations) => {
+                // A name that already exists replaces that column in place
+                let mut out = headers.to_vec();
+                for m in mutations {
+                    if !out.iter().any(|h| h.eq_ignore_ascii_case(&m.name)) {
+                        out.push(m.name.clone());
+                    }
+                }
+                out
+            }
+            DataStage::PivotLonger(p) => {
+                let mut out: Vec<String> = headers
+                    .iter()
+                    .filter(|h| p.keep || !p.pivots(h))
+                    .cloned()
+                    .collect();
+                out.push(p.names_to.clone());
+                out.push(p.values_to.clone());
+                out
+            }
+        }
+    }
+}
+
+/// Wide-to-long reshape for `pivot_longer()`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PivotLonger {
+    /// Columns melted into rows; the others repeat on every output row
+    pub cols: Vec<String>,
+    /// Column that receives each melted column's header
+    pub names_to: String,
+    /// Column that receives each melted cell
+    pub values_to: String,
+    /// Keep the melted columns on every output row as well; set by the `aes(y: [...])`
+    /// pivot so other layers can still map them
+    #[serde(skip)]
+    pub keep: bool,
+}
+
+impl Default for PivotLonger {
+    fn default() -> Self {
+        PivotLonger {
+            cols: Vec::new(),
+            names_to: "name".to_string(),
+            values_to: "value".to_string(),
+            keep: false,
+        }
+    }
+}
+
+impl PivotLonger {
+    /// Whether `header` is one of the melted columns
+    pub fn pivots(&self, header: &str) -> bool {
+        self.cols.iter().any(|col| col.eq_ignore_ascii_case(header))
+    }
+}
+
+/// One `name: expr` column of `mutate()`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Mutation {
+    pub name: String,
+    pub expr: ArithExpr,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+/// Arithmetic over numeric columns and literals, for `mutate()`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArithExpr {
+    Number(f64),
+    Column(String),
+    Neg(Box<ArithExpr>),
+    Binary {
+        op: ArithOp,
+        left: Box<ArithExpr>,
+        right: Box<ArithExpr>,
+    },
+    Call {
+        function: ArithFunction,
+        arg: Box<ArithExpr>,
+    },
+}
+
+/// Binary operator in an arithmetic expression
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArithOp {
+    Add, // +
+    Sub, // -
+    Mul, // *
+    Div, // /
+}
+
+/// One-argument functions callable in an arithmetic expression
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArithFunction {
+    Abs,
+    Log, // natural log
+    Log10,
+    Exp,
+    Sqrt,
+}
+
+impl ArithExpr {
+    /// Every column the expression reads, in order of appearance
+    pub fn columns(&self) -> Vec<&str> {
+        match self {
+            ArithExpr::Number(_) => Vec::new(),
+            ArithExpr::Column(col) => vec![col.as_str()],
+            ArithExpr::Neg(inner) | ArithExpr::Call { arg: inner, .. } => inner.columns(),
+            ArithExpr::Binary { left, right, .. } => {
+                let mut cols = left.columns();
+                cols.extend(right.columns());
+                cols
+            }
+        }
+    }
+}
+
+/// Row sampling for `sample()`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Sample {
+    pub n: usize,
+    /// Picks which rows are drawn; the same seed always draws the same rows
+    #[serde(default)]
+    pub seed: u64,
+}
+
+/// One `sort()` column; later keys break ties in earlier ones
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SortKey {
+    pub column: String,
+    #[serde(default)]
+    pub desc: bool,
+}
+
+/// Grouped aggregation for `summarize()`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Summarize {
+    /// Grouping columns; empty summarizes every row into one
+    #[serde(default)]
+    pub by: Vec<String>,
+    pub function: SummaryFunction,
+    /// Value column (optional only for `count()`)
+    pub column: Option<String>,
+    /// Output column name from `as:`
+    pub alias: Option<String>,
+}
+
+impl Summarize {
+    /// The aggregated column's name: the `as:` alias, else e.g. `mean_temp` (or `count`)
+    pub fn output_name(&self) -> String {
+        match (&self.alias, &self.column) {
+            (Some(alias), _) => alias.clone(),
+            (None, Some(col)) => format!("{}_{}", self.function.name(), col),
+            (None, None) => self.function.name().to_string(),
+        }
+    }
+}
+
+/// Aggregate functions for `summarize(y: fn(col))`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SummaryFunction {
+    Mean,
+    Sum,
+    Min,
+    Max,
+    Median,
+    Count,
+}
+
+impl SummaryFunction {
+    pub fn name(self) -> &'static str {
+        match self {
+            SummaryFunction::Mean => "mean",
+            SummaryFunction::Sum => "sum",
+            SummaryFunction::Min => "min",
+            SummaryFunction::Max => "max",
+            SummaryFunction::Median => "median",
+            SummaryFunction::Count => "count",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum LineInterpolation {
+    #[default]
     Linear,
     StepHV,
     StepVH,
     StepMid,
 }
 
-impl Default for LineInterpolation {
-    fn default() -> Self {
-        LineInterpolation::Linear
-    }
-}
-
 /// Line geometry layer
-#[derive(Debug, Clone, PartialEq, Default)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct LineLayer {
     pub stat: Stat,
+    pub filter: Option<FilterExpr>, // Row predicate from `filter:`
+    pub data: Option<String>,       // Table from `data:` (None = the plot's data)
     // Aesthetic overrides (None = inherit from global)
-    pub x: Option<String>,
-    pub y: Option<String>,
+    pub x: Option<ColumnRef>,
+    pub y: Option<ColumnRef>,
 
     // Visual properties (can be fixed or data-driven)
     pub color: Option<AestheticValue<String>>,
     pub width: Option<AestheticValue<f64>>,
     pub alpha: Option<AestheticValue<f64>>,
     pub interpolation: LineInterpolation,
-    // Future: linetype (solid, dashed, dotted)
+    // Dash pattern: "solid", "dashed", "dotted", "dotdash" (or a grouping column)
+    pub linetype: Option<AestheticValue<String>>,
+    // Column splitting rows into separate lines that share one style
+    pub group: Option<ColumnRef>,
+    // Position adjustment: "dodge" offsets each group within categorical x slots
+    pub position: LinePosition,
+    /// Skip points that can't change the picture because they share a pixel column
+    /// with their neighbours (`simplify: false` draws every point)
+    pub simplify: bool,
+}
+
+impl Default for LineLayer {
+    fn default() -> Self {
+        LineLayer {
+            stat: Stat::Identity,
+            filter: None,
+            data: None,
+            x: None,
+            y: None,
+            color: None,
+            width: None,
+            alpha: None,
+            interpolation: LineInterpolation::default(),
+            linetype: None,
+            group: None,
+            position: LinePosition::Identity,
+            simplify: true,
+        }
+    }
+}
+
+/// Position adjustment for line layers
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinePosition {
+    #[default]
+    Identity,
+    Dodge, // Groups side by side within each category, like dodged bars
 }
 
 /// Point geometry layer
-#[derive(Debug, Clone, PartialEq, Default)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct PointLayer {
     pub stat: Stat,
+    pub filter: Option<FilterExpr>, // Row predicate from `filter:`
+    pub data: Option<String>,       // Table from `data:` (None = the plot's data)
     // Aesthetic overrides
-    pub x: Option<String>,
-    pub y: Option<String>,
+    pub x: Option<ColumnRef>,
+    pub y: Option<ColumnRef>,
 
     // Visual properties (can be fixed or data-driven)
     pub color: Option<AestheticValue<String>>,
     pub size: Option<AestheticValue<f64>>,
     pub shape: Option<AestheticValue<String>>,
     pub alpha: Option<AestheticValue<f64>>,
+
+    // Recency fade: map a numeric/datetime column to per-point alpha within each group
+    pub fade_by: Option<ColumnRef>,
+    pub fade: FadeDirection,
+
+    // Position adjustment: "jitter" spreads overplotted points along x, "dodge" offsets groups
+    pub position: PointPosition,
+    pub jitter_width: Option<f64>, // Full jitter span in x units (category slots when categorical)
+
+    // Outlined glyphs: a fixed fill color (making `color` the outline) and the outline
+    // width in pixels
+    pub fill: Option<String>,
+    pub stroke: Option<f64>,
+
+    // Bin a layer with more points than `RenderOptions.max_exact_points` onto the pixel
+    // grid (`fast: false` draws every point)
+    pub fast: bool,
+}
+
+impl Default for PointLayer {
+    fn default() -> Self {
+        PointLayer {
+            stat: Stat::Identity,
+            filter: None,
+            data: None,
+            x: None,
+            y: None,
+            color: None,
+            size: None,
+            shape: None,
+            alpha: None,
+            fade_by: None,
+            fade: FadeDirection::default(),
+            position: PointPosition::default(),
+            jitter_width: None,
+            fill: None,
+            stroke: None,
+            fast: true,
+        }
+    }
+}
+
+/// Position adjustment for point layers
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PointPosition {
+    #[default]
+    Identity,
+    Jitter, // Deterministic per-row x offsets within +/- jitter_width / 2
+    Dodge,  // Groups side by side within each category, like dodged bars
+}
+
+/// Which end of a `fade_by` column is faded out
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FadeDirection {
+    #[default]
+    Oldest, // Smallest values are faint, largest are opaque
+    Newest, // Largest values are faint, smallest are opaque
 }
 
 /// Bar geometry layer
-#[derive(Debug, Clone, PartialEq, Default)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct BarLayer {
     pub stat: Stat,
+    pub filter: Option<FilterExpr>, // Row predicate from `filter:`
+    pub data: Option<String>,       // Table from `data:` (None = the plot's data)
     // Aesthetic overrides
-    pub x: Option<String>,
-    pub y: Option<String>,
+    pub x: Option<ColumnRef>,
+    pub y: Option<ColumnRef>,
 
     // Visual properties (can be fixed or data-driven)
     pub color: Option<AestheticValue<String>>,
@@ -357,15 +1331,23 @@ pub struct BarLayer {
 
     // Positioning strategy
     pub position: BarPosition,
+    // Keep a numeric x on a continuous axis; `width` is then in x units
+    pub x_continuous: bool,
+    // Outline drawn around each bar (fixed color) and its width in pixels
+    pub border: Option<String>,
+    pub border_width: Option<f64>,
 }
 
 /// Area geometry layer (filled area from baseline to y)
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct AreaLayer {
     pub stat: Stat,
+    pub filter: Option<FilterExpr>, // Row predicate from `filter:`
+    pub data: Option<String>,       // Table from `data:` (None = the plot's data)
     // Aesthetic overrides
-    pub x: Option<String>,
-    pub y: Option<String>,
+    pub x: Option<ColumnRef>,
+    pub y: Option<ColumnRef>,
 
     // Visual properties
     pub color: Option<AestheticValue<String>>,
@@ -377,6 +1359,8 @@ impl Default for AreaLayer {
     fn default() -> Self {
         AreaLayer {
             stat: Stat::Identity,
+            filter: None,
+            data: None,
             x: None,
             y: None,
             color: None,
@@ -387,12 +1371,15 @@ impl Default for AreaLayer {
 }
 
 /// Rug marks along the plot margins.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct RugLayer {
     pub stat: Stat,
+    pub filter: Option<FilterExpr>, // Row predicate from `filter:`
+    pub data: Option<String>,       // Table from `data:` (None = the plot's data)
     // Aesthetic overrides
-    pub x: Option<String>,
-    pub y: Option<String>,
+    pub x: Option<ColumnRef>,
+    pub y: Option<ColumnRef>,
 
     // Visual properties
     pub color: Option<AestheticValue<String>>,
@@ -408,6 +1395,8 @@ impl Default for RugLayer {
     fn default() -> Self {
         RugLayer {
             stat: Stat::Identity,
+            filter: None,
+            data: None,
             x: None,
             y: None,
             color: None,
@@ -420,12 +1409,15 @@ impl Default for RugLayer {
 }
 
 /// Spike layer: vertical stems from a baseline to y.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SpikeLayer {
     pub stat: Stat,
+    pub filter: Option<FilterExpr>, // Row predicate from `filter:`
+    pub data: Option<String>,       // Table from `data:` (None = the plot's data)
     // Aesthetic overrides
-    pub x: Option<String>,
-    pub y: Option<String>,
+    pub x: Option<ColumnRef>,
+    pub y: Option<ColumnRef>,
 
     // Visual properties
     pub color: Option<AestheticValue<String>>,
@@ -438,6 +1430,8 @@ impl Default for SpikeLayer {
     fn default() -> Self {
         SpikeLayer {
             stat: Stat::Identity,
+            filter: None,
+            data: None,
             x: None,
             y: None,
             color: None,
@@ -449,13 +1443,16 @@ impl Default for SpikeLayer {
 }
 
 /// Vertical interval layer from ymin to ymax at each x.
-#[derive(Debug, Clone, PartialEq, Default)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct LineRangeLayer {
     pub stat: Stat,
+    pub filter: Option<FilterExpr>, // Row predicate from `filter:`
+    pub data: Option<String>,       // Table from `data:` (None = the plot's data)
     // Aesthetic overrides
-    pub x: Option<String>,
-    pub ymin: Option<String>,
-    pub ymax: Option<String>,
+    pub x: Option<ColumnRef>,
+    pub ymin: Option<ColumnRef>,
+    pub ymax: Option<ColumnRef>,
 
     // Visual properties
     pub color: Option<AestheticValue<String>>,
@@ -464,13 +1461,16 @@ pub struct LineRangeLayer {
 }
 
 /// Error-bar interval layer from ymin to ymax at each x, with horizontal caps.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ErrorBarLayer {
     pub stat: Stat,
+    pub filter: Option<FilterExpr>, // Row predicate from `filter:`
+    pub data: Option<String>,       // Table from `data:` (None = the plot's data)
     // Aesthetic overrides
-    pub x: Option<String>,
-    pub ymin: Option<String>,
-    pub ymax: Option<String>,
+    pub x: Option<ColumnRef>,
+    pub ymin: Option<ColumnRef>,
+    pub ymax: Option<ColumnRef>,
 
     // Visual properties
     pub color: Option<AestheticValue<String>>,
@@ -485,6 +1485,8 @@ impl Default for ErrorBarLayer {
     fn default() -> Self {
         ErrorBarLayer {
             stat: Stat::Identity,
+            filter: None,
+            data: None,
             x: None,
             ymin: None,
             ymax: None,
@@ -497,7 +1499,8 @@ impl Default for ErrorBarLayer {
 }
 
 /// Horizontal reference line layer
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct HLineLayer {
     pub stat: Stat,
     pub yintercept: f64,
@@ -521,7 +1524,8 @@ impl Default for HLineLayer {
 }
 
 /// Vertical reference line layer
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct VLineLayer {
     pub stat: Stat,
     pub xintercept: f64,
@@ -545,7 +1549,8 @@ impl Default for VLineLayer {
 }
 
 /// Diagonal reference line layer, y = slope * x + intercept.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct AbLineLayer {
     pub stat: Stat,
     pub slope: f64,
@@ -570,45 +1575,81 @@ impl Default for AbLineLayer {
     }
 }
 
-/// Fixed segment layer from (x, y) to (xend, yend).
-#[derive(Debug, Clone, PartialEq)]
+/// Segment layer from (x, y) to (xend, yend). Numbers draw one fixed segment;
+/// columns draw one segment per row, with x and y falling back to `aes()`.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SegmentLayer {
     pub stat: Stat,
-    pub x: f64,
-    pub y: f64,
-    pub xend: f64,
-    pub yend: f64,
-    pub color: Option<String>,
+    pub x: Option<AestheticValue<f64>>,
+    pub y: Option<AestheticValue<f64>>,
+    pub xend: Option<AestheticValue<f64>>,
+    pub yend: Option<AestheticValue<f64>>,
+    pub color: Option<AestheticValue<String>>,
     pub width: Option<f64>,
     pub alpha: Option<f64>,
     pub label: Option<String>,
+    pub arrow: bool, // Arrowhead at (xend, yend)
 }
 
-impl Default for SegmentLayer {
-    fn default() -> Self {
-        SegmentLayer {
-            stat: Stat::Identity,
-            x: 0.0,
-            y: 0.0,
-            xend: 1.0,
-            yend: 1.0,
-            color: None,
-            width: None,
-            alpha: None,
-            label: None,
-        }
+impl SegmentLayer {
+    /// Whether any endpoint comes from a column, making this a per-row data layer
+    pub fn is_mapped(&self) -> bool {
+        [&self.x, &self.y, &self.xend, &self.yend]
+            .into_iter()
+            .any(|value| matches!(value, Some(AestheticValue::Mapped(_))))
     }
+
+    /// Endpoints of a fixed segment, (x, y, xend, yend); unset ones default to
+    /// (0, 0) -> (1, 1)
+    pub fn fixed_endpoints(&self) -> (f64, f64, f64, f64) {
+        let fixed = |value: &Option<AestheticValue<f64>>, default| match value {
+            Some(AestheticValue::Fixed(v)) => *v,
+            _ => default,
+        };
+        (
+            fixed(&self.x, 0.0),
+            fixed(&self.y, 0.0),
+            fixed(&self.xend, 1.0),
+            fixed(&self.yend, 1.0),
+        )
+    }
+}
+
+/// Text labels drawn at each data coordinate.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TextLayer {
+    pub stat: Stat,
+    pub filter: Option<FilterExpr>, // Row predicate from `filter:`
+    pub data: Option<String>,       // Table from `data:` (None = the plot's data)
+    // Aesthetic overrides
+    pub x: Option<ColumnRef>,
+    pub y: Option<ColumnRef>,
+    /// Column supplying the label text (required)
+    pub label: ColumnRef,
+
+    // Visual properties
+    pub color: Option<AestheticValue<String>>,
+    pub size: Option<f64>,
+    pub alpha: Option<AestheticValue<f64>>,
+    /// Offsets applied in data units before scaling
+    pub nudge_x: f64,
+    pub nudge_y: f64,
 }
 
 /// Point with a vertical interval from ymin to ymax at each x.
-#[derive(Debug, Clone, PartialEq, Default)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct PointRangeLayer {
     pub stat: Stat,
+    pub filter: Option<FilterExpr>, // Row predicate from `filter:`
+    pub data: Option<String>,       // Table from `data:` (None = the plot's data)
     // Aesthetic overrides
-    pub x: Option<String>,
-    pub y: Option<String>,
-    pub ymin: Option<String>,
-    pub ymax: Option<String>,
+    pub x: Option<ColumnRef>,
+    pub y: Option<ColumnRef>,
+    pub ymin: Option<ColumnRef>,
+    pub ymax: Option<ColumnRef>,
 
     // Visual properties
     pub color: Option<AestheticValue<String>>,
@@ -619,14 +1660,17 @@ pub struct PointRangeLayer {
 }
 
 /// Crossbar layer: interval box from ymin to ymax with a center line at y.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct CrossBarLayer {
     pub stat: Stat,
+    pub filter: Option<FilterExpr>, // Row predicate from `filter:`
+    pub data: Option<String>,       // Table from `data:` (None = the plot's data)
     // Aesthetic overrides
-    pub x: Option<String>,
-    pub y: Option<String>,
-    pub ymin: Option<String>,
-    pub ymax: Option<String>,
+    pub x: Option<ColumnRef>,
+    pub y: Option<ColumnRef>,
+    pub ymin: Option<ColumnRef>,
+    pub ymax: Option<ColumnRef>,
 
     // Visual properties
     pub color: Option<AestheticValue<String>>,
@@ -641,6 +1685,8 @@ impl Default for CrossBarLayer {
     fn default() -> Self {
         CrossBarLayer {
             stat: Stat::Identity,
+            filter: None,
+            data: None,
             x: None,
             y: None,
             ymin: None,
@@ -654,13 +1700,16 @@ impl Default for CrossBarLayer {
 }
 
 /// Ribbon geometry layer
-#[derive(Debug, Clone, PartialEq, Default)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct RibbonLayer {
     pub stat: Stat,
+    pub filter: Option<FilterExpr>, // Row predicate from `filter:`
+    pub data: Option<String>,       // Table from `data:` (None = the plot's data)
     // Aesthetic overrides
-    pub x: Option<String>,
-    pub ymin: Option<String>,
-    pub ymax: Option<String>,
+    pub x: Option<ColumnRef>,
+    pub ymin: Option<ColumnRef>,
+    pub ymax: Option<ColumnRef>,
 
     // Visual properties
     pub color: Option<AestheticValue<String>>, // Used for fill
@@ -668,12 +1717,15 @@ pub struct RibbonLayer {
 }
 
 /// Boxplot geometry layer
-#[derive(Debug, Clone, PartialEq, Default)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct BoxplotLayer {
     pub stat: Stat,
+    pub filter: Option<FilterExpr>, // Row predicate from `filter:`
+    pub data: Option<String>,       // Table from `data:` (None = the plot's data)
     // Aesthetic overrides
-    pub x: Option<String>,
-    pub y: Option<String>,
+    pub x: Option<ColumnRef>,
+    pub y: Option<ColumnRef>,
 
     // Visual properties
     pub color: Option<AestheticValue<String>>, // Border color
@@ -688,12 +1740,15 @@ pub struct BoxplotLayer {
 }
 
 /// Violin geometry layer
-#[derive(Debug, Clone, PartialEq, Default)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ViolinLayer {
     pub stat: Stat,
+    pub filter: Option<FilterExpr>, // Row predicate from `filter:`
+    pub data: Option<String>,       // Table from `data:` (None = the plot's data)
     // Aesthetic overrides
-    pub x: Option<String>,
-    pub y: Option<String>,
+    pub x: Option<ColumnRef>,
+    pub y: Option<ColumnRef>,
 
     // Visual properties
     pub color: Option<AestheticValue<String>>,
@@ -705,49 +1760,139 @@ pub struct ViolinLayer {
 }
 
 /// Density geometry layer (KDE-based density curve)
-#[derive(Debug, Clone, PartialEq, Default)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct DensityLayer {
     pub stat: Stat,
+    pub filter: Option<FilterExpr>, // Row predicate from `filter:`
+    pub data: Option<String>,       // Table from `data:` (None = the plot's data)
     // Aesthetic overrides
-    pub x: Option<String>,
+    pub x: Option<ColumnRef>,
 
     // Visual properties
     pub color: Option<AestheticValue<String>>,
     pub alpha: Option<AestheticValue<f64>>,
-    pub bw: Option<f64>, // Bandwidth (None = auto via Silverman's rule)
+    pub bw: Option<f64>,    // Bandwidth (None = auto via Silverman's rule)
+    pub fill: Option<bool>, // Shade under the curve (None = filled); false draws only the curve
+}
+
+/// Pie geometry layer: one wedge per x category, sized by its share of the y total
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PieLayer {
+    pub stat: Stat,
+    pub filter: Option<FilterExpr>, // Row predicate from `filter:`
+    pub data: Option<String>,       // Table from `data:` (None = the plot's data)
+    // Aesthetic overrides
+    pub x: Option<ColumnRef>,
+    pub y: Option<ColumnRef>,
+
+    // Visual properties
+    pub alpha: Option<f64>,
+    pub donut: Option<f64>, // Inner radius as a fraction of the outer (None = solid pie)
+    pub other: Option<f64>, // Slices under this share of the total merge into "Other"
 }
 
 /// Heatmap geometry layer (2D tile plot with color-mapped values)
-#[derive(Debug, Clone, PartialEq, Default)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct HeatmapLayer {
     pub stat: Stat,
+    pub filter: Option<FilterExpr>, // Row predicate from `filter:`
+    pub data: Option<String>,       // Table from `data:` (None = the plot's data)
     // Aesthetic overrides
-    pub x: Option<String>,
-    pub y: Option<String>,
+    pub x: Option<ColumnRef>,
+    pub y: Option<ColumnRef>,
 
     // Visual properties
     pub alpha: Option<AestheticValue<f64>>,
 
     // Heatmap-specific: column for fill values (alternative to aes fill)
-    pub fill: Option<String>,
+    pub fill: Option<ColumnRef>,
 }
 
 /// Bar positioning modes (how bars are arranged)
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum BarPosition {
+    #[default]
     Identity, // Bars overlap at same x position
-    Dodge,    // Bars side-by-side
-    Stack,    // Bars stacked vertically
+    Dodge, // Bars side-by-side
+    Stack, // Bars stacked vertically
+    Fill,  // Bars stacked, then each x position's total scaled to 1.0
+}
+
+/// A one-off note from `annotate(x: 2020, y: 95, text: "policy change")`: literal text
+/// at data coordinates, drawn on top of every layer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Annotation {
+    pub x: AnnotationX,
+    pub y: f64,
+    pub text: String,
+    pub color: Option<String>,
+    pub size: Option<f64>, // Font size in points (default 11)
+    /// Offset of the text from (x, y) in data units; the point and arrow stay at (x, y)
+    pub nudge_x: f64,
+    pub nudge_y: f64,
+    /// Mark (x, y) with a point
+    pub point: bool,
+    /// Draw an arrow from the text to (x, y); needs a nudge to leave room
+    pub arrow: bool,
+    /// Widen the axes to take in the note (the default); when false, a note outside
+    /// the data's axes is clipped
+    pub expand: bool,
+    /// Only draw on facet panels with this value (a facet_grid row or column value
+    /// picks that row or column); None draws on every panel
+    pub panel: Option<String>,
 }
 
-impl Default for BarPosition {
+impl Default for Annotation {
     fn default() -> Self {
-        BarPosition::Identity
+        Annotation {
+            x: AnnotationX::Number(0.0),
+            y: 0.0,
+            text: String::new(),
+            color: None,
+            size: None,
+            nudge_x: 0.0,
+            nudge_y: 0.0,
+            point: false,
+            arrow: false,
+            expand: true,
+            panel: None,
+        }
     }
 }
 
+/// An annotation's x: a number, or a category name or datetime on those axes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum AnnotationX {
+    Number(f64),
+    Text(String),
+}
+
+/// A background band from `shade(xmin: 2008, xmax: 2009.5)`: a rectangle in data
+/// coordinates drawn behind the grid and every layer. A missing side runs to the
+/// axis limit.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Shade {
+    /// Category names cover their whole slot
+    pub xmin: Option<AnnotationX>,
+    pub xmax: Option<AnnotationX>,
+    pub ymin: Option<f64>,
+    pub ymax: Option<f64>,
+    pub color: Option<String>, // Fill color (default gray)
+    pub alpha: Option<f64>,    // Fill opacity (default 0.2)
+    /// Only draw on facet panels with this value, as for `Annotation::panel`
+    pub panel: Option<String>,
+}
+
 /// Plot labels (title, axes)
-#[derive(Debug, Clone, PartialEq, Default)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Labels {
     pub title: Option<String>,
     pub subtitle: Option<String>,
@@ -757,20 +1902,99 @@ pub struct Labels {
 }
 
 /// Facet specification for creating subplot grids
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Facet {
     /// Column name to facet by (creates one subplot per unique value)
     pub by: String,
     /// Number of columns in the grid layout (auto-calculated if None)
     pub ncol: Option<usize>,
+    /// Number of rows in the grid layout; mutually exclusive with `ncol`
+    #[serde(default)]
+    pub nrow: Option<usize>,
     /// Axis scale sharing mode
+    #[serde(default)]
+    pub scales: FacetScales,
+    /// Cut a numeric `by` column into this many intervals, one panel per interval
+    #[serde(default)]
+    pub bins: Option<usize>,
+    /// How `bins` places the interval breaks
+    #[serde(default)]
+    pub cut: FacetCut,
+    /// How panels are captioned
+    #[serde(default)]
+    pub labeller: FacetLabeller,
+    /// Caption font size in pixels (15 if None)
+    #[serde(default)]
+    pub label_size: Option<f64>,
+}
+
+/// Panel caption format for a facet value
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FacetLabeller {
+    /// `region = North` (default)
+    #[default]
+    Equals,
+    /// `North`
+    Value,
+    /// `region: North`
+    Both,
+    /// `{value}` (and `{column}`) replaced in a template like `"Region {value}"`
+    Template(String),
+}
+
+impl FacetLabeller {
+    /// Caption for the panel showing `value` of `column`
+    pub fn label(&self, column: &str, value: &str) -> String {
+        match self {
+            FacetLabeller::Equals => format!("{} = {}", column, value),
+            FacetLabeller::Value => value.to_string(),
+            FacetLabeller::Both => format!("{}: {}", column, value),
+            FacetLabeller::Template(template) => template
+                .replace("{column}", column)
+                .replace("{value}", value),
+        }
+    }
+}
+
+/// Interval breaks for a binned facet column
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FacetCut {
+    /// Equal-width intervals between the column's min and max (default)
+    #[default]
+    Width,
+    /// Intervals holding roughly equal numbers of rows
+    Quantile,
+}
+
+/// Facet grid specification: one subplot per combination of two columns' values,
+/// laid out as a matrix with the row values down the right and column values on top
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FacetGrid {
+    /// Column whose values form the grid rows (a single row if None)
+    #[serde(default)]
+    pub rows: Option<String>,
+    /// Column whose values form the grid columns (a single column if None)
+    #[serde(default)]
+    pub cols: Option<String>,
+    /// Axis scale sharing mode; free x is shared down each column, free y along each row
+    #[serde(default)]
     pub scales: FacetScales,
+    /// How the column and row strips are captioned
+    #[serde(default)]
+    pub labeller: FacetLabeller,
+    /// Caption font size in pixels (15 if None)
+    #[serde(default)]
+    pub label_size: Option<f64>,
 }
 
 /// Facet axis scale sharing modes
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum FacetScales {
     /// All facets share the same x and y ranges (default)
+    #[default]
     Fixed,
     /// Independent x ranges, shared y range
     FreeX,
@@ -780,8 +2004,125 @@ pub enum FacetScales {
     Free,
 }
 
-impl Default for FacetScales {
-    fn default() -> Self {
-        FacetScales::Fixed
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::PlotData;
+    use crate::parser::parse_plot_spec;
+    use crate::{runtime, RenderOptions};
+
+    fn parse(dsl: &str) -> PlotSpec {
+        parse_plot_spec(dsl).unwrap().1
+    }
+
+    #[test]
+    fn json_round_trips_parsed_specs() {
+        let dsls = [
+            r#"aes(x: time, y: value, color: series) | line(width: 2) | point(size: 4, shape: "square") | labs(title: "T", caption: "C")"#,
+            r#"aes(x: quarter, y: amount, color: type) | bar(position: "stack", alpha: 0.8) | coord_flip() | facet_wrap(by: region, ncol: 2, scales: "free_y")"#,
+            r##"aes(x: value) | histogram(bins: 12) | theme_minimal() | theme(axis_text: element_text(size: 14, color: "#333"), legend_position: "lower-right")"##,
+            r#"aes(x: x, y: y) | hline(yintercept: 3, color: "red") | vline(xintercept: 1) | errorbar(ymin: lo, ymax: hi) | scale_y_log10()"#,
+            r#"aes(x: time, y: temp) | step(direction: "vh") | smooth(method: "loess") | scale_x_datetime(format: "%H:%M") | scale_color(palette: "set2")"#,
+            r#"aes(x: g, y: v, color: g) | boxplot() | violin(draw_quantiles: [0.5]) | scale_color_manual(values: "a" = "red", "blue")"#,
+            r#"aes(x: day, y: avg) | filter(site != "x" && (t > 1 || t <= -2)) | summarize(by: [site, day], y: median(t), as: avg) | point(filter: avg > 0)"#,
+            r#"pivot_longer(cols: [a, `B c`], names_to: k, values_to: v) | pivot_longer(cols: k) | aes(x: name, y: value) | point()"#,
+            r#"mutate(r: -log10(abs(a - b)) / (c + 2.5), s: r * r) | aes(x: a, y: s) | sort(by: s, desc: true) | head(n: 5) | point()"#,
+            r#"aes(x: source, y: share) | pie(donut: 0.4, other: 0.02, filter: share > 0)"#,
+        ];
+        for dsl in dsls {
+            let spec = parse(dsl);
+            let json = spec.to_json().unwrap();
+            assert_eq!(PlotSpec::from_json(&json).unwrap(), spec, "{}", dsl);
+        }
+    }
+
+    #[test]
+    fn json_uses_dsl_names() {
+        let json =
+            parse(r#"aes(x: a, y: b) | line(color: "red", width: g) | hline(yintercept: 1)"#)
+                .to_json()
+                .unwrap();
+        assert!(json.contains(r#""line": {"#));
+        assert!(json.contains(r#""hline": {"#));
+        assert!(json.contains(r#""fixed": "red""#));
+        assert!(json.contains(r#""mapped": "g""#));
+    }
+
+    #[test]
+    fn from_json_fills_defaults_and_validates() {
+        let spec = PlotSpec::from_json(
+            r#"{"aesthetics": {"x": "a", "y": "b"}, "layers": [{"line": {}}, {"bar": {"position": "dodge"}}], "facet": {"by": "g"}}"#,
+        )
+        .unwrap();
+        assert_eq!(spec.layers[0], Layer::Line(LineLayer::default()));
+        assert_eq!(
+            spec.layers[1],
+            Layer::Bar(BarLayer {
+                position: BarPosition::Dodge,
+                ..Default::default()
+            })
+        );
+        assert_eq!(spec.facet.unwrap().scales, FacetScales::Fixed);
+
+        let err = PlotSpec::from_json(r#"{"layers": []}"#).unwrap_err();
+        assert!(err.to_string().contains("at least one layer"));
+        let err = PlotSpec::from_json(r#"{"layers": [{"sankey": {}}]}"#).unwrap_err();
+        assert!(err.to_string().contains("Invalid JSON plot spec"));
+    }
+
+    #[test]
+    fn round_tripped_spec_renders_identically() {
+        let dsl =
+            r#"aes(x: x, y: y, color: g) | line() | point(size: 4) | labs(title: "Round trip")"#;
+        let data = || PlotData {
+            headers: vec!["x".to_string(), "y".to_string(), "g".to_string()],
+            rows: [
+                ["1", "3", "a"],
+                ["2", "5", "a"],
+                ["1", "4", "b"],
+                ["2", "2", "b"],
+            ]
+            .iter()
+            .map(|row| row.iter().map(|s| s.to_string()).collect())
+            .collect(),
+        };
+        let spec = parse(dsl);
+        let restored = PlotSpec::from_json(&spec.to_json().unwrap()).unwrap();
+        let options = RenderOptions::default;
+        assert_eq!(
+            runtime::render_plot(spec, data(), options()).unwrap(),
+            runtime::render_plot(restored, data(), options()).unwrap()
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn scene_graph_serializes_with_serde_feature() {
+        use crate::{compiler, resolve, scale, transform};
+        let spec = parse("aes(x: x, y: y) | line()");
+        let data = PlotData {
+            headers: vec!["x".to_string(), "y".to_string()],
+            rows: vec![
+                vec!["1".to_string(), "2".to_string()],
+                vec!["2".to_string(), "3".to_string()],
+            ],
+        };
+        let resolved = resolve::resolve_plot_aesthetics(&spec, &data).unwrap();
+        let render_data =
+            transform::apply_transformations(&resolved, &data, &crate::RenderOptions::default())
+                .unwrap();
+        let scales = scale::build_scales(&render_data, &resolved).unwrap();
+        let json = serde_json::to_string(&render_data).unwrap();
+        let _: crate::ir::RenderData = serde_json::from_str(&json).unwrap();
+        let scene =
+            compiler::compile_geometry(render_data, scales, &resolved, &RenderOptions::default())
+                .unwrap();
+        let json = serde_json::to_string(&scene).unwrap();
+        let restored: crate::ir::SceneGraph = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            restored.panels[0].commands.len(),
+            scene.panels[0].commands.len()
+        );
+        assert!(serde_json::to_string(&resolved).is_ok());
     }
 }
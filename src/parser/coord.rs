@@ -1,5 +1,12 @@
 use crate::parser::ast::CoordSystem;
-use nom::{bytes::complete::tag, character::complete::multispace0, sequence::delimited, IResult};
+use crate::parser::lexer::{number_literal, ws};
+use nom::{
+    bytes::complete::tag,
+    character::complete::{char, multispace0},
+    combinator::opt,
+    sequence::{delimited, preceded},
+    IResult,
+};
 
 pub fn parse_coord_flip(input: &str) -> IResult<&str, CoordSystem> {
     let (input, _) = tag("coord_flip")(input)?;
@@ -8,3 +15,38 @@ pub fn parse_coord_flip(input: &str) -> IResult<&str, CoordSystem> {
 
     Ok((input, CoordSystem::Flip))
 }
+
+/// Parse a fixed aspect ratio
+/// Format: coord_fixed() or coord_fixed(ratio: 2)
+/// - ratio: pixels per y unit over pixels per x unit (default 1)
+pub fn parse_coord_fixed(input: &str) -> IResult<&str, CoordSystem> {
+    let (input, _) = ws(tag("coord_fixed"))(input)?;
+    let (input, _) = ws(char('('))(input)?;
+    let (input, ratio) = opt(preceded(ws(tag("ratio:")), ws(number_literal)))(input)?;
+    let (input, _) = ws(char(')'))(input)?;
+
+    Ok((
+        input,
+        CoordSystem::Fixed {
+            ratio: ratio.unwrap_or(1.0),
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_coord_fixed() {
+        assert_eq!(
+            parse_coord_fixed("coord_fixed()").unwrap().1,
+            CoordSystem::Fixed { ratio: 1.0 }
+        );
+        assert_eq!(
+            parse_coord_fixed("coord_fixed(ratio: 0.5)").unwrap().1,
+            CoordSystem::Fixed { ratio: 0.5 }
+        );
+        assert!(parse_coord_fixed("coord_fixed(aspect: 2)").is_err());
+    }
+}
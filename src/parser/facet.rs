@@ -1,14 +1,23 @@
-// Facet parser for facet_wrap() syntax
+// Facet parsers for facet_wrap() and facet_grid() syntax
 
-use super::ast::{Facet, FacetScales};
-use super::lexer::{identifier, ws};
-use nom::{bytes::complete::tag, character::complete::char, multi::separated_list0, IResult};
+use super::arguments::is_allowed_value;
+use super::ast::{Facet, FacetCut, FacetGrid, FacetLabeller, FacetScales};
+use super::lexer::{column_name, identifier, number_literal, string_literal, ws};
+use nom::{
+    branch::alt, bytes::complete::tag, character::complete::char, combinator::verify,
+    multi::separated_list0, IResult,
+};
 
 /// Parse facet_wrap specification
 /// Format: facet_wrap(by: column_name, ncol: 2, scales: "free_x")
 /// - by: required (column name to facet by)
-/// - ncol: optional (number of columns in grid)
+/// - ncol: optional (number of columns in grid; the old `Some(2)` form still parses)
+/// - nrow: optional (number of rows in grid, instead of ncol)
 /// - scales: optional (axis sharing mode: "fixed", "free_x", "free_y", "free")
+/// - bins: optional (cut a numeric column into this many intervals)
+/// - cut: optional (interval breaks for bins: "width" or "quantile")
+/// - labeller: optional (panel captions: "value", "both", or a template like "Region {value}")
+/// - label_size: optional (caption font size in pixels)
 pub fn parse_facet_wrap(input: &str) -> IResult<&str, Facet> {
     // Parse function name
     let (input, _) = ws(tag("facet_wrap"))(input)?;
@@ -22,14 +31,30 @@ pub fn parse_facet_wrap(input: &str) -> IResult<&str, Facet> {
     // Extract arguments
     let mut by = None;
     let mut ncol = None;
+    let mut nrow = None;
     let mut scales = FacetScales::default();
+    let mut bins = None;
+    let mut cut = FacetCut::default();
+    let mut labeller = FacetLabeller::default();
+    let mut label_size = None;
 
     for (key, value) in args {
         match key.as_str() {
             "by" => by = Some(value.column),
-            "ncol" => ncol = value.ncol,
+            "ncol" => ncol = value.count,
+            "nrow" => nrow = value.count,
             "scales" => scales = value.scales.unwrap_or_default(),
-            _ => {}
+            "bins" => bins = value.count,
+            "cut" => cut = value.cut.unwrap_or_default(),
+            "labeller" => labeller = value.labeller.unwrap_or_default(),
+            "label_size" => label_size = value.size,
+            // Unknown keys fail instead of being ignored (see arguments::validate_arguments)
+            _ => {
+                return Err(nom::Err::Failure(nom::error::Error::new(
+                    input,
+                    nom::error::ErrorKind::Verify,
+                )))
+            }
         }
     }
 
@@ -38,7 +63,74 @@ pub fn parse_facet_wrap(input: &str) -> IResult<&str, Facet> {
         nom::Err::Error(nom::error::Error::new(input, nom::error::ErrorKind::Tag))
     })?;
 
-    Ok((input, Facet { by, ncol, scales }))
+    Ok((
+        input,
+        Facet {
+            by,
+            ncol,
+            nrow,
+            scales,
+            bins,
+            cut,
+            labeller,
+            label_size,
+        },
+    ))
+}
+
+/// Parse facet_grid specification
+/// Format: facet_grid(rows: region, cols: year, scales: "free_y")
+/// - rows: optional (column whose values form the grid rows)
+/// - cols: optional (column whose values form the grid columns)
+/// - scales: optional (axis sharing mode; free x is per grid column, free y per grid row)
+/// - labeller, label_size: optional (strip captions, as for facet_wrap)
+///
+/// At least one of rows and cols is required.
+pub fn parse_facet_grid(input: &str) -> IResult<&str, FacetGrid> {
+    let (input, _) = ws(tag("facet_grid"))(input)?;
+    let (input, _) = ws(char('('))(input)?;
+    let (input, args) = separated_list0(ws(char(',')), parse_facet_argument)(input)?;
+    let (input, _) = ws(char(')'))(input)?;
+
+    let mut rows = None;
+    let mut cols = None;
+    let mut scales = FacetScales::default();
+    let mut labeller = FacetLabeller::default();
+    let mut label_size = None;
+
+    for (key, value) in args {
+        match key.as_str() {
+            "rows" => rows = Some(value.column),
+            "cols" => cols = Some(value.column),
+            "scales" => scales = value.scales.unwrap_or_default(),
+            "labeller" => labeller = value.labeller.unwrap_or_default(),
+            "label_size" => label_size = value.size,
+            _ => {
+                return Err(nom::Err::Failure(nom::error::Error::new(
+                    input,
+                    nom::error::ErrorKind::Verify,
+                )))
+            }
+        }
+    }
+
+    if rows.is_none() && cols.is_none() {
+        return Err(nom::Err::Error(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::Tag,
+        )));
+    }
+
+    Ok((
+        input,
+        FacetGrid {
+            rows,
+            cols,
+            scales,
+            labeller,
+            label_size,
+        },
+    ))
 }
 
 /// Parse a single facet argument (key: value pair)
@@ -48,18 +140,30 @@ fn parse_facet_argument(input: &str) -> IResult<&str, (String, FacetArgValue)> {
 
     let value = match key.as_str() {
         "by" => {
-            let (input, col) = ws(identifier)(input)?;
+            let (input, col) = ws(column_name)(input)?;
             (input, FacetArgValue::column(col))
         }
         "ncol" => {
+            let (input, n) = alt((ws(nom::character::complete::u32), legacy_some_count))(input)?;
+            (input, FacetArgValue::count(n as usize))
+        }
+        "nrow" | "bins" => {
             let (input, n) = ws(nom::character::complete::u32)(input)?;
-            (input, FacetArgValue::ncol(n as usize))
+            (input, FacetArgValue::count(n as usize))
+        }
+        "cut" => {
+            let (input, cut_str) = ws(verify(string_literal, |s: &str| {
+                is_allowed_value("facet_wrap", "cut", s)
+            }))(input)?;
+            let cut = match cut_str.as_str() {
+                "quantile" => FacetCut::Quantile,
+                _ => FacetCut::Width,
+            };
+            (input, FacetArgValue::cut(cut))
         }
         "scales" => {
-            let (input, _) = ws(char('"'))(input)?;
-            let (input, scale_str) = nom::bytes::complete::take_while(|c: char| c != '"')(input)?;
-            let (input, _) = ws(char('"'))(input)?;
-            let scales = match scale_str {
+            let (input, scale_str) = ws(string_literal)(input)?;
+            let scales = match scale_str.as_str() {
                 "free_x" => FacetScales::FreeX,
                 "free_y" => FacetScales::FreeY,
                 "free" => FacetScales::Free,
@@ -68,9 +172,23 @@ fn parse_facet_argument(input: &str) -> IResult<&str, (String, FacetArgValue)> {
             };
             (input, FacetArgValue::scales(scales))
         }
+        "labeller" => {
+            // Anything else is a template; resolve checks it mentions {value}
+            let (input, labeller) = ws(string_literal)(input)?;
+            let labeller = match labeller.as_str() {
+                "value" => FacetLabeller::Value,
+                "both" => FacetLabeller::Both,
+                _ => FacetLabeller::Template(labeller),
+            };
+            (input, FacetArgValue::labeller(labeller))
+        }
+        "label_size" => {
+            let (input, size) = ws(number_literal)(input)?;
+            (input, FacetArgValue::size(size))
+        }
         _ => {
-            // Unknown argument, skip it
-            let (input, col) = ws(identifier)(input)?;
+            // Column arguments (facet_grid rows/cols); others fail in the caller
+            let (input, col) = ws(column_name)(input)?;
             (input, FacetArgValue::column(col))
         }
     };
@@ -78,36 +196,69 @@ fn parse_facet_argument(input: &str) -> IResult<&str, (String, FacetArgValue)> {
     Ok((value.0, (key, value.1)))
 }
 
+/// The pre-0.2 `ncol: Some(2)` spelling, still accepted; `arguments::deprecated_arguments`
+/// reports it
+fn legacy_some_count(input: &str) -> IResult<&str, u32> {
+    let (input, _) = ws(tag("Some"))(input)?;
+    let (input, _) = ws(char('('))(input)?;
+    let (input, n) = ws(nom::character::complete::u32)(input)?;
+    let (input, _) = ws(char(')'))(input)?;
+    Ok((input, n))
+}
+
 /// Intermediate representation for facet argument values
-#[derive(Debug)]
+#[derive(Debug, Default)]
 struct FacetArgValue {
     column: String,
-    ncol: Option<usize>,
+    /// ncol, nrow, or bins
+    count: Option<usize>,
     scales: Option<FacetScales>,
+    cut: Option<FacetCut>,
+    labeller: Option<FacetLabeller>,
+    /// label_size
+    size: Option<f64>,
 }
 
 impl FacetArgValue {
     fn column(s: String) -> Self {
         Self {
             column: s,
-            ncol: None,
-            scales: None,
+            ..Self::default()
         }
     }
 
-    fn ncol(n: usize) -> Self {
+    fn count(n: usize) -> Self {
         Self {
-            column: String::new(),
-            ncol: Some(n),
-            scales: None,
+            count: Some(n),
+            ..Self::default()
         }
     }
 
     fn scales(s: FacetScales) -> Self {
         Self {
-            column: String::new(),
-            ncol: None,
             scales: Some(s),
+            ..Self::default()
+        }
+    }
+
+    fn cut(c: FacetCut) -> Self {
+        Self {
+            cut: Some(c),
+            ..Self::default()
+        }
+    }
+
+    fn labeller(l: FacetLabeller) -> Self {
+        Self {
+            labeller: Some(l),
+            ..Self::default()
+        }
+    }
+
+    fn size(size: f64) -> Self {
+        Self {
+            size: Some(size),
+            ..Self::default()
         }
     }
 }
@@ -135,6 +286,78 @@ mod tests {
         assert_eq!(facet.ncol, Some(2));
     }
 
+    #[test]
+    fn test_parse_facet_wrap_with_legacy_some_ncol() {
+        let (_, facet) = parse_facet_wrap("facet_wrap(by: region, ncol: Some(2))").unwrap();
+        assert_eq!(facet.ncol, Some(2));
+        let (_, facet) = parse_facet_wrap("facet_wrap(by: region, ncol: Some( 3 ))").unwrap();
+        assert_eq!(facet.ncol, Some(3));
+        assert!(parse_facet_wrap("facet_wrap(by: region, ncol: Some())").is_err());
+    }
+
+    #[test]
+    fn test_parse_facet_wrap_with_nrow() {
+        let (_, facet) = parse_facet_wrap("facet_wrap(by: region, nrow: 1)").unwrap();
+        assert_eq!(facet.nrow, Some(1));
+        assert_eq!(facet.ncol, None);
+        // Both parse; resolve rejects the combination with a readable error
+        let (_, facet) = parse_facet_wrap("facet_wrap(by: region, nrow: 1, ncol: 2)").unwrap();
+        assert_eq!((facet.nrow, facet.ncol), (Some(1), Some(2)));
+    }
+
+    #[test]
+    fn test_parse_facet_quoted_columns() {
+        let (_, facet) = parse_facet_wrap("facet_wrap(by: `Region (EU)`)").unwrap();
+        assert_eq!(facet.by, "Region (EU)");
+        let (_, grid) = parse_facet_grid(r#"facet_grid(rows: "Année", cols: `store-id`)"#).unwrap();
+        assert_eq!(grid.rows.as_deref(), Some("Année"));
+        assert_eq!(grid.cols.as_deref(), Some("store-id"));
+    }
+
+    #[test]
+    fn test_parse_facet_labeller() {
+        let (_, facet) = parse_facet_wrap("facet_wrap(by: region)").unwrap();
+        assert_eq!(
+            (facet.labeller, facet.label_size),
+            (FacetLabeller::Equals, None)
+        );
+        for (arg, labeller) in [
+            (r#""value""#, FacetLabeller::Value),
+            (r#""both""#, FacetLabeller::Both),
+            (
+                r#""Region {value}""#,
+                FacetLabeller::Template("Region {value}".to_string()),
+            ),
+        ] {
+            let (_, facet) =
+                parse_facet_wrap(&format!("facet_wrap(by: region, labeller: {})", arg)).unwrap();
+            assert_eq!(facet.labeller, labeller);
+        }
+        let (_, facet) = parse_facet_wrap("facet_wrap(by: region, label_size: 10.5)").unwrap();
+        assert_eq!(facet.label_size, Some(10.5));
+
+        let (_, grid) =
+            parse_facet_grid(r#"facet_grid(rows: r, labeller: "both", label_size: 12)"#).unwrap();
+        assert_eq!(
+            (grid.labeller, grid.label_size),
+            (FacetLabeller::Both, Some(12.0))
+        );
+        assert!(parse_facet_wrap("facet_wrap(by: region, labeller: value)").is_err());
+    }
+
+    #[test]
+    fn test_parse_facet_wrap_with_bins() {
+        let (_, facet) = parse_facet_wrap("facet_wrap(by: age, bins: 4)").unwrap();
+        assert_eq!(facet.bins, Some(4));
+        assert_eq!(facet.cut, FacetCut::Width);
+        let (_, facet) =
+            parse_facet_wrap(r#"facet_wrap(by: age, bins: 3, cut: "quantile")"#).unwrap();
+        assert_eq!((facet.bins, facet.cut), (Some(3), FacetCut::Quantile));
+        let (_, facet) = parse_facet_wrap(r#"facet_wrap(by: age, bins: 3, cut: "width")"#).unwrap();
+        assert_eq!(facet.cut, FacetCut::Width);
+        assert!(parse_facet_wrap(r#"facet_wrap(by: age, bins: 3, cut: "weird")"#).is_err());
+    }
+
     #[test]
     fn test_parse_facet_wrap_with_scales_free_x() {
         let result = parse_facet_wrap(r#"facet_wrap(by: region, scales: "free_x")"#);
@@ -185,4 +408,27 @@ mod tests {
         assert_eq!(facet.by, "region");
         assert_eq!(facet.ncol, Some(2));
     }
+
+    #[test]
+    fn test_parse_facet_wrap_unknown_arg() {
+        assert!(parse_facet_wrap("facet_wrap(by: region, cols: region)").is_err());
+    }
+
+    #[test]
+    fn test_parse_facet_grid() {
+        let (_, grid) = parse_facet_grid("facet_grid(rows: region, cols: year)").unwrap();
+        assert_eq!(grid.rows.as_deref(), Some("region"));
+        assert_eq!(grid.cols.as_deref(), Some("year"));
+        assert_eq!(grid.scales, FacetScales::Fixed);
+
+        let (_, grid) =
+            parse_facet_grid(r#"facet_grid( cols : year , scales: "free_y" )"#).unwrap();
+        assert_eq!(grid.rows, None);
+        assert_eq!(grid.cols.as_deref(), Some("year"));
+        assert_eq!(grid.scales, FacetScales::FreeY);
+
+        // One of rows/cols is required, and facet_wrap's arguments are not accepted
+        assert!(parse_facet_grid("facet_grid()").is_err());
+        assert!(parse_facet_grid("facet_grid(by: region)").is_err());
+    }
 }
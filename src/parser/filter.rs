@@ -0,0 +1,158 @@
+// Row filter expressions: `region == "EU"`, `year >= 2020 && sales < 100`, used by a
+// layer's `filter:` argument and by the `filter(...)` pipeline stage (see stage.rs)
+//
+// `&&` binds tighter than `||`, and parentheses group. The left side of each
+// comparison is a column (bare or backtick-quoted); the right side is a number or a
+// double-quoted string literal.
+
+use super::ast::{CompareOp, FilterExpr, FilterValue};
+use super::lexer::{mapped_column, number_literal, string_literal, ws};
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::char,
+    combinator::{map, value},
+    error::{Error, ErrorKind},
+    multi::many0,
+    sequence::{delimited, preceded},
+    IResult,
+};
+
+/// Deepest parenthesis nesting accepted, so pathological input fails to parse
+/// instead of overflowing the stack
+const MAX_DEPTH: usize = 64;
+
+/// Parse a filter expression
+pub fn filter_expr(input: &str) -> IResult<&str, FilterExpr> {
+    or_expr(input, 0)
+}
+
+fn or_expr(input: &str, depth: usize) -> IResult<&str, FilterExpr> {
+    let (input, first) = and_expr(input, depth)?;
+    let (input, rest) = many0(preceded(ws(tag("||")), |i| and_expr(i, depth)))(input)?;
+    let expr = rest.into_iter().fold(first, |acc, next| {
+        FilterExpr::Or(Box::new(acc), Box::new(next))
+    });
+    Ok((input, expr))
+}
+
+fn and_expr(input: &str, depth: usize) -> IResult<&str, FilterExpr> {
+    let (input, first) = atom(input, depth)?;
+    let (input, rest) = many0(preceded(ws(tag("&&")), |i| atom(i, depth)))(input)?;
+    let expr = rest.into_iter().fold(first, |acc, next| {
+        FilterExpr::And(Box::new(acc), Box::new(next))
+    });
+    Ok((input, expr))
+}
+
+fn atom(input: &str, depth: usize) -> IResult<&str, FilterExpr> {
+    alt((
+        delimited(ws(char('(')), |i| nested(i, depth + 1), ws(char(')'))),
+        comparison,
+    ))(input)
+}
+
+fn nested(input: &str, depth: usize) -> IResult<&str, FilterExpr> {
+    if depth > MAX_DEPTH {
+        return Err(nom::Err::Failure(Error::new(input, ErrorKind::TooLarge)));
+    }
+    or_expr(input, depth)
+}
+
+fn comparison(input: &str) -> IResult<&str, FilterExpr> {
+    let (input, column) = ws(mapped_column)(input)?;
+    let (input, op) = ws(compare_op)(input)?;
+    let (input, value) = ws(alt((
+        map(number_literal, FilterValue::Number),
+        map(string_literal, FilterValue::Text),
+    )))(input)?;
+    Ok((input, FilterExpr::Compare { column, op, value }))
+}
+
+fn compare_op(input: &str) -> IResult<&str, CompareOp> {
+    // Two-character operators first so `<=` isn't read as `<`
+    alt((
+        value(CompareOp::Eq, tag("==")),
+        value(CompareOp::Ne, tag("!=")),
+        value(CompareOp::Le, tag("<=")),
+        value(CompareOp::Ge, tag(">=")),
+        value(CompareOp::Lt, tag("<")),
+        value(CompareOp::Gt, tag(">")),
+    ))(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compare(column: &str, op: CompareOp, value: FilterValue) -> FilterExpr {
+        FilterExpr::Compare {
+            column: column.to_string(),
+            op,
+            value,
+        }
+    }
+
+    #[test]
+    fn parses_comparisons() {
+        let (rest, expr) = filter_expr(r#"region == "EU""#).unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(
+            expr,
+            compare("region", CompareOp::Eq, FilterValue::Text("EU".to_string()))
+        );
+
+        let (_, expr) = filter_expr("year>=2020").unwrap();
+        assert_eq!(
+            expr,
+            compare("year", CompareOp::Ge, FilterValue::Number(2020.0))
+        );
+
+        let (_, expr) = filter_expr("`Temp (C)` < -5").unwrap();
+        assert_eq!(
+            expr,
+            compare("Temp (C)", CompareOp::Lt, FilterValue::Number(-5.0))
+        );
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let (_, expr) = filter_expr(r#"a == "x" || b > 1 && c != 2"#).unwrap();
+        assert_eq!(
+            expr,
+            FilterExpr::Or(
+                Box::new(compare(
+                    "a",
+                    CompareOp::Eq,
+                    FilterValue::Text("x".to_string())
+                )),
+                Box::new(FilterExpr::And(
+                    Box::new(compare("b", CompareOp::Gt, FilterValue::Number(1.0))),
+                    Box::new(compare("c", CompareOp::Ne, FilterValue::Number(2.0))),
+                )),
+            )
+        );
+
+        let (_, expr) = filter_expr("(a <= 1 || a > 9) && b == 0").unwrap();
+        assert!(
+            matches!(expr, FilterExpr::And(ref left, _) if matches!(**left, FilterExpr::Or(..)))
+        );
+        assert_eq!(expr.columns(), vec!["a", "a", "b"]);
+    }
+
+    #[test]
+    fn rejects_incomplete_expressions() {
+        assert!(filter_expr(r#"region = "EU""#).is_err());
+        assert!(filter_expr("year >=").is_err());
+        // A trailing operator is left unparsed for the caller to reject
+        let (rest, _) = filter_expr("a == 1 &&").unwrap();
+        assert_eq!(rest.trim(), "&&");
+    }
+
+    #[test]
+    fn rejects_runaway_nesting() {
+        let nested = |depth| format!("{}a > 1{}", "(".repeat(depth), ")".repeat(depth));
+        assert!(filter_expr(&nested(MAX_DEPTH)).is_ok());
+        assert!(filter_expr(&nested(100_000)).is_err());
+    }
+}
@@ -1,17 +1,23 @@
 // Geometry (geom) parser for Grammar of Graphics DSL
 
+use super::arguments::is_allowed_value;
 use super::ast::{
-    AbLineLayer, AestheticValue, AreaLayer, BarLayer, BarPosition, BoxplotLayer, CrossBarLayer,
-    DensityLayer, ErrorBarLayer, HLineLayer, HeatmapLayer, Layer, LineInterpolation, LineLayer,
-    LineRangeLayer, PointLayer, PointRangeLayer, RibbonLayer, RugLayer, SegmentLayer, SpikeLayer,
-    VLineLayer, ViolinLayer,
+    AbLineLayer, AestheticValue, Aggregate, AreaLayer, BarLayer, BarPosition, BoxplotLayer,
+    ColumnRef, CrossBarLayer, DensityLayer, ErrorBarLayer, FadeDirection, FilterExpr, HLineLayer,
+    HeatmapLayer, Layer, LineInterpolation, LineLayer, LinePosition, LineRangeLayer, PieLayer,
+    PointLayer, PointPosition, PointRangeLayer, RibbonLayer, RugLayer, SegmentLayer, SpikeLayer,
+    Stat, TextLayer, VLineLayer, ViolinLayer,
+};
+use super::filter::filter_expr;
+use super::lexer::{
+    bool_literal, column_ref, mapped_column_ref, number_literal, string_literal, ws,
 };
-use super::lexer::{identifier, number_literal, string_literal, ws};
 use nom::{
     branch::alt,
     bytes::complete::tag,
     character::complete::char,
-    combinator::{map, opt},
+    combinator::{map, verify},
+    error::{Error, ErrorKind},
     multi::separated_list0,
     sequence::preceded,
     IResult,
@@ -19,12 +25,26 @@ use nom::{
 
 /// Argument value type for geometry parsers
 enum ArgValue {
-    ColumnName(String),    // x, y aesthetic overrides
-    ColorFixed(String),    // color: "red" (literal)
-    ColorMapped(String),   // color: region (column)
-    NumericFixed(f64),     // width: 2, alpha: 0.5
-    NumericMapped(String), // width: size_col, alpha: alpha_col
-    NumberArray(Vec<f64>), // draw_quantiles: [0.25, 0.5, 0.75]
+    ColumnName(ColumnRef),    // x, y aesthetic overrides
+    ColorFixed(String),       // color: "red" (literal)
+    ColorMapped(ColumnRef),   // color: region (column)
+    NumericFixed(f64),        // width: 2, alpha: 0.5
+    NumericMapped(ColumnRef), // width: size_col, alpha: alpha_col
+    NumberArray(Vec<f64>),    // draw_quantiles: [0.25, 0.5, 0.75]
+    Filter(FilterExpr),       // filter: region == "EU"
+    Bool(bool),               // fill: false
+}
+
+/// `filter:` row predicate and `data:` table, accepted by every data geometry
+fn rows_arg(input: &str) -> IResult<&str, (&'static str, ArgValue)> {
+    alt((
+        map(preceded(ws(tag("filter:")), ws(filter_expr)), |f| {
+            ("filter", ArgValue::Filter(f))
+        }),
+        map(preceded(ws(tag("data:")), ws(string_literal)), |path| {
+            ("data", ArgValue::ColorFixed(path))
+        }),
+    ))(input)
 }
 
 /// Parse a number array like [0.25, 0.5, 0.75]
@@ -45,41 +65,82 @@ pub fn parse_line(input: &str) -> IResult<&str, Layer> {
     let (input, args) = separated_list0(
         ws(char(',')),
         alt((
+            rows_arg,
             // x: can be column
-            map(preceded(ws(tag("x:")), ws(identifier)), |x| {
+            map(preceded(ws(tag("x:")), ws(column_ref)), |x| {
                 ("x", ArgValue::ColumnName(x))
             }),
             // y: can be column
-            map(preceded(ws(tag("y:")), ws(identifier)), |y| {
+            map(preceded(ws(tag("y:")), ws(column_ref)), |y| {
                 ("y", ArgValue::ColumnName(y))
             }),
             // color: can be "red" (literal), region (column)
             map(preceded(ws(tag("color:")), ws(string_literal)), |c| {
                 ("color", ArgValue::ColorFixed(c))
             }),
-            map(preceded(ws(tag("color:")), ws(identifier)), |c| {
+            map(preceded(ws(tag("color:")), ws(mapped_column_ref)), |c| {
                 ("color", ArgValue::ColorMapped(c))
             }),
             // width: can be 2.0 (literal), width_col (column)
             map(preceded(ws(tag("width:")), ws(number_literal)), |w| {
                 ("width", ArgValue::NumericFixed(w))
             }),
-            map(preceded(ws(tag("width:")), ws(identifier)), |w| {
+            map(preceded(ws(tag("width:")), ws(mapped_column_ref)), |w| {
                 ("width", ArgValue::NumericMapped(w))
             }),
             // alpha: can be 0.5 (literal), alpha_col (column)
             map(preceded(ws(tag("alpha:")), ws(number_literal)), |a| {
                 ("alpha", ArgValue::NumericFixed(a))
             }),
-            map(preceded(ws(tag("alpha:")), ws(identifier)), |a| {
+            map(preceded(ws(tag("alpha:")), ws(mapped_column_ref)), |a| {
                 ("alpha", ArgValue::NumericMapped(a))
             }),
+            // linetype: can be "dashed" (literal), linetype_col (column)
+            map(
+                preceded(
+                    ws(tag("linetype:")),
+                    ws(verify(string_literal, |s: &str| {
+                        is_allowed_value("line", "linetype", s)
+                    })),
+                ),
+                |lt| ("linetype", ArgValue::ColorFixed(lt)),
+            ),
+            map(
+                preceded(ws(tag("linetype:")), ws(mapped_column_ref)),
+                |lt| ("linetype", ArgValue::ColorMapped(lt)),
+            ),
+            // group: column splitting the rows into separate lines
+            map(preceded(ws(tag("group:")), ws(column_ref)), |g| {
+                ("group", ArgValue::ColumnName(g))
+            }),
+            // position: "identity" or "dodge"
+            map(preceded(ws(tag("position:")), ws(string_literal)), |p| {
+                ("position", ArgValue::ColorFixed(p))
+            }),
+            // stat: "identity" or "rolling_mean" (with window: and min_periods:)
+            map(preceded(ws(tag("stat:")), ws(string_literal)), |s| {
+                ("stat", ArgValue::ColorFixed(s))
+            }),
+            map(
+                preceded(ws(tag("window:")), ws(nom::character::complete::u64)),
+                |w| ("window", ArgValue::NumericFixed(w as f64)),
+            ),
+            map(
+                preceded(ws(tag("min_periods:")), ws(nom::character::complete::u64)),
+                |m| ("min_periods", ArgValue::NumericFixed(m as f64)),
+            ),
+            map(preceded(ws(tag("simplify:")), ws(bool_literal)), |s| {
+                ("simplify", ArgValue::Bool(s))
+            }),
         )),
     )(input)?;
 
     let (input, _) = ws(char(')'))(input)?;
 
     let mut layer = LineLayer::default();
+    let mut rolling = false;
+    let mut window = None;
+    let mut min_periods = None;
 
     for (key, val) in args {
         match (key, val) {
@@ -91,10 +152,41 @@ pub fn parse_line(input: &str) -> IResult<&str, Layer> {
             ("width", ArgValue::NumericMapped(w)) => layer.width = Some(AestheticValue::Mapped(w)),
             ("alpha", ArgValue::NumericFixed(a)) => layer.alpha = Some(AestheticValue::Fixed(a)),
             ("alpha", ArgValue::NumericMapped(a)) => layer.alpha = Some(AestheticValue::Mapped(a)),
+            ("linetype", ArgValue::ColorFixed(lt)) => {
+                layer.linetype = Some(AestheticValue::Fixed(lt))
+            }
+            ("linetype", ArgValue::ColorMapped(lt)) => {
+                layer.linetype = Some(AestheticValue::Mapped(lt))
+            }
+            ("group", ArgValue::ColumnName(g)) => layer.group = Some(g),
+            ("position", ArgValue::ColorFixed(p)) => {
+                layer.position = match p.as_str() {
+                    "dodge" => LinePosition::Dodge,
+                    _ => LinePosition::Identity, // default for unknown values
+                };
+            }
+            // "identity" and unknown values keep the raw series
+            ("stat", ArgValue::ColorFixed(s)) => rolling = s == "rolling_mean",
+            ("window", ArgValue::NumericFixed(w)) => window = Some(w as usize),
+            ("min_periods", ArgValue::NumericFixed(m)) => min_periods = Some(m as usize),
+            ("simplify", ArgValue::Bool(s)) => layer.simplify = s,
+            ("filter", ArgValue::Filter(f)) => layer.filter = Some(f),
+            ("data", ArgValue::ColorFixed(path)) => layer.data = Some(path),
             _ => {}
         }
     }
 
+    // Validate: a rolling mean needs its window
+    if rolling {
+        let window = window.ok_or_else(|| {
+            nom::Err::Failure(nom::error::Error::new(input, nom::error::ErrorKind::Verify))
+        })?;
+        layer.stat = Stat::RollingMean {
+            window,
+            min_periods,
+        };
+    }
+
     Ok((input, Layer::Line(layer)))
 }
 
@@ -107,10 +199,11 @@ pub fn parse_step(input: &str) -> IResult<&str, Layer> {
     let (input, args) = separated_list0(
         ws(char(',')),
         alt((
-            map(preceded(ws(tag("x:")), ws(identifier)), |x| {
+            rows_arg,
+            map(preceded(ws(tag("x:")), ws(column_ref)), |x| {
                 ("x", ArgValue::ColumnName(x))
             }),
-            map(preceded(ws(tag("y:")), ws(identifier)), |y| {
+            map(preceded(ws(tag("y:")), ws(column_ref)), |y| {
                 ("y", ArgValue::ColumnName(y))
             }),
             map(preceded(ws(tag("direction:")), ws(string_literal)), |d| {
@@ -119,19 +212,19 @@ pub fn parse_step(input: &str) -> IResult<&str, Layer> {
             map(preceded(ws(tag("color:")), ws(string_literal)), |c| {
                 ("color", ArgValue::ColorFixed(c))
             }),
-            map(preceded(ws(tag("color:")), ws(identifier)), |c| {
+            map(preceded(ws(tag("color:")), ws(mapped_column_ref)), |c| {
                 ("color", ArgValue::ColorMapped(c))
             }),
             map(preceded(ws(tag("width:")), ws(number_literal)), |w| {
                 ("width", ArgValue::NumericFixed(w))
             }),
-            map(preceded(ws(tag("width:")), ws(identifier)), |w| {
+            map(preceded(ws(tag("width:")), ws(mapped_column_ref)), |w| {
                 ("width", ArgValue::NumericMapped(w))
             }),
             map(preceded(ws(tag("alpha:")), ws(number_literal)), |a| {
                 ("alpha", ArgValue::NumericFixed(a))
             }),
-            map(preceded(ws(tag("alpha:")), ws(identifier)), |a| {
+            map(preceded(ws(tag("alpha:")), ws(mapped_column_ref)), |a| {
                 ("alpha", ArgValue::NumericMapped(a))
             }),
         )),
@@ -161,6 +254,8 @@ pub fn parse_step(input: &str) -> IResult<&str, Layer> {
             ("width", ArgValue::NumericMapped(w)) => layer.width = Some(AestheticValue::Mapped(w)),
             ("alpha", ArgValue::NumericFixed(a)) => layer.alpha = Some(AestheticValue::Fixed(a)),
             ("alpha", ArgValue::NumericMapped(a)) => layer.alpha = Some(AestheticValue::Mapped(a)),
+            ("filter", ArgValue::Filter(f)) => layer.filter = Some(f),
+            ("data", ArgValue::ColorFixed(path)) => layer.data = Some(path),
             _ => {}
         }
     }
@@ -176,22 +271,23 @@ pub fn parse_area(input: &str) -> IResult<&str, Layer> {
     let (input, args) = separated_list0(
         ws(char(',')),
         alt((
-            map(preceded(ws(tag("x:")), ws(identifier)), |x| {
+            rows_arg,
+            map(preceded(ws(tag("x:")), ws(column_ref)), |x| {
                 ("x", ArgValue::ColumnName(x))
             }),
-            map(preceded(ws(tag("y:")), ws(identifier)), |y| {
+            map(preceded(ws(tag("y:")), ws(column_ref)), |y| {
                 ("y", ArgValue::ColumnName(y))
             }),
             map(preceded(ws(tag("color:")), ws(string_literal)), |c| {
                 ("color", ArgValue::ColorFixed(c))
             }),
-            map(preceded(ws(tag("color:")), ws(identifier)), |c| {
+            map(preceded(ws(tag("color:")), ws(mapped_column_ref)), |c| {
                 ("color", ArgValue::ColorMapped(c))
             }),
             map(preceded(ws(tag("alpha:")), ws(number_literal)), |a| {
                 ("alpha", ArgValue::NumericFixed(a))
             }),
-            map(preceded(ws(tag("alpha:")), ws(identifier)), |a| {
+            map(preceded(ws(tag("alpha:")), ws(mapped_column_ref)), |a| {
                 ("alpha", ArgValue::NumericMapped(a))
             }),
             map(preceded(ws(tag("baseline:")), ws(number_literal)), |b| {
@@ -213,6 +309,8 @@ pub fn parse_area(input: &str) -> IResult<&str, Layer> {
             ("alpha", ArgValue::NumericFixed(a)) => layer.alpha = Some(AestheticValue::Fixed(a)),
             ("alpha", ArgValue::NumericMapped(a)) => layer.alpha = Some(AestheticValue::Mapped(a)),
             ("baseline", ArgValue::NumericFixed(b)) => layer.baseline = b,
+            ("filter", ArgValue::Filter(f)) => layer.filter = Some(f),
+            ("data", ArgValue::ColorFixed(path)) => layer.data = Some(path),
             _ => {}
         }
     }
@@ -229,10 +327,11 @@ pub fn parse_rug(input: &str) -> IResult<&str, Layer> {
     let (input, args) = separated_list0(
         ws(char(',')),
         alt((
-            map(preceded(ws(tag("x:")), ws(identifier)), |x| {
+            rows_arg,
+            map(preceded(ws(tag("x:")), ws(column_ref)), |x| {
                 ("x", ArgValue::ColumnName(x))
             }),
-            map(preceded(ws(tag("y:")), ws(identifier)), |y| {
+            map(preceded(ws(tag("y:")), ws(column_ref)), |y| {
                 ("y", ArgValue::ColumnName(y))
             }),
             map(preceded(ws(tag("sides:")), ws(string_literal)), |s| {
@@ -244,19 +343,19 @@ pub fn parse_rug(input: &str) -> IResult<&str, Layer> {
             map(preceded(ws(tag("color:")), ws(string_literal)), |c| {
                 ("color", ArgValue::ColorFixed(c))
             }),
-            map(preceded(ws(tag("color:")), ws(identifier)), |c| {
+            map(preceded(ws(tag("color:")), ws(mapped_column_ref)), |c| {
                 ("color", ArgValue::ColorMapped(c))
             }),
             map(preceded(ws(tag("width:")), ws(number_literal)), |w| {
                 ("width", ArgValue::NumericFixed(w))
             }),
-            map(preceded(ws(tag("width:")), ws(identifier)), |w| {
+            map(preceded(ws(tag("width:")), ws(mapped_column_ref)), |w| {
                 ("width", ArgValue::NumericMapped(w))
             }),
             map(preceded(ws(tag("alpha:")), ws(number_literal)), |a| {
                 ("alpha", ArgValue::NumericFixed(a))
             }),
-            map(preceded(ws(tag("alpha:")), ws(identifier)), |a| {
+            map(preceded(ws(tag("alpha:")), ws(mapped_column_ref)), |a| {
                 ("alpha", ArgValue::NumericMapped(a))
             }),
         )),
@@ -277,6 +376,8 @@ pub fn parse_rug(input: &str) -> IResult<&str, Layer> {
             ("width", ArgValue::NumericMapped(w)) => layer.width = Some(AestheticValue::Mapped(w)),
             ("alpha", ArgValue::NumericFixed(a)) => layer.alpha = Some(AestheticValue::Fixed(a)),
             ("alpha", ArgValue::NumericMapped(a)) => layer.alpha = Some(AestheticValue::Mapped(a)),
+            ("filter", ArgValue::Filter(f)) => layer.filter = Some(f),
+            ("data", ArgValue::ColorFixed(path)) => layer.data = Some(path),
             _ => {}
         }
     }
@@ -292,10 +393,11 @@ pub fn parse_spike(input: &str) -> IResult<&str, Layer> {
     let (input, args) = separated_list0(
         ws(char(',')),
         alt((
-            map(preceded(ws(tag("x:")), ws(identifier)), |x| {
+            rows_arg,
+            map(preceded(ws(tag("x:")), ws(column_ref)), |x| {
                 ("x", ArgValue::ColumnName(x))
             }),
-            map(preceded(ws(tag("y:")), ws(identifier)), |y| {
+            map(preceded(ws(tag("y:")), ws(column_ref)), |y| {
                 ("y", ArgValue::ColumnName(y))
             }),
             map(preceded(ws(tag("baseline:")), ws(number_literal)), |b| {
@@ -304,19 +406,19 @@ pub fn parse_spike(input: &str) -> IResult<&str, Layer> {
             map(preceded(ws(tag("color:")), ws(string_literal)), |c| {
                 ("color", ArgValue::ColorFixed(c))
             }),
-            map(preceded(ws(tag("color:")), ws(identifier)), |c| {
+            map(preceded(ws(tag("color:")), ws(mapped_column_ref)), |c| {
                 ("color", ArgValue::ColorMapped(c))
             }),
             map(preceded(ws(tag("width:")), ws(number_literal)), |w| {
                 ("width", ArgValue::NumericFixed(w))
             }),
-            map(preceded(ws(tag("width:")), ws(identifier)), |w| {
+            map(preceded(ws(tag("width:")), ws(mapped_column_ref)), |w| {
                 ("width", ArgValue::NumericMapped(w))
             }),
             map(preceded(ws(tag("alpha:")), ws(number_literal)), |a| {
                 ("alpha", ArgValue::NumericFixed(a))
             }),
-            map(preceded(ws(tag("alpha:")), ws(identifier)), |a| {
+            map(preceded(ws(tag("alpha:")), ws(mapped_column_ref)), |a| {
                 ("alpha", ArgValue::NumericMapped(a))
             }),
         )),
@@ -336,6 +438,8 @@ pub fn parse_spike(input: &str) -> IResult<&str, Layer> {
             ("width", ArgValue::NumericMapped(w)) => layer.width = Some(AestheticValue::Mapped(w)),
             ("alpha", ArgValue::NumericFixed(a)) => layer.alpha = Some(AestheticValue::Fixed(a)),
             ("alpha", ArgValue::NumericMapped(a)) => layer.alpha = Some(AestheticValue::Mapped(a)),
+            ("filter", ArgValue::Filter(f)) => layer.filter = Some(f),
+            ("data", ArgValue::ColorFixed(path)) => layer.data = Some(path),
             _ => {}
         }
     }
@@ -351,31 +455,32 @@ pub fn parse_linerange(input: &str) -> IResult<&str, Layer> {
     let (input, args) = separated_list0(
         ws(char(',')),
         alt((
-            map(preceded(ws(tag("x:")), ws(identifier)), |x| {
+            rows_arg,
+            map(preceded(ws(tag("x:")), ws(column_ref)), |x| {
                 ("x", ArgValue::ColumnName(x))
             }),
-            map(preceded(ws(tag("ymin:")), ws(identifier)), |ymin| {
+            map(preceded(ws(tag("ymin:")), ws(column_ref)), |ymin| {
                 ("ymin", ArgValue::ColumnName(ymin))
             }),
-            map(preceded(ws(tag("ymax:")), ws(identifier)), |ymax| {
+            map(preceded(ws(tag("ymax:")), ws(column_ref)), |ymax| {
                 ("ymax", ArgValue::ColumnName(ymax))
             }),
             map(preceded(ws(tag("color:")), ws(string_literal)), |c| {
                 ("color", ArgValue::ColorFixed(c))
             }),
-            map(preceded(ws(tag("color:")), ws(identifier)), |c| {
+            map(preceded(ws(tag("color:")), ws(mapped_column_ref)), |c| {
                 ("color", ArgValue::ColorMapped(c))
             }),
             map(preceded(ws(tag("width:")), ws(number_literal)), |w| {
                 ("width", ArgValue::NumericFixed(w))
             }),
-            map(preceded(ws(tag("width:")), ws(identifier)), |w| {
+            map(preceded(ws(tag("width:")), ws(mapped_column_ref)), |w| {
                 ("width", ArgValue::NumericMapped(w))
             }),
             map(preceded(ws(tag("alpha:")), ws(number_literal)), |a| {
                 ("alpha", ArgValue::NumericFixed(a))
             }),
-            map(preceded(ws(tag("alpha:")), ws(identifier)), |a| {
+            map(preceded(ws(tag("alpha:")), ws(mapped_column_ref)), |a| {
                 ("alpha", ArgValue::NumericMapped(a))
             }),
         )),
@@ -395,6 +500,8 @@ pub fn parse_linerange(input: &str) -> IResult<&str, Layer> {
             ("width", ArgValue::NumericMapped(w)) => layer.width = Some(AestheticValue::Mapped(w)),
             ("alpha", ArgValue::NumericFixed(a)) => layer.alpha = Some(AestheticValue::Fixed(a)),
             ("alpha", ArgValue::NumericMapped(a)) => layer.alpha = Some(AestheticValue::Mapped(a)),
+            ("filter", ArgValue::Filter(f)) => layer.filter = Some(f),
+            ("data", ArgValue::ColorFixed(path)) => layer.data = Some(path),
             _ => {}
         }
     }
@@ -410,34 +517,36 @@ pub fn parse_errorbar(input: &str) -> IResult<&str, Layer> {
     let (input, args) = separated_list0(
         ws(char(',')),
         alt((
-            map(preceded(ws(tag("x:")), ws(identifier)), |x| {
+            rows_arg,
+            map(preceded(ws(tag("x:")), ws(column_ref)), |x| {
                 ("x", ArgValue::ColumnName(x))
             }),
-            map(preceded(ws(tag("ymin:")), ws(identifier)), |ymin| {
+            map(preceded(ws(tag("ymin:")), ws(column_ref)), |ymin| {
                 ("ymin", ArgValue::ColumnName(ymin))
             }),
-            map(preceded(ws(tag("ymax:")), ws(identifier)), |ymax| {
+            map(preceded(ws(tag("ymax:")), ws(column_ref)), |ymax| {
                 ("ymax", ArgValue::ColumnName(ymax))
             }),
             map(preceded(ws(tag("color:")), ws(string_literal)), |c| {
                 ("color", ArgValue::ColorFixed(c))
             }),
-            map(preceded(ws(tag("color:")), ws(identifier)), |c| {
+            map(preceded(ws(tag("color:")), ws(mapped_column_ref)), |c| {
                 ("color", ArgValue::ColorMapped(c))
             }),
             map(preceded(ws(tag("linewidth:")), ws(number_literal)), |w| {
                 ("linewidth", ArgValue::NumericFixed(w))
             }),
-            map(preceded(ws(tag("linewidth:")), ws(identifier)), |w| {
-                ("linewidth", ArgValue::NumericMapped(w))
-            }),
+            map(
+                preceded(ws(tag("linewidth:")), ws(mapped_column_ref)),
+                |w| ("linewidth", ArgValue::NumericMapped(w)),
+            ),
             map(preceded(ws(tag("width:")), ws(number_literal)), |w| {
                 ("width", ArgValue::NumericFixed(w))
             }),
             map(preceded(ws(tag("alpha:")), ws(number_literal)), |a| {
                 ("alpha", ArgValue::NumericFixed(a))
             }),
-            map(preceded(ws(tag("alpha:")), ws(identifier)), |a| {
+            map(preceded(ws(tag("alpha:")), ws(mapped_column_ref)), |a| {
                 ("alpha", ArgValue::NumericMapped(a))
             }),
         )),
@@ -462,6 +571,8 @@ pub fn parse_errorbar(input: &str) -> IResult<&str, Layer> {
             ("width", ArgValue::NumericFixed(w)) => layer.width = w,
             ("alpha", ArgValue::NumericFixed(a)) => layer.alpha = Some(AestheticValue::Fixed(a)),
             ("alpha", ArgValue::NumericMapped(a)) => layer.alpha = Some(AestheticValue::Mapped(a)),
+            ("filter", ArgValue::Filter(f)) => layer.filter = Some(f),
+            ("data", ArgValue::ColorFixed(path)) => layer.data = Some(path),
             _ => {}
         }
     }
@@ -477,46 +588,47 @@ pub fn parse_pointrange(input: &str) -> IResult<&str, Layer> {
     let (input, args) = separated_list0(
         ws(char(',')),
         alt((
-            map(preceded(ws(tag("x:")), ws(identifier)), |x| {
+            rows_arg,
+            map(preceded(ws(tag("x:")), ws(column_ref)), |x| {
                 ("x", ArgValue::ColumnName(x))
             }),
-            map(preceded(ws(tag("ymin:")), ws(identifier)), |ymin| {
+            map(preceded(ws(tag("ymin:")), ws(column_ref)), |ymin| {
                 ("ymin", ArgValue::ColumnName(ymin))
             }),
-            map(preceded(ws(tag("ymax:")), ws(identifier)), |ymax| {
+            map(preceded(ws(tag("ymax:")), ws(column_ref)), |ymax| {
                 ("ymax", ArgValue::ColumnName(ymax))
             }),
-            map(preceded(ws(tag("y:")), ws(identifier)), |y| {
+            map(preceded(ws(tag("y:")), ws(column_ref)), |y| {
                 ("y", ArgValue::ColumnName(y))
             }),
             map(preceded(ws(tag("color:")), ws(string_literal)), |c| {
                 ("color", ArgValue::ColorFixed(c))
             }),
-            map(preceded(ws(tag("color:")), ws(identifier)), |c| {
+            map(preceded(ws(tag("color:")), ws(mapped_column_ref)), |c| {
                 ("color", ArgValue::ColorMapped(c))
             }),
             map(preceded(ws(tag("width:")), ws(number_literal)), |w| {
                 ("width", ArgValue::NumericFixed(w))
             }),
-            map(preceded(ws(tag("width:")), ws(identifier)), |w| {
+            map(preceded(ws(tag("width:")), ws(mapped_column_ref)), |w| {
                 ("width", ArgValue::NumericMapped(w))
             }),
             map(preceded(ws(tag("size:")), ws(number_literal)), |s| {
                 ("size", ArgValue::NumericFixed(s))
             }),
-            map(preceded(ws(tag("size:")), ws(identifier)), |s| {
+            map(preceded(ws(tag("size:")), ws(mapped_column_ref)), |s| {
                 ("size", ArgValue::NumericMapped(s))
             }),
             map(preceded(ws(tag("shape:")), ws(string_literal)), |sh| {
                 ("shape", ArgValue::ColorFixed(sh))
             }),
-            map(preceded(ws(tag("shape:")), ws(identifier)), |sh| {
+            map(preceded(ws(tag("shape:")), ws(mapped_column_ref)), |sh| {
                 ("shape", ArgValue::ColorMapped(sh))
             }),
             map(preceded(ws(tag("alpha:")), ws(number_literal)), |a| {
                 ("alpha", ArgValue::NumericFixed(a))
             }),
-            map(preceded(ws(tag("alpha:")), ws(identifier)), |a| {
+            map(preceded(ws(tag("alpha:")), ws(mapped_column_ref)), |a| {
                 ("alpha", ArgValue::NumericMapped(a))
             }),
         )),
@@ -541,6 +653,8 @@ pub fn parse_pointrange(input: &str) -> IResult<&str, Layer> {
             ("shape", ArgValue::ColorMapped(sh)) => layer.shape = Some(AestheticValue::Mapped(sh)),
             ("alpha", ArgValue::NumericFixed(a)) => layer.alpha = Some(AestheticValue::Fixed(a)),
             ("alpha", ArgValue::NumericMapped(a)) => layer.alpha = Some(AestheticValue::Mapped(a)),
+            ("filter", ArgValue::Filter(f)) => layer.filter = Some(f),
+            ("data", ArgValue::ColorFixed(path)) => layer.data = Some(path),
             _ => {}
         }
     }
@@ -556,22 +670,23 @@ pub fn parse_crossbar(input: &str) -> IResult<&str, Layer> {
     let (input, args) = separated_list0(
         ws(char(',')),
         alt((
-            map(preceded(ws(tag("x:")), ws(identifier)), |x| {
+            rows_arg,
+            map(preceded(ws(tag("x:")), ws(column_ref)), |x| {
                 ("x", ArgValue::ColumnName(x))
             }),
-            map(preceded(ws(tag("ymin:")), ws(identifier)), |ymin| {
+            map(preceded(ws(tag("ymin:")), ws(column_ref)), |ymin| {
                 ("ymin", ArgValue::ColumnName(ymin))
             }),
-            map(preceded(ws(tag("ymax:")), ws(identifier)), |ymax| {
+            map(preceded(ws(tag("ymax:")), ws(column_ref)), |ymax| {
                 ("ymax", ArgValue::ColumnName(ymax))
             }),
-            map(preceded(ws(tag("y:")), ws(identifier)), |y| {
+            map(preceded(ws(tag("y:")), ws(column_ref)), |y| {
                 ("y", ArgValue::ColumnName(y))
             }),
             map(preceded(ws(tag("color:")), ws(string_literal)), |c| {
                 ("color", ArgValue::ColorFixed(c))
             }),
-            map(preceded(ws(tag("color:")), ws(identifier)), |c| {
+            map(preceded(ws(tag("color:")), ws(mapped_column_ref)), |c| {
                 ("color", ArgValue::ColorMapped(c))
             }),
             map(preceded(ws(tag("width:")), ws(number_literal)), |w| {
@@ -580,13 +695,14 @@ pub fn parse_crossbar(input: &str) -> IResult<&str, Layer> {
             map(preceded(ws(tag("linewidth:")), ws(number_literal)), |w| {
                 ("linewidth", ArgValue::NumericFixed(w))
             }),
-            map(preceded(ws(tag("linewidth:")), ws(identifier)), |w| {
-                ("linewidth", ArgValue::NumericMapped(w))
-            }),
+            map(
+                preceded(ws(tag("linewidth:")), ws(mapped_column_ref)),
+                |w| ("linewidth", ArgValue::NumericMapped(w)),
+            ),
             map(preceded(ws(tag("alpha:")), ws(number_literal)), |a| {
                 ("alpha", ArgValue::NumericFixed(a))
             }),
-            map(preceded(ws(tag("alpha:")), ws(identifier)), |a| {
+            map(preceded(ws(tag("alpha:")), ws(mapped_column_ref)), |a| {
                 ("alpha", ArgValue::NumericMapped(a))
             }),
         )),
@@ -612,6 +728,8 @@ pub fn parse_crossbar(input: &str) -> IResult<&str, Layer> {
             }
             ("alpha", ArgValue::NumericFixed(a)) => layer.alpha = Some(AestheticValue::Fixed(a)),
             ("alpha", ArgValue::NumericMapped(a)) => layer.alpha = Some(AestheticValue::Mapped(a)),
+            ("filter", ArgValue::Filter(f)) => layer.filter = Some(f),
+            ("data", ArgValue::ColorFixed(path)) => layer.data = Some(path),
             _ => {}
         }
     }
@@ -752,7 +870,9 @@ pub fn parse_abline(input: &str) -> IResult<&str, Layer> {
     Ok((input, Layer::AbLine(layer)))
 }
 
-/// Parse a fixed segment from (x, y) to (xend, yend).
+/// Parse a segment from (x, y) to (xend, yend)
+/// Format: segment(x: 1, y: 2, xend: 3, yend: 4) for one fixed segment, or
+/// segment(xend: x1, yend: y1, arrow: true) for one segment per row
 pub fn parse_segment(input: &str) -> IResult<&str, Layer> {
     let (input, _) = ws(tag("segment"))(input)?;
     let (input, _) = ws(char('('))(input)?;
@@ -760,21 +880,37 @@ pub fn parse_segment(input: &str) -> IResult<&str, Layer> {
     let (input, args) = separated_list0(
         ws(char(',')),
         alt((
+            // Each endpoint coordinate can be a number or a column
             map(preceded(ws(tag("xend:")), ws(number_literal)), |xend| {
                 ("xend", ArgValue::NumericFixed(xend))
             }),
+            map(preceded(ws(tag("xend:")), ws(mapped_column_ref)), |xend| {
+                ("xend", ArgValue::NumericMapped(xend))
+            }),
             map(preceded(ws(tag("yend:")), ws(number_literal)), |yend| {
                 ("yend", ArgValue::NumericFixed(yend))
             }),
+            map(preceded(ws(tag("yend:")), ws(mapped_column_ref)), |yend| {
+                ("yend", ArgValue::NumericMapped(yend))
+            }),
             map(preceded(ws(tag("x:")), ws(number_literal)), |x| {
                 ("x", ArgValue::NumericFixed(x))
             }),
+            map(preceded(ws(tag("x:")), ws(mapped_column_ref)), |x| {
+                ("x", ArgValue::NumericMapped(x))
+            }),
             map(preceded(ws(tag("y:")), ws(number_literal)), |y| {
                 ("y", ArgValue::NumericFixed(y))
             }),
+            map(preceded(ws(tag("y:")), ws(mapped_column_ref)), |y| {
+                ("y", ArgValue::NumericMapped(y))
+            }),
             map(preceded(ws(tag("color:")), ws(string_literal)), |c| {
                 ("color", ArgValue::ColorFixed(c))
             }),
+            map(preceded(ws(tag("color:")), ws(mapped_column_ref)), |c| {
+                ("color", ArgValue::ColorMapped(c))
+            }),
             map(preceded(ws(tag("width:")), ws(number_literal)), |w| {
                 ("width", ArgValue::NumericFixed(w))
             }),
@@ -784,19 +920,29 @@ pub fn parse_segment(input: &str) -> IResult<&str, Layer> {
             map(preceded(ws(tag("label:")), ws(string_literal)), |label| {
                 ("label", ArgValue::ColorFixed(label))
             }),
+            map(preceded(ws(tag("arrow:")), ws(bool_literal)), |arrow| {
+                ("arrow", ArgValue::Bool(arrow))
+            }),
         )),
     )(input)?;
 
     let (input, _) = ws(char(')'))(input)?;
 
+    let coordinate = |val| match val {
+        ArgValue::NumericFixed(v) => Some(AestheticValue::Fixed(v)),
+        ArgValue::NumericMapped(col) => Some(AestheticValue::Mapped(col)),
+        _ => None,
+    };
     let mut layer = SegmentLayer::default();
     for (key, val) in args {
         match (key, val) {
-            ("x", ArgValue::NumericFixed(x)) => layer.x = x,
-            ("y", ArgValue::NumericFixed(y)) => layer.y = y,
-            ("xend", ArgValue::NumericFixed(xend)) => layer.xend = xend,
-            ("yend", ArgValue::NumericFixed(yend)) => layer.yend = yend,
-            ("color", ArgValue::ColorFixed(c)) => layer.color = Some(c),
+            ("x", val) => layer.x = coordinate(val),
+            ("y", val) => layer.y = coordinate(val),
+            ("xend", val) => layer.xend = coordinate(val),
+            ("yend", val) => layer.yend = coordinate(val),
+            ("color", ArgValue::ColorFixed(c)) => layer.color = Some(AestheticValue::Fixed(c)),
+            ("color", ArgValue::ColorMapped(c)) => layer.color = Some(AestheticValue::Mapped(c)),
+            ("arrow", ArgValue::Bool(arrow)) => layer.arrow = arrow,
             ("width", ArgValue::NumericFixed(w)) => layer.width = Some(w),
             ("alpha", ArgValue::NumericFixed(a)) => layer.alpha = Some(a),
             ("label", ArgValue::ColorFixed(label)) => layer.label = Some(label),
@@ -807,76 +953,237 @@ pub fn parse_segment(input: &str) -> IResult<&str, Layer> {
     Ok((input, Layer::Segment(layer)))
 }
 
-/// Parse a point geometry
-/// Format: point() or point(size: 5, color: "blue", ...) or point(color: region, size: metric)
-pub fn parse_point(input: &str) -> IResult<&str, Layer> {
-    let (input, _) = ws(tag("point"))(input)?;
+/// Parse a text label geometry
+/// Format: text(label: country) or text(label: country, size: 10, nudge_y: 0.5, ...)
+pub fn parse_text(input: &str) -> IResult<&str, Layer> {
+    let (input, _) = ws(tag("text"))(input)?;
     let (input, _) = ws(char('('))(input)?;
 
-    // Parse optional named arguments
     let (input, args) = separated_list0(
         ws(char(',')),
         alt((
-            // x: can be column
-            map(preceded(ws(tag("x:")), ws(identifier)), |x| {
+            rows_arg,
+            map(preceded(ws(tag("x:")), ws(column_ref)), |x| {
                 ("x", ArgValue::ColumnName(x))
             }),
-            // y: can be column
-            map(preceded(ws(tag("y:")), ws(identifier)), |y| {
+            map(preceded(ws(tag("y:")), ws(column_ref)), |y| {
                 ("y", ArgValue::ColumnName(y))
             }),
-            // color: can be "blue" (literal), region (column)
+            // label: always a column
+            map(preceded(ws(tag("label:")), ws(column_ref)), |l| {
+                ("label", ArgValue::ColumnName(l))
+            }),
             map(preceded(ws(tag("color:")), ws(string_literal)), |c| {
                 ("color", ArgValue::ColorFixed(c))
             }),
-            map(preceded(ws(tag("color:")), ws(identifier)), |c| {
+            map(preceded(ws(tag("color:")), ws(mapped_column_ref)), |c| {
                 ("color", ArgValue::ColorMapped(c))
             }),
-            // size: can be 5.0 (literal), size_col (column)
             map(preceded(ws(tag("size:")), ws(number_literal)), |s| {
                 ("size", ArgValue::NumericFixed(s))
             }),
-            map(preceded(ws(tag("size:")), ws(identifier)), |s| {
-                ("size", ArgValue::NumericMapped(s))
-            }),
-            // shape: can be "circle" (literal), shape_col (column)
-            map(preceded(ws(tag("shape:")), ws(string_literal)), |sh| {
-                ("shape", ArgValue::ColorFixed(sh))
-            }),
-            map(preceded(ws(tag("shape:")), ws(identifier)), |sh| {
-                ("shape", ArgValue::ColorMapped(sh))
-            }),
-            // alpha: can be 0.8 (literal), alpha_col (column)
             map(preceded(ws(tag("alpha:")), ws(number_literal)), |a| {
                 ("alpha", ArgValue::NumericFixed(a))
             }),
-            map(preceded(ws(tag("alpha:")), ws(identifier)), |a| {
+            map(preceded(ws(tag("alpha:")), ws(mapped_column_ref)), |a| {
                 ("alpha", ArgValue::NumericMapped(a))
             }),
+            map(preceded(ws(tag("nudge_x:")), ws(number_literal)), |n| {
+                ("nudge_x", ArgValue::NumericFixed(n))
+            }),
+            map(preceded(ws(tag("nudge_y:")), ws(number_literal)), |n| {
+                ("nudge_y", ArgValue::NumericFixed(n))
+            }),
         )),
     )(input)?;
 
     let (input, _) = ws(char(')'))(input)?;
 
-    let mut layer = PointLayer::default();
+    let mut layer = TextLayer::default();
+    let mut has_label = false;
 
     for (key, val) in args {
         match (key, val) {
             ("x", ArgValue::ColumnName(x)) => layer.x = Some(x),
             ("y", ArgValue::ColumnName(y)) => layer.y = Some(y),
+            ("label", ArgValue::ColumnName(l)) => {
+                layer.label = l;
+                has_label = true;
+            }
             ("color", ArgValue::ColorFixed(c)) => layer.color = Some(AestheticValue::Fixed(c)),
             ("color", ArgValue::ColorMapped(c)) => layer.color = Some(AestheticValue::Mapped(c)),
-            ("size", ArgValue::NumericFixed(s)) => layer.size = Some(AestheticValue::Fixed(s)),
-            ("size", ArgValue::NumericMapped(s)) => layer.size = Some(AestheticValue::Mapped(s)),
-            ("shape", ArgValue::ColorFixed(sh)) => layer.shape = Some(AestheticValue::Fixed(sh)),
-            ("shape", ArgValue::ColorMapped(sh)) => layer.shape = Some(AestheticValue::Mapped(sh)),
+            ("size", ArgValue::NumericFixed(s)) => layer.size = Some(s),
             ("alpha", ArgValue::NumericFixed(a)) => layer.alpha = Some(AestheticValue::Fixed(a)),
             ("alpha", ArgValue::NumericMapped(a)) => layer.alpha = Some(AestheticValue::Mapped(a)),
+            ("nudge_x", ArgValue::NumericFixed(n)) => layer.nudge_x = n,
+            ("nudge_y", ArgValue::NumericFixed(n)) => layer.nudge_y = n,
+            ("filter", ArgValue::Filter(f)) => layer.filter = Some(f),
+            ("data", ArgValue::ColorFixed(path)) => layer.data = Some(path),
             _ => {}
         }
     }
 
-    Ok((input, Layer::Point(layer)))
+    // label: is required
+    if !has_label {
+        return Err(nom::Err::Failure(Error::new(input, ErrorKind::Verify)));
+    }
+
+    Ok((input, Layer::Text(layer)))
+}
+
+/// Parse a point geometry
+/// Format: point() or point(size: 5, color: "blue", ...) or point(color: region, size: metric)
+pub fn parse_point(input: &str) -> IResult<&str, Layer> {
+    parse_point_geom("point", PointPosition::Identity)(input)
+}
+
+/// Parse a jittered point geometry: shorthand for point(position: "jitter", ...)
+/// Format: jitter() or jitter(width: 0.2, color: region, ...)
+pub fn parse_jitter(input: &str) -> IResult<&str, Layer> {
+    parse_point_geom("jitter", PointPosition::Jitter)(input)
+}
+
+/// Point arguments shared by point() and jitter(); argument validation keeps
+/// `position:`/`jitter_width:` on point() and `width:` on jitter()
+fn parse_point_geom(
+    name: &'static str,
+    position: PointPosition,
+) -> impl FnMut(&str) -> IResult<&str, Layer> {
+    move |input| {
+        let (input, _) = ws(tag(name))(input)?;
+        let (input, _) = ws(char('('))(input)?;
+
+        // Parse optional named arguments
+        let (input, args) = separated_list0(
+            ws(char(',')),
+            alt((
+                rows_arg,
+                // x: can be column
+                map(preceded(ws(tag("x:")), ws(column_ref)), |x| {
+                    ("x", ArgValue::ColumnName(x))
+                }),
+                // y: can be column
+                map(preceded(ws(tag("y:")), ws(column_ref)), |y| {
+                    ("y", ArgValue::ColumnName(y))
+                }),
+                // color: can be "blue" (literal), region (column)
+                map(preceded(ws(tag("color:")), ws(string_literal)), |c| {
+                    ("color", ArgValue::ColorFixed(c))
+                }),
+                map(preceded(ws(tag("color:")), ws(mapped_column_ref)), |c| {
+                    ("color", ArgValue::ColorMapped(c))
+                }),
+                // size: can be 5.0 (literal), size_col (column)
+                map(preceded(ws(tag("size:")), ws(number_literal)), |s| {
+                    ("size", ArgValue::NumericFixed(s))
+                }),
+                map(preceded(ws(tag("size:")), ws(mapped_column_ref)), |s| {
+                    ("size", ArgValue::NumericMapped(s))
+                }),
+                // shape: can be "circle" (literal), shape_col (column)
+                map(preceded(ws(tag("shape:")), ws(string_literal)), |sh| {
+                    ("shape", ArgValue::ColorFixed(sh))
+                }),
+                map(preceded(ws(tag("shape:")), ws(mapped_column_ref)), |sh| {
+                    ("shape", ArgValue::ColorMapped(sh))
+                }),
+                // alpha: can be 0.8 (literal), alpha_col (column)
+                map(preceded(ws(tag("alpha:")), ws(number_literal)), |a| {
+                    ("alpha", ArgValue::NumericFixed(a))
+                }),
+                map(preceded(ws(tag("alpha:")), ws(mapped_column_ref)), |a| {
+                    ("alpha", ArgValue::NumericMapped(a))
+                }),
+                // fade_by: always a column
+                map(preceded(ws(tag("fade_by:")), ws(column_ref)), |f| {
+                    ("fade_by", ArgValue::ColumnName(f))
+                }),
+                // fade: always a string literal
+                map(preceded(ws(tag("fade:")), ws(string_literal)), |f| {
+                    ("fade", ArgValue::ColorFixed(f))
+                }),
+                // position: "identity", "jitter", or "dodge"
+                map(preceded(ws(tag("position:")), ws(string_literal)), |p| {
+                    ("position", ArgValue::ColorFixed(p))
+                }),
+                // jitter_width: (point) and width: (jitter) set the jitter span
+                map(
+                    preceded(ws(tag("jitter_width:")), ws(number_literal)),
+                    |w| ("jitter_width", ArgValue::NumericFixed(w)),
+                ),
+                map(preceded(ws(tag("width:")), ws(number_literal)), |w| {
+                    ("jitter_width", ArgValue::NumericFixed(w))
+                }),
+                // fill: always a color literal; stroke: the outline width
+                map(preceded(ws(tag("fill:")), ws(string_literal)), |f| {
+                    ("fill", ArgValue::ColorFixed(f))
+                }),
+                map(preceded(ws(tag("stroke:")), ws(number_literal)), |s| {
+                    ("stroke", ArgValue::NumericFixed(s))
+                }),
+                map(preceded(ws(tag("fast:")), ws(bool_literal)), |f| {
+                    ("fast", ArgValue::Bool(f))
+                }),
+            )),
+        )(input)?;
+
+        let (input, _) = ws(char(')'))(input)?;
+
+        let mut layer = PointLayer {
+            position,
+            ..Default::default()
+        };
+
+        for (key, val) in args {
+            match (key, val) {
+                ("x", ArgValue::ColumnName(x)) => layer.x = Some(x),
+                ("y", ArgValue::ColumnName(y)) => layer.y = Some(y),
+                ("color", ArgValue::ColorFixed(c)) => layer.color = Some(AestheticValue::Fixed(c)),
+                ("color", ArgValue::ColorMapped(c)) => {
+                    layer.color = Some(AestheticValue::Mapped(c))
+                }
+                ("size", ArgValue::NumericFixed(s)) => layer.size = Some(AestheticValue::Fixed(s)),
+                ("size", ArgValue::NumericMapped(s)) => {
+                    layer.size = Some(AestheticValue::Mapped(s))
+                }
+                ("shape", ArgValue::ColorFixed(sh)) => {
+                    layer.shape = Some(AestheticValue::Fixed(sh))
+                }
+                ("shape", ArgValue::ColorMapped(sh)) => {
+                    layer.shape = Some(AestheticValue::Mapped(sh))
+                }
+                ("alpha", ArgValue::NumericFixed(a)) => {
+                    layer.alpha = Some(AestheticValue::Fixed(a))
+                }
+                ("alpha", ArgValue::NumericMapped(a)) => {
+                    layer.alpha = Some(AestheticValue::Mapped(a))
+                }
+                ("fade_by", ArgValue::ColumnName(f)) => layer.fade_by = Some(f),
+                ("fade", ArgValue::ColorFixed(f)) => {
+                    layer.fade = match f.as_str() {
+                        "newest" => FadeDirection::Newest,
+                        _ => FadeDirection::Oldest, // default for unknown values
+                    };
+                }
+                ("position", ArgValue::ColorFixed(p)) => {
+                    layer.position = match p.as_str() {
+                        "jitter" => PointPosition::Jitter,
+                        "dodge" => PointPosition::Dodge,
+                        _ => PointPosition::Identity, // default for unknown values
+                    };
+                }
+                ("jitter_width", ArgValue::NumericFixed(w)) => layer.jitter_width = Some(w),
+                ("fill", ArgValue::ColorFixed(f)) => layer.fill = Some(f),
+                ("stroke", ArgValue::NumericFixed(s)) => layer.stroke = Some(s),
+                ("fast", ArgValue::Bool(f)) => layer.fast = f,
+                ("filter", ArgValue::Filter(f)) => layer.filter = Some(f),
+                ("data", ArgValue::ColorFixed(path)) => layer.data = Some(path),
+                _ => {}
+            }
+        }
+
+        Ok((input, Layer::Point(layer)))
+    }
 }
 
 /// Parse a bar geometry
@@ -889,39 +1196,60 @@ pub fn parse_bar(input: &str) -> IResult<&str, Layer> {
     let (input, args) = separated_list0(
         ws(char(',')),
         alt((
+            rows_arg,
             // x: can be column
-            map(preceded(ws(tag("x:")), ws(identifier)), |x| {
+            map(preceded(ws(tag("x:")), ws(column_ref)), |x| {
                 ("x", ArgValue::ColumnName(x))
             }),
             // y: can be column
-            map(preceded(ws(tag("y:")), ws(identifier)), |y| {
+            map(preceded(ws(tag("y:")), ws(column_ref)), |y| {
                 ("y", ArgValue::ColumnName(y))
             }),
             // color: can be "red" (literal), region (column)
             map(preceded(ws(tag("color:")), ws(string_literal)), |c| {
                 ("color", ArgValue::ColorFixed(c))
             }),
-            map(preceded(ws(tag("color:")), ws(identifier)), |c| {
+            map(preceded(ws(tag("color:")), ws(mapped_column_ref)), |c| {
                 ("color", ArgValue::ColorMapped(c))
             }),
             // width: can be 0.8 (literal), width_col (column)
             map(preceded(ws(tag("width:")), ws(number_literal)), |w| {
                 ("width", ArgValue::NumericFixed(w))
             }),
-            map(preceded(ws(tag("width:")), ws(identifier)), |w| {
+            map(preceded(ws(tag("width:")), ws(mapped_column_ref)), |w| {
                 ("width", ArgValue::NumericMapped(w))
             }),
             // alpha: can be 0.7 (literal), alpha_col (column)
             map(preceded(ws(tag("alpha:")), ws(number_literal)), |a| {
                 ("alpha", ArgValue::NumericFixed(a))
             }),
-            map(preceded(ws(tag("alpha:")), ws(identifier)), |a| {
+            map(preceded(ws(tag("alpha:")), ws(mapped_column_ref)), |a| {
                 ("alpha", ArgValue::NumericMapped(a))
             }),
             // position: always a string literal
             map(preceded(ws(tag("position:")), ws(string_literal)), |p| {
                 ("position", ArgValue::ColorFixed(p))
             }),
+            map(
+                preceded(
+                    ws(tag("stat:")),
+                    ws(verify(string_literal, |s: &str| {
+                        is_allowed_value("bar", "stat", s)
+                    })),
+                ),
+                |s| ("stat", ArgValue::ColorFixed(s)),
+            ),
+            map(preceded(ws(tag("x_continuous:")), ws(bool_literal)), |c| {
+                ("x_continuous", ArgValue::Bool(c))
+            }),
+            // border: always a color literal; the fill comes from color:
+            map(preceded(ws(tag("border:")), ws(string_literal)), |b| {
+                ("border", ArgValue::ColorFixed(b))
+            }),
+            map(
+                preceded(ws(tag("border_width:")), ws(number_literal)),
+                |w| ("border_width", ArgValue::NumericFixed(w)),
+            ),
         )),
     )(input)?;
 
@@ -943,10 +1271,34 @@ pub fn parse_bar(input: &str) -> IResult<&str, Layer> {
                 layer.position = match p.as_str() {
                     "dodge" => BarPosition::Dodge,
                     "stack" => BarPosition::Stack,
+                    "fill" => BarPosition::Fill,
                     "identity" => BarPosition::Identity,
                     _ => BarPosition::Identity, // default for unknown values
                 };
             }
+            ("stat", ArgValue::ColorFixed(s)) => {
+                layer.stat = match s.as_str() {
+                    "count" => Stat::Count,
+                    "sum" => Stat::Summary {
+                        fun: Aggregate::Sum,
+                    },
+                    "mean" => Stat::Summary {
+                        fun: Aggregate::Mean,
+                    },
+                    "min" => Stat::Summary {
+                        fun: Aggregate::Min,
+                    },
+                    "max" => Stat::Summary {
+                        fun: Aggregate::Max,
+                    },
+                    _ => Stat::Identity, // "identity"; the parser refuses unknown values
+                };
+            }
+            ("x_continuous", ArgValue::Bool(c)) => layer.x_continuous = c,
+            ("border", ArgValue::ColorFixed(b)) => layer.border = Some(b),
+            ("border_width", ArgValue::NumericFixed(w)) => layer.border_width = Some(w),
+            ("filter", ArgValue::Filter(f)) => layer.filter = Some(f),
+            ("data", ArgValue::ColorFixed(path)) => layer.data = Some(path),
             _ => {}
         }
     }
@@ -962,30 +1314,31 @@ pub fn parse_ribbon(input: &str) -> IResult<&str, Layer> {
     let (input, args) = separated_list0(
         ws(char(',')),
         alt((
+            rows_arg,
             // x: can be column
-            map(preceded(ws(tag("x:")), ws(identifier)), |x| {
+            map(preceded(ws(tag("x:")), ws(column_ref)), |x| {
                 ("x", ArgValue::ColumnName(x))
             }),
             // ymin: can be column
-            map(preceded(ws(tag("ymin:")), ws(identifier)), |y| {
+            map(preceded(ws(tag("ymin:")), ws(column_ref)), |y| {
                 ("ymin", ArgValue::ColumnName(y))
             }),
             // ymax: can be column
-            map(preceded(ws(tag("ymax:")), ws(identifier)), |y| {
+            map(preceded(ws(tag("ymax:")), ws(column_ref)), |y| {
                 ("ymax", ArgValue::ColumnName(y))
             }),
             // color: can be "literal", column
             map(preceded(ws(tag("color:")), ws(string_literal)), |c| {
                 ("color", ArgValue::ColorFixed(c))
             }),
-            map(preceded(ws(tag("color:")), ws(identifier)), |c| {
+            map(preceded(ws(tag("color:")), ws(mapped_column_ref)), |c| {
                 ("color", ArgValue::ColorMapped(c))
             }),
             // alpha: can be number, column
             map(preceded(ws(tag("alpha:")), ws(number_literal)), |a| {
                 ("alpha", ArgValue::NumericFixed(a))
             }),
-            map(preceded(ws(tag("alpha:")), ws(identifier)), |a| {
+            map(preceded(ws(tag("alpha:")), ws(mapped_column_ref)), |a| {
                 ("alpha", ArgValue::NumericMapped(a))
             }),
         )),
@@ -1004,6 +1357,8 @@ pub fn parse_ribbon(input: &str) -> IResult<&str, Layer> {
             ("color", ArgValue::ColorMapped(c)) => layer.color = Some(AestheticValue::Mapped(c)),
             ("alpha", ArgValue::NumericFixed(a)) => layer.alpha = Some(AestheticValue::Fixed(a)),
             ("alpha", ArgValue::NumericMapped(a)) => layer.alpha = Some(AestheticValue::Mapped(a)),
+            ("filter", ArgValue::Filter(f)) => layer.filter = Some(f),
+            ("data", ArgValue::ColorFixed(path)) => layer.data = Some(path),
             _ => {}
         }
     }
@@ -1015,14 +1370,31 @@ pub fn parse_ribbon(input: &str) -> IResult<&str, Layer> {
 pub fn parse_histogram(input: &str) -> IResult<&str, Layer> {
     let (input, _) = ws(tag("histogram"))(input)?;
     let (input, _) = ws(char('('))(input)?;
-    // Optional bins argument
-    let (input, bins) = opt(preceded(ws(tag("bins:")), ws(number_literal)))(input)?;
+    let (input, args) = separated_list0(
+        ws(char(',')),
+        alt((
+            rows_arg,
+            map(preceded(ws(tag("bins:")), ws(number_literal)), |b| {
+                ("bins", ArgValue::NumericFixed(b))
+            }),
+        )),
+    )(input)?;
     let (input, _) = ws(char(')'))(input)?;
 
-    let mut layer = BarLayer::default();
-    layer.stat = crate::parser::ast::Stat::Bin {
-        bins: bins.unwrap_or(30.0) as usize,
+    let mut layer = BarLayer {
+        stat: crate::parser::ast::Stat::Bin { bins: 30 },
+        ..Default::default()
     };
+    for (key, val) in args {
+        match (key, val) {
+            ("bins", ArgValue::NumericFixed(b)) => {
+                layer.stat = crate::parser::ast::Stat::Bin { bins: b as usize }
+            }
+            ("filter", ArgValue::Filter(f)) => layer.filter = Some(f),
+            ("data", ArgValue::ColorFixed(path)) => layer.data = Some(path),
+            _ => {}
+        }
+    }
     Ok((input, Layer::Bar(layer)))
 }
 
@@ -1034,7 +1406,8 @@ pub fn parse_freqpoly(input: &str) -> IResult<&str, Layer> {
     let (input, args) = separated_list0(
         ws(char(',')),
         alt((
-            map(preceded(ws(tag("x:")), ws(identifier)), |x| {
+            rows_arg,
+            map(preceded(ws(tag("x:")), ws(column_ref)), |x| {
                 ("x", ArgValue::ColumnName(x))
             }),
             map(preceded(ws(tag("bins:")), ws(number_literal)), |b| {
@@ -1043,19 +1416,19 @@ pub fn parse_freqpoly(input: &str) -> IResult<&str, Layer> {
             map(preceded(ws(tag("color:")), ws(string_literal)), |c| {
                 ("color", ArgValue::ColorFixed(c))
             }),
-            map(preceded(ws(tag("color:")), ws(identifier)), |c| {
+            map(preceded(ws(tag("color:")), ws(mapped_column_ref)), |c| {
                 ("color", ArgValue::ColorMapped(c))
             }),
             map(preceded(ws(tag("width:")), ws(number_literal)), |w| {
                 ("width", ArgValue::NumericFixed(w))
             }),
-            map(preceded(ws(tag("width:")), ws(identifier)), |w| {
+            map(preceded(ws(tag("width:")), ws(mapped_column_ref)), |w| {
                 ("width", ArgValue::NumericMapped(w))
             }),
             map(preceded(ws(tag("alpha:")), ws(number_literal)), |a| {
                 ("alpha", ArgValue::NumericFixed(a))
             }),
-            map(preceded(ws(tag("alpha:")), ws(identifier)), |a| {
+            map(preceded(ws(tag("alpha:")), ws(mapped_column_ref)), |a| {
                 ("alpha", ArgValue::NumericMapped(a))
             }),
         )),
@@ -1076,6 +1449,8 @@ pub fn parse_freqpoly(input: &str) -> IResult<&str, Layer> {
             ("width", ArgValue::NumericMapped(w)) => layer.width = Some(AestheticValue::Mapped(w)),
             ("alpha", ArgValue::NumericFixed(a)) => layer.alpha = Some(AestheticValue::Fixed(a)),
             ("alpha", ArgValue::NumericMapped(a)) => layer.alpha = Some(AestheticValue::Mapped(a)),
+            ("filter", ArgValue::Filter(f)) => layer.filter = Some(f),
+            ("data", ArgValue::ColorFixed(path)) => layer.data = Some(path),
             _ => {}
         }
     }
@@ -1092,6 +1467,7 @@ pub fn parse_smooth(input: &str) -> IResult<&str, Layer> {
     let (input, args) = separated_list0(
         ws(char(',')),
         alt((
+            rows_arg,
             map(preceded(ws(tag("method:")), ws(string_literal)), |m| {
                 ("method", ArgValue::ColorFixed(m))
             }),
@@ -1101,28 +1477,28 @@ pub fn parse_smooth(input: &str) -> IResult<&str, Layer> {
             map(preceded(ws(tag("samples:")), ws(number_literal)), |s| {
                 ("samples", ArgValue::NumericFixed(s))
             }),
-            map(preceded(ws(tag("x:")), ws(identifier)), |x| {
+            map(preceded(ws(tag("x:")), ws(column_ref)), |x| {
                 ("x", ArgValue::ColumnName(x))
             }),
-            map(preceded(ws(tag("y:")), ws(identifier)), |y| {
+            map(preceded(ws(tag("y:")), ws(column_ref)), |y| {
                 ("y", ArgValue::ColumnName(y))
             }),
             map(preceded(ws(tag("color:")), ws(string_literal)), |c| {
                 ("color", ArgValue::ColorFixed(c))
             }),
-            map(preceded(ws(tag("color:")), ws(identifier)), |c| {
+            map(preceded(ws(tag("color:")), ws(mapped_column_ref)), |c| {
                 ("color", ArgValue::ColorMapped(c))
             }),
             map(preceded(ws(tag("width:")), ws(number_literal)), |w| {
                 ("width", ArgValue::NumericFixed(w))
             }),
-            map(preceded(ws(tag("width:")), ws(identifier)), |w| {
+            map(preceded(ws(tag("width:")), ws(mapped_column_ref)), |w| {
                 ("width", ArgValue::NumericMapped(w))
             }),
             map(preceded(ws(tag("alpha:")), ws(number_literal)), |a| {
                 ("alpha", ArgValue::NumericFixed(a))
             }),
-            map(preceded(ws(tag("alpha:")), ws(identifier)), |a| {
+            map(preceded(ws(tag("alpha:")), ws(mapped_column_ref)), |a| {
                 ("alpha", ArgValue::NumericMapped(a))
             }),
         )),
@@ -1147,6 +1523,8 @@ pub fn parse_smooth(input: &str) -> IResult<&str, Layer> {
             ("width", ArgValue::NumericMapped(w)) => layer.width = Some(AestheticValue::Mapped(w)),
             ("alpha", ArgValue::NumericFixed(a)) => layer.alpha = Some(AestheticValue::Fixed(a)),
             ("alpha", ArgValue::NumericMapped(a)) => layer.alpha = Some(AestheticValue::Mapped(a)),
+            ("filter", ArgValue::Filter(f)) => layer.filter = Some(f),
+            ("data", ArgValue::ColorFixed(path)) => layer.data = Some(path),
             _ => {}
         }
     }
@@ -1167,33 +1545,34 @@ pub fn parse_boxplot(input: &str) -> IResult<&str, Layer> {
     let (input, args) = separated_list0(
         ws(char(',')),
         alt((
+            rows_arg,
             // x: can be column
-            map(preceded(ws(tag("x:")), ws(identifier)), |x| {
+            map(preceded(ws(tag("x:")), ws(column_ref)), |x| {
                 ("x", ArgValue::ColumnName(x))
             }),
             // y: can be column
-            map(preceded(ws(tag("y:")), ws(identifier)), |y| {
+            map(preceded(ws(tag("y:")), ws(column_ref)), |y| {
                 ("y", ArgValue::ColumnName(y))
             }),
             // color: can be "literal", column
             map(preceded(ws(tag("color:")), ws(string_literal)), |c| {
                 ("color", ArgValue::ColorFixed(c))
             }),
-            map(preceded(ws(tag("color:")), ws(identifier)), |c| {
+            map(preceded(ws(tag("color:")), ws(mapped_column_ref)), |c| {
                 ("color", ArgValue::ColorMapped(c))
             }),
             // width: can be number, column
             map(preceded(ws(tag("width:")), ws(number_literal)), |w| {
                 ("width", ArgValue::NumericFixed(w))
             }),
-            map(preceded(ws(tag("width:")), ws(identifier)), |w| {
+            map(preceded(ws(tag("width:")), ws(mapped_column_ref)), |w| {
                 ("width", ArgValue::NumericMapped(w))
             }),
             // alpha: can be number, column
             map(preceded(ws(tag("alpha:")), ws(number_literal)), |a| {
                 ("alpha", ArgValue::NumericFixed(a))
             }),
-            map(preceded(ws(tag("alpha:")), ws(identifier)), |a| {
+            map(preceded(ws(tag("alpha:")), ws(mapped_column_ref)), |a| {
                 ("alpha", ArgValue::NumericMapped(a))
             }),
             // Outlier specific args (keep as fixed for simplicity)
@@ -1214,8 +1593,10 @@ pub fn parse_boxplot(input: &str) -> IResult<&str, Layer> {
 
     let (input, _) = ws(char(')'))(input)?;
 
-    let mut layer = BoxplotLayer::default();
-    layer.stat = crate::parser::ast::Stat::Boxplot;
+    let mut layer = BoxplotLayer {
+        stat: crate::parser::ast::Stat::Boxplot,
+        ..Default::default()
+    };
 
     for (key, val) in args {
         match (key, val) {
@@ -1230,6 +1611,8 @@ pub fn parse_boxplot(input: &str) -> IResult<&str, Layer> {
             ("outlier_color", ArgValue::ColorFixed(c)) => layer.outlier_color = Some(c),
             ("outlier_size", ArgValue::NumericFixed(s)) => layer.outlier_size = Some(s),
             ("outlier_shape", ArgValue::ColorFixed(sh)) => layer.outlier_shape = Some(sh),
+            ("filter", ArgValue::Filter(f)) => layer.filter = Some(f),
+            ("data", ArgValue::ColorFixed(path)) => layer.data = Some(path),
             _ => {}
         }
     }
@@ -1246,33 +1629,34 @@ pub fn parse_violin(input: &str) -> IResult<&str, Layer> {
     let (input, args) = separated_list0(
         ws(char(',')),
         alt((
+            rows_arg,
             // x: can be column
-            map(preceded(ws(tag("x:")), ws(identifier)), |x| {
+            map(preceded(ws(tag("x:")), ws(column_ref)), |x| {
                 ("x", ArgValue::ColumnName(x))
             }),
             // y: can be column
-            map(preceded(ws(tag("y:")), ws(identifier)), |y| {
+            map(preceded(ws(tag("y:")), ws(column_ref)), |y| {
                 ("y", ArgValue::ColumnName(y))
             }),
             // color: can be "literal", column
             map(preceded(ws(tag("color:")), ws(string_literal)), |c| {
                 ("color", ArgValue::ColorFixed(c))
             }),
-            map(preceded(ws(tag("color:")), ws(identifier)), |c| {
+            map(preceded(ws(tag("color:")), ws(mapped_column_ref)), |c| {
                 ("color", ArgValue::ColorMapped(c))
             }),
             // width: can be number, column
             map(preceded(ws(tag("width:")), ws(number_literal)), |w| {
                 ("width", ArgValue::NumericFixed(w))
             }),
-            map(preceded(ws(tag("width:")), ws(identifier)), |w| {
+            map(preceded(ws(tag("width:")), ws(mapped_column_ref)), |w| {
                 ("width", ArgValue::NumericMapped(w))
             }),
             // alpha: can be number, column
             map(preceded(ws(tag("alpha:")), ws(number_literal)), |a| {
                 ("alpha", ArgValue::NumericFixed(a))
             }),
-            map(preceded(ws(tag("alpha:")), ws(identifier)), |a| {
+            map(preceded(ws(tag("alpha:")), ws(mapped_column_ref)), |a| {
                 ("alpha", ArgValue::NumericMapped(a))
             }),
             // Violin-specific: draw_quantiles array
@@ -1298,6 +1682,8 @@ pub fn parse_violin(input: &str) -> IResult<&str, Layer> {
             ("alpha", ArgValue::NumericFixed(a)) => layer.alpha = Some(AestheticValue::Fixed(a)),
             ("alpha", ArgValue::NumericMapped(a)) => layer.alpha = Some(AestheticValue::Mapped(a)),
             ("draw_quantiles", ArgValue::NumberArray(q)) => layer.draw_quantiles = q,
+            ("filter", ArgValue::Filter(f)) => layer.filter = Some(f),
+            ("data", ArgValue::ColorFixed(path)) => layer.data = Some(path),
             _ => {}
         }
     }
@@ -1319,28 +1705,33 @@ pub fn parse_density(input: &str) -> IResult<&str, Layer> {
     let (input, args) = separated_list0(
         ws(char(',')),
         alt((
+            rows_arg,
             // x: can be column
-            map(preceded(ws(tag("x:")), ws(identifier)), |x| {
+            map(preceded(ws(tag("x:")), ws(column_ref)), |x| {
                 ("x", ArgValue::ColumnName(x))
             }),
             // color: can be "literal", column
             map(preceded(ws(tag("color:")), ws(string_literal)), |c| {
                 ("color", ArgValue::ColorFixed(c))
             }),
-            map(preceded(ws(tag("color:")), ws(identifier)), |c| {
+            map(preceded(ws(tag("color:")), ws(mapped_column_ref)), |c| {
                 ("color", ArgValue::ColorMapped(c))
             }),
             // alpha: can be number, column
             map(preceded(ws(tag("alpha:")), ws(number_literal)), |a| {
                 ("alpha", ArgValue::NumericFixed(a))
             }),
-            map(preceded(ws(tag("alpha:")), ws(identifier)), |a| {
+            map(preceded(ws(tag("alpha:")), ws(mapped_column_ref)), |a| {
                 ("alpha", ArgValue::NumericMapped(a))
             }),
             // bw: bandwidth (number only)
             map(preceded(ws(tag("bw:")), ws(number_literal)), |b| {
                 ("bw", ArgValue::NumericFixed(b))
             }),
+            // fill: shade under the curve (true/false)
+            map(preceded(ws(tag("fill:")), ws(bool_literal)), |f| {
+                ("fill", ArgValue::Bool(f))
+            }),
         )),
     )(input)?;
 
@@ -1356,6 +1747,9 @@ pub fn parse_density(input: &str) -> IResult<&str, Layer> {
             ("alpha", ArgValue::NumericFixed(a)) => layer.alpha = Some(AestheticValue::Fixed(a)),
             ("alpha", ArgValue::NumericMapped(a)) => layer.alpha = Some(AestheticValue::Mapped(a)),
             ("bw", ArgValue::NumericFixed(b)) => layer.bw = Some(b),
+            ("fill", ArgValue::Bool(f)) => layer.fill = Some(f),
+            ("filter", ArgValue::Filter(f)) => layer.filter = Some(f),
+            ("data", ArgValue::ColorFixed(path)) => layer.data = Some(path),
             _ => {}
         }
     }
@@ -1366,6 +1760,56 @@ pub fn parse_density(input: &str) -> IResult<&str, Layer> {
     Ok((input, Layer::Density(layer)))
 }
 
+/// Parse a pie geometry
+/// Format: pie() or pie(donut: 0.5, other: 0.03, alpha: 0.9)
+pub fn parse_pie(input: &str) -> IResult<&str, Layer> {
+    let (input, _) = ws(tag("pie"))(input)?;
+    let (input, _) = ws(char('('))(input)?;
+
+    let (input, args) = separated_list0(
+        ws(char(',')),
+        alt((
+            rows_arg,
+            map(preceded(ws(tag("x:")), ws(column_ref)), |x| {
+                ("x", ArgValue::ColumnName(x))
+            }),
+            map(preceded(ws(tag("y:")), ws(column_ref)), |y| {
+                ("y", ArgValue::ColumnName(y))
+            }),
+            map(preceded(ws(tag("alpha:")), ws(number_literal)), |a| {
+                ("alpha", ArgValue::NumericFixed(a))
+            }),
+            // donut: inner radius as a fraction of the outer radius
+            map(preceded(ws(tag("donut:")), ws(number_literal)), |d| {
+                ("donut", ArgValue::NumericFixed(d))
+            }),
+            // other: share of the total below which slices merge into "Other"
+            map(preceded(ws(tag("other:")), ws(number_literal)), |o| {
+                ("other", ArgValue::NumericFixed(o))
+            }),
+        )),
+    )(input)?;
+
+    let (input, _) = ws(char(')'))(input)?;
+
+    let mut layer = PieLayer::default();
+
+    for (key, val) in args {
+        match (key, val) {
+            ("x", ArgValue::ColumnName(x)) => layer.x = Some(x),
+            ("y", ArgValue::ColumnName(y)) => layer.y = Some(y),
+            ("alpha", ArgValue::NumericFixed(a)) => layer.alpha = Some(a),
+            ("donut", ArgValue::NumericFixed(d)) => layer.donut = Some(d),
+            ("other", ArgValue::NumericFixed(o)) => layer.other = Some(o),
+            ("filter", ArgValue::Filter(f)) => layer.filter = Some(f),
+            ("data", ArgValue::ColorFixed(path)) => layer.data = Some(path),
+            _ => {}
+        }
+    }
+
+    Ok((input, Layer::Pie(layer)))
+}
+
 /// Parse a heatmap geometry
 /// Format: heatmap() or heatmap(bins: 20, alpha: 0.9, fill: value_col)
 pub fn parse_heatmap(input: &str) -> IResult<&str, Layer> {
@@ -1375,16 +1819,17 @@ pub fn parse_heatmap(input: &str) -> IResult<&str, Layer> {
     let (input, args) = separated_list0(
         ws(char(',')),
         alt((
+            rows_arg,
             // x: can be column
-            map(preceded(ws(tag("x:")), ws(identifier)), |x| {
+            map(preceded(ws(tag("x:")), ws(column_ref)), |x| {
                 ("x", ArgValue::ColumnName(x))
             }),
             // y: can be column
-            map(preceded(ws(tag("y:")), ws(identifier)), |y| {
+            map(preceded(ws(tag("y:")), ws(column_ref)), |y| {
                 ("y", ArgValue::ColumnName(y))
             }),
             // fill: column name for fill values
-            map(preceded(ws(tag("fill:")), ws(identifier)), |f| {
+            map(preceded(ws(tag("fill:")), ws(column_ref)), |f| {
                 ("fill", ArgValue::ColumnName(f))
             }),
             // bins: number of bins for 2D binning
@@ -1395,7 +1840,7 @@ pub fn parse_heatmap(input: &str) -> IResult<&str, Layer> {
             map(preceded(ws(tag("alpha:")), ws(number_literal)), |a| {
                 ("alpha", ArgValue::NumericFixed(a))
             }),
-            map(preceded(ws(tag("alpha:")), ws(identifier)), |a| {
+            map(preceded(ws(tag("alpha:")), ws(mapped_column_ref)), |a| {
                 ("alpha", ArgValue::NumericMapped(a))
             }),
         )),
@@ -1414,6 +1859,8 @@ pub fn parse_heatmap(input: &str) -> IResult<&str, Layer> {
             ("bins", ArgValue::NumericFixed(b)) => bins = Some(b as usize),
             ("alpha", ArgValue::NumericFixed(a)) => layer.alpha = Some(AestheticValue::Fixed(a)),
             ("alpha", ArgValue::NumericMapped(a)) => layer.alpha = Some(AestheticValue::Mapped(a)),
+            ("filter", ArgValue::Filter(f)) => layer.filter = Some(f),
+            ("data", ArgValue::ColorFixed(path)) => layer.data = Some(path),
             _ => {}
         }
     }
@@ -1423,6 +1870,57 @@ pub fn parse_heatmap(input: &str) -> IResult<&str, Layer> {
     Ok((input, Layer::Heatmap(layer)))
 }
 
+/// Parse a tile geometry (sugar for heatmap() without 2D binning)
+/// Format: tile() or tile(fill: value_col, alpha: 0.9)
+pub fn parse_tile(input: &str) -> IResult<&str, Layer> {
+    let (input, _) = ws(tag("tile"))(input)?;
+    let (input, _) = ws(char('('))(input)?;
+
+    let (input, args) = separated_list0(
+        ws(char(',')),
+        alt((
+            rows_arg,
+            map(preceded(ws(tag("x:")), ws(column_ref)), |x| {
+                ("x", ArgValue::ColumnName(x))
+            }),
+            map(preceded(ws(tag("y:")), ws(column_ref)), |y| {
+                ("y", ArgValue::ColumnName(y))
+            }),
+            map(preceded(ws(tag("fill:")), ws(column_ref)), |f| {
+                ("fill", ArgValue::ColumnName(f))
+            }),
+            map(preceded(ws(tag("alpha:")), ws(number_literal)), |a| {
+                ("alpha", ArgValue::NumericFixed(a))
+            }),
+            map(preceded(ws(tag("alpha:")), ws(mapped_column_ref)), |a| {
+                ("alpha", ArgValue::NumericMapped(a))
+            }),
+        )),
+    )(input)?;
+
+    let (input, _) = ws(char(')'))(input)?;
+
+    let mut layer = HeatmapLayer::default();
+
+    for (key, val) in args {
+        match (key, val) {
+            ("x", ArgValue::ColumnName(x)) => layer.x = Some(x),
+            ("y", ArgValue::ColumnName(y)) => layer.y = Some(y),
+            ("fill", ArgValue::ColumnName(f)) => layer.fill = Some(f),
+            ("alpha", ArgValue::NumericFixed(a)) => layer.alpha = Some(AestheticValue::Fixed(a)),
+            ("alpha", ArgValue::NumericMapped(a)) => layer.alpha = Some(AestheticValue::Mapped(a)),
+            ("filter", ArgValue::Filter(f)) => layer.filter = Some(f),
+            ("data", ArgValue::ColorFixed(path)) => layer.data = Some(path),
+            _ => {}
+        }
+    }
+
+    // Every (x, y) pair is its own cell; no binning
+    layer.stat = crate::parser::ast::Stat::Heatmap { bins: None };
+
+    Ok((input, Layer::Heatmap(layer)))
+}
+
 /// Parse any geometry layer
 pub fn parse_geom(input: &str) -> IResult<&str, Layer> {
     alt((
@@ -1430,6 +1928,7 @@ pub fn parse_geom(input: &str) -> IResult<&str, Layer> {
             parse_line,
             parse_step,
             parse_point,
+            parse_jitter,
             parse_bar,
             parse_area,
             parse_rug,
@@ -1452,6 +1951,9 @@ pub fn parse_geom(input: &str) -> IResult<&str, Layer> {
             parse_violin,
             parse_density,
             parse_heatmap,
+            parse_tile,
+            parse_text,
+            parse_pie,
         )),
     ))(input)
 }
@@ -1459,6 +1961,7 @@ pub fn parse_geom(input: &str) -> IResult<&str, Layer> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::parser::ast::{CompareOp, FilterValue};
 
     #[test]
     fn test_parse_line_empty() {
@@ -1487,6 +1990,64 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_line_linetype() {
+        let (_, layer) = parse_line(r#"line(linetype: "dashed", width: 2)"#).unwrap();
+        let Layer::Line(l) = layer else {
+            panic!("Expected Line layer");
+        };
+        assert_eq!(
+            l.linetype,
+            Some(AestheticValue::Fixed("dashed".to_string()))
+        );
+        assert_eq!(l.width, Some(AestheticValue::Fixed(2.0)));
+
+        let (_, layer) = parse_line("line(linetype: region)").unwrap();
+        let Layer::Line(l) = layer else {
+            panic!("Expected Line layer");
+        };
+        assert_eq!(l.linetype, Some(AestheticValue::Mapped("region".into())));
+
+        // Only the drawn dash patterns; validate_arguments lists them
+        assert!(parse_line(r#"line(linetype: "wavy")"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_line_group() {
+        let (_, layer) = parse_line(r#"line(group: sensor_id, color: "gray")"#).unwrap();
+        let Layer::Line(l) = layer else {
+            panic!("Expected Line layer");
+        };
+        assert_eq!(l.group, Some("sensor_id".into()));
+        assert_eq!(l.color, Some(AestheticValue::Fixed("gray".to_string())));
+        // There is no literal group, so a quoted name is a column
+        let (_, layer) = parse_line(r#"line(group: "Sensor ID")"#).unwrap();
+        let Layer::Line(l) = layer else {
+            panic!("Expected Line layer");
+        };
+        assert_eq!(l.group, Some("Sensor ID".into()));
+    }
+
+    #[test]
+    fn test_parse_geom_quoted_columns() {
+        let (_, layer) =
+            parse_point(r#"point(y: "Temp °C", color: `store-id`, size: `Size (kg)`)"#).unwrap();
+        let Layer::Point(p) = layer else {
+            panic!("Expected Point layer");
+        };
+        assert_eq!(p.y, Some("Temp °C".into()));
+        assert_eq!(p.color, Some(AestheticValue::Mapped("store-id".into())));
+        assert_eq!(p.size, Some(AestheticValue::Mapped("Size (kg)".into())));
+
+        // A double-quoted color is still a literal
+        let (_, layer) = parse_line(r#"line(x: `Date (UTC)`, color: "red")"#).unwrap();
+        let Layer::Line(l) = layer else {
+            panic!("Expected Line layer");
+        };
+        assert_eq!(l.x, Some("Date (UTC)".into()));
+        assert_eq!(l.color, Some(AestheticValue::Fixed("red".to_string())));
+    }
+
     #[test]
     fn test_parse_step_mid() {
         let result = parse_step(r#"step(direction: "mid", color: "red", width: 2)"#);
@@ -1538,7 +2099,7 @@ mod tests {
         match spike {
             Layer::Spike(s) => {
                 assert_eq!(s.baseline, -1.0);
-                assert_eq!(s.color, Some(AestheticValue::Mapped("group".to_string())));
+                assert_eq!(s.color, Some(AestheticValue::Mapped("group".into())));
                 assert_eq!(s.width, Some(AestheticValue::Fixed(2.0)));
             }
             _ => panic!("Expected Spike layer"),
@@ -1549,8 +2110,8 @@ mod tests {
                 .expect("pointrange should parse");
         match pointrange {
             Layer::PointRange(p) => {
-                assert_eq!(p.ymin, Some("lower".to_string()));
-                assert_eq!(p.ymax, Some("upper".to_string()));
+                assert_eq!(p.ymin, Some("lower".into()));
+                assert_eq!(p.ymax, Some("upper".into()));
                 assert_eq!(p.size, Some(AestheticValue::Fixed(4.0)));
                 assert_eq!(p.shape, Some(AestheticValue::Fixed("diamond".to_string())));
             }
@@ -1562,8 +2123,8 @@ mod tests {
                 .expect("crossbar should parse");
         match crossbar {
             Layer::CrossBar(c) => {
-                assert_eq!(c.ymin, Some("lower".to_string()));
-                assert_eq!(c.ymax, Some("upper".to_string()));
+                assert_eq!(c.ymin, Some("lower".into()));
+                assert_eq!(c.ymax, Some("upper".into()));
                 assert_eq!(c.width, 0.4);
                 assert_eq!(c.line_width, Some(AestheticValue::Fixed(2.0)));
             }
@@ -1589,9 +2150,9 @@ mod tests {
                 .expect("linerange should parse");
         match linerange {
             Layer::LineRange(l) => {
-                assert_eq!(l.ymin, Some("lower".to_string()));
-                assert_eq!(l.ymax, Some("upper".to_string()));
-                assert_eq!(l.color, Some(AestheticValue::Mapped("group".to_string())));
+                assert_eq!(l.ymin, Some("lower".into()));
+                assert_eq!(l.ymax, Some("upper".into()));
+                assert_eq!(l.color, Some(AestheticValue::Mapped("group".into())));
                 assert_eq!(l.width, Some(AestheticValue::Fixed(2.0)));
             }
             _ => panic!("Expected LineRange layer"),
@@ -1602,8 +2163,8 @@ mod tests {
                 .expect("errorbar should parse");
         match errorbar {
             Layer::ErrorBar(e) => {
-                assert_eq!(e.ymin, Some("lower".to_string()));
-                assert_eq!(e.ymax, Some("upper".to_string()));
+                assert_eq!(e.ymin, Some("lower".into()));
+                assert_eq!(e.ymax, Some("upper".into()));
                 assert_eq!(e.width, 0.3);
                 assert_eq!(e.line_width, Some(AestheticValue::Fixed(2.0)));
             }
@@ -1657,17 +2218,137 @@ mod tests {
         .expect("segment should parse");
         match segment {
             Layer::Segment(s) => {
-                assert_eq!(s.x, 1.0);
-                assert_eq!(s.y, 2.0);
-                assert_eq!(s.xend, 3.0);
-                assert_eq!(s.yend, 4.0);
-                assert_eq!(s.color, Some("red".to_string()));
+                assert!(!s.is_mapped());
+                assert_eq!(s.fixed_endpoints(), (1.0, 2.0, 3.0, 4.0));
+                assert_eq!(s.color, Some(AestheticValue::Fixed("red".to_string())));
                 assert_eq!(s.label, Some("Arrowless".to_string()));
+                assert!(!s.arrow);
             }
             _ => panic!("Expected Segment layer"),
         }
     }
 
+    #[test]
+    fn test_parse_mapped_segment() {
+        let (rest, segment) =
+            parse_segment("segment(x: x0, y: y0, xend: x1, yend: `y 1`, color: team, arrow: true)")
+                .expect("segment should parse");
+        assert_eq!(rest, "");
+        let Layer::Segment(s) = segment else {
+            panic!("Expected Segment layer");
+        };
+        assert!(s.is_mapped());
+        assert_eq!(s.x, Some(AestheticValue::Mapped("x0".into())));
+        assert_eq!(s.yend, Some(AestheticValue::Mapped("y 1".into())));
+        assert_eq!(s.color, Some(AestheticValue::Mapped("team".into())));
+        assert!(s.arrow);
+
+        // x and y may come from aes()
+        let (_, segment) = parse_segment("segment(xend: x1, yend: 4)").unwrap();
+        let Layer::Segment(s) = segment else {
+            panic!("Expected Segment layer");
+        };
+        assert!(s.is_mapped());
+        assert_eq!(s.x, None);
+        assert_eq!(s.yend, Some(AestheticValue::Fixed(4.0)));
+    }
+
+    #[test]
+    fn test_parse_text() {
+        let result = parse_text(r#"text(label: country, size: 10, color: "gray30", nudge_y: 0.5)"#);
+        assert!(result.is_ok());
+        let (_, layer) = result.unwrap();
+        match layer {
+            Layer::Text(t) => {
+                assert_eq!(t.label, "country");
+                assert_eq!(t.size, Some(10.0));
+                assert_eq!(t.color, Some(AestheticValue::Fixed("gray30".to_string())));
+                assert_eq!(t.nudge_x, 0.0);
+                assert_eq!(t.nudge_y, 0.5);
+            }
+            _ => panic!("Expected Text layer"),
+        }
+
+        // label: is required
+        assert!(parse_text("text(size: 10)").is_err());
+        assert!(parse_geom("text()").is_err());
+    }
+
+    #[test]
+    fn test_parse_tile() {
+        let (_, layer) = parse_geom("tile(fill: count, alpha: 0.8)").unwrap();
+        match layer {
+            Layer::Heatmap(h) => {
+                assert_eq!(h.fill, Some("count".into()));
+                assert_eq!(h.alpha, Some(AestheticValue::Fixed(0.8)));
+                assert_eq!(h.stat, crate::parser::ast::Stat::Heatmap { bins: None });
+            }
+            _ => panic!("Expected Heatmap layer"),
+        }
+    }
+
+    #[test]
+    fn test_parse_point_with_fade() {
+        let (_, layer) = parse_point("point(fade_by: timestamp)").unwrap();
+        match layer {
+            Layer::Point(p) => {
+                assert_eq!(p.fade_by, Some("timestamp".into()));
+                assert_eq!(p.fade, FadeDirection::Oldest);
+            }
+            _ => panic!("Expected Point layer"),
+        }
+
+        let (_, layer) = parse_point(r#"point(fade_by: timestamp, fade: "newest")"#).unwrap();
+        match layer {
+            Layer::Point(p) => assert_eq!(p.fade, FadeDirection::Newest),
+            _ => panic!("Expected Point layer"),
+        }
+    }
+
+    #[test]
+    fn test_parse_point_with_jitter() {
+        let (_, layer) = parse_point(r#"point(position: "jitter", jitter_width: 0.2)"#).unwrap();
+        match layer {
+            Layer::Point(p) => {
+                assert_eq!(p.position, PointPosition::Jitter);
+                assert_eq!(p.jitter_width, Some(0.2));
+            }
+            _ => panic!("Expected Point layer"),
+        }
+
+        // jitter() is point() with the jitter position, and width: sets the span
+        let (_, layer) = parse_geom(r#"jitter(width: 0.3, color: "red")"#).unwrap();
+        match layer {
+            Layer::Point(p) => {
+                assert_eq!(p.position, PointPosition::Jitter);
+                assert_eq!(p.jitter_width, Some(0.3));
+                assert_eq!(p.color, Some(AestheticValue::Fixed("red".to_string())));
+            }
+            _ => panic!("Expected Point layer"),
+        }
+
+        let (_, layer) = parse_point("point()").unwrap();
+        match layer {
+            Layer::Point(p) => assert_eq!(p.position, PointPosition::Identity),
+            _ => panic!("Expected Point layer"),
+        }
+    }
+
+    #[test]
+    fn test_parse_dodge_position_for_points_and_lines() {
+        let (_, layer) = parse_point(r#"point(position: "dodge")"#).unwrap();
+        match layer {
+            Layer::Point(p) => assert_eq!(p.position, PointPosition::Dodge),
+            _ => panic!("Expected Point layer"),
+        }
+
+        let (_, layer) = parse_line(r#"line(color: g, position: "dodge")"#).unwrap();
+        match layer {
+            Layer::Line(l) => assert_eq!(l.position, LinePosition::Dodge),
+            _ => panic!("Expected Line layer"),
+        }
+    }
+
     #[test]
     fn test_parse_point_with_size() {
         let result = parse_point("point(size: 5)");
@@ -1735,6 +2416,54 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_bar_fill_position() {
+        let (_, layer) = parse_bar(r#"bar(position: "fill")"#).unwrap();
+        match layer {
+            Layer::Bar(b) => assert_eq!(b.position, BarPosition::Fill),
+            _ => panic!("Expected Bar layer"),
+        }
+    }
+
+    #[test]
+    fn test_parse_layer_filter() {
+        let (_, layer) = parse_point(r#"point(filter: region == "EU", color: "red")"#).unwrap();
+        assert_eq!(
+            layer.filter(),
+            Some(&FilterExpr::Compare {
+                column: "region".to_string(),
+                op: CompareOp::Eq,
+                value: FilterValue::Text("EU".to_string()),
+            })
+        );
+        match layer {
+            Layer::Point(p) => assert_eq!(p.color, Some(AestheticValue::Fixed("red".to_string()))),
+            _ => panic!("Expected Point layer"),
+        }
+
+        let (_, layer) = parse_histogram("histogram(bins: 10, filter: x >= 0 && x < 5)").unwrap();
+        assert!(matches!(layer.filter(), Some(FilterExpr::And(..))));
+        assert!(matches!(layer.stat(), Stat::Bin { bins: 10 }));
+
+        // Without filter the layer keeps every row
+        let (_, layer) = parse_bar("bar()").unwrap();
+        assert_eq!(layer.filter(), None);
+    }
+
+    #[test]
+    fn test_parse_layer_data() {
+        let (_, layer) = parse_line(r#"line(data: "last_year.csv", color: "gray")"#).unwrap();
+        assert_eq!(layer.data(), Some("last_year.csv"));
+
+        let (_, layer) = parse_point(r#"point(filter: g == "a", data: "b.csv", size: 3)"#).unwrap();
+        assert_eq!(layer.data(), Some("b.csv"));
+        assert!(layer.filter().is_some());
+
+        // Without data the layer reads the plot's data
+        let (_, layer) = parse_bar("bar()").unwrap();
+        assert_eq!(layer.data(), None);
+    }
+
     #[test]
     fn test_parse_bar_full() {
         let result = parse_bar(r#"bar(position: "stack", color: "blue", alpha: 0.7, width: 0.6)"#);
@@ -1746,6 +2475,57 @@ mod tests {
                 assert_eq!(b.color, Some(AestheticValue::Fixed("blue".to_string())));
                 assert_eq!(b.alpha, Some(AestheticValue::Fixed(0.7)));
                 assert_eq!(b.width, Some(AestheticValue::Fixed(0.6)));
+                assert!(!b.x_continuous);
+            }
+            _ => panic!("Expected Bar layer"),
+        }
+    }
+
+    #[test]
+    fn test_parse_bar_border() {
+        let (_, layer) =
+            parse_bar(r#"bar(color: region, border: "black", border_width: 1.5)"#).unwrap();
+        match layer {
+            Layer::Bar(b) => {
+                assert_eq!(b.color, Some(AestheticValue::Mapped("region".into())));
+                assert_eq!(b.border, Some("black".to_string()));
+                assert_eq!(b.border_width, Some(1.5));
+            }
+            _ => panic!("Expected Bar layer"),
+        }
+    }
+
+    #[test]
+    fn test_parse_point_fill_and_stroke() {
+        let (_, layer) =
+            parse_point(r#"point(color: species, fill: "white", stroke: 1.5, shape: "square")"#)
+                .unwrap();
+        match layer {
+            Layer::Point(p) => {
+                assert_eq!(p.color, Some(AestheticValue::Mapped("species".into())));
+                assert_eq!(p.fill, Some("white".to_string()));
+                assert_eq!(p.stroke, Some(1.5));
+            }
+            _ => panic!("Expected Point layer"),
+        }
+
+        let (_, layer) = parse_jitter("jitter(stroke: 0.5)").unwrap();
+        match layer {
+            Layer::Point(p) => {
+                assert_eq!(p.fill, None);
+                assert_eq!(p.stroke, Some(0.5));
+            }
+            _ => panic!("Expected Point layer"),
+        }
+    }
+
+    #[test]
+    fn test_parse_bar_x_continuous() {
+        let (_, layer) = parse_bar(r#"bar(x_continuous: true, width: 0.5)"#).unwrap();
+        match layer {
+            Layer::Bar(b) => {
+                assert!(b.x_continuous);
+                assert_eq!(b.width, Some(AestheticValue::Fixed(0.5)));
             }
             _ => panic!("Expected Bar layer"),
         }
@@ -1759,8 +2539,8 @@ mod tests {
         let (_, layer) = result.unwrap();
         match layer {
             Layer::Line(l) => {
-                assert_eq!(l.x, Some("col1".to_string()));
-                assert_eq!(l.y, Some("col2".to_string()));
+                assert_eq!(l.x, Some("col1".into()));
+                assert_eq!(l.y, Some("col2".into()));
                 assert_eq!(l.color, Some(AestheticValue::Fixed("red".to_string())));
                 assert_eq!(l.width, Some(AestheticValue::Fixed(2.0)));
                 assert_eq!(l.alpha, Some(AestheticValue::Fixed(0.5)));
@@ -1769,6 +2549,19 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_line_column_indices() {
+        let (_, layer) = parse_line("line(y: 3, color: 2, width: 2, group: 1)").unwrap();
+        let Layer::Line(l) = layer else {
+            panic!("Expected Line layer");
+        };
+        assert_eq!(l.y, Some(ColumnRef::Index(3)));
+        assert_eq!(l.color, Some(AestheticValue::Mapped(ColumnRef::Index(2))));
+        assert_eq!(l.group, Some(ColumnRef::Index(1)));
+        // A bare number for a numeric aesthetic stays a fixed value
+        assert_eq!(l.width, Some(AestheticValue::Fixed(2.0)));
+    }
+
     #[test]
     fn test_parse_point_all_params() {
         // Test point with all parameters: x, y, color, size, alpha
@@ -1777,8 +2570,8 @@ mod tests {
         let (_, layer) = result.unwrap();
         match layer {
             Layer::Point(p) => {
-                assert_eq!(p.x, Some("col1".to_string()));
-                assert_eq!(p.y, Some("col2".to_string()));
+                assert_eq!(p.x, Some("col1".into()));
+                assert_eq!(p.y, Some("col2".into()));
                 assert_eq!(p.color, Some(AestheticValue::Fixed("blue".to_string())));
                 assert_eq!(p.size, Some(AestheticValue::Fixed(10.0)));
                 assert_eq!(p.alpha, Some(AestheticValue::Fixed(0.8)));
@@ -1816,6 +2609,96 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_bar_stat() {
+        let stat = |dsl: &str| match parse_bar(dsl).unwrap().1 {
+            Layer::Bar(b) => b.stat,
+            _ => panic!("Expected Bar layer"),
+        };
+        assert_eq!(stat("bar()"), Stat::Identity);
+        assert_eq!(stat(r#"bar(stat: "identity")"#), Stat::Identity);
+        assert_eq!(stat(r#"bar(stat: "count")"#), Stat::Count);
+        assert_eq!(
+            stat(r#"bar(stat: "sum", position: "stack")"#),
+            Stat::Summary {
+                fun: Aggregate::Sum
+            }
+        );
+        assert_eq!(
+            stat(r#"bar(stat: "mean")"#),
+            Stat::Summary {
+                fun: Aggregate::Mean
+            }
+        );
+        assert_eq!(
+            stat(r#"bar(stat: "max")"#),
+            Stat::Summary {
+                fun: Aggregate::Max
+            }
+        );
+        // Unknown stats are refused; validate_arguments lists the accepted ones
+        assert!(parse_bar(r#"bar(stat: "median")"#).is_err());
+        assert_eq!(
+            crate::parser::validate_arguments(r#"bar(stat: "median")"#)
+                .unwrap_err()
+                .to_string(),
+            "Unknown stat 'median' for bar(). Use one of: identity, sum, mean, min, max, count"
+        );
+    }
+
+    #[test]
+    fn test_parse_line_rolling_mean() {
+        let stat = |dsl: &str| match parse_line(dsl).unwrap().1 {
+            Layer::Line(l) => l.stat,
+            _ => panic!("Expected Line layer"),
+        };
+        assert_eq!(stat("line()"), Stat::Identity);
+        assert_eq!(
+            stat(r#"line(stat: "rolling_mean", window: 7, width: 2)"#),
+            Stat::RollingMean {
+                window: 7,
+                min_periods: None
+            }
+        );
+        assert_eq!(
+            stat(r#"line(min_periods: 1, window: 3, stat: "rolling_mean")"#),
+            Stat::RollingMean {
+                window: 3,
+                min_periods: Some(1)
+            }
+        );
+        assert_eq!(stat(r#"line(stat: "identity", window: 3)"#), Stat::Identity);
+
+        // A rolling mean needs a whole-number window
+        assert!(parse_line(r#"line(stat: "rolling_mean")"#).is_err());
+        assert!(parse_line(r#"line(stat: "rolling_mean", window: 2.5)"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_point_fast() {
+        let fast = |dsl: &str| match parse_point(dsl).unwrap().1 {
+            Layer::Point(p) => p.fast,
+            _ => panic!("Expected Point layer"),
+        };
+        assert!(fast("point()"));
+        assert!(!fast("point(size: 2, fast: false)"));
+        assert!(matches!(
+            parse_jitter("jitter(fast: false)").unwrap().1,
+            Layer::Point(PointLayer { fast: false, .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_line_simplify() {
+        let simplify = |dsl: &str| match parse_line(dsl).unwrap().1 {
+            Layer::Line(l) => l.simplify,
+            _ => panic!("Expected Line layer"),
+        };
+        assert!(simplify("line()"));
+        assert!(simplify("line(simplify: true)"));
+        assert!(!simplify(r#"line(color: "red", simplify: false)"#));
+    }
+
     #[test]
     fn test_parse_geom_multiple_params() {
         // Test that multiple parameters work correctly
@@ -1885,6 +2768,7 @@ mod tests {
                 assert_eq!(d.color, Some(AestheticValue::Fixed("blue".to_string())));
                 assert_eq!(d.alpha, Some(AestheticValue::Fixed(0.5)));
                 assert_eq!(d.bw, Some(1.5));
+                assert_eq!(d.fill, None);
                 assert!(
                     matches!(d.stat, crate::parser::ast::Stat::Density { bw: Some(b) } if b == 1.5)
                 );
@@ -1893,6 +2777,17 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_density_fill() {
+        let fill = |dsl: &str| match parse_density(dsl).unwrap().1 {
+            Layer::Density(d) => d.fill,
+            _ => panic!("Expected Density layer"),
+        };
+        assert_eq!(fill("density(fill: false)"), Some(false));
+        assert_eq!(fill(r#"density(fill: true, color: "red")"#), Some(true));
+        assert!(parse_density("density(fill: 1)").is_err());
+    }
+
     #[test]
     fn test_parse_density_in_pipeline() {
         use crate::parser::pipeline::parse_plot_spec;
@@ -1902,4 +2797,23 @@ mod tests {
         assert_eq!(spec.layers.len(), 1);
         assert!(matches!(spec.layers[0], Layer::Density(_)));
     }
+
+    #[test]
+    fn test_parse_pie() {
+        let (_, layer) = parse_pie("pie()").unwrap();
+        assert_eq!(layer, Layer::Pie(PieLayer::default()));
+
+        let (rest, layer) =
+            parse_pie(r#"pie(y: share, donut: 0.5, other: 0.02, filter: share > 0)"#).unwrap();
+        assert_eq!(rest, "");
+        match layer {
+            Layer::Pie(p) => {
+                assert_eq!(p.y, Some("share".into()));
+                assert_eq!(p.donut, Some(0.5));
+                assert_eq!(p.other, Some(0.02));
+                assert!(p.filter.is_some());
+            }
+            _ => panic!("Expected Pie layer"),
+        }
+    }
 }
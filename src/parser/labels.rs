@@ -60,4 +60,14 @@ mod tests {
         assert_eq!(labels.title, Some("My Chart".to_string()));
         assert_eq!(labels.x, Some("X Axis".to_string()));
     }
+
+    #[test]
+    fn test_parse_labs_escaped_strings() {
+        let (_, labels) =
+            parse_labs(r#"labs(title: "The \"best\" chart", subtitle: "", x: "C:\\data")"#)
+                .unwrap();
+        assert_eq!(labels.title, Some(r#"The "best" chart"#.to_string()));
+        assert_eq!(labels.subtitle, Some(String::new()));
+        assert_eq!(labels.x, Some(r"C:\data".to_string()));
+    }
 }
@@ -1,9 +1,11 @@
+use super::ast::ColumnRef;
 use nom::{
-    bytes::complete::take_while1,
-    character::complete::{char, multispace0},
-    combinator::recognize,
+    branch::alt,
+    bytes::complete::{tag, take_while1},
+    character::complete::{char, digit1, multispace0, satisfy},
+    combinator::{map, map_res, not, recognize, value},
     number::complete::double,
-    sequence::delimited,
+    sequence::{delimited, terminated},
     IResult,
 };
 
@@ -29,16 +31,86 @@ pub fn identifier(input: &str) -> IResult<&str, String> {
     Ok((input, ident.to_string()))
 }
 
-pub fn string_literal(input: &str) -> IResult<&str, String> {
-    let (input, content) = delimited(char('\"'), take_while1(|c| c != '\"'), char('\"'))(input)?;
+/// A column reference: a bare identifier, or a backtick- or double-quoted header for
+/// names with spaces or punctuation (`` `Sales (USD)` ``, `"Temp °C"`)
+pub fn column_name(input: &str) -> IResult<&str, String> {
+    alt((identifier, backtick_quoted, string_literal))(input)
+}
+
+/// A column reference for arguments where a double-quoted string is a literal value
+/// (`color: "red"`): a bare identifier or a backtick-quoted header
+pub fn mapped_column(input: &str) -> IResult<&str, String> {
+    alt((identifier, backtick_quoted))(input)
+}
+
+/// `column_name`, or a zero-based column position such as `0` or `3`
+pub fn column_ref(input: &str) -> IResult<&str, ColumnRef> {
+    alt((
+        map(column_index, ColumnRef::Index),
+        map(column_name, ColumnRef::Name),
+    ))(input)
+}
+
+/// `mapped_column`, or a zero-based column position
+pub fn mapped_column_ref(input: &str) -> IResult<&str, ColumnRef> {
+    alt((
+        map(column_index, ColumnRef::Index),
+        map(mapped_column, ColumnRef::Name),
+    ))(input)
+}
+
+/// Bare digits not running on into an identifier or a decimal (`2` but not `2x` or `2.5`)
+fn column_index(input: &str) -> IResult<&str, usize> {
+    terminated(
+        map_res(digit1, str::parse),
+        not(satisfy(|c| c.is_alphanumeric() || c == '_' || c == '.')),
+    )(input)
+}
+
+fn backtick_quoted(input: &str) -> IResult<&str, String> {
+    let (input, content) = delimited(char('`'), take_while1(|c| c != '`'), char('`'))(input)?;
 
     Ok((input, content.to_string()))
 }
 
+/// A double-quoted string. `\"`, `\\`, `\n`, and `\t` are unescaped; any other
+/// backslash sequence is kept verbatim. `""` is the empty string.
+pub fn string_literal(input: &str) -> IResult<&str, String> {
+    let (rest, _) = char('"')(input)?;
+    let mut content = String::new();
+    let mut chars = rest.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((&rest[i + 1..], content)),
+            '\\' => match chars.next() {
+                Some((_, '"')) => content.push('"'),
+                Some((_, '\\')) => content.push('\\'),
+                Some((_, 'n')) => content.push('\n'),
+                Some((_, 't')) => content.push('\t'),
+                Some((_, other)) => {
+                    content.push('\\');
+                    content.push(other);
+                }
+                None => break,
+            },
+            c => content.push(c),
+        }
+    }
+    Err(nom::Err::Error(nom::error::Error::new(
+        input,
+        nom::error::ErrorKind::Char,
+    )))
+}
+
 pub fn number_literal(input: &str) -> IResult<&str, f64> {
     double(input)
 }
 
+/// `true` or `false`
+pub fn bool_literal(input: &str) -> IResult<&str, bool> {
+    alt((value(true, tag("true")), value(false, tag("false"))))(input)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -55,6 +127,21 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_column_ref() {
+        assert_eq!(column_ref("3"), Ok(("", ColumnRef::Index(3))));
+        assert_eq!(column_ref("0 )"), Ok((" )", ColumnRef::Index(0))));
+        assert_eq!(column_ref("temp"), Ok(("", ColumnRef::Name("temp".into()))));
+        assert_eq!(
+            column_ref("`2020`"),
+            Ok(("", ColumnRef::Name("2020".into())))
+        );
+        assert_eq!(mapped_column_ref("1"), Ok(("", ColumnRef::Index(1))));
+        // Decimals and digits running into letters aren't positions
+        assert!(column_ref("2.5").is_err());
+        assert!(column_ref("2x").is_err());
+    }
+
     #[test]
     fn test_string_literal() {
         assert_eq!(string_literal(r#""hello""#), Ok(("", "hello".to_string())));
@@ -98,10 +185,65 @@ mod tests {
         assert!(string_literal(r#""hello"#).is_err());
     }
 
+    #[test]
+    fn test_string_literal_empty_quotes() {
+        assert_eq!(string_literal(r#""""#), Ok(("", String::new())));
+        assert_eq!(string_literal(r#""", x"#), Ok((", x", String::new())));
+    }
+
+    #[test]
+    fn test_string_literal_escaped_quote() {
+        assert_eq!(
+            string_literal(r#""The \"best\" chart") | x"#),
+            Ok((") | x", r#"The "best" chart"#.to_string()))
+        );
+    }
+
+    #[test]
+    fn test_string_literal_escaped_backslash() {
+        assert_eq!(
+            string_literal(r#""C:\\data""#),
+            Ok(("", r"C:\data".to_string()))
+        );
+        assert_eq!(string_literal(r#""end\\""#), Ok(("", r"end\".to_string())));
+    }
+
+    #[test]
+    fn test_string_literal_newline_and_tab() {
+        assert_eq!(string_literal(r#""a\nb""#), Ok(("", "a\nb".to_string())));
+        assert_eq!(string_literal(r#""a\tb""#), Ok(("", "a\tb".to_string())));
+    }
+
+    #[test]
+    fn test_string_literal_unknown_escape_is_kept() {
+        assert_eq!(string_literal(r#""100\%""#), Ok(("", r"100\%".to_string())));
+    }
+
+    #[test]
+    fn test_string_literal_unterminated_escape() {
+        assert!(string_literal(r#""abc\"#).is_err());
+        // An escaped quote doesn't close the string
+        assert!(string_literal(r#""abc\""#).is_err());
+    }
+
     #[test]
     fn test_number_literal_negative() {
         assert_eq!(number_literal("-42"), Ok(("", -42.0)));
         assert_eq!(number_literal("-3.5"), Ok(("", -3.5)));
         assert_eq!(number_literal("-0.1"), Ok(("", -0.1)));
     }
+
+    #[test]
+    fn test_column_name_quoted() {
+        assert_eq!(column_name("temp"), Ok(("", "temp".to_string())));
+        assert_eq!(
+            column_name("`Sales (USD)`"),
+            Ok(("", "Sales (USD)".to_string()))
+        );
+        assert_eq!(column_name(r#""Temp °C""#), Ok(("", "Temp °C".to_string())));
+        assert!(column_name("`unclosed").is_err());
+        // Double quotes stay literals where a value may be either
+        assert_eq!(mapped_column("`a-b`"), Ok(("", "a-b".to_string())));
+        assert!(mapped_column(r#""red""#).is_err());
+    }
 }
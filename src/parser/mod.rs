@@ -2,12 +2,20 @@
 
 pub mod aesthetics;
 
+pub mod annotate;
+
+pub mod arguments;
+
+pub mod arithmetic;
+
 pub mod ast;
 
 pub mod coord;
 
 pub mod facet;
 
+pub mod filter;
+
 pub mod geom;
 
 pub mod labels;
@@ -16,11 +24,16 @@ pub mod lexer;
 
 pub mod pipeline;
 
+pub mod printer;
+
 pub mod scale;
 
+pub mod stage;
+
 pub mod theme;
 
 // Public API re-exports
+pub use arguments::{deprecated_arguments, validate_arguments};
 pub use ast::{Aesthetics, Facet, FacetScales, Layer, LineLayer, PlotSpec, PointLayer};
-pub use facet::parse_facet_wrap;
-pub use pipeline::parse_plot_spec;
+pub use facet::{parse_facet_grid, parse_facet_wrap};
+pub use pipeline::{parse_error, parse_plot_spec, parse_plot_spec_prefix};
@@ -1,23 +1,30 @@
 // Pipeline parser for Grammar of Graphics DSL
 
 use super::aesthetics::parse_aesthetics;
+use super::annotate::{parse_annotate, parse_shade};
 use super::ast::{
-    Aesthetics, AxisScale, CoordSystem, Facet, Labels, Layer, PlotSpec, Theme, ThemeElement,
+    Aesthetics, Annotation, AxisLabels, AxisScale, ColorScale, CoordSystem, DataStage, Facet,
+    FacetGrid, Labels, Layer, ManualColorScale, PlotSpec, Shade, Theme, ThemeElement,
 };
-use super::coord::parse_coord_flip;
-use super::facet::parse_facet_wrap;
+use super::coord::{parse_coord_fixed, parse_coord_flip};
+use super::facet::{parse_facet_grid, parse_facet_wrap};
 use super::geom::parse_geom;
 use super::labels::parse_labs;
 use super::lexer::ws;
-use super::scale::parse_scale_command;
+use super::scale::{
+    parse_axis_labels_command, parse_scale_color, parse_scale_color_manual, parse_scale_command,
+};
+use super::stage::{parse_data_stage, sort_key_counts};
 use super::theme::parse_theme_command;
+use crate::error::GramGraphError;
 use nom::{
     branch::alt,
     bytes::complete::tag,
-    combinator::{eof, map, opt},
+    combinator::{consumed, eof, map, opt},
     error::{Error, ErrorKind},
     multi::separated_list0,
-    IResult,
+    sequence::terminated,
+    IResult, Offset,
 };
 
 /// Merge two themes together (ggplot2-style).
@@ -92,18 +99,28 @@ fn merge_themes(base: Theme, overlay: Theme) -> Theme {
         },
         legend_margin: overlay.legend_margin.or(base.legend_margin),
         legend_key_size: overlay.legend_key_size.or(base.legend_key_size),
+        margin: overlay.margin.or(base.margin),
+        grid_separators: overlay.grid_separators.or(base.grid_separators),
     }
 }
 
 #[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
 enum PipelineComponent {
     Aes(Aesthetics),
     Layer(Layer),
     Facet(Facet),
+    FacetGrid(FacetGrid),
     Coord(CoordSystem),
     Labels(Labels),
     Theme(Theme),
-    Scale(bool, AxisScale), // is_x, scale
+    Scale(bool, AxisScale),       // is_x, scale
+    AxisLabels(bool, AxisLabels), // is_x, tick label options
+    ColorScale(ColorScale),
+    ColorManual(ManualColorScale),
+    Stage(DataStage),
+    Annotation(Annotation),
+    Shade(Shade),
 }
 
 fn parse_pipeline_component(input: &str) -> IResult<&str, PipelineComponent> {
@@ -111,18 +128,42 @@ fn parse_pipeline_component(input: &str) -> IResult<&str, PipelineComponent> {
         map(parse_aesthetics, PipelineComponent::Aes),
         map(parse_geom, PipelineComponent::Layer),
         map(parse_facet_wrap, PipelineComponent::Facet),
+        map(parse_facet_grid, PipelineComponent::FacetGrid),
         map(parse_coord_flip, PipelineComponent::Coord),
+        map(parse_coord_fixed, PipelineComponent::Coord),
         map(parse_labs, PipelineComponent::Labels),
         map(parse_theme_command, PipelineComponent::Theme),
         map(parse_scale_command, |(is_x, s)| {
             PipelineComponent::Scale(is_x, s)
         }),
+        map(parse_axis_labels_command, |(is_x, l)| {
+            PipelineComponent::AxisLabels(is_x, l)
+        }),
+        map(parse_scale_color_manual, PipelineComponent::ColorManual),
+        map(parse_scale_color, PipelineComponent::ColorScale),
+        map(parse_data_stage, PipelineComponent::Stage),
+        map(parse_annotate, PipelineComponent::Annotation),
+        map(parse_shade, PipelineComponent::Shade),
     ))(input)
 }
 
+/// Error kind marking a second aes, coord, or facet, so `parse_error` can tell it
+/// from other failures; none of the combinators in this parser raise it
+const DUPLICATE: ErrorKind = ErrorKind::Permutation;
+
 /// Parse a complete plot specification
 /// Format: component | component | ...
+///
+/// Components may come in any order; at least one geometry is required, and a
+/// second aes, coord, facet_wrap, or facet_grid fails instead of replacing the first.
 pub fn parse_plot_spec(input: &str) -> IResult<&str, PlotSpec> {
+    // Consume trailing whitespace and ensure end of input
+    terminated(parse_plot_spec_prefix, ws(eof))(input)
+}
+
+/// Parse the longest run of valid components, leaving the rest (from the `|` before
+/// the first component that failed) unconsumed. `--lenient` renders this prefix.
+pub fn parse_plot_spec_prefix(input: &str) -> IResult<&str, PlotSpec> {
     // Optional: consume leading "df"
     let (input, _) = opt(ws(tag("df")))(input)?;
 
@@ -130,26 +171,42 @@ pub fn parse_plot_spec(input: &str) -> IResult<&str, PlotSpec> {
     let (input, _) = opt(ws(tag("|")))(input)?;
 
     // Parse list of components separated by "|"
-    let (input, components) = separated_list0(ws(tag("|")), parse_pipeline_component)(input)?;
-
-    // Consume trailing whitespace and ensure end of input
-    let (input, _) = ws(eof)(input)?;
+    let (input, components) =
+        separated_list0(ws(tag("|")), consumed(parse_pipeline_component))(input)?;
 
     // Aggregate components into PlotSpec
     let mut aesthetics = None;
     let mut layers = Vec::new();
     let mut facet = None;
+    let mut facet_grid = None;
     let mut coord = None;
     let mut labels = None;
     let mut theme = None;
-    let mut x_scale = None;
+    let mut x_scale: Option<AxisScale> = None;
     let mut y_scale = None;
+    let mut color_scale = None;
+    let mut color_manual = None;
+    let mut stages = Vec::new();
+    let mut annotations = Vec::new();
+    let mut shades = Vec::new();
 
-    for comp in components {
+    for (source, comp) in components {
+        // A second aes, coord, or facet would silently replace the first
+        let duplicate = match &comp {
+            PipelineComponent::Aes(_) => aesthetics.is_some(),
+            PipelineComponent::Coord(_) => coord.is_some(),
+            PipelineComponent::Facet(_) => facet.is_some(),
+            PipelineComponent::FacetGrid(_) => facet_grid.is_some(),
+            _ => false,
+        };
+        if duplicate {
+            return Err(nom::Err::Failure(Error::new(source.trim(), DUPLICATE)));
+        }
         match comp {
             PipelineComponent::Aes(a) => aesthetics = Some(a),
             PipelineComponent::Layer(l) => layers.push(l),
             PipelineComponent::Facet(f) => facet = Some(f),
+            PipelineComponent::FacetGrid(f) => facet_grid = Some(f),
             PipelineComponent::Coord(c) => coord = Some(c),
             PipelineComponent::Labels(l) => {
                 // Merge or override? Let's override for simplicity, or merge fields if needed.
@@ -163,13 +220,32 @@ pub fn parse_plot_spec(input: &str) -> IResult<&str, PlotSpec> {
                     None => t,
                 });
             }
-            PipelineComponent::Scale(is_x, s) => {
+            PipelineComponent::Scale(is_x, mut s) => {
+                let prev = if is_x { x_scale.take() } else { y_scale.take() };
+                if let Some(prev) = prev {
+                    // scale_x_discrete's order and scale_x/scale_y label options
+                    // survive a later scale command on the same axis
+                    if is_x && s.order.is_none() {
+                        s.order = prev.order;
+                    }
+                    s.labels = s.labels.or(prev.labels);
+                }
                 if is_x {
                     x_scale = Some(s);
                 } else {
                     y_scale = Some(s);
                 }
             }
+            PipelineComponent::AxisLabels(is_x, l) => {
+                let scale = if is_x { &mut x_scale } else { &mut y_scale };
+                let scale = scale.get_or_insert_with(AxisScale::default);
+                scale.labels = l.or(std::mem::take(&mut scale.labels));
+            }
+            PipelineComponent::ColorScale(s) => color_scale = Some(s),
+            PipelineComponent::ColorManual(s) => color_manual = Some(s),
+            PipelineComponent::Stage(s) => stages.push(s),
+            PipelineComponent::Annotation(a) => annotations.push(a),
+            PipelineComponent::Shade(s) => shades.push(s),
         }
     }
 
@@ -185,17 +261,81 @@ pub fn parse_plot_spec(input: &str) -> IResult<&str, PlotSpec> {
             layers,
             labels,
             facet,
+            facet_grid,
             coord,
             theme,
             x_scale,
             y_scale,
+            color_scale,
+            color_manual,
+            stages,
+            annotations,
+            shades,
         },
     ))
 }
 
+/// Turn a failed `parse_plot_spec` into an error naming the line and column where
+/// parsing stopped, with the offending line and a caret under the first character
+/// that could not be parsed
+pub fn parse_error(dsl: &str, err: nom::Err<Error<&str>>) -> GramGraphError {
+    let (rest, code) = match err {
+        nom::Err::Error(e) | nom::Err::Failure(e) => (e.input, e.code),
+        nom::Err::Incomplete(_) => (&dsl[dsl.len()..], ErrorKind::Complete),
+    };
+    // Point past the separator at the component that failed, not at the `|`
+    let rest = rest.trim_start();
+    let rest = rest.strip_prefix('|').unwrap_or(rest).trim_start();
+    let offset = dsl.offset(rest).min(dsl.len());
+
+    let line_start = dsl[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = dsl[offset..].find('\n').map_or(dsl.len(), |i| offset + i);
+    let line = dsl[..offset].matches('\n').count() + 1;
+    let column = dsl[line_start..offset].chars().count() + 1;
+
+    let problem = if rest.is_empty() && code == ErrorKind::Verify {
+        "a plot needs at least one geometry, such as line() or point()".to_string()
+    } else if rest.is_empty() {
+        "unexpected end of input".to_string()
+    } else if let Some((by, desc)) = sort_key_counts(rest).filter(|_| code == ErrorKind::Verify) {
+        if by == 0 {
+            "sort() needs by: with at least one column".to_string()
+        } else {
+            format!(
+                "sort() has {} desc: flags for {} by: column{}; give one flag, or one per column",
+                desc,
+                by,
+                if by == 1 { "" } else { "s" }
+            )
+        }
+    } else if code == DUPLICATE && rest.starts_with("coord_") {
+        "only one coord_flip() or coord_fixed() is allowed".to_string()
+    } else if code == DUPLICATE {
+        let name = rest.split('(').next().unwrap_or(rest).trim();
+        format!("only one {}() is allowed", name)
+    } else {
+        let snippet: String = dsl[offset..line_end].chars().take(40).collect();
+        let more = if snippet.len() < line_end - offset {
+            "…"
+        } else {
+            ""
+        };
+        format!("cannot parse '{}{}'", snippet.trim_end(), more)
+    };
+
+    GramGraphError::ParseError {
+        offset,
+        line,
+        column,
+        expected: problem,
+        source_line: dsl[line_start..line_end].to_string(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
 
     #[test]
     fn test_parse_aes_and_line() {
@@ -272,6 +412,25 @@ mod tests {
         assert_eq!(spec.layers.len(), 1);
     }
 
+    #[test]
+    fn test_parse_plot_spec_filter_stages() {
+        let (_, spec) = parse_plot_spec(
+            r#"aes(x: a, y: b) | filter(year >= 2020) | line() | filter(region == "EU")"#,
+        )
+        .unwrap();
+        assert_eq!(spec.layers.len(), 1);
+        assert_eq!(spec.stages.len(), 2);
+        assert_eq!(spec.stages[0].columns(), vec!["year"]);
+        assert_eq!(spec.stages[1].columns(), vec!["region"]);
+
+        assert!(parse_plot_spec("aes(x: a, y: b) | line()")
+            .unwrap()
+            .1
+            .stages
+            .is_empty());
+        assert!(parse_plot_spec("aes(x: a, y: b) | filter() | line()").is_err());
+    }
+
     #[test]
     fn test_parse_plot_spec_with_facet_wrap() {
         let result = parse_plot_spec("aes(x: time, y: sales) | line() | facet_wrap(by: region)");
@@ -295,6 +454,45 @@ mod tests {
         assert_eq!(facet.ncol, Some(2));
     }
 
+    #[test]
+    fn test_parse_plot_spec_components_in_any_order() {
+        // Facet before the geometry
+        let (_, spec) =
+            parse_plot_spec("aes(x: time, y: sales) | facet_wrap(by: region) | line()").unwrap();
+        assert_eq!(spec.facet.unwrap().by, "region");
+        assert_eq!(spec.layers.len(), 1);
+
+        // Facet between geometries, with modifiers on either side
+        let (_, spec) = parse_plot_spec(
+            r#"labs(title: "T") | aes(x: time, y: sales) | line() | facet_grid(rows: region) | point() | theme_minimal()"#,
+        )
+        .unwrap();
+        assert_eq!(spec.facet_grid.unwrap().rows.as_deref(), Some("region"));
+        assert_eq!(spec.layers.len(), 2);
+        assert_eq!(spec.labels.unwrap().title.as_deref(), Some("T"));
+        assert!(spec.theme.is_some());
+    }
+
+    #[test]
+    fn test_parse_plot_spec_rejects_duplicate_facet() {
+        let err = parse_plot_spec(
+            "aes(x: time, y: sales) | facet_wrap(by: region) | line() | facet_wrap(by: product)",
+        )
+        .unwrap_err();
+        // The error points at the second facet
+        assert!(
+            matches!(&err, nom::Err::Failure(e) if e.input == "facet_wrap(by: product)"),
+            "{:?}",
+            err
+        );
+        assert!(parse_plot_spec(
+            "aes(x: time, y: sales) | line() | facet_grid(rows: a) | facet_grid(cols: b)"
+        )
+        .is_err());
+        // Geometry is still required wherever the other components are
+        assert!(parse_plot_spec("aes(x: a, y: b) | facet_wrap(by: c)").is_err());
+    }
+
     #[test]
     fn test_parse_plot_spec_without_facet() {
         let result = parse_plot_spec("aes(x: time, y: sales) | line()");
@@ -342,4 +540,177 @@ mod tests {
             panic!("Expected Bar layer (histogram)");
         }
     }
+
+    #[test]
+    fn test_parse_category_order_survives_later_x_scale() {
+        let (_, spec) = parse_plot_spec(
+            r#"aes(x: month, y: sales) | bar() | scale_x_discrete(order: "alpha") | scale_x_reverse()"#,
+        )
+        .unwrap();
+        let x_scale = spec.x_scale.unwrap();
+        assert_eq!(x_scale.scale_type, crate::parser::ast::ScaleType::Reverse);
+        assert_eq!(
+            x_scale.order,
+            Some(crate::parser::ast::CategoryOrder::Alpha)
+        );
+    }
+
+    #[test]
+    fn test_parse_axis_labels_merge_with_scale_commands() {
+        let (_, spec) = parse_plot_spec(
+            r#"aes(x: a, y: b) | point() | scale_y(format: "si") | scale_y_log10() | scale_x(rotate: 90) | scale_x_sqrt() | scale_x(max_label_len: 6)"#,
+        )
+        .unwrap();
+        let y_scale = spec.y_scale.unwrap();
+        assert_eq!(y_scale.scale_type, crate::parser::ast::ScaleType::Log10);
+        assert_eq!(y_scale.labels.format.as_deref(), Some("si"));
+
+        let x_scale = spec.x_scale.unwrap();
+        assert_eq!(x_scale.scale_type, crate::parser::ast::ScaleType::Sqrt);
+        assert_eq!(x_scale.labels.rotate, Some(90.0));
+        assert_eq!(x_scale.labels.max_label_len, Some(6));
+    }
+
+    fn error_for(dsl: &str) -> String {
+        parse_error(dsl, parse_plot_spec(dsl).unwrap_err()).to_string()
+    }
+
+    #[test]
+    fn test_parse_error_points_at_the_first_unparsed_component() {
+        assert_eq!(
+            error_for("aes(x: a, y: b) | line() | pont()"),
+            "Parse error at line 1, column 28: cannot parse 'pont()'\n  \
+             aes(x: a, y: b) | line() | pont()\n  \
+             \x20                          ^"
+        );
+
+        let err = error_for("aes(x: a, y: b)\n| point()\n| line(width: \"w\")");
+        assert!(
+            err.starts_with("Parse error at line 3, column 3: cannot parse 'line(width: \"w\")'")
+        );
+        assert!(err.ends_with("\n  | line(width: \"w\")\n    ^"), "{}", err);
+
+        assert!(error_for("aes(x: a, y: b)").contains("at least one geometry"));
+        assert!(error_for("line() | facet_wrap(by: a) | facet_wrap(by: b)")
+            .contains("column 30: only one facet_wrap() is allowed"));
+    }
+
+    #[test]
+    fn test_parse_rejects_a_second_aes_or_coord() {
+        assert!(error_for("aes(x: a, y: b) | line() | aes(x: a, y: c)")
+            .starts_with("Parse error at line 1, column 28: only one aes() is allowed"));
+        assert!(error_for("coord_fixed() | coord_flip() | line()")
+            .starts_with("Parse error at line 1, column 17: only one coord_flip() or coord_fixed() is allowed"));
+        assert!(parse_plot_spec("aes(x: a, y: b) | line() | coord_flip()").is_ok());
+
+        // A malformed first component is a parse error there, not a duplicate
+        for dsl in [
+            "aes(x: @, y: b) | point()",
+            "aes(x: a, y: b | point()",
+            "facet_wrap(by: @) | point()",
+            "coord_fixed(ratio: x) | point()",
+        ] {
+            let err = error_for(dsl);
+            assert!(
+                err.starts_with("Parse error at line 1, column 1: cannot parse"),
+                "{}",
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_error_points_at_an_invalid_sort() {
+        let err = error_for("aes(x: a, y: b) | sort(by: g, desc: [true, false]) | line()");
+        assert!(
+            err.starts_with(
+                "Parse error at line 1, column 19: sort() has 2 desc: flags for 1 by: column;"
+            ),
+            "{}",
+            err
+        );
+        assert!(error_for("sort(desc: true) | line()").starts_with(
+            "Parse error at line 1, column 1: sort() needs by: with at least one column"
+        ));
+    }
+
+    #[test]
+    fn test_parse_prefix_stops_before_the_failed_component() {
+        let (rest, spec) = parse_plot_spec_prefix("aes(x: a, y: b) | line() | pont()").unwrap();
+        assert_eq!(rest, "| pont()");
+        assert_eq!(spec.layers.len(), 1);
+        assert!(parse_plot_spec("aes(x: a, y: b) | line() | pont()").is_err());
+    }
+
+    /// Fragments of DSL syntax, so the fuzzer reaches past the first token
+    fn token_soup() -> impl Strategy<Value = String> {
+        let token = prop_oneof![
+            Just("aes("),
+            Just("line("),
+            Just("point("),
+            Just("bar("),
+            Just("filter("),
+            Just("mutate("),
+            Just("facet_wrap("),
+            Just("theme("),
+            Just("element_text("),
+            Just("scale_x("),
+            Just(")"),
+            Just("("),
+            Just("|"),
+            Just(","),
+            Just("x:"),
+            Just("y:"),
+            Just("color:"),
+            Just("by:"),
+            Just("["),
+            Just("]"),
+            Just(" "),
+            Just("\n"),
+            Just("\""),
+            Just("\\"),
+            Just("`"),
+            Just("-"),
+            Just("*"),
+            Just("&&"),
+            Just(">="),
+            Just("=="),
+            Just("1.5"),
+            Just("1e999"),
+            Just("7"),
+            Just("inf"),
+            Just("a"),
+            Just("é"),
+        ];
+        proptest::collection::vec(token, 0..40).prop_map(|tokens| tokens.concat())
+    }
+
+    fn check_does_not_panic(dsl: &str) -> Result<(), TestCaseError> {
+        match parse_plot_spec(dsl) {
+            Ok((rest, _)) => prop_assert_eq!(rest, ""),
+            Err(e) => {
+                let err = parse_error(dsl, e);
+                let GramGraphError::ParseError { offset, .. } = &err else {
+                    panic!("expected a parse error, got {err:?}");
+                };
+                prop_assert!(*offset <= dsl.len());
+                err.to_string();
+            }
+        }
+        let _ = parse_plot_spec_prefix(dsl);
+        let _ = crate::parser::validate_arguments(dsl);
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn arbitrary_input_parses_or_fails_cleanly(dsl in any::<String>()) {
+            check_does_not_panic(&dsl)?;
+        }
+
+        #[test]
+        fn dsl_fragments_parse_or_fail_cleanly(dsl in token_soup()) {
+            check_does_not_panic(&dsl)?;
+        }
+    }
 }
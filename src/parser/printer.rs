@@ -0,0 +1,2125 @@
+// DSL printer: a PlotSpec back to the text `parse_plot_spec` reads
+//
+// `to_dsl()` (and `Display`) writes aes() first, then the data stages, the layers, the
+// facet, coord, labs, scales, and theme components, and last the annotations and
+// shades. Fixed values are quoted and mapped columns bare, and only the arguments that
+// differ from the parser's defaults are written, so
+// `parse_plot_spec(&spec.to_dsl())` gives back an equal spec for anything the DSL can
+// spell. A few AST states have no spelling (a numeric aesthetic mapped to a column
+// position, a step line with a linetype, a `ScaleType::DateTime` y axis); those print
+// their closest DSL form.
+
+use super::ast::{
+    AestheticValue, Aesthetics, Aggregate, Annotation, AnnotationX, ArithExpr, ArithFunction,
+    ArithOp, AxisScale, BarPosition, CategoryOrder, ColorScale, ColumnRef, CompareOp, CoordSystem,
+    DataStage, Facet, FacetCut, FacetGrid, FacetLabeller, FacetScales, FadeDirection, FilterExpr,
+    FilterValue, Labels, Layer, LegendPosition, LineInterpolation, LinePosition, ManualColorScale,
+    PlotSpec, PointPosition, ScaleType, Shade, Stat, Theme, ThemeElement,
+};
+use std::fmt;
+
+impl PlotSpec {
+    /// The spec as DSL text, one component per `|`
+    pub fn to_dsl(&self) -> String {
+        let mut components = Vec::new();
+        if let Some(aes) = &self.aesthetics {
+            components.push(aesthetics(aes));
+        }
+        components.extend(self.stages.iter().map(stage));
+        components.extend(self.layers.iter().map(layer));
+        if let Some(f) = &self.facet {
+            components.push(facet_wrap(f));
+        }
+        if let Some(g) = &self.facet_grid {
+            components.push(facet_grid(g));
+        }
+        match &self.coord {
+            Some(CoordSystem::Flip) => components.push("coord_flip()".to_string()),
+            Some(CoordSystem::Fixed { ratio }) => {
+                let mut args = Args::default();
+                args.number_unless("ratio", *ratio, 1.0);
+                components.push(args.call("coord_fixed"));
+            }
+            Some(CoordSystem::Cartesian) | None => {}
+        }
+        if let Some(l) = &self.labels {
+            components.push(labs(l));
+        }
+        if let Some(s) = &self.x_scale {
+            components.extend(axis_scale(true, s));
+        }
+        if let Some(s) = &self.y_scale {
+            components.extend(axis_scale(false, s));
+        }
+        if let Some(s) = &self.color_scale {
+            components.push(color_scale(s));
+        }
+        if let Some(s) = &self.color_manual {
+            components.push(color_manual(s));
+        }
+        if let Some(t) = &self.theme {
+            components.push(theme(t));
+        }
+        components.extend(self.annotations.iter().map(annotate));
+        components.extend(self.shades.iter().map(shade));
+        components.join(" | ")
+    }
+}
+
+/// Writes `to_dsl()`
+impl fmt::Display for PlotSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_dsl())
+    }
+}
+
+/// The `key: value` arguments of one DSL call, in the order they are added
+#[derive(Default)]
+struct Args(Vec<String>);
+
+impl Args {
+    fn call(self, name: &str) -> String {
+        format!("{}({})", name, self.0.join(", "))
+    }
+
+    fn raw(&mut self, key: &str, value: String) {
+        self.0.push(format!("{}: {}", key, value));
+    }
+
+    fn column(&mut self, key: &str, col: &Option<ColumnRef>) {
+        if let Some(col) = col {
+            self.raw(key, column_ref(col));
+        }
+    }
+
+    fn name(&mut self, key: &str, col: &Option<String>) {
+        if let Some(col) = col {
+            self.raw(key, column(col));
+        }
+    }
+
+    fn text(&mut self, key: &str, value: &Option<String>) {
+        if let Some(value) = value {
+            self.raw(key, quote(value));
+        }
+    }
+
+    fn number(&mut self, key: &str, value: Option<f64>) {
+        if let Some(value) = value {
+            self.raw(key, number(value));
+        }
+    }
+
+    fn number_unless(&mut self, key: &str, value: f64, default: f64) {
+        if value != default {
+            self.raw(key, number(value));
+        }
+    }
+
+    fn count(&mut self, key: &str, value: Option<usize>) {
+        if let Some(value) = value {
+            self.raw(key, value.to_string());
+        }
+    }
+
+    fn flag_unless(&mut self, key: &str, value: bool, default: bool) {
+        if value != default {
+            self.raw(key, value.to_string());
+        }
+    }
+
+    /// A color, shape, or linetype: quoted when fixed, bare when mapped
+    fn styled(&mut self, key: &str, value: &Option<AestheticValue<String>>) {
+        match value {
+            Some(AestheticValue::Fixed(v)) => self.raw(key, quote(v)),
+            Some(AestheticValue::Mapped(col)) => self.raw(key, column_ref(col)),
+            None => {}
+        }
+    }
+
+    /// A width, size, or alpha: a number when fixed, a column when mapped
+    fn numeric(&mut self, key: &str, value: &Option<AestheticValue<f64>>) {
+        match value {
+            Some(AestheticValue::Fixed(v)) => self.raw(key, number(*v)),
+            Some(AestheticValue::Mapped(col)) => self.raw(key, column_ref(col)),
+            None => {}
+        }
+    }
+
+    /// The `filter:` and `data:` arguments every data geometry takes
+    fn rows(&mut self, filter: &Option<FilterExpr>, data: &Option<String>) {
+        if let Some(f) = filter {
+            self.raw("filter", filter_expr(f));
+        }
+        self.text("data", data);
+    }
+}
+
+/// A double-quoted string literal, escaping what `lexer::string_literal` unescapes
+fn quote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Shortest text that parses back to the same f64
+fn number(n: f64) -> String {
+    format!("{}", n)
+}
+
+/// A column name: bare when it is an identifier, else backtick-quoted (or
+/// double-quoted when it holds a backtick). Names that `number_literal` would read as
+/// `inf` or `nan` are quoted too, since some arguments try a number first.
+fn column(name: &str) -> String {
+    let mut chars = name.chars();
+    let identifier = chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_');
+    let lower = name.to_ascii_lowercase();
+    if identifier && !lower.starts_with("inf") && !lower.starts_with("nan") {
+        name.to_string()
+    } else if !name.is_empty() && !name.contains('`') {
+        format!("`{}`", name)
+    } else {
+        quote(name)
+    }
+}
+
+fn column_ref(col: &ColumnRef) -> String {
+    match col {
+        ColumnRef::Name(name) => column(name),
+        ColumnRef::Index(index) => index.to_string(),
+    }
+}
+
+/// `[a, b]`
+fn list(items: impl IntoIterator<Item = String>) -> String {
+    format!("[{}]", items.into_iter().collect::<Vec<_>>().join(", "))
+}
+
+fn aesthetics(aes: &Aesthetics) -> String {
+    let mut args = Args::default();
+    args.raw("x", column_ref(&aes.x));
+    args.column("y", &aes.y);
+    if !aes.y_columns.is_empty() {
+        args.raw("y", list(aes.y_columns.iter().map(column_ref)));
+    }
+    args.column("color", &aes.color);
+    args.column("size", &aes.size);
+    args.column("shape", &aes.shape);
+    args.column("alpha", &aes.alpha);
+    args.column("ymin", &aes.ymin);
+    args.column("ymax", &aes.ymax);
+    args.column("fill", &aes.fill);
+    args.column("linetype", &aes.linetype);
+    args.column("group", &aes.group);
+    args.call("aes")
+}
+
+// === Expressions ===
+
+/// `||` binds loosest, then `&&`; a comparison is an atom
+fn filter_precedence(expr: &FilterExpr) -> u8 {
+    match expr {
+        FilterExpr::Or(..) => 1,
+        FilterExpr::And(..) => 2,
+        FilterExpr::Compare { .. } => 3,
+    }
+}
+
+fn filter_expr(expr: &FilterExpr) -> String {
+    // Both operators fold to the left, so a right operand of the same kind needs parentheses
+    let operand = |e: &FilterExpr, min: u8| {
+        if filter_precedence(e) < min {
+            format!("({})", filter_expr(e))
+        } else {
+            filter_expr(e)
+        }
+    };
+    match expr {
+        FilterExpr::Compare {
+            column: col,
+            op,
+            value,
+        } => {
+            let op = match op {
+                CompareOp::Eq => "==",
+                CompareOp::Ne => "!=",
+                CompareOp::Lt => "<",
+                CompareOp::Le => "<=",
+                CompareOp::Gt => ">",
+                CompareOp::Ge => ">=",
+            };
+            let value = match value {
+                FilterValue::Number(n) => number(*n),
+                FilterValue::Text(s) => quote(s),
+            };
+            format!("{} {} {}", column(col), op, value)
+        }
+        FilterExpr::Or(a, b) => format!("{} || {}", operand(a, 1), operand(b, 2)),
+        FilterExpr::And(a, b) => format!("{} && {}", operand(a, 2), operand(b, 3)),
+    }
+}
+
+/// `+`/`-` bind loosest, then `*`/`/`; everything else is an atom
+fn arith_precedence(expr: &ArithExpr) -> u8 {
+    match expr {
+        ArithExpr::Binary {
+            op: ArithOp::Add | ArithOp::Sub,
+            ..
+        } => 1,
+        ArithExpr::Binary { .. } => 2,
+        _ => 3,
+    }
+}
+
+fn arith_expr(expr: &ArithExpr) -> String {
+    let operand = |e: &ArithExpr, min: u8| {
+        if arith_precedence(e) < min {
+            format!("({})", arith_expr(e))
+        } else {
+            arith_expr(e)
+        }
+    };
+    match expr {
+        ArithExpr::Number(n) => number(*n),
+        ArithExpr::Column(col) => column(col),
+        ArithExpr::Neg(inner) => format!("-{}", operand(inner, 3)),
+        ArithExpr::Binary { op, left, right } => {
+            let (symbol, level) = match op {
+                ArithOp::Add => ("+", 1),
+                ArithOp::Sub => ("-", 1),
+                ArithOp::Mul => ("*", 2),
+                ArithOp::Div => ("/", 2),
+            };
+            format!(
+                "{} {} {}",
+                operand(left, level),
+                symbol,
+                operand(right, level + 1)
+            )
+        }
+        ArithExpr::Call { function, arg } => {
+            let name = match function {
+                ArithFunction::Abs => "abs",
+                ArithFunction::Log => "log",
+                ArithFunction::Log10 => "log10",
+                ArithFunction::Exp => "exp",
+                ArithFunction::Sqrt => "sqrt",
+            };
+            format!("{}({})", name, arith_expr(arg))
+        }
+    }
+}
+
+// === Components ===
+
+fn stage(stage: &DataStage) -> String {
+    let mut args = Args::default();
+    match stage {
+        DataStage::Filter(expr) => return format!("filter({})", filter_expr(expr)),
+        DataStage::Summarize(s) => {
+            if !s.by.is_empty() {
+                args.raw("by", list(s.by.iter().map(|col| column(col))));
+            }
+            let col = s.column.as_deref().map(column).unwrap_or_default();
+            args.raw("y", format!("{}({})", s.function.name(), col));
+            args.name("as", &s.alias);
+        }
+        DataStage::Sort(keys) => {
+            args.raw("by", list(keys.iter().map(|k| column(&k.column))));
+            if keys.iter().any(|k| k.desc) {
+                args.raw("desc", list(keys.iter().map(|k| k.desc.to_string())));
+            }
+        }
+        DataStage::Head(n) => args.raw("n", n.to_string()),
+        DataStage::Sample(s) => {
+            args.raw("n", s.n.to_string());
+            if s.seed != 0 {
+                args.raw("seed", s.seed.to_string());
+            }
+        }
+        DataStage::Mutate(mutations) => {
+            for m in mutations {
+                args.0
+                    .push(format!("{}: {}", column(&m.name), arith_expr(&m.expr)));
+            }
+        }
+        DataStage::PivotLonger(p) => {
+            args.raw("cols", list(p.cols.iter().map(|col| column(col))));
+            if p.names_to != "name" {
+                args.raw("names_to", column(&p.names_to));
+            }
+            if p.values_to != "value" {
+                args.raw("values_to", column(&p.values_to));
+            }
+        }
+    }
+    args.call(stage.name())
+}
+
+/// One geometry call. Stats and step interpolation pick the shorthand that builds them
+/// (`histogram()`, `step()`, `freqpoly()`, `smooth()`).
+fn layer(layer: &Layer) -> String {
+    let mut args = Args::default();
+    let name = match layer {
+        Layer::Line(l) => match (&l.stat, l.interpolation) {
+            (Stat::Bin { bins }, _) => {
+                args.column("x", &l.x);
+                args.raw("bins", bins.to_string());
+                args.styled("color", &l.color);
+                args.numeric("width", &l.width);
+                args.numeric("alpha", &l.alpha);
+                args.rows(&l.filter, &l.data);
+                "freqpoly"
+            }
+            (
+                Stat::Smooth {
+                    method,
+                    span,
+                    samples,
+                },
+                _,
+            ) => {
+                args.column("x", &l.x);
+                args.column("y", &l.y);
+                if method != "lm" {
+                    args.raw("method", quote(method));
+                }
+                args.number("span", *span);
+                args.count("samples", *samples);
+                args.styled("color", &l.color);
+                args.numeric("width", &l.width);
+                args.numeric("alpha", &l.alpha);
+                args.rows(&l.filter, &l.data);
+                "smooth"
+            }
+            (_, LineInterpolation::Linear) => {
+                args.column("x", &l.x);
+                args.column("y", &l.y);
+                args.styled("color", &l.color);
+                args.numeric("width", &l.width);
+                args.numeric("alpha", &l.alpha);
+                args.styled("linetype", &l.linetype);
+                args.column("group", &l.group);
+                if l.position == LinePosition::Dodge {
+                    args.raw("position", quote("dodge"));
+                }
+                if let Stat::RollingMean {
+                    window,
+                    min_periods,
+                } = &l.stat
+                {
+                    args.raw("stat", quote("rolling_mean"));
+                    args.raw("window", window.to_string());
+                    args.count("min_periods", *min_periods);
+                }
+                args.flag_unless("simplify", l.simplify, true);
+                args.rows(&l.filter, &l.data);
+                "line"
+            }
+            (_, step) => {
+                args.column("x", &l.x);
+                args.column("y", &l.y);
+                match step {
+                    LineInterpolation::StepVH => args.raw("direction", quote("vh")),
+                    LineInterpolation::StepMid => args.raw("direction", quote("mid")),
+                    _ => {}
+                }
+                args.styled("color", &l.color);
+                args.numeric("width", &l.width);
+                args.numeric("alpha", &l.alpha);
+                args.rows(&l.filter, &l.data);
+                "step"
+            }
+        },
+        Layer::Point(p) => {
+            args.column("x", &p.x);
+            args.column("y", &p.y);
+            args.styled("color", &p.color);
+            args.numeric("size", &p.size);
+            args.styled("shape", &p.shape);
+            args.numeric("alpha", &p.alpha);
+            args.column("fade_by", &p.fade_by);
+            if p.fade == FadeDirection::Newest {
+                args.raw("fade", quote("newest"));
+            }
+            match p.position {
+                PointPosition::Jitter => args.raw("position", quote("jitter")),
+                PointPosition::Dodge => args.raw("position", quote("dodge")),
+                PointPosition::Identity => {}
+            }
+            args.number("jitter_width", p.jitter_width);
+            args.text("fill", &p.fill);
+            args.number("stroke", p.stroke);
+            args.flag_unless("fast", p.fast, true);
+            args.rows(&p.filter, &p.data);
+            "point"
+        }
+        Layer::Bar(b) => {
+            if let Stat::Bin { bins } = b.stat {
+                if bins != 30 {
+                    args.raw("bins", bins.to_string());
+                }
+                args.rows(&b.filter, &b.data);
+                return args.call("histogram");
+            }
+            args.column("x", &b.x);
+            args.column("y", &b.y);
+            args.styled("color", &b.color);
+            args.numeric("width", &b.width);
+            args.numeric("alpha", &b.alpha);
+            match b.position {
+                BarPosition::Dodge => args.raw("position", quote("dodge")),
+                BarPosition::Stack => args.raw("position", quote("stack")),
+                BarPosition::Fill => args.raw("position", quote("fill")),
+                BarPosition::Identity => {}
+            }
+            let stat = match &b.stat {
+                Stat::Count => Some("count"),
+                Stat::Summary { fun } => Some(match fun {
+                    Aggregate::Sum => "sum",
+                    Aggregate::Mean => "mean",
+                    Aggregate::Min => "min",
+                    Aggregate::Max => "max",
+                }),
+                _ => None,
+            };
+            if let Some(stat) = stat {
+                args.raw("stat", quote(stat));
+            }
+            args.flag_unless("x_continuous", b.x_continuous, false);
+            args.text("border", &b.border);
+            args.number("border_width", b.border_width);
+            args.rows(&b.filter, &b.data);
+            "bar"
+        }
+        Layer::Area(a) => {
+            args.column("x", &a.x);
+            args.column("y", &a.y);
+            args.styled("color", &a.color);
+            args.numeric("alpha", &a.alpha);
+            args.number_unless("baseline", a.baseline, 0.0);
+            args.rows(&a.filter, &a.data);
+            "area"
+        }
+        Layer::Rug(r) => {
+            args.column("x", &r.x);
+            args.column("y", &r.y);
+            if r.sides != "b" {
+                args.raw("sides", quote(&r.sides));
+            }
+            args.number_unless("length", r.length, 0.03);
+            args.styled("color", &r.color);
+            args.numeric("width", &r.width);
+            args.numeric("alpha", &r.alpha);
+            args.rows(&r.filter, &r.data);
+            "rug"
+        }
+        Layer::Spike(s) => {
+            args.column("x", &s.x);
+            args.column("y", &s.y);
+            args.number_unless("baseline", s.baseline, 0.0);
+            args.styled("color", &s.color);
+            args.numeric("width", &s.width);
+            args.numeric("alpha", &s.alpha);
+            args.rows(&s.filter, &s.data);
+            "spike"
+        }
+        Layer::LineRange(l) => {
+            args.column("x", &l.x);
+            args.column("ymin", &l.ymin);
+            args.column("ymax", &l.ymax);
+            args.styled("color", &l.color);
+            args.numeric("width", &l.width);
+            args.numeric("alpha", &l.alpha);
+            args.rows(&l.filter, &l.data);
+            "linerange"
+        }
+        Layer::ErrorBar(e) => {
+            args.column("x", &e.x);
+            args.column("ymin", &e.ymin);
+            args.column("ymax", &e.ymax);
+            args.styled("color", &e.color);
+            args.numeric("linewidth", &e.line_width);
+            args.number_unless("width", e.width, 0.2);
+            args.numeric("alpha", &e.alpha);
+            args.rows(&e.filter, &e.data);
+            "errorbar"
+        }
+        Layer::PointRange(p) => {
+            args.column("x", &p.x);
+            args.column("y", &p.y);
+            args.column("ymin", &p.ymin);
+            args.column("ymax", &p.ymax);
+            args.styled("color", &p.color);
+            args.numeric("width", &p.width);
+            args.numeric("size", &p.size);
+            args.styled("shape", &p.shape);
+            args.numeric("alpha", &p.alpha);
+            args.rows(&p.filter, &p.data);
+            "pointrange"
+        }
+        Layer::CrossBar(c) => {
+            args.column("x", &c.x);
+            args.column("y", &c.y);
+            args.column("ymin", &c.ymin);
+            args.column("ymax", &c.ymax);
+            args.styled("color", &c.color);
+            args.number_unless("width", c.width, 0.5);
+            args.numeric("linewidth", &c.line_width);
+            args.numeric("alpha", &c.alpha);
+            args.rows(&c.filter, &c.data);
+            "crossbar"
+        }
+        Layer::Ribbon(r) => {
+            args.column("x", &r.x);
+            args.column("ymin", &r.ymin);
+            args.column("ymax", &r.ymax);
+            args.styled("color", &r.color);
+            args.numeric("alpha", &r.alpha);
+            args.rows(&r.filter, &r.data);
+            "ribbon"
+        }
+        Layer::Boxplot(b) => {
+            args.column("x", &b.x);
+            args.column("y", &b.y);
+            args.styled("color", &b.color);
+            args.numeric("width", &b.width);
+            args.numeric("alpha", &b.alpha);
+            args.text("outlier_color", &b.outlier_color);
+            args.number("outlier_size", b.outlier_size);
+            args.text("outlier_shape", &b.outlier_shape);
+            args.rows(&b.filter, &b.data);
+            "boxplot"
+        }
+        Layer::Violin(v) => {
+            args.column("x", &v.x);
+            args.column("y", &v.y);
+            args.styled("color", &v.color);
+            args.numeric("width", &v.width);
+            args.numeric("alpha", &v.alpha);
+            if !v.draw_quantiles.is_empty() {
+                args.raw(
+                    "draw_quantiles",
+                    list(v.draw_quantiles.iter().map(|q| number(*q))),
+                );
+            }
+            args.rows(&v.filter, &v.data);
+            "violin"
+        }
+        Layer::Density(d) => {
+            args.column("x", &d.x);
+            args.styled("color", &d.color);
+            args.numeric("alpha", &d.alpha);
+            args.number("bw", d.bw);
+            if let Some(fill) = d.fill {
+                args.raw("fill", fill.to_string());
+            }
+            args.rows(&d.filter, &d.data);
+            "density"
+        }
+        Layer::Heatmap(h) => {
+            args.column("x", &h.x);
+            args.column("y", &h.y);
+            args.column("fill", &h.fill);
+            if let Stat::Heatmap { bins } = h.stat {
+                args.count("bins", bins);
+            }
+            args.numeric("alpha", &h.alpha);
+            args.rows(&h.filter, &h.data);
+            "heatmap"
+        }
+        Layer::HLine(h) => {
+            args.raw("yintercept", number(h.yintercept));
+            args.text("color", &h.color);
+            args.number("width", h.width);
+            args.number("alpha", h.alpha);
+            args.text("label", &h.label);
+            "hline"
+        }
+        Layer::VLine(v) => {
+            args.raw("xintercept", number(v.xintercept));
+            args.text("color", &v.color);
+            args.number("width", v.width);
+            args.number("alpha", v.alpha);
+            args.text("label", &v.label);
+            "vline"
+        }
+        Layer::AbLine(a) => {
+            args.number_unless("slope", a.slope, 1.0);
+            args.number_unless("intercept", a.intercept, 0.0);
+            args.text("color", &a.color);
+            args.number("width", a.width);
+            args.number("alpha", a.alpha);
+            args.text("label", &a.label);
+            "abline"
+        }
+        Layer::Segment(s) => {
+            args.numeric("x", &s.x);
+            args.numeric("y", &s.y);
+            args.numeric("xend", &s.xend);
+            args.numeric("yend", &s.yend);
+            args.styled("color", &s.color);
+            args.number("width", s.width);
+            args.number("alpha", s.alpha);
+            args.text("label", &s.label);
+            args.flag_unless("arrow", s.arrow, false);
+            "segment"
+        }
+        Layer::Text(t) => {
+            args.column("x", &t.x);
+            args.column("y", &t.y);
+            args.raw("label", column_ref(&t.label));
+            args.styled("color", &t.color);
+            args.number("size", t.size);
+            args.numeric("alpha", &t.alpha);
+            args.number_unless("nudge_x", t.nudge_x, 0.0);
+            args.number_unless("nudge_y", t.nudge_y, 0.0);
+            args.rows(&t.filter, &t.data);
+            "text"
+        }
+        Layer::Pie(p) => {
+            args.column("x", &p.x);
+            args.column("y", &p.y);
+            args.number("alpha", p.alpha);
+            args.number("donut", p.donut);
+            args.number("other", p.other);
+            args.rows(&p.filter, &p.data);
+            "pie"
+        }
+    };
+    args.call(name)
+}
+
+fn facet_scales(args: &mut Args, scales: &FacetScales) {
+    let scales = match scales {
+        FacetScales::Fixed => return,
+        FacetScales::FreeX => "free_x",
+        FacetScales::FreeY => "free_y",
+        FacetScales::Free => "free",
+    };
+    args.raw("scales", quote(scales));
+}
+
+fn facet_labeller(args: &mut Args, labeller: &FacetLabeller) {
+    let labeller = match labeller {
+        FacetLabeller::Equals => return,
+        FacetLabeller::Value => "value",
+        FacetLabeller::Both => "both",
+        FacetLabeller::Template(template) => template,
+    };
+    args.raw("labeller", quote(labeller));
+}
+
+fn facet_wrap(facet: &Facet) -> String {
+    let mut args = Args::default();
+    args.raw("by", column(&facet.by));
+    args.count("ncol", facet.ncol);
+    args.count("nrow", facet.nrow);
+    facet_scales(&mut args, &facet.scales);
+    args.count("bins", facet.bins);
+    if facet.cut == FacetCut::Quantile {
+        args.raw("cut", quote("quantile"));
+    }
+    facet_labeller(&mut args, &facet.labeller);
+    args.number("label_size", facet.label_size);
+    args.call("facet_wrap")
+}
+
+fn facet_grid(grid: &FacetGrid) -> String {
+    let mut args = Args::default();
+    args.name("rows", &grid.rows);
+    args.name("cols", &grid.cols);
+    facet_scales(&mut args, &grid.scales);
+    facet_labeller(&mut args, &grid.labeller);
+    args.number("label_size", grid.label_size);
+    args.call("facet_grid")
+}
+
+fn labs(labels: &Labels) -> String {
+    let mut args = Args::default();
+    args.text("title", &labels.title);
+    args.text("subtitle", &labels.subtitle);
+    args.text("x", &labels.x);
+    args.text("y", &labels.y);
+    args.text("caption", &labels.caption);
+    args.call("labs")
+}
+
+fn category_order(order: CategoryOrder) -> String {
+    quote(match order {
+        CategoryOrder::Appearance => "appearance",
+        CategoryOrder::Alpha => "alpha",
+        CategoryOrder::Value => "value",
+    })
+}
+
+/// The commands that rebuild one axis. scale_x_discrete() comes first because the
+/// pipeline keeps its order through a later scale command, and scale_x()/scale_y()
+/// last because their options ride along with whatever scale the axis has.
+fn axis_scale(is_x: bool, scale: &AxisScale) -> Vec<String> {
+    let axis = if is_x { "x" } else { "y" };
+    let mut commands = Vec::new();
+    if let (true, Some(order)) = (is_x, scale.order) {
+        let mut args = Args::default();
+        args.raw("order", category_order(order));
+        commands.push(args.call("scale_x_discrete"));
+    }
+    match scale.scale_type {
+        ScaleType::Linear => {
+            if let Some((min, max)) = scale.limits {
+                commands.push(format!("{}lim({}, {})", axis, number(min), number(max)));
+            }
+        }
+        ScaleType::Log10 => commands.push(format!("scale_{}_log10()", axis)),
+        ScaleType::Sqrt => commands.push(format!("scale_{}_sqrt()", axis)),
+        ScaleType::Reverse => commands.push(format!("scale_{}_reverse()", axis)),
+        // Only the x axis has a datetime scale command
+        ScaleType::DateTime if !is_x => {}
+        ScaleType::DateTime => {
+            let mut args = Args::default();
+            if let Some(datetime) = &scale.datetime {
+                args.text("interval", &datetime.interval);
+                args.text("format", &datetime.format);
+            }
+            commands.push(args.call("scale_x_datetime"));
+        }
+    }
+
+    let labels = &scale.labels;
+    let mut args = Args::default();
+    args.number("rotate", labels.rotate);
+    args.text("format", &labels.format);
+    args.count("max_label_len", labels.max_label_len);
+    args.number("expand", labels.expand);
+    args.text("parse", &labels.parse);
+    if let Some(reverse) = labels.reverse {
+        args.raw("reverse", reverse.to_string());
+    }
+    args.count("ticks", labels.ticks);
+    // breaks: takes a bare list, so it goes last
+    if let Some(breaks) = &labels.breaks {
+        let breaks: Vec<String> = breaks.iter().map(|b| number(*b)).collect();
+        args.raw("breaks", breaks.join(", "));
+    }
+    if !args.0.is_empty() || commands.is_empty() {
+        commands.push(args.call(&format!("scale_{}", axis)));
+    }
+    commands
+}
+
+fn color_scale(scale: &ColorScale) -> String {
+    let defaults = ColorScale::default();
+    let mut args = Args::default();
+    if scale.low != defaults.low {
+        args.raw("low", quote(&scale.low));
+    }
+    if scale.high != defaults.high {
+        args.raw("high", quote(&scale.high));
+    }
+    args.text("palette", &scale.palette);
+    if let Some(order) = scale.order {
+        args.raw("order", category_order(order));
+    }
+    // levels: takes a bare list, so it goes last
+    if !scale.levels.is_empty() {
+        let levels: Vec<String> = scale.levels.iter().map(|l| quote(l)).collect();
+        args.raw("levels", levels.join(", "));
+    }
+    args.call("scale_color")
+}
+
+fn color_manual(scale: &ManualColorScale) -> String {
+    let entries: Vec<String> = scale
+        .named
+        .iter()
+        .map(|(key, color)| format!("{} = {}", quote(key), quote(color)))
+        .chain(scale.ordered.iter().map(|color| quote(color)))
+        .collect();
+    if entries.is_empty() {
+        "scale_color_manual()".to_string()
+    } else {
+        format!("scale_color_manual(values: {})", entries.join(", "))
+    }
+}
+
+fn element(elem: &ThemeElement) -> Option<String> {
+    let mut args = Args::default();
+    let name = match elem {
+        ThemeElement::Inherit => return None,
+        ThemeElement::Blank => "element_blank",
+        ThemeElement::Text(t) => {
+            args.number("size", t.size);
+            args.text("color", &t.color);
+            args.text("family", &t.family);
+            args.text("face", &t.face);
+            args.number("angle", t.angle);
+            args.number("hjust", t.hjust);
+            args.number("vjust", t.vjust);
+            "element_text"
+        }
+        ThemeElement::Line(l) => {
+            args.text("color", &l.color);
+            args.number("width", l.width);
+            args.text("linetype", &l.linetype);
+            "element_line"
+        }
+        ThemeElement::Rect(r) => {
+            args.text("fill", &r.fill);
+            args.text("color", &r.color);
+            args.number("width", r.width);
+            "element_rect"
+        }
+    };
+    Some(args.call(name))
+}
+
+/// The theme spelled out element by element, so a merged theme (a preset refined by
+/// later `theme()` calls) prints as the single `theme()` that builds it
+fn theme(theme: &Theme) -> String {
+    let mut args = Args::default();
+    let elements = [
+        ("line", &theme.line),
+        ("rect", &theme.rect),
+        ("text", &theme.text),
+        ("plot_background", &theme.plot_background),
+        ("plot_title", &theme.plot_title),
+        ("panel_background", &theme.panel_background),
+        ("panel_grid_major", &theme.panel_grid_major),
+        ("panel_grid_minor", &theme.panel_grid_minor),
+        ("axis_text", &theme.axis_text),
+        ("axis_line", &theme.axis_line),
+        ("axis_ticks", &theme.axis_ticks),
+        ("legend_background", &theme.legend_background),
+        ("legend_text", &theme.legend_text),
+    ];
+    for (key, elem) in elements {
+        if let Some(elem) = element(elem) {
+            args.raw(key, elem);
+        }
+    }
+    if let Some(position) = &theme.legend_position {
+        let position = match position {
+            LegendPosition::UpperLeft => "upper-left",
+            LegendPosition::UpperMiddle => "upper-middle",
+            LegendPosition::UpperRight => "upper-right",
+            LegendPosition::MiddleLeft => "middle-left",
+            LegendPosition::MiddleMiddle => "middle-middle",
+            LegendPosition::MiddleRight => "middle-right",
+            LegendPosition::LowerLeft => "lower-left",
+            LegendPosition::LowerMiddle => "lower-middle",
+            LegendPosition::LowerRight => "lower-right",
+            LegendPosition::None => "none",
+        };
+        args.raw("legend_position", quote(position));
+    }
+    args.number("legend_margin", theme.legend_margin);
+    args.number("legend_key_size", theme.legend_key_size);
+    args.number("margin", theme.margin);
+    if let Some(between) = theme.grid_separators {
+        args.raw("grid_separators", between.to_string());
+    }
+    args.call("theme")
+}
+
+fn annotation_x(x: &AnnotationX) -> String {
+    match x {
+        AnnotationX::Number(n) => number(*n),
+        AnnotationX::Text(s) => quote(s),
+    }
+}
+
+fn annotate(note: &Annotation) -> String {
+    let mut args = Args::default();
+    args.raw("x", annotation_x(&note.x));
+    args.raw("y", number(note.y));
+    args.raw("text", quote(&note.text));
+    args.text("color", &note.color);
+    args.number("size", note.size);
+    args.number_unless("nudge_x", note.nudge_x, 0.0);
+    args.number_unless("nudge_y", note.nudge_y, 0.0);
+    args.flag_unless("point", note.point, false);
+    args.flag_unless("arrow", note.arrow, false);
+    args.flag_unless("expand", note.expand, true);
+    args.text("panel", &note.panel);
+    args.call("annotate")
+}
+
+fn shade(shade: &Shade) -> String {
+    let mut args = Args::default();
+    if let Some(x) = &shade.xmin {
+        args.raw("xmin", annotation_x(x));
+    }
+    if let Some(x) = &shade.xmax {
+        args.raw("xmax", annotation_x(x));
+    }
+    args.number("ymin", shade.ymin);
+    args.number("ymax", shade.ymax);
+    args.text("color", &shade.color);
+    args.number("alpha", shade.alpha);
+    args.text("panel", &shade.panel);
+    args.call("shade")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::ast::{
+        AbLineLayer, AreaLayer, AxisLabels, BarLayer, BoxplotLayer, CrossBarLayer,
+        DateTimeScaleOptions, DensityLayer, ElementLine, ElementRect, ElementText, ErrorBarLayer,
+        HLineLayer, HeatmapLayer, LineLayer, LineRangeLayer, Mutation, PieLayer, PivotLonger,
+        PointLayer, PointRangeLayer, RibbonLayer, RugLayer, Sample, SegmentLayer, SortKey,
+        SpikeLayer, Summarize, SummaryFunction, TextLayer, VLineLayer, ViolinLayer,
+    };
+    use crate::parser::{parse_plot_spec, validate_arguments};
+    use proptest::prelude::*;
+    use proptest::{collection::vec, option::of};
+
+    // === Generators for specs the DSL can spell ===
+
+    /// Plain identifiers, names that must be quoted, and names that look like numbers
+    fn name() -> BoxedStrategy<String> {
+        prop_oneof![
+            "[a-z_][a-z0-9_]{0,6}",
+            "[A-Za-z0-9 ()$%.,:|\"\\\\é-]{1,8}",
+            Just("inf".to_string()),
+            Just("Nancy".to_string()),
+            Just("log".to_string()),
+        ]
+        .boxed()
+    }
+
+    fn column() -> BoxedStrategy<ColumnRef> {
+        prop_oneof![
+            name().prop_map(ColumnRef::Name),
+            (0usize..20).prop_map(ColumnRef::Index),
+        ]
+        .boxed()
+    }
+
+    fn text() -> BoxedStrategy<String> {
+        "(.|\n|\t){0,10}".boxed()
+    }
+
+    fn num() -> BoxedStrategy<f64> {
+        prop_oneof![
+            (-100i32..100).prop_map(f64::from),
+            prop::num::f64::NORMAL | prop::num::f64::ZERO,
+        ]
+        .boxed()
+    }
+
+    fn styled() -> BoxedStrategy<AestheticValue<String>> {
+        prop_oneof![
+            text().prop_map(AestheticValue::Fixed),
+            column().prop_map(AestheticValue::Mapped),
+        ]
+        .boxed()
+    }
+
+    /// A dash pattern the parser accepts
+    fn linetype() -> BoxedStrategy<String> {
+        let linetypes = crate::parser::arguments::allowed_values("line", "linetype").unwrap();
+        prop::sample::select(linetypes)
+            .prop_map(str::to_string)
+            .boxed()
+    }
+
+    /// A column position can't be told apart from a number here, so mapped values are names
+    fn numeric() -> BoxedStrategy<AestheticValue<f64>> {
+        prop_oneof![
+            num().prop_map(AestheticValue::Fixed),
+            name().prop_map(|n| AestheticValue::Mapped(ColumnRef::Name(n))),
+        ]
+        .boxed()
+    }
+
+    fn filter() -> BoxedStrategy<FilterExpr> {
+        let op = prop_oneof![
+            Just(CompareOp::Eq),
+            Just(CompareOp::Ne),
+            Just(CompareOp::Lt),
+            Just(CompareOp::Le),
+            Just(CompareOp::Gt),
+            Just(CompareOp::Ge),
+        ];
+        let value = prop_oneof![
+            num().prop_map(FilterValue::Number),
+            text().prop_map(FilterValue::Text),
+        ];
+        let compare = (name(), op, value).prop_map(|(column, op, value)| FilterExpr::Compare {
+            column,
+            op,
+            value,
+        });
+        compare
+            .prop_recursive(3, 8, 2, |inner| {
+                prop_oneof![
+                    (inner.clone(), inner.clone())
+                        .prop_map(|(a, b)| FilterExpr::And(Box::new(a), Box::new(b))),
+                    (inner.clone(), inner)
+                        .prop_map(|(a, b)| FilterExpr::Or(Box::new(a), Box::new(b))),
+                ]
+            })
+            .boxed()
+    }
+
+    fn arith() -> BoxedStrategy<ArithExpr> {
+        let leaf = prop_oneof![
+            num().prop_map(ArithExpr::Number),
+            name().prop_map(ArithExpr::Column),
+        ];
+        leaf.prop_recursive(3, 8, 2, |inner| {
+            let op = prop_oneof![
+                Just(ArithOp::Add),
+                Just(ArithOp::Sub),
+                Just(ArithOp::Mul),
+                Just(ArithOp::Div),
+            ];
+            let function = prop_oneof![
+                Just(ArithFunction::Abs),
+                Just(ArithFunction::Log),
+                Just(ArithFunction::Log10),
+                Just(ArithFunction::Exp),
+                Just(ArithFunction::Sqrt),
+            ];
+            prop_oneof![
+                // The parser folds a negated number into the number
+                inner.clone().prop_map(|e| match e {
+                    ArithExpr::Number(n) => ArithExpr::Number(-n),
+                    e => ArithExpr::Neg(Box::new(e)),
+                }),
+                (op, inner.clone(), inner.clone()).prop_map(|(op, left, right)| {
+                    ArithExpr::Binary {
+                        op,
+                        left: Box::new(left),
+                        right: Box::new(right),
+                    }
+                }),
+                (function, inner).prop_map(|(function, arg)| ArithExpr::Call {
+                    function,
+                    arg: Box::new(arg),
+                }),
+            ]
+        })
+        .boxed()
+    }
+
+    fn stage() -> BoxedStrategy<DataStage> {
+        let function = prop_oneof![
+            Just(SummaryFunction::Mean),
+            Just(SummaryFunction::Sum),
+            Just(SummaryFunction::Min),
+            Just(SummaryFunction::Max),
+            Just(SummaryFunction::Median),
+            Just(SummaryFunction::Count),
+        ];
+        prop_oneof![
+            filter().prop_map(DataStage::Filter),
+            (vec(name(), 0..3), function, of(name()), of(name())).prop_map(
+                |(by, function, column, alias)| {
+                    // Only count() may leave out its column
+                    let column = match function {
+                        SummaryFunction::Count => column,
+                        _ => Some(column.unwrap_or_else(|| "v".to_string())),
+                    };
+                    DataStage::Summarize(Summarize {
+                        by,
+                        function,
+                        column,
+                        alias,
+                    })
+                }
+            ),
+            vec((name(), any::<bool>()), 1..4).prop_map(|keys| DataStage::Sort(
+                keys.into_iter()
+                    .map(|(column, desc)| SortKey { column, desc })
+                    .collect()
+            )),
+            (0usize..100_000).prop_map(DataStage::Head),
+            (0usize..100_000, any::<u64>())
+                .prop_map(|(n, seed)| DataStage::Sample(Sample { n, seed })),
+            vec((name(), arith()), 1..3).prop_map(|mutations| DataStage::Mutate(
+                mutations
+                    .into_iter()
+                    .map(|(name, expr)| Mutation { name, expr })
+                    .collect()
+            )),
+            (vec(name(), 1..3), name(), name()).prop_map(|(cols, names_to, values_to)| {
+                DataStage::PivotLonger(PivotLonger {
+                    cols,
+                    names_to,
+                    values_to,
+                    keep: false,
+                })
+            }),
+        ]
+        .boxed()
+    }
+
+    /// Every argument a layer might take; `layer` keeps the ones its geometry can spell
+    #[derive(Debug, Clone)]
+    struct Bag {
+        kind: u8,
+        x: Option<ColumnRef>,
+        y: Option<ColumnRef>,
+        ymin: Option<ColumnRef>,
+        ymax: Option<ColumnRef>,
+        color: Option<AestheticValue<String>>,
+        shape: Option<AestheticValue<String>>,
+        width: Option<AestheticValue<f64>>,
+        alpha: Option<AestheticValue<f64>>,
+        size: Option<AestheticValue<f64>>,
+        nums: (Option<f64>, Option<f64>, Option<f64>, f64, f64),
+        count: usize,
+        texts: (Option<String>, Option<String>, String),
+        flags: (bool, bool),
+        choice: u8,
+        filter: Option<FilterExpr>,
+        data: Option<String>,
+    }
+
+    fn bag() -> BoxedStrategy<Bag> {
+        (
+            (
+                0u8..25,
+                of(column()),
+                of(column()),
+                of(column()),
+                of(column()),
+            ),
+            (
+                of(styled()),
+                of(styled()),
+                of(numeric()),
+                of(numeric()),
+                of(numeric()),
+            ),
+            (of(num()), of(num()), of(num()), num(), num()),
+            (1usize..200, of(text()), of(text()), text()),
+            (
+                any::<bool>(),
+                any::<bool>(),
+                0u8..4,
+                of(filter()),
+                of(text()),
+            ),
+        )
+            .prop_map(
+                |(
+                    (kind, x, y, ymin, ymax),
+                    (color, shape, width, alpha, size),
+                    nums,
+                    (count, text1, text2, text3),
+                    (flag1, flag2, choice, filter, data),
+                )| Bag {
+                    kind,
+                    x,
+                    y,
+                    ymin,
+                    ymax,
+                    color,
+                    shape,
+                    width,
+                    alpha,
+                    size,
+                    nums,
+                    count,
+                    texts: (text1, text2, text3),
+                    flags: (flag1, flag2),
+                    choice,
+                    filter,
+                    data,
+                },
+            )
+            .boxed()
+    }
+
+    fn fixed<T>(value: Option<AestheticValue<T>>) -> Option<T> {
+        match value {
+            Some(AestheticValue::Fixed(v)) => Some(v),
+            _ => None,
+        }
+    }
+
+    fn layer(b: Bag) -> Layer {
+        let (n1, n2, n3, n4, n5) = b.nums;
+        let (t1, t2, t3) = b.texts;
+        let (flag1, flag2) = b.flags;
+        let (filter, data) = (b.filter, b.data);
+        match b.kind {
+            0 => Layer::Line(LineLayer {
+                x: b.x,
+                y: b.y,
+                color: b.color,
+                width: b.width,
+                alpha: b.alpha,
+                linetype: b.shape.map(|shape| match shape {
+                    // The parser accepts only the drawn dash patterns
+                    AestheticValue::Fixed(text) => {
+                        let linetypes =
+                            crate::parser::arguments::allowed_values("line", "linetype").unwrap();
+                        AestheticValue::Fixed(linetypes[text.len() % linetypes.len()].to_string())
+                    }
+                    mapped => mapped,
+                }),
+                group: b.ymin,
+                position: if flag1 {
+                    LinePosition::Dodge
+                } else {
+                    LinePosition::Identity
+                },
+                stat: if flag2 {
+                    Stat::RollingMean {
+                        window: b.count,
+                        min_periods: n1.map(|n| n.abs() as usize % 100),
+                    }
+                } else {
+                    Stat::Identity
+                },
+                simplify: b.choice < 2,
+                filter,
+                data,
+                ..Default::default()
+            }),
+            1 => Layer::Line(LineLayer {
+                x: b.x,
+                y: b.y,
+                color: b.color,
+                width: b.width,
+                alpha: b.alpha,
+                interpolation: match b.choice {
+                    0 => LineInterpolation::StepVH,
+                    1 => LineInterpolation::StepMid,
+                    _ => LineInterpolation::StepHV,
+                },
+                filter,
+                data,
+                ..Default::default()
+            }),
+            2 => Layer::Line(LineLayer {
+                x: b.x,
+                color: b.color,
+                width: b.width,
+                alpha: b.alpha,
+                stat: Stat::Bin { bins: b.count },
+                filter,
+                data,
+                ..Default::default()
+            }),
+            3 => Layer::Line(LineLayer {
+                x: b.x,
+                y: b.y,
+                color: b.color,
+                width: b.width,
+                alpha: b.alpha,
+                stat: Stat::Smooth {
+                    method: t3,
+                    span: n1,
+                    samples: Some(b.count + 1).filter(|_| flag1),
+                },
+                filter,
+                data,
+                ..Default::default()
+            }),
+            4 => Layer::Point(PointLayer {
+                x: b.x,
+                y: b.y,
+                color: b.color,
+                size: b.size,
+                shape: b.shape,
+                alpha: b.alpha,
+                fade_by: b.ymin,
+                fade: if flag1 {
+                    FadeDirection::Newest
+                } else {
+                    FadeDirection::Oldest
+                },
+                position: match b.choice {
+                    0 => PointPosition::Jitter,
+                    1 => PointPosition::Dodge,
+                    _ => PointPosition::Identity,
+                },
+                jitter_width: n1,
+                fill: t1,
+                stroke: n2,
+                fast: flag2,
+                filter,
+                data,
+                ..Default::default()
+            }),
+            5 => Layer::Bar(BarLayer {
+                x: b.x,
+                y: b.y,
+                color: b.color,
+                width: b.width,
+                alpha: b.alpha,
+                position: match b.choice {
+                    0 => BarPosition::Dodge,
+                    1 => BarPosition::Stack,
+                    2 => BarPosition::Fill,
+                    _ => BarPosition::Identity,
+                },
+                stat: match b.count % 6 {
+                    0 => Stat::Count,
+                    1 => Stat::Summary {
+                        fun: Aggregate::Sum,
+                    },
+                    2 => Stat::Summary {
+                        fun: Aggregate::Mean,
+                    },
+                    3 => Stat::Summary {
+                        fun: Aggregate::Min,
+                    },
+                    4 => Stat::Summary {
+                        fun: Aggregate::Max,
+                    },
+                    _ => Stat::Identity,
+                },
+                x_continuous: flag1,
+                border: t1,
+                border_width: n1,
+                filter,
+                data,
+            }),
+            6 => Layer::Bar(BarLayer {
+                stat: Stat::Bin { bins: b.count },
+                filter,
+                data,
+                ..Default::default()
+            }),
+            7 => Layer::Area(AreaLayer {
+                x: b.x,
+                y: b.y,
+                color: b.color,
+                alpha: b.alpha,
+                baseline: n4,
+                filter,
+                data,
+                ..Default::default()
+            }),
+            8 => Layer::Rug(RugLayer {
+                x: b.x,
+                y: b.y,
+                color: b.color,
+                width: b.width,
+                alpha: b.alpha,
+                sides: t3,
+                length: n4,
+                filter,
+                data,
+                ..Default::default()
+            }),
+            9 => Layer::Spike(SpikeLayer {
+                x: b.x,
+                y: b.y,
+                color: b.color,
+                width: b.width,
+                alpha: b.alpha,
+                baseline: n4,
+                filter,
+                data,
+                ..Default::default()
+            }),
+            10 => Layer::LineRange(LineRangeLayer {
+                x: b.x,
+                ymin: b.ymin,
+                ymax: b.ymax,
+                color: b.color,
+                width: b.width,
+                alpha: b.alpha,
+                filter,
+                data,
+                ..Default::default()
+            }),
+            11 => Layer::ErrorBar(ErrorBarLayer {
+                x: b.x,
+                ymin: b.ymin,
+                ymax: b.ymax,
+                color: b.color,
+                line_width: b.width,
+                alpha: b.alpha,
+                width: n4,
+                filter,
+                data,
+                ..Default::default()
+            }),
+            12 => Layer::PointRange(PointRangeLayer {
+                x: b.x,
+                y: b.y,
+                ymin: b.ymin,
+                ymax: b.ymax,
+                color: b.color,
+                width: b.width,
+                alpha: b.alpha,
+                size: b.size,
+                shape: b.shape,
+                filter,
+                data,
+                ..Default::default()
+            }),
+            13 => Layer::CrossBar(CrossBarLayer {
+                x: b.x,
+                y: b.y,
+                ymin: b.ymin,
+                ymax: b.ymax,
+                color: b.color,
+                alpha: b.alpha,
+                width: n4,
+                line_width: b.width,
+                filter,
+                data,
+                ..Default::default()
+            }),
+            14 => Layer::Ribbon(RibbonLayer {
+                x: b.x,
+                ymin: b.ymin,
+                ymax: b.ymax,
+                color: b.color,
+                alpha: b.alpha,
+                filter,
+                data,
+                ..Default::default()
+            }),
+            15 => Layer::Boxplot(BoxplotLayer {
+                stat: Stat::Boxplot,
+                x: b.x,
+                y: b.y,
+                color: b.color,
+                alpha: b.alpha,
+                width: b.width,
+                outlier_color: t1,
+                outlier_size: n1,
+                outlier_shape: t2,
+                filter,
+                data,
+                ..Default::default()
+            }),
+            16 => {
+                let draw_quantiles: Vec<f64> = [n1, n2, n3].into_iter().flatten().collect();
+                Layer::Violin(ViolinLayer {
+                    stat: Stat::Violin {
+                        draw_quantiles: draw_quantiles.clone(),
+                    },
+                    x: b.x,
+                    y: b.y,
+                    color: b.color,
+                    alpha: b.alpha,
+                    width: b.width,
+                    draw_quantiles,
+                    filter,
+                    data,
+                })
+            }
+            17 => Layer::Density(DensityLayer {
+                stat: Stat::Density { bw: n1 },
+                x: b.x,
+                color: b.color,
+                alpha: b.alpha,
+                bw: n1,
+                fill: Some(flag1).filter(|_| flag2),
+                filter,
+                data,
+            }),
+            18 => Layer::Heatmap(HeatmapLayer {
+                stat: Stat::Heatmap {
+                    bins: Some(b.count).filter(|_| flag1),
+                },
+                x: b.x,
+                y: b.y,
+                alpha: b.alpha,
+                fill: b.ymin,
+                filter,
+                data,
+            }),
+            19 => Layer::HLine(HLineLayer {
+                yintercept: n4,
+                color: t1,
+                width: n1,
+                alpha: n2,
+                label: t2,
+                ..Default::default()
+            }),
+            20 => Layer::VLine(VLineLayer {
+                xintercept: n4,
+                color: t1,
+                width: n1,
+                alpha: n2,
+                label: t2,
+                ..Default::default()
+            }),
+            21 => Layer::AbLine(AbLineLayer {
+                slope: n4,
+                intercept: n5,
+                color: t1,
+                width: n1,
+                alpha: n2,
+                label: t2,
+                ..Default::default()
+            }),
+            22 => Layer::Segment(SegmentLayer {
+                x: b.width,
+                y: b.alpha.clone(),
+                xend: b.size,
+                yend: n1.map(AestheticValue::Fixed),
+                color: b.color,
+                width: n2,
+                alpha: fixed(b.alpha.clone()).or(n3),
+                label: t1,
+                arrow: flag1,
+                ..Default::default()
+            }),
+            23 => Layer::Text(TextLayer {
+                x: b.x,
+                y: b.y,
+                label: b.ymin.unwrap_or(ColumnRef::Index(0)),
+                color: b.color,
+                size: n1,
+                alpha: b.alpha,
+                nudge_x: n4,
+                nudge_y: n5,
+                filter,
+                data,
+                ..Default::default()
+            }),
+            _ => Layer::Pie(PieLayer {
+                x: b.x,
+                y: b.y,
+                alpha: n1,
+                donut: n2,
+                other: n3,
+                filter,
+                data,
+                ..Default::default()
+            }),
+        }
+    }
+
+    fn aesthetics() -> BoxedStrategy<Aesthetics> {
+        (
+            (column(), of(column()), vec(column(), 0..3), of(column())),
+            (of(column()), of(column()), of(column()), of(column())),
+            (of(column()), of(column()), of(column()), of(column())),
+        )
+            .prop_map(
+                |(
+                    (x, y, y_columns, color),
+                    (size, shape, alpha, ymin),
+                    (ymax, fill, linetype, group),
+                )| Aesthetics {
+                    x,
+                    y,
+                    y_columns,
+                    color,
+                    size,
+                    shape,
+                    alpha,
+                    ymin,
+                    ymax,
+                    fill,
+                    linetype,
+                    group,
+                },
+            )
+            .boxed()
+    }
+
+    fn facet_scales() -> BoxedStrategy<FacetScales> {
+        prop_oneof![
+            Just(FacetScales::Fixed),
+            Just(FacetScales::FreeX),
+            Just(FacetScales::FreeY),
+            Just(FacetScales::Free),
+        ]
+        .boxed()
+    }
+
+    fn labeller() -> BoxedStrategy<FacetLabeller> {
+        prop_oneof![
+            Just(FacetLabeller::Equals),
+            Just(FacetLabeller::Value),
+            Just(FacetLabeller::Both),
+            text()
+                .prop_filter("a named labeller", |t| t != "value" && t != "both")
+                .prop_map(FacetLabeller::Template),
+        ]
+        .boxed()
+    }
+
+    fn facet() -> BoxedStrategy<Facet> {
+        (
+            (name(), of(0usize..50), of(0usize..50), facet_scales()),
+            (of(0usize..50), any::<bool>(), labeller(), of(num())),
+        )
+            .prop_map(
+                |((by, ncol, nrow, scales), (bins, quantile, labeller, label_size))| Facet {
+                    by,
+                    ncol,
+                    nrow,
+                    scales,
+                    bins,
+                    cut: if quantile {
+                        FacetCut::Quantile
+                    } else {
+                        FacetCut::Width
+                    },
+                    labeller,
+                    label_size,
+                },
+            )
+            .boxed()
+    }
+
+    fn facet_grid() -> BoxedStrategy<FacetGrid> {
+        (
+            name(),
+            of(name()),
+            facet_scales(),
+            labeller(),
+            of(num()),
+            any::<bool>(),
+        )
+            .prop_map(
+                |(first, second, scales, labeller, label_size, rows_first)| {
+                    // One of rows and cols is required
+                    let (rows, cols) = if rows_first {
+                        (Some(first), second)
+                    } else {
+                        (second, Some(first))
+                    };
+                    FacetGrid {
+                        rows,
+                        cols,
+                        scales,
+                        labeller,
+                        label_size,
+                    }
+                },
+            )
+            .boxed()
+    }
+
+    fn order() -> BoxedStrategy<CategoryOrder> {
+        prop_oneof![
+            Just(CategoryOrder::Appearance),
+            Just(CategoryOrder::Alpha),
+            Just(CategoryOrder::Value),
+        ]
+        .boxed()
+    }
+
+    fn axis_scale(is_x: bool) -> BoxedStrategy<AxisScale> {
+        let labels = (
+            (of(num()), of(text()), of(1usize..80), of(num())),
+            (
+                of(text()),
+                of(any::<bool>()),
+                of(1usize..20),
+                of(vec(num(), 1..4)),
+            ),
+        )
+            .prop_map(
+                |((rotate, format, max_label_len, expand), (parse, reverse, ticks, breaks))| {
+                    AxisLabels {
+                        rotate,
+                        format,
+                        max_label_len,
+                        expand,
+                        parse,
+                        reverse,
+                        ticks,
+                        breaks,
+                    }
+                },
+            );
+        (
+            0u8..6,
+            of((num(), num())),
+            of(text()),
+            of(text()),
+            of(order()),
+            labels,
+        )
+            .prop_map(move |(kind, limits, interval, format, order, labels)| {
+                let (scale_type, limits, datetime) = match kind {
+                    0 => (ScaleType::Log10, None, None),
+                    1 => (ScaleType::Sqrt, None, None),
+                    2 => (ScaleType::Reverse, None, None),
+                    // scale_x_datetime() always sets its options; y has no datetime command
+                    3 if is_x => (
+                        ScaleType::DateTime,
+                        None,
+                        Some(DateTimeScaleOptions { interval, format }),
+                    ),
+                    _ => (ScaleType::Linear, limits, None),
+                };
+                AxisScale {
+                    scale_type,
+                    limits,
+                    datetime,
+                    // Only scale_x_discrete() sets an order
+                    order: order.filter(|_| is_x),
+                    labels,
+                }
+            })
+            .boxed()
+    }
+
+    fn color_scale() -> BoxedStrategy<ColorScale> {
+        (text(), text(), of(text()), of(order()), vec(text(), 0..3))
+            .prop_map(|(low, high, palette, order, levels)| ColorScale {
+                low,
+                high,
+                palette,
+                order,
+                levels,
+            })
+            .boxed()
+    }
+
+    fn color_manual() -> BoxedStrategy<ManualColorScale> {
+        (vec((text(), text()), 0..3), vec(text(), 0..3))
+            .prop_map(|(named, ordered)| ManualColorScale { named, ordered })
+            .boxed()
+    }
+
+    fn element() -> BoxedStrategy<ThemeElement> {
+        prop_oneof![
+            Just(ThemeElement::Inherit),
+            Just(ThemeElement::Blank),
+            (of(text()), of(num()), of(linetype())).prop_map(|(color, width, linetype)| {
+                ThemeElement::Line(ElementLine {
+                    color,
+                    width,
+                    linetype,
+                })
+            }),
+            (of(text()), of(text()), of(num())).prop_map(|(fill, color, width)| {
+                ThemeElement::Rect(ElementRect { fill, color, width })
+            }),
+            (
+                (of(text()), of(text()), of(num()), of(text())),
+                (of(num()), of(num()), of(num())),
+            )
+                .prop_map(|((family, color, size, face), (angle, hjust, vjust))| {
+                    ThemeElement::Text(ElementText {
+                        family,
+                        color,
+                        size,
+                        face,
+                        angle,
+                        hjust,
+                        vjust,
+                    })
+                }),
+        ]
+        .boxed()
+    }
+
+    fn theme() -> BoxedStrategy<Theme> {
+        let position = prop_oneof![
+            Just(LegendPosition::UpperLeft),
+            Just(LegendPosition::UpperMiddle),
+            Just(LegendPosition::UpperRight),
+            Just(LegendPosition::MiddleLeft),
+            Just(LegendPosition::MiddleMiddle),
+            Just(LegendPosition::MiddleRight),
+            Just(LegendPosition::LowerLeft),
+            Just(LegendPosition::LowerMiddle),
+            Just(LegendPosition::LowerRight),
+            Just(LegendPosition::None),
+        ];
+        (
+            vec(element(), 13),
+            (of(position), of(num()), of(num()), of(num())),
+            of(any::<bool>()),
+        )
+            .prop_map(
+                |(
+                    elements,
+                    (legend_position, legend_margin, legend_key_size, margin),
+                    grid_separators,
+                )| {
+                    let mut e = elements.into_iter();
+                    let mut next = || e.next().unwrap();
+                    Theme {
+                        line: next(),
+                        rect: next(),
+                        text: next(),
+                        plot_background: next(),
+                        plot_title: next(),
+                        panel_background: next(),
+                        panel_grid_major: next(),
+                        panel_grid_minor: next(),
+                        axis_text: next(),
+                        axis_line: next(),
+                        axis_ticks: next(),
+                        legend_background: next(),
+                        legend_text: next(),
+                        legend_position,
+                        legend_margin,
+                        legend_key_size,
+                        margin,
+                        grid_separators,
+                    }
+                },
+            )
+            .boxed()
+    }
+
+    fn annotation_x() -> BoxedStrategy<AnnotationX> {
+        prop_oneof![
+            num().prop_map(AnnotationX::Number),
+            text().prop_map(AnnotationX::Text),
+        ]
+        .boxed()
+    }
+
+    fn annotation() -> BoxedStrategy<Annotation> {
+        (
+            (annotation_x(), num(), text(), of(text()), of(num())),
+            (num(), num(), any::<bool>(), any::<bool>(), any::<bool>()),
+            of(text()),
+        )
+            .prop_map(
+                |((x, y, text, color, size), (nudge_x, nudge_y, point, arrow, expand), panel)| {
+                    Annotation {
+                        x,
+                        y,
+                        text,
+                        color,
+                        size,
+                        nudge_x,
+                        nudge_y,
+                        point,
+                        arrow,
+                        expand,
+                        panel,
+                    }
+                },
+            )
+            .boxed()
+    }
+
+    fn shade() -> BoxedStrategy<Shade> {
+        (
+            annotation_x(),
+            of(annotation_x()),
+            of(num()),
+            of(num()),
+            (of(text()), of(num()), of(text())),
+            any::<bool>(),
+        )
+            .prop_map(
+                |(bound, xmax, ymin, ymax, (color, alpha, panel), on_xmin)| {
+                    // A band needs at least one side
+                    let (xmin, xmax) = if on_xmin {
+                        (Some(bound), xmax)
+                    } else {
+                        (xmax, Some(bound))
+                    };
+                    Shade {
+                        xmin,
+                        xmax,
+                        ymin,
+                        ymax,
+                        color,
+                        alpha,
+                        panel,
+                    }
+                },
+            )
+            .boxed()
+    }
+
+    fn labels() -> BoxedStrategy<Labels> {
+        (of(text()), of(text()), of(text()), of(text()), of(text()))
+            .prop_map(|(title, subtitle, x, y, caption)| Labels {
+                title,
+                subtitle,
+                x,
+                y,
+                caption,
+            })
+            .boxed()
+    }
+
+    fn coord() -> BoxedStrategy<CoordSystem> {
+        prop_oneof![
+            Just(CoordSystem::Flip),
+            num().prop_map(|ratio| CoordSystem::Fixed { ratio }),
+        ]
+        .boxed()
+    }
+
+    prop_compose! {
+        fn plot_spec()(
+            aesthetics in of(aesthetics()),
+            layers in vec(bag().prop_map(layer), 1..4),
+            stages in vec(stage(), 0..3),
+            facets in (of(facet()), of(facet_grid()), of(coord()), of(labels())),
+            scales in (of(axis_scale(true)), of(axis_scale(false)), of(color_scale()), of(color_manual())),
+            theme in of(theme()),
+            annotations in vec(annotation(), 0..2),
+            shades in vec(shade(), 0..2),
+        ) -> PlotSpec {
+            let (facet, facet_grid, coord, labels) = facets;
+            let (x_scale, y_scale, color_scale, color_manual) = scales;
+            PlotSpec {
+                aesthetics,
+                layers,
+                labels,
+                facet,
+                facet_grid,
+                coord,
+                theme,
+                x_scale,
+                y_scale,
+                color_scale,
+                color_manual,
+                stages,
+                annotations,
+                shades,
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn printed_specs_parse_back_to_the_same_spec(spec in plot_spec()) {
+            let dsl = spec.to_dsl();
+            let parsed = parse_plot_spec(&dsl);
+            prop_assert!(parsed.is_ok(), "{}\n{:?}", dsl, parsed);
+            let (rest, reparsed) = parsed.unwrap();
+            prop_assert_eq!(rest, "");
+            prop_assert_eq!(&reparsed, &spec, "{}", dsl);
+            prop_assert!(validate_arguments(&dsl).is_ok(), "{}", dsl);
+        }
+    }
+
+    #[test]
+    fn test_quote_escapes() {
+        assert_eq!(super::quote("a \"b\" \\ c\n\td"), r#""a \"b\" \\ c\n\td""#);
+    }
+
+    #[test]
+    fn test_column_quoting() {
+        assert_eq!(super::column("sales_2024"), "sales_2024");
+        assert_eq!(super::column("Sales (USD)"), "`Sales (USD)`");
+        assert_eq!(super::column("2024"), "`2024`");
+        assert_eq!(super::column("info"), "`info`");
+        assert_eq!(super::column("a`b"), "\"a`b\"");
+        assert_eq!(super::column_ref(&ColumnRef::Index(3)), "3");
+    }
+
+    fn parse(dsl: &str) -> PlotSpec {
+        let (rest, spec) = parse_plot_spec(dsl).unwrap();
+        assert_eq!(rest, "");
+        spec
+    }
+
+    #[test]
+    fn test_corpus_round_trips() {
+        let dsls = [
+            r#"aes(x: time, y: value, color: series) | line(width: 2) | point(size: 4, shape: "square") | labs(title: "T", caption: "C")"#,
+            r#"aes(x: quarter, y: amount, color: type) | bar(position: "stack", alpha: 0.8) | coord_flip() | facet_wrap(by: region, ncol: 2, scales: "free_y")"#,
+            r##"aes(x: value) | histogram(bins: 12) | theme_minimal() | theme(axis_text: element_text(size: 14, color: "#333"), legend_position: "lower-right")"##,
+            r#"aes(x: x, y: y) | hline(yintercept: 3, color: "red") | vline(xintercept: 1) | errorbar(ymin: lo, ymax: hi) | scale_y_log10()"#,
+            r#"aes(x: time, y: temp) | step(direction: "vh") | smooth(method: "loess") | scale_x_datetime(format: "%H:%M") | scale_color(palette: "set2")"#,
+            r#"aes(x: g, y: v, color: g) | boxplot() | violin(draw_quantiles: [0.5]) | scale_color_manual(values: "a" = "red", "blue")"#,
+            r#"aes(x: day, y: avg) | filter(site != "x" && (t > 1 || t <= -2)) | summarize(by: [site, day], y: median(t), as: avg) | point(filter: avg > 0)"#,
+            r#"pivot_longer(cols: [a, `B c`], names_to: k, values_to: v) | pivot_longer(cols: k) | aes(x: name, y: value) | point()"#,
+            r#"mutate(r: -log10(abs(a - b)) / (c + 2.5), s: r * r) | aes(x: a, y: s) | sort(by: s, desc: true) | head(n: 5) | point()"#,
+            r#"aes(x: source, y: share) | pie(donut: 0.4, other: 0.02, filter: share > 0)"#,
+            r#"aes(x: `Order Date`, y: 2, color: "Region Name") | line(linetype: region, group: id, position: "dodge") | point(position: "jitter", jitter_width: 0.3, fill: "white", stroke: 1.5)"#,
+            r#"aes(x: x, y: [a, b, c]) | line(stat: "rolling_mean", window: 7, min_periods: 3) | area(alpha: 0.2, baseline: 1) | ribbon(ymin: lo, ymax: hi, alpha: 0.3)"#,
+            r#"aes(x: cat, y: n) | bar(stat: "mean", position: "dodge", border: "black", border_width: 2) | text(label: n, nudge_y: 0.5, size: 10) | scale_x_discrete(order: "value") | scale_x(rotate: 45, max_label_len: 8)"#,
+            r#"aes(x: t, y: v) | point(alpha: w, size: w, color: g) | facet_grid(rows: a, cols: b, scales: "free", labeller: "both") | scale_y(format: "{:.1}%", breaks: 0, 50, 100) | ylim(0, 100)"#,
+            r#"aes(x: a) | density(bw: 0.5, fill: true) | freqpoly(bins: 20, color: "red") | rug(sides: "bl") | coord_fixed(ratio: 2)"#,
+            r#"aes(x: a, y: b, fill: z) | heatmap(bins: 25) | scale_color(low: "white", high: "navy", levels: "lo", "mid", "hi")"#,
+            r#"aes(x: x, y: y) | segment(x: 0, y: 0, xend: 1, yend: 1, arrow: true) | abline(slope: 2, intercept: -1) | annotate(x: 3, y: 4, text: "peak", arrow: true) | shade(xmin: 1, xmax: 2, alpha: 0.1)"#,
+            r#"aes(x: x, y: mid) | pointrange(ymin: lo, ymax: hi) | crossbar(ymin: lo, ymax: hi, width: 0.3) | linerange(ymin: lo, ymax: hi) | spike(color: "gray")"#,
+            r#"aes(x: 0, y: 3) | sample(n: 500, seed: 7) | point(fast: false, fade: "newest", fade_by: t) | facet_wrap(by: g, bins: 4, cut: "quantile", labeller: "{var}: {value}", label_size: 9)"#,
+            r##"aes(x: t, y: v) | line() | theme(panel_grid_minor: element_blank(), plot_background: element_rect(fill: "#fafafa"), legend_position: "none", margin: 20, grid_separators: false)"##,
+        ];
+        for dsl in dsls {
+            let spec = parse(dsl);
+            let printed = spec.to_dsl();
+            assert_eq!(parse(&printed), spec, "{}\nprinted as\n{}", dsl, printed);
+            // Printing is canonical: a printed spec prints the same way again
+            assert_eq!(parse(&printed).to_dsl(), printed);
+        }
+    }
+
+    #[test]
+    fn test_canonical_form() {
+        assert_eq!(
+            parse(r#"line(color: "red", y: v, x: t) | aes(color: g, x: a, y: b)"#).to_dsl(),
+            r#"aes(x: a, y: b, color: g) | line(x: t, y: v, color: "red")"#
+        );
+        assert_eq!(
+            parse(r#"df | point(color: region, size: 3, position: "dodge") | facet_wrap(scales: "free", by: site) | bar(position: "identity")"#)
+                .to_dsl(),
+            r#"point(color: region, size: 3, position: "dodge") | bar() | facet_wrap(by: site, scales: "free")"#
+        );
+        assert_eq!(
+            parse(r#"aes(x: `Sales (USD)`, y: 2) | histogram()"#).to_dsl(),
+            r#"aes(x: `Sales (USD)`, y: 2) | histogram()"#
+        );
+    }
+
+    #[test]
+    fn test_display_writes_the_dsl() {
+        let spec = parse(r#"aes(x: a, y: b) | line(color: "red") | labs(title: "Sales")"#);
+        assert_eq!(spec.to_string(), spec.to_dsl());
+    }
+
+    #[test]
+    fn test_built_specs_round_trip() {
+        use crate::{BarOptions, LineOptions, PointOptions};
+
+        let spec = PlotSpec::builder()
+            .aes("time", "temp")
+            .color("region")
+            .line(LineOptions::new().width(2.0).linetype_by("kind"))
+            .point(
+                PointOptions::new()
+                    .size(3.0)
+                    .shape("triangle")
+                    .fill("white"),
+            )
+            .bar(
+                BarOptions::new()
+                    .position(BarPosition::Dodge)
+                    .border("black"),
+            )
+            .title("Temperature")
+            .facet_wrap("site", 2)
+            .facet_scales(FacetScales::FreeY)
+            .build()
+            .unwrap();
+        assert_eq!(parse(&spec.to_dsl()), spec);
+
+        let spec = PlotSpec::builder()
+            .aes("time", "temp")
+            .color("region")
+            .line(LineOptions::new().width(2.0))
+            .facet_wrap("site", 2)
+            .build()
+            .unwrap();
+        assert_eq!(
+            spec.to_string(),
+            "aes(x: time, y: temp, color: region) | line(width: 2) | facet_wrap(by: site, ncol: 2)"
+        );
+    }
+}
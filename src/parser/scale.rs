@@ -1,12 +1,15 @@
-use crate::parser::ast::{AxisScale, DateTimeScaleOptions, ScaleType};
-use crate::parser::lexer::{number_literal, string_literal, ws};
+use crate::parser::ast::{
+    AxisLabels, AxisScale, CategoryOrder, ColorScale, DateTimeScaleOptions, ManualColorScale,
+    ScaleType,
+};
+use crate::parser::lexer::{bool_literal, number_literal, string_literal, ws};
 use nom::{
     branch::alt,
     bytes::complete::tag,
     character::complete::char,
-    combinator::map,
-    multi::separated_list0,
-    sequence::{delimited, preceded},
+    combinator::{map, opt},
+    multi::{separated_list0, separated_list1},
+    sequence::{delimited, preceded, separated_pair},
     IResult,
 };
 
@@ -14,7 +17,7 @@ fn axis_scale(scale_type: ScaleType, limits: Option<(f64, f64)>) -> AxisScale {
     AxisScale {
         scale_type,
         limits,
-        datetime: None,
+        ..AxisScale::default()
     }
 }
 
@@ -114,15 +117,216 @@ pub fn parse_scale_x_datetime(input: &str) -> IResult<&str, AxisScale> {
         input,
         AxisScale {
             scale_type: ScaleType::DateTime,
-            limits: None,
             datetime: Some(datetime),
+            ..AxisScale::default()
         },
     ))
 }
 
+fn category_order(order: &str) -> CategoryOrder {
+    match order {
+        "alpha" | "alphabetical" => CategoryOrder::Alpha,
+        "value" => CategoryOrder::Value,
+        _ => CategoryOrder::Appearance,
+    }
+}
+
+/// Format: scale_x_discrete(order: "appearance" | "alpha" | "value")
+pub fn parse_scale_x_discrete(input: &str) -> IResult<&str, AxisScale> {
+    let (input, _) = ws(tag("scale_x_discrete"))(input)?;
+    let (input, _) = ws(char('('))(input)?;
+    let (input, order) = opt(preceded(ws(tag("order:")), ws(string_literal)))(input)?;
+    let (input, _) = ws(char(')'))(input)?;
+
+    let order = order.as_deref().map(category_order);
+
+    Ok((
+        input,
+        AxisScale {
+            order,
+            ..AxisScale::default()
+        },
+    ))
+}
+
+#[derive(Debug)]
+enum AxisLabelsArg {
+    Rotate(f64),
+    Format(String),
+    MaxLabelLen(f64),
+    Expand(f64),
+    Parse(String),
+    Reverse(bool),
+    Ticks(f64),
+    Breaks(Vec<f64>),
+}
+
+/// Parse tick label options for one axis
+/// Format: scale_x(rotate: 90, format: "comma", max_label_len: 12, expand: 0.05,
+/// parse: "percent", reverse: true, ticks: 5, breaks: 0, 25, 50); scale_y takes the same
+/// options, though rotation only applies on the x axis
+fn parse_axis_labels<'a>(
+    name: &'static str,
+) -> impl FnMut(&'a str) -> IResult<&'a str, AxisLabels> {
+    move |input| {
+        let (input, _) = ws(tag(name))(input)?;
+        let (input, _) = ws(char('('))(input)?;
+        let (input, args) = separated_list0(
+            ws(char(',')),
+            alt((
+                map(
+                    preceded(ws(tag("rotate:")), ws(number_literal)),
+                    AxisLabelsArg::Rotate,
+                ),
+                map(
+                    preceded(ws(tag("format:")), ws(string_literal)),
+                    AxisLabelsArg::Format,
+                ),
+                map(
+                    preceded(ws(tag("max_label_len:")), ws(number_literal)),
+                    AxisLabelsArg::MaxLabelLen,
+                ),
+                map(
+                    preceded(ws(tag("expand:")), ws(number_literal)),
+                    AxisLabelsArg::Expand,
+                ),
+                map(
+                    preceded(ws(tag("parse:")), ws(string_literal)),
+                    AxisLabelsArg::Parse,
+                ),
+                map(
+                    preceded(ws(tag("reverse:")), ws(bool_literal)),
+                    AxisLabelsArg::Reverse,
+                ),
+                map(
+                    preceded(ws(tag("ticks:")), ws(number_literal)),
+                    AxisLabelsArg::Ticks,
+                ),
+                map(
+                    preceded(
+                        ws(tag("breaks:")),
+                        separated_list1(ws(char(',')), ws(number_literal)),
+                    ),
+                    AxisLabelsArg::Breaks,
+                ),
+            )),
+        )(input)?;
+        let (input, _) = ws(char(')'))(input)?;
+
+        let mut labels = AxisLabels::default();
+        for arg in args {
+            match arg {
+                AxisLabelsArg::Rotate(angle) => labels.rotate = Some(angle),
+                AxisLabelsArg::Format(format) => labels.format = Some(format),
+                AxisLabelsArg::MaxLabelLen(len) => {
+                    labels.max_label_len = Some(len.max(1.0).round() as usize)
+                }
+                AxisLabelsArg::Expand(fraction) => labels.expand = Some(fraction),
+                AxisLabelsArg::Parse(unit) => labels.parse = Some(unit),
+                AxisLabelsArg::Reverse(reverse) => labels.reverse = Some(reverse),
+                AxisLabelsArg::Ticks(count) => labels.ticks = Some(count.max(1.0).round() as usize),
+                AxisLabelsArg::Breaks(breaks) => labels.breaks = Some(breaks),
+            }
+        }
+        Ok((input, labels))
+    }
+}
+
+/// Parse `scale_x(...)` or `scale_y(...)` tick label options, flagged true for x
+pub fn parse_axis_labels_command(input: &str) -> IResult<&str, (bool, AxisLabels)> {
+    alt((
+        map(parse_axis_labels("scale_x"), |labels| (true, labels)),
+        map(parse_axis_labels("scale_y"), |labels| (false, labels)),
+    ))(input)
+}
+
+#[derive(Debug)]
+enum ColorScaleArg {
+    Text(&'static str, String),
+    Levels(Vec<String>),
+}
+
+/// Parse a color scale
+/// Format: scale_color(low: "blue", high: "red", palette: "set2", order: "appearance",
+/// levels: "A", "C", "B"); omitted values keep their defaults
+pub fn parse_scale_color(input: &str) -> IResult<&str, ColorScale> {
+    let (input, _) = ws(alt((tag("scale_color"), tag("scale_colour"))))(input)?;
+    let (input, _) = ws(char('('))(input)?;
+    let (input, args) = separated_list0(
+        ws(char(',')),
+        alt((
+            map(preceded(ws(tag("low:")), ws(string_literal)), |v| {
+                ColorScaleArg::Text("low", v)
+            }),
+            map(preceded(ws(tag("high:")), ws(string_literal)), |v| {
+                ColorScaleArg::Text("high", v)
+            }),
+            map(preceded(ws(tag("palette:")), ws(string_literal)), |v| {
+                ColorScaleArg::Text("palette", v)
+            }),
+            map(preceded(ws(tag("order:")), ws(string_literal)), |v| {
+                ColorScaleArg::Text("order", v)
+            }),
+            map(
+                preceded(
+                    ws(tag("levels:")),
+                    separated_list1(ws(char(',')), ws(string_literal)),
+                ),
+                ColorScaleArg::Levels,
+            ),
+        )),
+    )(input)?;
+    let (input, _) = ws(char(')'))(input)?;
+
+    let mut scale = ColorScale::default();
+    for arg in args {
+        match arg {
+            ColorScaleArg::Text("low", value) => scale.low = value,
+            ColorScaleArg::Text("high", value) => scale.high = value,
+            ColorScaleArg::Text("palette", value) => scale.palette = Some(value),
+            ColorScaleArg::Text("order", value) => scale.order = Some(category_order(&value)),
+            ColorScaleArg::Text(..) => {}
+            ColorScaleArg::Levels(levels) => scale.levels = levels,
+        }
+    }
+
+    Ok((input, scale))
+}
+
+/// Parse fixed colors for categorical groups
+/// Format: scale_color_manual(values: "North" = "blue", "South" = "red")
+///     or: scale_color_manual("blue", "red", "green")
+pub fn parse_scale_color_manual(input: &str) -> IResult<&str, ManualColorScale> {
+    let (input, _) = ws(alt((tag("scale_color_manual"), tag("scale_colour_manual"))))(input)?;
+    let (input, _) = ws(char('('))(input)?;
+    let (input, _) = opt(ws(tag("values:")))(input)?;
+    let (input, entries) = separated_list0(
+        ws(char(',')),
+        alt((
+            map(
+                separated_pair(ws(string_literal), char('='), ws(string_literal)),
+                |(key, color)| (Some(key), color),
+            ),
+            map(ws(string_literal), |color| (None, color)),
+        )),
+    )(input)?;
+    let (input, _) = ws(char(')'))(input)?;
+
+    let mut scale = ManualColorScale::default();
+    for (key, color) in entries {
+        match key {
+            Some(key) => scale.named.push((key, color)),
+            None => scale.ordered.push(color),
+        }
+    }
+
+    Ok((input, scale))
+}
+
 pub fn parse_scale_command(input: &str) -> IResult<&str, (bool, AxisScale)> {
     alt((
         map(parse_scale_x_datetime, |s| (true, s)),
+        map(parse_scale_x_discrete, |s| (true, s)),
         map(parse_scale_x_log10, |s| (true, s)),
         map(parse_scale_y_log10, |s| (false, s)),
         map(parse_scale_x_sqrt, |s| (true, s)),
@@ -164,6 +368,58 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_scale_color_endpoints() {
+        let (_, scale) = parse_scale_color(r#"scale_color(low: "blue", high: "red")"#).unwrap();
+        assert_eq!(scale.low, "blue");
+        assert_eq!(scale.high, "red");
+
+        let (_, scale) = parse_scale_color(r#"scale_colour(high: "orange")"#).unwrap();
+        assert_eq!(scale.low, ColorScale::default().low);
+        assert_eq!(scale.high, "orange");
+
+        let (_, scale) = parse_scale_color(r#"scale_color(palette: "dark2")"#).unwrap();
+        assert_eq!(scale.palette.as_deref(), Some("dark2"));
+    }
+
+    #[test]
+    fn parse_scale_color_group_order() {
+        let (_, scale) = parse_scale_color(r#"scale_color(order: "appearance")"#).unwrap();
+        assert_eq!(scale.order, Some(CategoryOrder::Appearance));
+        assert!(scale.levels.is_empty());
+
+        // The level list ends at the next keyword argument
+        let (rest, scale) =
+            parse_scale_color(r#"scale_color(levels: "A", "C", "B", palette: "set2")"#).unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(scale.levels, vec!["A", "C", "B"]);
+        assert_eq!(scale.palette.as_deref(), Some("set2"));
+        assert_eq!(scale.order, None);
+
+        assert!(parse_scale_color("scale_color(levels: )").is_err());
+    }
+
+    #[test]
+    fn parse_scale_color_manual_forms() {
+        let (_, scale) = parse_scale_color_manual(
+            r##"scale_color_manual(values: "North" = "blue", "South"="#ff0000")"##,
+        )
+        .unwrap();
+        assert_eq!(
+            scale.named,
+            vec![
+                ("North".to_string(), "blue".to_string()),
+                ("South".to_string(), "#ff0000".to_string()),
+            ]
+        );
+        assert!(scale.ordered.is_empty());
+
+        let (_, scale) =
+            parse_scale_color_manual(r#"scale_colour_manual("blue", "red", "green")"#).unwrap();
+        assert!(scale.named.is_empty());
+        assert_eq!(scale.ordered, vec!["blue", "red", "green"]);
+    }
+
     #[test]
     fn parse_scale_x_sqrt_command() {
         let (_, scale) = parse_scale_x_sqrt("scale_x_sqrt()").unwrap();
@@ -175,4 +431,53 @@ mod tests {
         let (_, scale) = parse_scale_y_sqrt("scale_y_sqrt()").unwrap();
         assert_eq!(scale.scale_type, ScaleType::Sqrt);
     }
+
+    #[test]
+    fn parse_scale_x_discrete_order() {
+        let (_, scale) = parse_scale_x_discrete(r#"scale_x_discrete(order: "value")"#).unwrap();
+        assert_eq!(scale.order, Some(CategoryOrder::Value));
+        assert_eq!(scale.scale_type, ScaleType::Linear);
+
+        let (_, scale) = parse_scale_x_discrete(r#"scale_x_discrete(order: "alpha")"#).unwrap();
+        assert_eq!(scale.order, Some(CategoryOrder::Alpha));
+
+        let (_, scale) = parse_scale_x_discrete("scale_x_discrete()").unwrap();
+        assert_eq!(scale.order, None);
+    }
+
+    #[test]
+    fn parse_axis_label_options() {
+        let (_, (is_x, labels)) =
+            parse_axis_labels_command(r#"scale_x(rotate: 90, format: "si", max_label_len: 8)"#)
+                .unwrap();
+        assert!(is_x);
+        assert_eq!(labels.rotate, Some(90.0));
+        assert_eq!(labels.format.as_deref(), Some("si"));
+        assert_eq!(labels.max_label_len, Some(8));
+
+        let (_, (is_x, labels)) = parse_axis_labels_command(r#"scale_y(format: "%.2f")"#).unwrap();
+        assert!(!is_x);
+        assert_eq!(labels.format.as_deref(), Some("%.2f"));
+        assert_eq!(labels.rotate, None);
+
+        let (_, (_, labels)) = parse_axis_labels_command("scale_y(expand: 0.05)").unwrap();
+        assert_eq!(labels.expand, Some(0.05));
+        let (_, (_, labels)) = parse_axis_labels_command("scale_x(expand: 0)").unwrap();
+        assert_eq!(labels.expand, Some(0.0));
+        let (_, (_, labels)) =
+            parse_axis_labels_command(r#"scale_y(parse: "percent", format: "%.1f%%")"#).unwrap();
+        assert_eq!(labels.parse.as_deref(), Some("percent"));
+        assert_eq!(labels.format.as_deref(), Some("%.1f%%"));
+        let (_, (_, labels)) = parse_axis_labels_command("scale_y(reverse: true)").unwrap();
+        assert_eq!(labels.reverse, Some(true));
+        let (_, (_, labels)) =
+            parse_axis_labels_command(r#"scale_x(breaks: 0, 25, 50, ticks: 4, format: "si")"#)
+                .unwrap();
+        assert_eq!(labels.breaks, Some(vec![0.0, 25.0, 50.0]));
+        assert_eq!(labels.ticks, Some(4));
+        assert_eq!(labels.format.as_deref(), Some("si"));
+
+        // The typed scale commands are not mistaken for label options
+        assert!(parse_axis_labels_command("scale_x_log10()").is_err());
+    }
 }
@@ -0,0 +1,474 @@
+// Data stage parsers: filter(), summarize(), sort(), head(), sample(), mutate(), and
+// pivot_longer()
+//
+// Stages reshape the rows before any layer sees them and apply in pipeline order, so
+// `summarize(...) | filter(mean_temp > 20)` filters the aggregated table.
+
+use super::arithmetic::arith_expr;
+use super::ast::{DataStage, Mutation, PivotLonger, Sample, SortKey, Summarize, SummaryFunction};
+use super::filter::filter_expr;
+use super::lexer::{bool_literal, column_name, ws};
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::char,
+    combinator::{map, not, opt, value},
+    multi::separated_list1,
+    sequence::{delimited, preceded, separated_pair, terminated, tuple},
+    IResult,
+};
+
+/// Parse any data stage
+pub fn parse_data_stage(input: &str) -> IResult<&str, DataStage> {
+    alt((
+        map(parse_filter_stage, DataStage::Filter),
+        map(parse_summarize, DataStage::Summarize),
+        map(parse_sort, DataStage::Sort),
+        map(parse_head, DataStage::Head),
+        map(parse_sample, DataStage::Sample),
+        map(parse_mutate, DataStage::Mutate),
+        map(parse_pivot_longer, DataStage::PivotLonger),
+    ))(input)
+}
+
+enum PivotArg {
+    Cols(Vec<String>),
+    NamesTo(String),
+    ValuesTo(String),
+}
+
+/// Parse pivot_longer specification
+/// Format: pivot_longer(cols: [north, south, east], names_to: region, values_to: sales)
+/// - cols: required (a bracketed list, or bare columns up to the next `key:`)
+/// - names_to: optional (column for the melted headers; default `name`)
+/// - values_to: optional (column for the melted cells; default `value`)
+pub fn parse_pivot_longer(input: &str) -> IResult<&str, PivotLonger> {
+    let (input, _) = ws(tag("pivot_longer"))(input)?;
+    let (input, _) = ws(char('('))(input)?;
+    let (input, args) = nom::multi::separated_list0(
+        ws(char(',')),
+        alt((
+            map(
+                preceded(
+                    ws(tag("cols:")),
+                    alt((
+                        // `cols: a, b, c`: stop before a name followed by `:`
+                        separated_list1(ws(char(',')), terminated(ws(column_name), not(char(':')))),
+                        column_list,
+                    )),
+                ),
+                PivotArg::Cols,
+            ),
+            map(
+                preceded(ws(tag("names_to:")), ws(column_name)),
+                PivotArg::NamesTo,
+            ),
+            map(
+                preceded(ws(tag("values_to:")), ws(column_name)),
+                PivotArg::ValuesTo,
+            ),
+        )),
+    )(input)?;
+    let (input, _) = ws(char(')'))(input)?;
+
+    let mut pivot = PivotLonger::default();
+    for arg in args {
+        match arg {
+            PivotArg::Cols(cols) => pivot.cols = cols,
+            PivotArg::NamesTo(name) => pivot.names_to = name,
+            PivotArg::ValuesTo(name) => pivot.values_to = name,
+        }
+    }
+
+    // Validate: "cols" is required
+    if pivot.cols.is_empty() {
+        return Err(nom::Err::Failure(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::Verify,
+        )));
+    }
+    Ok((input, pivot))
+}
+
+/// Parse mutate specification
+/// Format: mutate(margin: revenue - cost, pct: margin / revenue * 100)
+/// - one or more `name: expr` pairs, computed in order (later ones may use earlier ones)
+pub fn parse_mutate(input: &str) -> IResult<&str, Vec<Mutation>> {
+    let (input, _) = ws(tag("mutate"))(input)?;
+    let (input, mutations) = delimited(
+        ws(char('(')),
+        separated_list1(
+            ws(char(',')),
+            map(
+                separated_pair(ws(column_name), char(':'), arith_expr),
+                |(name, expr)| Mutation { name, expr },
+            ),
+        ),
+        ws(char(')')),
+    )(input)?;
+    Ok((input, mutations))
+}
+
+/// Parse head specification
+/// Format: head(n: 1000)
+pub fn parse_head(input: &str) -> IResult<&str, usize> {
+    let (input, _) = ws(tag("head"))(input)?;
+    let (input, n) = delimited(
+        ws(char('(')),
+        preceded(ws(tag("n:")), ws(nom::character::complete::u64)),
+        ws(char(')')),
+    )(input)?;
+    Ok((input, n as usize))
+}
+
+/// Parse sample specification
+/// Format: sample(n: 10000, seed: 7)
+/// - n: required (rows to keep)
+/// - seed: optional (which rows are drawn; default 0)
+pub fn parse_sample(input: &str) -> IResult<&str, Sample> {
+    let (input, _) = ws(tag("sample"))(input)?;
+    let (input, _) = ws(char('('))(input)?;
+    let (input, args) = nom::multi::separated_list0(
+        ws(char(',')),
+        alt((
+            map(
+                preceded(ws(tag("n:")), ws(nom::character::complete::u64)),
+                |n| ("n", n),
+            ),
+            map(
+                preceded(ws(tag("seed:")), ws(nom::character::complete::u64)),
+                |seed| ("seed", seed),
+            ),
+        )),
+    )(input)?;
+    let (input, _) = ws(char(')'))(input)?;
+
+    let mut n = None;
+    let mut seed = 0;
+    for (key, value) in args {
+        match key {
+            "n" => n = Some(value as usize),
+            _ => seed = value,
+        }
+    }
+
+    // Validate: "n" is required
+    let n = n.ok_or_else(|| {
+        nom::Err::Failure(nom::error::Error::new(input, nom::error::ErrorKind::Verify))
+    })?;
+    Ok((input, Sample { n, seed }))
+}
+
+/// A column or a bracketed list of columns: `day` or `[site, day]`
+fn column_list(input: &str) -> IResult<&str, Vec<String>> {
+    alt((
+        delimited(
+            ws(char('[')),
+            separated_list1(ws(char(',')), ws(column_name)),
+            ws(char(']')),
+        ),
+        map(ws(column_name), |col| vec![col]),
+    ))(input)
+}
+
+/// Parse sort specification
+/// Format: sort(by: [region, sales], desc: [false, true])
+/// - by: required (a column or a list of columns, compared in order)
+/// - desc: optional (one flag for every column, or one per column; default false)
+pub fn parse_sort(input: &str) -> IResult<&str, Vec<SortKey>> {
+    let start = input;
+    let (input, (by, desc)) = sort_args(input)?;
+
+    // Validate: "by" is required, and a desc list must match it. Fail at `sort(`
+    // so the error points at this stage, not the component after it.
+    let keys = match by {
+        Some(by) if desc.len() == 1 || desc.len() == by.len() => by
+            .into_iter()
+            .enumerate()
+            .map(|(i, column)| SortKey {
+                column,
+                desc: desc[if desc.len() == 1 { 0 } else { i }],
+            })
+            .collect(),
+        _ => {
+            return Err(nom::Err::Failure(nom::error::Error::new(
+                start,
+                nom::error::ErrorKind::Verify,
+            )))
+        }
+    };
+    Ok((input, keys))
+}
+
+/// The number of `by` columns and `desc` flags in a `sort(...)` that `parse_sort`
+/// rejected, for the error message
+pub fn sort_key_counts(input: &str) -> Option<(usize, usize)> {
+    let (_, (by, desc)) = sort_args(input).ok()?;
+    Some((by.map_or(0, |by| by.len()), desc.len()))
+}
+
+/// `sort(...)`'s `by` columns (if given) and `desc` flags (`[false]` if not given)
+fn sort_args(input: &str) -> IResult<&str, (Option<Vec<String>>, Vec<bool>)> {
+    let (input, _) = ws(tag("sort"))(input)?;
+    let (input, _) = ws(char('('))(input)?;
+    let (input, args) = nom::multi::separated_list0(
+        ws(char(',')),
+        alt((
+            map(preceded(ws(tag("by:")), column_list), |cols| {
+                (Some(cols), None)
+            }),
+            map(
+                preceded(
+                    ws(tag("desc:")),
+                    alt((
+                        delimited(
+                            ws(char('[')),
+                            separated_list1(ws(char(',')), ws(bool_literal)),
+                            ws(char(']')),
+                        ),
+                        map(ws(bool_literal), |flag| vec![flag]),
+                    )),
+                ),
+                |flags| (None, Some(flags)),
+            ),
+        )),
+    )(input)?;
+    let (input, _) = ws(char(')'))(input)?;
+
+    let mut by = None;
+    let mut desc = vec![false];
+    for (cols, flags) in args {
+        by = cols.or(by);
+        desc = flags.unwrap_or(desc);
+    }
+    Ok((input, (by, desc)))
+}
+
+/// Parse a `filter(expr)` pipeline stage
+pub fn parse_filter_stage(input: &str) -> IResult<&str, super::ast::FilterExpr> {
+    preceded(
+        ws(tag("filter")),
+        delimited(ws(char('(')), filter_expr, ws(char(')'))),
+    )(input)
+}
+
+enum SummarizeArg {
+    By(Vec<String>),
+    Y(SummaryFunction, Option<String>),
+    As(String),
+}
+
+/// Parse summarize specification
+/// Format: summarize(by: day, y: mean(temp), as: avg_temp)
+/// - by: optional (a column or `[col, col]`; without it every row forms one group)
+/// - y: required (`mean`, `sum`, `min`, `max`, `median`, or `count` of a column; `count()`
+///   counts rows)
+/// - as: optional (output column name; defaults to e.g. `mean_temp`)
+pub fn parse_summarize(input: &str) -> IResult<&str, Summarize> {
+    let (input, _) = ws(alt((tag("summarize"), tag("summarise"))))(input)?;
+    let (input, _) = ws(char('('))(input)?;
+    let (input, args) = nom::multi::separated_list0(
+        ws(char(',')),
+        alt((
+            map(preceded(ws(tag("by:")), column_list), SummarizeArg::By),
+            map(
+                preceded(ws(tag("y:")), summary_call),
+                |(function, column)| SummarizeArg::Y(function, column),
+            ),
+            map(preceded(ws(tag("as:")), ws(column_name)), SummarizeArg::As),
+        )),
+    )(input)?;
+    let (input, _) = ws(char(')'))(input)?;
+
+    let mut by = Vec::new();
+    let mut call = None;
+    let mut alias = None;
+    for arg in args {
+        match arg {
+            SummarizeArg::By(cols) => by = cols,
+            SummarizeArg::Y(function, column) => call = Some((function, column)),
+            SummarizeArg::As(name) => alias = Some(name),
+        }
+    }
+
+    // Validate: "y" is required, and only count() may omit its column
+    let (function, column) = match call {
+        Some((function, column)) if column.is_some() || function == SummaryFunction::Count => {
+            (function, column)
+        }
+        _ => {
+            return Err(nom::Err::Failure(nom::error::Error::new(
+                input,
+                nom::error::ErrorKind::Verify,
+            )))
+        }
+    };
+
+    Ok((
+        input,
+        Summarize {
+            by,
+            function,
+            column,
+            alias,
+        },
+    ))
+}
+
+/// `mean(temp)`, `count()`, ...
+fn summary_call(input: &str) -> IResult<&str, (SummaryFunction, Option<String>)> {
+    let (input, (function, _, column, _)) = tuple((
+        ws(alt((
+            value(SummaryFunction::Mean, tag("mean")),
+            value(SummaryFunction::Sum, tag("sum")),
+            value(SummaryFunction::Min, tag("min")),
+            value(SummaryFunction::Max, tag("max")),
+            value(SummaryFunction::Median, tag("median")),
+            value(SummaryFunction::Count, tag("count")),
+        ))),
+        char('('),
+        opt(ws(column_name)),
+        ws(char(')')),
+    ))(input)?;
+    Ok((input, (function, column)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_filter_stage() {
+        let (rest, stage) = parse_data_stage("filter(year >= 2020)").unwrap();
+        assert_eq!(rest, "");
+        assert!(matches!(stage, DataStage::Filter(_)));
+        assert!(parse_filter_stage("filter()").is_err());
+    }
+
+    #[test]
+    fn parses_summarize() {
+        let (_, s) = parse_summarize("summarize(by: day, y: mean(temp))").unwrap();
+        assert_eq!(s.by, vec!["day"]);
+        assert_eq!(s.function, SummaryFunction::Mean);
+        assert_eq!(s.column.as_deref(), Some("temp"));
+        assert_eq!(s.output_name(), "mean_temp");
+
+        let (_, s) =
+            parse_summarize("summarise(y: median(`Temp (C)`), by: [site, day], as: typical)")
+                .unwrap();
+        assert_eq!(s.by, vec!["site", "day"]);
+        assert_eq!(s.function, SummaryFunction::Median);
+        assert_eq!(s.output_name(), "typical");
+
+        let (_, s) = parse_summarize("summarize(by: day, y: count())").unwrap();
+        assert_eq!(
+            (s.function, s.column.as_deref()),
+            (SummaryFunction::Count, None)
+        );
+        assert_eq!(s.output_name(), "count");
+    }
+
+    #[test]
+    fn parses_sort() {
+        let key = |column: &str, desc| SortKey {
+            column: column.to_string(),
+            desc,
+        };
+        let (_, keys) = parse_sort("sort(by: value, desc: true)").unwrap();
+        assert_eq!(keys, vec![key("value", true)]);
+
+        let (_, keys) = parse_sort("sort(by: [region, sales])").unwrap();
+        assert_eq!(keys, vec![key("region", false), key("sales", false)]);
+
+        let (_, keys) = parse_sort("sort(desc: [false, true], by: [region, sales])").unwrap();
+        assert_eq!(keys, vec![key("region", false), key("sales", true)]);
+
+        let (_, keys) = parse_sort("sort(by: [a, b], desc: true)").unwrap();
+        assert!(keys.iter().all(|k| k.desc));
+
+        assert!(parse_sort("sort(desc: true)").is_err());
+        assert!(parse_sort("sort(by: [a, b, c], desc: [true, false])").is_err());
+        assert!(parse_sort("sort(by: a, desc: yes)").is_err());
+    }
+
+    #[test]
+    fn parses_head_and_sample() {
+        assert_eq!(parse_head("head(n: 1000)").unwrap().1, 1000);
+        assert!(parse_head("head()").is_err());
+        assert!(parse_head("head(n: -5)").is_err());
+
+        let (_, sample) = parse_sample("sample(seed: 7, n: 10000)").unwrap();
+        assert_eq!(sample, Sample { n: 10000, seed: 7 });
+        assert_eq!(parse_sample("sample(n: 5)").unwrap().1.seed, 0);
+        assert!(parse_sample("sample(seed: 7)").is_err());
+    }
+
+    #[test]
+    fn parses_mutate() {
+        let (rest, mutations) = parse_mutate("mutate(margin: revenue - cost)").unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(mutations.len(), 1);
+        assert_eq!(mutations[0].name, "margin");
+        assert_eq!(mutations[0].expr.columns(), vec!["revenue", "cost"]);
+
+        let (_, mutations) =
+            parse_mutate("mutate(margin: revenue - cost, `Margin %`: margin / revenue * 100)")
+                .unwrap();
+        assert_eq!(mutations[1].name, "Margin %");
+        // Only `revenue` and `cost` come from the input
+        assert_eq!(
+            DataStage::Mutate(mutations).columns(),
+            vec!["revenue", "cost", "revenue"]
+        );
+
+        assert!(parse_mutate("mutate()").is_err());
+        assert!(parse_mutate("mutate(margin: revenue -)").is_err());
+        assert!(parse_mutate("mutate(revenue - cost)").is_err());
+    }
+
+    #[test]
+    fn parses_pivot_longer() {
+        let cols = |names: &[&str]| names.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let (rest, pivot) = parse_pivot_longer(
+            "pivot_longer(cols: north, south, east, names_to: region, values_to: sales)",
+        )
+        .unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(
+            pivot,
+            PivotLonger {
+                cols: cols(&["north", "south", "east"]),
+                names_to: "region".to_string(),
+                values_to: "sales".to_string(),
+                keep: false,
+            }
+        );
+
+        let (_, pivot) =
+            parse_pivot_longer("pivot_longer(values_to: v, cols: [`North (k)`, south])").unwrap();
+        assert_eq!(pivot.cols, cols(&["North (k)", "south"]));
+        assert_eq!(
+            (pivot.names_to.as_str(), pivot.values_to.as_str()),
+            ("name", "v")
+        );
+
+        let (_, pivot) = parse_pivot_longer("pivot_longer(cols: a)").unwrap();
+        assert_eq!(
+            pivot,
+            PivotLonger {
+                cols: cols(&["a"]),
+                ..PivotLonger::default()
+            }
+        );
+
+        assert!(parse_pivot_longer("pivot_longer(names_to: region)").is_err());
+        assert!(parse_pivot_longer("pivot_longer(cols: [])").is_err());
+    }
+
+    #[test]
+    fn rejects_incomplete_summarize() {
+        assert!(parse_summarize("summarize(by: day)").is_err());
+        assert!(parse_summarize("summarize(by: day, y: mean())").is_err());
+        assert!(parse_summarize("summarize(by: day, y: average(temp))").is_err());
+    }
+}
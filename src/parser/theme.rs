@@ -1,10 +1,16 @@
+use crate::parser::arguments::is_allowed_value;
 use crate::parser::ast::{
     ElementLine, ElementRect, ElementText, LegendPosition, Theme, ThemeElement,
 };
-use crate::parser::lexer::{number_literal, string_literal, ws};
+use crate::parser::lexer::{bool_literal, number_literal, string_literal, ws};
 use nom::{
-    branch::alt, bytes::complete::tag, character::complete::char, combinator::map,
-    multi::separated_list0, sequence::preceded, IResult,
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::char,
+    combinator::{map, verify},
+    multi::separated_list0,
+    sequence::preceded,
+    IResult,
 };
 
 // === Element Parsers ===
@@ -74,9 +80,15 @@ fn parse_element_line(input: &str) -> IResult<&str, ThemeElement> {
             map(preceded(ws(tag("width:")), ws(number_literal)), |v| {
                 ("width", ArgValue::Number(v))
             }),
-            map(preceded(ws(tag("linetype:")), ws(string_literal)), |v| {
-                ("linetype", ArgValue::String(v))
-            }),
+            map(
+                preceded(
+                    ws(tag("linetype:")),
+                    ws(verify(string_literal, |s: &str| {
+                        is_allowed_value("element_line", "linetype", s)
+                    })),
+                ),
+                |v| ("linetype", ArgValue::String(v)),
+            ),
         )),
     )(input)?;
 
@@ -175,6 +187,18 @@ enum ThemeArg {
     Line(ThemeElement),
     Rect(ThemeElement),
     Text(ThemeElement),
+    // Shorthands over the element tree
+    Preset(String),
+    Background(String),
+    Grid(String),
+    FontSize(f64),
+    Font(String),
+    Margin(f64),
+    GridMinor(bool),
+    GridColor(String),
+    GridWidth(f64),
+    GridLinetype(String),
+    GridSeparators(bool),
 }
 
 fn parse_legend_position_arg(input: &str) -> IResult<&str, ThemeArg> {
@@ -259,6 +283,66 @@ fn parse_theme_arg(input: &str) -> IResult<&str, ThemeArg> {
             preceded(ws(tag("text:")), ws(parse_theme_element)),
             ThemeArg::Text,
         ),
+        parse_theme_shorthand_arg,
+    ))(input)
+}
+
+/// A string literal `theme(key:)` accepts (see `arguments::allowed_values`)
+fn allowed_string(key: &'static str) -> impl FnMut(&str) -> IResult<&str, String> {
+    move |input| verify(string_literal, |s: &str| is_allowed_value("theme", key, s))(input)
+}
+
+/// Parse preset:, background:, grid:, font_size:, and font: shorthands, the grid_*
+/// styling knobs, and margin:
+fn parse_theme_shorthand_arg(input: &str) -> IResult<&str, ThemeArg> {
+    alt((
+        map(
+            preceded(ws(tag("preset:")), ws(allowed_string("preset"))),
+            ThemeArg::Preset,
+        ),
+        map(
+            preceded(ws(tag("background:")), ws(string_literal)),
+            ThemeArg::Background,
+        ),
+        map(
+            preceded(ws(tag("grid:")), ws(allowed_string("grid"))),
+            ThemeArg::Grid,
+        ),
+        map(
+            preceded(ws(tag("font_size:")), ws(number_literal)),
+            ThemeArg::FontSize,
+        ),
+        map(
+            preceded(ws(tag("font:")), ws(string_literal)),
+            ThemeArg::Font,
+        ),
+        map(
+            preceded(ws(tag("margin:")), ws(number_literal)),
+            ThemeArg::Margin,
+        ),
+        map(
+            preceded(ws(tag("grid_minor:")), ws(bool_literal)),
+            ThemeArg::GridMinor,
+        ),
+        map(
+            preceded(ws(tag("grid_color:")), ws(string_literal)),
+            ThemeArg::GridColor,
+        ),
+        map(
+            preceded(ws(tag("grid_width:")), ws(number_literal)),
+            ThemeArg::GridWidth,
+        ),
+        map(
+            preceded(
+                ws(tag("grid_linetype:")),
+                ws(allowed_string("grid_linetype")),
+            ),
+            ThemeArg::GridLinetype,
+        ),
+        map(
+            preceded(ws(tag("grid_separators:")), ws(bool_literal)),
+            ThemeArg::GridSeparators,
+        ),
     ))(input)
 }
 
@@ -270,37 +354,40 @@ pub fn parse_theme_minimal(input: &str) -> IResult<&str, Theme> {
     let (input, _) = ws(char('('))(input)?;
     let (input, _) = ws(char(')'))(input)?;
 
-    Ok((
-        input,
-        Theme {
-            line: ThemeElement::Inherit,
-            rect: ThemeElement::Inherit,
-            text: ThemeElement::Inherit,
-            plot_background: ThemeElement::Rect(ElementRect {
-                fill: Some("white".to_string()),
-                ..Default::default()
-            }),
-            plot_title: ThemeElement::Inherit,
-            panel_background: ThemeElement::Rect(ElementRect {
-                fill: Some("white".to_string()),
-                ..Default::default()
-            }),
-            panel_grid_major: ThemeElement::Line(ElementLine {
-                color: Some("#CCCCCC".to_string()),
-                width: Some(0.5),
-                ..Default::default()
-            }),
-            panel_grid_minor: ThemeElement::Blank,
-            axis_text: ThemeElement::Inherit,
-            axis_line: ThemeElement::Blank,
-            axis_ticks: ThemeElement::Blank,
-            legend_position: None,
-            legend_background: ThemeElement::Inherit,
-            legend_text: ThemeElement::Inherit,
-            legend_margin: None,
-            legend_key_size: None,
-        },
-    ))
+    Ok((input, minimal_theme()))
+}
+
+fn minimal_theme() -> Theme {
+    Theme {
+        line: ThemeElement::Inherit,
+        rect: ThemeElement::Inherit,
+        text: ThemeElement::Inherit,
+        plot_background: ThemeElement::Rect(ElementRect {
+            fill: Some("white".to_string()),
+            ..Default::default()
+        }),
+        plot_title: ThemeElement::Inherit,
+        panel_background: ThemeElement::Rect(ElementRect {
+            fill: Some("white".to_string()),
+            ..Default::default()
+        }),
+        panel_grid_major: ThemeElement::Line(ElementLine {
+            color: Some("#CCCCCC".to_string()),
+            width: Some(0.5),
+            ..Default::default()
+        }),
+        panel_grid_minor: ThemeElement::Blank,
+        axis_text: ThemeElement::Inherit,
+        axis_line: ThemeElement::Blank,
+        axis_ticks: ThemeElement::Blank,
+        legend_position: None,
+        legend_background: ThemeElement::Inherit,
+        legend_text: ThemeElement::Inherit,
+        legend_margin: None,
+        legend_key_size: None,
+        margin: None,
+        grid_separators: None,
+    }
 }
 
 /// Parse theme_dark() - dark background with light foreground elements
@@ -309,62 +396,65 @@ pub fn parse_theme_dark(input: &str) -> IResult<&str, Theme> {
     let (input, _) = ws(char('('))(input)?;
     let (input, _) = ws(char(')'))(input)?;
 
-    Ok((
-        input,
-        Theme {
-            line: ThemeElement::Inherit,
-            rect: ThemeElement::Inherit,
-            text: ThemeElement::Text(ElementText {
-                color: Some("#f2f2f2".to_string()),
-                ..Default::default()
-            }),
-            plot_background: ThemeElement::Rect(ElementRect {
-                fill: Some("#1f1f1f".to_string()),
-                ..Default::default()
-            }),
-            plot_title: ThemeElement::Inherit,
-            panel_background: ThemeElement::Rect(ElementRect {
-                fill: Some("#2b2b2b".to_string()),
-                ..Default::default()
-            }),
-            panel_grid_major: ThemeElement::Line(ElementLine {
-                color: Some("#555555".to_string()),
-                width: Some(0.5),
-                ..Default::default()
-            }),
-            panel_grid_minor: ThemeElement::Line(ElementLine {
-                color: Some("#3f3f3f".to_string()),
-                width: Some(0.25),
-                ..Default::default()
-            }),
-            axis_text: ThemeElement::Text(ElementText {
-                color: Some("#d8d8d8".to_string()),
-                ..Default::default()
-            }),
-            axis_line: ThemeElement::Line(ElementLine {
-                color: Some("#d8d8d8".to_string()),
-                width: Some(1.0),
-                ..Default::default()
-            }),
-            axis_ticks: ThemeElement::Line(ElementLine {
-                color: Some("#d8d8d8".to_string()),
-                width: Some(1.0),
-                ..Default::default()
-            }),
-            legend_position: None,
-            legend_background: ThemeElement::Rect(ElementRect {
-                fill: Some("#2b2b2b".to_string()),
-                color: Some("#d8d8d8".to_string()),
-                width: Some(1.0),
-            }),
-            legend_text: ThemeElement::Text(ElementText {
-                color: Some("#f2f2f2".to_string()),
-                ..Default::default()
-            }),
-            legend_margin: None,
-            legend_key_size: None,
-        },
-    ))
+    Ok((input, dark_theme()))
+}
+
+fn dark_theme() -> Theme {
+    Theme {
+        line: ThemeElement::Inherit,
+        rect: ThemeElement::Inherit,
+        text: ThemeElement::Text(ElementText {
+            color: Some("#f2f2f2".to_string()),
+            ..Default::default()
+        }),
+        plot_background: ThemeElement::Rect(ElementRect {
+            fill: Some("#1f1f1f".to_string()),
+            ..Default::default()
+        }),
+        plot_title: ThemeElement::Inherit,
+        panel_background: ThemeElement::Rect(ElementRect {
+            fill: Some("#2b2b2b".to_string()),
+            ..Default::default()
+        }),
+        panel_grid_major: ThemeElement::Line(ElementLine {
+            color: Some("#555555".to_string()),
+            width: Some(0.5),
+            ..Default::default()
+        }),
+        panel_grid_minor: ThemeElement::Line(ElementLine {
+            color: Some("#3f3f3f".to_string()),
+            width: Some(0.25),
+            ..Default::default()
+        }),
+        axis_text: ThemeElement::Text(ElementText {
+            color: Some("#d8d8d8".to_string()),
+            ..Default::default()
+        }),
+        axis_line: ThemeElement::Line(ElementLine {
+            color: Some("#d8d8d8".to_string()),
+            width: Some(1.0),
+            ..Default::default()
+        }),
+        axis_ticks: ThemeElement::Line(ElementLine {
+            color: Some("#d8d8d8".to_string()),
+            width: Some(1.0),
+            ..Default::default()
+        }),
+        legend_position: None,
+        legend_background: ThemeElement::Rect(ElementRect {
+            fill: Some("#2b2b2b".to_string()),
+            color: Some("#d8d8d8".to_string()),
+            width: Some(1.0),
+        }),
+        legend_text: ThemeElement::Text(ElementText {
+            color: Some("#f2f2f2".to_string()),
+            ..Default::default()
+        }),
+        legend_margin: None,
+        legend_key_size: None,
+        margin: None,
+        grid_separators: None,
+    }
 }
 
 /// Parse theme_classic() - white background, axis lines, and no grid
@@ -373,45 +463,48 @@ pub fn parse_theme_classic(input: &str) -> IResult<&str, Theme> {
     let (input, _) = ws(char('('))(input)?;
     let (input, _) = ws(char(')'))(input)?;
 
-    Ok((
-        input,
-        Theme {
-            line: ThemeElement::Inherit,
-            rect: ThemeElement::Inherit,
-            text: ThemeElement::Inherit,
-            plot_background: ThemeElement::Rect(ElementRect {
-                fill: Some("white".to_string()),
-                ..Default::default()
-            }),
-            plot_title: ThemeElement::Inherit,
-            panel_background: ThemeElement::Rect(ElementRect {
-                fill: Some("white".to_string()),
-                ..Default::default()
-            }),
-            panel_grid_major: ThemeElement::Blank,
-            panel_grid_minor: ThemeElement::Blank,
-            axis_text: ThemeElement::Inherit,
-            axis_line: ThemeElement::Line(ElementLine {
-                color: Some("black".to_string()),
-                width: Some(1.0),
-                ..Default::default()
-            }),
-            axis_ticks: ThemeElement::Line(ElementLine {
-                color: Some("black".to_string()),
-                width: Some(1.0),
-                ..Default::default()
-            }),
-            legend_position: None,
-            legend_background: ThemeElement::Rect(ElementRect {
-                fill: Some("white".to_string()),
-                color: Some("black".to_string()),
-                width: Some(1.0),
-            }),
-            legend_text: ThemeElement::Inherit,
-            legend_margin: None,
-            legend_key_size: None,
-        },
-    ))
+    Ok((input, classic_theme()))
+}
+
+fn classic_theme() -> Theme {
+    Theme {
+        line: ThemeElement::Inherit,
+        rect: ThemeElement::Inherit,
+        text: ThemeElement::Inherit,
+        plot_background: ThemeElement::Rect(ElementRect {
+            fill: Some("white".to_string()),
+            ..Default::default()
+        }),
+        plot_title: ThemeElement::Inherit,
+        panel_background: ThemeElement::Rect(ElementRect {
+            fill: Some("white".to_string()),
+            ..Default::default()
+        }),
+        panel_grid_major: ThemeElement::Blank,
+        panel_grid_minor: ThemeElement::Blank,
+        axis_text: ThemeElement::Inherit,
+        axis_line: ThemeElement::Line(ElementLine {
+            color: Some("black".to_string()),
+            width: Some(1.0),
+            ..Default::default()
+        }),
+        axis_ticks: ThemeElement::Line(ElementLine {
+            color: Some("black".to_string()),
+            width: Some(1.0),
+            ..Default::default()
+        }),
+        legend_position: None,
+        legend_background: ThemeElement::Rect(ElementRect {
+            fill: Some("white".to_string()),
+            color: Some("black".to_string()),
+            width: Some(1.0),
+        }),
+        legend_text: ThemeElement::Inherit,
+        legend_margin: None,
+        legend_key_size: None,
+        margin: None,
+        grid_separators: None,
+    }
 }
 
 /// Parse theme_light() - white background with light gray panel and grid.
@@ -420,50 +513,53 @@ pub fn parse_theme_light(input: &str) -> IResult<&str, Theme> {
     let (input, _) = ws(char('('))(input)?;
     let (input, _) = ws(char(')'))(input)?;
 
-    Ok((
-        input,
-        Theme {
-            line: ThemeElement::Inherit,
-            rect: ThemeElement::Inherit,
-            text: ThemeElement::Inherit,
-            plot_background: ThemeElement::Rect(ElementRect {
-                fill: Some("white".to_string()),
-                ..Default::default()
-            }),
-            plot_title: ThemeElement::Inherit,
-            panel_background: ThemeElement::Rect(ElementRect {
-                fill: Some("#F5F5F5".to_string()),
-                color: Some("#BDBDBD".to_string()),
-                width: Some(1.0),
-            }),
-            panel_grid_major: ThemeElement::Line(ElementLine {
-                color: Some("white".to_string()),
-                width: Some(1.0),
-                ..Default::default()
-            }),
-            panel_grid_minor: ThemeElement::Blank,
-            axis_text: ThemeElement::Inherit,
-            axis_line: ThemeElement::Line(ElementLine {
-                color: Some("#777777".to_string()),
-                width: Some(1.0),
-                ..Default::default()
-            }),
-            axis_ticks: ThemeElement::Line(ElementLine {
-                color: Some("#777777".to_string()),
-                width: Some(1.0),
-                ..Default::default()
-            }),
-            legend_position: None,
-            legend_background: ThemeElement::Rect(ElementRect {
-                fill: Some("white".to_string()),
-                color: Some("#BDBDBD".to_string()),
-                width: Some(1.0),
-            }),
-            legend_text: ThemeElement::Inherit,
-            legend_margin: None,
-            legend_key_size: None,
-        },
-    ))
+    Ok((input, light_theme()))
+}
+
+fn light_theme() -> Theme {
+    Theme {
+        line: ThemeElement::Inherit,
+        rect: ThemeElement::Inherit,
+        text: ThemeElement::Inherit,
+        plot_background: ThemeElement::Rect(ElementRect {
+            fill: Some("white".to_string()),
+            ..Default::default()
+        }),
+        plot_title: ThemeElement::Inherit,
+        panel_background: ThemeElement::Rect(ElementRect {
+            fill: Some("#F5F5F5".to_string()),
+            color: Some("#BDBDBD".to_string()),
+            width: Some(1.0),
+        }),
+        panel_grid_major: ThemeElement::Line(ElementLine {
+            color: Some("white".to_string()),
+            width: Some(1.0),
+            ..Default::default()
+        }),
+        panel_grid_minor: ThemeElement::Blank,
+        axis_text: ThemeElement::Inherit,
+        axis_line: ThemeElement::Line(ElementLine {
+            color: Some("#777777".to_string()),
+            width: Some(1.0),
+            ..Default::default()
+        }),
+        axis_ticks: ThemeElement::Line(ElementLine {
+            color: Some("#777777".to_string()),
+            width: Some(1.0),
+            ..Default::default()
+        }),
+        legend_position: None,
+        legend_background: ThemeElement::Rect(ElementRect {
+            fill: Some("white".to_string()),
+            color: Some("#BDBDBD".to_string()),
+            width: Some(1.0),
+        }),
+        legend_text: ThemeElement::Inherit,
+        legend_margin: None,
+        legend_key_size: None,
+        margin: None,
+        grid_separators: None,
+    }
 }
 
 /// Parse theme_void() - blank panel with no axes, ticks, grid, or legend.
@@ -472,36 +568,39 @@ pub fn parse_theme_void(input: &str) -> IResult<&str, Theme> {
     let (input, _) = ws(char('('))(input)?;
     let (input, _) = ws(char(')'))(input)?;
 
-    Ok((
-        input,
-        Theme {
-            line: ThemeElement::Inherit,
-            rect: ThemeElement::Inherit,
-            text: ThemeElement::Inherit,
-            plot_background: ThemeElement::Rect(ElementRect {
-                fill: Some("white".to_string()),
-                ..Default::default()
-            }),
-            plot_title: ThemeElement::Inherit,
-            panel_background: ThemeElement::Rect(ElementRect {
-                fill: Some("white".to_string()),
-                ..Default::default()
-            }),
-            panel_grid_major: ThemeElement::Blank,
-            panel_grid_minor: ThemeElement::Blank,
-            axis_text: ThemeElement::Text(ElementText {
-                color: Some("white".to_string()),
-                ..Default::default()
-            }),
-            axis_line: ThemeElement::Blank,
-            axis_ticks: ThemeElement::Blank,
-            legend_position: Some(LegendPosition::None),
-            legend_background: ThemeElement::Blank,
-            legend_text: ThemeElement::Inherit,
-            legend_margin: None,
-            legend_key_size: None,
-        },
-    ))
+    Ok((input, void_theme()))
+}
+
+fn void_theme() -> Theme {
+    Theme {
+        line: ThemeElement::Inherit,
+        rect: ThemeElement::Inherit,
+        text: ThemeElement::Inherit,
+        plot_background: ThemeElement::Rect(ElementRect {
+            fill: Some("white".to_string()),
+            ..Default::default()
+        }),
+        plot_title: ThemeElement::Inherit,
+        panel_background: ThemeElement::Rect(ElementRect {
+            fill: Some("white".to_string()),
+            ..Default::default()
+        }),
+        panel_grid_major: ThemeElement::Blank,
+        panel_grid_minor: ThemeElement::Blank,
+        axis_text: ThemeElement::Text(ElementText {
+            color: Some("white".to_string()),
+            ..Default::default()
+        }),
+        axis_line: ThemeElement::Blank,
+        axis_ticks: ThemeElement::Blank,
+        legend_position: Some(LegendPosition::None),
+        legend_background: ThemeElement::Blank,
+        legend_text: ThemeElement::Inherit,
+        legend_margin: None,
+        legend_key_size: None,
+        margin: None,
+        grid_separators: None,
+    }
 }
 
 /// Parse theme(...) with hierarchical element arguments
@@ -513,7 +612,17 @@ pub fn parse_theme(input: &str) -> IResult<&str, Theme> {
 
     let (input, _) = ws(char(')'))(input)?;
 
-    let mut theme = Theme::default();
+    // A preset is the base the other arguments refine, wherever it is listed
+    let mut theme = args
+        .iter()
+        .find_map(|arg| match arg {
+            ThemeArg::Preset(name) => preset_theme(name),
+            _ => None,
+        })
+        .unwrap_or_default();
+    // grid_minor: and the grid styling apply after grid:, wherever they are listed
+    let mut grid_minor = None;
+    let mut grid_style = ElementLine::default();
     for arg in args {
         match arg {
             ThemeArg::LegendPosition(pos) => theme.legend_position = Some(pos),
@@ -521,6 +630,7 @@ pub fn parse_theme(input: &str) -> IResult<&str, Theme> {
             ThemeArg::LegendText(elem) => theme.legend_text = elem,
             ThemeArg::LegendMargin(margin) => theme.legend_margin = Some(margin),
             ThemeArg::LegendKeySize(size) => theme.legend_key_size = Some(size),
+            ThemeArg::Margin(margin) => theme.margin = Some(margin),
             ThemeArg::PlotBackground(elem) => theme.plot_background = elem,
             ThemeArg::PlotTitle(elem) => theme.plot_title = elem,
             ThemeArg::PanelBackground(elem) => theme.panel_background = elem,
@@ -532,12 +642,117 @@ pub fn parse_theme(input: &str) -> IResult<&str, Theme> {
             ThemeArg::Line(elem) => theme.line = elem,
             ThemeArg::Rect(elem) => theme.rect = elem,
             ThemeArg::Text(elem) => theme.text = elem,
+            ThemeArg::Preset(_) => {}
+            ThemeArg::Background(color) => {
+                set_fill(&mut theme.plot_background, &color);
+                set_fill(&mut theme.panel_background, &color);
+            }
+            ThemeArg::Grid(grid) => match grid.as_str() {
+                "none" => {
+                    theme.panel_grid_major = ThemeElement::Blank;
+                    theme.panel_grid_minor = ThemeElement::Blank;
+                }
+                "major" => {
+                    show_grid(&mut theme.panel_grid_major);
+                    theme.panel_grid_minor = ThemeElement::Blank;
+                }
+                "both" => {
+                    show_grid(&mut theme.panel_grid_major);
+                    show_grid(&mut theme.panel_grid_minor);
+                }
+                _ => unreachable!("the parser only accepts the grid values above"),
+            },
+            ThemeArg::FontSize(size) => text_mut(&mut theme.text).size = Some(size),
+            ThemeArg::Font(family) => text_mut(&mut theme.text).family = Some(family),
+            ThemeArg::GridMinor(show) => grid_minor = Some(show),
+            ThemeArg::GridColor(color) => grid_style.color = Some(color),
+            ThemeArg::GridWidth(width) => grid_style.width = Some(width),
+            ThemeArg::GridLinetype(linetype) => grid_style.linetype = Some(linetype),
+            ThemeArg::GridSeparators(between) => theme.grid_separators = Some(between),
         }
     }
 
+    match grid_minor {
+        // An explicit line, so a later merge can't fall back to a preset's blank grid
+        Some(true) if !matches!(theme.panel_grid_minor, ThemeElement::Line(_)) => {
+            theme.panel_grid_minor = ThemeElement::Line(ElementLine {
+                color: Some("#CCCCCC".to_string()),
+                width: Some(0.5),
+                ..Default::default()
+            });
+        }
+        Some(false) => theme.panel_grid_minor = ThemeElement::Blank,
+        _ => {}
+    }
+    if grid_style != ElementLine::default() {
+        style_grid(&mut theme.panel_grid_major, grid_style);
+    }
+
     Ok((input, theme))
 }
 
+/// The theme behind `theme(preset: "...")`, matching the theme_*() commands
+fn preset_theme(name: &str) -> Option<Theme> {
+    match name {
+        "minimal" => Some(minimal_theme()),
+        "dark" => Some(dark_theme()),
+        "classic" => Some(classic_theme()),
+        "light" => Some(light_theme()),
+        "void" => Some(void_theme()),
+        _ => None,
+    }
+}
+
+/// Set a background's fill, keeping any border it already has
+fn set_fill(elem: &mut ThemeElement, color: &str) {
+    match elem {
+        ThemeElement::Rect(rect) => rect.fill = Some(color.to_string()),
+        _ => {
+            *elem = ThemeElement::Rect(ElementRect {
+                fill: Some(color.to_string()),
+                ..Default::default()
+            })
+        }
+    }
+}
+
+/// Turn a blanked grid back on with a light gray line
+fn show_grid(elem: &mut ThemeElement) {
+    if *elem == ThemeElement::Blank {
+        *elem = ThemeElement::Line(ElementLine {
+            color: Some("#CCCCCC".to_string()),
+            width: Some(0.5),
+            ..Default::default()
+        });
+    }
+}
+
+/// Restyle the major grid; a blanked grid stays hidden
+fn style_grid(elem: &mut ThemeElement, style: ElementLine) {
+    if *elem == ThemeElement::Blank {
+        return;
+    }
+    if !matches!(elem, ThemeElement::Line(_)) {
+        *elem = ThemeElement::Line(ElementLine::default());
+    }
+    if let ThemeElement::Line(line) = elem {
+        line.color = style.color.or(line.color.take());
+        line.width = style.width.or(line.width);
+        line.linetype = style.linetype.or(line.linetype.take());
+    }
+}
+
+/// The root text element, made explicit so a preset's text color is kept
+fn text_mut(elem: &mut ThemeElement) -> &mut ElementText {
+    if !matches!(elem, ThemeElement::Text(_)) {
+        *elem = ThemeElement::Text(ElementText::default());
+    }
+    match elem {
+        ThemeElement::Text(text) => text,
+        _ => unreachable!(),
+    }
+}
+
 /// Parse any theme command (theme_minimal or theme)
 pub fn parse_theme_command(input: &str) -> IResult<&str, Theme> {
     alt((
@@ -695,4 +910,113 @@ mod tests {
             panic!("Expected Text element for axis_text");
         }
     }
+
+    #[test]
+    fn test_parse_theme_background_shorthand() {
+        let (_, theme) = parse_theme(r##"theme(background: "#f0f0f0")"##).unwrap();
+        for elem in [&theme.plot_background, &theme.panel_background] {
+            if let ThemeElement::Rect(r) = elem {
+                assert_eq!(r.fill, Some("#f0f0f0".to_string()));
+            } else {
+                panic!("Expected Rect element");
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_theme_grid_shorthand() {
+        let (_, theme) = parse_theme(r#"theme(grid: "none")"#).unwrap();
+        assert_eq!(theme.panel_grid_major, ThemeElement::Blank);
+        assert_eq!(theme.panel_grid_minor, ThemeElement::Blank);
+
+        let (_, theme) = parse_theme(r#"theme(grid: "major")"#).unwrap();
+        assert_eq!(theme.panel_grid_major, ThemeElement::Inherit);
+        assert_eq!(theme.panel_grid_minor, ThemeElement::Blank);
+
+        // "both" turns a preset's blank minor grid back on
+        let (_, theme) = parse_theme(r#"theme(preset: "minimal", grid: "both")"#).unwrap();
+        assert!(matches!(theme.panel_grid_major, ThemeElement::Line(_)));
+        assert!(matches!(theme.panel_grid_minor, ThemeElement::Line(_)));
+    }
+
+    #[test]
+    fn test_parse_theme_font_shorthands() {
+        let (_, theme) = parse_theme(r#"theme(font_size: 16, font: "serif")"#).unwrap();
+        if let ThemeElement::Text(t) = &theme.text {
+            assert_eq!(t.size, Some(16.0));
+            assert_eq!(t.family, Some("serif".to_string()));
+        } else {
+            panic!("Expected Text element");
+        }
+    }
+
+    #[test]
+    fn test_parse_theme_margin() {
+        let (_, theme) = parse_theme("theme(margin: 30, legend_margin: 4)").unwrap();
+        assert_eq!(theme.margin, Some(30.0));
+        assert_eq!(theme.legend_margin, Some(4.0));
+        assert!(theme.resolve().has_customization);
+    }
+
+    #[test]
+    fn test_parse_theme_grid_knobs() {
+        let (_, theme) = parse_theme(
+            r#"theme(grid_color: "red", grid_width: 2, grid_linetype: "dashed", grid_minor: true, grid_separators: true)"#,
+        )
+        .unwrap();
+        assert_eq!(
+            theme.panel_grid_major,
+            ThemeElement::Line(ElementLine {
+                color: Some("red".to_string()),
+                width: Some(2.0),
+                linetype: Some("dashed".to_string()),
+            })
+        );
+        assert!(matches!(theme.panel_grid_minor, ThemeElement::Line(_)));
+        assert_eq!(theme.grid_separators, Some(true));
+        assert!(theme.resolve().grid_separators);
+
+        // grid: "none" wins over the styling, in either order
+        for dsl in [
+            r#"theme(grid: "none", grid_color: "red")"#,
+            r#"theme(grid_color: "red", grid: "none")"#,
+        ] {
+            let (_, theme) = parse_theme(dsl).unwrap();
+            assert_eq!(theme.panel_grid_major, ThemeElement::Blank, "{dsl}");
+        }
+
+        // The styling refines a preset's grid line, and grid_minor: overrides grid:
+        let (_, theme) =
+            parse_theme(r##"theme(grid_minor: true, preset: "minimal", grid_color: "#999999")"##)
+                .unwrap();
+        if let ThemeElement::Line(l) = &theme.panel_grid_major {
+            assert_eq!(l.color.as_deref(), Some("#999999"));
+            assert!(l.width.is_some(), "minimal grid width is kept");
+        } else {
+            panic!("Expected Line element");
+        }
+        assert!(matches!(theme.panel_grid_minor, ThemeElement::Line(_)));
+        let (_, theme) = parse_theme(r#"theme(grid_minor: false, grid: "both")"#).unwrap();
+        assert_eq!(theme.panel_grid_minor, ThemeElement::Blank);
+    }
+
+    #[test]
+    fn test_parse_theme_preset_is_the_base() {
+        // The preset applies first even when listed after the overrides
+        let (_, theme) = parse_theme(r#"theme(font_size: 9, preset: "dark")"#).unwrap();
+        assert_eq!(theme.panel_background, dark_theme().panel_background);
+        if let ThemeElement::Text(t) = &theme.text {
+            assert_eq!(t.size, Some(9.0));
+            assert!(t.color.is_some(), "dark preset text color is kept");
+        } else {
+            panic!("Expected Text element");
+        }
+
+        let (_, theme) = parse_theme(r#"theme(preset: "minimal")"#).unwrap();
+        assert_eq!(theme, minimal_theme());
+        // Unknown presets and grids are parse errors, not a silently default theme
+        assert!(parse_theme(r#"theme(preset: "nope")"#).is_err());
+        assert!(parse_theme(r#"theme(grid: "minor")"#).is_err());
+        assert!(parse_theme(r#"theme(grid_linetype: "wavy")"#).is_err());
+    }
 }
@@ -0,0 +1,225 @@
+// Data ranges: the one min/max scan behind axis domains, stat grids, and color
+// domains, and the adjustments an axis makes to it (zero for bars, room around a
+// lone value, `expand:` padding)
+
+/// The least and greatest values seen. Starts empty (min above max), and NaN is never
+/// recorded, so one stray value can't poison a scan.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DataRange {
+    pub min: f64,
+    pub max: f64,
+}
+
+impl DataRange {
+    /// No values yet
+    pub const EMPTY: DataRange = DataRange {
+        min: f64::INFINITY,
+        max: f64::NEG_INFINITY,
+    };
+
+    /// The range of `values`
+    pub fn of(values: impl IntoIterator<Item = f64>) -> Self {
+        let mut range = Self::EMPTY;
+        for value in values {
+            range.include(value);
+        }
+        range
+    }
+
+    /// Widen to cover `value`
+    pub fn include(&mut self, value: f64) {
+        if value < self.min {
+            self.min = value;
+        }
+        if value > self.max {
+            self.max = value;
+        }
+    }
+
+    /// The range covering both; an empty side changes nothing
+    pub fn merge(self, other: DataRange) -> Self {
+        DataRange {
+            min: if other.min < self.min {
+                other.min
+            } else {
+                self.min
+            },
+            max: if other.max > self.max {
+                other.max
+            } else {
+                self.max
+            },
+        }
+    }
+
+    /// True until a value is included
+    pub fn is_empty(&self) -> bool {
+        self.min > self.max
+    }
+
+    pub fn span(&self) -> f64 {
+        self.max - self.min
+    }
+
+    /// Stretch to reach zero, so bars keep their baseline in view. An empty range
+    /// becomes 0..0.
+    pub fn include_zero(self) -> Self {
+        DataRange {
+            min: if self.min > 0.0 { 0.0 } else { self.min },
+            max: if self.max < 0.0 { 0.0 } else { self.max },
+        }
+    }
+
+    /// 0..1 when nothing was seen
+    pub fn or_unit_if_empty(self) -> Self {
+        if self.min == f64::INFINITY {
+            DataRange { min: 0.0, max: 1.0 }
+        } else {
+            self
+        }
+    }
+
+    /// One unit either side of a lone value, so the axis has some width
+    pub fn widen_if_flat(self) -> Self {
+        if self.min == self.max {
+            DataRange {
+                min: self.min - 1.0,
+                max: self.max + 1.0,
+            }
+        } else {
+            self
+        }
+    }
+
+    /// Pad each end by `fraction` of the span, after widening a lone value
+    pub fn expand(self, fraction: f64) -> Self {
+        let range = self.widen_if_flat();
+        let pad = range.span() * fraction;
+        DataRange {
+            min: range.min - pad,
+            max: range.max + pad,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic values with NaN, infinities, signed zeros, and repeats mixed in
+    fn samples(seed: u64, len: usize) -> Vec<f64> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+                let mut z = state;
+                z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+                z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+                z ^= z >> 31;
+                match z % 16 {
+                    0 => f64::NAN,
+                    1 => f64::INFINITY,
+                    2 => f64::NEG_INFINITY,
+                    3 => 0.0,
+                    4 => -0.0,
+                    5 => 7.0,
+                    _ => (z >> 11) as f64 / (1u64 << 53) as f64 * 2000.0 - 1000.0,
+                }
+            })
+            .collect()
+    }
+
+    // The scans this module replaced, kept to check the new one against
+    fn old_fold(values: &[f64]) -> (f64, f64) {
+        let min = values.iter().fold(f64::INFINITY, |a, &b| a.min(b));
+        let max = values.iter().fold(f64::NEG_INFINITY, |a, &b| a.max(b));
+        (min, max)
+    }
+
+    fn old_compare(values: &[f64]) -> (f64, f64) {
+        let (mut min, mut max) = (f64::INFINITY, f64::NEG_INFINITY);
+        for &val in values {
+            if val < min {
+                min = val;
+            }
+            if val > max {
+                max = val;
+            }
+        }
+        (min, max)
+    }
+
+    fn same(a: (f64, f64), b: DataRange) -> bool {
+        // Signed zeros compare equal, which is all an axis can tell apart
+        a.0 == b.min && a.1 == b.max
+    }
+
+    #[test]
+    fn scans_match_the_replaced_folds() {
+        for seed in 0..500 {
+            let values = samples(seed, (seed % 40) as usize);
+            let range = DataRange::of(values.iter().copied());
+            assert!(same(old_compare(&values), range), "{:?}", values);
+            assert!(same(old_fold(&values), range), "{:?}", values);
+
+            let finite: Vec<f64> = values.into_iter().filter(|v| v.is_finite()).collect();
+            assert!(same(
+                old_fold(&finite),
+                DataRange::of(finite.iter().copied())
+            ));
+        }
+    }
+
+    #[test]
+    fn merges_match_a_scan_of_everything() {
+        for seed in 0..500 {
+            let a = samples(seed, (seed % 7) as usize);
+            let b = samples(seed + 10_000, (seed % 5) as usize);
+            let merged = DataRange::of(a.iter().copied()).merge(DataRange::of(b.iter().copied()));
+            let all: Vec<f64> = a.iter().chain(&b).copied().collect();
+            assert!(same(old_compare(&all), merged));
+        }
+        assert!(DataRange::EMPTY.merge(DataRange::EMPTY).is_empty());
+    }
+
+    #[test]
+    fn adjustments_for_axes() {
+        let range = DataRange::of([3.0, 8.0]);
+        assert_eq!(range.include_zero(), DataRange { min: 0.0, max: 8.0 });
+        assert_eq!(
+            DataRange::of([-3.0, -1.0]).include_zero(),
+            DataRange {
+                min: -3.0,
+                max: 0.0
+            }
+        );
+        assert_eq!(
+            DataRange::EMPTY.include_zero(),
+            DataRange { min: 0.0, max: 0.0 }
+        );
+        assert_eq!(
+            DataRange::EMPTY.or_unit_if_empty(),
+            DataRange { min: 0.0, max: 1.0 }
+        );
+        assert_eq!(range.or_unit_if_empty(), range);
+
+        assert_eq!(
+            DataRange::of([5.0]).widen_if_flat(),
+            DataRange { min: 4.0, max: 6.0 }
+        );
+        assert_eq!(range.widen_if_flat(), range);
+
+        assert_eq!(range.expand(0.0), range);
+        assert_eq!(
+            DataRange::of([0.0, 100.0]).expand(0.05),
+            DataRange {
+                min: -5.0,
+                max: 105.0
+            }
+        );
+        assert_eq!(
+            DataRange::of([5.0]).expand(0.25),
+            DataRange { min: 3.5, max: 6.5 }
+        );
+    }
+}
@@ -1,29 +1,200 @@
-use crate::data::PlotData;
-use crate::ir::{ResolvedAesthetics, ResolvedFacet, ResolvedLayer, ResolvedSpec};
-use crate::parser::ast::{AestheticValue, Aesthetics, Layer, PlotSpec};
-use anyhow::Result;
+use crate::data::{DataSources, PlotData};
+use crate::error::GramGraphError;
+use crate::ir::{
+    ResolvedAesthetics, ResolvedFacet, ResolvedFacetGrid, ResolvedLayer, ResolvedSpec,
+};
+use crate::parser::ast::{
+    AestheticValue, Aesthetics, CategoryOrder, ColumnRef, CompareOp, CoordSystem, DataStage,
+    FacetLabeller, FilterExpr, FilterValue, Layer, PieLayer, PivotLonger, PlotSpec, Sample,
+    SegmentLayer, Stat,
+};
+use anyhow::{anyhow, Context, Result};
 
 /// Resolve all aesthetic mappings for the entire plot
-pub fn resolve_plot_aesthetics(spec: &PlotSpec, _data: &PlotData) -> Result<ResolvedSpec> {
-    // 0. Resolve global aesthetics (simple clone now)
-    let resolved_aes = spec.aesthetics.clone();
+pub fn resolve_plot_aesthetics(spec: &PlotSpec, data: &PlotData) -> Result<ResolvedSpec> {
+    resolve_plot_aesthetics_with_sources(spec, data, &DataSources::new())
+}
+
+/// `resolve_plot_aesthetics` for a spec whose layers may read other tables with
+/// `data:`; each such layer's columns are checked against its own table
+pub fn resolve_plot_aesthetics_with_sources(
+    spec: &PlotSpec,
+    data: &PlotData,
+    sources: &DataSources,
+) -> Result<ResolvedSpec> {
+    // 0. Resolve global aesthetics; positions become the header names they point at
+    // and a y list becomes a pivot_longer() stage
+    let headers = spec
+        .stages
+        .iter()
+        .fold(data.headers.clone(), |headers, stage| {
+            stage.output_headers(&headers)
+        });
+    let mut resolved_aes = spec.aesthetics.clone();
+    if let Some(aes) = resolved_aes.as_mut() {
+        for col in aes.columns_mut() {
+            col.resolve_index(&headers)?;
+        }
+    }
+    let mut spec_layers = spec.layers.clone();
+    for (i, layer) in spec_layers.iter_mut().enumerate() {
+        // A `data:` layer counts from its own table; an unloaded one fails below
+        let headers = match layer.data() {
+            Some(name) => match sources.get(name) {
+                Some(table) => table.headers.clone(),
+                None => continue,
+            },
+            None => headers.clone(),
+        };
+        for col in layer.columns_mut() {
+            col.resolve_index(&headers)
+                .with_context(|| format!("Layer {}", i + 1))?;
+        }
+    }
+    let mut stages = spec.stages.clone();
+    let y_list = resolved_aes
+        .as_mut()
+        .map(|aes| expand_y_columns(aes, &mut stages, data))
+        .transpose()?
+        .flatten();
 
     // 1. Resolve Facet (if any)
-    let facet = if let Some(f) = &spec.facet {
-        Some(ResolvedFacet {
+    if spec.facet.is_some() && spec.facet_grid.is_some() {
+        return Err(anyhow!("Use facet_wrap() or facet_grid(), not both"));
+    }
+    if let Some(f) = &spec.facet {
+        if f.ncol.is_some() && f.nrow.is_some() {
+            return Err(anyhow!("facet_wrap() takes ncol or nrow, not both"));
+        }
+        if f.ncol == Some(0) || f.nrow == Some(0) {
+            return Err(anyhow!("facet_wrap() ncol and nrow must be at least 1"));
+        }
+        if f.bins == Some(0) {
+            return Err(anyhow!("facet_wrap() bins must be at least 1"));
+        }
+    }
+    if let Some((name, labeller, label_size)) = spec
+        .facet
+        .as_ref()
+        .map(|f| ("facet_wrap", &f.labeller, f.label_size))
+        .or_else(|| {
+            spec.facet_grid
+                .as_ref()
+                .map(|g| ("facet_grid", &g.labeller, g.label_size))
+        })
+    {
+        if let FacetLabeller::Template(template) = labeller {
+            if !template.contains("{value}") {
+                return Err(anyhow!(
+                    "{}() labeller must be \"value\", \"both\", or a template containing {{value}}, got \"{}\"",
+                    name,
+                    template
+                ));
+            }
+        }
+        if label_size.is_some_and(|size| size <= 0.0) {
+            return Err(anyhow!("{}() label_size must be positive", name));
+        }
+    }
+    let facet = spec
+        .facet
+        .as_ref()
+        .map(|f| ResolvedFacet {
             col: f.by.clone(),
             ncol: f.ncol,
+            nrow: f.nrow,
             scales: f.scales.clone(),
+            bins: f.bins,
+            cut: f.cut,
+            grid: None,
+            labeller: f.labeller.clone(),
+            label_size: f.label_size,
         })
-    } else {
-        None
-    };
+        .or_else(|| {
+            spec.facet_grid.as_ref().map(|g| ResolvedFacet {
+                col: String::new(),
+                ncol: None,
+                nrow: None,
+                scales: g.scales.clone(),
+                bins: None,
+                cut: Default::default(),
+                grid: Some(ResolvedFacetGrid {
+                    rows: g.rows.clone(),
+                    cols: g.cols.clone(),
+                }),
+                labeller: g.labeller.clone(),
+                label_size: g.label_size,
+            })
+        });
+
+    for stage in &spec.stages {
+        if let DataStage::Head(0) | DataStage::Sample(Sample { n: 0, .. }) = stage {
+            return Err(anyhow!("{}() n must be at least 1", stage.name()));
+        }
+    }
+
+    if let Some(CoordSystem::Fixed { ratio }) = spec.coord {
+        if !(ratio.is_finite() && ratio > 0.0) {
+            return Err(anyhow!("coord_fixed() ratio must be greater than 0"));
+        }
+    }
+    if spec
+        .theme
+        .as_ref()
+        .and_then(|t| t.margin)
+        .is_some_and(|m| m.is_nan() || m < 0.0)
+    {
+        return Err(anyhow!("theme() margin must be at least 0"));
+    }
 
     // 2. Resolve layers
     let mut layers = Vec::new();
-    for layer in &spec.layers {
+    for layer in &spec_layers {
         // Layer variables are already resolved by preprocessor
         // Just resolve aesthetics
+        if let Stat::RollingMean {
+            window,
+            min_periods,
+        } = layer.stat()
+        {
+            if *window == 0 {
+                return Err(anyhow!("line() window must be at least 1"));
+            }
+            if min_periods.is_some_and(|m| m == 0 || m > *window) {
+                return Err(anyhow!(
+                    "line() min_periods must be between 1 and the window ({})",
+                    window
+                ));
+            }
+        }
+        if let Stat::Density { bw: Some(bw) } = layer.stat() {
+            if *bw <= 0.0 {
+                return Err(anyhow!("density() bw must be greater than 0"));
+            }
+        }
+        if let Layer::Pie(pie) = layer {
+            check_pie(pie, spec.layers.len(), spec.coord.as_ref())?;
+        }
+        if let Layer::Bar(bar) = layer {
+            if let Some(border) = &bar.border {
+                if crate::theme_resolve::parse_color(border).is_none() {
+                    return Err(anyhow!("Unknown color '{}' in bar() border", border));
+                }
+            }
+            if bar.border_width.is_some_and(|w| w < 0.0) {
+                return Err(anyhow!("bar() border_width must be at least 0"));
+            }
+        }
+        if let Layer::Point(point) = layer {
+            if let Some(fill) = &point.fill {
+                if crate::theme_resolve::parse_color(fill).is_none() {
+                    return Err(anyhow!("Unknown color '{}' in point() fill", fill));
+                }
+            }
+            if point.stroke.is_some_and(|s| s < 0.0) {
+                return Err(anyhow!("point() stroke must be at least 0"));
+            }
+        }
         let aesthetics = resolve_layer_aesthetics(layer, &resolved_aes)?;
         layers.push(ResolvedLayer {
             original_layer: layer.clone(),
@@ -31,9 +202,136 @@ pub fn resolve_plot_aesthetics(spec: &PlotSpec, _data: &PlotData) -> Result<Reso
         });
     }
 
-    // 3. Resolve labels (simple clone now)
+    if let Some(pivot) = &y_list {
+        if let Some(color) = layers
+            .iter()
+            .filter_map(|layer| layer.aesthetics.color.as_ref())
+            .find(|color| *color != &pivot.names_to)
+        {
+            return Err(anyhow!(
+                "aes(y: [...]) colors each listed column, so color can't also map '{}'; remove it, or pivot_longer() the columns to map color yourself",
+                color
+            ));
+        }
+        // A layer with its own y reads each source row once, from the first listed
+        // column's melted row, and isn't split by the list
+        let first_copy = FilterExpr::Compare {
+            column: pivot.names_to.clone(),
+            op: CompareOp::Eq,
+            value: FilterValue::Text(pivot.cols[0].clone()),
+        };
+        for layer in layers
+            .iter_mut()
+            .filter(|layer| layer.original_layer.data().is_none())
+            .filter(|layer| layer.aesthetics.y_col.as_ref() != Some(&pivot.values_to))
+        {
+            let aes = &mut layer.aesthetics;
+            if aes.color.as_ref() == Some(&pivot.names_to) {
+                aes.color = None;
+            }
+            aes.group.retain(|col| col != &pivot.names_to);
+            if let Some(filter) = layer.original_layer.filter_mut() {
+                *filter = Some(match filter.take() {
+                    Some(expr) => FilterExpr::And(Box::new(expr), Box::new(first_copy.clone())),
+                    None => first_copy.clone(),
+                });
+            }
+        }
+    }
+
+    // 3. Check every referenced column exists before any data is touched
+    let plot_layers: Vec<ResolvedLayer> = layers
+        .iter()
+        .filter(|layer| layer.original_layer.data().is_none())
+        .cloned()
+        .collect();
+    validate_columns(&plot_layers, facet.as_ref(), &stages, data)?;
+    for (i, layer) in layers.iter().enumerate() {
+        let Some(name) = layer.original_layer.data() else {
+            continue;
+        };
+        let table = sources
+            .get(name)
+            .ok_or_else(|| anyhow!("Layer {} data: \"{}\" is not loaded", i + 1, name))?;
+        if facet.is_some() {
+            return Err(anyhow!(
+                "Layer {} data: \"{}\" can't be faceted; facets split the plot's data only",
+                i + 1,
+                name
+            ));
+        }
+        validate_columns(std::slice::from_ref(layer), None, &[], table)
+            .with_context(|| format!("Layer {} data: \"{}\"", i + 1, name))?;
+    }
+
+    // Listed y columns keep their order in the palette and legend
+    let mut color_scale = spec.color_scale.clone().unwrap_or_default();
+    if let Some(pivot) = &y_list {
+        if color_scale.levels.is_empty() && color_scale.order.is_none() {
+            color_scale.levels = pivot.cols.clone();
+        }
+    }
+
+    // 4. Resolve labels (simple clone now)
     let labels = spec.labels.clone().unwrap_or_default();
 
+    // 5. Validate palette and manual colors up front so typos fail instead of rendering a default
+    if let Some(name) = spec.color_scale.as_ref().and_then(|s| s.palette.as_ref()) {
+        if crate::palette::ColorPalette::named(name).is_none() {
+            return Err(anyhow!(
+                "Unknown palette '{}' in scale_color. Available palettes: {}",
+                name,
+                crate::palette::ColorPalette::names().join(", ")
+            ));
+        }
+    }
+    if spec
+        .color_scale
+        .as_ref()
+        .is_some_and(|s| s.order == Some(CategoryOrder::Value))
+    {
+        anyhow::bail!(r#"scale_color order must be "appearance" or "alpha""#);
+    }
+    if let Some(manual) = &spec.color_manual {
+        let colors = manual
+            .named
+            .iter()
+            .map(|(_, color)| color)
+            .chain(manual.ordered.iter());
+        for color in colors {
+            if crate::theme_resolve::parse_color(color).is_none() {
+                return Err(anyhow!("Unknown color '{}' in scale_color_manual", color));
+            }
+        }
+    }
+    for note in &spec.annotations {
+        if let Some(color) = &note.color {
+            if crate::theme_resolve::parse_color(color).is_none() {
+                return Err(anyhow!("Unknown color '{}' in annotate()", color));
+            }
+        }
+        if note.size.is_some_and(|size| size.is_nan() || size <= 0.0) {
+            anyhow::bail!("annotate() size must be greater than 0");
+        }
+        if note.arrow && note.nudge_x == 0.0 && note.nudge_y == 0.0 {
+            anyhow::bail!(
+                "annotate() arrow: true needs nudge_x: or nudge_y: to move the text off its point"
+            );
+        }
+    }
+    for shade in &spec.shades {
+        if let Some(color) = &shade.color {
+            if crate::theme_resolve::parse_color(color).is_none() {
+                return Err(anyhow!("Unknown color '{}' in shade()", color));
+            }
+        }
+        if let (Some(ymin), Some(ymax)) = (shade.ymin, shade.ymax) {
+            if ymin > ymax {
+                anyhow::bail!("shade() ymin {} is above ymax {}", ymin, ymax);
+            }
+        }
+    }
+
     Ok(ResolvedSpec {
         layers,
         facet,
@@ -42,9 +340,174 @@ pub fn resolve_plot_aesthetics(spec: &PlotSpec, _data: &PlotData) -> Result<Reso
         theme: spec.theme.clone().unwrap_or_default(),
         x_scale_spec: spec.x_scale.clone(),
         y_scale_spec: spec.y_scale.clone(),
+        color_scale,
+        color_manual: spec.color_manual.clone(),
+        stages,
+        annotations: spec.annotations.clone(),
+        shades: spec.shades.clone(),
     })
 }
 
+/// `aes(y: [temp, humidity])` is shorthand for `pivot_longer(cols: temp, humidity)` after
+/// the other stages, with y mapped to the melted values and color to the column names.
+/// The pivot keeps the listed columns, and its `name`/`value` columns take a numbered
+/// name (`value_2`, ...) when the data already has one. Returns the pivot when `aes`
+/// lists columns; its `cols` are spelled as in the data, like the names they become.
+fn expand_y_columns(
+    aes: &mut Aesthetics,
+    stages: &mut Vec<DataStage>,
+    data: &PlotData,
+) -> Result<Option<PivotLonger>> {
+    if aes.y_columns.is_empty() {
+        return Ok(None);
+    }
+    if let Some(color) = &aes.color {
+        return Err(anyhow!(
+            "aes(y: [...]) colors each listed column, so color can't also map '{}'; remove it, or pivot_longer() the columns to map color yourself",
+            color
+        ));
+    }
+    let headers = stages.iter().fold(data.headers.clone(), |headers, stage| {
+        stage.output_headers(&headers)
+    });
+    let cols = std::mem::take(&mut aes.y_columns)
+        .into_iter()
+        .map(|col| {
+            headers
+                .iter()
+                .find(|h| h.eq_ignore_ascii_case(&col.to_string()))
+                .cloned()
+                .unwrap_or_else(|| col.to_string())
+        })
+        .collect();
+    let unused = |base: &str| {
+        std::iter::once(base.to_string())
+            .chain((2..).map(|n| format!("{}_{}", base, n)))
+            .find(|name| !headers.iter().any(|h| h.eq_ignore_ascii_case(name)))
+            .unwrap_or_default()
+    };
+    let pivot = PivotLonger {
+        cols,
+        names_to: unused("name"),
+        values_to: unused("value"),
+        keep: true,
+    };
+    aes.y = Some(pivot.values_to.clone().into());
+    aes.color = Some(pivot.names_to.clone().into());
+    stages.push(DataStage::PivotLonger(pivot.clone()));
+    Ok(Some(pivot))
+}
+
+/// Check every column referenced by the data stages, layers, and facet against the data
+/// headers (case-insensitively) and report all missing ones at once, with suggestions.
+/// Each stage is checked against the headers the stages before it produce, and the
+/// layers against the headers the last stage produces.
+pub fn validate_columns(
+    layers: &[ResolvedLayer],
+    facet: Option<&ResolvedFacet>,
+    stages: &[DataStage],
+    data: &PlotData,
+) -> Result<()> {
+    let mut headers = data.headers.clone();
+    for stage in stages {
+        check_columns(stage.columns(), &headers)?;
+        if let DataStage::PivotLonger(pivot) = stage {
+            check_pivot_names(pivot, &headers)?;
+        }
+        headers = stage.output_headers(&headers);
+    }
+
+    let mut referenced: Vec<&str> = Vec::new();
+    for layer in layers {
+        let aes = &layer.aesthetics;
+        referenced.extend(
+            [
+                Some(&aes.x_col),
+                aes.y_col.as_ref(),
+                aes.ymin_col.as_ref(),
+                aes.ymax_col.as_ref(),
+                aes.xend_col.as_ref(),
+                aes.yend_col.as_ref(),
+                aes.color.as_ref(),
+                aes.size.as_ref(),
+                aes.shape.as_ref(),
+                aes.alpha.as_ref(),
+                aes.fill.as_ref(),
+                aes.linetype.as_ref(),
+                aes.label.as_ref(),
+            ]
+            .into_iter()
+            .flatten()
+            .chain(&aes.group)
+            .map(String::as_str),
+        );
+        if let Layer::Point(p) = &layer.original_layer {
+            referenced.extend(p.fade_by.as_ref().and_then(ColumnRef::name));
+        }
+        if let Some(filter) = layer.original_layer.filter() {
+            referenced.extend(filter.columns());
+        }
+    }
+    if let Some(f) = facet {
+        referenced.push(f.col.as_str());
+        if let Some(grid) = &f.grid {
+            referenced.extend(grid.rows.as_deref());
+            referenced.extend(grid.cols.as_deref());
+        }
+    }
+    check_columns(referenced, &headers)
+}
+
+/// pivot_longer()'s new columns must not collide with each other or a kept column
+fn check_pivot_names(pivot: &PivotLonger, headers: &[String]) -> Result<()> {
+    if pivot.names_to.eq_ignore_ascii_case(&pivot.values_to) {
+        return Err(anyhow!(
+            "pivot_longer() names_to and values_to are both '{}'",
+            pivot.names_to
+        ));
+    }
+    for (arg, name) in [
+        ("names_to", &pivot.names_to),
+        ("values_to", &pivot.values_to),
+    ] {
+        if headers
+            .iter()
+            .any(|h| h.eq_ignore_ascii_case(name) && (pivot.keep || !pivot.pivots(h)))
+        {
+            return Err(anyhow!(
+                "pivot_longer() {}: '{}' is already a column; pick another name",
+                arg,
+                name
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn check_columns(referenced: Vec<&str>, headers: &[String]) -> Result<()> {
+    let mut missing: Vec<&str> = Vec::new();
+    for col in referenced {
+        // Reference layers (hline, vline, ...) resolve to an empty x column
+        if col.is_empty()
+            || headers.iter().any(|h| h.eq_ignore_ascii_case(col))
+            || missing.contains(&col)
+        {
+            continue;
+        }
+        missing.push(col);
+    }
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    Err(GramGraphError::ColumnNotFound {
+        name: missing[0].to_string(),
+        also_missing: missing[1..].iter().map(|col| col.to_string()).collect(),
+        available: headers.to_vec(),
+    }
+    .into())
+}
+
 /// Resolve all aesthetic mappings for a single layer (layer-specific + global)
 fn resolve_layer_aesthetics(
     layer: &Layer,
@@ -53,52 +516,58 @@ fn resolve_layer_aesthetics(
     // Resolve x and y (required)
     let (x_col, y_col) = resolve_positional(layer, global_aes)?;
 
+    // A fixed layer value wins over the inherited mapping, whose column then only groups
+    let global = global_aes.as_ref();
+    let mut overridden = Vec::new();
+
     // Resolve color mapping
     let color = match layer {
-        Layer::Line(l) => extract_mapped_string(&l.color),
-        Layer::Point(p) => extract_mapped_string(&p.color),
-        Layer::Bar(b) => extract_mapped_string(&b.color),
-        Layer::Area(a) => extract_mapped_string(&a.color),
-        Layer::Rug(r) => extract_mapped_string(&r.color),
-        Layer::Spike(s) => extract_mapped_string(&s.color),
-        Layer::LineRange(l) => extract_mapped_string(&l.color),
-        Layer::ErrorBar(e) => extract_mapped_string(&e.color),
-        Layer::PointRange(p) => extract_mapped_string(&p.color),
-        Layer::CrossBar(c) => extract_mapped_string(&c.color),
-        Layer::Ribbon(r) => extract_mapped_string(&r.color),
-        Layer::Boxplot(b) => extract_mapped_string(&b.color),
-        Layer::Violin(v) => extract_mapped_string(&v.color),
-        Layer::Density(d) => extract_mapped_string(&d.color),
-        Layer::Heatmap(_) => None,
-        Layer::HLine(_) | Layer::VLine(_) | Layer::AbLine(_) | Layer::Segment(_) => None,
-    }
-    .or_else(|| global_aes.as_ref().and_then(|a| a.color.clone()));
+        Layer::Line(l) => setting(&l.color),
+        Layer::Point(p) => setting(&p.color),
+        Layer::Bar(b) => setting(&b.color),
+        Layer::Area(a) => setting(&a.color),
+        Layer::Rug(r) => setting(&r.color),
+        Layer::Spike(s) => setting(&s.color),
+        Layer::LineRange(l) => setting(&l.color),
+        Layer::ErrorBar(e) => setting(&e.color),
+        Layer::PointRange(p) => setting(&p.color),
+        Layer::CrossBar(c) => setting(&c.color),
+        Layer::Ribbon(r) => setting(&r.color),
+        Layer::Boxplot(b) => setting(&b.color),
+        Layer::Violin(v) => setting(&v.color),
+        Layer::Density(d) => setting(&d.color),
+        Layer::Text(t) => setting(&t.color),
+        Layer::Segment(s) if s.is_mapped() => setting(&s.color),
+        Layer::Heatmap(_) | Layer::Pie(_) => Setting::Unset,
+        Layer::HLine(_) | Layer::VLine(_) | Layer::AbLine(_) | Layer::Segment(_) => Setting::Unset,
+    }
+    .inherit(global.and_then(|a| a.color.as_ref()), &mut overridden);
 
     // Resolve size mapping
     let size = match layer {
-        Layer::Line(l) => extract_mapped_string_from_f64(&l.width), // width can be data-driven
-        Layer::Point(p) => extract_mapped_string_from_f64(&p.size),
-        Layer::Bar(b) => extract_mapped_string_from_f64(&b.width),
-        Layer::Area(_) => None,
-        Layer::Rug(r) => extract_mapped_string_from_f64(&r.width),
-        Layer::Spike(s) => extract_mapped_string_from_f64(&s.width),
-        Layer::LineRange(l) => extract_mapped_string_from_f64(&l.width),
-        Layer::ErrorBar(e) => extract_mapped_string_from_f64(&e.line_width),
-        Layer::PointRange(p) => extract_mapped_string_from_f64(&p.size)
-            .or_else(|| extract_mapped_string_from_f64(&p.width)),
-        Layer::CrossBar(c) => extract_mapped_string_from_f64(&c.line_width),
-        Layer::Ribbon(_) => None,
-        Layer::Boxplot(b) => extract_mapped_string_from_f64(&b.width),
-        Layer::Violin(v) => extract_mapped_string_from_f64(&v.width),
-        Layer::Density(_) => None,
-        Layer::Heatmap(_) => None,
-        Layer::HLine(_) | Layer::VLine(_) | Layer::AbLine(_) | Layer::Segment(_) => None,
-    }
-    .or_else(|| global_aes.as_ref().and_then(|a| a.size.clone()));
+        Layer::Line(l) => setting(&l.width), // width can be data-driven
+        Layer::Point(p) => setting(&p.size),
+        Layer::Bar(b) => setting(&b.width),
+        Layer::Area(_) => Setting::Unset,
+        Layer::Rug(r) => setting(&r.width),
+        Layer::Spike(s) => setting(&s.width),
+        Layer::LineRange(l) => setting(&l.width),
+        Layer::ErrorBar(e) => setting(&e.line_width),
+        Layer::PointRange(p) => setting(&p.size).or(setting(&p.width)),
+        Layer::CrossBar(c) => setting(&c.line_width),
+        Layer::Ribbon(_) => Setting::Unset,
+        Layer::Boxplot(b) => setting(&b.width),
+        Layer::Violin(v) => setting(&v.width),
+        Layer::Density(_) => Setting::Unset,
+        Layer::Heatmap(_) => Setting::Unset,
+        Layer::Text(_) | Layer::Pie(_) => Setting::Unset,
+        Layer::HLine(_) | Layer::VLine(_) | Layer::AbLine(_) | Layer::Segment(_) => Setting::Unset,
+    }
+    .inherit(global.and_then(|a| a.size.as_ref()), &mut overridden);
 
     // Resolve shape mapping (point only)
     let shape = match layer {
-        Layer::Point(p) => extract_mapped_string(&p.shape),
+        Layer::Point(p) => setting(&p.shape),
         Layer::Line(_)
         | Layer::Bar(_)
         | Layer::Area(_)
@@ -115,52 +584,64 @@ fn resolve_layer_aesthetics(
         | Layer::HLine(_)
         | Layer::VLine(_)
         | Layer::AbLine(_)
-        | Layer::Segment(_) => None,
-        Layer::PointRange(p) => extract_mapped_string(&p.shape),
+        | Layer::Segment(_)
+        | Layer::Text(_)
+        | Layer::Pie(_) => Setting::Unset,
+        Layer::PointRange(p) => setting(&p.shape),
     }
-    .or_else(|| global_aes.as_ref().and_then(|a| a.shape.clone()));
+    .inherit(global.and_then(|a| a.shape.as_ref()), &mut overridden);
 
     // Resolve alpha mapping
     let alpha = match layer {
-        Layer::Line(l) => extract_mapped_string_from_f64(&l.alpha),
-        Layer::Point(p) => extract_mapped_string_from_f64(&p.alpha),
-        Layer::Bar(b) => extract_mapped_string_from_f64(&b.alpha),
-        Layer::Area(a) => extract_mapped_string_from_f64(&a.alpha),
-        Layer::Rug(r) => extract_mapped_string_from_f64(&r.alpha),
-        Layer::Spike(s) => extract_mapped_string_from_f64(&s.alpha),
-        Layer::LineRange(l) => extract_mapped_string_from_f64(&l.alpha),
-        Layer::ErrorBar(e) => extract_mapped_string_from_f64(&e.alpha),
-        Layer::PointRange(p) => extract_mapped_string_from_f64(&p.alpha),
-        Layer::CrossBar(c) => extract_mapped_string_from_f64(&c.alpha),
-        Layer::Ribbon(r) => extract_mapped_string_from_f64(&r.alpha),
-        Layer::Boxplot(b) => extract_mapped_string_from_f64(&b.alpha),
-        Layer::Violin(v) => extract_mapped_string_from_f64(&v.alpha),
-        Layer::Density(d) => extract_mapped_string_from_f64(&d.alpha),
-        Layer::Heatmap(h) => extract_mapped_string_from_f64(&h.alpha),
-        Layer::HLine(_) | Layer::VLine(_) | Layer::AbLine(_) | Layer::Segment(_) => None,
-    }
-    .or_else(|| global_aes.as_ref().and_then(|a| a.alpha.clone()));
+        Layer::Line(l) => setting(&l.alpha),
+        Layer::Point(p) => setting(&p.alpha),
+        Layer::Bar(b) => setting(&b.alpha),
+        Layer::Area(a) => setting(&a.alpha),
+        Layer::Rug(r) => setting(&r.alpha),
+        Layer::Spike(s) => setting(&s.alpha),
+        Layer::LineRange(l) => setting(&l.alpha),
+        Layer::ErrorBar(e) => setting(&e.alpha),
+        Layer::PointRange(p) => setting(&p.alpha),
+        Layer::CrossBar(c) => setting(&c.alpha),
+        Layer::Ribbon(r) => setting(&r.alpha),
+        Layer::Boxplot(b) => setting(&b.alpha),
+        Layer::Violin(v) => setting(&v.alpha),
+        Layer::Density(d) => setting(&d.alpha),
+        Layer::Heatmap(h) => setting(&h.alpha),
+        Layer::Text(t) => setting(&t.alpha),
+        Layer::Pie(_) => Setting::Unset,
+        Layer::HLine(_) | Layer::VLine(_) | Layer::AbLine(_) | Layer::Segment(_) => Setting::Unset,
+    }
+    .inherit(global.and_then(|a| a.alpha.as_ref()), &mut overridden);
 
     // Resolve ymin/ymax
     let ymin_col = match layer {
-        Layer::Ribbon(r) => r.ymin.clone(),
-        Layer::LineRange(l) => l.ymin.clone(),
-        Layer::ErrorBar(e) => e.ymin.clone(),
-        Layer::PointRange(p) => p.ymin.clone(),
-        Layer::CrossBar(c) => c.ymin.clone(),
+        Layer::Ribbon(r) => r.ymin.as_ref().map(ColumnRef::to_string),
+        Layer::LineRange(l) => l.ymin.as_ref().map(ColumnRef::to_string),
+        Layer::ErrorBar(e) => e.ymin.as_ref().map(ColumnRef::to_string),
+        Layer::PointRange(p) => p.ymin.as_ref().map(ColumnRef::to_string),
+        Layer::CrossBar(c) => c.ymin.as_ref().map(ColumnRef::to_string),
         _ => None,
     }
-    .or_else(|| global_aes.as_ref().and_then(|a| a.ymin.clone()));
+    .or_else(|| {
+        global_aes
+            .as_ref()
+            .and_then(|a| a.ymin.as_ref().map(ColumnRef::to_string))
+    });
 
     let ymax_col = match layer {
-        Layer::Ribbon(r) => r.ymax.clone(),
-        Layer::LineRange(l) => l.ymax.clone(),
-        Layer::ErrorBar(e) => e.ymax.clone(),
-        Layer::PointRange(p) => p.ymax.clone(),
-        Layer::CrossBar(c) => c.ymax.clone(),
+        Layer::Ribbon(r) => r.ymax.as_ref().map(ColumnRef::to_string),
+        Layer::LineRange(l) => l.ymax.as_ref().map(ColumnRef::to_string),
+        Layer::ErrorBar(e) => e.ymax.as_ref().map(ColumnRef::to_string),
+        Layer::PointRange(p) => p.ymax.as_ref().map(ColumnRef::to_string),
+        Layer::CrossBar(c) => c.ymax.as_ref().map(ColumnRef::to_string),
         _ => None,
     }
-    .or_else(|| global_aes.as_ref().and_then(|a| a.ymax.clone()));
+    .or_else(|| {
+        global_aes
+            .as_ref()
+            .and_then(|a| a.ymax.as_ref().map(ColumnRef::to_string))
+    });
 
     if matches!(
         layer,
@@ -172,23 +653,67 @@ fn resolve_layer_aesthetics(
         );
     }
 
+    // Resolve segment ends (segments mapped to data)
+    let (xend_col, yend_col) = match layer {
+        Layer::Segment(s) if s.is_mapped() => segment_end_columns(s)?,
+        _ => (None, None),
+    };
+
+    // Resolve linetype mapping (line only)
+    let linetype = match layer {
+        Layer::Line(l) => {
+            setting(&l.linetype).inherit(global.and_then(|a| a.linetype.as_ref()), &mut overridden)
+        }
+        _ => None,
+    };
+
+    // Resolve the style-free grouping columns: `group:`, then overridden mappings
+    let mut group: Vec<String> = match layer {
+        Layer::Line(l) => l.group.as_ref(),
+        _ => None,
+    }
+    .or_else(|| global.and_then(|a| a.group.as_ref()))
+    .map(ColumnRef::to_string)
+    .into_iter()
+    .collect();
+    for col in overridden {
+        if !group.contains(&col) {
+            group.push(col);
+        }
+    }
+
     // Resolve fill column (heatmap value)
     let fill = match layer {
-        Layer::Heatmap(h) => h.fill.clone(),
+        Layer::Heatmap(h) => h.fill.as_ref().map(ColumnRef::to_string),
         _ => None,
     }
-    .or_else(|| global_aes.as_ref().and_then(|a| a.fill.clone()));
+    .or_else(|| {
+        global_aes
+            .as_ref()
+            .and_then(|a| a.fill.as_ref().map(ColumnRef::to_string))
+    });
+
+    // Resolve label column (text only)
+    let label = match layer {
+        Layer::Text(t) => Some(t.label.to_string()),
+        _ => None,
+    };
 
     Ok(ResolvedAesthetics {
         x_col,
         y_col,
         ymin_col,
         ymax_col,
+        xend_col,
+        yend_col,
         color,
         size,
         shape,
         alpha,
         fill,
+        linetype,
+        group,
+        label,
     })
 }
 
@@ -213,6 +738,9 @@ fn resolve_positional(
         Layer::Violin(v) => (v.x.as_ref(), v.y.as_ref()),
         Layer::Density(d) => (d.x.as_ref(), None), // Density only needs x
         Layer::Heatmap(h) => (h.x.as_ref(), h.y.as_ref()),
+        Layer::Text(t) => (t.x.as_ref(), t.y.as_ref()),
+        Layer::Pie(p) => (p.x.as_ref(), p.y.as_ref()),
+        Layer::Segment(s) if s.is_mapped() => (mapped_coordinate(&s.x), mapped_coordinate(&s.y)),
         Layer::HLine(_) | Layer::VLine(_) | Layer::AbLine(_) | Layer::Segment(_) => {
             return Ok(("".to_string(), None));
         }
@@ -220,18 +748,18 @@ fn resolve_positional(
 
     // Get x column
     let x_col = if let Some(x) = x_override {
-        x.clone()
+        x.to_string()
     } else if let Some(ref aes) = global_aes {
-        aes.x.clone()
+        aes.x.to_string()
     } else {
         anyhow::bail!("No x aesthetic specified (use aes(x: ..., y: ...) or layer-level x: ...)");
     };
 
     // Get y column
     let y_col = if let Some(y) = y_override {
-        Some(y.clone())
+        Some(y.to_string())
     } else if let Some(ref aes) = global_aes {
-        aes.y.clone()
+        aes.y.as_ref().map(ColumnRef::to_string)
     } else {
         // y is optional for some layers (e.g. histogram)
         None
@@ -273,7 +801,7 @@ fn resolve_positional(
             }
             _ => {
                 anyhow::bail!(
-                    "No y aesthetic specified (use aes(x: ..., y: ...) or layer-level y: ...)"
+                    "No y aesthetic specified (use aes(x: ..., y: ...) or layer-level y: ..., or bar(stat: \"count\") to count rows)"
                 );
             }
         }
@@ -282,19 +810,90 @@ fn resolve_positional(
     Ok((x_col, y_col))
 }
 
-/// Extract column name from Mapped variant of AestheticValue<String>
-fn extract_mapped_string(value: &Option<AestheticValue<String>>) -> Option<String> {
+/// A pie fills its panel on its own, with no axes for other layers or a flip to share
+fn check_pie(pie: &PieLayer, n_layers: usize, coord: Option<&CoordSystem>) -> Result<()> {
+    if n_layers > 1 {
+        anyhow::bail!("pie() draws without axes and can't be combined with other layers");
+    }
+    if matches!(coord, Some(CoordSystem::Flip)) {
+        anyhow::bail!("pie() has no axes to flip; remove coord_flip()");
+    }
+    if pie.donut.is_some_and(|d| !(0.0..1.0).contains(&d)) {
+        anyhow::bail!("pie() donut must be at least 0 and less than 1");
+    }
+    if pie.other.is_some_and(|o| !(0.0..1.0).contains(&o)) {
+        anyhow::bail!("pie() other must be at least 0 and less than 1");
+    }
+    Ok(())
+}
+
+/// Column of a mapped segment coordinate
+fn mapped_coordinate(value: &Option<AestheticValue<f64>>) -> Option<&ColumnRef> {
     match value {
-        Some(AestheticValue::Mapped(col)) => Some(col.clone()),
+        Some(AestheticValue::Mapped(col)) => Some(col),
         _ => None,
     }
 }
 
-/// Extract column name from Mapped variant of AestheticValue<f64>
-fn extract_mapped_string_from_f64(value: &Option<AestheticValue<f64>>) -> Option<String> {
+/// xend and yend columns of a segment drawn per row. Every endpoint must be a
+/// column: a fixed number alongside columns has no row to pair with.
+fn segment_end_columns(segment: &SegmentLayer) -> Result<(Option<String>, Option<String>)> {
+    let coordinates = [&segment.x, &segment.y, &segment.xend, &segment.yend];
+    if coordinates
+        .iter()
+        .any(|value| matches!(value, Some(AestheticValue::Fixed(_))))
+    {
+        anyhow::bail!(
+            "segment() can't mix columns and numbers; map x, y, xend, and yend all to columns (mutate() can add a constant column)"
+        );
+    }
+    match (
+        mapped_coordinate(&segment.xend),
+        mapped_coordinate(&segment.yend),
+    ) {
+        (Some(xend), Some(yend)) => Ok((Some(xend.to_string()), Some(yend.to_string()))),
+        _ => anyhow::bail!("segment() needs xend: and yend: columns to draw a segment per row"),
+    }
+}
+
+/// How a layer sets one aesthetic itself
+enum Setting {
+    Mapped(String),
+    Fixed,
+    Unset,
+}
+
+/// Read a layer's own aesthetic argument
+fn setting<T>(value: &Option<AestheticValue<T>>) -> Setting {
     match value {
-        Some(AestheticValue::Mapped(col)) => Some(col.clone()),
-        _ => None,
+        Some(AestheticValue::Mapped(col)) => Setting::Mapped(col.to_string()),
+        Some(AestheticValue::Fixed(_)) => Setting::Fixed,
+        None => Setting::Unset,
+    }
+}
+
+impl Setting {
+    /// This setting, or `other` when the layer leaves it unset
+    fn or(self, other: Setting) -> Setting {
+        match self {
+            Setting::Unset => other,
+            set => set,
+        }
+    }
+
+    /// The column that styles the aesthetic: the layer's own mapping, else the global
+    /// one unless the layer fixes a value. An overridden global column is recorded so
+    /// it still splits the layer into groups.
+    fn inherit(self, global: Option<&ColumnRef>, overridden: &mut Vec<String>) -> Option<String> {
+        let global = global.map(ColumnRef::to_string);
+        match self {
+            Setting::Mapped(col) => Some(col),
+            Setting::Fixed => {
+                overridden.extend(global);
+                None
+            }
+            Setting::Unset => global,
+        }
     }
 }
 
@@ -315,8 +914,9 @@ mod tests {
     fn test_resolve_simple() {
         let spec = PlotSpec {
             aesthetics: Some(Aesthetics {
-                x: "x".to_string(),
-                y: Some("y".to_string()),
+                x: "x".into(),
+                y: Some("y".into()),
+                y_columns: Vec::new(),
                 color: None,
                 size: None,
                 shape: None,
@@ -324,14 +924,22 @@ mod tests {
                 ymin: None,
                 ymax: None,
                 fill: None,
+                linetype: None,
+                group: None,
             }),
             layers: vec![Layer::Line(LineLayer::default())],
             labels: Some(crate::parser::ast::Labels::default()),
             facet: None,
+            facet_grid: None,
             coord: None,
             theme: None,
             x_scale: None,
             y_scale: None,
+            color_scale: None,
+            color_manual: None,
+            stages: Vec::new(),
+            annotations: Vec::new(),
+            shades: Vec::new(),
         };
         let data = make_data();
         let resolved = resolve_plot_aesthetics(&spec, &data).unwrap();
@@ -344,8 +952,9 @@ mod tests {
     fn test_resolve_override() {
         let spec = PlotSpec {
             aesthetics: Some(Aesthetics {
-                x: "x".to_string(),
-                y: Some("y".to_string()),
+                x: "x".into(),
+                y: Some("y".into()),
+                y_columns: Vec::new(),
                 color: None,
                 size: None,
                 shape: None,
@@ -353,18 +962,26 @@ mod tests {
                 ymin: None,
                 ymax: None,
                 fill: None,
+                linetype: None,
+                group: None,
             }),
             layers: vec![Layer::Point(PointLayer {
                 x: None,
-                y: Some("g".to_string()),
+                y: Some("g".into()),
                 ..Default::default()
             })],
             labels: Some(crate::parser::ast::Labels::default()),
             facet: None,
+            facet_grid: None,
             coord: None,
             theme: None,
             x_scale: None,
             y_scale: None,
+            color_scale: None,
+            color_manual: None,
+            stages: Vec::new(),
+            annotations: Vec::new(),
+            shades: Vec::new(),
         };
         let data = make_data();
         let resolved = resolve_plot_aesthetics(&spec, &data).unwrap();
@@ -378,10 +995,16 @@ mod tests {
             layers: vec![Layer::Line(LineLayer::default())],
             labels: None,
             facet: None,
+            facet_grid: None,
             coord: None,
             theme: None,
             x_scale: None,
             y_scale: None,
+            color_scale: None,
+            color_manual: None,
+            stages: Vec::new(),
+            annotations: Vec::new(),
+            shades: Vec::new(),
         };
         let data = make_data();
         let res = resolve_plot_aesthetics(&spec, &data);
@@ -392,8 +1015,9 @@ mod tests {
     fn test_resolve_facet() {
         let spec = PlotSpec {
             aesthetics: Some(Aesthetics {
-                x: "x".to_string(),
-                y: Some("y".to_string()),
+                x: "x".into(),
+                y: Some("y".into()),
+                y_columns: Vec::new(),
                 color: None,
                 size: None,
                 shape: None,
@@ -401,22 +1025,417 @@ mod tests {
                 ymin: None,
                 ymax: None,
                 fill: None,
+                linetype: None,
+                group: None,
             }),
             layers: vec![],
             labels: Some(crate::parser::ast::Labels::default()),
             facet: Some(crate::parser::ast::Facet {
                 by: "g".to_string(),
                 ncol: None,
+                nrow: None,
                 scales: crate::parser::ast::FacetScales::Fixed,
+                bins: None,
+                cut: Default::default(),
+                labeller: Default::default(),
+                label_size: None,
             }),
+            facet_grid: None,
             coord: None,
             theme: None,
             x_scale: None,
             y_scale: None,
+            color_scale: None,
+            color_manual: None,
+            stages: Vec::new(),
+            annotations: Vec::new(),
+            shades: Vec::new(),
         };
         let data = make_data();
         let resolved = resolve_plot_aesthetics(&spec, &data).unwrap();
         assert!(resolved.facet.is_some());
         assert_eq!(resolved.facet.unwrap().col, "g");
+
+        let mut both = spec.clone();
+        let facet = both.facet.as_mut().unwrap();
+        facet.ncol = Some(2);
+        facet.nrow = Some(1);
+        let err = resolve_plot_aesthetics(&both, &data).unwrap_err();
+        assert!(err.to_string().contains("ncol or nrow, not both"));
+
+        // A template has to show the value, or every panel would read the same
+        let mut untitled = spec.clone();
+        untitled.facet.as_mut().unwrap().labeller = FacetLabeller::Template("Region".into());
+        let err = resolve_plot_aesthetics(&untitled, &data).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            r#"facet_wrap() labeller must be "value", "both", or a template containing {value}, got "Region""#
+        );
+        let mut tiny = spec.clone();
+        tiny.facet.as_mut().unwrap().label_size = Some(0.0);
+        let err = resolve_plot_aesthetics(&tiny, &data).unwrap_err();
+        assert!(err.to_string().contains("label_size must be positive"));
+
+        let mut grid = spec.clone();
+        grid.facet = None;
+        grid.facet_grid = Some(crate::parser::ast::FacetGrid {
+            rows: Some("g".to_string()),
+            cols: None,
+            scales: crate::parser::ast::FacetScales::FreeY,
+            labeller: Default::default(),
+            label_size: None,
+        });
+        let facet = resolve_plot_aesthetics(&grid, &data)
+            .unwrap()
+            .facet
+            .unwrap();
+        assert_eq!(facet.grid.unwrap().rows.as_deref(), Some("g"));
+        assert_eq!(facet.scales, crate::parser::ast::FacetScales::FreeY);
+        assert_eq!(facet.labeller, FacetLabeller::Equals);
+
+        grid.facet = spec.facet.clone();
+        let err = resolve_plot_aesthetics(&grid, &data).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("facet_wrap() or facet_grid(), not both"));
+    }
+
+    fn spec_with(aesthetics: Aesthetics, layers: Vec<Layer>) -> PlotSpec {
+        PlotSpec {
+            aesthetics: Some(aesthetics),
+            layers,
+            labels: None,
+            facet: None,
+            facet_grid: None,
+            coord: None,
+            theme: None,
+            x_scale: None,
+            y_scale: None,
+            color_scale: None,
+            color_manual: None,
+            stages: Vec::new(),
+            annotations: Vec::new(),
+            shades: Vec::new(),
+        }
+    }
+
+    fn aes(x: &str, y: &str) -> Aesthetics {
+        Aesthetics {
+            x: x.into(),
+            y: Some(y.into()),
+            y_columns: Vec::new(),
+            color: None,
+            size: None,
+            shape: None,
+            alpha: None,
+            ymin: None,
+            ymax: None,
+            fill: None,
+            linetype: None,
+            group: None,
+        }
+    }
+
+    #[test]
+    fn test_resolve_reports_all_unknown_columns() {
+        let data = PlotData {
+            headers: vec![
+                "time".to_string(),
+                "temperature".to_string(),
+                "city".to_string(),
+            ],
+            rows: vec![],
+        };
+        let mut spec = spec_with(
+            aes("time", "temprature"),
+            vec![Layer::Point(PointLayer {
+                color: Some(AestheticValue::Mapped("cty".into())),
+                size: Some(AestheticValue::Mapped("pressure".into())),
+                ..Default::default()
+            })],
+        );
+        spec.facet = Some(crate::parser::ast::Facet {
+            by: "region".to_string(),
+            ncol: None,
+            nrow: None,
+            scales: crate::parser::ast::FacetScales::Fixed,
+            bins: None,
+            cut: Default::default(),
+            labeller: Default::default(),
+            label_size: None,
+        });
+        let err = resolve_plot_aesthetics(&spec, &data)
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("Unknown columns 'temprature' (did you mean 'temperature'?)"));
+        assert!(err.contains("'cty' (did you mean 'city'?)"));
+        assert!(err.contains("'pressure', 'region'."));
+        assert!(err.ends_with("Available columns: time, temperature, city"));
+    }
+
+    #[test]
+    fn test_resolve_columns_match_case_insensitively() {
+        let data = PlotData {
+            headers: vec!["Time".to_string(), "Value".to_string()],
+            rows: vec![],
+        };
+        let spec = spec_with(
+            aes("time", "VALUE"),
+            vec![
+                Layer::Line(LineLayer::default()),
+                Layer::HLine(crate::parser::ast::HLineLayer::default()),
+            ],
+        );
+        assert!(resolve_plot_aesthetics(&spec, &data).is_ok());
+
+        let spec = spec_with(
+            aes("time", "valeu"),
+            vec![Layer::Line(LineLayer::default())],
+        );
+        let err = resolve_plot_aesthetics(&spec, &data)
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("Unknown column 'valeu' (did you mean 'Value'?)"));
+    }
+
+    #[test]
+    fn test_resolve_mapped_segment_ends() {
+        let data = PlotData {
+            headers: ["x0", "y0", "x1", "y1", "team"].map(String::from).to_vec(),
+            rows: vec![],
+        };
+        let resolve = |dsl: &str| {
+            let (_, spec) = crate::parser::parse_plot_spec(dsl).unwrap();
+            resolve_plot_aesthetics(&spec, &data)
+        };
+
+        // x and y fall back to aes(), and so does the color grouping
+        let resolved =
+            resolve("aes(x: x0, y: y0, color: team) | segment(xend: x1, yend: y1)").unwrap();
+        let aes = &resolved.layers[0].aesthetics;
+        assert_eq!(aes.x_col, "x0");
+        assert_eq!(aes.y_col.as_deref(), Some("y0"));
+        assert_eq!(aes.xend_col.as_deref(), Some("x1"));
+        assert_eq!(aes.yend_col.as_deref(), Some("y1"));
+        assert_eq!(aes.color.as_deref(), Some("team"));
+
+        // A fixed segment reads no columns
+        let resolved = resolve("segment(x: 1, y: 2, xend: 3, yend: 4)").unwrap();
+        assert_eq!(resolved.layers[0].aesthetics.xend_col, None);
+
+        let err = resolve("segment(x: x0, y: y0, xend: 5, yend: y1)").unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("segment() can't mix columns and numbers"));
+        let err = resolve("aes(x: x0, y: y0) | segment(xend: x1)").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "segment() needs xend: and yend: columns to draw a segment per row"
+        );
+        let err = resolve("aes(x: x0, y: y0) | segment(xend: x1, yend: y2)").unwrap_err();
+        assert!(
+            err.to_string().starts_with("Unknown column 'y2'"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_resolve_column_indices() {
+        let data = PlotData {
+            headers: ["date", "sales", "2"].map(String::from).to_vec(),
+            rows: vec![],
+        };
+        let resolve = |dsl: &str| {
+            let (_, spec) = crate::parser::parse_plot_spec(dsl).unwrap();
+            resolve_plot_aesthetics(&spec, &data)
+        };
+
+        let resolved = resolve("aes(x: 0, y: 1) | line(color: `2`) | point(y: 2)").unwrap();
+        let line = &resolved.layers[0];
+        assert_eq!(line.aesthetics.x_col, "date");
+        assert_eq!(line.aesthetics.y_col.as_deref(), Some("sales"));
+        assert_eq!(line.aesthetics.color.as_deref(), Some("2"));
+        assert_eq!(resolved.layers[1].aesthetics.y_col.as_deref(), Some("2"));
+        // Positions count the columns the data stages leave
+        let resolved = resolve("mutate(double: sales * 2) | aes(x: 0, y: 3) | line()").unwrap();
+        assert_eq!(
+            resolved.layers[0].aesthetics.y_col.as_deref(),
+            Some("double")
+        );
+
+        let err = resolve("aes(x: 0, y: 3) | line()").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Column index 3 out of bounds (available columns: 3, numbered from 0)"
+        );
+        let err = resolve("aes(x: 0, y: 1) | line() | point(color: 9)").unwrap_err();
+        assert_eq!(
+            format!("{:#}", err),
+            "Layer 2: Column index 9 out of bounds (available columns: 3, numbered from 0)"
+        );
+    }
+
+    #[test]
+    fn test_resolve_bar_border() {
+        let data = make_data();
+        let resolve = |dsl: &str| {
+            let (_, spec) = crate::parser::parse_plot_spec(dsl).unwrap();
+            resolve_plot_aesthetics(&spec, &data)
+        };
+
+        assert!(resolve(r##"aes(x: g, y: y) | bar(border: "#333", border_width: 0)"##).is_ok());
+        let err = resolve(r#"aes(x: g, y: y) | bar(border: "blurple")"#).unwrap_err();
+        assert_eq!(err.to_string(), "Unknown color 'blurple' in bar() border");
+        let err =
+            resolve(r#"aes(x: g, y: y) | bar(border: "black", border_width: -1)"#).unwrap_err();
+        assert_eq!(err.to_string(), "bar() border_width must be at least 0");
+    }
+
+    #[test]
+    fn test_resolve_y_list_pivots_the_columns() {
+        let data = PlotData {
+            headers: vec![
+                "Time".into(),
+                "Temp".into(),
+                "humidity".into(),
+                "site".into(),
+            ],
+            rows: vec![],
+        };
+        let resolve = |dsl: &str| {
+            let (_, spec) = crate::parser::parse_plot_spec(dsl).unwrap();
+            resolve_plot_aesthetics(&spec, &data)
+        };
+
+        let spec =
+            resolve("aes(x: time, y: [temp, humidity]) | filter(temp > 0) | line()").unwrap();
+        // The pivot runs after the user's stages, so they still see the wide columns
+        assert_eq!(spec.stages.len(), 2);
+        assert_eq!(
+            spec.stages[1],
+            DataStage::PivotLonger(PivotLonger {
+                cols: vec!["Temp".into(), "humidity".into()],
+                keep: true,
+                ..PivotLonger::default()
+            })
+        );
+        let aes = &spec.layers[0].aesthetics;
+        assert_eq!(aes.y_col.as_deref(), Some("value"));
+        assert_eq!(aes.color.as_deref(), Some("name"));
+        assert_eq!(spec.color_scale.levels, vec!["Temp", "humidity"]);
+
+        // A fixed layer color is fine; any other color mapping is not
+        assert!(resolve(r#"aes(x: time, y: [temp, humidity]) | line(color: "gray")"#).is_ok());
+        for dsl in [
+            "aes(x: time, y: [temp, humidity], color: site) | line()",
+            "aes(x: time, y: [temp, humidity]) | line(color: site)",
+        ] {
+            let err = resolve(dsl).unwrap_err().to_string();
+            assert!(err.contains("color can't also map 'site'"), "{err}");
+        }
+
+        let err = resolve("aes(x: time, y: [temp, nope]) | line()").unwrap_err();
+        assert!(err.to_string().contains("Unknown column 'nope'"), "{err}");
+
+        // Columns already called name or value push the melted ones to the next free name
+        let spec = resolve("mutate(value: temp * 2) | aes(x: time, y: [temp, humidity]) | line()")
+            .unwrap();
+        let aes = &spec.layers[0].aesthetics;
+        assert_eq!(aes.y_col.as_deref(), Some("value_2"));
+        assert_eq!(aes.color.as_deref(), Some("name"));
+
+        // A layer with its own y keeps the listed columns, reads one melted copy of each
+        // row, and drops the list's color
+        let spec = resolve(
+            r#"aes(x: time, y: [temp, humidity]) | line() | point(y: temp, filter: site == "a")"#,
+        )
+        .unwrap();
+        let aes = &spec.layers[1].aesthetics;
+        assert_eq!(aes.y_col.as_deref(), Some("temp"));
+        assert_eq!(aes.color, None);
+        assert_eq!(
+            spec.layers[1].original_layer.filter(),
+            Some(&FilterExpr::And(
+                Box::new(FilterExpr::Compare {
+                    column: "site".into(),
+                    op: CompareOp::Eq,
+                    value: FilterValue::Text("a".into()),
+                }),
+                Box::new(FilterExpr::Compare {
+                    column: "name".into(),
+                    op: CompareOp::Eq,
+                    value: FilterValue::Text("Temp".into()),
+                }),
+            ))
+        );
+    }
+
+    #[test]
+    fn test_resolve_point_fill_and_stroke() {
+        let data = make_data();
+        let resolve = |dsl: &str| {
+            let (_, spec) = crate::parser::parse_plot_spec(dsl).unwrap();
+            resolve_plot_aesthetics(&spec, &data)
+        };
+
+        assert!(resolve(r#"aes(x: x, y: y) | point(fill: "white", stroke: 1.5)"#).is_ok());
+        let err = resolve(r#"aes(x: x, y: y) | point(fill: "blurple")"#).unwrap_err();
+        assert_eq!(err.to_string(), "Unknown color 'blurple' in point() fill");
+        let err = resolve("aes(x: x, y: y) | jitter(stroke: -1)").unwrap_err();
+        assert_eq!(err.to_string(), "point() stroke must be at least 0");
+    }
+
+    #[test]
+    fn test_resolve_pie_stands_alone() {
+        let data = make_data();
+        let resolve = |dsl: &str| {
+            let (_, spec) = crate::parser::parse_plot_spec(dsl).unwrap();
+            resolve_plot_aesthetics(&spec, &data)
+        };
+
+        assert!(resolve("aes(x: g, y: y) | pie(donut: 0.5, other: 0.05)").is_ok());
+        let err = resolve("aes(x: g, y: y) | pie() | point()").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "pie() draws without axes and can't be combined with other layers"
+        );
+        let err = resolve("aes(x: g, y: y) | pie() | coord_flip()").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "pie() has no axes to flip; remove coord_flip()"
+        );
+        let err = resolve("aes(x: g, y: y) | pie(donut: 1)").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "pie() donut must be at least 0 and less than 1"
+        );
+        let err = resolve("aes(x: g, y: y) | pie(other: -0.1)").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "pie() other must be at least 0 and less than 1"
+        );
+        assert!(resolve("aes(x: g) | pie()")
+            .unwrap_err()
+            .to_string()
+            .starts_with("No y aesthetic specified"));
+    }
+
+    #[test]
+    fn test_resolve_coord_fixed_and_margin() {
+        let data = make_data();
+        let resolve = |dsl: &str| {
+            let (_, spec) = crate::parser::parse_plot_spec(dsl).unwrap();
+            resolve_plot_aesthetics(&spec, &data)
+        };
+
+        assert!(resolve("aes(x: x, y: y) | point() | coord_fixed() | theme(margin: 0)").is_ok());
+        let err = resolve("aes(x: x, y: y) | point() | coord_fixed(ratio: -1)").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "coord_fixed() ratio must be greater than 0"
+        );
+        let err = resolve("aes(x: x, y: y) | point() | theme(margin: -5)").unwrap_err();
+        assert_eq!(err.to_string(), "theme() margin must be at least 0");
     }
 }
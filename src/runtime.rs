@@ -1,33 +1,766 @@
-use crate::data::PlotData;
-use crate::parser::ast::PlotSpec;
+use crate::csv_reader::{self, CellUnit, CsvData};
+use crate::data::{DataSources, PlotData};
+use crate::error::GramGraphError;
+use crate::inspect::PlotPlan;
+use crate::ir::{RenderData, ResolvedSpec, SceneGraph};
+use crate::parser::{
+    self,
+    ast::{AxisScale, PlotSpec},
+};
+use crate::warning::{SkipReason, Warning};
 use crate::{compiler, graph, resolve, scale, transform, RenderOptions};
-use anyhow::Result;
+use anyhow::{Context, Result};
+
+/// What the public entry points return: a structured error instead of anyhow's
+pub type RenderResult<T> = std::result::Result<T, GramGraphError>;
+
+/// A rendered plot and the warnings raised while drawing it
+#[derive(Debug, Clone)]
+pub struct RenderReport {
+    /// PNG or SVG bytes, per `RenderOptions::format`
+    pub output: Vec<u8>,
+    pub warnings: Vec<Warning>,
+}
+
+/// Render a DSL string against in-memory data to PNG or SVG bytes.
+/// This is the library entry point; the CLI reads its input and calls this.
+/// Variables (`$name`) must already be expanded (see `preprocessor::expand_variables`).
+/// Warnings are dropped; `render_with_report` returns them.
+///
+/// ```
+/// use gramgraph::{csv_reader::CsvData, render, OutputFormat, RenderOptions};
+///
+/// let data = CsvData::from_columns(vec![
+///     ("day".to_string(), vec!["1".to_string(), "2".to_string(), "3".to_string()]),
+///     ("sales".to_string(), vec!["10".to_string(), "14".to_string(), "9".to_string()]),
+/// ])
+/// .unwrap();
+/// let options = RenderOptions {
+///     format: OutputFormat::Svg,
+///     ..RenderOptions::default()
+/// };
+/// let svg = render("aes(x: day, y: sales) | line()", data, options).unwrap();
+/// assert!(String::from_utf8(svg).unwrap().starts_with("<svg"));
+/// ```
+pub fn render(dsl: &str, data: CsvData, options: RenderOptions) -> RenderResult<Vec<u8>> {
+    render_with_report(dsl, data, options).map(|report| report.output)
+}
+
+/// Render a DSL string like `render`, returning the warnings alongside the output
+pub fn render_with_report(
+    dsl: &str,
+    data: CsvData,
+    options: RenderOptions,
+) -> RenderResult<RenderReport> {
+    render_with_sources(dsl, data, &no_sources, options)
+}
+
+/// Render a DSL string like `render_with_report`, reading the table each layer's
+/// `data: "name"` names with `load` (the CLI reads `name` as a file path). Each name is
+/// loaded once, and a failed load names the layer that asked for it.
+///
+/// ```
+/// use gramgraph::{csv_reader::CsvData, render_with_sources, OutputFormat, RenderOptions};
+///
+/// let table = |sales: [&str; 2]| {
+///     CsvData::from_columns(vec![
+///         ("day".to_string(), vec!["1".to_string(), "2".to_string()]),
+///         ("sales".to_string(), sales.map(String::from).to_vec()),
+///     ])
+/// };
+/// let options = RenderOptions {
+///     format: OutputFormat::Svg,
+///     ..RenderOptions::default()
+/// };
+/// let report = render_with_sources(
+///     r#"aes(x: day, y: sales) | line() | line(data: "last_year", color: "gray")"#,
+///     table(["10", "14"]).unwrap(),
+///     &|_name: &str| table(["8", "11"]),
+///     options,
+/// )
+/// .unwrap();
+/// assert!(report.output.starts_with(b"<svg"));
+/// ```
+pub fn render_with_sources(
+    dsl: &str,
+    data: CsvData,
+    load: &dyn Fn(&str) -> Result<CsvData>,
+    options: RenderOptions,
+) -> RenderResult<RenderReport> {
+    let (spec, mut warnings) = parse_dsl(dsl, &options).map_err(GramGraphError::from)?;
+    let mut report = render_spec_with_sources(spec, data, load, options)?;
+    warnings.append(&mut report.warnings);
+    report.warnings = warnings;
+    Ok(report)
+}
+
+/// Check a DSL string's argument names and parse it, keeping only the components that
+/// parse under `options.lenient`. Returns the warnings parsing raised.
+fn parse_dsl(dsl: &str, options: &RenderOptions) -> Result<(PlotSpec, Vec<Warning>)> {
+    // Reject misspelled argument names before the parser can ignore them
+    parser::validate_arguments(dsl)?;
+    let mut warnings = parser::deprecated_arguments(dsl);
+
+    let spec = if options.lenient {
+        let (remaining, spec) =
+            parser::parse_plot_spec_prefix(dsl).map_err(|e| parser::parse_error(dsl, e))?;
+        if !remaining.trim().is_empty() {
+            warnings.push(Warning::UnparsedInput {
+                input: remaining.trim().to_string(),
+            });
+        }
+        spec
+    } else {
+        parser::parse_plot_spec(dsl)
+            .map_err(|e| parser::parse_error(dsl, e))?
+            .1
+    };
+    Ok((spec, warnings))
+}
+
+/// Parse, resolve, transform, and scale a DSL string like `render_with_sources`
+/// without drawing anything: the layers, groups, facet panels, and axes a render
+/// would produce (the CLI's `--dry-run` and `--explain json`)
+///
+/// ```
+/// use gramgraph::{csv_reader::CsvData, runtime::plan_with_sources, RenderOptions};
+///
+/// let data = CsvData::from_columns(vec![
+///     ("day".to_string(), vec!["1".to_string(), "2".to_string()]),
+///     ("store".to_string(), vec!["A".to_string(), "B".to_string()]),
+/// ])
+/// .unwrap();
+/// let plan = plan_with_sources(
+///     "aes(x: day, y: day) | point() | facet_wrap(by: store)",
+///     data,
+///     &|name: &str| anyhow::bail!("no table {}", name),
+///     RenderOptions::default(),
+/// )
+/// .unwrap();
+/// assert_eq!(plan.data.panels.len(), 2);
+/// ```
+pub fn plan_with_sources(
+    dsl: &str,
+    data: CsvData,
+    load: &dyn Fn(&str) -> Result<CsvData>,
+    options: RenderOptions,
+) -> RenderResult<PlotPlan> {
+    let (spec, mut warnings) = parse_dsl(dsl, &options).map_err(GramGraphError::from)?;
+    let mut plan = plan_spec_with_sources(spec, data, load, options)?;
+    warnings.append(&mut plan.warnings);
+    plan.warnings = warnings;
+    Ok(plan)
+}
+
+/// `plan_with_sources` for an already-built spec
+pub fn plan_spec_with_sources(
+    spec: PlotSpec,
+    data: CsvData,
+    load: &dyn Fn(&str) -> Result<CsvData>,
+    options: RenderOptions,
+) -> RenderResult<PlotPlan> {
+    let rows = data.rows.len();
+    let (spec, data, warnings) = transform_plot(spec, PlotData::from_csv(data), load, &options)
+        .map_err(phase_error("Failed to plan plot"))?;
+    let scales = scale::build_scales(&data, &spec).map_err(phase_error("Failed to plan plot"))?;
+    Ok(PlotPlan {
+        spec,
+        data,
+        scales,
+        rows,
+        warnings,
+    })
+}
+
+/// Render an already-built spec (parsed, from `PlotSpec::builder()`, or from JSON)
+pub fn render_spec(spec: PlotSpec, data: CsvData, options: RenderOptions) -> RenderResult<Vec<u8>> {
+    render_spec_with_report(spec, data, options).map(|report| report.output)
+}
+
+/// Render an already-built spec like `render_spec`, returning the warnings alongside
+pub fn render_spec_with_report(
+    spec: PlotSpec,
+    data: CsvData,
+    options: RenderOptions,
+) -> RenderResult<RenderReport> {
+    render_spec_with_sources(spec, data, &no_sources, options)
+}
+
+/// Render an already-built spec like `render_spec_with_report`, reading `data:` tables
+/// with `load` (see `render_with_sources`)
+pub fn render_spec_with_sources(
+    spec: PlotSpec,
+    data: CsvData,
+    load: &dyn Fn(&str) -> Result<CsvData>,
+    options: RenderOptions,
+) -> RenderResult<RenderReport> {
+    render_report(spec, PlotData::from_csv(data), load, options)
+        .map_err(phase_error("Failed to render plot"))
+}
+
+/// Convert a failure of `phase` for the public entry points: a structured failure
+/// comes back as is (the CLI adds `phase` as context), anything else as a
+/// `RenderError` whose message starts with `phase`
+fn phase_error(phase: &'static str) -> impl Fn(anyhow::Error) -> GramGraphError {
+    move |err| match GramGraphError::from(err) {
+        GramGraphError::RenderError(message) => {
+            GramGraphError::RenderError(format!("{}: {}", phase, message))
+        }
+        structured => structured,
+    }
+}
+
+/// The loader for entry points without one: any `data:` layer fails
+fn no_sources(name: &str) -> Result<CsvData> {
+    anyhow::bail!(
+        "no table is loaded under that name; render with render_with_sources to read \"{}\"",
+        name
+    )
+}
 
 /// Render a plot specification to PNG bytes using the Ideal GoG Pipeline
-pub fn render_plot(spec: PlotSpec, data: PlotData, options: RenderOptions) -> Result<Vec<u8>> {
+pub fn render_plot(
+    spec: PlotSpec,
+    data: PlotData,
+    options: RenderOptions,
+) -> RenderResult<Vec<u8>> {
+    render_plot_with_report(spec, data, options).map(|report| report.output)
+}
+
+/// Run the pipeline, collecting warnings instead of printing them
+pub fn render_plot_with_report(
+    spec: PlotSpec,
+    data: PlotData,
+    options: RenderOptions,
+) -> RenderResult<RenderReport> {
+    render_report(spec, data, &no_sources, options).map_err(GramGraphError::from)
+}
+
+/// Run the pipeline through rendering, collecting warnings instead of printing them
+fn render_report(
+    spec: PlotSpec,
+    data: PlotData,
+    load: &dyn Fn(&str) -> Result<CsvData>,
+    options: RenderOptions,
+) -> Result<RenderReport> {
+    let (scene, warnings) = compile_plot(spec, data, load, &options)?;
+
+    // PHASE 5: RENDERING
+    // Execute drawing commands on the canvas.
+    let output = graph::Canvas::execute(scene, &options)
+        .map_err(|e| GramGraphError::RenderError(format!("{:#}", e)))?;
+    Ok(RenderReport { output, warnings })
+}
+
+/// Render a plot specification to raw pixels for embedding: 8-bit RGBA, row-major,
+/// with the width and height. These are the pixels the PNG output encodes, so
+/// `options.format` is ignored. Warnings are dropped.
+///
+/// ```
+/// use gramgraph::{csv_reader::CsvData, data::PlotData, parser, render_plot_rgba, RenderOptions};
+///
+/// let data = CsvData::from_columns(vec![
+///     ("day".to_string(), vec!["1".to_string(), "2".to_string()]),
+///     ("sales".to_string(), vec!["10".to_string(), "14".to_string()]),
+/// ])
+/// .unwrap();
+/// let (_, spec) = parser::parse_plot_spec("aes(x: day, y: sales) | line()").unwrap();
+/// let options = RenderOptions {
+///     width: 320,
+///     height: 240,
+///     ..RenderOptions::default()
+/// };
+/// let (pixels, width, height) =
+///     render_plot_rgba(spec, PlotData::from_csv(data), options).unwrap();
+/// assert_eq!(pixels.len(), (width * height * 4) as usize);
+/// ```
+pub fn render_plot_rgba(
+    spec: PlotSpec,
+    data: PlotData,
+    options: RenderOptions,
+) -> RenderResult<(Vec<u8>, u32, u32)> {
+    let (scene, _) = compile_plot(spec, data, &no_sources, &options)?;
+    graph::Canvas::render_rgba(scene).map_err(GramGraphError::from)
+}
+
+/// Strip percent and currency units from the data and `data:` tables
+/// (`csv_reader::clean_unit_columns`), leniently for the columns of an axis with
+/// `scale_x/scale_y(parse: ...)`. An axis whose columns all held percents gets percent
+/// tick labels unless it sets a format.
+fn apply_cell_units(
+    spec: &mut ResolvedSpec,
+    data: &mut PlotData,
+    sources: &mut DataSources,
+) -> Result<()> {
+    let mut hints: [Option<CellUnit>; 2] = [None, None];
+    for (hint, scale) in hints
+        .iter_mut()
+        .zip([&spec.x_scale_spec, &spec.y_scale_spec])
+    {
+        if let Some(name) = scale.as_ref().and_then(|s| s.labels.parse.as_deref()) {
+            *hint = Some(CellUnit::from_name(name).with_context(|| {
+                format!("Unknown parse '{name}'; expected \"percent\" or \"currency\"")
+            })?);
+        }
+    }
+
+    // Per axis: whether any column was seen, and whether all of them held percents
+    let mut seen = [false, false];
+    let mut percent = [true, true];
+    let tables = std::iter::once((None, data)).chain(
+        sources
+            .iter_mut()
+            .map(|(name, table)| (Some(name.as_str()), table)),
+    );
+    for (name, table) in tables {
+        let axis_columns = |x: bool| -> Vec<usize> {
+            spec.layers
+                .iter()
+                .filter(|layer| {
+                    !layer.original_layer.is_reference() && layer.original_layer.data() == name
+                })
+                .flat_map(|layer| {
+                    let aes = &layer.aesthetics;
+                    if x {
+                        vec![Some(&aes.x_col), aes.xend_col.as_ref()]
+                    } else {
+                        vec![
+                            aes.y_col.as_ref(),
+                            aes.ymin_col.as_ref(),
+                            aes.ymax_col.as_ref(),
+                            aes.yend_col.as_ref(),
+                        ]
+                    }
+                })
+                .flatten()
+                .filter_map(|col| {
+                    table
+                        .headers
+                        .iter()
+                        .position(|h| h.eq_ignore_ascii_case(col))
+                })
+                .collect()
+        };
+        let columns = [axis_columns(true), axis_columns(false)];
+
+        let table_hints: Vec<(usize, CellUnit)> = columns
+            .iter()
+            .zip(hints)
+            .filter_map(|(columns, hint)| {
+                let hint = hint?;
+                Some(columns.iter().map(move |&col| (col, hint)))
+            })
+            .flatten()
+            .collect();
+        let units = csv_reader::clean_unit_columns(&table.headers, &mut table.rows, &table_hints);
+
+        for (axis, columns) in columns.iter().enumerate() {
+            seen[axis] |= !columns.is_empty();
+            percent[axis] &= columns
+                .iter()
+                .all(|&col| units[col] == Some(CellUnit::Percent));
+        }
+    }
+
+    for ((scale, seen), percent) in [&mut spec.x_scale_spec, &mut spec.y_scale_spec]
+        .into_iter()
+        .zip(seen)
+        .zip(percent)
+    {
+        if !(seen && percent) {
+            continue;
+        }
+        let scale = scale.get_or_insert_with(AxisScale::default);
+        if scale.labels.format.is_none() {
+            scale.labels.format = Some("percent".to_string());
+        }
+    }
+    Ok(())
+}
+
+/// Phases 1-4: resolve, transform, scale, and compile `spec` into a scene
+fn compile_plot(
+    spec: PlotSpec,
+    data: PlotData,
+    load: &dyn Fn(&str) -> Result<CsvData>,
+    options: &RenderOptions,
+) -> Result<(SceneGraph, Vec<Warning>)> {
+    let (resolved_spec, render_data, warnings) = transform_plot(spec, data, load, options)?;
+
+    // 3. Scaling
+    let scales = scale::build_scales(&render_data, &resolved_spec)?;
+
+    // PHASE 4: COMPILATION (MAPPING)
+    // Convert data units to drawing commands.
+    let scene = compiler::compile_geometry(render_data, scales, &resolved_spec, options)?;
+    Ok((scene, warnings))
+}
+
+/// Phases 1-2: load `data:` tables, then resolve and transform `spec`
+fn transform_plot(
+    spec: PlotSpec,
+    mut data: PlotData,
+    load: &dyn Fn(&str) -> Result<CsvData>,
+    options: &RenderOptions,
+) -> Result<(ResolvedSpec, RenderData, Vec<Warning>)> {
     // Check for empty data (maintain legacy behavior for tests)
     if data.rows.is_empty() {
-        anyhow::bail!("Plot requires at least one data row");
+        return Err(GramGraphError::EmptyData.into());
+    }
+
+    // Repeated headers get numbered names, so each column can still be referenced
+    let mut warnings = Vec::new();
+    let renamed = csv_reader::disambiguate_headers(&mut data.headers);
+    if !renamed.is_empty() {
+        warnings.push(Warning::DuplicateColumns {
+            table: None,
+            renamed,
+        });
+    }
+
+    // Tables named by layers' `data:`, each read once
+    let mut sources = DataSources::new();
+    for (i, layer) in spec.layers.iter().enumerate() {
+        let Some(name) = layer.data() else {
+            continue;
+        };
+        if sources.contains_key(name) {
+            continue;
+        }
+        let mut table =
+            load(name).with_context(|| format!("Layer {} data: \"{}\"", i + 1, name))?;
+        let renamed = csv_reader::disambiguate_headers(&mut table.headers);
+        if !renamed.is_empty() {
+            warnings.push(Warning::DuplicateColumns {
+                table: Some(name.to_string()),
+                renamed,
+            });
+        }
+        if table.rows.is_empty() {
+            anyhow::bail!("Layer {} data: \"{}\" has no data rows", i + 1, name);
+        }
+        sources.insert(name.to_string(), PlotData::from_csv(table));
     }
 
     // PHASE 1: RESOLUTION
     // Resolve all aesthetics for all layers once.
     // Variables are substituted during resolution.
-    let resolved_spec = resolve::resolve_plot_aesthetics(&spec, &data)?;
+    let mut resolved_spec = resolve::resolve_plot_aesthetics_with_sources(&spec, &data, &sources)?;
+    apply_cell_units(&mut resolved_spec, &mut data, &mut sources)?;
 
     // PHASE 2: TRANSFORMATION
     // Apply stats (binning) and positions (stacking/dodging).
     // Returns RenderData with normalized geometry points.
-    let render_data = transform::apply_transformations(&resolved_spec, &data)?;
+    let render_data =
+        transform::apply_transformations_with_sources(&resolved_spec, &data, &sources, options)?;
+    for limit in &render_data.row_limits {
+        warnings.push(Warning::RowLimit {
+            stage: limit.stage.clone(),
+            kept: limit.after,
+            total: limit.before,
+        });
+    }
+    if render_data.filter_rejected_rows > 0 {
+        warnings.push(Warning::SkippedRows {
+            count: render_data.filter_rejected_rows,
+            layer: None,
+            reason: SkipReason::NonNumericFilter,
+        });
+    }
+    for i in 0..resolved_spec.layers.len() {
+        let skipped: usize = render_data
+            .panels
+            .iter()
+            .map(|panel| panel.layers[i].skipped_rows)
+            .sum();
+        if skipped > 0 {
+            warnings.push(Warning::SkippedRows {
+                count: skipped,
+                layer: Some(i + 1),
+                reason: SkipReason::MissingValues,
+            });
+        }
+        let rejected: usize = render_data
+            .panels
+            .iter()
+            .map(|panel| panel.layers[i].filter_rejected_rows)
+            .sum();
+        if rejected > 0 {
+            warnings.push(Warning::SkippedRows {
+                count: rejected,
+                layer: Some(i + 1),
+                reason: SkipReason::NonNumericFilter,
+            });
+        }
+        let matched: Option<usize> = render_data
+            .panels
+            .iter()
+            .map(|panel| panel.layers[i].filter_matched_rows)
+            .sum();
+        if matched == Some(0) {
+            warnings.push(Warning::EmptyLayerFilter { layer: i + 1 });
+        }
+    }
+    Ok((resolved_spec, render_data, warnings))
+}
 
-    // 3. Scaling
-    let scales = scale::build_scales(&render_data, &resolved_spec)?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LineOptions, PointOptions};
 
-    // PHASE 4: COMPILATION (MAPPING)
-    // Convert data units to drawing commands.
-    let scene = compiler::compile_geometry(render_data, scales, &resolved_spec, &options)?;
+    fn sales() -> CsvData {
+        let column = |name: &str, values: &[&str]| {
+            (
+                name.to_string(),
+                values.iter().map(|v| v.to_string()).collect(),
+            )
+        };
+        CsvData::from_columns(vec![
+            column("day", &["1", "2", "3", "1", "2", "3"]),
+            column("sales", &["10", "14", "9", "7", "8", "12"]),
+            column("store", &["a", "a", "a", "b", "b", "b"]),
+        ])
+        .unwrap()
+    }
 
-    // PHASE 5: RENDERING
-    // Execute drawing commands on the canvas.
-    graph::Canvas::execute(scene, &options)
+    #[test]
+    fn test_render_dsl_and_spec_share_output() {
+        let from_dsl = render(
+            "aes(x: day, y: sales, color: store) | line() | point(size: 4)",
+            sales(),
+            RenderOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(&from_dsl[..8], b"\x89PNG\r\n\x1a\n");
+
+        let spec = PlotSpec::builder()
+            .aes("day", "sales")
+            .color("store")
+            .line(LineOptions::new())
+            .point(PointOptions::new().size(4.0))
+            .build()
+            .unwrap();
+        let from_spec = render_spec(spec, sales(), RenderOptions::default()).unwrap();
+        assert_eq!(from_dsl, from_spec);
+    }
+
+    #[test]
+    fn test_render_plot_rgba_matches_png_pixels() {
+        let dsl = "aes(x: day, y: sales, color: store) | line()";
+        let options = RenderOptions {
+            width: 300,
+            height: 200,
+            ..RenderOptions::default()
+        };
+        let spec = || parser::parse_plot_spec(dsl).unwrap().1;
+        let (pixels, width, height) =
+            render_plot_rgba(spec(), PlotData::from_csv(sales()), options.clone()).unwrap();
+        assert_eq!((width, height), (300, 200));
+        assert_eq!(pixels.len(), 300 * 200 * 4);
+        // The background corner is opaque white
+        assert_eq!(&pixels[..4], &[255, 255, 255, 255]);
+
+        let png = render_plot(spec(), PlotData::from_csv(sales()), options).unwrap();
+        let decoded = image::load_from_memory(&png).unwrap().to_rgba8();
+        assert_eq!(decoded.into_raw(), pixels);
+    }
+
+    #[test]
+    fn test_render_with_report_returns_warnings() {
+        let report = render_with_report(
+            "aes(x: day, y: sales) | head(n: 4) | point() | facet_wrap(by: store, ncol: Some(2))",
+            sales(),
+            RenderOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(&report.output[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(
+            report.warnings,
+            vec![
+                Warning::Deprecated {
+                    message: "facet_wrap(ncol: Some(2)) is deprecated; write ncol: 2".to_string()
+                },
+                Warning::RowLimit {
+                    stage: "head".to_string(),
+                    kept: 4,
+                    total: 6
+                },
+            ]
+        );
+
+        let data = CsvData::from_columns(vec![
+            ("x".to_string(), vec!["1".into(), "2".into(), "3".into()]),
+            ("y".to_string(), vec!["5".into(), "NA".into(), "".into()]),
+        ])
+        .unwrap();
+        let report =
+            render_with_report("aes(x: x, y: y) | line()", data, RenderOptions::default()).unwrap();
+        assert_eq!(
+            report.warnings,
+            vec![Warning::SkippedRows {
+                count: 2,
+                layer: Some(1),
+                reason: SkipReason::MissingValues
+            }]
+        );
+        assert_eq!(
+            report.warnings[0].to_string(),
+            "skipped 2 row(s) with missing values in layer 1"
+        );
+
+        // A layer filter that keeps no rows in any panel is reported; one panel is enough
+        let report = render_with_report(
+            "aes(x: day, y: sales) | line() | point(filter: sales > 100) | text(label: store, filter: sales > 13) | facet_wrap(by: store)",
+            sales(),
+            RenderOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            report.warnings,
+            vec![Warning::EmptyLayerFilter { layer: 2 }]
+        );
+        assert_eq!(
+            report.warnings[0].to_string(),
+            "filter matched 0 rows in layer 2, so it draws nothing"
+        );
+
+        // A clean render has nothing to report
+        let report = render_with_report(
+            "aes(x: day, y: sales) | line()",
+            sales(),
+            RenderOptions::default(),
+        )
+        .unwrap();
+        assert!(report.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_render_renames_duplicate_headers() {
+        let data = |headers: [&str; 3]| {
+            CsvData::from_columns(
+                headers
+                    .iter()
+                    .zip([["1", "2", "3"], ["5", "6", "7"], ["40", "10", "20"]])
+                    .map(|(name, values)| (name.to_string(), values.map(String::from).to_vec()))
+                    .collect(),
+            )
+            .unwrap()
+        };
+        let report = render_with_report(
+            "aes(x: x, y: value_2) | line()",
+            data(["x", "value", "Value"]),
+            RenderOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(report.warnings.len(), 1);
+        assert_eq!(
+            report.warnings[0].to_string(),
+            "duplicate column names: 'Value' (column 2) repeats 'value' ignoring case and is now 'Value_2'"
+        );
+        // The renamed column holds the second copy's values
+        let distinct = render(
+            "aes(x: x, y: b) | line()",
+            data(["x", "a", "b"]),
+            RenderOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(report.output, distinct);
+    }
+
+    #[test]
+    fn test_render_reports_dsl_errors() {
+        let err = render(
+            "aes(x: day, y: sales) | line(colour: \"red\")",
+            sales(),
+            RenderOptions::default(),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("Unknown argument 'colour'"));
+
+        let err = render(
+            "aes(x: day, y: sales) | nope()",
+            sales(),
+            RenderOptions::default(),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("Parse error"));
+
+        let err = render(
+            "aes(x: day, y: profit) | line()",
+            sales(),
+            RenderOptions::default(),
+        )
+        .unwrap_err();
+        assert!(format!("{:#}", err).contains("Unknown column 'profit'"));
+    }
+
+    #[test]
+    fn test_percent_and_currency_cells_are_numbers() {
+        let data = |share: &[&str]| {
+            let column = |name: &str, values: &[&str]| {
+                (
+                    name.to_string(),
+                    values.iter().map(|v| v.to_string()).collect(),
+                )
+            };
+            CsvData::from_columns(vec![
+                column("month", &["Jan", "Feb", "Mar"]),
+                column("share", share),
+                column("price", &["$1,299.00", "$999", "NA"]),
+            ])
+            .unwrap()
+        };
+        let svg_texts = |dsl: &str, share: &[&str]| {
+            let options = RenderOptions {
+                format: crate::OutputFormat::Svg,
+                ..RenderOptions::default()
+            };
+            let svg = String::from_utf8(render(dsl, data(share), options).unwrap()).unwrap();
+            svg.lines()
+                .map(|line| line.trim().to_string())
+                .collect::<Vec<_>>()
+        };
+        let has = |texts: &[String], text: &str| texts.iter().any(|t| t == text);
+
+        // Percent columns default to percent tick labels
+        let texts = svg_texts("aes(x: month, y: share) | bar()", &["45%", "52.5%", "12 %"]);
+        assert!(has(&texts, "50%"), "{texts:?}");
+        // ... unless the axis sets a format
+        let texts = svg_texts(
+            r#"aes(x: month, y: share) | bar() | scale_y(format: "%.2f")"#,
+            &["45%", "52.5%", "12 %"],
+        );
+        assert!(has(&texts, "0.50"), "{texts:?}");
+
+        // Currency amounts plot by value, missing cells skipped
+        let texts = svg_texts("aes(x: month, y: price) | bar()", &["1", "2", "3"]);
+        assert!(has(&texts, "1200"), "{texts:?}");
+
+        // Bare numbers mixed into a percent column need the hint
+        let mixed = &["45%", "50", "12%"];
+        let err = render(
+            "aes(x: month, y: share) | bar()",
+            data(mixed),
+            RenderOptions::default(),
+        )
+        .unwrap_err();
+        assert!(format!("{err:#}").contains("45%"), "{err:#}");
+        let texts = svg_texts(
+            r#"aes(x: month, y: share) | bar() | scale_y(parse: "percent")"#,
+            mixed,
+        );
+        assert!(has(&texts, "50%"), "{texts:?}");
+
+        let err = render(
+            r#"aes(x: month, y: share) | bar() | scale_y(parse: "permille")"#,
+            data(mixed),
+            RenderOptions::default(),
+        )
+        .unwrap_err();
+        assert!(
+            format!("{err:#}").contains("Unknown parse 'permille'"),
+            "{err:#}"
+        );
+    }
 }
@@ -1,17 +1,20 @@
 use crate::datetime::{parse_datetime_interval_seconds, DEFAULT_DATETIME_FORMAT};
 use crate::ir::{
     AxisTransform, DateTimeScale, PanelScales, RenderData, ResolvedSpec, Scale, ScaleSystem,
+    TickLabels,
 };
-use crate::parser::ast::{AxisScale, FacetScales, ScaleType};
+use crate::parser::ast::{Annotation, AxisScale, FacetScales, ScaleType};
+use crate::range::DataRange;
 use anyhow::{anyhow, Result};
 
 /// Build the scale system for the plot
 pub fn build_scales(data: &RenderData, spec: &ResolvedSpec) -> Result<ScaleSystem> {
     // 1. Calculate raw ranges per panel
     let mut panel_raw_ranges = Vec::new();
-    for panel in &data.panels {
-        let x_mm = calculate_min_max_x(panel);
-        let y_mm = calculate_min_max_y(panel);
+    for (i, panel) in data.panels.iter().enumerate() {
+        check_finite(i, panel)?;
+        let x_mm = calculate_min_max_x(panel, spec);
+        let y_mm = calculate_min_max_y(panel, spec);
         panel_raw_ranges.push((x_mm, y_mm));
     }
 
@@ -25,29 +28,52 @@ pub fn build_scales(data: &RenderData, spec: &ResolvedSpec) -> Result<ScaleSyste
     // 3. Resolve final domains
     let mut final_scales = Vec::new();
 
-    // Pre-calculate globals if needed
-    let global_x = if matches!(scales_mode, FacetScales::Fixed | FacetScales::FreeY) {
-        merge_ranges(panel_raw_ranges.iter().map(|(x, _)| x))
-    } else {
-        MinMax::default() // Unused
-    };
-
-    let global_y = if matches!(scales_mode, FacetScales::Fixed | FacetScales::FreeX) {
-        merge_ranges(panel_raw_ranges.iter().map(|(_, y)| y))
-    } else {
-        MinMax::default()
-    };
+    // Shared axes use the ranges over every panel; free ones fall back to them when a
+    // panel (or grid row/column) has no rows left to draw
+    let global_x = merge_ranges(panel_raw_ranges.iter().map(|(x, _)| x));
+    let global_y = merge_ranges(panel_raw_ranges.iter().map(|(_, y)| y));
 
-    for (x_local, y_local) in &panel_raw_ranges {
-        let x_mm = match scales_mode {
-            FacetScales::Fixed | FacetScales::FreeY => global_x.clone(),
+    // A facet grid frees x per grid column and y per grid row rather than per panel
+    let grid_ncol = spec
+        .facet
+        .as_ref()
+        .filter(|f| f.grid.is_some())
+        .map(|_| data.facet_layout.ncol);
+
+    for (i, (x_local, y_local)) in panel_raw_ranges.iter().enumerate() {
+        let x_mm = match (scales_mode, grid_ncol) {
+            (FacetScales::Fixed | FacetScales::FreeY, _) => global_x.clone(),
+            (_, Some(ncol)) => merge_ranges(
+                panel_raw_ranges
+                    .iter()
+                    .skip(i % ncol)
+                    .step_by(ncol)
+                    .map(|(x, _)| x),
+            ),
             _ => x_local.clone(),
         };
+        let x_mm = if x_mm.is_empty() {
+            global_x.clone()
+        } else {
+            x_mm
+        };
 
-        let y_mm = match scales_mode {
-            FacetScales::Fixed | FacetScales::FreeX => global_y.clone(),
+        let y_mm = match (scales_mode, grid_ncol) {
+            (FacetScales::Fixed | FacetScales::FreeX, _) => global_y.clone(),
+            (_, Some(ncol)) => merge_ranges(
+                panel_raw_ranges
+                    .iter()
+                    .skip(i / ncol * ncol)
+                    .take(ncol)
+                    .map(|(_, y)| y),
+            ),
             _ => y_local.clone(),
         };
+        let y_mm = if y_mm.is_empty() {
+            global_y.clone()
+        } else {
+            y_mm
+        };
 
         // 4. Construct Scale objects
         // X-Axis
@@ -56,12 +82,8 @@ pub fn build_scales(data: &RenderData, spec: &ResolvedSpec) -> Result<ScaleSyste
             let n = x_mm.categories.len() as f64;
             Scale {
                 domain: (0.0, n),
-                range: if let Some(s) = &spec.x_scale_spec {
-                    if matches!(s.scale_type, ScaleType::Reverse) {
-                        (n - 0.5, -0.5)
-                    } else {
-                        (-0.5, n - 0.5)
-                    }
+                range: if is_reversed(spec.x_scale_spec.as_ref()) {
+                    (n - 0.5, -0.5)
                 } else {
                     (-0.5, n - 0.5)
                 },
@@ -70,6 +92,7 @@ pub fn build_scales(data: &RenderData, spec: &ResolvedSpec) -> Result<ScaleSyste
                 tick_positions: vec![],
                 datetime: None,
                 transform: AxisTransform::Linear,
+                labels: TickLabels::from_spec(spec.x_scale_spec.as_ref()),
             }
         } else {
             build_continuous_scale(&x_mm, spec.x_scale_spec.as_ref(), "x")?
@@ -80,12 +103,8 @@ pub fn build_scales(data: &RenderData, spec: &ResolvedSpec) -> Result<ScaleSyste
             let n = y_mm.categories.len() as f64;
             Scale {
                 domain: (0.0, n),
-                range: if let Some(s) = &spec.y_scale_spec {
-                    if matches!(s.scale_type, ScaleType::Reverse) {
-                        (n - 0.5, -0.5)
-                    } else {
-                        (-0.5, n - 0.5)
-                    }
+                range: if is_reversed(spec.y_scale_spec.as_ref()) {
+                    (n - 0.5, -0.5)
                 } else {
                     (-0.5, n - 0.5)
                 },
@@ -94,6 +113,7 @@ pub fn build_scales(data: &RenderData, spec: &ResolvedSpec) -> Result<ScaleSyste
                 tick_positions: vec![],
                 datetime: None,
                 transform: AxisTransform::Linear,
+                labels: TickLabels::from_spec(spec.y_scale_spec.as_ref()),
             }
         } else {
             build_continuous_scale(&y_mm, spec.y_scale_spec.as_ref(), "y")?
@@ -117,37 +137,62 @@ fn build_continuous_scale(
 ) -> Result<Scale> {
     let is_datetime = axis_scale.is_some_and(|s| matches!(s.scale_type, ScaleType::DateTime));
     let transform = axis_transform(axis_scale);
-    let reverse = axis_scale.is_some_and(|s| matches!(s.scale_type, ScaleType::Reverse));
-    let raw_min = mm.min;
-    let raw_max = mm.max;
+    let reverse = is_reversed(axis_scale);
+    let raw_min = mm.range.min;
+    let raw_max = mm.range.max;
+    let expand = axis_scale.and_then(|s| s.labels.expand);
+    if let Some(fraction) = expand {
+        if fraction < 0.0 {
+            return Err(anyhow!(
+                "scale_{}() expand must be at least 0, got {}",
+                axis_name,
+                fraction
+            ));
+        }
+    }
 
     if is_datetime {
-        let datetime_range = if raw_min == raw_max {
-            (raw_min - 1.0, raw_max + 1.0)
-        } else {
-            (raw_min, raw_max)
+        let datetime_range = match expand {
+            Some(fraction) => mm.range.expand(fraction),
+            None => mm.range.widen_if_flat(),
         };
 
+        let (min, max) = (datetime_range.min, datetime_range.max);
         return Ok(Scale {
             domain: (raw_min, raw_max),
-            range: datetime_range,
+            range: if reverse { (max, min) } else { (min, max) },
             is_categorical: false,
             categories: Vec::new(),
             tick_positions: vec![],
             datetime: build_datetime_scale(axis_scale)?,
             transform: AxisTransform::Linear,
+            labels: TickLabels::from_spec(axis_scale),
         });
     }
 
-    let (min, max, ticks) = if let Some(scale) = axis_scale {
-        if let Some((lmin, lmax)) = scale.limits {
-            transformed_ticks_within(lmin, lmax, transform, 8, axis_name)?
-        } else {
-            transformed_nice_range(raw_min, raw_max, transform, 8, axis_name)?
+    let limits = match (axis_scale.and_then(|s| s.limits), expand) {
+        (Some(limits), _) => Some(limits),
+        (None, Some(fraction)) => Some(expanded_limits(
+            raw_min, raw_max, transform, fraction, axis_name,
+        )?),
+        (None, None) => None,
+    };
+    let target_count = axis_scale.and_then(|s| s.labels.ticks).unwrap_or(8);
+    let (min, max, mut ticks) = match limits {
+        Some((lmin, lmax)) => {
+            transformed_ticks_within(lmin, lmax, transform, target_count, axis_name)?
         }
-    } else {
-        transformed_nice_range(raw_min, raw_max, transform, 8, axis_name)?
+        None => transformed_nice_range(raw_min, raw_max, transform, target_count, axis_name)?,
     };
+    if let Some(breaks) = axis_scale.and_then(|s| s.labels.breaks.as_ref()) {
+        // Breaks are in data units; ones the transform can't place or the axis
+        // doesn't reach are dropped
+        ticks = breaks
+            .iter()
+            .filter_map(|&value| transform.apply(value))
+            .filter(|tick| (min..=max).contains(tick))
+            .collect();
+    }
 
     Ok(Scale {
         domain: (min, max),
@@ -157,6 +202,17 @@ fn build_continuous_scale(
         tick_positions: ticks,
         datetime: None,
         transform,
+        labels: TickLabels::from_spec(axis_scale),
+    })
+}
+
+/// True when the axis runs high to low: `scale_x_reverse()` unless `reverse:` says
+/// otherwise
+fn is_reversed(axis_scale: Option<&AxisScale>) -> bool {
+    axis_scale.is_some_and(|s| {
+        s.labels
+            .reverse
+            .unwrap_or(matches!(s.scale_type, ScaleType::Reverse))
     })
 }
 
@@ -238,6 +294,45 @@ fn transformed_ticks_within(
     }
 }
 
+/// Limits for `expand:`: the data range padded by a fraction of its span, measured
+/// along the transformed axis so a log axis pads by the same share of decades
+fn expanded_limits(
+    raw_min: f64,
+    raw_max: f64,
+    transform: AxisTransform,
+    fraction: f64,
+    axis_name: &str,
+) -> Result<(f64, f64)> {
+    ensure_transform_domain(raw_min, raw_max, transform, axis_name)?;
+    let limits = match transform {
+        AxisTransform::Linear => {
+            let range = DataRange {
+                min: raw_min,
+                max: raw_max,
+            }
+            .expand(fraction);
+            (range.min, range.max)
+        }
+        AxisTransform::Log10 => {
+            let range = DataRange {
+                min: raw_min.log10(),
+                max: raw_max.log10(),
+            }
+            .expand(fraction);
+            (10f64.powf(range.min), 10f64.powf(range.max))
+        }
+        AxisTransform::Sqrt => {
+            let range = DataRange {
+                min: raw_min.sqrt(),
+                max: raw_max.sqrt(),
+            }
+            .expand(fraction);
+            (range.min.max(0.0).powi(2), range.max.powi(2))
+        }
+    };
+    Ok(limits)
+}
+
 fn padded_raw_range(
     raw_min: f64,
     raw_max: f64,
@@ -250,7 +345,14 @@ fn padded_raw_range(
     }
 
     let range = match transform {
-        AxisTransform::Linear => (raw_min - 1.0, raw_max + 1.0),
+        AxisTransform::Linear => {
+            let range = DataRange {
+                min: raw_min,
+                max: raw_max,
+            }
+            .widen_if_flat();
+            (range.min, range.max)
+        }
         AxisTransform::Log10 => {
             ensure_transform_domain(raw_min, raw_max, transform, axis_name)?;
             (raw_min / 10.0, raw_max * 10.0)
@@ -330,17 +432,78 @@ fn build_datetime_scale(
     }))
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone)]
 struct MinMax {
-    min: f64,
-    max: f64,
+    range: DataRange,
     is_categorical: bool,
     categories: Vec<String>,
 }
 
-fn calculate_min_max_x(panel: &crate::ir::PanelData) -> MinMax {
-    let mut min = f64::INFINITY;
-    let mut max = f64::NEG_INFINITY;
+impl MinMax {
+    /// No values were seen (a facet panel whose rows were all dropped)
+    fn is_empty(&self) -> bool {
+        !self.is_categorical && self.range.is_empty()
+    }
+
+    /// Categorical axes place categories at indices 0..N-1
+    fn categorical(categories: Vec<String>) -> Self {
+        MinMax {
+            range: DataRange {
+                min: 0.0,
+                max: (categories.len().max(1) - 1) as f64,
+            },
+            is_categorical: true,
+            categories,
+        }
+    }
+}
+
+/// Transform drops non-finite cells as missing (or rejects them under strict), so one
+/// reaching the ranges is a bug. NaN would slip past the min/max comparisons and an
+/// infinity would swallow the axis, so fail with where it came from instead.
+fn check_finite(panel_idx: usize, panel: &crate::ir::PanelData) -> Result<()> {
+    for (layer_idx, layer) in panel.layers.iter().enumerate() {
+        for group in &layer.groups {
+            let fields: [(&str, &[f64]); 6] = [
+                ("x", &group.x),
+                ("y", &group.y),
+                ("y_start", &group.y_start),
+                ("y_min", &group.y_min),
+                ("y_max", &group.y_max),
+                ("heatmap_y_positions", &group.heatmap_y_positions),
+            ];
+            for (field, values) in fields {
+                if let Some(value) = values.iter().find(|v| !v.is_finite()) {
+                    return Err(anyhow!(
+                        "Internal error: non-finite {} value {} in layer {} (group '{}') of panel {} reached the scales",
+                        field,
+                        value,
+                        layer_idx + 1,
+                        group.key,
+                        panel_idx + 1
+                    ));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The panel's `annotate()` notes that widen its axes (`expand: true`), with each
+/// note's anchor and its nudge from there
+fn expanding_notes<'a>(
+    panel: &'a crate::ir::PanelData,
+    annotations: &'a [Annotation],
+) -> impl Iterator<Item = (&'a Annotation, &'a crate::ir::PanelAnnotation)> {
+    panel
+        .annotations
+        .iter()
+        .map(|placed| (&annotations[placed.index], placed))
+        .filter(|(note, _)| note.expand)
+}
+
+fn calculate_min_max_x(panel: &crate::ir::PanelData, spec: &ResolvedSpec) -> MinMax {
+    let mut range = DataRange::EMPTY;
     let mut categories = Vec::new();
     let mut is_cat = false;
 
@@ -357,40 +520,53 @@ fn calculate_min_max_x(panel: &crate::ir::PanelData) -> MinMax {
             let x_padding = match &group.style {
                 crate::ir::RenderStyle::ErrorBar { width, .. } => width / 2.0,
                 crate::ir::RenderStyle::CrossBar { width, .. } => width / 2.0,
+                crate::ir::RenderStyle::Heatmap(_) => group.heatmap_cell_width / 2.0,
+                // Bars on a continuous axis reach half their width past their x
+                crate::ir::RenderStyle::Bar(style) if group.x_categories.is_none() => {
+                    style.width.unwrap_or(0.0) / 2.0
+                }
                 _ => 0.0,
             };
 
+            // Pie slices are drawn on the unit circle
+            if matches!(group.style, crate::ir::RenderStyle::Pie { .. }) {
+                range = range.merge(DataRange {
+                    min: -1.0,
+                    max: 1.0,
+                });
+            }
+
             for &val in &group.x {
-                let padded_min = val - x_padding;
-                let padded_max = val + x_padding;
-                if padded_min < min {
-                    min = padded_min;
-                }
-                if padded_max > max {
-                    max = padded_max;
-                }
+                range.include(val - x_padding);
+                range.include(val + x_padding);
             }
         }
     }
 
     if is_cat {
-        // For categorical, range is determined by number of categories
-        // Indices are 0..N-1
-        min = 0.0;
-        max = (categories.len().max(1) - 1) as f64;
+        return MinMax::categorical(categories);
+    }
+
+    for (note, placed) in expanding_notes(panel, &spec.annotations) {
+        range.include(placed.x);
+        range.include(placed.x + note.nudge_x);
+    }
+    // A band's given sides are on the axis; the others follow it
+    for band in &panel.shades {
+        for x in band.xmin.into_iter().chain(band.xmax) {
+            range.include(x);
+        }
     }
 
     MinMax {
-        min,
-        max,
-        is_categorical: is_cat,
+        range,
+        is_categorical: false,
         categories,
     }
 }
 
-fn calculate_min_max_y(panel: &crate::ir::PanelData) -> MinMax {
-    let mut min = f64::INFINITY;
-    let mut max = f64::NEG_INFINITY;
+fn calculate_min_max_y(panel: &crate::ir::PanelData, spec: &ResolvedSpec) -> MinMax {
+    let mut range = DataRange::EMPTY;
 
     // Helper to include 0 for bar charts
     let mut has_bars = false;
@@ -412,104 +588,60 @@ fn calculate_min_max_y(panel: &crate::ir::PanelData) -> MinMax {
                 }
             }
 
-            // For heatmap, use y_positions for range
+            // For heatmap, use y_positions for range, including the cell extent
             if matches!(group.style, crate::ir::RenderStyle::Heatmap(_)) {
-                for &val in &group.heatmap_y_positions {
-                    if val < min {
-                        min = val;
-                    }
-                    if val > max {
-                        max = val;
-                    }
-                }
-                // Include cell extent
+                let positions = DataRange::of(group.heatmap_y_positions.iter().copied());
+                range = range.merge(positions);
                 let half_h = group.heatmap_cell_height / 2.0;
-                if !group.heatmap_y_positions.is_empty() {
-                    let ext_min = group
-                        .heatmap_y_positions
-                        .iter()
-                        .fold(f64::INFINITY, |a, &b| a.min(b))
-                        - half_h;
-                    let ext_max = group
-                        .heatmap_y_positions
-                        .iter()
-                        .fold(f64::NEG_INFINITY, |a, &b| a.max(b))
-                        + half_h;
-                    if ext_min < min {
-                        min = ext_min;
-                    }
-                    if ext_max > max {
-                        max = ext_max;
-                    }
+                if !positions.is_empty() {
+                    range = range.merge(DataRange {
+                        min: positions.min - half_h,
+                        max: positions.max + half_h,
+                    });
                 }
                 continue;
             }
 
-            // Check y (and y_start for stacked)
-            for &val in &group.y {
-                if val < min {
-                    min = val;
-                }
-                if val > max {
-                    max = val;
-                }
-            }
-            for &val in &group.y_start {
-                if val < min {
-                    min = val;
-                }
-                if val > max {
-                    max = val;
-                }
-            }
-            for &val in &group.y_min {
-                if val < min {
-                    min = val;
-                }
-                if val > max {
-                    max = val;
-                }
+            if matches!(group.style, crate::ir::RenderStyle::Pie { .. }) {
+                range = range.merge(DataRange {
+                    min: -1.0,
+                    max: 1.0,
+                });
             }
-            for &val in &group.y_max {
-                if val < min {
-                    min = val;
-                }
-                if val > max {
-                    max = val;
-                }
+
+            // Check y (and y_start for stacked)
+            for values in [&group.y, &group.y_start, &group.y_min, &group.y_max] {
+                range = range.merge(DataRange::of(values.iter().copied()));
             }
             for outlier_set in &group.outliers {
-                for &val in outlier_set {
-                    if val < min {
-                        min = val;
-                    }
-                    if val > max {
-                        max = val;
-                    }
-                }
+                range = range.merge(DataRange::of(outlier_set.iter().copied()));
             }
         }
     }
 
     if has_bars {
         // Bar charts always include 0
-        if min > 0.0 {
-            min = 0.0;
-        }
-        if max < 0.0 {
-            max = 0.0;
-        }
+        range = range.include_zero();
     }
 
     if is_cat {
-        min = 0.0;
-        max = (categories.len().max(1) - 1) as f64;
+        return MinMax::categorical(categories);
+    }
+
+    for (note, placed) in expanding_notes(panel, &spec.annotations) {
+        range.include(placed.y);
+        range.include(placed.y + note.nudge_y);
+    }
+    for band in &panel.shades {
+        let shade = &spec.shades[band.index];
+        for y in shade.ymin.into_iter().chain(shade.ymax) {
+            range.include(y);
+        }
     }
 
     MinMax {
-        min,
-        max,
-        is_categorical: is_cat,
+        range,
+        is_categorical: false,
         categories,
     }
 }
@@ -519,19 +651,13 @@ where
     I: Iterator<Item = &'a MinMax>,
 {
     let mut global = MinMax {
-        min: f64::INFINITY,
-        max: f64::NEG_INFINITY,
+        range: DataRange::EMPTY,
         is_categorical: false,
         categories: Vec::new(),
     };
 
     for local in iter {
-        if local.min < global.min {
-            global.min = local.min;
-        }
-        if local.max > global.max {
-            global.max = local.max;
-        }
+        global.range = global.range.merge(local.range);
         if local.is_categorical {
             global.is_categorical = true;
             // Naive merge: if one has categories, take them.
@@ -542,12 +668,7 @@ where
         }
     }
 
-    // Handle empty case
-    if global.min == f64::INFINITY {
-        global.min = 0.0;
-        global.max = 1.0;
-    }
-
+    global.range = global.range.or_unit_if_empty();
     global
 }
 
@@ -577,8 +698,12 @@ fn nice_step(data_range: f64, target_count: usize) -> f64 {
 /// Returns ((nice_min, nice_max), tick_positions).
 fn nice_range(data_min: f64, data_max: f64, target_count: usize) -> ((f64, f64), Vec<f64>) {
     if data_min == data_max {
-        let ticks = vec![data_min - 1.0, data_min, data_min + 1.0];
-        return ((data_min - 1.0, data_max + 1.0), ticks);
+        let range = DataRange {
+            min: data_min,
+            max: data_max,
+        }
+        .widen_if_flat();
+        return ((range.min, range.max), vec![range.min, data_min, range.max]);
     }
 
     let step = nice_step(data_max - data_min, target_count);
@@ -597,7 +722,7 @@ fn nice_range(data_min: f64, data_max: f64, target_count: usize) -> ((f64, f64),
 }
 
 /// Compute nice tick positions within user-specified limits (no domain expansion).
-fn nice_ticks_within(min: f64, max: f64, target_count: usize) -> Vec<f64> {
+pub(crate) fn nice_ticks_within(min: f64, max: f64, target_count: usize) -> Vec<f64> {
     if min == max {
         return vec![min];
     }
@@ -629,17 +754,22 @@ pub fn format_nice_number(v: f64) -> String {
 mod tests {
     use super::*;
     use crate::graph::LineStyle;
-    use crate::ir::{AxisTransform, FacetLayout, GroupData, LayerData, PanelData, RenderStyle};
+    use crate::ir::{
+        AxisTransform, FacetLayout, GroupAesthetic, GroupData, LayerData, PanelData, RenderStyle,
+    };
 
     fn make_render_data(x: Vec<f64>, y: Vec<f64>) -> RenderData {
         RenderData {
             panels: vec![PanelData {
                 index: 0,
+                annotations: Vec::new(),
+                shades: Vec::new(),
                 layers: vec![LayerData {
                     groups: vec![GroupData {
                         key: "A".to_string(),
+                        key_parts: vec!["A".to_string()],
                         x,
-                        y: y,
+                        y,
                         y_start: vec![],
                         y_min: vec![],
                         y_max: vec![],
@@ -656,15 +786,31 @@ mod tests {
                         heatmap_cell_height: 0.0,
                         x_categories: None,
                         y_categories: None,
+                        labels: vec![],
+                        point_alphas: vec![],
+                        color_values: vec![],
+                        size_values: vec![],
+                        line_breaks: vec![],
                         style: RenderStyle::Line(LineStyle::default()),
                     }],
+                    color_domain: None,
+                    size_domain: None,
+                    skipped_rows: 0,
+                    filter_rejected_rows: 0,
+                    filter_matched_rows: None,
+                    group_aesthetics: vec![vec![GroupAesthetic::Color]],
+                    group_levels: vec![vec!["A".to_string()]],
                 }],
             }],
             facet_layout: FacetLayout {
                 nrow: 1,
                 ncol: 1,
                 panel_titles: vec![],
+                row_titles: vec![],
+                panel_rows: vec![],
             },
+            filter_rejected_rows: 0,
+            row_limits: Vec::new(),
         }
     }
 
@@ -677,9 +823,30 @@ mod tests {
             theme: crate::parser::ast::Theme::default(),
             x_scale_spec: None,
             y_scale_spec: None,
+            color_scale: crate::parser::ast::ColorScale::default(),
+            color_manual: None,
+            stages: Vec::new(),
+            annotations: Vec::new(),
+            shades: Vec::new(),
         }
     }
 
+    #[test]
+    fn test_scale_rejects_non_finite_values() {
+        let spec = make_resolved_spec();
+        let data = make_render_data(vec![0.0, 1.0], vec![f64::NAN, 2.0]);
+        let err = build_scales(&data, &spec).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Internal error: non-finite y value NaN in layer 1 (group 'A') of panel 1 reached the scales"
+        );
+        let data = make_render_data(vec![0.0, f64::INFINITY], vec![1.0, 2.0]);
+        assert!(build_scales(&data, &spec)
+            .unwrap_err()
+            .to_string()
+            .contains("non-finite x value inf"));
+    }
+
     #[test]
     fn test_scale_continuous() {
         let data = make_render_data(vec![0.0, 10.0], vec![0.0, 100.0]);
@@ -733,6 +900,8 @@ mod tests {
             scale_type: ScaleType::Log10,
             limits: None,
             datetime: None,
+            order: None,
+            labels: Default::default(),
         });
 
         let scales = build_scales(&data, &spec).unwrap();
@@ -751,6 +920,8 @@ mod tests {
             scale_type: ScaleType::Log10,
             limits: None,
             datetime: None,
+            order: None,
+            labels: Default::default(),
         });
 
         let err = build_scales(&data, &spec).unwrap_err();
@@ -765,6 +936,8 @@ mod tests {
             scale_type: ScaleType::Sqrt,
             limits: None,
             datetime: None,
+            order: None,
+            labels: Default::default(),
         });
 
         let scales = build_scales(&data, &spec).unwrap();
@@ -776,6 +949,58 @@ mod tests {
         assert!(panel.x.tick_positions.contains(&10.0));
     }
 
+    #[test]
+    fn test_scale_expand_pads_by_a_fraction_of_the_span() {
+        let data = make_render_data(vec![3.0, 47.0], vec![10.0, 1000.0]);
+        let mut spec = make_resolved_spec();
+        let expand = |fraction: f64| crate::parser::ast::AxisScale {
+            labels: crate::parser::ast::AxisLabels {
+                expand: Some(fraction),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        // Without expand the axis snaps outward to nice boundaries
+        let panel = &build_scales(&data, &spec).unwrap().panels[0];
+        assert_eq!(panel.x.domain, (0.0, 50.0));
+
+        spec.x_scale_spec = Some(expand(0.0));
+        let panel = &build_scales(&data, &spec).unwrap().panels[0];
+        assert_eq!(panel.x.domain, (3.0, 47.0));
+        assert!(panel
+            .x
+            .tick_positions
+            .iter()
+            .all(|t| (3.0..=47.0).contains(t)));
+
+        spec.x_scale_spec = Some(expand(0.25));
+        let panel = &build_scales(&data, &spec).unwrap().panels[0];
+        assert_eq!(panel.x.domain, (-8.0, 58.0));
+
+        // Limits win over expand
+        let mut limited = expand(0.25);
+        limited.limits = Some((0.0, 100.0));
+        spec.x_scale_spec = Some(limited);
+        let panel = &build_scales(&data, &spec).unwrap().panels[0];
+        assert_eq!(panel.x.domain, (0.0, 100.0));
+
+        // On a log axis the padding is a share of the decades shown
+        spec.y_scale_spec = Some(crate::parser::ast::AxisScale {
+            scale_type: ScaleType::Log10,
+            ..expand(0.5)
+        });
+        let panel = &build_scales(&data, &spec).unwrap().panels[0];
+        assert_eq!(panel.y.domain, (0.0, 4.0));
+
+        spec.y_scale_spec = Some(expand(-0.1));
+        let err = build_scales(&data, &spec).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "scale_y() expand must be at least 0, got -0.1"
+        );
+    }
+
     #[test]
     fn test_nice_step_small_range() {
         // Range 10, target 8 => rough_step 1.25 => magnitude 1, residual 1.25 => nice 1 => step 1
@@ -863,4 +1088,289 @@ mod tests {
         assert_eq!(format_nice_number(2.5), "2.5");
         assert_eq!(format_nice_number(0.25), "0.25");
     }
+
+    #[test]
+    fn test_scale_stacked_bars_cover_cumulative_tops() {
+        use crate::data::PlotData;
+        use crate::parser::parse_plot_spec;
+        use crate::{resolve, transform};
+
+        // The largest single value is 70, but category A stacks to 60 + 70 = 130
+        let data = PlotData::new(
+            vec!["x".to_string(), "y".to_string(), "g".to_string()],
+            [
+                ["A", "60", "p"],
+                ["A", "70", "q"],
+                ["B", "70", "p"],
+                ["B", "10", "q"],
+            ]
+            .iter()
+            .map(|row| row.iter().map(|s| s.to_string()).collect())
+            .collect(),
+        );
+        let (_, spec) =
+            parse_plot_spec(r#"aes(x: x, y: y, color: g) | bar(position: "stack")"#).unwrap();
+        let resolved = resolve::resolve_plot_aesthetics(&spec, &data).unwrap();
+        let render_data =
+            transform::apply_transformations(&resolved, &data, &crate::RenderOptions::default())
+                .unwrap();
+        let scales = build_scales(&render_data, &resolved).unwrap();
+
+        let y = &scales.panels[0].y;
+        assert!(y.domain.0 <= 0.0);
+        assert!(
+            y.domain.1 >= 130.0,
+            "y domain {:?} clips the stack",
+            y.domain
+        );
+        assert!(y.tick_positions.last().is_some_and(|&top| top >= 130.0));
+    }
+
+    #[test]
+    fn test_scale_continuous_bars_share_a_numeric_axis() {
+        use crate::data::PlotData;
+        use crate::parser::parse_plot_spec;
+        use crate::{resolve, transform};
+
+        let data = PlotData::new(
+            vec!["year".to_string(), "y".to_string()],
+            [["2001", "50"], ["2002", "70"], ["2004", "60"]]
+                .iter()
+                .map(|row| row.iter().map(|s| s.to_string()).collect())
+                .collect(),
+        );
+        let (_, spec) =
+            parse_plot_spec("aes(x: year, y: y) | bar(x_continuous: true) | line()").unwrap();
+        let resolved = resolve::resolve_plot_aesthetics(&spec, &data).unwrap();
+        let render_data =
+            transform::apply_transformations(&resolved, &data, &crate::RenderOptions::default())
+                .unwrap();
+
+        // Half a 0.8-year bar past the first and last year
+        let x = calculate_min_max_x(&render_data.panels[0], &resolved);
+        assert!(!x.is_categorical);
+        assert!((x.range.min - 2000.6).abs() < 1e-9 && (x.range.max - 2004.4).abs() < 1e-9);
+
+        let scales = build_scales(&render_data, &resolved).unwrap();
+        assert!(!scales.panels[0].x.is_categorical);
+        assert!(scales.panels[0].y.domain.0 <= 0.0);
+    }
+
+    #[test]
+    fn test_scale_free_facets_without_rows_use_the_full_range() {
+        use crate::data::PlotData;
+        use crate::parser::parse_plot_spec;
+        use crate::{resolve, transform};
+
+        // Every y in facet "b" is missing, so its layers draw nothing
+        let data = PlotData::new(
+            vec!["x".to_string(), "y".to_string(), "g".to_string()],
+            [
+                ["1", "2", "a"],
+                ["2", "4", "a"],
+                ["1", "NA", "b"],
+                ["3", "NA", "b"],
+                ["5", "80", "c"],
+                ["9", "90", "c"],
+            ]
+            .iter()
+            .map(|row| row.iter().map(|s| s.to_string()).collect())
+            .collect(),
+        );
+        for mode in ["free", "free_x", "free_y"] {
+            let dsl = format!(
+                r#"aes(x: x, y: y) | line() | point() | facet_wrap(by: g, scales: "{}")"#,
+                mode
+            );
+            let (_, spec) = parse_plot_spec(&dsl).unwrap();
+            let resolved = resolve::resolve_plot_aesthetics(&spec, &data).unwrap();
+            let render_data = transform::apply_transformations(
+                &resolved,
+                &data,
+                &crate::RenderOptions::default(),
+            )
+            .unwrap();
+            let scales = build_scales(&render_data, &resolved).unwrap();
+
+            let empty = &scales.panels[1];
+            let (x, y) = (empty.x.domain, empty.y.domain);
+            assert!(x.0 <= 1.0 && x.1 >= 9.0, "{}: x {:?}", mode, x);
+            assert!(y.0 <= 0.0 && y.1 >= 90.0, "{}: y {:?}", mode, y);
+        }
+    }
+
+    #[test]
+    fn test_scale_facet_grid_frees_y_per_row_and_x_per_column() {
+        use crate::data::PlotData;
+        use crate::parser::parse_plot_spec;
+        use crate::{resolve, transform};
+
+        // Row "lo" stays under 10 and row "hi" reaches 100; column "b" spans wider x
+        let data = PlotData::new(
+            vec![
+                "x".to_string(),
+                "y".to_string(),
+                "r".to_string(),
+                "c".to_string(),
+            ],
+            [
+                ["1", "2", "lo", "a"],
+                ["2", "9", "lo", "b"],
+                ["1", "50", "hi", "a"],
+                ["40", "100", "hi", "b"],
+            ]
+            .iter()
+            .map(|row| row.iter().map(|s| s.to_string()).collect())
+            .collect(),
+        );
+        let (_, spec) = parse_plot_spec(
+            r#"aes(x: x, y: y) | point() | facet_grid(rows: r, cols: c, scales: "free")"#,
+        )
+        .unwrap();
+        let resolved = resolve::resolve_plot_aesthetics(&spec, &data).unwrap();
+        let render_data =
+            transform::apply_transformations(&resolved, &data, &crate::RenderOptions::default())
+                .unwrap();
+        let scales = build_scales(&render_data, &resolved).unwrap();
+
+        // Panels are row-major: (hi, a), (hi, b), (lo, a), (lo, b)
+        let y = |i: usize| scales.panels[i].y.domain;
+        let x = |i: usize| scales.panels[i].x.domain;
+        assert_eq!(y(0), y(1));
+        assert_eq!(y(2), y(3));
+        assert_ne!(y(0), y(2));
+        assert_eq!(x(0), x(2));
+        assert_eq!(x(1), x(3));
+        assert_ne!(x(0), x(1));
+    }
+
+    #[test]
+    fn test_tick_format_named_formats() {
+        use crate::ir::TickFormat;
+        let comma = TickFormat::parse("comma").unwrap();
+        assert_eq!(comma.format(1_500_000.0), "1,500,000");
+        assert_eq!(comma.format(-1234.5), "-1,234.5");
+        assert_eq!(comma.format(999.0), "999");
+
+        let percent = TickFormat::parse("percent").unwrap();
+        assert_eq!(percent.format(0.25), "25%");
+        assert_eq!(percent.format(0.15), "15%");
+
+        let si = TickFormat::parse("si").unwrap();
+        assert_eq!(si.format(1_500_000.0), "1.5M");
+        assert_eq!(si.format(2500.0), "2.5k");
+        assert_eq!(si.format(-3e9), "-3G");
+        assert_eq!(si.format(12.0), "12");
+    }
+
+    #[test]
+    fn test_tick_format_printf_patterns() {
+        use crate::ir::TickFormat;
+        assert_eq!(TickFormat::parse("%.2f").unwrap().format(1.23456), "1.23");
+        assert_eq!(TickFormat::parse("$%d").unwrap().format(2.6), "$3");
+        assert_eq!(TickFormat::parse("%.1f%%").unwrap().format(12.34), "12.3%");
+        assert_eq!(TickFormat::parse("%.0f kg").unwrap().format(70.0), "70 kg");
+        // Unsupported patterns fall back to the plain labels
+        assert_eq!(TickFormat::parse("%x"), None);
+        assert_eq!(TickFormat::parse("dollars"), None);
+    }
+
+    #[test]
+    fn test_tick_labels_truncate_long_categories() {
+        use crate::ir::TickLabels;
+        let labels = TickLabels {
+            max_len: Some(6),
+            ..Default::default()
+        };
+        assert_eq!(labels.category("Chicago"), "Chica…");
+        assert_eq!(labels.category("Boston"), "Boston");
+        assert_eq!(TickLabels::default().category("Chicago"), "Chicago");
+    }
+
+    #[test]
+    fn test_scale_carries_tick_label_options() {
+        let data = make_render_data(vec![1.0, 1000.0], vec![1.0, 2.0]);
+        let mut spec = make_resolved_spec();
+        let mut y_scale = crate::parser::ast::AxisScale::default();
+        y_scale.labels.format = Some("comma".to_string());
+        y_scale.labels.rotate = Some(90.0);
+        spec.y_scale_spec = Some(y_scale);
+
+        let scales = build_scales(&data, &spec).unwrap();
+        let panel = &scales.panels[0];
+        assert_eq!(panel.y.labels.format, Some(crate::ir::TickFormat::Comma));
+        assert_eq!(panel.y.labels.angle, Some(90.0));
+        assert_eq!(panel.x.labels, crate::ir::TickLabels::default());
+    }
+
+    #[test]
+    fn test_scale_ticks_and_breaks() {
+        let data = make_render_data(vec![3.0, 97.0], vec![10.0, 1000.0]);
+        let mut spec = make_resolved_spec();
+        let options = |labels: crate::parser::ast::AxisLabels| crate::parser::ast::AxisScale {
+            labels,
+            ..Default::default()
+        };
+        let default_ticks = build_scales(&data, &spec).unwrap().panels[0]
+            .x
+            .tick_positions
+            .len();
+
+        spec.x_scale_spec = Some(options(crate::parser::ast::AxisLabels {
+            ticks: Some(2),
+            ..Default::default()
+        }));
+        let panel = &build_scales(&data, &spec).unwrap().panels[0];
+        assert!(panel.x.tick_positions.len() < default_ticks);
+
+        // Breaks replace the ticks; ones past the axis are dropped
+        spec.x_scale_spec = Some(options(crate::parser::ast::AxisLabels {
+            breaks: Some(vec![-50.0, 0.0, 25.0, 50.0, 75.0, 100.0, 150.0]),
+            ..Default::default()
+        }));
+        let panel = &build_scales(&data, &spec).unwrap().panels[0];
+        assert_eq!(panel.x.range, (0.0, 100.0));
+        assert_eq!(panel.x.tick_positions, vec![0.0, 25.0, 50.0, 75.0, 100.0]);
+
+        // On a log axis they're placed in transformed space, and unplaceable ones dropped
+        spec.y_scale_spec = Some(crate::parser::ast::AxisScale {
+            scale_type: ScaleType::Log10,
+            ..options(crate::parser::ast::AxisLabels {
+                breaks: Some(vec![-1.0, 10.0, 100.0]),
+                ..Default::default()
+            })
+        });
+        let panel = &build_scales(&data, &spec).unwrap().panels[0];
+        assert_eq!(panel.y.tick_positions, vec![1.0, 2.0]);
+    }
+
+    #[test]
+    fn test_scale_reverse_flag_swaps_the_range() {
+        let data = make_render_data(vec![1.0, 9.0], vec![3.0, 47.0]);
+        let mut spec = make_resolved_spec();
+        let plain = build_scales(&data, &spec).unwrap().panels.remove(0);
+        let reverse = |flag: bool, scale_type: ScaleType| crate::parser::ast::AxisScale {
+            scale_type,
+            labels: crate::parser::ast::AxisLabels {
+                reverse: Some(flag),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        spec.y_scale_spec = Some(reverse(true, ScaleType::Linear));
+        let panel = build_scales(&data, &spec).unwrap().panels.remove(0);
+        assert_eq!(panel.y.range, (plain.y.range.1, plain.y.range.0));
+        // Ticks keep the data's values; only the direction changes
+        assert_eq!(panel.y.tick_positions, plain.y.tick_positions);
+        assert_eq!(panel.x.range, plain.x.range);
+
+        // It rides on a log axis too, and `false` undoes scale_y_reverse()
+        spec.y_scale_spec = Some(reverse(true, ScaleType::Log10));
+        let panel = build_scales(&data, &spec).unwrap().panels.remove(0);
+        assert_eq!(panel.y.range, (2.0, 0.0));
+        spec.y_scale_spec = Some(reverse(false, ScaleType::Reverse));
+        let panel = build_scales(&data, &spec).unwrap().panels.remove(0);
+        assert_eq!(panel.y.range, plain.y.range);
+    }
 }
@@ -0,0 +1,98 @@
+// "Did you mean" suggestions for misspelled argument and column names
+
+/// Closest candidate within a small edit distance, compared case-insensitively.
+/// Ties go to the earliest candidate; names too short to judge get no suggestion.
+pub fn closest_match<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let name = name.to_lowercase();
+    let max_distance = 2.min(name.chars().count().saturating_sub(1));
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(&name, &candidate.to_lowercase()), candidate))
+        .filter(|&(distance, _)| distance <= max_distance)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Levenshtein distance, counting an adjacent transposition as one edit
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edit_distance_counts_single_edits() {
+        assert_eq!(edit_distance("width", "width"), 0);
+        assert_eq!(edit_distance("colour", "color"), 1); // deletion
+        assert_eq!(edit_distance("temprature", "temperature"), 1); // insertion
+        assert_eq!(edit_distance("sise", "size"), 1); // substitution
+        assert_eq!(edit_distance("widht", "width"), 1); // transposition
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn closest_match_picks_nearest_candidate() {
+        let columns = ["time", "temperature", "humidity"];
+        assert_eq!(
+            closest_match("temprature", columns.iter().copied()),
+            Some("temperature")
+        );
+        assert_eq!(closest_match("tmie", columns.iter().copied()), Some("time"));
+        // Nearest wins over merely close
+        assert_eq!(
+            closest_match("colr", ["colour", "color"].iter().copied()),
+            Some("color")
+        );
+        assert_eq!(
+            closest_match("sise", ["width", "size"].iter().copied()),
+            Some("size")
+        );
+    }
+
+    #[test]
+    fn closest_match_ignores_case() {
+        let columns = ["Temperature", "Time"];
+        assert_eq!(
+            closest_match("TEMPRATURE", columns.iter().copied()),
+            Some("Temperature")
+        );
+    }
+
+    #[test]
+    fn closest_match_rejects_distant_and_short_names() {
+        let columns = ["time", "temperature"];
+        assert_eq!(closest_match("pressure", columns.iter().copied()), None);
+        // A one-letter name is one edit from every other one-letter name
+        assert_eq!(closest_match("x", ["y", "g"].iter().copied()), None);
+        assert_eq!(
+            closest_match("ab", ["ax", "zz"].iter().copied()),
+            Some("ax")
+        );
+    }
+}
@@ -91,6 +91,10 @@ pub struct ResolvedTheme {
     pub legend_text: ResolvedText,
     pub legend_margin: f64,
     pub legend_key_size: f64,
+    /// Space around each panel in pixels; None keeps the renderer's default
+    pub margin: Option<f64>,
+    /// Major grid lines on a categorical axis sit between the category slots
+    pub grid_separators: bool,
     /// True if user explicitly customized theme (vs using all defaults)
     pub has_customization: bool,
 }
@@ -240,7 +244,9 @@ impl Theme {
             || self.legend_background != ThemeElement::Inherit
             || self.legend_text != ThemeElement::Inherit
             || self.legend_margin.is_some()
-            || self.legend_key_size.is_some();
+            || self.legend_key_size.is_some()
+            || self.margin.is_some()
+            || self.grid_separators.is_some();
 
         // Resolve root element defaults
         let base_text = self.resolve_base_text();
@@ -259,7 +265,8 @@ impl Theme {
         let axis_ticks = self.resolve_optional_line(&self.axis_ticks, &base_line);
 
         // Grid lines have their own inheritance: panel_grid_minor -> panel_grid_major -> line
-        let panel_grid_major = self.resolve_optional_line(&self.panel_grid_major, &base_line);
+        let grid_line = self.resolve_grid_base(&base_line);
+        let panel_grid_major = self.resolve_optional_line(&self.panel_grid_major, &grid_line);
         let panel_grid_minor = self.resolve_grid_minor(&panel_grid_major);
         let legend_background = self.resolve_legend_background(&panel_background, &legend_text);
 
@@ -277,6 +284,8 @@ impl Theme {
             legend_text,
             legend_margin: self.legend_margin.unwrap_or(10.0),
             legend_key_size: self.legend_key_size.unwrap_or(30.0),
+            margin: self.margin,
+            grid_separators: self.grid_separators.unwrap_or(false),
             has_customization,
         }
     }
@@ -299,6 +308,18 @@ impl Theme {
         base
     }
 
+    /// Grid lines follow an explicit root `line`; otherwise they default to light gray
+    /// rather than the black used for axes
+    fn resolve_grid_base(&self, base_line: &ResolvedLine) -> ResolvedLine {
+        match &self.line {
+            ThemeElement::Inherit => ResolvedLine {
+                color: RGBColor(204, 204, 204),
+                ..base_line.clone()
+            },
+            _ => base_line.clone(),
+        }
+    }
+
     /// Resolve base rect style from root `rect` element
     fn resolve_base_rect(&self) -> ResolvedRect {
         let mut base = ResolvedRect::default();
@@ -362,7 +383,7 @@ impl Theme {
                 apply_line_overrides(&mut resolved, l);
                 // Minor grid typically thinner
                 if l.width.is_none() {
-                    resolved.width = resolved.width * 0.5;
+                    resolved.width *= 0.5;
                 }
                 Some(resolved)
             }
@@ -506,9 +527,11 @@ mod tests {
 
     #[test]
     fn test_resolve_with_blank_elements() {
-        let mut theme = Theme::default();
-        theme.axis_line = ThemeElement::Blank;
-        theme.axis_ticks = ThemeElement::Blank;
+        let theme = Theme {
+            axis_line: ThemeElement::Blank,
+            axis_ticks: ThemeElement::Blank,
+            ..Default::default()
+        };
 
         let resolved = theme.resolve();
 
@@ -518,13 +541,15 @@ mod tests {
 
     #[test]
     fn test_resolve_with_custom_text() {
-        let mut theme = Theme::default();
-        theme.plot_title = ThemeElement::Text(ElementText {
-            size: Some(24.0),
-            face: Some("bold".to_string()),
-            color: Some("#FF0000".to_string()),
+        let theme = Theme {
+            plot_title: ThemeElement::Text(ElementText {
+                size: Some(24.0),
+                face: Some("bold".to_string()),
+                color: Some("#FF0000".to_string()),
+                ..Default::default()
+            }),
             ..Default::default()
-        });
+        };
 
         let resolved = theme.resolve();
 
@@ -535,12 +560,14 @@ mod tests {
 
     #[test]
     fn test_inheritance_from_root() {
-        let mut theme = Theme::default();
         // Set root text color
-        theme.text = ThemeElement::Text(ElementText {
-            color: Some("blue".to_string()),
+        let theme = Theme {
+            text: ThemeElement::Text(ElementText {
+                color: Some("blue".to_string()),
+                ..Default::default()
+            }),
             ..Default::default()
-        });
+        };
 
         let resolved = theme.resolve();
 
@@ -549,4 +576,28 @@ mod tests {
         // plot_title should also inherit
         assert_eq!(resolved.plot_title.color, RGBColor(0, 0, 255));
     }
+
+    #[test]
+    fn test_inherited_grid_defaults_to_light_gray() {
+        let theme = Theme {
+            text: ThemeElement::Text(ElementText {
+                size: Some(14.0),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let major = theme.resolve().panel_grid_major.unwrap();
+        assert_eq!(major.color, RGBColor(204, 204, 204));
+
+        // An explicit root line still flows into the grid
+        let theme = Theme {
+            line: ThemeElement::Line(ElementLine {
+                color: Some("red".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let major = theme.resolve().panel_grid_major.unwrap();
+        assert_eq!(major.color, RGBColor(255, 0, 0));
+    }
 }
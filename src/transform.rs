@@ -1,745 +1,2737 @@
-use crate::data::PlotData;
+use crate::analysis::{x_axis_kind, XAxisKind};
+use crate::collate::LabelOrder;
+use crate::compiler::DEFAULT_BAR_WIDTH;
+use crate::csv_reader::{is_missing, is_non_finite, numeric_value, Column, ColumnStore};
+use crate::data::{DataSources, PlotData};
 use crate::datetime::parse_datetime_value;
+use crate::error::GramGraphError;
 use crate::graph::{
-    BarStyle, DensityStyle, HeatmapStyle, LineStyle, PointStyle, RibbonStyle, ViolinStyle,
+    BarStyle, DensityStyle, HeatmapStyle, LabelStyle, LineStyle, PointStyle, RibbonStyle,
+    ViolinStyle,
+};
+use crate::ir::{
+    FacetLayout, GroupAesthetic, GroupData, LayerData, PanelAnnotation, PanelData, PanelShade,
+    RenderData, RenderStyle, RowLimit,
 };
-use crate::ir::{FacetLayout, GroupData, LayerData, PanelData, RenderData, RenderStyle};
 use crate::ir::{ResolvedAesthetics, ResolvedFacet, ResolvedLayer, ResolvedSpec};
-use crate::palette::{AlphaPalette, ColorPalette, ShapePalette, SizePalette};
-use crate::parser::ast::{AxisScale, BarPosition, Layer, ScaleType, Stat};
+use crate::palette::{AlphaPalette, ColorPalette, LinetypePalette, ShapePalette, SizePalette};
+use crate::parser::ast::{
+    Aggregate, AnnotationX, ArithExpr, ArithFunction, ArithOp, AxisScale, BarPosition,
+    CategoryOrder, ColorScale, ColumnRef, CompareOp, DataStage, FacetCut, FacetScales,
+    FadeDirection, FilterExpr, FilterValue, Layer, ManualColorScale, Mutation, PieLayer,
+    PivotLonger, PointPosition, Sample, ScaleType, SortKey, Stat, Summarize, SummaryFunction,
+};
+use crate::range::DataRange;
+use crate::RenderOptions;
 use anyhow::{anyhow, Context, Result};
-use std::collections::{HashMap, HashSet};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// Raw per-group columns extracted from the data: GroupKey -> (RawX, RawY, RawYMin, RawYMax)
+type RawGroups = HashMap<String, (Vec<String>, Vec<f64>, Vec<f64>, Vec<f64>)>;
+
+/// Main entry point: Transform resolved spec and CSV data into renderable data.
+/// Unless `options.strict` is set, rows missing a needed cell are skipped and counted
+/// per layer. A styled grouping column with more than `options.max_groups` values fails.
+pub fn apply_transformations(
+    spec: &ResolvedSpec,
+    data: &PlotData,
+    options: &RenderOptions,
+) -> Result<RenderData> {
+    apply_transformations_with_sources(spec, data, &DataSources::new(), options)
+}
+
+/// `apply_transformations` for a spec whose layers may read other tables with `data:`.
+/// Those layers read every row of their table (the data stages reshape the plot's data
+/// only), while category orders and group levels still span all the layers.
+pub fn apply_transformations_with_sources(
+    spec: &ResolvedSpec,
+    data: &PlotData,
+    sources: &DataSources,
+    options: &RenderOptions,
+) -> Result<RenderData> {
+    let strict = options.strict;
+    // 0. Data stages (filter, summarize, sort, ...) reshape the rows before anything else sees them
+    let StagedData {
+        data: staged,
+        filter_rejected_rows,
+        row_limits,
+    } = apply_data_stages(&spec.stages, data)?;
+    let data = staged.as_ref().unwrap_or(data);
+
+    // Type every column once; layers and facets then index into it by row
+    let store = ColumnStore::new(&data.headers, &data.rows);
+    let mut tables: HashMap<&str, (&PlotData, ColumnStore, Vec<usize>)> = HashMap::new();
+    for name in spec.layers.iter().filter_map(|l| l.original_layer.data()) {
+        let table = sources
+            .get(name)
+            .ok_or_else(|| anyhow!("data: \"{}\" is not loaded", name))?;
+        tables.entry(name).or_insert_with(|| {
+            let store = ColumnStore::new(&table.headers, &table.rows);
+            (table, store, (0..table.rows.len()).collect())
+        });
+    }
+    let layer_sources: Vec<LayerSource> = spec
+        .layers
+        .iter()
+        .map(|layer| match layer.original_layer.data() {
+            Some(name) => {
+                let (data, store, rows) = &tables[name];
+                LayerSource {
+                    data,
+                    store,
+                    rows: Some(rows),
+                }
+            }
+            None => LayerSource {
+                data,
+                store: &store,
+                rows: None,
+            },
+        })
+        .collect();
 
-/// Main entry point: Transform resolved spec and CSV data into renderable data
-pub fn apply_transformations(spec: &ResolvedSpec, data: &PlotData) -> Result<RenderData> {
     // 1. Partition Data (Faceting)
-    let partitions = partition_data(spec, data)?;
+    let (partitions, grid_shape) = partition_data(spec, data, &store, options.label_order)?;
 
     // 2. Calculate Layout info
-    let (nrow, ncol) = calculate_grid_dimensions(partitions.len(), spec.facet.as_ref());
+    let (nrow, ncol) = grid_shape
+        .unwrap_or_else(|| calculate_grid_dimensions(partitions.len(), spec.facet.as_ref()));
     let facet_layout = FacetLayout {
         nrow,
         ncol,
         panel_titles: partitions.iter().map(|p| p.title.clone()).collect(),
+        row_titles: match grid_shape {
+            Some(_) => partitions.iter().map(|p| p.row_title.clone()).collect(),
+            None => Vec::new(),
+        },
+        panel_rows: partitions.iter().map(|p| p.rows.len()).collect(),
     };
 
+    // Continuous color/size domains span the full data so every facet shares one scale
+    let domains = spec
+        .layers
+        .iter()
+        .zip(&layer_sources)
+        .map(|(layer, source)| {
+            Ok(ContinuousDomains {
+                color: continuous_domain(
+                    layer,
+                    layer.aesthetics.color.as_ref(),
+                    source.data,
+                    source.store,
+                    strict,
+                )?,
+                size: continuous_domain(
+                    layer,
+                    layer.aesthetics.size.as_ref(),
+                    source.data,
+                    source.store,
+                    strict,
+                )?,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // One category order for every layer and panel, so categorical x values line up
+    let x_categories = x_category_order(spec, &layer_sources, strict, options.label_order);
+
+    let levels = spec
+        .layers
+        .iter()
+        .zip(&layer_sources)
+        .zip(&domains)
+        .map(|((layer, source), domains)| {
+            layer_group_levels(layer, source.data, *domains, &spec.color_scale, options)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    for (((layer, source), domains), levels) in spec
+        .layers
+        .iter()
+        .zip(&layer_sources)
+        .zip(&domains)
+        .zip(&levels)
+    {
+        check_group_count(
+            layer,
+            *domains,
+            levels,
+            source.store,
+            source.data,
+            options.max_groups,
+        )?;
+    }
+    // Facet panels share each layer's group levels, so palettes agree across panels
+    let group_levels = spec.facet.as_ref().map(|_| levels);
+
     // 3. Process each partition into a Panel
-    let mut panels = Vec::new();
-    for (idx, partition) in partitions.into_iter().enumerate() {
-        let panel = process_partition(idx, partition, spec)?;
-        panels.push(panel);
+    // (panels are independent; with `parallel` they run on rayon's pool, and results
+    // are gathered in partition order so output and the reported error stay deterministic)
+    let process = |(idx, partition): (usize, &DataPartition)| {
+        process_partition(
+            idx,
+            partition,
+            &layer_sources,
+            &x_categories,
+            group_levels.as_deref(),
+            spec,
+            &domains,
+            options,
+        )
+    };
+    #[cfg(feature = "parallel")]
+    let panels: Vec<Result<PanelData>> = {
+        use rayon::prelude::*;
+        partitions.par_iter().enumerate().map(process).collect()
+    };
+    #[cfg(not(feature = "parallel"))]
+    let panels: Vec<Result<PanelData>> = partitions.iter().enumerate().map(process).collect();
+    let mut panels = panels.into_iter().collect::<Result<Vec<_>>>()?;
+    let annotations = place_annotations(spec, &partitions, &x_categories)?;
+    let shades = place_shades(spec, &partitions, &x_categories)?;
+    for ((panel, annotations), shades) in panels.iter_mut().zip(annotations).zip(shades) {
+        panel.annotations = annotations;
+        panel.shades = shades;
+    }
+
+    // Free x scales only show the categories each panel uses (in the shared order);
+    // a grid's columns share x, so there it is the categories each column uses
+    if spec
+        .facet
+        .as_ref()
+        .is_some_and(|f| matches!(f.scales, FacetScales::FreeX | FacetScales::Free))
+    {
+        if grid_shape.is_some() {
+            for col in 0..ncol {
+                drop_unused_categories(panels.iter_mut().skip(col).step_by(ncol).collect());
+            }
+        } else {
+            for panel in &mut panels {
+                drop_unused_categories(vec![panel]);
+            }
+        }
     }
 
     Ok(RenderData {
         panels,
         facet_layout,
+        filter_rejected_rows,
+        row_limits,
     })
 }
 
-struct DataPartition {
-    title: String,
-    data: PlotData,
+/// The result of running the data stages
+#[derive(Debug, Default)]
+struct StagedData {
+    /// The final table; None without stages
+    data: Option<PlotData>,
+    /// Rows `filter()` stages rejected only for non-numeric cells
+    filter_rejected_rows: usize,
+    /// `head()`/`sample()` stages that dropped rows
+    row_limits: Vec<RowLimit>,
 }
 
-/// Split CSV data based on facet configuration
-fn partition_data(spec: &ResolvedSpec, data: &PlotData) -> Result<Vec<DataPartition>> {
-    if let Some(facet) = &spec.facet {
-        // Find facet column index
-        let col_idx = data
-            .headers
-            .iter()
-            .position(|h| h.eq_ignore_ascii_case(&facet.col))
-            .ok_or_else(|| anyhow!("Facet column '{}' not found", facet.col))?;
-
-        // Group rows
-        let mut groups: HashMap<String, Vec<Vec<String>>> = HashMap::new();
-        for row in &data.rows {
-            if let Some(val) = row.get(col_idx) {
-                groups.entry(val.clone()).or_default().push(row.clone());
+/// Run the spec's data stages in order
+fn apply_data_stages(stages: &[DataStage], data: &PlotData) -> Result<StagedData> {
+    let mut staged = StagedData::default();
+    for stage in stages {
+        let current = staged.data.take();
+        let input = current.as_ref().unwrap_or(data);
+        let output = match stage {
+            // Limits larger than the table keep it as is
+            DataStage::Head(n) | DataStage::Sample(Sample { n, .. }) if *n >= input.rows.len() => {
+                staged.data = current;
+                continue;
             }
-        }
-
-        // Sort keys
-        let mut keys: Vec<String> = groups.keys().cloned().collect();
-        keys.sort();
-
-        let mut partitions = Vec::new();
-        for key in keys {
-            let rows = groups.remove(&key).unwrap();
-            partitions.push(DataPartition {
-                title: key,
-                data: PlotData {
-                    headers: data.headers.clone(),
-                    rows,
-                },
+            DataStage::Head(n) => PlotData::new(input.headers.clone(), input.rows[..*n].to_vec()),
+            DataStage::Sample(sample) => sample_rows(sample, input),
+            DataStage::Filter(expr) => {
+                let filter = RowFilter::new(expr, &input.headers)?;
+                let (kept, unparsable) = filter.apply(input, 0..input.rows.len());
+                if kept.is_empty() {
+                    return Err(anyhow!("filter matched 0 rows (of {})", input.rows.len()));
+                }
+                staged.filter_rejected_rows += unparsable;
+                let rows = kept
+                    .into_iter()
+                    .map(|idx| input.rows[idx].clone())
+                    .collect();
+                PlotData::new(input.headers.clone(), rows)
+            }
+            DataStage::Summarize(summary) => summarize_rows(summary, input)?,
+            DataStage::Sort(keys) => sort_rows(keys, input)?,
+            DataStage::Mutate(mutations) => mutate_rows(mutations, input)?,
+            DataStage::PivotLonger(pivot) => pivot_rows(pivot, input)?,
+        };
+        if let DataStage::Head(_) | DataStage::Sample(_) = stage {
+            staged.row_limits.push(RowLimit {
+                stage: stage.name().to_string(),
+                before: input.rows.len(),
+                after: output.rows.len(),
             });
         }
-        Ok(partitions)
-    } else {
-        // No facet, single partition
-        Ok(vec![DataPartition {
-            title: "".to_string(),
-            data: data.clone(), // Clone is expensive but safe for now
-        }])
+        staged.data = Some(output);
     }
+    Ok(staged)
 }
 
-fn calculate_grid_dimensions(n_panels: usize, facet: Option<&ResolvedFacet>) -> (usize, usize) {
-    if let Some(f) = facet {
-        if let Some(cols) = f.ncol {
-            let rows = (n_panels as f64 / cols as f64).ceil() as usize;
-            return (rows, cols);
-        }
-    }
-    // Default: square-ish
-    let cols = (n_panels as f64).sqrt().ceil() as usize;
-    let rows = (n_panels as f64 / cols as f64).ceil() as usize;
-    (rows, cols)
+/// `n` rows drawn without replacement, in their original order. Each row's rank is a
+/// hash of its index and the seed, so a seed always picks the same rows.
+fn sample_rows(sample: &Sample, data: &PlotData) -> PlotData {
+    let salt = splitmix64(sample.seed);
+    let mut picked: Vec<usize> = (0..data.rows.len()).collect();
+    picked.select_nth_unstable_by_key(sample.n, |&idx| splitmix64(idx as u64 ^ salt));
+    picked.truncate(sample.n);
+    picked.sort_unstable();
+    let rows = picked
+        .into_iter()
+        .map(|idx| data.rows[idx].clone())
+        .collect();
+    PlotData::new(data.headers.clone(), rows)
 }
 
-/// Process a single data partition (Panel)
-fn process_partition(
-    index: usize,
-    partition: DataPartition,
-    spec: &ResolvedSpec,
-) -> Result<PanelData> {
-    let mut layers = Vec::new();
-
-    for layer_spec in &spec.layers {
-        let layer_data = process_layer(layer_spec, &partition.data, spec.x_scale_spec.as_ref())?;
-        layers.push(layer_data);
+/// Stable sort of the rows by each key in turn. Numeric columns (every non-missing
+/// cell parses) compare as numbers, others as text; missing cells sort last either way.
+fn sort_rows(keys: &[SortKey], data: &PlotData) -> Result<PlotData> {
+    struct Key {
+        idx: usize,
+        desc: bool,
+        numbers: Option<Vec<f64>>,
     }
-
-    Ok(PanelData { index, layers })
+    let keys = keys
+        .iter()
+        .map(|key| {
+            let idx = find_col_index(&data.headers, &key.column)?;
+            let numbers = data
+                .rows
+                .iter()
+                .map(|row| match row[idx].trim().parse::<f64>() {
+                    Ok(value) => Some(value),
+                    Err(_) if is_missing(&row[idx]) => Some(f64::NAN),
+                    Err(_) => None,
+                })
+                .collect::<Option<Vec<f64>>>();
+            Ok(Key {
+                idx,
+                desc: key.desc,
+                numbers,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut order: Vec<usize> = (0..data.rows.len()).collect();
+    order.sort_by(|&a, &b| {
+        keys.iter()
+            .map(|key| {
+                let (cell_a, cell_b) = (&data.rows[a][key.idx], &data.rows[b][key.idx]);
+                let ordering = match (is_missing(cell_a), is_missing(cell_b)) {
+                    (true, true) => return Ordering::Equal,
+                    (true, false) => return Ordering::Greater,
+                    (false, true) => return Ordering::Less,
+                    (false, false) => match &key.numbers {
+                        Some(numbers) => numbers[a].total_cmp(&numbers[b]),
+                        None => cell_a.cmp(cell_b),
+                    },
+                };
+                if key.desc {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    });
+    let rows = order
+        .into_iter()
+        .map(|idx| data.rows[idx].clone())
+        .collect();
+    Ok(PlotData::new(data.headers.clone(), rows))
 }
 
-/// Process a single layer: Extract, Group, Stack
-fn process_layer(
-    layer_spec: &ResolvedLayer,
-    data: &PlotData,
-    x_scale_spec: Option<&AxisScale>,
-) -> Result<LayerData> {
-    let aes = &layer_spec.aesthetics;
-
-    if matches!(
-        layer_spec.original_layer,
-        Layer::HLine(_) | Layer::VLine(_) | Layer::AbLine(_) | Layer::Segment(_)
-    ) {
-        return Ok(process_reference_layer(&layer_spec.original_layer));
-    }
-
-    // 1. Identify Grouping Column
-    let group_col = aes
-        .color
+/// Aggregate a value column per group of the `by` columns into a new table with one
+/// row per group, in order of each group's first row. Missing values are skipped; a
+/// group with no values gets a missing cell (except `sum`, 0, and `count`, 0).
+fn summarize_rows(summary: &Summarize, data: &PlotData) -> Result<PlotData> {
+    let by_idxs = summary
+        .by
+        .iter()
+        .map(|col| find_col_index(&data.headers, col))
+        .collect::<Result<Vec<_>>>()?;
+    let value_idx = summary
+        .column
         .as_ref()
-        .or(aes.size.as_ref())
-        .or(aes.shape.as_ref())
-        .or(aes.alpha.as_ref());
-
-    // 2. Extract Data (Grouped)
-    // We return a map: GroupKey -> (RawX, RawY, RawYMin, RawYMax)
-    // RawX is String to handle both numeric and categorical initially
-    let mut raw_groups: HashMap<String, (Vec<String>, Vec<f64>, Vec<f64>, Vec<f64>)> =
-        HashMap::new();
-
-    // Column Indices
-    let x_idx = find_col_index(&data.headers, &aes.x_col)?;
-    let y_idx = if let Some(y) = &aes.y_col {
-        Some(find_col_index(&data.headers, y)?)
-    } else {
-        None
-    };
-    let ymin_idx = if let Some(col) = &aes.ymin_col {
-        Some(find_col_index(&data.headers, col)?)
-    } else {
-        None
-    };
-    let ymax_idx = if let Some(col) = &aes.ymax_col {
-        Some(find_col_index(&data.headers, col)?)
-    } else {
-        None
-    };
-    let fill_idx = if let Some(col) = &aes.fill {
-        Some(find_col_index(&data.headers, col)?)
-    } else {
-        None
-    };
+        .map(|col| find_col_index(&data.headers, col))
+        .transpose()?;
+    let numeric = summary.function != SummaryFunction::Count;
+
+    let mut index: HashMap<Vec<&str>, usize> = HashMap::new();
+    let mut groups: Vec<(Vec<&str>, Vec<f64>)> = Vec::new();
+    for (row_idx, row) in data.rows.iter().enumerate() {
+        let key: Vec<&str> = by_idxs.iter().map(|&idx| row[idx].as_str()).collect();
+        let slot = *index.entry(key.clone()).or_insert_with(|| {
+            groups.push((key, Vec::new()));
+            groups.len() - 1
+        });
+        let value = match value_idx {
+            None => 1.0,
+            Some(idx) if is_missing(&row[idx]) => continue,
+            Some(_) if !numeric => 1.0,
+            Some(idx) => row[idx].trim().parse::<f64>().map_err(|_| {
+                anyhow!(
+                    "summarize() {}: '{}' in row {} is not a number",
+                    summary.output_name(),
+                    row[idx],
+                    row_idx + 1
+                )
+            })?,
+        };
+        groups[slot].1.push(value);
+    }
 
-    let group_idx = if let Some(g) = group_col {
-        Some(find_col_index(&data.headers, g)?)
-    } else {
-        None
-    };
+    let mut headers: Vec<String> = by_idxs
+        .iter()
+        .map(|&idx| data.headers[idx].clone())
+        .collect();
+    headers.push(summary.output_name());
+    let rows = groups
+        .into_iter()
+        .map(|(key, mut values)| {
+            let mut row: Vec<String> = key.into_iter().map(str::to_string).collect();
+            let n = values.len();
+            let result = match summary.function {
+                SummaryFunction::Count => Some(n as f64),
+                SummaryFunction::Sum => Some(values.iter().sum()),
+                _ if n == 0 => None,
+                SummaryFunction::Mean => Some(values.iter().sum::<f64>() / n as f64),
+                SummaryFunction::Min => values.iter().copied().reduce(f64::min),
+                SummaryFunction::Max => values.iter().copied().reduce(f64::max),
+                SummaryFunction::Median => {
+                    values.sort_by(f64::total_cmp);
+                    Some(if n % 2 == 1 {
+                        values[n / 2]
+                    } else {
+                        (values[n / 2 - 1] + values[n / 2]) / 2.0
+                    })
+                }
+            };
+            row.push(result.map(|v| v.to_string()).unwrap_or_default());
+            row
+        })
+        .collect();
+    Ok(PlotData::new(headers, rows))
+}
 
-    let is_heatmap = matches!(layer_spec.original_layer, Layer::Heatmap(_));
+/// Computes each derived column in turn, appending it (or replacing the column of the
+/// same name). Missing input cells and undefined results (division by zero, log of a
+/// non-positive number) leave the cell missing, so layers skip those rows like any
+/// other missing value.
+fn mutate_rows(mutations: &[Mutation], data: &PlotData) -> Result<PlotData> {
+    let mut headers = data.headers.clone();
+    let mut rows = data.rows.clone();
+    for mutation in mutations {
+        let columns = mutation
+            .expr
+            .columns()
+            .into_iter()
+            .map(|col| Ok((col, find_col_index(&headers, col)?)))
+            .collect::<Result<HashMap<_, _>>>()?;
+        let values = rows
+            .iter()
+            .enumerate()
+            .map(|(row_idx, row)| {
+                eval_arith(&mutation.expr, &columns, row)
+                    .map(|v| v.map(|v| v.to_string()).unwrap_or_default())
+                    .map_err(|idx| {
+                        anyhow!(
+                            "mutate() {}: '{}' in column '{}', row {} is not a number",
+                            mutation.name,
+                            row[idx],
+                            headers[idx],
+                            row_idx + 1
+                        )
+                    })
+            })
+            .collect::<Result<Vec<_>>>()?;
 
-    // For heatmap with categorical y, pre-build a y category mapping
-    let heatmap_y_cat_map: Option<HashMap<String, f64>> = if is_heatmap && y_idx.is_some() {
-        let idx = y_idx.unwrap();
-        // Check if y values are numeric
-        let all_y_numeric = data.rows.iter().all(|row| row[idx].parse::<f64>().is_ok());
-        if !all_y_numeric {
-            // Build categorical mapping
-            let mut unique_y: Vec<String> = Vec::new();
-            let mut seen: std::collections::HashSet<String> = std::collections::HashSet::new();
-            for row in &data.rows {
-                let val = row[idx].clone();
-                if seen.insert(val.clone()) {
-                    unique_y.push(val);
+        match headers
+            .iter()
+            .position(|h| h.eq_ignore_ascii_case(&mutation.name))
+        {
+            Some(idx) => {
+                for (row, value) in rows.iter_mut().zip(values) {
+                    row[idx] = value;
+                }
+            }
+            None => {
+                headers.push(mutation.name.clone());
+                for (row, value) in rows.iter_mut().zip(values) {
+                    row.push(value);
                 }
             }
-            // Preserve data appearance order (no alphabetical sort)
-            Some(
-                unique_y
-                    .iter()
-                    .enumerate()
-                    .map(|(i, s)| (s.clone(), i as f64))
-                    .collect(),
-            )
-        } else {
-            None
         }
-    } else {
-        None
-    };
+    }
+    Ok(PlotData::new(headers, rows))
+}
+
+/// Melts the pivoted columns into one row per input row and pivoted column: the other
+/// columns repeated (all of them with `keep`), then the pivoted column's header and its
+/// cell. Cells are copied as text, so the value column is typed later like any other.
+fn pivot_rows(pivot: &PivotLonger, data: &PlotData) -> Result<PlotData> {
+    let pivoted = pivot
+        .cols
+        .iter()
+        .map(|col| find_col_index(&data.headers, col))
+        .collect::<Result<Vec<_>>>()?;
+    let kept: Vec<usize> = (0..data.headers.len())
+        .filter(|idx| pivot.keep || !pivoted.contains(idx))
+        .collect();
 
+    let mut headers: Vec<String> = kept.iter().map(|&idx| data.headers[idx].clone()).collect();
+    headers.push(pivot.names_to.clone());
+    headers.push(pivot.values_to.clone());
+    let mut rows = Vec::with_capacity(data.rows.len() * pivoted.len());
     for row in &data.rows {
-        let x_str = row[x_idx].clone();
-        let y_val = if let Some(idx) = y_idx {
-            if let Some(ref cat_map) = heatmap_y_cat_map {
-                // Categorical y for heatmap: use index
-                *cat_map.get(&row[idx]).unwrap_or(&0.0)
-            } else {
-                row[idx]
-                    .parse::<f64>()
-                    .context(format!("Failed to parse Y value '{}'", row[idx]))?
-            }
-        } else {
-            0.0 // Default for histogram if not provided
-        };
-        // For heatmap, repurpose ymin slot to carry fill values
-        let ymin_val = if is_heatmap {
-            if let Some(idx) = fill_idx {
-                row[idx].parse::<f64>().unwrap_or(0.0)
+        for &idx in &pivoted {
+            let mut out: Vec<String> = kept.iter().map(|&k| row[k].clone()).collect();
+            out.push(data.headers[idx].clone());
+            out.push(row[idx].clone());
+            rows.push(out);
+        }
+    }
+    Ok(PlotData::new(headers, rows))
+}
+
+/// Evaluate an arithmetic expression on one row. `Ok(None)` is a missing or undefined
+/// (non-finite) result; `Err` is the index of a cell that isn't a number.
+fn eval_arith(
+    expr: &ArithExpr,
+    columns: &HashMap<&str, usize>,
+    row: &[String],
+) -> std::result::Result<Option<f64>, usize> {
+    let finite = |v: f64| Some(v).filter(|v| v.is_finite());
+    Ok(match expr {
+        ArithExpr::Number(n) => Some(*n),
+        ArithExpr::Column(col) => {
+            let idx = columns[col.as_str()];
+            let cell = &row[idx];
+            if is_missing(cell) {
+                None
             } else {
-                0.0
+                Some(cell.trim().parse::<f64>().map_err(|_| idx)?)
             }
-        } else {
-            if let Some(idx) = ymin_idx {
-                row[idx].parse::<f64>().unwrap_or(0.0)
-            } else {
-                0.0
+        }
+        ArithExpr::Neg(inner) => eval_arith(inner, columns, row)?.map(|v| -v),
+        ArithExpr::Binary { op, left, right } => {
+            let left = eval_arith(left, columns, row)?;
+            let right = eval_arith(right, columns, row)?;
+            match (left, right) {
+                (Some(l), Some(r)) => finite(match op {
+                    ArithOp::Add => l + r,
+                    ArithOp::Sub => l - r,
+                    ArithOp::Mul => l * r,
+                    ArithOp::Div => l / r,
+                }),
+                _ => None,
             }
-        };
-        let ymax_val = if let Some(idx) = ymax_idx {
-            row[idx].parse::<f64>().unwrap_or(0.0)
-        } else {
-            0.0
-        };
-
-        let group_key = if let Some(idx) = group_idx {
-            row[idx].clone()
-        } else {
-            "default".to_string()
-        };
-
-        let entry = raw_groups
-            .entry(group_key)
-            .or_insert_with(|| (Vec::new(), Vec::new(), Vec::new(), Vec::new()));
-        entry.0.push(x_str);
-        entry.1.push(y_val);
-        entry.2.push(ymin_val);
-        entry.3.push(ymax_val);
-    }
+        }
+        ArithExpr::Call { function, arg } => eval_arith(arg, columns, row)?.and_then(|v| {
+            finite(match function {
+                ArithFunction::Abs => v.abs(),
+                ArithFunction::Log => v.ln(),
+                ArithFunction::Log10 => v.log10(),
+                ArithFunction::Exp => v.exp(),
+                ArithFunction::Sqrt => v.sqrt(),
+            })
+        }),
+    })
+}
 
-    // Apply Statistics
-    let raw_groups = apply_statistics(raw_groups, layer_spec.original_layer.stat())?;
+/// The table a layer reads and its typed columns
+#[derive(Clone, Copy)]
+struct LayerSource<'a> {
+    data: &'a PlotData,
+    store: &'a ColumnStore,
+    /// Every row of a `data:` table; None reads the panel's rows of the plot's data
+    rows: Option<&'a [usize]>,
+}
 
-    // 3. Determine X-Axis Type (Numeric vs Categorical)
-    // Logic: If ALL x values in this layer can be parsed as float, it's numeric.
-    // UNLESS it's a Bar chart, which forces categorical.
-    let is_bar = matches!(layer_spec.original_layer, Layer::Bar(_));
-    let is_boxplot = matches!(layer_spec.original_layer, Layer::Boxplot(_));
-    let is_violin = matches!(layer_spec.original_layer, Layer::Violin(_));
-    let is_heatmap_layer = matches!(layer_spec.original_layer, Layer::Heatmap(_));
+/// A facet panel's title and the indices of its rows in the full data
+struct DataPartition {
+    title: String,
+    /// facet_grid row value (empty for facet_wrap)
+    row_title: String,
+    rows: Vec<usize>,
+}
 
-    let sorted_group_keys = get_sorted_keys(&raw_groups);
-    let all_x_strings: Vec<&String> = sorted_group_keys
-        .iter()
-        .filter_map(|key| raw_groups.get(key))
-        .flat_map(|d| d.x.iter())
-        .collect();
-    let all_numeric = all_x_strings.iter().all(|s| s.parse::<f64>().is_ok());
-    let use_datetime =
-        x_scale_spec.is_some_and(|scale| matches!(scale.scale_type, ScaleType::DateTime));
+/// Whether a layer's x values may reach the axis as data categories: bars, boxplots,
+/// violins, and pie slices always do, other geoms when the column is text. Stats like binning and
+/// smoothing replace x with computed numbers, and datetime scales parse it.
+fn keeps_x_categories(
+    layer: &ResolvedLayer,
+    data: &PlotData,
+    store: &ColumnStore,
+    x_scale_spec: Option<&AxisScale>,
+) -> bool {
+    if x_scale_spec.is_some_and(|scale| matches!(scale.scale_type, ScaleType::DateTime)) {
+        return false;
+    }
+    let forced = layer.original_layer.requires_categorical_x();
+    let text = find_col_index(&data.headers, &layer.aesthetics.x_col)
+        .is_ok_and(|idx| matches!(store.column(idx), Column::Text));
+    if !forced && !text {
+        return false;
+    }
+    match layer.original_layer.stat() {
+        Stat::Identity
+        | Stat::Count
+        | Stat::Summary { .. }
+        | Stat::RollingMean { .. }
+        | Stat::Boxplot
+        | Stat::Violin { .. } => !layer.original_layer.is_reference(),
+        Stat::Heatmap { bins } => bins.is_none(),
+        Stat::Bin { .. } | Stat::Smooth { .. } | Stat::Density { .. } => false,
+    }
+}
 
-    // Heatmap with bins uses numeric x; categorical heatmap uses categorical x
-    let heatmap_has_bins = match &layer_spec.original_layer {
-        Layer::Heatmap(_) => {
-            matches!(layer_spec.original_layer.stat(), Stat::Heatmap { bins } if bins.is_some())
+/// Categorical x order for the whole plot, from every layer's x column over the full data.
+/// `scale_x_discrete(order:)` picks first appearance, alphabetical, or descending total y;
+/// by default categories keep first-appearance order unless all of them are numbers.
+fn x_category_order(
+    spec: &ResolvedSpec,
+    sources: &[LayerSource],
+    strict: bool,
+    label_order: LabelOrder,
+) -> Vec<String> {
+    let mut categories: Vec<String> = Vec::new();
+    let mut totals: Vec<f64> = Vec::new();
+    let mut index: HashMap<&str, usize> = HashMap::new();
+
+    let x_scale_spec = spec.x_scale_spec.as_ref();
+    for (layer, &LayerSource { data, store, .. }) in
+        spec.layers.iter().zip(sources).filter(|(layer, source)| {
+            keeps_x_categories(layer, source.data, source.store, x_scale_spec)
+        })
+    {
+        let Ok(x_idx) = find_col_index(&data.headers, &layer.aesthetics.x_col) else {
+            continue;
+        };
+        let y_idx = layer
+            .aesthetics
+            .y_col
+            .as_ref()
+            .and_then(|col| find_col_index(&data.headers, col).ok());
+        // A segment's far end is a second x (and y) per row
+        let end_idxs = layer.aesthetics.xend_col.as_ref().and_then(|col| {
+            let xend_idx = find_col_index(&data.headers, col).ok()?;
+            let yend_idx = layer
+                .aesthetics
+                .yend_col
+                .as_ref()
+                .and_then(|col| find_col_index(&data.headers, col).ok());
+            Some((xend_idx, yend_idx))
+        });
+        for (row_idx, row) in data.rows.iter().enumerate() {
+            for (x_idx, y_idx) in std::iter::once((x_idx, y_idx)).chain(end_idxs) {
+                let x = row[x_idx].as_str();
+                if !strict && is_missing(x) {
+                    continue;
+                }
+                let i = *index.entry(x).or_insert_with(|| {
+                    categories.push(x.to_string());
+                    totals.push(0.0);
+                    categories.len() - 1
+                });
+                if let Some(value) =
+                    y_idx.and_then(|idx| numeric_cell(store, data, row_idx, idx).ok())
+                {
+                    if value.is_finite() {
+                        totals[i] += value;
+                    }
+                }
+            }
         }
-        _ => false,
-    };
-    let heatmap_numeric = is_heatmap_layer && heatmap_has_bins && all_numeric;
-    let use_categorical =
-        !use_datetime && (is_bar || is_boxplot || is_violin || (!all_numeric && !heatmap_numeric));
-
-    // 4. Normalize X Values
-    // If categorical, we need a unified mapping for stacking/grouping
-    let mut x_category_map = HashMap::new();
-    let mut category_order = Vec::new();
+    }
 
-    if use_categorical {
-        // Collect unique categories preserving order of first appearance in data.
-        // This lets the CSV control display order (like ggplot2 factor levels).
-        let mut seen_cats: HashSet<String> = HashSet::new();
-        for s in &all_x_strings {
-            if seen_cats.insert((*s).clone()) {
-                category_order.push((*s).clone());
+    let order = x_scale_spec.and_then(|scale| scale.order);
+    match order {
+        Some(CategoryOrder::Appearance) => {}
+        Some(CategoryOrder::Alpha) => label_order.sort(&mut categories),
+        Some(CategoryOrder::Value) => {
+            let mut ranked: Vec<(String, f64)> = categories.into_iter().zip(totals).collect();
+            // Stable, so ties keep first-appearance order
+            ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+            categories = ranked.into_iter().map(|(category, _)| category).collect();
+        }
+        None => {
+            let numeric: Option<Vec<f64>> = categories.iter().map(|s| numeric_value(s)).collect();
+            if let Some(numeric) = numeric {
+                let mut ranked: Vec<(String, f64)> = categories.into_iter().zip(numeric).collect();
+                ranked.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+                categories = ranked.into_iter().map(|(category, _)| category).collect();
             }
         }
+    }
+    categories
+}
 
-        // Sort numerically if all categories are numbers
-        let all_numeric_cats = category_order.iter().all(|s| s.parse::<f64>().is_ok());
-        if all_numeric_cats {
-            category_order.sort_by(|a, b| {
-                let fa = a.parse::<f64>().unwrap();
-                let fb = b.parse::<f64>().unwrap();
-                fa.partial_cmp(&fb).unwrap_or(std::cmp::Ordering::Equal)
-            });
+/// Where an `annotate()` or `shade()` x lands (`what` names it in errors). A text x
+/// names a category on a categorical axis and a time on a datetime one; a number x
+/// on a categorical axis must spell one of the categories.
+fn annotation_x(
+    x: &AnnotationX,
+    what: &str,
+    datetime: bool,
+    x_categories: &[String],
+) -> Result<f64> {
+    let category = |position: Option<usize>, x: &dyn std::fmt::Display| {
+        position.map(|slot| slot as f64).ok_or_else(|| {
+            anyhow!(
+                "{} {} is not a category on the x axis ({})",
+                what,
+                x,
+                x_categories.join(", ")
+            )
+        })
+    };
+    match x {
+        AnnotationX::Text(text) if datetime => {
+            parse_datetime_value(text).with_context(|| format!("{} \"{}\"", what, text))
         }
+        AnnotationX::Text(text) if !x_categories.is_empty() => category(
+            x_categories.iter().position(|c| c == text),
+            &format_args!("\"{}\"", text),
+        ),
+        AnnotationX::Text(text) => numeric_value(text).ok_or_else(|| {
+            anyhow!(
+                "{} \"{}\" is not a number; the x axis has no categories",
+                what,
+                text
+            )
+        }),
+        AnnotationX::Number(x) if !x_categories.is_empty() && !datetime => category(
+            x_categories
+                .iter()
+                .position(|c| numeric_value(c) == Some(*x)),
+            x,
+        ),
+        AnnotationX::Number(x) => Ok(*x),
+    }
+}
 
-        for (i, cat) in category_order.iter().enumerate() {
-            x_category_map.insert(cat.clone(), i as f64);
+/// The partitions an `annotate()` or `shade()` draws on: every one, or those whose
+/// facet value (a facet_grid row or column value) is its `panel:`
+fn selected_panels(
+    panel: Option<&str>,
+    what: &str,
+    partitions: &[DataPartition],
+) -> Result<Vec<usize>> {
+    let selected: Vec<usize> = partitions
+        .iter()
+        .enumerate()
+        .filter(|(_, partition)| {
+            panel.is_none_or(|p| p == partition.title || p == partition.row_title)
+        })
+        .map(|(i, _)| i)
+        .collect();
+    if selected.is_empty() {
+        let mut values: Vec<&str> = Vec::new();
+        for partition in partitions {
+            for value in [partition.row_title.as_str(), partition.title.as_str()] {
+                if !value.is_empty() && !values.contains(&value) {
+                    values.push(value);
+                }
+            }
         }
+        anyhow::bail!(
+            "{} panel: \"{}\" matches no facet panel{}",
+            what,
+            panel.unwrap_or_default(),
+            if values.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", values.join(", "))
+            }
+        );
     }
+    Ok(selected)
+}
 
-    // 5. Build Groups (Styles & Coordinates)
-    let mut groups = Vec::new();
-    // Assign Palettes
-    let color_map = ColorPalette::category10().assign_colors(&sorted_group_keys);
-    let size_map = SizePalette::default_range().assign_sizes(&sorted_group_keys);
-    let shape_map = ShapePalette::default_shapes().assign_shapes(&sorted_group_keys);
-    let alpha_map = AlphaPalette::default_range().assign_alphas(&sorted_group_keys);
-
-    // Prepare for Stacking (if needed)
-    let mut stack_offsets: HashMap<String, f64> = HashMap::new(); // Map "X_Key" -> Current Height
-    let is_stacked = match &layer_spec.original_layer {
-        Layer::Bar(b) => matches!(b.position, BarPosition::Stack),
-        _ => false,
-    };
+fn datetime_x(spec: &ResolvedSpec) -> bool {
+    spec.x_scale_spec
+        .as_ref()
+        .is_some_and(|scale| matches!(scale.scale_type, ScaleType::DateTime))
+}
 
-    // Iterate groups in defined order (important for stacking order)
-    for key in sorted_group_keys {
-        let stat_data = raw_groups.get(&key).unwrap();
-        let raw_x = &stat_data.x;
-        let raw_y = &stat_data.y;
-        let raw_ymin = &stat_data.ymin;
-        let raw_ymax = &stat_data.ymax;
+/// Each panel's `annotate()` notes
+fn place_annotations(
+    spec: &ResolvedSpec,
+    partitions: &[DataPartition],
+    x_categories: &[String],
+) -> Result<Vec<Vec<PanelAnnotation>>> {
+    let datetime = datetime_x(spec);
+    let mut placed = vec![Vec::new(); partitions.len()];
+    for (index, note) in spec.annotations.iter().enumerate() {
+        let x = annotation_x(&note.x, "annotate() x", datetime, x_categories)?;
+        for i in selected_panels(note.panel.as_deref(), "annotate()", partitions)? {
+            placed[i].push(PanelAnnotation {
+                index,
+                x,
+                y: note.y,
+            });
+        }
+    }
+    Ok(placed)
+}
 
-        let mut x_floats = Vec::with_capacity(raw_x.len());
-        let mut y_starts = Vec::with_capacity(raw_x.len());
-        let mut y_ends = Vec::with_capacity(raw_x.len());
-        let mut y_mins = Vec::with_capacity(raw_x.len());
-        let mut y_maxs = Vec::with_capacity(raw_x.len());
+/// Each panel's `shade()` bands
+fn place_shades(
+    spec: &ResolvedSpec,
+    partitions: &[DataPartition],
+    x_categories: &[String],
+) -> Result<Vec<Vec<PanelShade>>> {
+    let datetime = datetime_x(spec);
+    let mut placed = vec![Vec::new(); partitions.len()];
+    for (index, shade) in spec.shades.iter().enumerate() {
+        let side = |x: &Option<AnnotationX>, what| {
+            x.as_ref()
+                .map(|x| annotation_x(x, what, datetime, x_categories))
+                .transpose()
+        };
+        let xmin = side(&shade.xmin, "shade() xmin")?;
+        let xmax = side(&shade.xmax, "shade() xmax")?;
+        if let (Some(min), Some(max)) = (xmin, xmax) {
+            if min > max {
+                anyhow::bail!("shade() xmin is past xmax on the x axis");
+            }
+        }
+        for i in selected_panels(shade.panel.as_deref(), "shade()", partitions)? {
+            placed[i].push(PanelShade { index, xmin, xmax });
+        }
+    }
+    Ok(placed)
+}
 
-        // Boxplot specific
-        let mut y_q1s = Vec::new();
-        let mut y_medians = Vec::new();
-        let mut y_q3s = Vec::new();
-        let mut outliers_vec = Vec::new();
+/// Reindex panels' categorical x values onto only the categories they contain
+fn drop_unused_categories(mut panels: Vec<&mut PanelData>) {
+    let Some(all) = panels
+        .iter()
+        .flat_map(|panel| &panel.layers)
+        .flat_map(|layer| &layer.groups)
+        .find_map(|group| group.x_categories.clone())
+    else {
+        return;
+    };
 
-        // Violin specific
-        let mut violin_density_vec: Vec<Vec<f64>> = Vec::new();
-        let mut violin_density_y_vec: Vec<Vec<f64>> = Vec::new();
-        let mut violin_quantile_values_vec: Vec<Vec<f64>> = Vec::new();
+    let mut used = vec![false; all.len()];
+    for group in panels
+        .iter()
+        .flat_map(|panel| &panel.layers)
+        .flat_map(|layer| &layer.groups)
+    {
+        if group.x_categories.is_some() {
+            for &x in &group.x {
+                if let Some(slot) = used.get_mut(x as usize) {
+                    *slot = true;
+                }
+            }
+        }
+    }
+    // Categories that notes and bands are pinned to stay on the axis too
+    let pinned = panels.iter().flat_map(|panel| {
+        let notes = panel.annotations.iter().map(|note| note.x);
+        let bands = panel
+            .shades
+            .iter()
+            .flat_map(|band| band.xmin.into_iter().chain(band.xmax));
+        notes.chain(bands)
+    });
+    for x in pinned {
+        if let Some(slot) = used.get_mut(x as usize) {
+            *slot = true;
+        }
+    }
 
-        for (i, x_s) in raw_x.iter().enumerate() {
-            let y_val = raw_y[i];
-            let raw_min = raw_ymin[i];
-            let raw_max = raw_ymax[i];
+    let mut remap = vec![0.0; all.len()];
+    let mut kept = Vec::new();
+    for (i, category) in all.into_iter().enumerate() {
+        if used[i] {
+            remap[i] = kept.len() as f64;
+            kept.push(category);
+        }
+    }
 
-            // Resolve X
-            let x_val = if use_categorical {
-                *x_category_map.get(x_s).unwrap() // Should exist
-            } else if use_datetime {
-                parse_datetime_value(x_s)?
-            } else {
-                x_s.parse::<f64>().unwrap() // Verified numeric earlier
-            };
-            x_floats.push(x_val);
+    for group in panels
+        .iter_mut()
+        .flat_map(|panel| &mut panel.layers)
+        .flat_map(|layer| &mut layer.groups)
+    {
+        if group.x_categories.is_some() {
+            for x in &mut group.x {
+                *x = remap[*x as usize];
+            }
+            group.x_categories = Some(kept.clone());
+        }
+    }
+    for panel in panels.iter_mut() {
+        for note in &mut panel.annotations {
+            note.x = remap[note.x as usize];
+        }
+        for band in &mut panel.shades {
+            for x in band.xmin.iter_mut().chain(band.xmax.iter_mut()) {
+                *x = remap[*x as usize];
+            }
+        }
+    }
+}
 
-            // Resolve Y (Stacking and Min/Max)
-            let stack_key = if use_categorical {
-                x_s.clone()
-            } else {
-                x_val.to_string()
-            };
+/// A facet_grid's (rows, cols)
+type GridShape = (usize, usize);
 
-            let (y_start, y_end, y_min, y_max) = if is_stacked {
-                let start = *stack_offsets.get(&stack_key).unwrap_or(&0.0);
-                let end = start + y_val;
-                stack_offsets.insert(stack_key, end);
-                (start, end, start, end)
-            } else if let Layer::Area(area) = &layer_spec.original_layer {
-                let baseline = area.baseline;
-                (baseline, y_val, baseline.min(y_val), baseline.max(y_val))
-            } else if let Layer::Spike(spike) = &layer_spec.original_layer {
-                let baseline = spike.baseline;
-                (baseline, y_val, baseline.min(y_val), baseline.max(y_val))
-            } else if matches!(layer_spec.original_layer, Layer::Ribbon(_))
-                || matches!(layer_spec.original_layer, Layer::LineRange(_))
-                || matches!(layer_spec.original_layer, Layer::ErrorBar(_))
-                || matches!(layer_spec.original_layer, Layer::PointRange(_))
-                || matches!(layer_spec.original_layer, Layer::CrossBar(_))
-                || matches!(layer_spec.original_layer, Layer::Boxplot(_))
-                || matches!(layer_spec.original_layer, Layer::Violin(_))
-            {
-                // Interval-like geoms, Boxplot, and Violin use raw ymin/ymax
-                (raw_min, raw_max, raw_min, raw_max)
-            } else {
-                // Line/Point/Bar(unstacked)
-                (0.0, y_val, 0.0, y_val)
-            };
+/// Split CSV data based on facet configuration. For facet_grid this also returns the
+/// (rows, cols) shape: every combination gets a panel, even one with no rows.
+fn partition_data(
+    spec: &ResolvedSpec,
+    data: &PlotData,
+    store: &ColumnStore,
+    label_order: LabelOrder,
+) -> Result<(Vec<DataPartition>, Option<GridShape>)> {
+    let Some(facet) = &spec.facet else {
+        // No facet, single partition
+        return Ok((
+            vec![DataPartition {
+                title: "".to_string(),
+                row_title: String::new(),
+                rows: (0..data.rows.len()).collect(),
+            }],
+            None,
+        ));
+    };
 
-            y_starts.push(y_start);
-            y_ends.push(y_end);
-            y_mins.push(y_min);
-            y_maxs.push(y_max);
+    if let Some(grid) = &facet.grid {
+        let (row_levels, row_of) = facet_levels(data, grid.rows.as_deref(), label_order)?;
+        let (col_levels, col_of) = facet_levels(data, grid.cols.as_deref(), label_order)?;
 
-            // Collect boxplot stats if available
-            if let Some(bp) = &stat_data.boxplot {
-                y_q1s.push(bp.q1[i]);
-                y_medians.push(bp.median[i]);
-                y_q3s.push(bp.q3[i]);
-                outliers_vec.push(bp.outliers[i].clone());
-            } else {
-                // Fill defaults to keep vectors aligned
-                y_q1s.push(0.0);
-                y_medians.push(0.0);
-                y_q3s.push(0.0);
-                outliers_vec.push(vec![]);
+        // Row-major, so panel i sits at (i / ncol, i % ncol)
+        let mut partitions: Vec<DataPartition> = row_levels
+            .iter()
+            .flat_map(|r| {
+                col_levels.iter().map(move |c| DataPartition {
+                    title: c.clone(),
+                    row_title: r.clone(),
+                    rows: Vec::new(),
+                })
+            })
+            .collect();
+        for (row_idx, (r, c)) in row_of.iter().zip(&col_of).enumerate() {
+            if let (Some(r), Some(c)) = (r, c) {
+                partitions[r * col_levels.len() + c].rows.push(row_idx);
             }
+        }
+        return Ok((partitions, Some((row_levels.len(), col_levels.len()))));
+    }
 
-            // Collect violin stats if available
-            if let Some(vp) = &stat_data.violin {
-                violin_density_vec.push(vp.density[i].clone());
-                violin_density_y_vec.push(vp.density_y[i].clone());
-                violin_quantile_values_vec.push(vp.quantile_values[i].clone());
-            } else {
-                violin_density_vec.push(vec![]);
-                violin_density_y_vec.push(vec![]);
-                violin_quantile_values_vec.push(vec![]);
-            }
+    let (levels, level_of) = match facet.bins {
+        Some(bins) => binned_facet_levels(data, store, &facet.col, bins, facet.cut)?,
+        None => facet_levels(data, Some(&facet.col), label_order)?,
+    };
+    let mut partitions: Vec<DataPartition> = levels
+        .into_iter()
+        .map(|title| DataPartition {
+            title,
+            row_title: String::new(),
+            rows: Vec::new(),
+        })
+        .collect();
+    for (row_idx, level) in level_of.iter().enumerate() {
+        if let Some(level) = level {
+            partitions[*level].rows.push(row_idx);
         }
+    }
+    Ok((partitions, None))
+}
 
-        // Build Style
-        let style = build_style(
-            key.clone(),
-            &layer_spec.original_layer,
-            aes,
-            &color_map,
-            &size_map,
-            &shape_map,
-            &alpha_map,
-            stat_data.heatmap.as_ref(),
-        );
+/// Cut a numeric facet column into `bins` intervals labeled like "[20, 35)", in
+/// numeric order; the last interval is closed. Intervals without rows are dropped
+/// and missing cells get None.
+fn binned_facet_levels(
+    data: &PlotData,
+    store: &ColumnStore,
+    column: &str,
+    bins: usize,
+    cut: FacetCut,
+) -> Result<(Vec<String>, Vec<Option<usize>>)> {
+    let col_idx = data
+        .headers
+        .iter()
+        .position(|h| h.eq_ignore_ascii_case(column))
+        .ok_or_else(|| anyhow!("Facet column '{}' not found", column))?;
+
+    let values = (0..data.rows.len())
+        .map(|row_idx| {
+            let cell = data.rows[row_idx].get(col_idx).map(String::as_str);
+            match cell {
+                None => Ok(None),
+                Some(cell) if is_missing(cell) => Ok(None),
+                Some(cell) => numeric_cell(store, data, row_idx, col_idx)
+                    .map(Some)
+                    .map_err(|_| {
+                        anyhow!(
+                            "facet_wrap(bins: {}) needs a numeric column, but '{}' has the value '{}'",
+                            bins,
+                            column,
+                            cell
+                        )
+                    }),
+            }
+        })
+        .collect::<Result<Vec<Option<f64>>>>()?;
 
-        // Extract heatmap data
-        let (hm_y_pos, hm_fill, hm_cw, hm_ch, hm_y_cats) = if let Some(hm) = &stat_data.heatmap {
-            // If we pre-mapped categorical y, use those categories
-            let y_cats = if let Some(ref cat_map) = heatmap_y_cat_map {
-                let mut cats: Vec<(f64, String)> =
-                    cat_map.iter().map(|(s, &i)| (i, s.clone())).collect();
-                cats.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
-                Some(cats.into_iter().map(|(_, s)| s).collect())
-            } else {
-                hm.y_categories.clone()
-            };
-            (
-                hm.y_positions.clone(),
-                hm.fill_values.clone(),
-                hm.cell_width,
-                hm.cell_height,
-                y_cats,
-            )
-        } else {
-            (vec![], vec![], 0.0, 0.0, None)
-        };
+    let mut sorted: Vec<f64> = values.iter().flatten().copied().collect();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let (Some(&min), Some(&max)) = (sorted.first(), sorted.last()) else {
+        return Ok((Vec::new(), values.iter().map(|_| None).collect()));
+    };
 
-        groups.push(GroupData {
-            key: key.clone(),
-            x: x_floats,
-            y: y_ends, // Main value
-            y_start: y_starts,
-            y_min: y_mins,
-            y_max: y_maxs,
+    let mut breaks: Vec<f64> = match cut {
+        FacetCut::Width => (0..=bins)
+            .map(|i| min + (max - min) * i as f64 / bins as f64)
+            .collect(),
+        FacetCut::Quantile => (0..=bins)
+            .map(|i| {
+                let pos = (sorted.len() - 1) as f64 * i as f64 / bins as f64;
+                let (lo, hi) = (pos.floor() as usize, pos.ceil() as usize);
+                sorted[lo] + (sorted[hi] - sorted[lo]) * (pos - lo as f64)
+            })
+            .collect(),
+    };
+    // Ties (or a constant column) collapse repeated breaks into fewer intervals
+    breaks.dedup();
+    let n_intervals = (breaks.len() - 1).max(1);
 
-            y_q1: y_q1s,
-            y_median: y_medians,
-            y_q3: y_q3s,
-            outliers: outliers_vec,
+    let interval_of = |v: f64| {
+        breaks[1..]
+            .iter()
+            .position(|&upper| v < upper)
+            .unwrap_or(n_intervals - 1)
+    };
+    let mut used = vec![false; n_intervals];
+    for v in values.iter().flatten() {
+        used[interval_of(*v)] = true;
+    }
 
-            violin_density: violin_density_vec,
-            violin_density_y: violin_density_y_vec,
-            violin_quantile_values: violin_quantile_values_vec,
+    // Renumber the intervals that have rows
+    let mut level_index = vec![None; n_intervals];
+    let mut levels = Vec::new();
+    for i in (0..n_intervals).filter(|&i| used[i]) {
+        let lower = breaks[i];
+        let upper = breaks.get(i + 1).copied().unwrap_or(lower);
+        let close = if i + 1 == n_intervals { ']' } else { ')' };
+        level_index[i] = Some(levels.len());
+        levels.push(format!(
+            "[{}, {}{}",
+            format_bin_edge(lower),
+            format_bin_edge(upper),
+            close
+        ));
+    }
+    let level_of = values
+        .iter()
+        .map(|v| v.and_then(|v| level_index[interval_of(v)]))
+        .collect();
+    Ok((levels, level_of))
+}
 
-            heatmap_y_positions: hm_y_pos,
-            heatmap_fill_values: hm_fill,
-            heatmap_cell_width: hm_cw,
-            heatmap_cell_height: hm_ch,
+/// An interval edge for a facet label, to at most two decimals
+fn format_bin_edge(v: f64) -> String {
+    crate::scale::format_nice_number((v * 100.0).round() / 100.0)
+}
 
-            x_categories: if use_categorical {
-                Some(category_order.clone())
-            } else {
-                None
-            },
-            y_categories: hm_y_cats,
-            style,
-        });
-    }
+/// A facet column's distinct values (sorted by `label_order`) and each row's index
+/// into them; rows missing the cell get None. Without a column every row falls in one
+/// unnamed level.
+fn facet_levels(
+    data: &PlotData,
+    column: Option<&str>,
+    label_order: LabelOrder,
+) -> Result<(Vec<String>, Vec<Option<usize>>)> {
+    let Some(column) = column else {
+        return Ok((vec![String::new()], vec![Some(0); data.rows.len()]));
+    };
+    let col_idx = data
+        .headers
+        .iter()
+        .position(|h| h.eq_ignore_ascii_case(column))
+        .ok_or_else(|| anyhow!("Facet column '{}' not found", column))?;
 
-    Ok(LayerData { groups })
+    let mut levels: Vec<String> = data
+        .rows
+        .iter()
+        .filter_map(|row| row.get(col_idx).cloned())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    label_order.sort(&mut levels);
+    let index: HashMap<&str, usize> = levels
+        .iter()
+        .enumerate()
+        .map(|(i, level)| (level.as_str(), i))
+        .collect();
+    let level_of = data
+        .rows
+        .iter()
+        .map(|row| row.get(col_idx).map(|v| index[v.as_str()]))
+        .collect();
+    Ok((levels, level_of))
 }
 
-fn empty_group_data(key: String, style: RenderStyle) -> GroupData {
-    GroupData {
-        key,
-        x: vec![],
-        y: vec![],
-        y_start: vec![],
-        y_min: vec![],
-        y_max: vec![],
-        y_q1: vec![],
-        y_median: vec![],
-        y_q3: vec![],
-        outliers: vec![],
-        violin_density: vec![],
-        violin_density_y: vec![],
-        violin_quantile_values: vec![],
-        heatmap_y_positions: vec![],
-        heatmap_fill_values: vec![],
-        heatmap_cell_width: 0.0,
-        heatmap_cell_height: 0.0,
-        x_categories: None,
-        y_categories: None,
-        style,
-    }
+/// Value ranges of a layer's continuous color and size mappings
+#[derive(Debug, Clone, Copy, Default)]
+struct ContinuousDomains {
+    color: Option<(f64, f64)>,
+    size: Option<(f64, f64)>,
 }
 
-fn process_reference_layer(layer: &Layer) -> LayerData {
-    let mut group = match layer {
-        Layer::HLine(hline) => empty_group_data(
-            "default".to_string(),
-            RenderStyle::Line(LineStyle {
-                color: hline.color.clone(),
-                width: hline.width,
-                alpha: hline.alpha,
-            }),
-        ),
-        Layer::VLine(vline) => empty_group_data(
-            "default".to_string(),
-            RenderStyle::Line(LineStyle {
-                color: vline.color.clone(),
-                width: vline.width,
-                alpha: vline.alpha,
-            }),
-        ),
-        Layer::AbLine(abline) => empty_group_data(
-            "default".to_string(),
-            RenderStyle::Line(LineStyle {
-                color: abline.color.clone(),
-                width: abline.width,
-                alpha: abline.alpha,
-            }),
-        ),
-        Layer::Segment(segment) => empty_group_data(
-            "default".to_string(),
-            RenderStyle::Line(LineStyle {
-                color: segment.color.clone(),
-                width: segment.width,
-                alpha: segment.alpha,
-            }),
-        ),
-        _ => unreachable!("process_reference_layer only accepts reference layers"),
+/// Min/max of a point layer's aesthetic column when every value is numeric.
+/// Such columns map onto a gradient or size range instead of creating one
+/// group per distinct value. Missing cells are ignored unless `strict`.
+fn continuous_domain(
+    layer: &ResolvedLayer,
+    col: Option<&String>,
+    data: &PlotData,
+    store: &ColumnStore,
+    strict: bool,
+) -> Result<Option<(f64, f64)>> {
+    let Some(col) = col else {
+        return Ok(None);
     };
+    if !matches!(layer.original_layer, Layer::Point(_)) || data.rows.is_empty() {
+        return Ok(None);
+    }
 
-    match layer {
-        Layer::HLine(hline) => group.y.push(hline.yintercept),
-        Layer::VLine(vline) => group.x.push(vline.xintercept),
-        Layer::Segment(segment) => {
-            group.x.push(segment.x);
-            group.y.push(segment.y);
-            group.x.push(segment.xend);
-            group.y.push(segment.yend);
+    let idx = find_col_index(&data.headers, col)?;
+    let values: Option<Vec<f64>> = match store.column(idx) {
+        Column::Text => None,
+        Column::Numeric(values) if !strict => {
+            Some(values.iter().copied().filter(|v| !v.is_nan()).collect())
         }
-        _ => {}
-    }
+        // Missing cells hold NaN, but under strict they only count if they parse
+        Column::Numeric(_) => data
+            .rows
+            .iter()
+            .map(|row| row[idx].trim().parse::<f64>().ok())
+            .collect(),
+    };
 
-    LayerData {
-        groups: vec![group],
+    Ok(values.filter(|values| !values.is_empty()).map(|values| {
+        let range = DataRange::of(values);
+        (range.min, range.max)
+    }))
+}
+
+fn calculate_grid_dimensions(n_panels: usize, facet: Option<&ResolvedFacet>) -> (usize, usize) {
+    if let Some(f) = facet {
+        if let Some(cols) = f.ncol {
+            let rows = (n_panels as f64 / cols as f64).ceil() as usize;
+            return (rows, cols);
+        }
+        if let Some(rows) = f.nrow {
+            let cols = (n_panels as f64 / rows as f64).ceil() as usize;
+            return (rows, cols);
+        }
     }
+    // Default: square-ish
+    let cols = (n_panels as f64).sqrt().ceil() as usize;
+    let rows = (n_panels as f64 / cols as f64).ceil() as usize;
+    (rows, cols)
 }
 
-fn find_col_index(headers: &[String], name: &str) -> Result<usize> {
-    headers
+/// Process a single data partition (Panel)
+#[allow(clippy::too_many_arguments)]
+fn process_partition(
+    index: usize,
+    partition: &DataPartition,
+    sources: &[LayerSource],
+    x_categories: &[String],
+    group_levels: Option<&[Vec<Vec<String>>]>,
+    spec: &ResolvedSpec,
+    domains: &[ContinuousDomains],
+    options: &RenderOptions,
+) -> Result<PanelData> {
+    let mut layers = Vec::new();
+
+    for (i, ((layer_spec, source), layer_domains)) in
+        spec.layers.iter().zip(sources).zip(domains).enumerate()
+    {
+        let layer_data = process_layer(
+            layer_spec,
+            source.data,
+            source.rows.unwrap_or(&partition.rows),
+            source.store,
+            x_categories,
+            group_levels.map(|levels| levels[i].as_slice()),
+            spec.x_scale_spec.as_ref(),
+            *layer_domains,
+            &spec.color_scale,
+            spec.color_manual.as_ref(),
+            options,
+        )?;
+        layers.push(layer_data);
+    }
+
+    Ok(PanelData {
+        index,
+        layers,
+        annotations: Vec::new(),
+        shades: Vec::new(),
+    })
+}
+
+/// The columns that split a layer into groups, each with the aesthetics it maps, in
+/// color, size, shape, linetype, alpha order, then the `group` columns that map none.
+/// Aesthetics mapping the same column share it. Continuous color/size columns style
+/// points individually and do not group.
+fn group_columns(
+    aes: &ResolvedAesthetics,
+    domains: ContinuousDomains,
+) -> Vec<(&String, Vec<GroupAesthetic>)> {
+    let mapped = [
+        (
+            aes.color.as_ref().filter(|_| domains.color.is_none()),
+            GroupAesthetic::Color,
+        ),
+        (
+            aes.size.as_ref().filter(|_| domains.size.is_none()),
+            GroupAesthetic::Size,
+        ),
+        (aes.shape.as_ref(), GroupAesthetic::Shape),
+        (aes.linetype.as_ref(), GroupAesthetic::Linetype),
+        (aes.alpha.as_ref(), GroupAesthetic::Alpha),
+    ];
+    let mut columns: Vec<(&String, Vec<GroupAesthetic>)> = Vec::new();
+    for (col, aesthetic) in mapped {
+        let Some(col) = col else { continue };
+        match columns.iter_mut().find(|(seen, _)| *seen == col) {
+            Some((_, aesthetics)) => aesthetics.push(aesthetic),
+            None => columns.push((col, vec![aesthetic])),
+        }
+    }
+    for col in &aes.group {
+        if !columns.iter().any(|(seen, _)| *seen == col) {
+            columns.push((col, Vec::new()));
+        }
+    }
+    columns
+}
+
+/// A row's group key: its grouping cells joined with ", " ("default" when the layer
+/// doesn't group)
+fn group_key<'r>(row: &'r [String], group_idxs: &[usize]) -> Cow<'r, str> {
+    match group_idxs {
+        [] => Cow::Borrowed("default"),
+        [idx] => Cow::Borrowed(row[*idx].as_str()),
+        idxs => Cow::Owned(
+            idxs.iter()
+                .map(|&idx| row[idx].as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+        ),
+    }
+}
+
+/// Cells every row of a layer needs; a missing one drops the row (unless strict)
+fn required_columns(
+    layer_spec: &ResolvedLayer,
+    headers: &[String],
+    domains: ContinuousDomains,
+) -> Result<Vec<usize>> {
+    let aes = &layer_spec.aesthetics;
+    let layer = &layer_spec.original_layer;
+    let fade_col = match layer {
+        Layer::Point(p) => p.fade_by.as_ref().map(ColumnRef::to_string),
+        _ => None,
+    };
+    let columns = [
+        Some(&aes.x_col),
+        // Counting ignores y
+        aes.y_col
+            .as_ref()
+            .filter(|_| !matches!(layer.stat(), Stat::Count)),
+        aes.ymin_col.as_ref(),
+        aes.ymax_col.as_ref(),
+        aes.xend_col.as_ref(),
+        aes.yend_col.as_ref(),
+        fade_col.as_ref(),
+        aes.color.as_ref().filter(|_| domains.color.is_some()),
+        aes.size.as_ref().filter(|_| domains.size.is_some()),
+        aes.fill
+            .as_ref()
+            .filter(|_| matches!(layer, Layer::Heatmap(_))),
+    ];
+    columns
+        .into_iter()
+        .flatten()
+        .chain(group_columns(aes, domains).into_iter().map(|(col, _)| col))
+        .map(|col| find_col_index(headers, col))
+        .collect()
+}
+
+/// Every value each grouping column takes over the full data, ordered the way panels
+/// order their own. Facet panels assign palettes from these lists, so a group keeps its
+/// color, size, and shape in panels where other groups are missing.
+fn layer_group_levels(
+    layer_spec: &ResolvedLayer,
+    data: &PlotData,
+    domains: ContinuousDomains,
+    color_scale: &ColorScale,
+    options: &RenderOptions,
+) -> Result<Vec<Vec<String>>> {
+    let strict = options.strict;
+    if layer_spec.original_layer.is_reference() {
+        return Ok(Vec::new());
+    }
+    let columns = group_columns(&layer_spec.aesthetics, domains);
+    let group_idxs = columns
         .iter()
-        .position(|h| h.eq_ignore_ascii_case(name))
-        .ok_or_else(|| anyhow!("Column '{}' not found", name))
+        .map(|(col, _)| find_col_index(&data.headers, col))
+        .collect::<Result<Vec<_>>>()?;
+    let required_idxs = required_columns(layer_spec, &data.headers, domains)?;
+
+    let mut seen: Vec<HashSet<&str>> = vec![HashSet::new(); group_idxs.len()];
+    let mut levels: Vec<Vec<String>> = vec![Vec::new(); group_idxs.len()];
+    for row in &data.rows {
+        if !strict && required_idxs.iter().any(|&idx| is_missing(&row[idx])) {
+            continue;
+        }
+        for ((values, seen), &idx) in levels.iter_mut().zip(&mut seen).zip(&group_idxs) {
+            if seen.insert(row[idx].as_str()) {
+                values.push(row[idx].clone());
+            }
+        }
+    }
+    Ok(levels
+        .into_iter()
+        .zip(&columns)
+        .map(|(values, (_, aesthetics))| {
+            order_levels(values, aesthetics, color_scale, options.label_order)
+        })
+        .collect())
 }
 
-fn get_sorted_keys<V>(map: &HashMap<String, V>) -> Vec<String> {
-    let mut keys: Vec<String> = map.keys().cloned().collect();
-    keys.sort();
-    keys
+/// Fail when a styled grouping column has more than `max_groups` values (0 allows any
+/// number): thousands of palette entries make an unreadable plot and legend. `group:`
+/// columns style nothing and are exempt.
+fn check_group_count(
+    layer_spec: &ResolvedLayer,
+    domains: ContinuousDomains,
+    levels: &[Vec<String>],
+    store: &ColumnStore,
+    data: &PlotData,
+    max_groups: usize,
+) -> Result<()> {
+    if max_groups == 0 || layer_spec.original_layer.is_reference() {
+        return Ok(());
+    }
+    let columns = group_columns(&layer_spec.aesthetics, domains);
+    for ((col, aesthetics), values) in columns.into_iter().zip(levels) {
+        if aesthetics.is_empty() || values.len() <= max_groups {
+            continue;
+        }
+        let numeric = matches!(
+            store.column(find_col_index(&data.headers, col)?),
+            Column::Numeric(_)
+        );
+        let hint = if numeric {
+            "a numeric color or size column maps to a continuous scale on point(), or use group:"
+        } else {
+            "did you mean group:, which splits series without styling them"
+        };
+        anyhow::bail!(
+            "Column '{}' has {} distinct values, more than the {} groups allowed ({}; raise the limit with --max-groups)",
+            col,
+            format_count(values.len()),
+            max_groups,
+            hint
+        );
+    }
+    Ok(())
 }
 
-fn build_style(
-    group_key: String,
-    layer: &Layer,
-    aes: &ResolvedAesthetics,
-    color_map: &HashMap<String, String>,
-    size_map: &HashMap<String, f64>,
-    shape_map: &HashMap<String, String>,
-    alpha_map: &HashMap<String, f64>,
-    heatmap_data: Option<&HeatmapData>,
-) -> RenderStyle {
-    // Helper to pick color: GroupMapped ?? Fixed ?? Default
-    let pick_color =
-        |l_color: &Option<crate::parser::ast::AestheticValue<String>>| -> Option<String> {
-            if aes.color.is_some() && color_map.contains_key(&group_key) {
-                color_map.get(&group_key).cloned()
-            } else {
-                // Check fixed
-                match l_color {
-                    Some(crate::parser::ast::AestheticValue::Fixed(c)) => Some(c.clone()),
-                    _ => None,
+/// A count with thousands separators: 9871 -> "9,871"
+fn format_count(count: usize) -> String {
+    let digits = count.to_string();
+    let mut out = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(digit);
+    }
+    out
+}
+
+/// Each grouping column's aesthetics and levels, and each group's key parts
+type GroupColumns<'a> = (
+    &'a [Vec<GroupAesthetic>],
+    &'a [Vec<String>],
+    &'a HashMap<String, Vec<String>>,
+);
+
+/// Palette values keyed by group: `assign` runs over the levels of the column mapping
+/// `aesthetic` (empty when none does), and each group takes its own level's value
+fn group_palette<V: Clone>(
+    aesthetic: GroupAesthetic,
+    (group_aesthetics, levels, key_parts): GroupColumns,
+    assign: impl FnOnce(&[String]) -> HashMap<String, V>,
+) -> HashMap<String, V> {
+    let Some(col) = group_aesthetics
+        .iter()
+        .position(|aesthetics| aesthetics.contains(&aesthetic))
+    else {
+        return HashMap::new();
+    };
+    let palette = assign(&levels[col]);
+    key_parts
+        .iter()
+        .filter_map(|(key, parts)| Some((key.clone(), palette.get(&parts[col])?.clone())))
+        .collect()
+}
+
+/// Process a single layer: Extract, Group, Stack
+#[allow(clippy::too_many_arguments)]
+fn process_layer(
+    layer_spec: &ResolvedLayer,
+    data: &PlotData,
+    rows: &[usize],
+    store: &ColumnStore,
+    x_categories: &[String],
+    group_levels: Option<&[Vec<String>]>,
+    x_scale_spec: Option<&AxisScale>,
+    domains: ContinuousDomains,
+    color_scale: &ColorScale,
+    color_manual: Option<&ManualColorScale>,
+    options: &RenderOptions,
+) -> Result<LayerData> {
+    let strict = options.strict;
+    let aes = &layer_spec.aesthetics;
+    let color_domain = domains.color;
+    let size_domain = domains.size;
+
+    if layer_spec.original_layer.is_reference() {
+        return Ok(process_reference_layer(&layer_spec.original_layer));
+    }
+    if let Layer::Pie(pie) = &layer_spec.original_layer {
+        return process_pie_layer(
+            layer_spec,
+            pie,
+            data,
+            rows,
+            store,
+            x_categories,
+            color_scale.palette.as_deref(),
+            color_manual,
+            strict,
+        );
+    }
+
+    // 0. Layer filter: keep only the rows its predicate accepts
+    let mut filter_rejected_rows = 0;
+    let mut filter_matched_rows = None;
+    let filtered_rows: Vec<usize>;
+    let rows = match layer_spec.original_layer.filter() {
+        Some(expr) => {
+            let filter = RowFilter::new(expr, &data.headers)?;
+            let (kept, rejected) = filter.apply(data, rows.iter().copied());
+            filter_matched_rows = Some(kept.len());
+            filtered_rows = kept;
+            filter_rejected_rows = rejected;
+            &filtered_rows[..]
+        }
+        None => rows,
+    };
+
+    // 1. Identify Grouping Columns
+    let group_cols = group_columns(aes, domains);
+    let group_aesthetics: Vec<Vec<GroupAesthetic>> = group_cols
+        .iter()
+        .map(|(_, aesthetics)| aesthetics.clone())
+        .collect();
+
+    // 2. Extract Data (Grouped)
+    // We return a map: GroupKey -> (RawX, RawY, RawYMin, RawYMax)
+    // RawX is String to handle both numeric and categorical initially
+    let mut raw_groups: RawGroups = HashMap::new();
+
+    // Column Indices
+    let x_idx = find_col_index(&data.headers, &aes.x_col)?;
+    // Counting ignores y, so its cells are neither parsed nor required
+    let y_idx = match &aes.y_col {
+        Some(y) if !matches!(layer_spec.original_layer.stat(), Stat::Count) => {
+            Some(find_col_index(&data.headers, y)?)
+        }
+        _ => None,
+    };
+    let ymin_idx = if let Some(col) = &aes.ymin_col {
+        Some(find_col_index(&data.headers, &col.to_string())?)
+    } else {
+        None
+    };
+    let ymax_idx = if let Some(col) = &aes.ymax_col {
+        Some(find_col_index(&data.headers, col)?)
+    } else {
+        None
+    };
+    // Mapped segments add each row's (xend, yend) as a second point
+    let end_idxs = match (&aes.xend_col, &aes.yend_col) {
+        (Some(xend), Some(yend)) => Some((
+            find_col_index(&data.headers, xend)?,
+            find_col_index(&data.headers, yend)?,
+        )),
+        _ => None,
+    };
+    let fill_idx = if let Some(col) = &aes.fill {
+        Some(find_col_index(&data.headers, col)?)
+    } else {
+        None
+    };
+
+    let label_idx = if let Some(col) = &aes.label {
+        Some(find_col_index(&data.headers, col)?)
+    } else {
+        None
+    };
+    // Text labels travel alongside the raw groups (text layers only use the identity stat)
+    let mut raw_labels: HashMap<String, Vec<String>> = HashMap::new();
+
+    // Recency fade values travel the same way (points only use the identity stat)
+    let fade_spec = match &layer_spec.original_layer {
+        Layer::Point(p) => p.fade_by.as_ref().map(|col| (col, p.fade)),
+        _ => None,
+    };
+    let fade_idx = if let Some((col, _)) = fade_spec {
+        Some(find_col_index(&data.headers, &col.to_string())?)
+    } else {
+        None
+    };
+    let mut raw_fade: HashMap<String, Vec<f64>> = HashMap::new();
+
+    // Jittered points keep their source row indices, which seed each point's offset
+    // (identity stat only, so the indices still line up with the points)
+    let jitter_width = match &layer_spec.original_layer {
+        Layer::Point(p)
+            if p.position == PointPosition::Jitter && matches!(p.stat, Stat::Identity) =>
+        {
+            Some(p.jitter_width)
+        }
+        _ => None,
+    };
+    let mut raw_rows: HashMap<String, Vec<usize>> = HashMap::new();
+
+    // Continuous color values travel the same way (points only use the identity stat)
+    let color_value_idx = match (&aes.color, color_domain) {
+        (Some(col), Some(_)) => Some(find_col_index(&data.headers, col)?),
+        _ => None,
+    };
+    let mut raw_colors: HashMap<String, Vec<f64>> = HashMap::new();
+
+    let size_value_idx = match (&aes.size, size_domain) {
+        (Some(col), Some(_)) => Some(find_col_index(&data.headers, col)?),
+        _ => None,
+    };
+    let mut raw_sizes: HashMap<String, Vec<f64>> = HashMap::new();
+
+    let group_idxs = group_cols
+        .iter()
+        .map(|(col, _)| find_col_index(&data.headers, col))
+        .collect::<Result<Vec<_>>>()?;
+    // Each group's value in every grouping column, and the keys in first-appearance order
+    let mut key_parts: HashMap<String, Vec<String>> = HashMap::new();
+    let mut group_order: Vec<String> = Vec::new();
+
+    let is_heatmap = matches!(layer_spec.original_layer, Layer::Heatmap(_));
+
+    // For heatmap with categorical y, pre-build a y category mapping
+    let heatmap_y_cat_map: Option<HashMap<String, f64>> =
+        if let (true, Some(idx)) = (is_heatmap, y_idx) {
+            // Check if y values are numeric
+            let all_y_numeric = rows
+                .iter()
+                .filter(|&&row_idx| strict || !is_missing(&data.rows[row_idx][idx]))
+                .all(|&row_idx| numeric_cell(store, data, row_idx, idx).is_ok());
+            if !all_y_numeric {
+                // Build categorical mapping
+                let mut unique_y: Vec<String> = Vec::new();
+                let mut seen: std::collections::HashSet<String> = std::collections::HashSet::new();
+                for row in rows
+                    .iter()
+                    .map(|&row_idx| &data.rows[row_idx])
+                    .filter(|row| strict || !is_missing(&row[idx]))
+                {
+                    let val = row[idx].clone();
+                    if seen.insert(val.clone()) {
+                        unique_y.push(val);
+                    }
                 }
+                // Preserve data appearance order (no alphabetical sort)
+                Some(
+                    unique_y
+                        .iter()
+                        .enumerate()
+                        .map(|(i, s)| (s.clone(), i as f64))
+                        .collect(),
+                )
+            } else {
+                None
             }
+        } else {
+            None
         };
 
-    // Helper to pick size/width
-    let pick_size = |l_val: &Option<crate::parser::ast::AestheticValue<f64>>| -> Option<f64> {
-        if aes.size.is_some() && size_map.contains_key(&group_key) {
-            size_map.get(&group_key).copied()
-        } else {
-            match l_val {
-                Some(crate::parser::ast::AestheticValue::Fixed(v)) => Some(*v),
-                _ => None,
+    let required_idxs = required_columns(layer_spec, &data.headers, domains)?;
+    let mut skipped_rows = 0;
+    // Groups whose next point starts a new line segment after skipped rows
+    let mut pending_breaks: HashSet<String> = HashSet::new();
+    let mut raw_breaks: HashMap<String, Vec<usize>> = HashMap::new();
+
+    for &row_idx in rows {
+        let row = &data.rows[row_idx];
+        if strict {
+            check_finite_cells(store, data, row_idx, &required_idxs)?;
+        } else if required_idxs.iter().any(|&idx| is_missing(&row[idx])) {
+            skipped_rows += 1;
+            if group_idxs.iter().all(|&idx| !is_missing(&row[idx])) {
+                pending_breaks.insert(group_key(row, &group_idxs).into_owned());
             }
+            continue;
         }
-    };
 
-    // Helper to pick alpha
-    let pick_alpha = |l_val: &Option<crate::parser::ast::AestheticValue<f64>>| -> Option<f64> {
-        if aes.alpha.is_some() && alpha_map.contains_key(&group_key) {
-            alpha_map.get(&group_key).copied()
+        let x_str = row[x_idx].clone();
+        let y_val = if let Some(idx) = y_idx {
+            if let Some(ref cat_map) = heatmap_y_cat_map {
+                // Categorical y for heatmap: use index
+                *cat_map.get(&row[idx]).unwrap_or(&0.0)
+            } else {
+                aesthetic_cell(store, data, row_idx, idx, "y")?
+            }
         } else {
-            match l_val {
-                Some(crate::parser::ast::AestheticValue::Fixed(v)) => Some(*v),
-                _ => None,
+            0.0 // Default for histogram if not provided
+        };
+        // For heatmap, repurpose ymin slot to carry fill values
+        let ymin_val = if is_heatmap {
+            if let Some(idx) = fill_idx {
+                numeric_cell(store, data, row_idx, idx).unwrap_or(0.0)
+            } else {
+                0.0
+            }
+        } else {
+            if let Some(idx) = ymin_idx {
+                numeric_cell(store, data, row_idx, idx).unwrap_or(0.0)
+            } else {
+                0.0
             }
+        };
+        let ymax_val = if let Some(idx) = ymax_idx {
+            numeric_cell(store, data, row_idx, idx).unwrap_or(0.0)
+        } else {
+            0.0
+        };
+
+        let group_key = group_key(row, &group_idxs);
+        let group_key = group_key.as_ref();
+        if !key_parts.contains_key(group_key) {
+            let parts = group_idxs.iter().map(|&idx| row[idx].clone()).collect();
+            key_parts.insert(group_key.to_string(), parts);
+            group_order.push(group_key.to_string());
+        }
+
+        if let Some(idx) = fade_idx {
+            let value = match numeric_cell(store, data, row_idx, idx) {
+                Ok(v) => v,
+                Err(_) => parse_datetime_value(&row[idx])
+                    .map_err(|_| non_numeric(data, row_idx, idx, "fade_by"))?,
+            };
+            group_entry(&mut raw_fade, group_key).push(value);
+        }
+
+        if let Some(idx) = color_value_idx {
+            let value = aesthetic_cell(store, data, row_idx, idx, "color")?;
+            group_entry(&mut raw_colors, group_key).push(value);
+        }
+
+        if let Some(idx) = size_value_idx {
+            let value = aesthetic_cell(store, data, row_idx, idx, "size")?;
+            group_entry(&mut raw_sizes, group_key).push(value);
+        }
+
+        if let Some(idx) = label_idx {
+            group_entry(&mut raw_labels, group_key).push(row[idx].clone());
+        }
+
+        if jitter_width.is_some() {
+            group_entry(&mut raw_rows, group_key).push(row_idx);
+        }
+
+        let entry = group_entry(&mut raw_groups, group_key);
+        // Each segment is its own run of two points
+        let new_run = pending_breaks.remove(group_key) || end_idxs.is_some();
+        if new_run && !entry.0.is_empty() {
+            group_entry(&mut raw_breaks, group_key).push(entry.0.len());
+        }
+        entry.0.push(x_str);
+        entry.1.push(y_val);
+        entry.2.push(ymin_val);
+        entry.3.push(ymax_val);
+        if let Some((xend_idx, yend_idx)) = end_idxs {
+            let yend_val = aesthetic_cell(store, data, row_idx, yend_idx, "yend")?;
+            entry.0.push(row[xend_idx].clone());
+            entry.1.push(yend_val);
+            entry.2.push(0.0);
+            entry.3.push(0.0);
+        }
+    }
+
+    // Identity bars draw one bar per row, so repeated x values would overlap unseen
+    if let (Layer::Bar(_), Stat::Identity) =
+        (&layer_spec.original_layer, layer_spec.original_layer.stat())
+    {
+        check_unique_bar_x(&raw_groups)?;
+    }
+
+    // Apply Statistics
+    let raw_groups = apply_statistics(raw_groups, layer_spec.original_layer.stat())?;
+
+    // 3. Determine X-Axis Type (Numeric vs Categorical) by `analysis::x_axis_kind`
+    let is_bar = matches!(layer_spec.original_layer, Layer::Bar(_));
+
+    // Each grouping column's values in palette order
+    // (from the plot-wide levels when faceted, so every panel agrees)
+    let local_levels: Vec<Vec<String>>;
+    let levels = match group_levels {
+        Some(levels) => levels,
+        None => {
+            local_levels = group_aesthetics
+                .iter()
+                .enumerate()
+                .map(|(col, aesthetics)| {
+                    let mut seen = HashSet::new();
+                    let values = group_order
+                        .iter()
+                        .map(|key| &key_parts[key][col])
+                        .filter(|value| seen.insert(*value))
+                        .cloned()
+                        .collect();
+                    order_levels(values, aesthetics, color_scale, options.label_order)
+                })
+                .collect();
+            &local_levels
         }
     };
 
-    match layer {
-        Layer::Line(l) => RenderStyle::Line(LineStyle {
-            color: pick_color(&l.color),
-            width: pick_size(&l.width),
-            alpha: pick_alpha(&l.alpha),
-        }),
-        Layer::Point(p) => RenderStyle::Point(PointStyle {
-            color: pick_color(&p.color),
-            size: pick_size(&p.size),
-            shape: if aes.shape.is_some() && shape_map.contains_key(&group_key) {
-                shape_map.get(&group_key).cloned()
-            } else {
-                match &p.shape {
-                    Some(crate::parser::ast::AestheticValue::Fixed(s)) => Some(s.clone()),
-                    _ => None,
+    let mut raw_groups = raw_groups;
+    // Groups draw, stack, and dodge in level order
+    let sorted_group_keys = order_group_keys(&raw_groups, &key_parts, levels, options.label_order);
+    let all_x_strings: Vec<&String> = sorted_group_keys
+        .iter()
+        .filter_map(|key| raw_groups.get(key))
+        .flat_map(|d| d.x.iter())
+        .collect();
+    let x_kind = x_axis_kind(
+        &layer_spec.original_layer,
+        x_scale_spec,
+        all_x_strings.iter().map(|s| s.as_str()),
+    );
+    let use_datetime = x_kind == XAxisKind::DateTime;
+    let use_categorical = x_kind == XAxisKind::Categorical;
+
+    // `bar(x_continuous: true)` opts a numeric x out of the categorical default
+    let continuous_bar = matches!(&layer_spec.original_layer, Layer::Bar(b) if b.x_continuous);
+    if continuous_bar && use_categorical {
+        let value = all_x_strings
+            .iter()
+            .find(|s| numeric_value(s).is_none())
+            .map_or("", |s| s.as_str());
+        return Err(anyhow!(
+            "bar(x_continuous: true) needs numeric x values, but column '{}' has '{}'; remove x_continuous to draw one bar per category",
+            aes.x_col,
+            value
+        ));
+    }
+
+    // 4. Normalize X Values
+    // If categorical, we need a unified mapping for stacking/grouping
+    let mut x_category_map = HashMap::new();
+    let mut category_order = Vec::new();
+
+    if use_categorical {
+        // The plot-wide order (like ggplot2 factor levels), so every layer and
+        // panel places a category at the same index
+        category_order = x_categories.to_vec();
+        for (i, cat) in category_order.iter().enumerate() {
+            x_category_map.insert(cat.clone(), i as f64);
+        }
+        // Values the plot-wide pass skipped (missing-value tokens kept under strict) go last
+        for s in &all_x_strings {
+            if !x_category_map.contains_key(s.as_str()) {
+                x_category_map.insert((*s).clone(), category_order.len() as f64);
+                category_order.push((*s).clone());
+            }
+        }
+    }
+
+    // Positions of every x value on a continuous axis
+    let continuous_xs = || {
+        all_x_strings
+            .iter()
+            .map(|s| {
+                if use_datetime {
+                    parse_datetime_value(s)
+                } else {
+                    Ok(numeric_value(s).unwrap())
                 }
+            })
+            .collect::<Result<Vec<f64>>>()
+    };
+
+    // Jitter spans 40% of a category slot, or of the closest spacing between
+    // numeric x values, unless an absolute width is given
+    let jitter_span = match jitter_width {
+        Some(Some(width)) => Some(width),
+        Some(None) if use_categorical => Some(0.4),
+        Some(None) => Some(0.4 * min_spacing(&continuous_xs()?)),
+        None => None,
+    };
+
+    // Bars on a continuous axis fill the default share of the closest spacing between
+    // x values, unless `width:` gives one in x units
+    let continuous_bar_width = if is_bar && !use_categorical {
+        Some(DEFAULT_BAR_WIDTH * min_spacing(&continuous_xs()?))
+    } else {
+        None
+    };
+
+    // 5. Build Groups (Styles & Coordinates)
+    let mut groups = Vec::new();
+    // Assign Palettes
+    // Manual colors only apply when the groups come from a categorical color mapping
+    // Each aesthetic's palette runs over its own column's values
+    let palette = color_scale
+        .palette
+        .as_deref()
+        .and_then(ColorPalette::named)
+        .unwrap_or_else(ColorPalette::category10);
+    let by_group = (&group_aesthetics[..], levels, &key_parts);
+    let color_map = group_palette(GroupAesthetic::Color, by_group, |keys| match color_manual {
+        Some(manual) => palette.assign_manual_colors(keys, &manual.named, &manual.ordered),
+        None => palette.assign_colors(keys),
+    });
+    let size_map = group_palette(GroupAesthetic::Size, by_group, |keys| {
+        SizePalette::default_range().assign_sizes(keys)
+    });
+    let shape_map = group_palette(GroupAesthetic::Shape, by_group, |keys| {
+        ShapePalette::default_shapes().assign_shapes(keys)
+    });
+    let linetype_map = group_palette(GroupAesthetic::Linetype, by_group, |keys| {
+        LinetypePalette::default_linetypes().assign_linetypes(keys)
+    });
+    let alpha_map = group_palette(GroupAesthetic::Alpha, by_group, |keys| {
+        AlphaPalette::default_range().assign_alphas(keys)
+    });
+
+    // Prepare for Stacking (if needed)
+    // Map x position -> (top of the positive stack, bottom of the negative stack)
+    let mut stack_offsets: HashMap<StackKey, (f64, f64)> = HashMap::new();
+    let stack_key = |x: f64| {
+        if use_categorical {
+            StackKey::Category(x as usize)
+        } else {
+            StackKey::value(x)
+        }
+    };
+    let is_stacked = match &layer_spec.original_layer {
+        Layer::Bar(b) => matches!(b.position, BarPosition::Stack | BarPosition::Fill),
+        _ => false,
+    };
+
+    // Iterate groups in defined order (important for stacking order)
+    for key in sorted_group_keys {
+        let stat_data = raw_groups.remove(&key).unwrap();
+        let raw_x = &stat_data.x;
+        let raw_y = &stat_data.y;
+        let raw_ymin = &stat_data.ymin;
+        let raw_ymax = &stat_data.ymax;
+        let jitter_rows = raw_rows.remove(&key);
+
+        let mut x_floats = Vec::with_capacity(raw_x.len());
+        let mut y_starts = Vec::with_capacity(raw_x.len());
+        let mut y_ends = Vec::with_capacity(raw_x.len());
+        let mut y_mins = Vec::with_capacity(raw_x.len());
+        let mut y_maxs = Vec::with_capacity(raw_x.len());
+
+        for (i, x_s) in raw_x.iter().enumerate() {
+            let y_val = raw_y[i];
+            let raw_min = raw_ymin[i];
+            let raw_max = raw_ymax[i];
+
+            // Resolve X
+            let x_val = if use_categorical {
+                *x_category_map.get(x_s).unwrap() // Should exist
+            } else if use_datetime {
+                parse_datetime_value(x_s)?
+            } else {
+                numeric_value(x_s).unwrap() // Verified numeric earlier
+            };
+            let position = x_val;
+            let x_val = match (jitter_span, &jitter_rows) {
+                (Some(span), Some(rows)) => x_val + span * jitter_unit(rows[i]),
+                _ => x_val,
+            };
+            x_floats.push(x_val);
+
+            // Resolve Y (Stacking and Min/Max)
+            let (y_start, y_end, y_min, y_max) = if is_stacked {
+                // Positive values stack up from zero and negative values down from it
+                let (positive, negative) = stack_offsets
+                    .entry(stack_key(position))
+                    .or_insert((0.0, 0.0));
+                let offset = if y_val < 0.0 { negative } else { positive };
+                let start = *offset;
+                let end = start + y_val;
+                *offset = end;
+                (start, end, start.min(end), start.max(end))
+            } else if let Layer::Area(area) = &layer_spec.original_layer {
+                let baseline = area.baseline;
+                (baseline, y_val, baseline.min(y_val), baseline.max(y_val))
+            } else if let Layer::Spike(spike) = &layer_spec.original_layer {
+                let baseline = spike.baseline;
+                (baseline, y_val, baseline.min(y_val), baseline.max(y_val))
+            } else if matches!(layer_spec.original_layer, Layer::Ribbon(_))
+                || matches!(layer_spec.original_layer, Layer::LineRange(_))
+                || matches!(layer_spec.original_layer, Layer::ErrorBar(_))
+                || matches!(layer_spec.original_layer, Layer::PointRange(_))
+                || matches!(layer_spec.original_layer, Layer::CrossBar(_))
+                || matches!(layer_spec.original_layer, Layer::Boxplot(_))
+                || matches!(layer_spec.original_layer, Layer::Violin(_))
+            {
+                // Interval-like geoms, Boxplot, and Violin use raw ymin/ymax
+                (raw_min, raw_max, raw_min, raw_max)
+            } else {
+                // Line/Point/Bar(unstacked)
+                (0.0, y_val, y_val.min(0.0), y_val.max(0.0))
+            };
+
+            y_starts.push(y_start);
+            y_ends.push(y_end);
+            y_mins.push(y_min);
+            y_maxs.push(y_max);
+        }
+
+        // Boxplot and violin statistics move over whole; other layers leave them empty
+        let (y_q1s, y_medians, y_q3s, outliers_vec) = match stat_data.boxplot {
+            Some(bp) => (bp.q1, bp.median, bp.q3, bp.outliers),
+            None => Default::default(),
+        };
+        let (violin_density_vec, violin_density_y_vec, violin_quantile_values_vec) =
+            match stat_data.violin {
+                Some(vp) => (vp.density, vp.density_y, vp.quantile_values),
+                None => Default::default(),
+            };
+
+        // Build Style
+        let mut style = build_style(
+            key.clone(),
+            &layer_spec.original_layer,
+            aes,
+            &color_map,
+            &size_map,
+            &shape_map,
+            &linetype_map,
+            &alpha_map,
+            stat_data.heatmap.as_ref(),
+        );
+        if let (Some(width), RenderStyle::Bar(bar)) = (continuous_bar_width, &mut style) {
+            bar.width.get_or_insert(width);
+        }
+
+        // Extract heatmap data
+        let (hm_y_pos, hm_fill, hm_cw, hm_ch, hm_y_cats) = if let Some(hm) = stat_data.heatmap {
+            // If we pre-mapped categorical y, use those categories
+            let y_cats = if let Some(ref cat_map) = heatmap_y_cat_map {
+                let mut cats: Vec<(f64, String)> =
+                    cat_map.iter().map(|(s, &i)| (i, s.clone())).collect();
+                cats.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+                Some(cats.into_iter().map(|(_, s)| s).collect())
+            } else {
+                hm.y_categories
+            };
+            (
+                hm.y_positions,
+                hm.fill_values,
+                hm.cell_width,
+                hm.cell_height,
+                y_cats,
+            )
+        } else {
+            (vec![], vec![], 0.0, 0.0, None)
+        };
+
+        groups.push(GroupData {
+            key_parts: key_parts.remove(&key).unwrap_or_default(),
+            key: key.clone(),
+            x: x_floats,
+            y: y_ends, // Main value
+            y_start: y_starts,
+            y_min: y_mins,
+            y_max: y_maxs,
+
+            y_q1: y_q1s,
+            y_median: y_medians,
+            y_q3: y_q3s,
+            outliers: outliers_vec,
+
+            violin_density: violin_density_vec,
+            violin_density_y: violin_density_y_vec,
+            violin_quantile_values: violin_quantile_values_vec,
+
+            heatmap_y_positions: hm_y_pos,
+            heatmap_fill_values: hm_fill,
+            heatmap_cell_width: hm_cw,
+            heatmap_cell_height: hm_ch,
+
+            x_categories: if use_categorical {
+                Some(category_order.clone())
+            } else {
+                None
             },
-            alpha: pick_alpha(&p.alpha),
-        }),
-        Layer::Bar(b) => RenderStyle::Bar(BarStyle {
-            color: pick_color(&b.color),
-            width: pick_size(&b.width),
-            alpha: pick_alpha(&b.alpha),
-        }),
-        Layer::Area(a) => RenderStyle::Area(RibbonStyle {
-            color: pick_color(&a.color),
-            alpha: pick_alpha(&a.alpha),
-        }),
-        Layer::Rug(r) => RenderStyle::Rug {
-            style: LineStyle {
-                color: pick_color(&r.color),
-                width: pick_size(&r.width),
-                alpha: pick_alpha(&r.alpha),
-            },
-            sides: r.sides.clone(),
-            length: r.length,
-        },
-        Layer::Spike(s) => RenderStyle::Spike(LineStyle {
-            color: pick_color(&s.color),
-            width: pick_size(&s.width),
-            alpha: pick_alpha(&s.alpha),
-        }),
-        Layer::LineRange(l) => RenderStyle::LineRange(LineStyle {
-            color: pick_color(&l.color),
-            width: pick_size(&l.width),
-            alpha: pick_alpha(&l.alpha),
-        }),
-        Layer::ErrorBar(e) => RenderStyle::ErrorBar {
-            style: LineStyle {
-                color: pick_color(&e.color),
-                width: pick_size(&e.line_width),
-                alpha: pick_alpha(&e.alpha),
+            y_categories: hm_y_cats,
+            labels: raw_labels.remove(&key).unwrap_or_default(),
+            point_alphas: match (raw_fade.remove(&key), fade_spec) {
+                (Some(values), Some((_, direction))) => compute_fade_alphas(&values, direction),
+                _ => vec![],
             },
-            width: e.width,
-        },
-        Layer::PointRange(p) => RenderStyle::PointRange {
-            line_style: LineStyle {
-                color: pick_color(&p.color),
-                width: pick_size(&p.width),
-                alpha: pick_alpha(&p.alpha),
+            color_values: raw_colors.remove(&key).unwrap_or_default(),
+            size_values: raw_sizes.remove(&key).unwrap_or_default(),
+            // Only the identity stat keeps rows in data order, so breaks still line up
+            line_breaks: if matches!(layer_spec.original_layer.stat(), Stat::Identity) {
+                raw_breaks.remove(&key).unwrap_or_default()
+            } else {
+                vec![]
             },
-            point_style: PointStyle {
-                color: pick_color(&p.color),
-                size: pick_size(&p.size),
-                shape: if aes.shape.is_some() && shape_map.contains_key(&group_key) {
-                    shape_map.get(&group_key).cloned()
-                } else {
-                    match &p.shape {
-                        Some(crate::parser::ast::AestheticValue::Fixed(s)) => Some(s.clone()),
-                        _ => None,
-                    }
+            style,
+        });
+    }
+
+    if matches!(&layer_spec.original_layer, Layer::Bar(b) if b.position == BarPosition::Fill) {
+        normalize_fill_stacks(&mut groups, &stack_offsets, stack_key);
+    }
+
+    Ok(LayerData {
+        groups,
+        color_domain,
+        size_domain,
+        skipped_rows,
+        filter_rejected_rows,
+        filter_matched_rows,
+        group_levels: levels.to_vec(),
+        group_aesthetics,
+    })
+}
+
+/// A filter expression bound to column indices
+struct RowFilter<'a> {
+    expr: &'a FilterExpr,
+    columns: HashMap<&'a str, usize>,
+}
+
+impl<'a> RowFilter<'a> {
+    fn new(expr: &'a FilterExpr, headers: &[String]) -> Result<Self> {
+        let mut columns = HashMap::new();
+        for column in expr.columns() {
+            columns.insert(column, find_col_index(headers, column)?);
+        }
+        Ok(Self { expr, columns })
+    }
+
+    /// Split `rows` into the accepted rows and a count of rows rejected only because a
+    /// numeric comparison met a non-numeric cell
+    fn apply(&self, data: &PlotData, rows: impl IntoIterator<Item = usize>) -> (Vec<usize>, usize) {
+        let mut kept = Vec::new();
+        let mut unparsable = 0;
+        for row_idx in rows {
+            match self.matches(self.expr, &data.rows[row_idx]) {
+                Some(true) => kept.push(row_idx),
+                Some(false) => {}
+                None => unparsable += 1,
+            }
+        }
+        (kept, unparsable)
+    }
+
+    /// Three-valued match: None when the outcome hinges on a cell that isn't a number
+    fn matches(&self, expr: &FilterExpr, row: &[String]) -> Option<bool> {
+        match expr {
+            FilterExpr::And(left, right) => match self.matches(left, row) {
+                Some(false) => Some(false),
+                l => match (l, self.matches(right, row)?) {
+                    (_, false) => Some(false),
+                    (l, true) => l,
                 },
-                alpha: pick_alpha(&p.alpha),
-            },
-        },
-        Layer::CrossBar(c) => RenderStyle::CrossBar {
-            box_style: BarStyle {
-                color: pick_color(&c.color),
-                alpha: pick_alpha(&c.alpha).or(Some(0.45)),
-                width: None,
             },
-            line_style: LineStyle {
-                color: pick_color(&c.color),
-                width: pick_size(&c.line_width),
-                alpha: pick_alpha(&c.alpha).or(Some(1.0)),
+            FilterExpr::Or(left, right) => match self.matches(left, row) {
+                Some(true) => Some(true),
+                l => match (l, self.matches(right, row)?) {
+                    (_, true) => Some(true),
+                    (l, false) => l,
+                },
             },
-            width: c.width,
-        },
-        Layer::Ribbon(r) => RenderStyle::Ribbon(RibbonStyle {
-            color: pick_color(&r.color),
-            alpha: pick_alpha(&r.alpha),
+            FilterExpr::Compare { column, op, value } => {
+                let cell = row[self.columns[column.as_str()]].trim();
+                let ordering = match value {
+                    FilterValue::Number(n) => {
+                        if is_missing(cell) {
+                            return Some(false);
+                        }
+                        cell.parse::<f64>().ok()?.partial_cmp(n)?
+                    }
+                    FilterValue::Text(s) => cell.cmp(s.as_str()),
+                };
+                Some(match op {
+                    CompareOp::Eq => ordering.is_eq(),
+                    CompareOp::Ne => ordering.is_ne(),
+                    CompareOp::Lt => ordering.is_lt(),
+                    CompareOp::Le => ordering.is_le(),
+                    CompareOp::Gt => ordering.is_gt(),
+                    CompareOp::Ge => ordering.is_ge(),
+                })
+            }
+        }
+    }
+}
+
+/// The x position a stacked segment belongs to: the category's index, or the exact
+/// bits of a numeric or datetime x, so no formatting round trip can split a stack
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum StackKey {
+    Category(usize),
+    Value(u64),
+}
+
+impl StackKey {
+    fn value(x: f64) -> Self {
+        // Adding 0.0 turns -0.0 into 0.0 so both land in one stack
+        StackKey::Value((x + 0.0).to_bits())
+    }
+}
+
+/// Rescale stacked segments so each x position's stack spans 0..1 (and 0..-1 for
+/// negative values, which stack separately). Stacks whose total is zero collapse to
+/// zero height instead of dividing by zero.
+fn normalize_fill_stacks(
+    groups: &mut [GroupData],
+    stack_offsets: &HashMap<StackKey, (f64, f64)>,
+    stack_key: impl Fn(f64) -> StackKey,
+) {
+    for group in groups {
+        for i in 0..group.x.len() {
+            let Some(&(positive, negative)) = stack_offsets.get(&stack_key(group.x[i])) else {
+                continue;
+            };
+            let total = if group.y[i] < group.y_start[i] {
+                -negative
+            } else {
+                positive
+            };
+            let factor = if total > 0.0 { 1.0 / total } else { 0.0 };
+            group.y_start[i] *= factor;
+            group.y[i] *= factor;
+            group.y_min[i] *= factor;
+            group.y_max[i] *= factor;
+        }
+    }
+}
+
+fn empty_group_data(key: String, style: RenderStyle) -> GroupData {
+    GroupData {
+        key,
+        key_parts: vec![],
+        x: vec![],
+        y: vec![],
+        y_start: vec![],
+        y_min: vec![],
+        y_max: vec![],
+        y_q1: vec![],
+        y_median: vec![],
+        y_q3: vec![],
+        outliers: vec![],
+        violin_density: vec![],
+        violin_density_y: vec![],
+        violin_quantile_values: vec![],
+        heatmap_y_positions: vec![],
+        heatmap_fill_values: vec![],
+        heatmap_cell_width: 0.0,
+        heatmap_cell_height: 0.0,
+        x_categories: None,
+        y_categories: None,
+        labels: vec![],
+        point_alphas: vec![],
+        color_values: vec![],
+        size_values: vec![],
+        line_breaks: vec![],
+        style,
+    }
+}
+
+fn process_reference_layer(layer: &Layer) -> LayerData {
+    let mut group = match layer {
+        Layer::HLine(hline) => empty_group_data(
+            "default".to_string(),
+            RenderStyle::Line(LineStyle {
+                color: hline.color.clone(),
+                width: hline.width,
+                alpha: hline.alpha,
+                linetype: None,
+                arrow: false,
+                simplify: false,
+            }),
+        ),
+        Layer::VLine(vline) => empty_group_data(
+            "default".to_string(),
+            RenderStyle::Line(LineStyle {
+                color: vline.color.clone(),
+                width: vline.width,
+                alpha: vline.alpha,
+                linetype: None,
+                arrow: false,
+                simplify: false,
+            }),
+        ),
+        Layer::AbLine(abline) => empty_group_data(
+            "default".to_string(),
+            RenderStyle::Line(LineStyle {
+                color: abline.color.clone(),
+                width: abline.width,
+                alpha: abline.alpha,
+                linetype: None,
+                arrow: false,
+                simplify: false,
+            }),
+        ),
+        Layer::Segment(segment) => empty_group_data(
+            "default".to_string(),
+            RenderStyle::Line(LineStyle {
+                color: match &segment.color {
+                    Some(crate::parser::ast::AestheticValue::Fixed(c)) => Some(c.clone()),
+                    _ => None,
+                },
+                width: segment.width,
+                alpha: segment.alpha,
+                linetype: None,
+                arrow: segment.arrow,
+                simplify: false,
+            }),
+        ),
+        _ => unreachable!("process_reference_layer only accepts reference layers"),
+    };
+
+    match layer {
+        Layer::HLine(hline) => group.y.push(hline.yintercept),
+        Layer::VLine(vline) => group.x.push(vline.xintercept),
+        Layer::Segment(segment) => {
+            let (x, y, xend, yend) = segment.fixed_endpoints();
+            group.x.push(x);
+            group.y.push(y);
+            group.x.push(xend);
+            group.y.push(yend);
+        }
+        _ => {}
+    }
+
+    LayerData {
+        groups: vec![group],
+        color_domain: None,
+        size_domain: None,
+        skipped_rows: 0,
+        filter_rejected_rows: 0,
+        filter_matched_rows: None,
+        group_aesthetics: vec![],
+        group_levels: vec![],
+    }
+}
+
+/// Sum each x category's y into one slice and lay the slices clockwise from 12
+/// o'clock in category order. With `other:`, slices under that share of the total
+/// merge into a last "Other" slice, as long as at least two of them do. Colors are
+/// assigned over the plot-wide categories so a slice keeps its color across facets.
+#[allow(clippy::too_many_arguments)]
+fn process_pie_layer(
+    layer_spec: &ResolvedLayer,
+    pie: &PieLayer,
+    data: &PlotData,
+    rows: &[usize],
+    store: &ColumnStore,
+    x_categories: &[String],
+    color_palette: Option<&str>,
+    color_manual: Option<&ManualColorScale>,
+    strict: bool,
+) -> Result<LayerData> {
+    const OTHER: &str = "Other";
+
+    let mut filter_rejected_rows = 0;
+    let mut filter_matched_rows = None;
+    let filtered_rows: Vec<usize>;
+    let rows = match &pie.filter {
+        Some(expr) => {
+            let filter = RowFilter::new(expr, &data.headers)?;
+            let (kept, rejected) = filter.apply(data, rows.iter().copied());
+            filter_matched_rows = Some(kept.len());
+            filtered_rows = kept;
+            filter_rejected_rows = rejected;
+            &filtered_rows[..]
+        }
+        None => rows,
+    };
+
+    let aes = &layer_spec.aesthetics;
+    let x_idx = find_col_index(&data.headers, &aes.x_col)?;
+    let y_col = aes
+        .y_col
+        .as_ref()
+        .ok_or_else(|| anyhow!("pie() needs a y column for the slice sizes"))?;
+    let y_idx = find_col_index(&data.headers, y_col)?;
+
+    let mut labels: Vec<String> = x_categories.to_vec();
+    let mut values = vec![0.0; labels.len()];
+    let mut skipped_rows = 0;
+    for &row_idx in rows {
+        let row = &data.rows[row_idx];
+        if strict {
+            check_finite_cells(store, data, row_idx, &[x_idx, y_idx])?;
+        } else if is_missing(&row[x_idx]) || is_missing(&row[y_idx]) {
+            skipped_rows += 1;
+            continue;
+        }
+        let value = aesthetic_cell(store, data, row_idx, y_idx, "y")?;
+        if value < 0.0 {
+            return Err(anyhow!(
+                "pie() slices can't be negative: '{}' in column '{}' (row {})",
+                row[y_idx],
+                data.headers[y_idx],
+                row_idx + 1
+            ));
+        }
+        // Values the plot-wide pass skipped (missing-value tokens kept under strict) go last
+        let i = match labels.iter().position(|label| *label == row[x_idx]) {
+            Some(i) => i,
+            None => {
+                labels.push(row[x_idx].clone());
+                values.push(0.0);
+                labels.len() - 1
+            }
+        };
+        values[i] += value;
+    }
+
+    let palette = color_palette
+        .and_then(ColorPalette::named)
+        .unwrap_or_else(ColorPalette::category10);
+    let mut palette_keys = labels.clone();
+    if pie.other.is_some() && !palette_keys.iter().any(|label| label == OTHER) {
+        palette_keys.push(OTHER.to_string());
+    }
+    let color_map = match color_manual {
+        Some(manual) => palette.assign_manual_colors(&palette_keys, &manual.named, &manual.ordered),
+        None => palette.assign_colors(&palette_keys),
+    };
+
+    let mut slices: Vec<(String, f64)> = labels
+        .into_iter()
+        .zip(values)
+        .filter(|(_, value)| *value > 0.0)
+        .collect();
+    let total: f64 = slices.iter().map(|(_, value)| value).sum();
+    if let Some(threshold) = pie.other {
+        let is_small = |value: f64| value / total < threshold;
+        if slices.iter().filter(|(_, value)| is_small(*value)).count() >= 2 {
+            // A category already named "Other" joins the merged slice
+            let (merged, kept): (Vec<_>, Vec<_>) = slices
+                .into_iter()
+                .partition(|(label, value)| is_small(*value) || label == OTHER);
+            slices = kept;
+            slices.push((OTHER.to_string(), merged.iter().map(|(_, v)| v).sum()));
+        }
+    }
+
+    let mut groups = Vec::with_capacity(slices.len());
+    let mut cumulative = 0.0;
+    for (label, value) in slices {
+        let start_angle = std::f64::consts::TAU * cumulative / total;
+        cumulative += value;
+        let end_angle = std::f64::consts::TAU * cumulative / total;
+        let style = RenderStyle::Pie {
+            style: RibbonStyle {
+                color: color_map.get(&label).cloned(),
+                alpha: pie.alpha,
+            },
+            start_angle,
+            end_angle,
+            inner_radius: pie.donut.unwrap_or(0.0),
+        };
+        groups.push(empty_group_data(label, style));
+    }
+
+    Ok(LayerData {
+        groups,
+        color_domain: None,
+        size_domain: None,
+        skipped_rows,
+        filter_rejected_rows,
+        filter_matched_rows,
+        group_aesthetics: vec![],
+        group_levels: vec![],
+    })
+}
+
+/// Map fade values linearly onto alpha within a group (0.15 faded end -> 1.0 emphasized end)
+fn compute_fade_alphas(values: &[f64], direction: FadeDirection) -> Vec<f64> {
+    const MIN_ALPHA: f64 = 0.15;
+
+    let DataRange { min, max } = DataRange::of(values.iter().copied());
+    let span = max - min;
+
+    values
+        .iter()
+        .map(|&v| {
+            let t = if span > 0.0 { (v - min) / span } else { 1.0 };
+            let t = match direction {
+                FadeDirection::Oldest => t,
+                FadeDirection::Newest => 1.0 - t,
+            };
+            MIN_ALPHA + (1.0 - MIN_ALPHA) * t
+        })
+        .collect()
+}
+
+/// The value for a group key, inserting a default on first use.
+/// Unlike `entry`, this only allocates the key the first time it is seen.
+fn group_entry<'m, V: Default>(map: &'m mut HashMap<String, V>, key: &str) -> &'m mut V {
+    if !map.contains_key(key) {
+        map.insert(key.to_string(), V::default());
+    }
+    map.get_mut(key).unwrap()
+}
+
+/// A cell's number from the column store. Text columns, and missing cells reached
+/// under `strict`, parse the raw cell so errors name the offending value.
+fn numeric_cell(
+    store: &ColumnStore,
+    data: &PlotData,
+    row_idx: usize,
+    col_idx: usize,
+) -> Result<f64, std::num::ParseFloatError> {
+    match store.column(col_idx) {
+        Column::Numeric(values) if !values[row_idx].is_nan() => Ok(values[row_idx]),
+        _ => data.rows[row_idx][col_idx].trim().parse::<f64>(),
+    }
+}
+
+/// `numeric_cell` for a column mapped to a numeric aesthetic, whose text must parse
+fn aesthetic_cell(
+    store: &ColumnStore,
+    data: &PlotData,
+    row_idx: usize,
+    col_idx: usize,
+    aesthetic: &str,
+) -> Result<f64> {
+    numeric_cell(store, data, row_idx, col_idx)
+        .map_err(|_| non_numeric(data, row_idx, col_idx, aesthetic))
+}
+
+fn non_numeric(data: &PlotData, row_idx: usize, col_idx: usize, aesthetic: &str) -> anyhow::Error {
+    GramGraphError::NonNumericValue {
+        aesthetic: aesthetic.to_string(),
+        column: data.headers[col_idx].clone(),
+        row: row_idx + 1,
+        value: data.rows[row_idx][col_idx].clone(),
+    }
+    .into()
+}
+
+/// Under `strict`, nothing drops a NaN or infinite cell, so one in a numeric column
+/// is an error before it can reach a range
+fn check_finite_cells(
+    store: &ColumnStore,
+    data: &PlotData,
+    row_idx: usize,
+    col_idxs: &[usize],
+) -> Result<()> {
+    for &idx in col_idxs {
+        let cell = &data.rows[row_idx][idx];
+        if matches!(store.column(idx), Column::Numeric(_)) && is_non_finite(cell) {
+            return Err(anyhow!(
+                "Non-finite value '{}' in column '{}' (row {}); without --strict such rows are skipped",
+                cell.trim(),
+                data.headers[idx],
+                row_idx + 1
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn find_col_index(headers: &[String], name: &str) -> Result<usize> {
+    headers
+        .iter()
+        .position(|h| h.eq_ignore_ascii_case(name))
+        .ok_or_else(|| anyhow!("Column '{}' not found", name))
+}
+
+fn get_sorted_keys<V>(map: &HashMap<String, V>) -> Vec<String> {
+    let mut keys: Vec<String> = map.keys().cloned().collect();
+    keys.sort();
+    keys
+}
+
+/// Group keys ordered by each grouping column's position in `levels`; keys without
+/// key parts sort after the rest, in label order
+fn order_group_keys<V>(
+    groups: &HashMap<String, V>,
+    key_parts: &HashMap<String, Vec<String>>,
+    levels: &[Vec<String>],
+    label_order: LabelOrder,
+) -> Vec<String> {
+    let ranks: Vec<HashMap<&str, usize>> = levels
+        .iter()
+        .map(|values| {
+            values
+                .iter()
+                .enumerate()
+                .map(|(i, v)| (v.as_str(), i))
+                .collect()
+        })
+        .collect();
+    let rank = |key: &String| -> Option<Vec<usize>> {
+        key_parts
+            .get(key)?
+            .iter()
+            .zip(&ranks)
+            .map(|(part, ranks)| ranks.get(part.as_str()).copied())
+            .collect()
+    };
+    let mut keys: Vec<String> = groups.keys().cloned().collect();
+    label_order.sort(&mut keys);
+    keys.sort_by_cached_key(|key| rank(key).map_or((1, Vec::new()), |rank| (0, rank)));
+    keys
+}
+
+/// A grouping column's values, given in first-appearance order, in palette order:
+/// sorted by `label_order`, except that the color column follows
+/// `scale_color(order:, levels:)`
+fn order_levels(
+    mut values: Vec<String>,
+    aesthetics: &[GroupAesthetic],
+    scale: &ColorScale,
+    label_order: LabelOrder,
+) -> Vec<String> {
+    let is_color = aesthetics.contains(&GroupAesthetic::Color);
+    if !is_color || scale.order != Some(CategoryOrder::Appearance) {
+        label_order.sort(&mut values);
+    }
+    if is_color && !scale.levels.is_empty() {
+        values.sort_by_key(|value| {
+            scale
+                .levels
+                .iter()
+                .position(|level| level == value)
+                .unwrap_or(scale.levels.len())
+        });
+    }
+    values
+}
+
+#[allow(clippy::too_many_arguments)]
+fn build_style(
+    group_key: String,
+    layer: &Layer,
+    aes: &ResolvedAesthetics,
+    color_map: &HashMap<String, String>,
+    size_map: &HashMap<String, f64>,
+    shape_map: &HashMap<String, String>,
+    linetype_map: &HashMap<String, String>,
+    alpha_map: &HashMap<String, f64>,
+    heatmap_data: Option<&HeatmapData>,
+) -> RenderStyle {
+    // Helper to pick color: GroupMapped ?? Fixed ?? Default
+    let pick_color =
+        |l_color: &Option<crate::parser::ast::AestheticValue<String>>| -> Option<String> {
+            if aes.color.is_some() && color_map.contains_key(&group_key) {
+                color_map.get(&group_key).cloned()
+            } else {
+                // Check fixed
+                match l_color {
+                    Some(crate::parser::ast::AestheticValue::Fixed(c)) => Some(c.clone()),
+                    _ => None,
+                }
+            }
+        };
+
+    // Helper to pick size/width
+    let pick_size = |l_val: &Option<crate::parser::ast::AestheticValue<f64>>| -> Option<f64> {
+        if aes.size.is_some() && size_map.contains_key(&group_key) {
+            size_map.get(&group_key).copied()
+        } else {
+            match l_val {
+                Some(crate::parser::ast::AestheticValue::Fixed(v)) => Some(*v),
+                _ => None,
+            }
+        }
+    };
+
+    // Helper to pick alpha
+    let pick_alpha = |l_val: &Option<crate::parser::ast::AestheticValue<f64>>| -> Option<f64> {
+        if aes.alpha.is_some() && alpha_map.contains_key(&group_key) {
+            alpha_map.get(&group_key).copied()
+        } else {
+            match l_val {
+                Some(crate::parser::ast::AestheticValue::Fixed(v)) => Some(*v),
+                _ => None,
+            }
+        }
+    };
+
+    match layer {
+        Layer::Line(l) => RenderStyle::Line(LineStyle {
+            color: pick_color(&l.color),
+            width: pick_size(&l.width),
+            alpha: pick_alpha(&l.alpha),
+            linetype: if aes.linetype.is_some() && linetype_map.contains_key(&group_key) {
+                linetype_map.get(&group_key).cloned()
+            } else {
+                match &l.linetype {
+                    Some(crate::parser::ast::AestheticValue::Fixed(lt)) => Some(lt.clone()),
+                    _ => None,
+                }
+            },
+            arrow: false,
+            simplify: l.simplify,
         }),
-        Layer::Boxplot(b) => RenderStyle::Boxplot(crate::graph::BoxplotStyle {
+        Layer::Point(p) => RenderStyle::Point(PointStyle {
+            color: pick_color(&p.color),
+            size: pick_size(&p.size),
+            shape: if aes.shape.is_some() && shape_map.contains_key(&group_key) {
+                shape_map.get(&group_key).cloned()
+            } else {
+                match &p.shape {
+                    Some(crate::parser::ast::AestheticValue::Fixed(s)) => Some(s.clone()),
+                    _ => None,
+                }
+            },
+            alpha: pick_alpha(&p.alpha),
+            fill: p.fill.clone(),
+            stroke_width: p.stroke,
+        }),
+        Layer::Bar(b) => RenderStyle::Bar(BarStyle {
             color: pick_color(&b.color),
             width: pick_size(&b.width),
             alpha: pick_alpha(&b.alpha),
-            outlier_color: b.outlier_color.clone(),
-            outlier_size: b.outlier_size,
+            border: b.border.clone(),
+            border_width: b.border_width,
+        }),
+        Layer::Area(a) => RenderStyle::Area(RibbonStyle {
+            color: pick_color(&a.color),
+            alpha: pick_alpha(&a.alpha),
+        }),
+        Layer::Rug(r) => RenderStyle::Rug {
+            style: LineStyle {
+                color: pick_color(&r.color),
+                width: pick_size(&r.width),
+                alpha: pick_alpha(&r.alpha),
+                linetype: None,
+                arrow: false,
+                simplify: false,
+            },
+            sides: r.sides.clone(),
+            length: r.length,
+        },
+        Layer::Spike(s) => RenderStyle::Spike(LineStyle {
+            color: pick_color(&s.color),
+            width: pick_size(&s.width),
+            alpha: pick_alpha(&s.alpha),
+            linetype: None,
+            arrow: false,
+            simplify: false,
+        }),
+        Layer::LineRange(l) => RenderStyle::LineRange(LineStyle {
+            color: pick_color(&l.color),
+            width: pick_size(&l.width),
+            alpha: pick_alpha(&l.alpha),
+            linetype: None,
+            arrow: false,
+            simplify: false,
+        }),
+        Layer::ErrorBar(e) => RenderStyle::ErrorBar {
+            style: LineStyle {
+                color: pick_color(&e.color),
+                width: pick_size(&e.line_width),
+                alpha: pick_alpha(&e.alpha),
+                linetype: None,
+                arrow: false,
+                simplify: false,
+            },
+            width: e.width,
+        },
+        Layer::PointRange(p) => RenderStyle::PointRange {
+            line_style: LineStyle {
+                color: pick_color(&p.color),
+                width: pick_size(&p.width),
+                alpha: pick_alpha(&p.alpha),
+                linetype: None,
+                arrow: false,
+                simplify: false,
+            },
+            point_style: PointStyle {
+                color: pick_color(&p.color),
+                size: pick_size(&p.size),
+                shape: if aes.shape.is_some() && shape_map.contains_key(&group_key) {
+                    shape_map.get(&group_key).cloned()
+                } else {
+                    match &p.shape {
+                        Some(crate::parser::ast::AestheticValue::Fixed(s)) => Some(s.clone()),
+                        _ => None,
+                    }
+                },
+                alpha: pick_alpha(&p.alpha),
+                fill: None,
+                stroke_width: None,
+            },
+        },
+        Layer::CrossBar(c) => RenderStyle::CrossBar {
+            box_style: BarStyle {
+                color: pick_color(&c.color),
+                alpha: pick_alpha(&c.alpha).or(Some(0.45)),
+                width: None,
+                border: None,
+                border_width: None,
+            },
+            line_style: LineStyle {
+                color: pick_color(&c.color),
+                width: pick_size(&c.line_width),
+                alpha: pick_alpha(&c.alpha).or(Some(1.0)),
+                linetype: None,
+                arrow: false,
+                simplify: false,
+            },
+            width: c.width,
+        },
+        Layer::Ribbon(r) => RenderStyle::Ribbon(RibbonStyle {
+            color: pick_color(&r.color),
+            alpha: pick_alpha(&r.alpha),
+        }),
+        Layer::Boxplot(b) => RenderStyle::Boxplot(crate::graph::BoxplotStyle {
+            color: pick_color(&b.color),
+            width: pick_size(&b.width),
+            alpha: pick_alpha(&b.alpha),
+            outlier_color: b.outlier_color.clone(),
+            outlier_size: b.outlier_size,
             outlier_shape: b.outlier_shape.clone(),
         }),
         Layer::Violin(v) => RenderStyle::Violin(ViolinStyle {
@@ -751,1057 +2743,3598 @@ fn build_style(
         Layer::Density(d) => RenderStyle::Density(DensityStyle {
             color: pick_color(&d.color),
             alpha: pick_alpha(&d.alpha),
+            fill: d.fill.unwrap_or(true),
         }),
         Layer::Heatmap(h) => {
             // Calculate value range from heatmap data
             let (vmin, vmax) = if let Some(hm) = heatmap_data {
-                let min = hm.fill_values.iter().fold(f64::INFINITY, |a, &b| a.min(b));
-                let max = hm
-                    .fill_values
-                    .iter()
-                    .fold(f64::NEG_INFINITY, |a, &b| a.max(b));
-                (min, max)
+                let range = DataRange::of(hm.fill_values.iter().copied());
+                (range.min, range.max)
+            } else {
+                (0.0, 1.0)
+            };
+            RenderStyle::Heatmap(HeatmapStyle {
+                alpha: pick_alpha(&h.alpha),
+                value_min: vmin,
+                value_max: vmax,
+            })
+        }
+        Layer::Text(t) => RenderStyle::Text {
+            style: LabelStyle {
+                color: pick_color(&t.color),
+                size: t.size,
+                alpha: pick_alpha(&t.alpha),
+            },
+            nudge_x: t.nudge_x,
+            nudge_y: t.nudge_y,
+        },
+        Layer::HLine(h) => RenderStyle::Line(LineStyle {
+            color: h.color.clone(),
+            width: h.width,
+            alpha: h.alpha,
+            linetype: None,
+            arrow: false,
+            simplify: false,
+        }),
+        Layer::VLine(v) => RenderStyle::Line(LineStyle {
+            color: v.color.clone(),
+            width: v.width,
+            alpha: v.alpha,
+            linetype: None,
+            arrow: false,
+            simplify: false,
+        }),
+        Layer::AbLine(a) => RenderStyle::Line(LineStyle {
+            color: a.color.clone(),
+            width: a.width,
+            alpha: a.alpha,
+            linetype: None,
+            arrow: false,
+            simplify: false,
+        }),
+        Layer::Segment(s) => RenderStyle::Line(LineStyle {
+            color: pick_color(&s.color),
+            width: s.width,
+            alpha: s.alpha,
+            linetype: None,
+            arrow: s.arrow,
+            simplify: false,
+        }),
+        Layer::Pie(_) => unreachable!("pie slices are styled by process_pie_layer"),
+    }
+}
+
+#[derive(Debug, Clone)]
+struct BoxplotData {
+    q1: Vec<f64>,
+    median: Vec<f64>,
+    q3: Vec<f64>,
+    outliers: Vec<Vec<f64>>,
+}
+
+#[derive(Debug, Clone)]
+struct ViolinData {
+    density: Vec<Vec<f64>>,   // Normalized density values (0-1) per x category
+    density_y: Vec<Vec<f64>>, // Y coordinates for density curve per x category
+    quantile_values: Vec<Vec<f64>>, // Computed Y values at requested quantiles per x category
+}
+
+#[derive(Debug, Clone)]
+struct HeatmapData {
+    y_positions: Vec<f64>,             // Y position for each cell
+    fill_values: Vec<f64>,             // Fill value for color mapping
+    cell_width: f64,                   // Cell width in data units
+    cell_height: f64,                  // Cell height in data units
+    y_categories: Option<Vec<String>>, // Y-axis categories (if categorical)
+}
+
+#[derive(Debug, Clone)]
+struct StatData {
+    x: Vec<String>,
+    y: Vec<f64>,
+    ymin: Vec<f64>,
+    ymax: Vec<f64>,
+    boxplot: Option<BoxplotData>,
+    violin: Option<ViolinData>,
+    heatmap: Option<HeatmapData>,
+}
+
+impl StatData {
+    fn from_tuple(t: (Vec<String>, Vec<f64>, Vec<f64>, Vec<f64>)) -> Self {
+        StatData {
+            x: t.0,
+            y: t.1,
+            ymin: t.2,
+            ymax: t.3,
+            boxplot: None,
+            violin: None,
+            heatmap: None,
+        }
+    }
+}
+
+fn compute_boxplot_stat(groups: RawGroups) -> Result<HashMap<String, StatData>> {
+    let mut new_groups = HashMap::new();
+
+    for (key, (x_strs, y_vals, _, _)) in groups {
+        // Group by X value
+        let mut x_groups: HashMap<String, Vec<f64>> = HashMap::new();
+        for (x, y) in x_strs.iter().zip(y_vals.iter()) {
+            x_groups.entry(x.clone()).or_default().push(*y);
+        }
+
+        let mut unique_x: Vec<String> = x_groups.keys().cloned().collect();
+        // Sort unique X?
+        // We rely on numeric parsing if possible, or string sort.
+        // Let's replicate the sorting logic from process_layer loosely or just string sort.
+        // process_layer handles final sorting. Here we just need consistent order.
+        unique_x.sort();
+
+        let mut res_x = Vec::new();
+        let mut res_min = Vec::new(); // Lower whisker
+        let mut res_max = Vec::new(); // Upper whisker
+        let mut res_q1 = Vec::new();
+        let mut res_median = Vec::new();
+        let mut res_q3 = Vec::new();
+        let mut res_outliers = Vec::new();
+
+        for x_val in unique_x {
+            let mut ys = x_groups[&x_val].clone();
+            ys.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+            if ys.is_empty() {
+                continue;
+            }
+
+            let q1 = percentile(&ys, 0.25);
+            let median = percentile(&ys, 0.50);
+            let q3 = percentile(&ys, 0.75);
+            let iqr = q3 - q1;
+
+            let lower_fence = q1 - 1.5 * iqr;
+            let upper_fence = q3 + 1.5 * iqr;
+
+            // Whiskers: Range of data within fences
+            let lower_whisker = ys
+                .iter()
+                .filter(|&&v| v >= lower_fence)
+                .min_by(|a, b| a.partial_cmp(b).unwrap())
+                .unwrap_or(&q1);
+            let upper_whisker = ys
+                .iter()
+                .filter(|&&v| v <= upper_fence)
+                .max_by(|a, b| a.partial_cmp(b).unwrap())
+                .unwrap_or(&q3);
+
+            // Outliers
+            let outliers: Vec<f64> = ys
+                .iter()
+                .filter(|&&v| v < lower_fence || v > upper_fence)
+                .cloned()
+                .collect();
+
+            res_x.push(x_val);
+            res_min.push(*lower_whisker);
+            res_max.push(*upper_whisker);
+            res_q1.push(q1);
+            res_median.push(median);
+            res_q3.push(q3);
+            res_outliers.push(outliers);
+        }
+
+        // Y in StatData usually represents the "main" value. For boxplot, maybe median?
+        // Or we just ignore Y and use the boxplot specific fields.
+        // Let's use median for Y so stacking/etc (if applied) does something sane-ish, though boxplots aren't usually stacked.
+        let res_y = res_median.clone();
+
+        new_groups.insert(
+            key,
+            StatData {
+                x: res_x,
+                y: res_y,
+                ymin: res_min,
+                ymax: res_max,
+                boxplot: Some(BoxplotData {
+                    q1: res_q1,
+                    median: res_median,
+                    q3: res_q3,
+                    outliers: res_outliers,
+                }),
+                violin: None,
+                heatmap: None,
+            },
+        );
+    }
+
+    Ok(new_groups)
+}
+
+/// Silverman's rule of thumb for bandwidth selection
+fn silverman_bandwidth(data: &[f64]) -> f64 {
+    let n = data.len() as f64;
+    if n < 2.0 {
+        return 1.0;
+    }
+
+    let mean = data.iter().sum::<f64>() / n;
+    let variance = data.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    let std_dev = variance.sqrt();
+
+    // IQR-based estimate for robustness
+    let mut sorted = data.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let q1 = percentile(&sorted, 0.25);
+    let q3 = percentile(&sorted, 0.75);
+    let iqr = q3 - q1;
+
+    // Silverman's rule: h = 0.9 * min(std, IQR/1.34) * n^(-1/5)
+    let scale = if iqr > 0.0 {
+        std_dev.min(iqr / 1.34)
+    } else {
+        std_dev
+    };
+    if scale <= 0.0 {
+        return 1.0;
+    }
+    0.9 * scale * n.powf(-0.2)
+}
+
+/// Gaussian kernel function
+fn gaussian_kernel(u: f64) -> f64 {
+    const SQRT_2PI: f64 = 2.5066282746310002;
+    (-0.5 * u * u).exp() / SQRT_2PI
+}
+
+/// Compute Gaussian KDE at grid points
+fn compute_kde(data: &[f64], bandwidth: f64) -> (Vec<f64>, Vec<f64>) {
+    const GRID_POINTS: usize = 128; // Resolution of density curve
+
+    let n = data.len() as f64;
+    if n == 0.0 {
+        return (vec![], vec![]);
+    }
+
+    let DataRange {
+        min: min_y,
+        max: max_y,
+    } = DataRange::of(data.iter().copied());
+
+    // Extend range slightly for smooth edges
+    let extend = 3.0 * bandwidth;
+    let y_start = min_y - extend;
+    let y_end = max_y + extend;
+
+    let range = y_end - y_start;
+    if range <= 0.0 {
+        return (vec![min_y], vec![1.0]);
+    }
+
+    let step = range / (GRID_POINTS - 1) as f64;
+    let mut grid_y = Vec::with_capacity(GRID_POINTS);
+    let mut density = Vec::with_capacity(GRID_POINTS);
+
+    for i in 0..GRID_POINTS {
+        let y = y_start + i as f64 * step;
+        grid_y.push(y);
+
+        // Gaussian kernel density estimation
+        let mut d = 0.0;
+        for &xi in data {
+            let u = (y - xi) / bandwidth;
+            d += gaussian_kernel(u);
+        }
+        d /= n * bandwidth;
+        density.push(d);
+    }
+
+    // Normalize density to 0-1 range for rendering
+    let max_density = density.iter().fold(0.0f64, |a, &b| a.max(b));
+    if max_density > 0.0 {
+        for d in &mut density {
+            *d /= max_density;
+        }
+    }
+
+    (grid_y, density)
+}
+
+/// Compute violin statistics using KDE
+fn compute_violin_stat(
+    groups: RawGroups,
+    draw_quantiles: &[f64],
+) -> Result<HashMap<String, StatData>> {
+    let mut new_groups = HashMap::new();
+
+    for (key, (x_strs, y_vals, _, _)) in groups {
+        // Group by X value (category)
+        let mut x_groups: HashMap<String, Vec<f64>> = HashMap::new();
+        for (x, y) in x_strs.iter().zip(y_vals.iter()) {
+            x_groups.entry(x.clone()).or_default().push(*y);
+        }
+
+        let mut unique_x: Vec<String> = x_groups.keys().cloned().collect();
+        unique_x.sort();
+
+        let mut res_x = Vec::new();
+        let mut res_y = Vec::new(); // Will hold median for y
+        let mut res_min = Vec::new();
+        let mut res_max = Vec::new();
+        let mut density_vec = Vec::new();
+        let mut density_y_vec = Vec::new();
+        let mut quantile_values_vec = Vec::new();
+
+        for x_val in unique_x {
+            let ys = &x_groups[&x_val];
+            if ys.is_empty() {
+                continue;
+            }
+
+            let mut sorted_ys = ys.clone();
+            sorted_ys.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+            let min_y = sorted_ys[0];
+            let max_y = sorted_ys[sorted_ys.len() - 1];
+            let median = percentile(&sorted_ys, 0.5);
+
+            // Compute bandwidth using Silverman's rule
+            let bandwidth = silverman_bandwidth(&sorted_ys);
+
+            // Compute KDE
+            let (grid_y, density) = compute_kde(&sorted_ys, bandwidth);
+
+            // Compute actual data percentiles for requested quantiles
+            let quantile_y_values: Vec<f64> = draw_quantiles
+                .iter()
+                .map(|&q| percentile(&sorted_ys, q))
+                .collect();
+
+            res_x.push(x_val);
+            res_y.push(median);
+            res_min.push(min_y);
+            res_max.push(max_y);
+            density_vec.push(density);
+            density_y_vec.push(grid_y);
+            quantile_values_vec.push(quantile_y_values);
+        }
+
+        new_groups.insert(
+            key,
+            StatData {
+                x: res_x,
+                y: res_y,
+                ymin: res_min,
+                ymax: res_max,
+                boxplot: None,
+                violin: Some(ViolinData {
+                    density: density_vec,
+                    density_y: density_y_vec,
+                    quantile_values: quantile_values_vec,
+                }),
+                heatmap: None,
+            },
+        );
+    }
+
+    Ok(new_groups)
+}
+
+/// Compute density statistics using KDE (reuses KDE infrastructure from violin)
+fn compute_density_stat(
+    groups: RawGroups,
+    bw_override: Option<f64>,
+) -> Result<HashMap<String, StatData>> {
+    // First collect all x values across all groups to determine shared range
+    let mut all_x_values: Vec<f64> = Vec::new();
+    for (x_strs, _, _, _) in groups.values() {
+        for s in x_strs {
+            let v = s
+                .parse::<f64>()
+                .map_err(|_| anyhow!("Stat 'density' requires numeric x data"))?;
+            all_x_values.push(v);
+        }
+    }
+
+    if all_x_values.is_empty() {
+        return Ok(groups
+            .into_iter()
+            .map(|(k, v)| (k, StatData::from_tuple(v)))
+            .collect());
+    }
+
+    // Use shared range for all groups so density curves align
+    let DataRange {
+        min: global_min,
+        max: global_max,
+    } = DataRange::of(all_x_values.iter().copied());
+
+    let mut new_groups = HashMap::new();
+
+    for (key, (x_strs, _, _, _)) in groups {
+        let mut x_floats: Vec<f64> = Vec::new();
+        for s in &x_strs {
+            x_floats.push(s.parse::<f64>().unwrap());
+        }
+
+        if x_floats.is_empty() {
+            continue;
+        }
+
+        // Compute bandwidth
+        let bandwidth = bw_override.unwrap_or_else(|| silverman_bandwidth(&x_floats));
+
+        // Compute KDE on a shared grid
+        let grid_points: usize = 256;
+        let n = x_floats.len() as f64;
+
+        let extend = 3.0 * bandwidth;
+        let x_start = global_min - extend;
+        let x_end = global_max + extend;
+        let range = x_end - x_start;
+
+        if range <= 0.0 {
+            new_groups.insert(
+                key,
+                StatData::from_tuple((
+                    vec![global_min.to_string()],
+                    vec![1.0],
+                    vec![0.0],
+                    vec![1.0],
+                )),
+            );
+            continue;
+        }
+
+        let step = range / (grid_points - 1) as f64;
+        let mut grid_x = Vec::with_capacity(grid_points);
+        let mut density = Vec::with_capacity(grid_points);
+
+        for i in 0..grid_points {
+            let x = x_start + i as f64 * step;
+            grid_x.push(x);
+
+            let mut d = 0.0;
+            for &xi in &x_floats {
+                let u = (x - xi) / bandwidth;
+                d += gaussian_kernel(u);
+            }
+            d /= n * bandwidth;
+            density.push(d);
+        }
+
+        // Convert to string x and build stat data
+        let new_x: Vec<String> = grid_x.iter().map(|x| format!("{}", x)).collect();
+        let new_ymin: Vec<f64> = vec![0.0; grid_points];
+        let new_ymax: Vec<f64> = density.clone();
+
+        new_groups.insert(
+            key,
+            StatData::from_tuple((new_x, density, new_ymin, new_ymax)),
+        );
+    }
+
+    Ok(new_groups)
+}
+
+fn percentile(sorted_data: &[f64], p: f64) -> f64 {
+    let n = sorted_data.len();
+    if n == 0 {
+        return 0.0;
+    }
+    if n == 1 {
+        return sorted_data[0];
+    }
+
+    let rank = p * (n - 1) as f64;
+    let lower_idx = rank.floor() as usize;
+    let upper_idx = rank.ceil() as usize;
+
+    if lower_idx == upper_idx {
+        sorted_data[lower_idx]
+    } else {
+        let weight = rank - lower_idx as f64;
+        sorted_data[lower_idx] * (1.0 - weight) + sorted_data[upper_idx] * weight
+    }
+}
+
+/// Compute heatmap statistics using 2D binning
+/// When bins is specified, performs 2D histogram binning (count in each cell)
+/// When bins is None, treats data as pre-aggregated (x, y are categories, fill values from ymin)
+fn compute_heatmap_stat(
+    groups: RawGroups,
+    bins: Option<usize>,
+) -> Result<HashMap<String, StatData>> {
+    let mut new_groups = HashMap::new();
+
+    for (key, (x_strs, y_vals, fill_vals, _)) in groups {
+        if x_strs.is_empty() {
+            continue;
+        }
+
+        // Check if x and y are numeric
+        let x_numeric: Result<Vec<f64>, _> = x_strs.iter().map(|s| s.parse::<f64>()).collect();
+
+        if let (Ok(x_floats), Some(bin_count)) = (x_numeric.as_ref(), bins) {
+            // 2D numeric binning mode
+            let DataRange {
+                min: x_min,
+                max: x_max,
+            } = DataRange::of(x_floats.iter().copied());
+            let DataRange {
+                min: y_min_val,
+                max: y_max_val,
+            } = DataRange::of(y_vals.iter().copied());
+
+            let x_range = if x_max == x_min { 1.0 } else { x_max - x_min };
+            let y_range = if y_max_val == y_min_val {
+                1.0
+            } else {
+                y_max_val - y_min_val
+            };
+
+            let x_bin_width = x_range / bin_count as f64;
+            let y_bin_width = y_range / bin_count as f64;
+
+            // 2D binning
+            let mut bin_counts: HashMap<(usize, usize), f64> = HashMap::new();
+            let has_fill = fill_vals.iter().any(|v| *v != 0.0);
+
+            for i in 0..x_floats.len() {
+                let bx = ((x_floats[i] - x_min) / x_bin_width).floor() as usize;
+                let by = ((y_vals[i] - y_min_val) / y_bin_width).floor() as usize;
+                let bx = bx.min(bin_count - 1);
+                let by = by.min(bin_count - 1);
+
+                let entry = bin_counts.entry((bx, by)).or_insert(0.0);
+                if has_fill {
+                    *entry += fill_vals[i];
+                } else {
+                    *entry += 1.0; // Count mode
+                }
+            }
+
+            // Build cell data
+            let mut res_x = Vec::new();
+            let mut res_y_pos = Vec::new();
+            let mut res_fill = Vec::new();
+
+            for bx in 0..bin_count {
+                for by in 0..bin_count {
+                    let count = bin_counts.get(&(bx, by)).copied().unwrap_or(0.0);
+                    let x_center = x_min + (bx as f64 + 0.5) * x_bin_width;
+                    let y_center = y_min_val + (by as f64 + 0.5) * y_bin_width;
+
+                    res_x.push(format!("{}", x_center));
+                    res_y_pos.push(y_center);
+                    res_fill.push(count);
+                }
+            }
+
+            // Use y_pos as y for scale calculation
+            let res_y = res_y_pos.clone();
+            let res_ymin = vec![0.0; res_y.len()];
+            let res_ymax = res_y.clone();
+
+            new_groups.insert(
+                key,
+                StatData {
+                    x: res_x,
+                    y: res_y,
+                    ymin: res_ymin,
+                    ymax: res_ymax,
+                    boxplot: None,
+                    violin: None,
+                    heatmap: Some(HeatmapData {
+                        y_positions: res_y_pos,
+                        fill_values: res_fill,
+                        cell_width: x_bin_width,
+                        cell_height: y_bin_width,
+                        y_categories: None,
+                    }),
+                },
+            );
+        } else {
+            // Categorical mode: treat x and y as categories
+            // y_vals are parsed from the y column (numeric), fill_vals from the fill column
+            // Group by (x, y_as_string) pairs
+            let mut unique_x: Vec<String> = Vec::new();
+            let mut unique_y: Vec<String> = Vec::new();
+            let mut seen_x: std::collections::HashSet<String> = std::collections::HashSet::new();
+            let mut seen_y: std::collections::HashSet<String> = std::collections::HashSet::new();
+
+            // For categorical heatmap, y values come as f64 from y column
+            // We need to track unique y values as strings for category labels
+            let y_strings: Vec<String> = y_vals.iter().map(|v| format!("{}", v)).collect();
+
+            for x in &x_strs {
+                if seen_x.insert(x.clone()) {
+                    unique_x.push(x.clone());
+                }
+            }
+            for y_s in &y_strings {
+                if seen_y.insert(y_s.clone()) {
+                    unique_y.push(y_s.clone());
+                }
+            }
+            // Preserve data appearance order (no alphabetical sort)
+
+            let x_count = unique_x.len();
+            let y_count = unique_y.len();
+
+            // Build cell lookup
+            let x_idx_map: HashMap<String, usize> = unique_x
+                .iter()
+                .enumerate()
+                .map(|(i, s)| (s.clone(), i))
+                .collect();
+            let y_idx_map: HashMap<String, usize> = unique_y
+                .iter()
+                .enumerate()
+                .map(|(i, s)| (s.clone(), i))
+                .collect();
+
+            // Aggregate values per cell
+            let mut cell_values: HashMap<(usize, usize), f64> = HashMap::new();
+            let mut cell_counts: HashMap<(usize, usize), usize> = HashMap::new();
+
+            for i in 0..x_strs.len() {
+                let xi = x_idx_map[&x_strs[i]];
+                let yi = y_idx_map[&y_strings[i]];
+                let val = fill_vals[i];
+                *cell_values.entry((xi, yi)).or_insert(0.0) += val;
+                *cell_counts.entry((xi, yi)).or_insert(0) += 1;
+            }
+
+            // Build output
+            let mut res_x = Vec::new();
+            let mut res_y_pos = Vec::new();
+            let mut res_fill = Vec::new();
+
+            for (xi, x_val) in unique_x.iter().enumerate().take(x_count) {
+                for yi in 0..y_count {
+                    res_x.push(x_val.clone());
+                    res_y_pos.push(yi as f64);
+                    let val = cell_values.get(&(xi, yi)).copied().unwrap_or(0.0);
+                    let count = cell_counts.get(&(xi, yi)).copied().unwrap_or(0);
+                    // If we have fill values, use mean; otherwise count
+                    let fill_val = if fill_vals.iter().any(|v| *v != 0.0) && count > 0 {
+                        val / count as f64
+                    } else if count > 0 {
+                        count as f64
+                    } else {
+                        0.0
+                    };
+                    res_fill.push(fill_val);
+                }
+            }
+
+            let res_y = res_y_pos.clone();
+            let res_ymin = vec![0.0; res_y.len()];
+            let res_ymax = vec![(y_count as f64 - 1.0).max(0.0); res_y.len()];
+
+            // Categorical x fills one slot per category; numeric x keeps its own
+            // positions, so each cell spans the spacing between adjacent values
+            let cell_width = match &x_numeric {
+                Ok(x_floats) => min_spacing(x_floats),
+                Err(_) => 1.0,
+            };
+
+            new_groups.insert(
+                key,
+                StatData {
+                    x: res_x,
+                    y: res_y,
+                    ymin: res_ymin,
+                    ymax: res_ymax,
+                    boxplot: None,
+                    violin: None,
+                    heatmap: Some(HeatmapData {
+                        y_positions: res_y_pos,
+                        fill_values: res_fill,
+                        cell_width,
+                        cell_height: 1.0,
+                        y_categories: Some(unique_y),
+                    }),
+                },
+            );
+        }
+    }
+
+    Ok(new_groups)
+}
+
+/// Deterministic offset in [-0.5, 0.5) for a data row (splitmix64 of the row index),
+/// so a jittered plot renders identically every time
+fn jitter_unit(row_idx: usize) -> f64 {
+    (splitmix64(row_idx as u64) >> 11) as f64 / (1u64 << 53) as f64 - 0.5
+}
+
+/// The splitmix64 mix of `x`: a fast, well-spread 64-bit hash
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Smallest positive gap between distinct values (1.0 when there is no gap to measure)
+fn min_spacing(values: &[f64]) -> f64 {
+    let mut sorted: Vec<f64> = values.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    sorted
+        .windows(2)
+        .map(|w| w[1] - w[0])
+        .filter(|gap| *gap > 0.0)
+        .fold(None, |min: Option<f64>, gap| {
+            Some(min.map_or(gap, |m| m.min(gap)))
+        })
+        .unwrap_or(1.0)
+}
+
+fn apply_statistics(groups: RawGroups, stat: &Stat) -> Result<HashMap<String, StatData>> {
+    match stat {
+        Stat::Identity => Ok(groups
+            .into_iter()
+            .map(|(k, v)| (k, StatData::from_tuple(v)))
+            .collect()),
+        Stat::Bin { bins } => compute_bin_stat(groups, *bins),
+        Stat::Count => compute_count_stat(groups),
+        Stat::Summary { fun } => compute_summary_stat(groups, *fun),
+        Stat::RollingMean {
+            window,
+            min_periods,
+        } => compute_rolling_mean_stat(groups, *window, min_periods.unwrap_or(*window)),
+        Stat::Smooth {
+            method,
+            span,
+            samples,
+        } => compute_smooth_stat(groups, method, *span, *samples),
+        Stat::Boxplot => compute_boxplot_stat(groups),
+        Stat::Violin { draw_quantiles } => compute_violin_stat(groups, draw_quantiles),
+        Stat::Density { bw } => compute_density_stat(groups, *bw),
+        Stat::Heatmap { bins } => compute_heatmap_stat(groups, *bins),
+    }
+}
+
+/// Error on an x value that appears in more than one row of a group
+fn check_unique_bar_x(groups: &RawGroups) -> Result<()> {
+    for key in get_sorted_keys(groups) {
+        let mut seen: HashSet<&str> = HashSet::new();
+        for x in &groups[&key].0 {
+            if !seen.insert(x) {
+                let group = if key == "default" {
+                    String::new()
+                } else {
+                    format!(" in group '{}'", key)
+                };
+                return Err(anyhow!(
+                    "bar() has more than one row for x = '{}'{}. Combine them with \
+                     bar(stat: \"sum\"), \"mean\", \"min\", \"max\", or \"count\"",
+                    x,
+                    group
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Combine each group's y values per x value, keeping first-appearance order
+fn compute_summary_stat(groups: RawGroups, fun: Aggregate) -> Result<HashMap<String, StatData>> {
+    let mut new_groups = HashMap::new();
+
+    for (key, (x_strs, ys, _, _)) in groups {
+        // x -> its slot in new_x and acc (running value, row count)
+        let mut slots: HashMap<String, usize> = HashMap::new();
+        let mut new_x: Vec<String> = Vec::new();
+        let mut acc: Vec<(f64, usize)> = Vec::new();
+        for (x, y) in x_strs.into_iter().zip(ys) {
+            match slots.get(&x) {
+                Some(&i) => {
+                    let (value, n) = &mut acc[i];
+                    *value = match fun {
+                        Aggregate::Sum | Aggregate::Mean => *value + y,
+                        Aggregate::Min => value.min(y),
+                        Aggregate::Max => value.max(y),
+                    };
+                    *n += 1;
+                }
+                None => {
+                    slots.insert(x.clone(), new_x.len());
+                    new_x.push(x);
+                    acc.push((y, 1));
+                }
+            }
+        }
+
+        let new_y: Vec<f64> = acc
+            .into_iter()
+            .map(|(value, n)| match fun {
+                Aggregate::Mean => value / n as f64,
+                _ => value,
+            })
+            .collect();
+        let new_ymin = new_y.iter().map(|&y| y.min(0.0)).collect();
+        let new_ymax = new_y.iter().map(|&y| y.max(0.0)).collect();
+
+        new_groups.insert(
+            key,
+            StatData::from_tuple((new_x, new_y, new_ymin, new_ymax)),
+        );
+    }
+
+    Ok(new_groups)
+}
+
+/// Trailing mean of each group's last `window` points, after sorting the group by x
+/// (numerically, else as datetimes; categorical x keeps data order). Points whose window
+/// holds fewer than `min_periods` values are dropped, and a group too short to emit
+/// any point is an error rather than an empty line.
+fn compute_rolling_mean_stat(
+    groups: RawGroups,
+    window: usize,
+    min_periods: usize,
+) -> Result<HashMap<String, StatData>> {
+    let mut new_groups = HashMap::new();
+
+    for key in get_sorted_keys(&groups) {
+        let (x_strs, ys, _, _) = &groups[&key];
+        if ys.len() < min_periods {
+            let group = if key == "default" {
+                String::new()
             } else {
-                (0.0, 1.0)
+                format!(" in group '{}'", key)
             };
-            RenderStyle::Heatmap(HeatmapStyle {
-                alpha: pick_alpha(&h.alpha),
-                value_min: vmin,
-                value_max: vmax,
-            })
+            return Err(anyhow!(
+                "line(stat: \"rolling_mean\") needs at least {} points{}, found {}. \
+                 Use a smaller window: or min_periods:",
+                min_periods,
+                group,
+                ys.len()
+            ));
         }
-        Layer::HLine(h) => RenderStyle::Line(LineStyle {
-            color: h.color.clone(),
-            width: h.width,
-            alpha: h.alpha,
-        }),
-        Layer::VLine(v) => RenderStyle::Line(LineStyle {
-            color: v.color.clone(),
-            width: v.width,
-            alpha: v.alpha,
-        }),
-        Layer::AbLine(a) => RenderStyle::Line(LineStyle {
-            color: a.color.clone(),
-            width: a.width,
-            alpha: a.alpha,
-        }),
-        Layer::Segment(s) => RenderStyle::Line(LineStyle {
-            color: s.color.clone(),
-            width: s.width,
-            alpha: s.alpha,
-        }),
+
+        let sort_keys: Option<Vec<f64>> = x_strs
+            .iter()
+            .map(|s| s.trim().parse::<f64>().ok())
+            .collect::<Option<_>>()
+            .or_else(|| {
+                x_strs
+                    .iter()
+                    .map(|s| parse_datetime_value(s).ok())
+                    .collect()
+            });
+        let mut order: Vec<usize> = (0..ys.len()).collect();
+        if let Some(sort_keys) = &sort_keys {
+            order.sort_by(|&a, &b| sort_keys[a].total_cmp(&sort_keys[b]));
+        }
+
+        let mut new_x = Vec::new();
+        let mut new_y = Vec::new();
+        let mut sum = 0.0;
+        for (i, &idx) in order.iter().enumerate() {
+            sum += ys[idx];
+            if i >= window {
+                sum -= ys[order[i - window]];
+            }
+            let n = (i + 1).min(window);
+            if n >= min_periods {
+                new_x.push(x_strs[idx].clone());
+                new_y.push(sum / n as f64);
+            }
+        }
+
+        let new_ymin = new_y.clone();
+        let new_ymax = new_y.clone();
+        new_groups.insert(
+            key,
+            StatData::from_tuple((new_x, new_y, new_ymin, new_ymax)),
+        );
     }
+
+    Ok(new_groups)
 }
 
-#[derive(Debug, Clone)]
-struct BoxplotData {
-    q1: Vec<f64>,
-    median: Vec<f64>,
-    q3: Vec<f64>,
-    outliers: Vec<Vec<f64>>,
+fn compute_count_stat(groups: RawGroups) -> Result<HashMap<String, StatData>> {
+    let mut new_groups = HashMap::new();
+
+    for (key, (x_strs, _, _, _)) in groups {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for s in x_strs {
+            *counts.entry(s).or_default() += 1;
+        }
+
+        let mut keys: Vec<String> = counts.keys().cloned().collect();
+        keys.sort();
+
+        let mut new_x = Vec::new();
+        let mut new_y = Vec::new();
+        let mut new_ymin = Vec::new();
+        let mut new_ymax = Vec::new();
+
+        for k in keys {
+            let count = counts[&k] as f64;
+            new_x.push(k);
+            new_y.push(count);
+            new_ymin.push(0.0);
+            new_ymax.push(count);
+        }
+
+        new_groups.insert(
+            key,
+            StatData::from_tuple((new_x, new_y, new_ymin, new_ymax)),
+        );
+    }
+
+    Ok(new_groups)
 }
 
-#[derive(Debug, Clone)]
-struct ViolinData {
-    density: Vec<Vec<f64>>,   // Normalized density values (0-1) per x category
-    density_y: Vec<Vec<f64>>, // Y coordinates for density curve per x category
-    quantile_values: Vec<Vec<f64>>, // Computed Y values at requested quantiles per x category
+fn compute_smooth_stat(
+    groups: RawGroups,
+    method: &str,
+    span: Option<f64>,
+    samples: Option<usize>,
+) -> Result<HashMap<String, StatData>> {
+    let mut new_groups = HashMap::new();
+
+    for (key, (x_strs, y_vals, _, _)) in groups {
+        let mut x_floats = Vec::new();
+        for s in &x_strs {
+            x_floats.push(
+                s.parse::<f64>()
+                    .map_err(|_| anyhow!("Stat 'smooth' requires numeric x data"))?,
+            );
+        }
+
+        if x_floats.len() < 2 {
+            continue;
+        }
+
+        let method = method.to_ascii_lowercase();
+        let (new_x_values, new_y) = match method.as_str() {
+            "lm" | "linear" | "linear_regression" => {
+                compute_linear_smooth_points(&x_floats, &y_vals)
+            }
+            "loess" | "lowess" => compute_loess_smooth_points(&x_floats, &y_vals, span, samples),
+            other => {
+                return Err(anyhow!(
+                    "Unknown smooth method '{}'. Supported methods: \"lm\", \"loess\"",
+                    other
+                ))
+            }
+        }?;
+
+        let new_x: Vec<String> = new_x_values.iter().map(|x| x.to_string()).collect();
+        let new_ymin = new_y.clone();
+        let new_ymax = new_y.clone();
+
+        new_groups.insert(
+            key,
+            StatData::from_tuple((new_x, new_y, new_ymin, new_ymax)),
+        );
+    }
+
+    Ok(new_groups)
 }
 
-#[derive(Debug, Clone)]
-struct HeatmapData {
-    y_positions: Vec<f64>,             // Y position for each cell
-    fill_values: Vec<f64>,             // Fill value for color mapping
-    cell_width: f64,                   // Cell width in data units
-    cell_height: f64,                  // Cell height in data units
-    y_categories: Option<Vec<String>>, // Y-axis categories (if categorical)
+fn compute_linear_smooth_points(x: &[f64], y: &[f64]) -> Result<(Vec<f64>, Vec<f64>)> {
+    let n = x.len() as f64;
+    let sum_x: f64 = x.iter().sum();
+    let sum_y: f64 = y.iter().sum();
+    let sum_xx: f64 = x.iter().map(|&x| x * x).sum();
+    let sum_xy: f64 = x.iter().zip(y.iter()).map(|(&x, &y)| x * y).sum();
+
+    let denom = n * sum_xx - sum_x * sum_x;
+    if denom.abs() < f64::EPSILON {
+        return Err(anyhow!(
+            "Stat 'smooth' requires x data with non-zero variance"
+        ));
+    }
+
+    let slope = (n * sum_xy - sum_x * sum_y) / denom;
+    let intercept = (sum_y - slope * sum_x) / n;
+
+    let DataRange {
+        min: min_x,
+        max: max_x,
+    } = DataRange::of(x.iter().copied());
+
+    Ok((
+        vec![min_x, max_x],
+        vec![slope * min_x + intercept, slope * max_x + intercept],
+    ))
 }
 
-#[derive(Debug, Clone)]
-struct StatData {
-    x: Vec<String>,
-    y: Vec<f64>,
-    ymin: Vec<f64>,
-    ymax: Vec<f64>,
-    boxplot: Option<BoxplotData>,
-    violin: Option<ViolinData>,
-    heatmap: Option<HeatmapData>,
+fn compute_loess_smooth_points(
+    x: &[f64],
+    y: &[f64],
+    span: Option<f64>,
+    samples: Option<usize>,
+) -> Result<(Vec<f64>, Vec<f64>)> {
+    if x.len() != y.len() || x.len() < 2 {
+        return Ok((Vec::new(), Vec::new()));
+    }
+
+    let mut pairs: Vec<(f64, f64)> = x.iter().copied().zip(y.iter().copied()).collect();
+    pairs.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+
+    let min_x = pairs.first().map(|p| p.0).unwrap();
+    let max_x = pairs.last().map(|p| p.0).unwrap();
+    if (max_x - min_x).abs() < f64::EPSILON {
+        return Err(anyhow!(
+            "Stat 'smooth' requires x data with non-zero variance"
+        ));
+    }
+
+    let sample_count = samples.unwrap_or(80).max(2);
+    let span = span.unwrap_or(0.75).clamp(0.05, 1.0);
+    let neighborhood = ((pairs.len() as f64 * span).ceil() as usize).clamp(2, pairs.len());
+
+    let mut smooth_x = Vec::with_capacity(sample_count);
+    let mut smooth_y = Vec::with_capacity(sample_count);
+
+    for i in 0..sample_count {
+        let t = i as f64 / (sample_count - 1) as f64;
+        let x0 = min_x + (max_x - min_x) * t;
+        let y0 = loess_predict_at(&pairs, x0, neighborhood);
+        if y0.is_finite() {
+            smooth_x.push(x0);
+            smooth_y.push(y0);
+        }
+    }
+
+    Ok((smooth_x, smooth_y))
 }
 
-impl StatData {
-    fn from_tuple(t: (Vec<String>, Vec<f64>, Vec<f64>, Vec<f64>)) -> Self {
-        StatData {
-            x: t.0,
-            y: t.1,
-            ymin: t.2,
-            ymax: t.3,
-            boxplot: None,
-            violin: None,
-            heatmap: None,
+fn loess_predict_at(pairs: &[(f64, f64)], x0: f64, neighborhood: usize) -> f64 {
+    let mut distances: Vec<f64> = pairs.iter().map(|(x, _)| (x - x0).abs()).collect();
+    distances.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+    let mut bandwidth = distances[neighborhood.saturating_sub(1)];
+    if bandwidth <= f64::EPSILON {
+        bandwidth = distances
+            .iter()
+            .copied()
+            .find(|d| *d > f64::EPSILON)
+            .unwrap_or(1.0);
+    }
+
+    let mut sw = 0.0;
+    let mut sx = 0.0;
+    let mut sy = 0.0;
+    let mut sxx = 0.0;
+    let mut sxy = 0.0;
+
+    for &(x, y) in pairs {
+        let distance = (x - x0).abs();
+        if distance > bandwidth {
+            continue;
         }
+
+        let u = distance / bandwidth;
+        let weight = (1.0 - u.powi(3)).powi(3);
+        let centered_x = x - x0;
+
+        sw += weight;
+        sx += weight * centered_x;
+        sy += weight * y;
+        sxx += weight * centered_x * centered_x;
+        sxy += weight * centered_x * y;
+    }
+
+    if sw <= f64::EPSILON {
+        return pairs
+            .iter()
+            .min_by(|a, b| {
+                (a.0 - x0)
+                    .abs()
+                    .partial_cmp(&(b.0 - x0).abs())
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .map(|(_, y)| *y)
+            .unwrap_or(0.0);
+    }
+
+    let denom = sw * sxx - sx * sx;
+    if denom.abs() <= f64::EPSILON {
+        sy / sw
+    } else {
+        (sy * sxx - sx * sxy) / denom
     }
 }
 
-fn compute_boxplot_stat(
-    groups: HashMap<String, (Vec<String>, Vec<f64>, Vec<f64>, Vec<f64>)>,
-) -> Result<HashMap<String, StatData>> {
-    let mut new_groups = HashMap::new();
+fn compute_bin_stat(groups: RawGroups, bin_count: usize) -> Result<HashMap<String, StatData>> {
+    let bin_count = bin_count.max(1);
 
-    for (key, (x_strs, y_vals, _, _)) in groups {
-        // Group by X value
-        let mut x_groups: HashMap<String, Vec<f64>> = HashMap::new();
-        for (x, y) in x_strs.iter().zip(y_vals.iter()) {
-            x_groups.entry(x.clone()).or_default().push(*y);
+    // 1. Collect all X values to determine range
+    let mut all_values = Vec::new();
+    for (x_strs, _, _, _) in groups.values() {
+        for s in x_strs {
+            let v = s
+                .parse::<f64>()
+                .map_err(|_| anyhow!("Stat 'bin' requires numeric x data"))?;
+            all_values.push(v);
         }
+    }
 
-        let mut unique_x: Vec<String> = x_groups.keys().cloned().collect();
-        // Sort unique X?
-        // We rely on numeric parsing if possible, or string sort.
-        // Let's replicate the sorting logic from process_layer loosely or just string sort.
-        // process_layer handles final sorting. Here we just need consistent order.
-        unique_x.sort();
-
-        let mut res_x = Vec::new();
-        let mut res_min = Vec::new(); // Lower whisker
-        let mut res_max = Vec::new(); // Upper whisker
-        let mut res_q1 = Vec::new();
-        let mut res_median = Vec::new();
-        let mut res_q3 = Vec::new();
-        let mut res_outliers = Vec::new();
+    if all_values.is_empty() {
+        return Ok(groups
+            .into_iter()
+            .map(|(k, v)| (k, StatData::from_tuple(v)))
+            .collect());
+    }
 
-        for x_val in unique_x {
-            let mut ys = x_groups[&x_val].clone();
-            ys.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let DataRange { min, max } = DataRange::of(all_values.iter().copied());
 
-            if ys.is_empty() {
-                continue;
-            }
+    // Add small buffer or handle 0 range
+    let range = max - min;
+    let width = if range == 0.0 {
+        1.0
+    } else {
+        range / bin_count as f64
+    };
 
-            let q1 = percentile(&ys, 0.25);
-            let median = percentile(&ys, 0.50);
-            let q3 = percentile(&ys, 0.75);
-            let iqr = q3 - q1;
+    let mut new_groups = HashMap::new();
 
-            let lower_fence = q1 - 1.5 * iqr;
-            let upper_fence = q3 + 1.5 * iqr;
+    for (key, (x_strs, _, _, _)) in groups {
+        let mut bins: HashMap<usize, usize> = HashMap::new();
 
-            // Whiskers: Range of data within fences
-            let lower_whisker = ys
-                .iter()
-                .filter(|&&v| v >= lower_fence)
-                .min_by(|a, b| a.partial_cmp(b).unwrap())
-                .unwrap_or(&q1);
-            let upper_whisker = ys
-                .iter()
-                .filter(|&&v| v <= upper_fence)
-                .max_by(|a, b| a.partial_cmp(b).unwrap())
-                .unwrap_or(&q3);
+        for s in x_strs {
+            // We already checked they are numeric
+            let v = s.parse::<f64>().unwrap();
+            let bin_idx = ((v - min) / width).floor() as usize;
+            let bin_idx = bin_idx.min(bin_count - 1);
+            *bins.entry(bin_idx).or_default() += 1;
+        }
 
-            // Outliers
-            let outliers: Vec<f64> = ys
-                .iter()
-                .filter(|&&v| v < lower_fence || v > upper_fence)
-                .cloned()
-                .collect();
+        let mut new_x = Vec::new();
+        let mut new_y = Vec::new();
+        let mut new_ymin = Vec::new();
+        let mut new_ymax = Vec::new();
 
-            res_x.push(x_val);
-            res_min.push(*lower_whisker);
-            res_max.push(*upper_whisker);
-            res_q1.push(q1);
-            res_median.push(median);
-            res_q3.push(q3);
-            res_outliers.push(outliers);
+        for idx in 0..bin_count {
+            let center = min + (idx as f64 * width) + (width / 2.0);
+            let count = bins.get(&idx).copied().unwrap_or(0) as f64;
+            new_x.push(format!("{:.2}", center));
+            new_y.push(count);
+            new_ymin.push(0.0);
+            new_ymax.push(count);
         }
 
-        // Y in StatData usually represents the "main" value. For boxplot, maybe median?
-        // Or we just ignore Y and use the boxplot specific fields.
-        // Let's use median for Y so stacking/etc (if applied) does something sane-ish, though boxplots aren't usually stacked.
-        let res_y = res_median.clone();
-
         new_groups.insert(
             key,
-            StatData {
-                x: res_x,
-                y: res_y,
-                ymin: res_min,
-                ymax: res_max,
-                boxplot: Some(BoxplotData {
-                    q1: res_q1,
-                    median: res_median,
-                    q3: res_q3,
-                    outliers: res_outliers,
-                }),
-                violin: None,
-                heatmap: None,
-            },
+            StatData::from_tuple((new_x, new_y, new_ymin, new_ymax)),
         );
     }
 
     Ok(new_groups)
 }
 
-/// Silverman's rule of thumb for bandwidth selection
-fn silverman_bandwidth(data: &[f64]) -> f64 {
-    let n = data.len() as f64;
-    if n < 2.0 {
-        return 1.0;
-    }
-
-    let mean = data.iter().sum::<f64>() / n;
-    let variance = data.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0);
-    let std_dev = variance.sqrt();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::ast::{Layer, LineLayer};
 
-    // IQR-based estimate for robustness
-    let mut sorted = data.to_vec();
-    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
-    let q1 = percentile(&sorted, 0.25);
-    let q3 = percentile(&sorted, 0.75);
-    let iqr = q3 - q1;
+    fn make_data() -> PlotData {
+        PlotData {
+            headers: vec!["x".to_string(), "y".to_string(), "cat".to_string()],
+            rows: vec![
+                vec!["1.0".to_string(), "10.0".to_string(), "A".to_string()],
+                vec!["2.0".to_string(), "20.0".to_string(), "A".to_string()],
+                vec!["1.0".to_string(), "15.0".to_string(), "B".to_string()],
+            ],
+        }
+    }
 
-    // Silverman's rule: h = 0.9 * min(std, IQR/1.34) * n^(-1/5)
-    let scale = if iqr > 0.0 {
-        std_dev.min(iqr / 1.34)
-    } else {
-        std_dev
-    };
-    if scale <= 0.0 {
-        return 1.0;
+    fn make_spec() -> ResolvedSpec {
+        ResolvedSpec {
+            layers: vec![ResolvedLayer {
+                original_layer: Layer::Line(LineLayer::default()),
+                aesthetics: ResolvedAesthetics {
+                    x_col: "x".to_string(),
+                    y_col: Some("y".to_string()),
+                    ymin_col: None,
+                    ymax_col: None,
+                    xend_col: None,
+                    yend_col: None,
+                    color: Some("cat".to_string()),
+                    size: None,
+                    shape: None,
+                    alpha: None,
+                    fill: None,
+                    linetype: None,
+                    group: Vec::new(),
+                    label: None,
+                },
+            }],
+            facet: None,
+            coord: None,
+            labels: crate::parser::ast::Labels::default(),
+            theme: crate::parser::ast::Theme::default(),
+            x_scale_spec: None,
+            y_scale_spec: None,
+            color_scale: crate::parser::ast::ColorScale::default(),
+            color_manual: None,
+            stages: Vec::new(),
+            annotations: Vec::new(),
+            shades: Vec::new(),
+        }
     }
-    0.9 * scale * n.powf(-0.2)
-}
 
-/// Gaussian kernel function
-fn gaussian_kernel(u: f64) -> f64 {
-    const SQRT_2PI: f64 = 2.5066282746310002;
-    (-0.5 * u * u).exp() / SQRT_2PI
-}
+    #[test]
+    fn test_transform_grouping() {
+        let csv = make_data();
+        let spec = make_spec();
+        let render_data = apply_transformations(&spec, &csv, &RenderOptions::default()).unwrap();
 
-/// Compute Gaussian KDE at grid points
-fn compute_kde(data: &[f64], bandwidth: f64) -> (Vec<f64>, Vec<f64>) {
-    const GRID_POINTS: usize = 128; // Resolution of density curve
+        assert_eq!(render_data.panels.len(), 1);
+        let panel = &render_data.panels[0];
+        assert_eq!(panel.layers.len(), 1);
+        let layer = &panel.layers[0];
+        assert_eq!(layer.groups.len(), 2); // A and B
 
-    let n = data.len() as f64;
-    if n == 0.0 {
-        return (vec![], vec![]);
+        // Check group A
+        let group_a = layer.groups.iter().find(|g| g.key == "A").unwrap();
+        assert_eq!(group_a.x.len(), 2);
+        assert_eq!(group_a.y, vec![10.0, 20.0]);
     }
 
-    let min_y = data.iter().fold(f64::INFINITY, |a, &b| a.min(b));
-    let max_y = data.iter().fold(f64::NEG_INFINITY, |a, &b| a.max(b));
+    #[test]
+    fn test_transform_facet() {
+        let mut spec = make_spec();
+        spec.facet = Some(ResolvedFacet {
+            col: "cat".to_string(),
+            ncol: None,
+            nrow: None,
+            scales: crate::parser::ast::FacetScales::Fixed,
+            bins: None,
+            cut: Default::default(),
+            grid: None,
+            labeller: Default::default(),
+            label_size: None,
+        });
 
-    // Extend range slightly for smooth edges
-    let extend = 3.0 * bandwidth;
-    let y_start = min_y - extend;
-    let y_end = max_y + extend;
+        let csv = make_data();
+        let render_data = apply_transformations(&spec, &csv, &RenderOptions::default()).unwrap();
 
-    let range = y_end - y_start;
-    if range <= 0.0 {
-        return (vec![min_y], vec![1.0]);
+        assert_eq!(render_data.panels.len(), 2); // A and B panels
+        assert_eq!(render_data.facet_layout.panel_titles.len(), 2);
+        assert!(render_data
+            .facet_layout
+            .panel_titles
+            .contains(&"A".to_string()));
     }
 
-    let step = range / (GRID_POINTS - 1) as f64;
-    let mut grid_y = Vec::with_capacity(GRID_POINTS);
-    let mut density = Vec::with_capacity(GRID_POINTS);
-
-    for i in 0..GRID_POINTS {
-        let y = y_start + i as f64 * step;
-        grid_y.push(y);
+    /// Regions East/North/West by years 2023/2024, with no North rows for 2024
+    fn region_year_data() -> PlotData {
+        PlotData::new(
+            vec!["region".into(), "year".into(), "x".into(), "y".into()],
+            [
+                ["East", "2023", "a", "1"],
+                ["East", "2024", "b", "2"],
+                ["North", "2023", "a", "3"],
+                ["West", "2023", "c", "4"],
+                ["West", "2024", "a", "5"],
+            ]
+            .iter()
+            .map(|row| row.iter().map(|s| s.to_string()).collect())
+            .collect(),
+        )
+    }
 
-        // Gaussian kernel density estimation
-        let mut d = 0.0;
-        for &xi in data {
-            let u = (y - xi) / bandwidth;
-            d += gaussian_kernel(u);
-        }
-        d /= n * bandwidth;
-        density.push(d);
+    fn transform_dsl(dsl: &str, data: &PlotData) -> RenderData {
+        let (_, spec) = crate::parser::parse_plot_spec(dsl).unwrap();
+        let resolved = crate::resolve::resolve_plot_aesthetics(&spec, data).unwrap();
+        apply_transformations(&resolved, data, &RenderOptions::default()).unwrap()
     }
 
-    // Normalize density to 0-1 range for rendering
-    let max_density = density.iter().fold(0.0f64, |a, &b| a.max(b));
-    if max_density > 0.0 {
-        for d in &mut density {
-            *d /= max_density;
-        }
+    #[test]
+    fn test_transform_label_order_for_facets_and_groups() {
+        let data = PlotData::new(
+            vec!["dose".into(), "run".into(), "x".into(), "y".into()],
+            [
+                ["10", "run10", "1", "1"],
+                ["2", "run2", "1", "2"],
+                ["1", "run1", "2", "3"],
+                ["10", "run2", "2", "4"],
+            ]
+            .iter()
+            .map(|row| row.iter().map(|s| s.to_string()).collect())
+            .collect(),
+        );
+        let dsl = r#"aes(x: x, y: y, color: run) | point() | facet_wrap(by: dose)"#;
+        let (_, spec) = crate::parser::parse_plot_spec(dsl).unwrap();
+        let resolved = crate::resolve::resolve_plot_aesthetics(&spec, &data).unwrap();
+        // Group keys in the "10" panel, which holds run10 and run2
+        let keys = |render_data: &RenderData, panel: usize| -> Vec<String> {
+            render_data.panels[panel].layers[0]
+                .groups
+                .iter()
+                .map(|g| g.key.clone())
+                .collect()
+        };
+
+        let render_data =
+            apply_transformations(&resolved, &data, &RenderOptions::default()).unwrap();
+        assert_eq!(render_data.facet_layout.panel_titles, ["1", "2", "10"]);
+        assert_eq!(keys(&render_data, 2), ["run2", "run10"]);
+
+        let options = RenderOptions {
+            label_order: LabelOrder::Lexical,
+            ..RenderOptions::default()
+        };
+        let render_data = apply_transformations(&resolved, &data, &options).unwrap();
+        assert_eq!(render_data.facet_layout.panel_titles, ["1", "10", "2"]);
+        assert_eq!(keys(&render_data, 1), ["run10", "run2"]);
     }
 
-    (grid_y, density)
-}
+    #[test]
+    fn test_transform_filter_stages_prune_rows_before_faceting() {
+        let data = region_year_data();
+        let render_data = transform_dsl(
+            r#"aes(x: x, y: y) | filter(year >= 2024) | filter(region != "East") | point() | facet_wrap(by: region)"#,
+            &data,
+        );
+        // Both stages must pass, and facets only see the surviving rows
+        assert_eq!(render_data.facet_layout.panel_titles, vec!["West"]);
+        assert_eq!(render_data.panels[0].layers[0].groups[0].y, vec![5.0]);
+        assert_eq!(render_data.filter_rejected_rows, 0);
+
+        // Categories come from the filtered rows too
+        let render_data = transform_dsl(r#"aes(x: x, y: y) | filter(x != "c") | point()"#, &data);
+        let group = &render_data.panels[0].layers[0].groups[0];
+        assert_eq!(
+            group.x_categories.as_deref(),
+            Some(&["a".to_string(), "b".to_string()][..])
+        );
 
-/// Compute violin statistics using KDE
-fn compute_violin_stat(
-    groups: HashMap<String, (Vec<String>, Vec<f64>, Vec<f64>, Vec<f64>)>,
-    draw_quantiles: &[f64],
-) -> Result<HashMap<String, StatData>> {
-    let mut new_groups = HashMap::new();
+        let (_, spec) =
+            crate::parser::parse_plot_spec("aes(x: x, y: y) | filter(y > 99) | point()").unwrap();
+        let resolved = crate::resolve::resolve_plot_aesthetics(&spec, &data).unwrap();
+        let err = apply_transformations(&resolved, &data, &RenderOptions::default()).unwrap_err();
+        assert_eq!(err.to_string(), "filter matched 0 rows (of 5)");
+    }
 
-    for (key, (x_strs, y_vals, _, _)) in groups {
-        // Group by X value (category)
-        let mut x_groups: HashMap<String, Vec<f64>> = HashMap::new();
-        for (x, y) in x_strs.iter().zip(y_vals.iter()) {
-            x_groups.entry(x.clone()).or_default().push(*y);
-        }
+    #[test]
+    fn test_transform_places_annotations() {
+        let data = PlotData::new(
+            vec!["year".into(), "v".into(), "region".into()],
+            [
+                ["2019", "1", "EU"],
+                ["2020", "2", "US"],
+                ["2021", "3", "EU"],
+            ]
+            .iter()
+            .map(|row| row.iter().map(|s| s.to_string()).collect())
+            .collect(),
+        );
+        let notes = |render_data: &RenderData| -> Vec<Vec<(usize, f64, f64)>> {
+            render_data
+                .panels
+                .iter()
+                .map(|p| p.annotations.iter().map(|a| (a.index, a.x, a.y)).collect())
+                .collect()
+        };
 
-        let mut unique_x: Vec<String> = x_groups.keys().cloned().collect();
-        unique_x.sort();
+        // A number x names its category on a bar's axis, and keeps it on a facet
+        // that drops unused categories
+        let render_data = transform_dsl(
+            r#"aes(x: year, y: v) | bar() | annotate(x: 2020, y: 5, text: "a") | annotate(x: 2020, y: 1, text: "b", panel: "US") | facet_wrap(by: region, scales: "free_x")"#,
+            &data,
+        );
+        assert_eq!(
+            notes(&render_data),
+            vec![vec![(0, 1.0, 5.0)], vec![(0, 0.0, 5.0), (1, 0.0, 1.0)]]
+        );
+        let eu = &render_data.panels[0].layers[0].groups[0];
+        assert_eq!(eu.x_categories.as_ref().unwrap(), &["2019", "2020", "2021"]);
+
+        let err = |dsl: &str| {
+            let (_, spec) = crate::parser::parse_plot_spec(dsl).unwrap();
+            let resolved = crate::resolve::resolve_plot_aesthetics(&spec, &data).unwrap();
+            apply_transformations(&resolved, &data, &RenderOptions::default())
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            err(r#"aes(x: year, y: v) | bar() | annotate(x: 2030, y: 1, text: "a")"#),
+            "annotate() x 2030 is not a category on the x axis (2019, 2020, 2021)"
+        );
+        assert_eq!(
+            err(r#"aes(x: year, y: v) | line() | annotate(x: "late", y: 1, text: "a")"#),
+            r#"annotate() x "late" is not a number; the x axis has no categories"#
+        );
+        assert_eq!(
+            err(
+                r#"aes(x: year, y: v) | line() | annotate(x: 2020, y: 1, text: "a", panel: "ASIA") | facet_wrap(by: region)"#
+            ),
+            r#"annotate() panel: "ASIA" matches no facet panel (EU, US)"#
+        );
+    }
 
-        let mut res_x = Vec::new();
-        let mut res_y = Vec::new(); // Will hold median for y
-        let mut res_min = Vec::new();
-        let mut res_max = Vec::new();
-        let mut density_vec = Vec::new();
-        let mut density_y_vec = Vec::new();
-        let mut quantile_values_vec = Vec::new();
+    fn summarize_dsl(dsl: &str, data: &PlotData) -> PlotData {
+        let (_, spec) = crate::parser::parse_plot_spec(dsl).unwrap();
+        apply_data_stages(&spec.stages, data).unwrap().data.unwrap()
+    }
 
-        for x_val in unique_x {
-            let ys = &x_groups[&x_val];
-            if ys.is_empty() {
-                continue;
-            }
+    fn readings() -> PlotData {
+        PlotData::new(
+            vec!["site".into(), "day".into(), "temp".into()],
+            [
+                ["A", "1", "10"],
+                ["A", "1", "14"],
+                ["B", "1", "3"],
+                ["A", "2", "20"],
+                ["B", "1", "NA"],
+                ["A", "1", "12"],
+            ]
+            .iter()
+            .map(|row| row.iter().map(|s| s.to_string()).collect())
+            .collect(),
+        )
+    }
 
-            let mut sorted_ys = ys.clone();
-            sorted_ys.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    #[test]
+    fn test_summarize_each_aggregate() {
+        let data = readings();
+        let column = |function: &str| {
+            let dsl = format!("summarize(by: day, y: {}(temp)) | point()", function);
+            let table = summarize_dsl(&dsl, &data);
+            assert_eq!(
+                table.headers,
+                vec!["day".to_string(), format!("{}_temp", function)]
+            );
+            table
+                .rows
+                .iter()
+                .map(|row| row[1].clone())
+                .collect::<Vec<_>>()
+        };
+        // Day 1 holds 10, 14, 3, 12 (the NA is skipped); day 2 holds 20
+        assert_eq!(column("mean"), vec!["9.75", "20"]);
+        assert_eq!(column("sum"), vec!["39", "20"]);
+        assert_eq!(column("min"), vec!["3", "20"]);
+        assert_eq!(column("max"), vec!["14", "20"]);
+        assert_eq!(column("median"), vec!["11", "20"]);
+        assert_eq!(column("count"), vec!["4", "1"]);
+
+        // count() counts rows, missing values included
+        let table = summarize_dsl("summarize(by: day, y: count()) | point()", &data);
+        assert_eq!(table.headers, vec!["day", "count"]);
+        assert_eq!(table.rows, vec![vec!["1", "5"], vec!["2", "1"]]);
+
+        // Without by, every row is one group
+        let table = summarize_dsl("summarize(y: max(temp), as: peak) | point()", &data);
+        assert_eq!(table.headers, vec!["peak"]);
+        assert_eq!(table.rows, vec![vec!["20"]]);
+    }
 
-            let min_y = sorted_ys[0];
-            let max_y = sorted_ys[sorted_ys.len() - 1];
-            let median = percentile(&sorted_ys, 0.5);
+    #[test]
+    fn test_summarize_groups_by_two_columns_in_first_row_order() {
+        let data = readings();
+        let table = summarize_dsl(
+            "summarize(by: [site, day], y: mean(temp), as: avg) | point()",
+            &data,
+        );
+        assert_eq!(table.headers, vec!["site", "day", "avg"]);
+        assert_eq!(
+            table.rows,
+            vec![
+                vec!["A", "1", "12"],
+                vec!["B", "1", "3"],
+                vec!["A", "2", "20"],
+            ]
+        );
 
-            // Compute bandwidth using Silverman's rule
-            let bandwidth = silverman_bandwidth(&sorted_ys);
+        // A group whose values are all missing gets a missing cell
+        let sparse = PlotData::new(
+            vec!["g".into(), "v".into()],
+            vec![vec!["a".into(), "5".into()], vec!["b".into(), "NA".into()]],
+        );
+        let table = summarize_dsl("summarize(by: g, y: mean(v)) | point()", &sparse);
+        assert_eq!(table.rows, vec![vec!["a", "5"], vec!["b", ""]]);
 
-            // Compute KDE
-            let (grid_y, density) = compute_kde(&sorted_ys, bandwidth);
+        let bad = PlotData::new(
+            vec!["g".into(), "v".into()],
+            vec![vec!["a".into(), "1".into()], vec!["a".into(), "x".into()]],
+        );
+        let (_, spec) =
+            crate::parser::parse_plot_spec("summarize(by: g, y: sum(v)) | point()").unwrap();
+        let err = apply_data_stages(&spec.stages, &bad).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "summarize() sum_v: 'x' in row 2 is not a number"
+        );
+    }
 
-            // Compute actual data percentiles for requested quantiles
-            let quantile_y_values: Vec<f64> = draw_quantiles
+    #[test]
+    fn test_sort_compares_numeric_columns_as_numbers() {
+        let data = PlotData::new(
+            vec!["name".into(), "n".into(), "g".into()],
+            [
+                ["b", "10", "y"],
+                ["a", "2", "x"],
+                ["c", "NA", "x"],
+                ["d", "2", "y"],
+                ["e", "-1.5", "y"],
+            ]
+            .iter()
+            .map(|row| row.iter().map(|s| s.to_string()).collect())
+            .collect(),
+        );
+        let names = |dsl: &str| {
+            summarize_dsl(dsl, &data)
+                .rows
                 .iter()
-                .map(|&q| percentile(&sorted_ys, q))
-                .collect();
+                .map(|row| row[0].clone())
+                .collect::<Vec<_>>()
+        };
 
-            res_x.push(x_val);
-            res_y.push(median);
-            res_min.push(min_y);
-            res_max.push(max_y);
-            density_vec.push(density);
-            density_y_vec.push(grid_y);
-            quantile_values_vec.push(quantile_y_values);
-        }
+        // 2 < 10 (not "10" < "2"); ties keep input order; missing sorts last
+        assert_eq!(
+            names("sort(by: n) | point()"),
+            vec!["e", "a", "d", "b", "c"]
+        );
+        assert_eq!(
+            names("sort(by: n, desc: true) | point()"),
+            vec!["b", "a", "d", "e", "c"]
+        );
 
-        new_groups.insert(
-            key,
-            StatData {
-                x: res_x,
-                y: res_y,
-                ymin: res_min,
-                ymax: res_max,
-                boxplot: None,
-                violin: Some(ViolinData {
-                    density: density_vec,
-                    density_y: density_y_vec,
-                    quantile_values: quantile_values_vec,
-                }),
-                heatmap: None,
-            },
+        // Text columns compare lexicographically; later keys break ties, each with its own direction
+        assert_eq!(
+            names("sort(by: [g, n], desc: [false, true]) | point()"),
+            vec!["a", "c", "b", "d", "e"]
         );
     }
 
-    Ok(new_groups)
-}
+    #[test]
+    fn test_transform_sort_sets_first_appearance_category_order() {
+        let data = bar_data(&[["A", "5", "p"], ["B", "30", "p"], ["C", "12", "p"]]);
+        let layer = bar_layer(&data, "aes(x: x, y: y) | sort(by: y, desc: true) | bar()");
+        let group = &layer.groups[0];
+        assert_eq!(
+            group.x_categories.as_deref(),
+            Some(&["B".to_string(), "C".to_string(), "A".to_string()][..])
+        );
+        assert_eq!(group.y, vec![30.0, 12.0, 5.0]);
+    }
 
-/// Compute density statistics using KDE (reuses KDE infrastructure from violin)
-fn compute_density_stat(
-    groups: HashMap<String, (Vec<String>, Vec<f64>, Vec<f64>, Vec<f64>)>,
-    bw_override: Option<f64>,
-) -> Result<HashMap<String, StatData>> {
-    // First collect all x values across all groups to determine shared range
-    let mut all_x_values: Vec<f64> = Vec::new();
-    for (x_strs, _, _, _) in groups.values() {
-        for s in x_strs {
-            let v = s
-                .parse::<f64>()
-                .map_err(|_| anyhow!("Stat 'density' requires numeric x data"))?;
-            all_x_values.push(v);
-        }
+    fn numbered_rows(n: usize) -> PlotData {
+        PlotData::new(
+            vec!["i".into(), "v".into()],
+            (0..n)
+                .map(|i| vec![i.to_string(), (i % 7).to_string()])
+                .collect(),
+        )
     }
 
-    if all_x_values.is_empty() {
-        return Ok(groups
-            .into_iter()
-            .map(|(k, v)| (k, StatData::from_tuple(v)))
-            .collect());
+    fn staged(dsl: &str, data: &PlotData) -> StagedData {
+        let (_, spec) = crate::parser::parse_plot_spec(dsl).unwrap();
+        apply_data_stages(&spec.stages, data).unwrap()
     }
 
-    // Use shared range for all groups so density curves align
-    let global_min = all_x_values.iter().fold(f64::INFINITY, |a, &b| a.min(b));
-    let global_max = all_x_values
-        .iter()
-        .fold(f64::NEG_INFINITY, |a, &b| a.max(b));
+    #[test]
+    fn test_head_keeps_first_rows_and_reports_the_cut() {
+        let data = numbered_rows(50);
+        let result = staged("head(n: 3) | point()", &data);
+        let table = result.data.unwrap();
+        assert_eq!(table.rows, data.rows[..3].to_vec());
+        assert_eq!(
+            result.row_limits,
+            vec![RowLimit {
+                stage: "head".to_string(),
+                before: 50,
+                after: 3
+            }]
+        );
 
-    let mut new_groups = HashMap::new();
+        // A limit past the end keeps every row and reports nothing
+        let result = staged("head(n: 50) | sample(n: 1000) | point()", &data);
+        assert!(result.data.is_none());
+        assert!(result.row_limits.is_empty());
+    }
 
-    for (key, (x_strs, _, _, _)) in groups {
-        let mut x_floats: Vec<f64> = Vec::new();
-        for s in &x_strs {
-            x_floats.push(s.parse::<f64>().unwrap());
-        }
+    #[test]
+    fn test_sample_is_deterministic_per_seed() {
+        let data = numbered_rows(1000);
+        let picks = |dsl: &str| {
+            staged(dsl, &data)
+                .data
+                .unwrap()
+                .rows
+                .iter()
+                .map(|row| row[0].parse::<usize>().unwrap())
+                .collect::<Vec<_>>()
+        };
 
-        if x_floats.is_empty() {
-            continue;
-        }
+        let first = picks("sample(n: 100, seed: 7) | point()");
+        assert_eq!(first.len(), 100);
+        assert_eq!(picks("sample(n: 100, seed: 7) | point()"), first);
+        // Without replacement, and in input order
+        assert!(first.windows(2).all(|w| w[0] < w[1]));
+        // Spread across the data rather than a prefix
+        assert!(*first.last().unwrap() > 500);
+
+        let other = picks("sample(n: 100, seed: 8) | point()");
+        assert_ne!(other, first);
+        assert_eq!(
+            picks("sample(n: 100) | point()"),
+            picks("sample(n: 100, seed: 0) | point()")
+        );
+    }
 
-        // Compute bandwidth
-        let bandwidth = bw_override.unwrap_or_else(|| silverman_bandwidth(&x_floats));
+    #[test]
+    fn test_transform_layers_read_the_summarized_table() {
+        let data = readings();
+        let render_data = transform_dsl(
+            "aes(x: day, y: mean_temp, color: site) | summarize(by: [site, day], y: mean(temp)) | filter(mean_temp > 5) | point()",
+            &data,
+        );
+        let layer = &render_data.panels[0].layers[0];
+        let keys: Vec<&str> = layer.groups.iter().map(|g| g.key.as_str()).collect();
+        assert_eq!(keys, vec!["A"]);
+        assert_eq!(layer.groups[0].y, vec![12.0, 20.0]);
+
+        // Columns the summary dropped are no longer available
+        let (_, spec) = crate::parser::parse_plot_spec(
+            "aes(x: day, y: temp) | summarize(by: day, y: mean(temp)) | point()",
+        )
+        .unwrap();
+        let err = crate::resolve::resolve_plot_aesthetics(&spec, &data).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unknown column 'temp'. Available columns: day, mean_temp"
+        );
+    }
 
-        // Compute KDE on a shared grid
-        let grid_points: usize = 256;
-        let n = x_floats.len() as f64;
+    fn ledger() -> PlotData {
+        PlotData::new(
+            vec!["month".into(), "revenue".into(), "cost".into()],
+            [
+                ["Jan", "100", "60"],
+                ["Feb", "80", "NA"],
+                ["Mar", "0", "30"],
+                ["Apr", "50", "50"],
+            ]
+            .iter()
+            .map(|row| row.iter().map(|s| s.to_string()).collect())
+            .collect(),
+        )
+    }
 
-        let extend = 3.0 * bandwidth;
-        let x_start = global_min - extend;
-        let x_end = global_max + extend;
-        let range = x_end - x_start;
+    fn mutated(dsl: &str, data: &PlotData, column: &str) -> Vec<String> {
+        let table = summarize_dsl(dsl, data);
+        let idx = table.headers.iter().position(|h| h == column).unwrap();
+        table.rows.iter().map(|row| row[idx].clone()).collect()
+    }
 
-        if range <= 0.0 {
-            new_groups.insert(
-                key,
-                StatData::from_tuple((
-                    vec![global_min.to_string()],
-                    vec![1.0],
-                    vec![0.0],
-                    vec![1.0],
-                )),
-            );
-            continue;
-        }
+    #[test]
+    fn test_mutate_evaluates_arithmetic_row_by_row() {
+        let data = ledger();
+        // A missing input leaves the result missing
+        assert_eq!(
+            mutated("mutate(margin: revenue - cost) | point()", &data, "margin"),
+            vec!["40", "", "-30", "0"]
+        );
+        assert_eq!(
+            mutated("mutate(v: -revenue + cost * 2 / 4) | point()", &data, "v"),
+            vec!["-70", "", "15", "-25"]
+        );
+        assert_eq!(
+            mutated("mutate(v: (revenue + 20) * 0.5) | point()", &data, "v"),
+            vec!["60", "50", "10", "35"]
+        );
+        assert_eq!(
+            mutated("mutate(v: abs(cost - revenue)) | point()", &data, "v"),
+            vec!["40", "", "30", "0"]
+        );
+        assert_eq!(
+            mutated("mutate(v: log10(revenue)) | point()", &data, "v")[0],
+            "2"
+        );
 
-        let step = range / (grid_points - 1) as f64;
-        let mut grid_x = Vec::with_capacity(grid_points);
-        let mut density = Vec::with_capacity(grid_points);
+        // Undefined results are missing rather than inf/NaN
+        assert_eq!(
+            mutated("mutate(v: cost / revenue) | point()", &data, "v"),
+            vec!["0.6", "", "", "1"]
+        );
+        assert_eq!(
+            mutated("mutate(v: log(revenue - cost)) | point()", &data, "v")[1..],
+            ["", "", ""]
+        );
+    }
 
-        for i in 0..grid_points {
-            let x = x_start + i as f64 * step;
-            grid_x.push(x);
+    #[test]
+    fn test_mutate_chains_and_replaces_columns() {
+        let data = ledger();
+        let table = summarize_dsl(
+            "mutate(margin: revenue - cost, pct: margin / revenue * 100, Cost: cost * 2) | point()",
+            &data,
+        );
+        // New columns are appended; an existing name is replaced in place
+        assert_eq!(
+            table.headers,
+            vec!["month", "revenue", "cost", "margin", "pct"]
+        );
+        assert_eq!(table.rows[0], vec!["Jan", "100", "120", "40", "40"]);
+        assert_eq!(table.rows[2], vec!["Mar", "0", "60", "-30", ""]);
+
+        let bad = PlotData::new(
+            vec!["a".into(), "b".into()],
+            vec![vec!["1".into(), "2".into()], vec!["3".into(), "n/a".into()]],
+        );
+        let (_, spec) = crate::parser::parse_plot_spec("mutate(total: a + b) | point()").unwrap();
+        let err = apply_data_stages(&spec.stages, &bad).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "mutate() total: 'n/a' in column 'b', row 2 is not a number"
+        );
+    }
+
+    #[test]
+    fn test_transform_mutated_column_works_in_aes_facet_and_filter() {
+        let data = ledger();
+        let render_data = transform_dsl(
+            "mutate(margin: revenue - cost, scaled: margin * 10) | filter(margin >= 0) | aes(x: month, y: scaled) | point(filter: revenue > 60) | facet_wrap(by: margin)",
+            &data,
+        );
+        // Feb (no margin) and Mar (negative) are filtered out before faceting
+        let mut titles = render_data.facet_layout.panel_titles.clone();
+        titles.sort();
+        assert_eq!(titles, vec!["0", "40"]);
+        let ys: Vec<Vec<f64>> = render_data
+            .panels
+            .iter()
+            .map(|panel| {
+                panel.layers[0]
+                    .groups
+                    .iter()
+                    .flat_map(|g| g.y.clone())
+                    .collect()
+            })
+            .collect();
+        assert!(ys.contains(&vec![400.0]));
+        assert!(ys.contains(&vec![]));
+
+        // Expression columns are checked against the data like any other reference
+        let (_, spec) = crate::parser::parse_plot_spec(
+            "mutate(margin: revenue - costs) | aes(x: month, y: margin) | point()",
+        )
+        .unwrap();
+        let err = crate::resolve::resolve_plot_aesthetics(&spec, &data).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unknown column 'costs' (did you mean 'cost'?). Available columns: month, revenue, cost"
+        );
+    }
+
+    fn wide_sales() -> PlotData {
+        PlotData::new(
+            vec![
+                "month".into(),
+                "north".into(),
+                "South".into(),
+                "note".into(),
+            ],
+            [["Jan", "10", "7", "x"], ["Feb", "12", "NA", "y"]]
+                .iter()
+                .map(|row| row.iter().map(|s| s.to_string()).collect())
+                .collect(),
+        )
+    }
 
-            let mut d = 0.0;
-            for &xi in &x_floats {
-                let u = (x - xi) / bandwidth;
-                d += gaussian_kernel(u);
-            }
-            d /= n * bandwidth;
-            density.push(d);
-        }
+    #[test]
+    fn test_pivot_longer_melts_listed_columns() {
+        let data = wide_sales();
+        let table = summarize_dsl(
+            "pivot_longer(cols: north, south, names_to: region, values_to: sales) | point()",
+            &data,
+        );
+        // Unlisted columns repeat per output row; headers keep the data's spelling
+        assert_eq!(table.headers, vec!["month", "note", "region", "sales"]);
+        assert_eq!(
+            table.rows,
+            vec![
+                vec!["Jan", "x", "north", "10"],
+                vec!["Jan", "x", "South", "7"],
+                vec!["Feb", "y", "north", "12"],
+                vec!["Feb", "y", "South", "NA"],
+            ]
+        );
 
-        // Convert to string x and build stat data
-        let new_x: Vec<String> = grid_x.iter().map(|x| format!("{}", x)).collect();
-        let new_ymin: Vec<f64> = vec![0.0; grid_points];
-        let new_ymax: Vec<f64> = density.clone();
+        // Non-numeric cells pass through as text
+        let table = summarize_dsl("pivot_longer(cols: [north, note]) | point()", &data);
+        assert_eq!(table.headers, vec!["month", "South", "name", "value"]);
+        assert_eq!(table.rows[1], vec!["Jan", "7", "note", "x"]);
+    }
 
-        new_groups.insert(
-            key,
-            StatData::from_tuple((new_x, density, new_ymin, new_ymax)),
+    #[test]
+    fn test_transform_pivoted_columns_group_layers() {
+        let data = wide_sales();
+        let render_data = transform_dsl(
+            "pivot_longer(cols: north, south, names_to: region, values_to: sales) | aes(x: month, y: sales, color: region) | line()",
+            &data,
+        );
+        let layer = &render_data.panels[0].layers[0];
+        let group = |key: &str| layer.groups.iter().find(|g| g.key == key).unwrap();
+        assert_eq!(layer.groups.len(), 2);
+        assert_eq!(group("north").y, vec![10.0, 12.0]);
+        // The NA cell is skipped like any missing value
+        assert_eq!(group("South").y, vec![7.0]);
+
+        let resolve_err = |dsl: &str| {
+            let (_, spec) = crate::parser::parse_plot_spec(dsl).unwrap();
+            crate::resolve::resolve_plot_aesthetics(&spec, &data)
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            resolve_err("pivot_longer(cols: north, west) | aes(x: month, y: value) | line()"),
+            "Unknown column 'west'. Available columns: month, north, South, note"
+        );
+        assert_eq!(
+            resolve_err(
+                "pivot_longer(cols: north, south, names_to: note) | aes(x: month, y: value) | line()"
+            ),
+            "pivot_longer() names_to: 'note' is already a column; pick another name"
+        );
+        assert_eq!(
+            resolve_err(
+                "pivot_longer(cols: north, names_to: v, values_to: v) | aes(x: month, y: v) | line()"
+            ),
+            "pivot_longer() names_to and values_to are both 'v'"
+        );
+        // The melted columns are gone afterwards
+        assert!(
+            resolve_err("pivot_longer(cols: north, south) | aes(x: month, y: north) | line()")
+                .starts_with("Unknown column 'north'")
         );
     }
 
-    Ok(new_groups)
-}
+    fn rolling(x: &[&str], y: &[f64], window: usize, min_periods: usize) -> StatData {
+        let mut groups: RawGroups = HashMap::new();
+        groups.insert(
+            "default".to_string(),
+            (
+                x.iter().map(|s| s.to_string()).collect(),
+                y.to_vec(),
+                vec![],
+                vec![],
+            ),
+        );
+        compute_rolling_mean_stat(groups, window, min_periods)
+            .unwrap()
+            .remove("default")
+            .unwrap()
+    }
 
-fn percentile(sorted_data: &[f64], p: f64) -> f64 {
-    let n = sorted_data.len();
-    if n == 0 {
-        return 0.0;
+    #[test]
+    fn test_rolling_mean_hand_computed_windows() {
+        let x = ["1", "2", "3", "4", "5"];
+        let y = [2.0, 4.0, 9.0, 1.0, 6.0];
+
+        // Full windows only: (2+4+9)/3, (4+9+1)/3, (9+1+6)/3
+        let stat = rolling(&x, &y, 3, 3);
+        assert_eq!(stat.x, vec!["3", "4", "5"]);
+        assert_eq!(stat.y, vec![5.0, 14.0 / 3.0, 16.0 / 3.0]);
+
+        // min_periods: 1 also emits the partial windows at the start
+        let stat = rolling(&x, &y, 3, 1);
+        assert_eq!(stat.x, x.to_vec());
+        assert_eq!(stat.y, vec![2.0, 3.0, 5.0, 14.0 / 3.0, 16.0 / 3.0]);
+        let stat = rolling(&x, &y, 3, 2);
+        assert_eq!(stat.y, vec![3.0, 5.0, 14.0 / 3.0, 16.0 / 3.0]);
+
+        // A window of 1 is the raw series; a window of the whole series is one point
+        assert_eq!(rolling(&x, &y, 1, 1).y, y.to_vec());
+        assert_eq!(rolling(&x, &y, 5, 5).y, vec![22.0 / 5.0]);
     }
-    if n == 1 {
-        return sorted_data[0];
+
+    #[test]
+    fn test_rolling_mean_sorts_by_x_first() {
+        // Numeric x compares as numbers (2 before 10), not in row order
+        let stat = rolling(&["10", "2", "1"], &[30.0, 20.0, 10.0], 2, 2);
+        assert_eq!(stat.x, vec!["2", "10"]);
+        assert_eq!(stat.y, vec![15.0, 25.0]);
+
+        let stat = rolling(
+            &["2024-01-03", "2024-01-01", "2024-01-02"],
+            &[9.0, 3.0, 6.0],
+            2,
+            2,
+        );
+        assert_eq!(stat.x, vec!["2024-01-02", "2024-01-03"]);
+        assert_eq!(stat.y, vec![4.5, 7.5]);
+
+        // Categorical x keeps data order
+        assert_eq!(rolling(&["b", "a"], &[1.0, 3.0], 2, 2).y, vec![2.0]);
     }
 
-    let rank = p * (n - 1) as f64;
-    let lower_idx = rank.floor() as usize;
-    let upper_idx = rank.ceil() as usize;
+    #[test]
+    fn test_transform_rolling_mean_layers_compose() {
+        let data = PlotData::new(
+            vec!["t".into(), "v".into(), "g".into()],
+            [
+                ["1", "1", "a"],
+                ["2", "3", "a"],
+                ["3", "5", "a"],
+                ["1", "10", "b"],
+                ["2", "20", "b"],
+                ["3", "NA", "b"],
+                ["4", "60", "b"],
+            ]
+            .iter()
+            .map(|row| row.iter().map(|s| s.to_string()).collect())
+            .collect(),
+        );
+        let render_data = transform_dsl(
+            r#"aes(x: t, y: v, color: g) | line(alpha: 0.3) | line(stat: "rolling_mean", window: 2, width: 2)"#,
+            &data,
+        );
+        let layers = &render_data.panels[0].layers;
+        let group = |layer: usize, key: &str| {
+            layers[layer]
+                .groups
+                .iter()
+                .find(|g| g.key == key)
+                .unwrap()
+                .clone()
+        };
+        // The raw layer is untouched
+        assert_eq!(group(0, "a").y, vec![1.0, 3.0, 5.0]);
+        // Each group averages on its own; the NA row is skipped before windowing
+        assert_eq!(group(1, "a").y, vec![2.0, 4.0]);
+        assert_eq!(group(1, "b").y, vec![15.0, 40.0]);
+
+        let err = |dsl: &str| {
+            let (_, spec) = crate::parser::parse_plot_spec(dsl).unwrap();
+            match crate::resolve::resolve_plot_aesthetics(&spec, &data) {
+                Ok(resolved) => apply_transformations(&resolved, &data, &RenderOptions::default())
+                    .unwrap_err()
+                    .to_string(),
+                Err(e) => e.to_string(),
+            }
+        };
+        assert_eq!(
+            err(r#"aes(x: t, y: v, color: g) | line(stat: "rolling_mean", window: 4)"#),
+            "line(stat: \"rolling_mean\") needs at least 4 points in group 'a', found 3. \
+             Use a smaller window: or min_periods:"
+        );
+        assert_eq!(
+            err(r#"aes(x: t, y: v) | line(stat: "rolling_mean", window: 0)"#),
+            "line() window must be at least 1"
+        );
+        assert_eq!(
+            err(r#"aes(x: t, y: v) | line(stat: "rolling_mean", window: 3, min_periods: 4)"#),
+            "line() min_periods must be between 1 and the window (3)"
+        );
+    }
 
-    if lower_idx == upper_idx {
-        sorted_data[lower_idx]
-    } else {
-        let weight = rank - lower_idx as f64;
-        sorted_data[lower_idx] * (1.0 - weight) + sorted_data[upper_idx] * weight
+    fn trapezoid_area(x: &[f64], y: &[f64]) -> f64 {
+        x.windows(2)
+            .zip(y.windows(2))
+            .map(|(x, y)| (x[1] - x[0]) * (y[0] + y[1]) / 2.0)
+            .sum()
     }
-}
 
-/// Compute heatmap statistics using 2D binning
-/// When bins is specified, performs 2D histogram binning (count in each cell)
-/// When bins is None, treats data as pre-aggregated (x, y are categories, fill values from ymin)
-fn compute_heatmap_stat(
-    groups: HashMap<String, (Vec<String>, Vec<f64>, Vec<f64>, Vec<f64>)>,
-    bins: Option<usize>,
-) -> Result<HashMap<String, StatData>> {
-    let mut new_groups = HashMap::new();
+    #[test]
+    fn test_transform_mapped_segments_are_two_point_runs() {
+        let data = PlotData::new(
+            ["team", "a", "b", "y0", "y1"].map(String::from).to_vec(),
+            vec![
+                vec!["A".into(), "1".into(), "3".into(), "10".into(), "14".into()],
+                vec!["A".into(), "2".into(), "4".into(), "".into(), "9".into()],
+                vec!["A".into(), "5".into(), "6".into(), "7".into(), "8".into()],
+                vec!["B".into(), "1".into(), "2".into(), "20".into(), "-5".into()],
+            ],
+        );
+        let render_data = transform_dsl(
+            "aes(x: a, y: y0, color: team) | segment(xend: b, yend: y1, arrow: true)",
+            &data,
+        );
+        let layer = &render_data.panels[0].layers[0];
+        // The row with a missing y0 is skipped
+        assert_eq!(layer.skipped_rows, 1);
+        let a = layer.groups.iter().find(|g| g.key == "A").unwrap();
+        assert_eq!(a.x, vec![1.0, 3.0, 5.0, 6.0]);
+        assert_eq!(a.y, vec![10.0, 14.0, 7.0, 8.0]);
+        assert_eq!(a.line_breaks, vec![2]);
+        let b = layer.groups.iter().find(|g| g.key == "B").unwrap();
+        assert_eq!(b.y, vec![20.0, -5.0]);
+        assert!(matches!(&b.style, RenderStyle::Line(style) if style.arrow));
+
+        // Text endpoints share one categorical axis across x and xend
+        let data = PlotData::new(
+            ["a", "b", "y0", "y1"].map(String::from).to_vec(),
+            vec![
+                vec!["before".into(), "after".into(), "1".into(), "2".into()],
+                vec!["before".into(), "later".into(), "3".into(), "1".into()],
+            ],
+        );
+        let render_data = transform_dsl("segment(x: a, y: y0, xend: b, yend: y1)", &data);
+        let group = &render_data.panels[0].layers[0].groups[0];
+        assert_eq!(group.x, vec![0.0, 1.0, 0.0, 2.0]);
+        assert_eq!(
+            group.x_categories.as_deref(),
+            Some(
+                &[
+                    "before".to_string(),
+                    "after".to_string(),
+                    "later".to_string()
+                ][..]
+            )
+        );
+    }
 
-    for (key, (x_strs, y_vals, fill_vals, _)) in groups {
-        if x_strs.is_empty() {
-            continue;
+    #[test]
+    fn test_transform_density_curves_integrate_to_one() {
+        let rows: Vec<Vec<String>> = [1.0, 2.5, 3.0, 3.2, 4.1, 5.0, 5.5, 7.0, 9.5, 10.0]
+            .iter()
+            .enumerate()
+            .map(|(i, v)| {
+                vec![
+                    v.to_string(),
+                    if i % 2 == 0 { "a" } else { "b" }.to_string(),
+                ]
+            })
+            .collect();
+        let data = PlotData::new(vec!["v".into(), "g".into()], rows);
+
+        // No y column needed
+        let render_data = transform_dsl("aes(x: v) | density()", &data);
+        let group = &render_data.panels[0].layers[0].groups[0];
+        assert_eq!(group.x.len(), 256);
+        assert!(group.y.iter().all(|&d| d >= 0.0));
+        let area = trapezoid_area(&group.x, &group.y);
+        assert!((area - 1.0).abs() < 0.01, "area {}", area);
+
+        // A wider bandwidth flattens the peak but keeps the area
+        let wide = transform_dsl("aes(x: v) | density(bw: 4)", &data);
+        let wide_group = &wide.panels[0].layers[0].groups[0];
+        let peak = |y: &[f64]| y.iter().cloned().fold(0.0, f64::max);
+        assert!(peak(&wide_group.y) < peak(&group.y));
+        let area = trapezoid_area(&wide_group.x, &wide_group.y);
+        assert!((area - 1.0).abs() < 0.01, "area {}", area);
+
+        // One curve per color group, each with its own bandwidth and unit area
+        let grouped = transform_dsl("aes(x: v, color: g) | density(fill: false)", &data);
+        let groups = &grouped.panels[0].layers[0].groups;
+        assert_eq!(groups.len(), 2);
+        for g in groups {
+            let area = trapezoid_area(&g.x, &g.y);
+            assert!((area - 1.0).abs() < 0.01, "{}: area {}", g.key, area);
         }
 
-        // Check if x and y are numeric
-        let x_numeric: Result<Vec<f64>, _> = x_strs.iter().map(|s| s.parse::<f64>()).collect();
-        let x_is_numeric = x_numeric.is_ok();
-
-        if x_is_numeric && bins.is_some() {
-            // 2D numeric binning mode
-            let x_floats = x_numeric.unwrap();
-            let bin_count = bins.unwrap();
+        let (_, spec) = crate::parser::parse_plot_spec("aes(x: v) | density(bw: 0)").unwrap();
+        let err = crate::resolve::resolve_plot_aesthetics(&spec, &data).unwrap_err();
+        assert_eq!(err.to_string(), "density() bw must be greater than 0");
+    }
 
-            let x_min = x_floats.iter().fold(f64::INFINITY, |a, &b| a.min(b));
-            let x_max = x_floats.iter().fold(f64::NEG_INFINITY, |a, &b| a.max(b));
-            let y_min_val = y_vals.iter().fold(f64::INFINITY, |a, &b| a.min(b));
-            let y_max_val = y_vals.iter().fold(f64::NEG_INFINITY, |a, &b| a.max(b));
+    #[test]
+    fn test_transform_pie_slices_share_the_circle() {
+        let rows = [
+            ("a", "30"),
+            ("b", "50"),
+            ("a", "20"),
+            ("c", "1"),
+            ("d", "0.5"),
+        ]
+        .iter()
+        .map(|(x, y)| vec![x.to_string(), y.to_string()])
+        .collect();
+        let data = PlotData::new(vec!["x".into(), "y".into()], rows);
+        let angles = |dsl: &str| -> Vec<(String, f64, f64, f64)> {
+            let render_data = transform_dsl(dsl, &data);
+            render_data.panels[0].layers[0]
+                .groups
+                .iter()
+                .map(|g| match &g.style {
+                    RenderStyle::Pie {
+                        start_angle,
+                        end_angle,
+                        inner_radius,
+                        ..
+                    } => (g.key.clone(), *start_angle, *end_angle, *inner_radius),
+                    other => panic!("Expected a pie slice, got {:?}", other),
+                })
+                .collect()
+        };
+        let percent = |start: f64, end: f64| 100.0 * (end - start) / std::f64::consts::TAU;
+
+        // Rows with the same x sum into one slice; slices run end to end in x order
+        let slices = angles("aes(x: x, y: y) | pie()");
+        let keys: Vec<&str> = slices.iter().map(|s| s.0.as_str()).collect();
+        assert_eq!(keys, vec!["a", "b", "c", "d"]);
+        assert_eq!(slices[0].1, 0.0);
+        assert!(slices.windows(2).all(|w| w[0].2 == w[1].1));
+        let shares: Vec<f64> = slices.iter().map(|s| percent(s.1, s.2)).collect();
+        assert!((shares.iter().sum::<f64>() - 100.0).abs() < 1e-9);
+        assert!((shares[0] - 100.0 * 50.0 / 101.5).abs() < 1e-9);
+        assert!(slices.iter().all(|s| s.3 == 0.0));
+
+        // Under 2% of the total: c and d merge into a last "Other" slice
+        let slices = angles("aes(x: x, y: y) | pie(other: 0.02, donut: 0.4)");
+        let keys: Vec<&str> = slices.iter().map(|s| s.0.as_str()).collect();
+        assert_eq!(keys, vec!["a", "b", "Other"]);
+        let other = percent(slices[2].1, slices[2].2);
+        assert!((other - 100.0 * 1.5 / 101.5).abs() < 1e-9);
+        assert!((slices[2].2 - std::f64::consts::TAU).abs() < 1e-12);
+        assert!(slices.iter().all(|s| s.3 == 0.4));
+
+        // A lone small slice keeps its own label
+        let slices = angles("aes(x: x, y: y) | pie(other: 0.007)");
+        let keys: Vec<&str> = slices.iter().map(|s| s.0.as_str()).collect();
+        assert_eq!(keys, vec!["a", "b", "c", "d"]);
+
+        let negative = PlotData::new(
+            vec!["x".into(), "y".into()],
+            vec![vec!["a".into(), "3".into()], vec!["b".into(), "-1".into()]],
+        );
+        let (_, spec) = crate::parser::parse_plot_spec("aes(x: x, y: y) | pie()").unwrap();
+        let resolved = crate::resolve::resolve_plot_aesthetics(&spec, &negative).unwrap();
+        let err =
+            apply_transformations(&resolved, &negative, &RenderOptions::default()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "pie() slices can't be negative: '-1' in column 'y' (row 2)"
+        );
+    }
 
-            let x_range = if x_max == x_min { 1.0 } else { x_max - x_min };
-            let y_range = if y_max_val == y_min_val {
-                1.0
-            } else {
-                y_max_val - y_min_val
-            };
+    #[test]
+    fn test_transform_facet_grid_keeps_empty_combinations() {
+        let data = region_year_data();
+        let render_data = transform_dsl(
+            "aes(x: x, y: y) | point() | facet_grid(rows: region, cols: year)",
+            &data,
+        );
 
-            let x_bin_width = x_range / bin_count as f64;
-            let y_bin_width = y_range / bin_count as f64;
+        let layout = &render_data.facet_layout;
+        assert_eq!((layout.nrow, layout.ncol), (3, 2));
+        assert_eq!(layout.panel_titles, ["2023", "2024"].repeat(3));
+        assert_eq!(
+            layout.row_titles,
+            ["East", "East", "North", "North", "West", "West"]
+        );
+        let points: Vec<usize> = render_data
+            .panels
+            .iter()
+            .map(|p| p.layers[0].groups.iter().map(|g| g.x.len()).sum())
+            .collect();
+        // North has no 2024 rows, so its panel is empty instead of collapsing the grid
+        assert_eq!(points, vec![1, 1, 1, 0, 1, 1]);
+
+        // Only one variable: a single column (or row) of panels
+        let rows_only = transform_dsl(
+            "aes(x: x, y: y) | point() | facet_grid(rows: region)",
+            &data,
+        );
+        assert_eq!(
+            (rows_only.facet_layout.nrow, rows_only.facet_layout.ncol),
+            (3, 1)
+        );
+        let cols_only = transform_dsl("aes(x: x, y: y) | point() | facet_grid(cols: year)", &data);
+        assert_eq!(
+            (cols_only.facet_layout.nrow, cols_only.facet_layout.ncol),
+            (1, 2)
+        );
+    }
 
-            // 2D binning
-            let mut bin_counts: HashMap<(usize, usize), f64> = HashMap::new();
-            let has_fill = fill_vals.iter().any(|v| *v != 0.0);
+    #[test]
+    fn test_transform_facet_bins_cut_numeric_column() {
+        let data = PlotData::new(
+            vec!["x".into(), "y".into(), "age".into()],
+            ["2", "3", "5", "9", "12", "20", "NA"]
+                .iter()
+                .enumerate()
+                .map(|(i, age)| vec![i.to_string(), i.to_string(), age.to_string()])
+                .collect(),
+        );
+        let panels = |dsl: &str| -> Vec<(String, usize)> {
+            let render_data = transform_dsl(dsl, &data);
+            render_data
+                .facet_layout
+                .panel_titles
+                .iter()
+                .zip(&render_data.panels)
+                .map(|(title, p)| (title.clone(), p.layers[0].groups[0].x.len()))
+                .collect()
+        };
 
-            for i in 0..x_floats.len() {
-                let bx = ((x_floats[i] - x_min) / x_bin_width).floor() as usize;
-                let by = ((y_vals[i] - y_min_val) / y_bin_width).floor() as usize;
-                let bx = bx.min(bin_count - 1);
-                let by = by.min(bin_count - 1);
+        // Equal widths, in numeric (not lexicographic) order; the missing age is dropped
+        assert_eq!(
+            panels("aes(x: x, y: y) | point() | facet_wrap(by: age, bins: 2)"),
+            vec![("[2, 11)".to_string(), 4), ("[11, 20]".to_string(), 2)]
+        );
+        // Empty intervals get no panel
+        assert_eq!(
+            panels("aes(x: x, y: y) | point() | facet_wrap(by: age, bins: 6)")
+                .iter()
+                .map(|(title, _)| title.as_str())
+                .collect::<Vec<_>>(),
+            vec!["[2, 5)", "[5, 8)", "[8, 11)", "[11, 14)", "[17, 20]"]
+        );
+        // Quantile breaks split the rows evenly
+        assert_eq!(
+            panels(r#"aes(x: x, y: y) | point() | facet_wrap(by: age, bins: 2, cut: "quantile")"#),
+            vec![("[2, 7)".to_string(), 3), ("[7, 20]".to_string(), 3)]
+        );
 
-                let entry = bin_counts.entry((bx, by)).or_insert(0.0);
-                if has_fill {
-                    *entry += fill_vals[i];
-                } else {
-                    *entry += 1.0; // Count mode
-                }
-            }
+        let (_, spec) = crate::parser::parse_plot_spec(
+            "aes(x: x, y: y) | point() | facet_wrap(by: y, bins: 2)",
+        )
+        .unwrap();
+        let mut text = data.clone();
+        text.rows[1][1] = "n/a?".to_string();
+        let resolved = crate::resolve::resolve_plot_aesthetics(&spec, &text).unwrap();
+        let err = apply_transformations(&resolved, &text, &RenderOptions::default()).unwrap_err();
+        assert!(
+            err.to_string().contains(
+                "facet_wrap(bins: 2) needs a numeric column, but 'y' has the value 'n/a?'"
+            ),
+            "{}",
+            err
+        );
+    }
 
-            // Build cell data
-            let mut res_x = Vec::new();
-            let mut res_y_pos = Vec::new();
-            let mut res_fill = Vec::new();
+    #[test]
+    fn test_transform_facet_grid_free_x_keeps_column_categories() {
+        let data = region_year_data();
+        let render_data = transform_dsl(
+            r#"aes(x: x, y: y) | point() | facet_grid(rows: region, cols: year, scales: "free_x")"#,
+            &data,
+        );
+        let categories = |i: usize| -> Vec<String> {
+            render_data.panels[i].layers[0]
+                .groups
+                .iter()
+                .find_map(|g| g.x_categories.clone())
+                .unwrap_or_default()
+        };
+        // Each grid column keeps the categories used anywhere in that column
+        assert_eq!(categories(0), vec!["a", "c"]);
+        assert_eq!(categories(4), vec!["a", "c"]);
+        assert_eq!(categories(1), vec!["a", "b"]);
+        assert_eq!(categories(5), vec!["a", "b"]);
+    }
 
-            for bx in 0..bin_count {
-                for by in 0..bin_count {
-                    let count = bin_counts.get(&(bx, by)).copied().unwrap_or(0.0);
-                    let x_center = x_min + (bx as f64 + 0.5) * x_bin_width;
-                    let y_center = y_min_val + (by as f64 + 0.5) * y_bin_width;
+    #[test]
+    fn test_transform_linetype_mapping_groups_lines() {
+        let data = PlotData::new(
+            vec!["x".into(), "y".into(), "g".into()],
+            [
+                ["1", "1", "a"],
+                ["2", "2", "a"],
+                ["1", "3", "b"],
+                ["2", "4", "b"],
+                ["1", "5", "c"],
+                ["2", "6", "c"],
+            ]
+            .iter()
+            .map(|row| row.iter().map(|s| s.to_string()).collect())
+            .collect(),
+        );
+        let linetypes = |dsl: &str| -> Vec<(String, Option<String>)> {
+            transform_dsl(dsl, &data).panels[0].layers[0]
+                .groups
+                .iter()
+                .map(|group| {
+                    let RenderStyle::Line(style) = &group.style else {
+                        panic!("Expected line style");
+                    };
+                    (group.key.clone(), style.linetype.clone())
+                })
+                .collect()
+        };
 
-                    res_x.push(format!("{}", x_center));
-                    res_y_pos.push(y_center);
-                    res_fill.push(count);
-                }
-            }
+        let mapped = linetypes("aes(x: x, y: y) | line(linetype: g)");
+        assert_eq!(
+            mapped,
+            vec![
+                ("a".to_string(), Some("solid".to_string())),
+                ("b".to_string(), Some("dashed".to_string())),
+                ("c".to_string(), Some("dotted".to_string())),
+            ]
+        );
+        // The global mapping groups the same way
+        assert_eq!(linetypes("aes(x: x, y: y, linetype: g) | line()"), mapped);
 
-            // Use y_pos as y for scale calculation
-            let res_y = res_y_pos.clone();
-            let res_ymin = vec![0.0; res_y.len()];
-            let res_ymax = res_y.clone();
+        // A fixed linetype styles the single group; color still groups
+        let fixed = linetypes(r#"aes(x: x, y: y, color: g) | line(linetype: "dotdash")"#);
+        assert_eq!(fixed.len(), 3);
+        assert!(fixed
+            .iter()
+            .all(|(_, linetype)| linetype.as_deref() == Some("dotdash")));
+    }
 
-            new_groups.insert(
-                key,
-                StatData {
-                    x: res_x,
-                    y: res_y,
-                    ymin: res_ymin,
-                    ymax: res_ymax,
-                    boxplot: None,
-                    violin: None,
-                    heatmap: Some(HeatmapData {
-                        y_positions: res_y_pos,
-                        fill_values: res_fill,
-                        cell_width: x_bin_width,
-                        cell_height: y_bin_width,
-                        y_categories: None,
-                    }),
-                },
-            );
-        } else {
-            // Categorical mode: treat x and y as categories
-            // y_vals are parsed from the y column (numeric), fill_vals from the fill column
-            // Group by (x, y_as_string) pairs
-            let mut unique_x: Vec<String> = Vec::new();
-            let mut unique_y: Vec<String> = Vec::new();
-            let mut seen_x: std::collections::HashSet<String> = std::collections::HashSet::new();
-            let mut seen_y: std::collections::HashSet<String> = std::collections::HashSet::new();
+    #[test]
+    fn test_transform_groups_by_every_mapped_column() {
+        let data = PlotData::new(
+            vec!["a".into(), "b".into(), "region".into(), "product".into()],
+            [
+                ["1", "2", "EU", "widget"],
+                ["2", "3", "EU", "gadget"],
+                ["3", "4", "EU", "gizmo"],
+                ["1", "5", "US", "widget"],
+                ["2", "6", "US", "gadget"],
+                ["3", "1", "US", "gizmo"],
+            ]
+            .iter()
+            .map(|row| row.iter().map(|s| s.to_string()).collect())
+            .collect(),
+        );
+        let layer = &transform_dsl(
+            "aes(x: a, y: b, color: region, shape: product) | point()",
+            &data,
+        )
+        .panels[0]
+            .layers[0];
+        assert_eq!(
+            layer.group_aesthetics,
+            vec![vec![GroupAesthetic::Color], vec![GroupAesthetic::Shape]]
+        );
 
-            // For categorical heatmap, y values come as f64 from y column
-            // We need to track unique y values as strings for category labels
-            let y_strings: Vec<String> = y_vals.iter().map(|v| format!("{}", v)).collect();
+        let styles: Vec<(Vec<String>, Option<String>, Option<String>)> = layer
+            .groups
+            .iter()
+            .map(|group| {
+                let RenderStyle::Point(style) = &group.style else {
+                    panic!("Expected point style");
+                };
+                (
+                    group.key_parts.clone(),
+                    style.color.clone(),
+                    style.shape.clone(),
+                )
+            })
+            .collect();
+        assert_eq!(styles.len(), 6);
+        assert_eq!(layer.groups[0].key, "EU, gadget");
+        assert_eq!(styles[0].0, vec!["EU".to_string(), "gadget".to_string()]);
+        // Color follows the region alone and shape the product alone
+        for (parts, color, shape) in &styles {
+            let same_region = styles.iter().filter(|(p, ..)| p[0] == parts[0]);
+            assert!(same_region.clone().all(|(_, c, _)| c == color));
+            assert_eq!(same_region.count(), 3);
+            let same_product = styles.iter().filter(|(p, ..)| p[1] == parts[1]);
+            assert!(same_product.clone().all(|(_, _, s)| s == shape));
+            assert_eq!(same_product.count(), 2);
+        }
+        let colors: HashSet<_> = styles.iter().map(|(_, color, _)| color).collect();
+        let shapes: HashSet<_> = styles.iter().map(|(_, _, shape)| shape).collect();
+        assert_eq!((colors.len(), shapes.len()), (2, 3));
+
+        // One column mapped to both aesthetics is still one key part
+        let layer = &transform_dsl(
+            "aes(x: a, y: b, color: region, shape: region) | point()",
+            &data,
+        )
+        .panels[0]
+            .layers[0];
+        assert_eq!(layer.groups.len(), 2);
+        assert_eq!(
+            layer.group_aesthetics,
+            vec![vec![GroupAesthetic::Color, GroupAesthetic::Shape]]
+        );
+    }
 
-            for x in &x_strs {
-                if seen_x.insert(x.clone()) {
-                    unique_x.push(x.clone());
-                }
-            }
-            for y_s in &y_strings {
-                if seen_y.insert(y_s.clone()) {
-                    unique_y.push(y_s.clone());
-                }
+    #[test]
+    fn test_transform_group_splits_without_styling() {
+        let mut rows = Vec::new();
+        for sensor in 0..50 {
+            for time in 0..3 {
+                rows.push(vec![
+                    time.to_string(),
+                    (sensor + time).to_string(),
+                    format!("s{}", sensor),
+                    if sensor % 2 == 0 { "even" } else { "odd" }.to_string(),
+                ]);
             }
-            // Preserve data appearance order (no alphabetical sort)
+        }
+        let data = PlotData::new(
+            vec![
+                "time".into(),
+                "value".into(),
+                "sensor".into(),
+                "kind".into(),
+            ],
+            rows,
+        );
+        let styles = |dsl: &str| -> Vec<(usize, Option<String>)> {
+            transform_dsl(dsl, &data).panels[0].layers[0]
+                .groups
+                .iter()
+                .map(|group| {
+                    let RenderStyle::Line(style) = &group.style else {
+                        panic!("Expected line style");
+                    };
+                    (group.x.len(), style.color.clone())
+                })
+                .collect()
+        };
 
-            let x_count = unique_x.len();
-            let y_count = unique_y.len();
+        let grouped = styles("aes(x: time, y: value, group: sensor) | line()");
+        assert_eq!(grouped.len(), 50);
+        assert!(grouped.iter().all(|(n, _)| *n == 3));
+        assert!(grouped.iter().all(|(_, color)| *color == grouped[0].1));
+        // The line layer's own group overrides the global one
+        assert_eq!(
+            styles("aes(x: time, y: value, group: kind) | line(group: sensor)").len(),
+            50
+        );
 
-            // Build cell lookup
-            let x_idx_map: HashMap<String, usize> = unique_x
-                .iter()
-                .enumerate()
-                .map(|(i, s)| (s.clone(), i))
-                .collect();
-            let y_idx_map: HashMap<String, usize> = unique_y
-                .iter()
-                .enumerate()
-                .map(|(i, s)| (s.clone(), i))
-                .collect();
+        // Color still styles by its own column inside the finer groups
+        let layer = &transform_dsl(
+            "aes(x: time, y: value, color: kind, group: sensor) | line()",
+            &data,
+        )
+        .panels[0]
+            .layers[0];
+        assert_eq!(layer.groups.len(), 50);
+        assert_eq!(
+            layer.group_aesthetics,
+            vec![vec![GroupAesthetic::Color], vec![]]
+        );
+        let colors: HashSet<_> = layer
+            .groups
+            .iter()
+            .map(|group| match &group.style {
+                RenderStyle::Line(style) => style.color.clone(),
+                _ => panic!("Expected line style"),
+            })
+            .collect();
+        assert_eq!(colors.len(), 2);
+
+        // Grouping by a column color already maps adds nothing
+        let layer = &transform_dsl(
+            "aes(x: time, y: value, color: kind, group: kind) | line()",
+            &data,
+        )
+        .panels[0]
+            .layers[0];
+        assert_eq!(layer.groups.len(), 2);
+        assert_eq!(layer.group_aesthetics, vec![vec![GroupAesthetic::Color]]);
+    }
 
-            // Aggregate values per cell
-            let mut cell_values: HashMap<(usize, usize), f64> = HashMap::new();
-            let mut cell_counts: HashMap<(usize, usize), usize> = HashMap::new();
+    #[test]
+    fn test_transform_fixed_values_win_over_inherited_mappings() {
+        let data = PlotData::new(
+            vec!["x".into(), "y".into(), "g".into(), "h".into()],
+            (0..6)
+                .map(|i| {
+                    vec![
+                        i.to_string(),
+                        i.to_string(),
+                        ["a", "b"][i % 2].to_string(),
+                        ["p", "q", "r"][i % 3].to_string(),
+                    ]
+                })
+                .collect(),
+        );
+        let value = |style: &PointStyle, aesthetic: &str| match aesthetic {
+            "color" => style.color.clone(),
+            "size" => style.size.map(|v| v.to_string()),
+            "alpha" => style.alpha.map(|v| v.to_string()),
+            "shape" => style.shape.clone(),
+            _ => unreachable!(),
+        };
+        // Each group's value of every aesthetic
+        let styles = |dsl: &str| -> Vec<HashMap<&str, Option<String>>> {
+            transform_dsl(dsl, &data).panels[0].layers[0]
+                .groups
+                .iter()
+                .map(|group| {
+                    let RenderStyle::Point(style) = &group.style else {
+                        panic!("Expected point style");
+                    };
+                    ["color", "size", "alpha", "shape"]
+                        .into_iter()
+                        .map(|a| (a, value(style, a)))
+                        .collect()
+                })
+                .collect()
+        };
+        let distinct = |styles: &[HashMap<&str, Option<String>>], aesthetic: &str| {
+            styles
+                .iter()
+                .map(|s| s[aesthetic].clone())
+                .collect::<HashSet<_>>()
+                .len()
+        };
 
-            for i in 0..x_strs.len() {
-                let xi = x_idx_map[&x_strs[i]];
-                let yi = y_idx_map[&y_strings[i]];
-                let val = fill_vals[i];
-                *cell_values.entry((xi, yi)).or_insert(0.0) += val;
-                *cell_counts.entry((xi, yi)).or_insert(0) += 1;
+        let fixed = [
+            ("color", r#""red""#, "red"),
+            ("size", "9", "9"),
+            ("alpha", "0.3", "0.3"),
+            ("shape", r#""star""#, "star"),
+        ];
+        for (aesthetic, literal, expected) in fixed {
+            // (layer setting, global mapping) -> (groups, distinct values, fixed value kept)
+            let cases = [
+                (format!("{}: {}", aesthetic, literal), true, (2, 1, true)),
+                (format!("{}: h", aesthetic), true, (3, 3, false)),
+                (String::new(), true, (2, 2, false)),
+                (format!("{}: {}", aesthetic, literal), false, (1, 1, true)),
+                (format!("{}: h", aesthetic), false, (3, 3, false)),
+                (String::new(), false, (1, 1, false)),
+            ];
+            for (layer, global, (groups, values, keeps_fixed)) in cases {
+                let mapping = if global {
+                    format!(", {}: g", aesthetic)
+                } else {
+                    String::new()
+                };
+                let dsl = format!("aes(x: x, y: y{}) | point({})", mapping, layer);
+                let styles = styles(&dsl);
+                assert_eq!(styles.len(), groups, "{}", dsl);
+                assert_eq!(distinct(&styles, aesthetic), values, "{}", dsl);
+                assert_eq!(
+                    styles
+                        .iter()
+                        .all(|s| s[aesthetic].as_deref() == Some(expected)),
+                    keeps_fixed,
+                    "{}",
+                    dsl
+                );
             }
 
-            // Build output
-            let mut res_x = Vec::new();
-            let mut res_y_pos = Vec::new();
-            let mut res_fill = Vec::new();
-
-            for xi in 0..x_count {
-                for yi in 0..y_count {
-                    res_x.push(unique_x[xi].clone());
-                    res_y_pos.push(yi as f64);
-                    let val = cell_values.get(&(xi, yi)).copied().unwrap_or(0.0);
-                    let count = cell_counts.get(&(xi, yi)).copied().unwrap_or(0);
-                    // If we have fill values, use mean; otherwise count
-                    let fill_val = if fill_vals.iter().any(|v| *v != 0.0) && count > 0 {
-                        val / count as f64
-                    } else if count > 0 {
-                        count as f64
-                    } else {
-                        0.0
-                    };
-                    res_fill.push(fill_val);
+            // A fixed value survives another aesthetic's mapping, global or per layer
+            for (other, ..) in fixed.iter().filter(|(a, ..)| *a != aesthetic) {
+                for dsl in [
+                    format!(
+                        "aes(x: x, y: y, {}: h) | point({}: {})",
+                        other, aesthetic, literal
+                    ),
+                    format!(
+                        "aes(x: x, y: y) | point({}: h, {}: {})",
+                        other, aesthetic, literal
+                    ),
+                ] {
+                    let styles = styles(&dsl);
+                    assert_eq!(distinct(&styles, other), 3, "{}", dsl);
+                    assert!(
+                        styles
+                            .iter()
+                            .all(|s| s[aesthetic].as_deref() == Some(expected)),
+                        "{}",
+                        dsl
+                    );
                 }
             }
-
-            let res_y = res_y_pos.clone();
-            let res_ymin = vec![0.0; res_y.len()];
-            let res_ymax = vec![(y_count as f64 - 1.0).max(0.0); res_y.len()];
-
-            new_groups.insert(
-                key,
-                StatData {
-                    x: res_x,
-                    y: res_y,
-                    ymin: res_ymin,
-                    ymax: res_ymax,
-                    boxplot: None,
-                    violin: None,
-                    heatmap: Some(HeatmapData {
-                        y_positions: res_y_pos,
-                        fill_values: res_fill,
-                        cell_width: 1.0,
-                        cell_height: 1.0,
-                        y_categories: Some(unique_y),
-                    }),
-                },
-            );
         }
+
+        // The overridden column still groups but styles nothing
+        let layer = &transform_dsl(
+            r#"aes(x: x, y: y, color: g, shape: h) | point(color: "red")"#,
+            &data,
+        )
+        .panels[0]
+            .layers[0];
+        assert_eq!(layer.groups.len(), 6);
+        assert_eq!(
+            layer.group_aesthetics,
+            vec![vec![GroupAesthetic::Shape], vec![]]
+        );
     }
 
-    Ok(new_groups)
-}
+    #[test]
+    fn test_transform_limits_styled_group_count() {
+        let ids = |count: usize| {
+            let rows = (0..count)
+                .map(|i| vec![i.to_string(), (i % 7).to_string(), format!("id{}", i)])
+                .collect();
+            PlotData::new(vec!["x".into(), "y".into(), "id".into()], rows)
+        };
+        let transform = |dsl: &str, data: &PlotData, max_groups: usize| {
+            let (_, spec) = crate::parser::parse_plot_spec(dsl).unwrap();
+            let resolved = crate::resolve::resolve_plot_aesthetics(&spec, data).unwrap();
+            let options = RenderOptions {
+                max_groups,
+                ..Default::default()
+            };
+            apply_transformations(&resolved, data, &options)
+        };
 
-fn apply_statistics(
-    groups: HashMap<String, (Vec<String>, Vec<f64>, Vec<f64>, Vec<f64>)>,
-    stat: &Stat,
-) -> Result<HashMap<String, StatData>> {
-    match stat {
-        Stat::Identity => Ok(groups
-            .into_iter()
-            .map(|(k, v)| (k, StatData::from_tuple(v)))
-            .collect()),
-        Stat::Bin { bins } => compute_bin_stat(groups, *bins),
-        Stat::Count => compute_count_stat(groups),
-        Stat::Smooth {
-            method,
-            span,
-            samples,
-        } => compute_smooth_stat(groups, method, *span, *samples),
-        Stat::Boxplot => compute_boxplot_stat(groups),
-        Stat::Violin { draw_quantiles } => compute_violin_stat(groups, draw_quantiles),
-        Stat::Density { bw } => compute_density_stat(groups, *bw),
-        Stat::Heatmap { bins } => compute_heatmap_stat(groups, *bins),
+        // The default allows exactly 50
+        let at_limit = transform_dsl("aes(x: x, y: y, color: id) | point()", &ids(50));
+        assert_eq!(at_limit.panels[0].layers[0].groups.len(), 50);
+        let err = transform("aes(x: x, y: y, color: id) | point()", &ids(51), 50).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Column 'id' has 51 distinct values, more than the 50 groups allowed (did you mean group:, which splits series without styling them; raise the limit with --max-groups)"
+        );
+        // Faceted or not, and for any styled aesthetic
+        assert!(transform(
+            "aes(x: x, y: y) | line(linetype: id) | facet_wrap(by: y)",
+            &ids(51),
+            50
+        )
+        .is_err());
+
+        // group: styles nothing, and 0 turns the check off
+        assert!(transform("aes(x: x, y: y, group: id) | line()", &ids(51), 50).is_ok());
+        assert!(transform("aes(x: x, y: y, color: id) | point()", &ids(51), 0).is_ok());
+        assert!(transform("aes(x: x, y: y, color: id) | point()", &ids(9), 8).is_err());
+
+        // Numeric columns point at the continuous scales
+        let err = transform("aes(x: y, y: x, color: x) | bar()", &ids(9871), 50).unwrap_err();
+        let message = err.to_string();
+        assert!(message.starts_with("Column 'x' has 9,871 distinct values"));
+        assert!(message.contains("continuous scale on point()"));
+        // ...which don't group at all
+        assert!(transform("aes(x: y, y: x, color: x) | point()", &ids(9871), 50).is_ok());
     }
-}
 
-fn compute_count_stat(
-    groups: HashMap<String, (Vec<String>, Vec<f64>, Vec<f64>, Vec<f64>)>,
-) -> Result<HashMap<String, StatData>> {
-    let mut new_groups = HashMap::new();
+    #[test]
+    fn test_format_count() {
+        assert_eq!(format_count(7), "7");
+        assert_eq!(format_count(999), "999");
+        assert_eq!(format_count(1000), "1,000");
+        assert_eq!(format_count(9871), "9,871");
+        assert_eq!(format_count(1234567), "1,234,567");
+    }
 
-    for (key, (x_strs, _, _, _)) in groups {
-        let mut counts: HashMap<String, usize> = HashMap::new();
-        for s in x_strs {
-            *counts.entry(s).or_default() += 1;
+    #[test]
+    fn test_transform_facets_share_group_palette() {
+        // Group "a" only appears in panel "q", so panel-local keys would give
+        // "b" a different color in each panel
+        let data = PlotData::new(
+            vec!["x".into(), "y".into(), "g".into(), "f".into()],
+            [
+                ["1", "1", "b", "p"],
+                ["2", "2", "c", "p"],
+                ["1", "3", "a", "q"],
+                ["2", "4", "b", "q"],
+                ["3", "5", "c", "q"],
+            ]
+            .iter()
+            .map(|row| row.iter().map(|s| s.to_string()).collect())
+            .collect(),
+        );
+        let (_, spec) = crate::parser::parse_plot_spec(
+            "aes(x: x, y: y, color: g, shape: g) | point() | facet_wrap(by: f)",
+        )
+        .unwrap();
+        let resolved = crate::resolve::resolve_plot_aesthetics(&spec, &data).unwrap();
+        let render_data =
+            apply_transformations(&resolved, &data, &RenderOptions::default()).unwrap();
+
+        let mut styles: HashMap<String, (Option<String>, Option<String>)> = HashMap::new();
+        for panel in &render_data.panels {
+            for group in &panel.layers[0].groups {
+                let RenderStyle::Point(style) = &group.style else {
+                    panic!("Expected point style");
+                };
+                let style = (style.color.clone(), style.shape.clone());
+                assert_eq!(
+                    styles.entry(group.key.clone()).or_insert(style.clone()),
+                    &style,
+                    "group {} changed style between panels",
+                    group.key
+                );
+            }
         }
+        assert_eq!(styles.len(), 3);
+        let colors: HashSet<_> = styles.values().map(|(color, _)| color.clone()).collect();
+        assert_eq!(colors.len(), 3);
+    }
 
-        let mut keys: Vec<String> = counts.keys().cloned().collect();
-        keys.sort();
+    #[test]
+    fn test_grid_dimensions_honor_ncol_or_nrow() {
+        let facet = |ncol, nrow| ResolvedFacet {
+            col: "g".to_string(),
+            ncol,
+            nrow,
+            scales: crate::parser::ast::FacetScales::Fixed,
+            bins: None,
+            cut: Default::default(),
+            grid: None,
+            labeller: Default::default(),
+            label_size: None,
+        };
+        assert_eq!(calculate_grid_dimensions(5, None), (2, 3));
+        assert_eq!(
+            calculate_grid_dimensions(5, Some(&facet(Some(2), None))),
+            (3, 2)
+        );
+        assert_eq!(
+            calculate_grid_dimensions(5, Some(&facet(None, Some(1)))),
+            (1, 5)
+        );
+        assert_eq!(
+            calculate_grid_dimensions(5, Some(&facet(None, Some(2)))),
+            (2, 3)
+        );
+    }
 
-        let mut new_x = Vec::new();
-        let mut new_y = Vec::new();
-        let mut new_ymin = Vec::new();
-        let mut new_ymax = Vec::new();
+    #[test]
+    fn test_transform_point_fade_by() {
+        let mut spec = make_spec();
+        spec.layers[0].original_layer = Layer::Point(crate::parser::ast::PointLayer {
+            fade_by: Some("x".into()),
+            ..Default::default()
+        });
 
-        for k in keys {
-            let count = counts[&k] as f64;
-            new_x.push(k);
-            new_y.push(count);
-            new_ymin.push(0.0);
-            new_ymax.push(count);
-        }
+        let csv = make_data();
+        let render_data = apply_transformations(&spec, &csv, &RenderOptions::default()).unwrap();
+        let layer = &render_data.panels[0].layers[0];
 
-        new_groups.insert(
-            key,
-            StatData::from_tuple((new_x, new_y, new_ymin, new_ymax)),
+        // Fade is computed within each group: oldest -> 0.15, newest -> 1.0
+        let group_a = layer.groups.iter().find(|g| g.key == "A").unwrap();
+        assert_eq!(group_a.point_alphas, vec![0.15, 1.0]);
+        // A single-point group has no span and stays fully opaque
+        let group_b = layer.groups.iter().find(|g| g.key == "B").unwrap();
+        assert_eq!(group_b.point_alphas, vec![1.0]);
+
+        assert_eq!(
+            compute_fade_alphas(&[1.0, 2.0], FadeDirection::Newest),
+            vec![1.0, 0.15]
         );
     }
 
-    Ok(new_groups)
-}
+    #[test]
+    fn test_transform_point_jitter() {
+        let mut spec = make_spec();
+        spec.layers[0].aesthetics.x_col = "cat".to_string();
+        spec.layers[0].aesthetics.color = None;
+        spec.layers[0].original_layer = Layer::Point(crate::parser::ast::PointLayer {
+            position: PointPosition::Jitter,
+            jitter_width: Some(0.2),
+            ..Default::default()
+        });
 
-fn compute_smooth_stat(
-    groups: HashMap<String, (Vec<String>, Vec<f64>, Vec<f64>, Vec<f64>)>,
-    method: &str,
-    span: Option<f64>,
-    samples: Option<usize>,
-) -> Result<HashMap<String, StatData>> {
-    let mut new_groups = HashMap::new();
+        let csv = make_data();
+        let render_data = apply_transformations(&spec, &csv, &RenderOptions::default()).unwrap();
+        let group = &render_data.panels[0].layers[0].groups[0];
 
-    for (key, (x_strs, y_vals, _, _)) in groups {
-        let mut x_floats = Vec::new();
-        for s in &x_strs {
-            x_floats.push(
-                s.parse::<f64>()
-                    .map_err(|_| anyhow!("Stat 'smooth' requires numeric x data"))?,
-            );
+        // Each point stays within +/- width/2 of its category slot (A = 0, B = 1)
+        let slots = [0.0, 0.0, 1.0];
+        for (x, slot) in group.x.iter().zip(slots) {
+            assert!((x - slot).abs() <= 0.1, "{} is outside the jitter band", x);
         }
+        assert_ne!(group.x[0], group.x[1]);
 
-        if x_floats.len() < 2 {
-            continue;
-        }
+        // Offsets come from the row index, so repeated runs match exactly
+        let again = apply_transformations(&spec, &csv, &RenderOptions::default()).unwrap();
+        assert_eq!(again.panels[0].layers[0].groups[0].x, group.x);
+    }
 
-        let method = method.to_ascii_lowercase();
-        let (new_x_values, new_y) = match method.as_str() {
-            "lm" | "linear" | "linear_regression" => {
-                compute_linear_smooth_points(&x_floats, &y_vals)
-            }
-            "loess" | "lowess" => compute_loess_smooth_points(&x_floats, &y_vals, span, samples),
-            other => {
-                return Err(anyhow!(
-                    "Unknown smooth method '{}'. Supported methods: \"lm\", \"loess\"",
-                    other
-                ))
-            }
-        }?;
+    #[test]
+    fn test_transform_point_jitter_numeric_default_width() {
+        let mut spec = make_spec();
+        spec.layers[0].original_layer = Layer::Point(crate::parser::ast::PointLayer {
+            position: PointPosition::Jitter,
+            ..Default::default()
+        });
 
-        let new_x: Vec<String> = new_x_values.iter().map(|x| x.to_string()).collect();
-        let new_ymin = new_y.clone();
-        let new_ymax = new_y.clone();
+        let csv = make_data();
+        let render_data = apply_transformations(&spec, &csv, &RenderOptions::default()).unwrap();
+        let layer = &render_data.panels[0].layers[0];
 
-        new_groups.insert(
-            key,
-            StatData::from_tuple((new_x, new_y, new_ymin, new_ymax)),
+        // Numeric x values 1 and 2 are 1.0 apart, so points move at most 0.2
+        let group_a = layer.groups.iter().find(|g| g.key == "A").unwrap();
+        assert!((group_a.x[0] - 1.0).abs() <= 0.2);
+        assert!((group_a.x[1] - 2.0).abs() <= 0.2);
+        let group_b = layer.groups.iter().find(|g| g.key == "B").unwrap();
+        assert!((group_b.x[0] - 1.0).abs() <= 0.2);
+
+        for row in 0..1000 {
+            let unit = jitter_unit(row);
+            assert!((-0.5..0.5).contains(&unit));
+        }
+    }
+
+    #[test]
+    fn test_transform_tile_numeric_spacing() {
+        let mut spec = make_spec();
+        spec.layers[0].original_layer = Layer::Heatmap(crate::parser::ast::HeatmapLayer {
+            stat: Stat::Heatmap { bins: None },
+            ..Default::default()
+        });
+        spec.layers[0].aesthetics.color = None;
+        spec.layers[0].aesthetics.fill = Some("fill".to_string());
+
+        let csv = PlotData {
+            headers: vec!["x".to_string(), "y".to_string(), "fill".to_string()],
+            rows: vec![
+                vec!["0".to_string(), "1".to_string(), "3".to_string()],
+                vec!["2".to_string(), "1".to_string(), "5".to_string()],
+                vec!["4".to_string(), "2".to_string(), "7".to_string()],
+            ],
+        };
+        let render_data = apply_transformations(&spec, &csv, &RenderOptions::default()).unwrap();
+        let group = &render_data.panels[0].layers[0].groups[0];
+
+        // Numeric x keeps its positions; cells fill the regular spacing of 2
+        assert_eq!(group.x_categories, None);
+        assert_eq!(group.heatmap_cell_width, 2.0);
+        assert_eq!(group.heatmap_cell_height, 1.0);
+        assert_eq!(
+            group.heatmap_fill_values,
+            vec![3.0, 0.0, 5.0, 0.0, 0.0, 7.0]
         );
     }
 
-    Ok(new_groups)
-}
+    #[test]
+    fn test_transform_continuous_color_does_not_group() {
+        let mut spec = make_spec();
+        spec.layers[0].original_layer = Layer::Point(crate::parser::ast::PointLayer::default());
+        spec.layers[0].aesthetics.color = Some("y".to_string());
+        spec.facet = Some(ResolvedFacet {
+            col: "cat".to_string(),
+            ncol: None,
+            nrow: None,
+            scales: crate::parser::ast::FacetScales::Fixed,
+            bins: None,
+            cut: Default::default(),
+            grid: None,
+            labeller: Default::default(),
+            label_size: None,
+        });
 
-fn compute_linear_smooth_points(x: &[f64], y: &[f64]) -> Result<(Vec<f64>, Vec<f64>)> {
-    let n = x.len() as f64;
-    let sum_x: f64 = x.iter().sum();
-    let sum_y: f64 = y.iter().sum();
-    let sum_xx: f64 = x.iter().map(|&x| x * x).sum();
-    let sum_xy: f64 = x.iter().zip(y.iter()).map(|(&x, &y)| x * y).sum();
+        let csv = make_data();
+        let render_data = apply_transformations(&spec, &csv, &RenderOptions::default()).unwrap();
+
+        // One ungrouped set of points per panel, each carrying its own color value
+        for panel in &render_data.panels {
+            let layer = &panel.layers[0];
+            assert_eq!(layer.groups.len(), 1);
+            assert_eq!(layer.groups[0].key, "default");
+            assert_eq!(layer.groups[0].color_values, layer.groups[0].y);
+            // The domain spans every facet, not just this panel's rows
+            assert_eq!(layer.color_domain, Some((10.0, 20.0)));
+        }
 
-    let denom = n * sum_xx - sum_x * sum_x;
-    if denom.abs() < f64::EPSILON {
-        return Err(anyhow!(
-            "Stat 'smooth' requires x data with non-zero variance"
-        ));
+        // Non-numeric color columns still group categorically
+        spec.layers[0].aesthetics.color = Some("cat".to_string());
+        spec.facet = None;
+        let render_data = apply_transformations(&spec, &csv, &RenderOptions::default()).unwrap();
+        let layer = &render_data.panels[0].layers[0];
+        assert_eq!(layer.groups.len(), 2);
+        assert_eq!(layer.color_domain, None);
+        assert!(layer.groups.iter().all(|g| g.color_values.is_empty()));
     }
 
-    let slope = (n * sum_xy - sum_x * sum_y) / denom;
-    let intercept = (sum_y - slope * sum_x) / n;
-
-    let min_x = x.iter().fold(f64::INFINITY, |a, &b| a.min(b));
-    let max_x = x.iter().fold(f64::NEG_INFINITY, |a, &b| a.max(b));
+    #[test]
+    fn test_transform_continuous_size_does_not_group() {
+        let mut spec = make_spec();
+        spec.layers[0].original_layer = Layer::Point(crate::parser::ast::PointLayer::default());
+        spec.layers[0].aesthetics.color = None;
+        spec.layers[0].aesthetics.size = Some("y".to_string());
 
-    Ok((
-        vec![min_x, max_x],
-        vec![slope * min_x + intercept, slope * max_x + intercept],
-    ))
-}
+        let csv = make_data();
+        let render_data = apply_transformations(&spec, &csv, &RenderOptions::default()).unwrap();
+        let layer = &render_data.panels[0].layers[0];
+        assert_eq!(layer.groups.len(), 1);
+        assert_eq!(layer.groups[0].size_values, layer.groups[0].y);
+        assert_eq!(layer.size_domain, Some((10.0, 20.0)));
+
+        // Categorical color still groups while size stays continuous within each group
+        spec.layers[0].aesthetics.color = Some("cat".to_string());
+        let render_data = apply_transformations(&spec, &csv, &RenderOptions::default()).unwrap();
+        let layer = &render_data.panels[0].layers[0];
+        assert_eq!(layer.groups.len(), 2);
+        assert!(layer
+            .groups
+            .iter()
+            .all(|g| g.size_values.len() == g.y.len()));
+
+        // Non-numeric size columns still group categorically
+        spec.layers[0].aesthetics.color = None;
+        spec.layers[0].aesthetics.size = Some("cat".to_string());
+        let render_data = apply_transformations(&spec, &csv, &RenderOptions::default()).unwrap();
+        let layer = &render_data.panels[0].layers[0];
+        assert_eq!(layer.groups.len(), 2);
+        assert_eq!(layer.size_domain, None);
+    }
 
-fn compute_loess_smooth_points(
-    x: &[f64],
-    y: &[f64],
-    span: Option<f64>,
-    samples: Option<usize>,
-) -> Result<(Vec<f64>, Vec<f64>)> {
-    if x.len() != y.len() || x.len() < 2 {
-        return Ok((Vec::new(), Vec::new()));
+    #[test]
+    fn test_transform_skips_missing_values_and_breaks_lines() {
+        let csv = PlotData {
+            headers: vec!["x".to_string(), "y".to_string(), "cat".to_string()],
+            rows: vec![
+                vec!["1".to_string(), "NA".to_string(), "A".to_string()],
+                vec!["2".to_string(), "20".to_string(), "A".to_string()],
+                vec!["3".to_string(), "".to_string(), "A".to_string()],
+                vec!["4".to_string(), "40".to_string(), "A".to_string()],
+                vec!["5".to_string(), "50".to_string(), "A".to_string()],
+                vec!["6".to_string(), "NaN".to_string(), "A".to_string()],
+                vec!["1".to_string(), "5".to_string(), "null".to_string()],
+            ],
+        };
+        let spec = make_spec();
+        let render_data = apply_transformations(&spec, &csv, &RenderOptions::default()).unwrap();
+        let layer = &render_data.panels[0].layers[0];
+        assert_eq!(layer.skipped_rows, 4);
+        assert_eq!(layer.groups.len(), 1);
+
+        let group = &layer.groups[0];
+        assert_eq!(group.x, vec![2.0, 4.0, 5.0]);
+        assert_eq!(group.y, vec![20.0, 40.0, 50.0]);
+        // Leading and trailing gaps need no break; the interior gap does
+        assert_eq!(group.line_breaks, vec![1]);
+
+        let err = apply_transformations(
+            &spec,
+            &csv,
+            &RenderOptions {
+                strict: true,
+                ..Default::default()
+            },
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("Failed to parse y value 'NA'"));
     }
 
-    let mut pairs: Vec<(f64, f64)> = x.iter().copied().zip(y.iter().copied()).collect();
-    pairs.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+    #[test]
+    fn test_transform_skips_non_finite_values() {
+        let row = |x: &str, y: &str| vec![x.to_string(), y.to_string(), "A".to_string()];
+        let csv = PlotData {
+            headers: vec!["x".to_string(), "y".to_string(), "cat".to_string()],
+            rows: vec![
+                row("1", "10"),
+                row("2", "inf"),
+                row("3", "-inf"),
+                row("4", "NaN"),
+                row("inf", "50"),
+                row("6", "60"),
+            ],
+        };
+        let spec = make_spec();
+        let render_data = apply_transformations(&spec, &csv, &RenderOptions::default()).unwrap();
+        let layer = &render_data.panels[0].layers[0];
+        assert_eq!(layer.skipped_rows, 4);
+        assert_eq!(layer.groups[0].x, vec![1.0, 6.0]);
+        assert_eq!(layer.groups[0].y, vec![10.0, 60.0]);
+        assert_eq!(layer.groups[0].line_breaks, vec![1]);
+
+        let strict = RenderOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let err = apply_transformations(&spec, &csv, &strict).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Non-finite value 'inf' in column 'y' (row 2); without --strict such rows are skipped"
+        );
 
-    let min_x = pairs.first().map(|p| p.0).unwrap();
-    let max_x = pairs.last().map(|p| p.0).unwrap();
-    if (max_x - min_x).abs() < f64::EPSILON {
-        return Err(anyhow!(
-            "Stat 'smooth' requires x data with non-zero variance"
-        ));
+        // A column with no finite values leaves nothing to draw, not a broken range
+        let csv = PlotData {
+            headers: csv.headers.clone(),
+            rows: vec![row("1", "inf"), row("2", "-inf"), row("3", "NaN")],
+        };
+        let render_data = apply_transformations(&spec, &csv, &RenderOptions::default()).unwrap();
+        let layer = &render_data.panels[0].layers[0];
+        assert_eq!(layer.skipped_rows, 3);
+        assert!(layer.groups.iter().all(|g| g.x.is_empty()));
+        assert!(crate::scale::build_scales(&render_data, &spec).is_ok());
     }
 
-    let sample_count = samples.unwrap_or(80).max(2);
-    let span = span.unwrap_or(0.75).clamp(0.05, 1.0);
-    let neighborhood = ((pairs.len() as f64 * span).ceil() as usize).clamp(2, pairs.len());
+    #[test]
+    fn test_transform_reports_row_of_non_numeric_y() {
+        let mut csv = make_data();
+        csv.rows
+            .push(vec!["3.0".to_string(), "n/a?".to_string(), "B".to_string()]);
+        let mut spec = make_spec();
+        spec.facet = Some(ResolvedFacet {
+            col: "cat".to_string(),
+            ncol: None,
+            nrow: None,
+            scales: crate::parser::ast::FacetScales::Fixed,
+            bins: None,
+            cut: Default::default(),
+            grid: None,
+            labeller: Default::default(),
+            label_size: None,
+        });
+
+        // The row number is the row's position in the full data, not in its facet
+        let err = apply_transformations(&spec, &csv, &RenderOptions::default()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Failed to parse y value 'n/a?' in column 'y' (row 4)"
+        );
+    }
 
-    let mut smooth_x = Vec::with_capacity(sample_count);
-    let mut smooth_y = Vec::with_capacity(sample_count);
+    #[test]
+    fn test_transform_facet_panels_keep_partition_order() {
+        let mut csv = PlotData {
+            headers: vec!["x".to_string(), "y".to_string(), "cat".to_string()],
+            rows: (0..64)
+                .map(|i| vec![i.to_string(), (i * 2).to_string(), format!("g{}", i % 8)])
+                .collect(),
+        };
+        let mut spec = make_spec();
+        spec.layers[0].aesthetics.color = None;
+        spec.facet = Some(ResolvedFacet {
+            col: "cat".to_string(),
+            ncol: None,
+            nrow: None,
+            scales: crate::parser::ast::FacetScales::Fixed,
+            bins: None,
+            cut: Default::default(),
+            grid: None,
+            labeller: Default::default(),
+            label_size: None,
+        });
 
-    for i in 0..sample_count {
-        let t = i as f64 / (sample_count - 1) as f64;
-        let x0 = min_x + (max_x - min_x) * t;
-        let y0 = loess_predict_at(&pairs, x0, neighborhood);
-        if y0.is_finite() {
-            smooth_x.push(x0);
-            smooth_y.push(y0);
+        let render_data = apply_transformations(&spec, &csv, &RenderOptions::default()).unwrap();
+        assert_eq!(render_data.panels.len(), 8);
+        for (i, panel) in render_data.panels.iter().enumerate() {
+            assert_eq!(panel.index, i);
+            assert_eq!(render_data.facet_layout.panel_titles[i], format!("g{}", i));
+            assert_eq!(panel.layers[0].groups[0].x[0], i as f64);
         }
-    }
 
-    Ok((smooth_x, smooth_y))
-}
+        // With several failing panels, the error is always the first panel's
+        csv.rows[13][1] = "bad13".to_string();
+        csv.rows[10][1] = "bad10".to_string();
+        for _ in 0..4 {
+            let err = apply_transformations(&spec, &csv, &RenderOptions::default()).unwrap_err();
+            assert!(err.to_string().contains("'bad10'"));
+        }
+    }
 
-fn loess_predict_at(pairs: &[(f64, f64)], x0: f64, neighborhood: usize) -> f64 {
-    let mut distances: Vec<f64> = pairs.iter().map(|(x, _)| (x - x0).abs()).collect();
-    distances.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    fn bar_layer(data: &PlotData, dsl: &str) -> LayerData {
+        let (_, spec) = crate::parser::parse_plot_spec(dsl).unwrap();
+        let resolved = crate::resolve::resolve_plot_aesthetics(&spec, data).unwrap();
+        let mut render_data =
+            apply_transformations(&resolved, data, &RenderOptions::default()).unwrap();
+        render_data.panels.remove(0).layers.remove(0)
+    }
 
-    let mut bandwidth = distances[neighborhood.saturating_sub(1)];
-    if bandwidth <= f64::EPSILON {
-        bandwidth = distances
-            .iter()
-            .copied()
-            .find(|d| *d > f64::EPSILON)
-            .unwrap_or(1.0);
+    fn bar_data(rows: &[[&str; 3]]) -> PlotData {
+        PlotData::new(
+            vec!["x".to_string(), "y".to_string(), "g".to_string()],
+            rows.iter()
+                .map(|row| row.iter().map(|s| s.to_string()).collect())
+                .collect(),
+        )
     }
 
-    let mut sw = 0.0;
-    let mut sx = 0.0;
-    let mut sy = 0.0;
-    let mut sxx = 0.0;
-    let mut sxy = 0.0;
+    #[test]
+    fn test_transform_continuous_bars_keep_numeric_x() {
+        let data = bar_data(&[["2001", "5", "p"], ["2003", "3", "p"], ["2004", "4", "p"]]);
+        let layer = bar_layer(&data, "aes(x: x, y: y) | bar(x_continuous: true)");
+        let group = &layer.groups[0];
+        assert_eq!(group.x, vec![2001.0, 2003.0, 2004.0]);
+        assert!(group.x_categories.is_none());
+        // 80% of the closest spacing between years
+        match &group.style {
+            RenderStyle::Bar(style) => assert_eq!(style.width, Some(0.8)),
+            other => panic!("Expected bar style, got {:?}", other),
+        }
 
-    for &(x, y) in pairs {
-        let distance = (x - x0).abs();
-        if distance > bandwidth {
-            continue;
+        // A width is in x units
+        let layer = bar_layer(
+            &data,
+            "aes(x: x, y: y) | bar(x_continuous: true, width: 2.5)",
+        );
+        match &layer.groups[0].style {
+            RenderStyle::Bar(style) => assert_eq!(style.width, Some(2.5)),
+            other => panic!("Expected bar style, got {:?}", other),
         }
 
-        let u = distance / bandwidth;
-        let weight = (1.0 - u.powi(3)).powi(3);
-        let centered_x = x - x0;
+        // Without the hint years are categories
+        let layer = bar_layer(&data, "aes(x: x, y: y) | bar()");
+        assert_eq!(layer.groups[0].x, vec![0.0, 1.0, 2.0]);
+
+        let text = bar_data(&[["A", "5", "p"], ["2003", "3", "p"]]);
+        let (_, spec) =
+            crate::parser::parse_plot_spec("aes(x: x, y: y) | bar(x_continuous: true)").unwrap();
+        let resolved = crate::resolve::resolve_plot_aesthetics(&spec, &text).unwrap();
+        let err = apply_transformations(&resolved, &text, &RenderOptions::default())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("needs numeric x values"), "{}", err);
+        assert!(err.contains("'A'"), "{}", err);
+        assert!(err.contains("remove x_continuous"), "{}", err);
+    }
 
-        sw += weight;
-        sx += weight * centered_x;
-        sy += weight * y;
-        sxx += weight * centered_x * centered_x;
-        sxy += weight * centered_x * y;
+    #[test]
+    fn test_transform_negative_bars_extend_down_from_zero() {
+        let single = bar_data(&[["A", "5", "p"], ["B", "-3", "p"]]);
+        let layer = bar_layer(&single, "aes(x: x, y: y) | bar()");
+        let group = &layer.groups[0];
+        assert_eq!(group.y_start, vec![0.0, 0.0]);
+        assert_eq!(group.y, vec![5.0, -3.0]);
+        assert_eq!(group.y_min, vec![0.0, -3.0]);
+        assert_eq!(group.y_max, vec![5.0, 0.0]);
+
+        let all_negative = bar_data(&[["A", "-5", "p"], ["B", "-3", "p"]]);
+        let layer = bar_layer(&all_negative, "aes(x: x, y: y) | bar()");
+        assert_eq!(layer.groups[0].y, vec![-5.0, -3.0]);
+        assert!(layer.groups[0].y_max.iter().all(|&v| v == 0.0));
     }
 
-    if sw <= f64::EPSILON {
-        return pairs
+    #[test]
+    fn test_transform_stacks_mixed_signs_independently() {
+        let data = bar_data(&[
+            ["A", "4", "p"],
+            ["A", "-2", "q"],
+            ["A", "3", "r"],
+            ["A", "-5", "s"],
+        ]);
+        let layer = bar_layer(
+            &data,
+            r#"aes(x: x, y: y, color: g) | bar(position: "stack")"#,
+        );
+        let spans: Vec<(f64, f64)> = layer
+            .groups
             .iter()
-            .min_by(|a, b| {
-                (a.0 - x0)
-                    .abs()
-                    .partial_cmp(&(b.0 - x0).abs())
-                    .unwrap_or(std::cmp::Ordering::Equal)
-            })
-            .map(|(_, y)| *y)
-            .unwrap_or(0.0);
+            .map(|g| (g.y_start[0], g.y[0]))
+            .collect();
+        // Positives build up from zero, negatives build down from zero
+        assert_eq!(
+            spans,
+            vec![(0.0, 4.0), (0.0, -2.0), (4.0, 7.0), (-2.0, -7.0)]
+        );
+
+        // Dodged bars are unaffected
+        let layer = bar_layer(
+            &data,
+            r#"aes(x: x, y: y, color: g) | bar(position: "dodge")"#,
+        );
+        assert!(layer.groups.iter().all(|g| g.y_start == vec![0.0]));
     }
 
-    let denom = sw * sxx - sx * sx;
-    if denom.abs() <= f64::EPSILON {
-        sy / sw
-    } else {
-        (sy * sxx - sx * sxy) / denom
+    #[test]
+    fn test_transform_stacks_align_on_float_like_x() {
+        let data = bar_data(&[
+            ["0.30000000000000004", "1", "p"],
+            ["0.1", "2", "p"],
+            ["0.30000000000000004", "3", "q"],
+            ["0.1", "4", "q"],
+        ]);
+        let spans = |dsl: &str| -> Vec<Vec<(f64, f64)>> {
+            bar_layer(&data, dsl)
+                .groups
+                .iter()
+                .map(|g| g.y_start.iter().copied().zip(g.y.iter().copied()).collect())
+                .collect()
+        };
+        // q sits on p at both positions
+        assert_eq!(
+            spans(r#"aes(x: x, y: y, color: g) | bar(position: "stack")"#),
+            vec![vec![(0.0, 1.0), (0.0, 2.0)], vec![(1.0, 4.0), (2.0, 6.0)]]
+        );
+        let fill = spans(r#"aes(x: x, y: y, color: g) | bar(position: "fill")"#);
+        assert!(fill[1].iter().all(|&(_, top)| (top - 1.0).abs() < 1e-12));
+
+        // Numeric keys compare the parsed value, not its formatting
+        let artifact: f64 = "0.30000000000000004".parse().unwrap();
+        assert_eq!(StackKey::value(0.1 + 0.2), StackKey::value(artifact));
+        assert_ne!(StackKey::value(0.3), StackKey::value(artifact));
+        assert_eq!(StackKey::value(-0.0), StackKey::value(0.0));
     }
-}
 
-fn compute_bin_stat(
-    groups: HashMap<String, (Vec<String>, Vec<f64>, Vec<f64>, Vec<f64>)>,
-    bin_count: usize,
-) -> Result<HashMap<String, StatData>> {
-    let bin_count = bin_count.max(1);
+    #[test]
+    fn test_transform_color_scale_orders_stacks() {
+        let data = bar_data(&[
+            ["A", "1", "Other"],
+            ["A", "2", "gamma"],
+            ["A", "4", "beta"],
+            ["B", "8", "gamma"],
+        ]);
+        let stack = |scale: &str| -> Vec<(String, f64, f64)> {
+            let dsl = format!(
+                r#"aes(x: x, y: y, color: g) | bar(position: "stack") {}"#,
+                scale
+            );
+            bar_layer(&data, &dsl)
+                .groups
+                .iter()
+                .map(|g| (g.key.clone(), g.y_start[0], g.y[0]))
+                .collect()
+        };
+        let span = |key: &str, start: f64, end: f64| (key.to_string(), start, end);
+
+        // Sorted keys by default ("O" sorts before lowercase)
+        assert_eq!(
+            stack(""),
+            vec![
+                span("Other", 0.0, 1.0),
+                span("beta", 1.0, 5.0),
+                span("gamma", 5.0, 7.0)
+            ]
+        );
+        assert_eq!(stack(r#"| scale_color(order: "alpha")"#), stack(""));
+        assert_eq!(
+            stack(r#"| scale_color(order: "appearance")"#),
+            vec![
+                span("Other", 0.0, 1.0),
+                span("gamma", 1.0, 3.0),
+                span("beta", 3.0, 7.0)
+            ]
+        );
+        // Listed levels first, the rest in the chosen order, so "Other" tops the stack
+        assert_eq!(
+            stack(r#"| scale_color(levels: "gamma", "beta", "Other")"#),
+            vec![
+                span("gamma", 0.0, 2.0),
+                span("beta", 2.0, 6.0),
+                span("Other", 6.0, 7.0)
+            ]
+        );
+        assert_eq!(
+            stack(r#"| scale_color(levels: "beta")"#),
+            vec![
+                span("beta", 0.0, 4.0),
+                span("Other", 4.0, 5.0),
+                span("gamma", 5.0, 7.0)
+            ]
+        );
 
-    // 1. Collect all X values to determine range
-    let mut all_values = Vec::new();
-    for (x_strs, _, _, _) in groups.values() {
-        for s in x_strs {
-            let v = s
-                .parse::<f64>()
-                .map_err(|_| anyhow!("Stat 'bin' requires numeric x data"))?;
-            all_values.push(v);
-        }
+        // Palette colors follow the same order
+        let colors = |scale: &str| -> Vec<(String, Option<String>)> {
+            let dsl = format!(
+                r#"aes(x: x, y: y, color: g) | bar(position: "dodge") {}"#,
+                scale
+            );
+            bar_layer(&data, &dsl)
+                .groups
+                .iter()
+                .map(|g| match &g.style {
+                    RenderStyle::Bar(style) => (g.key.clone(), style.color.clone()),
+                    _ => panic!("Expected bar style"),
+                })
+                .collect()
+        };
+        let reordered = colors(r#"| scale_color(levels: "gamma", "beta", "Other")"#);
+        assert_eq!(reordered[0].0, "gamma");
+        assert_eq!(reordered[0].1, colors("")[0].1);
     }
 
-    if all_values.is_empty() {
-        return Ok(groups
-            .into_iter()
-            .map(|(k, v)| (k, StatData::from_tuple(v)))
-            .collect());
+    #[test]
+    fn test_transform_layer_filter_keeps_matching_rows() {
+        let data = bar_data(&[
+            ["A", "1", "EU"],
+            ["B", "2", "US"],
+            ["C", "3", "EU"],
+            ["D", "NA", "EU"],
+            ["E", "n/a-ish", "US"],
+        ]);
+        let ys = |dsl: &str| {
+            let layer = bar_layer(&data, dsl);
+            let ys: Vec<f64> = layer.groups.iter().flat_map(|g| g.y.clone()).collect();
+            (ys, layer.filter_rejected_rows)
+        };
+
+        // String equality
+        let (y, rejected) = ys(r#"aes(x: x, y: y) | point(filter: g == "EU")"#);
+        assert_eq!(y, vec![1.0, 3.0]);
+        assert_eq!(rejected, 0, "missing y is skipped as usual, not rejected");
+
+        // Numeric range: the missing cell fails quietly, the unparsable one is counted
+        let (y, rejected) = ys("aes(x: x, y: y) | point(filter: y >= 1.5 && y < 3)");
+        assert_eq!(y, vec![2.0]);
+        assert_eq!(rejected, 1);
+
+        // Compound condition; a failed string test decides the row without parsing y
+        let (y, rejected) =
+            ys(r#"aes(x: x, y: y) | point(filter: x == "A" || y > 2 && g != "US")"#);
+        assert_eq!(y, vec![1.0, 3.0]);
+        assert_eq!(rejected, 0);
+
+        // A filter on a missing column is caught during resolution
+        let (_, spec) =
+            crate::parser::parse_plot_spec("aes(x: x, y: y) | point(filter: reigon == 1)").unwrap();
+        let err = crate::resolve::resolve_plot_aesthetics(&spec, &data).unwrap_err();
+        assert!(err.to_string().contains("reigon"));
     }
 
-    let min = all_values.iter().fold(f64::INFINITY, |a, &b| a.min(b));
-    let max = all_values.iter().fold(f64::NEG_INFINITY, |a, &b| a.max(b));
+    #[test]
+    fn test_transform_data_source_layers_read_their_own_table() {
+        let data = bar_data(&[["A", "1", "p"], ["B", "2", "p"]]);
+        let sources = DataSources::from([(
+            "last".to_string(),
+            bar_data(&[["B", "5", "p"], ["C", "6", "p"]]),
+        )]);
+        let transform = |dsl: &str| {
+            let (_, spec) = crate::parser::parse_plot_spec(dsl).unwrap();
+            let resolved =
+                crate::resolve::resolve_plot_aesthetics_with_sources(&spec, &data, &sources)?;
+            apply_transformations_with_sources(
+                &resolved,
+                &data,
+                &sources,
+                &RenderOptions::default(),
+            )
+        };
 
-    // Add small buffer or handle 0 range
-    let range = max - min;
-    let width = if range == 0.0 {
-        1.0
-    } else {
-        range / bin_count as f64
-    };
+        let render_data = transform(r#"aes(x: x, y: y) | bar() | bar(data: "last")"#).unwrap();
+        let layers = &render_data.panels[0].layers;
+        assert_eq!(layers[0].groups[0].y, vec![1.0, 2.0]);
+        assert_eq!(layers[1].groups[0].y, vec![5.0, 6.0]);
+        // Both layers place x on one category order
+        for layer in layers {
+            assert_eq!(
+                layer.groups[0].x_categories.as_deref(),
+                Some(&["A".to_string(), "B".to_string(), "C".to_string()][..])
+            );
+        }
+        assert_eq!(layers[1].groups[0].x, vec![1.0, 2.0]);
 
-    let mut new_groups = HashMap::new();
+        // Each layer's columns are checked against its own table
+        let err = transform(r#"aes(x: x, y: y) | bar() | bar(data: "last", y: z)"#).unwrap_err();
+        assert!(
+            format!("{err:#}").starts_with(r#"Layer 2 data: "last": Unknown column 'z'"#),
+            "{err:#}"
+        );
+        let err = transform(r#"aes(x: x, y: y) | bar(data: "other")"#).unwrap_err();
+        assert_eq!(err.to_string(), r#"Layer 1 data: "other" is not loaded"#);
+    }
 
-    for (key, (x_strs, _, _, _)) in groups {
-        let mut bins: HashMap<usize, usize> = HashMap::new();
+    #[test]
+    fn test_transform_fill_normalizes_each_category() {
+        let data = bar_data(&[
+            ["A", "1", "p"],
+            ["A", "3", "q"],
+            ["B", "5", "p"],
+            ["B", "15", "q"],
+            ["B", "-2", "r"],
+            ["C", "0", "p"],
+            ["C", "0", "q"],
+        ]);
+        let layer = bar_layer(
+            &data,
+            r#"aes(x: x, y: y, color: g) | bar(position: "fill")"#,
+        );
+        let segments = |g: &str| {
+            let group = layer.groups.iter().find(|group| group.key == g).unwrap();
+            let cats = group.x_categories.as_ref().unwrap();
+            group
+                .x
+                .iter()
+                .enumerate()
+                .map(|(i, &x)| (cats[x as usize].clone(), group.y_start[i], group.y[i]))
+                .collect::<Vec<_>>()
+        };
 
-        for s in x_strs {
-            // We already checked they are numeric
-            let v = s.parse::<f64>().unwrap();
-            let bin_idx = ((v - min) / width).floor() as usize;
-            let bin_idx = bin_idx.min(bin_count - 1);
-            *bins.entry(bin_idx).or_default() += 1;
+        // Positive segments of each category sum to 1.0
+        for cat in ["A", "B"] {
+            let total: f64 = ["p", "q"]
+                .iter()
+                .flat_map(|g| segments(g))
+                .filter(|(c, _, _)| c == cat)
+                .map(|(_, start, end)| end - start)
+                .sum();
+            assert!((total - 1.0).abs() < 1e-9, "{} sums to {}", cat, total);
         }
+        assert_eq!(segments("p")[0], ("A".to_string(), 0.0, 0.25));
+        assert_eq!(segments("q")[0], ("A".to_string(), 0.25, 1.0));
 
-        let mut new_x = Vec::new();
-        let mut new_y = Vec::new();
-        let mut new_ymin = Vec::new();
-        let mut new_ymax = Vec::new();
+        // Negative values fill 0..-1 on their own
+        assert_eq!(segments("r"), vec![("B".to_string(), 0.0, -1.0)]);
 
-        for idx in 0..bin_count {
-            let center = min + (idx as f64 * width) + (width / 2.0);
-            let count = bins.get(&idx).copied().unwrap_or(0) as f64;
-            new_x.push(format!("{:.2}", center));
-            new_y.push(count);
-            new_ymin.push(0.0);
-            new_ymax.push(count);
-        }
+        // A zero total renders empty instead of NaN
+        let (_, start, end) = segments("q")[2].clone();
+        assert_eq!((start, end), (0.0, 0.0));
+        assert!(layer
+            .groups
+            .iter()
+            .all(|g| g.y.iter().chain(&g.y_max).all(|v| v.is_finite())));
+    }
 
-        new_groups.insert(
-            key,
-            StatData::from_tuple((new_x, new_y, new_ymin, new_ymax)),
-        );
+    fn bar_heights(layer: &LayerData) -> Vec<(String, f64)> {
+        let group = &layer.groups[0];
+        let categories = group.x_categories.as_ref().unwrap();
+        let mut heights: Vec<(String, f64)> = group
+            .x
+            .iter()
+            .zip(&group.y)
+            .map(|(&x, &y)| (categories[x as usize].clone(), y))
+            .collect();
+        heights.sort_by(|a, b| a.0.cmp(&b.0));
+        heights
     }
 
-    Ok(new_groups)
-}
+    #[test]
+    fn test_transform_bar_stats_combine_duplicate_x() {
+        let data = bar_data(&[
+            ["A", "4", "p"],
+            ["B", "10", "p"],
+            ["A", "-2", "p"],
+            ["A", "7", "p"],
+        ]);
+        let heights = |stat: &str| {
+            bar_layer(
+                &data,
+                &format!(r#"aes(x: x, y: y) | bar(stat: "{}")"#, stat),
+            )
+        };
+        let pairs = |v: &[(&str, f64)]| -> Vec<(String, f64)> {
+            v.iter().map(|&(x, y)| (x.to_string(), y)).collect()
+        };
+        assert_eq!(
+            bar_heights(&heights("sum")),
+            pairs(&[("A", 9.0), ("B", 10.0)])
+        );
+        assert_eq!(
+            bar_heights(&heights("mean")),
+            pairs(&[("A", 3.0), ("B", 10.0)])
+        );
+        assert_eq!(
+            bar_heights(&heights("min")),
+            pairs(&[("A", -2.0), ("B", 10.0)])
+        );
+        assert_eq!(
+            bar_heights(&heights("max")),
+            pairs(&[("A", 7.0), ("B", 10.0)])
+        );
+        assert_eq!(
+            bar_heights(&heights("count")),
+            pairs(&[("A", 3.0), ("B", 1.0)])
+        );
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::parser::ast::{Layer, LineLayer};
+        // A negative summary still extends down from zero
+        let layer = heights("min");
+        let a = layer.groups[0].y.iter().position(|&y| y == -2.0).unwrap();
+        assert_eq!(layer.groups[0].y_max[a], 0.0);
+    }
 
-    fn make_data() -> PlotData {
-        PlotData {
-            headers: vec!["x".to_string(), "y".to_string(), "cat".to_string()],
-            rows: vec![
-                vec!["1.0".to_string(), "10.0".to_string(), "A".to_string()],
-                vec!["2.0".to_string(), "20.0".to_string(), "A".to_string()],
-                vec!["1.0".to_string(), "15.0".to_string(), "B".to_string()],
-            ],
-        }
+    #[test]
+    fn test_transform_bar_count_ignores_y() {
+        let data = bar_data(&[["A", "n/a", "p"], ["B", "", "p"], ["A", "", "p"]]);
+        let expected = vec![("A".to_string(), 2.0), ("B".to_string(), 1.0)];
+        let layer = bar_layer(&data, r#"aes(x: x, y: y) | bar(stat: "count")"#);
+        assert_eq!(bar_heights(&layer), expected);
+        // y is optional for counts
+        let layer = bar_layer(&data, r#"aes(x: x) | bar(stat: "count")"#);
+        assert_eq!(bar_heights(&layer), expected);
     }
 
-    fn make_spec() -> ResolvedSpec {
-        ResolvedSpec {
-            layers: vec![ResolvedLayer {
-                original_layer: Layer::Line(LineLayer::default()),
-                aesthetics: ResolvedAesthetics {
-                    x_col: "x".to_string(),
-                    y_col: Some("y".to_string()),
-                    ymin_col: None,
-                    ymax_col: None,
-                    color: Some("cat".to_string()),
-                    size: None,
-                    shape: None,
-                    alpha: None,
-                    fill: None,
-                },
-            }],
-            facet: None,
-            coord: None,
-            labels: crate::parser::ast::Labels::default(),
-            theme: crate::parser::ast::Theme::default(),
-            x_scale_spec: None,
-            y_scale_spec: None,
-        }
+    #[test]
+    fn test_transform_bar_count_per_color_group() {
+        let data = bar_data(&[
+            ["A", "", "p"],
+            ["A", "", "q"],
+            ["A", "", "p"],
+            ["B", "", "q"],
+        ]);
+        let spans = |dsl: &str| -> Vec<(String, f64, f64)> {
+            let layer = bar_layer(&data, dsl);
+            layer
+                .groups
+                .iter()
+                .flat_map(|g| {
+                    g.x.iter()
+                        .enumerate()
+                        .map(move |(i, _)| (g.key.clone(), g.y_start[i], g.y[i]))
+                })
+                .collect()
+        };
+        // Stacked counts: p has 2 at A, q stacks its 1 on top; q's 1 at B starts at 0
+        assert_eq!(
+            spans(r#"aes(x: x, color: g) | bar(stat: "count", position: "stack")"#),
+            vec![
+                ("p".to_string(), 0.0, 2.0),
+                ("q".to_string(), 2.0, 3.0),
+                ("q".to_string(), 0.0, 1.0)
+            ]
+        );
+        // Dodged counts each start at zero
+        assert!(
+            spans(r#"aes(x: x, color: g) | bar(stat: "count", position: "dodge")"#)
+                .iter()
+                .all(|(_, start, _)| *start == 0.0)
+        );
     }
 
     #[test]
-    fn test_transform_grouping() {
-        let csv = make_data();
-        let spec = make_spec();
-        let render_data = apply_transformations(&spec, &csv).unwrap();
+    fn test_transform_bar_rejects_duplicate_x_without_stat() {
+        let run = |data: &PlotData, dsl: &str| {
+            let (_, spec) = crate::parser::parse_plot_spec(dsl).unwrap();
+            let resolved = crate::resolve::resolve_plot_aesthetics(&spec, data).unwrap();
+            apply_transformations(&resolved, data, &RenderOptions::default())
+        };
+        let data = bar_data(&[["A", "4", "p"], ["B", "1", "p"], ["A", "2", "p"]]);
+        let err = run(&data, "aes(x: x, y: y) | bar()")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("more than one row for x = 'A'"), "{}", err);
+        assert!(err.contains(r#"bar(stat: "sum")"#), "{}", err);
+
+        // Duplicates are judged per group, so one row per x per color is fine
+        let grouped = bar_data(&[["A", "4", "p"], ["A", "2", "q"], ["A", "1", "q"]]);
+        assert!(run(&grouped, "aes(x: x, y: y) | bar()").is_err());
+        let err = run(
+            &grouped,
+            "aes(x: x, y: y, color: g) | bar(position: \"dodge\")",
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("x = 'A' in group 'q'"), "{}", err);
+        let unique = bar_data(&[["A", "4", "p"], ["A", "2", "q"]]);
+        assert!(run(
+            &unique,
+            "aes(x: x, y: y, color: g) | bar(position: \"stack\")"
+        )
+        .is_ok());
+    }
 
-        assert_eq!(render_data.panels.len(), 1);
-        let panel = &render_data.panels[0];
-        assert_eq!(panel.layers.len(), 1);
-        let layer = &panel.layers[0];
-        assert_eq!(layer.groups.len(), 2); // A and B
+    fn x_categories(data: &PlotData, dsl: &str) -> Vec<Vec<String>> {
+        let (_, spec) = crate::parser::parse_plot_spec(dsl).unwrap();
+        let resolved = crate::resolve::resolve_plot_aesthetics(&spec, data).unwrap();
+        let render_data =
+            apply_transformations(&resolved, data, &RenderOptions::default()).unwrap();
+        render_data
+            .panels
+            .iter()
+            .map(|panel| {
+                panel.layers[0].groups[0]
+                    .x_categories
+                    .clone()
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
 
-        // Check group A
-        let group_a = layer.groups.iter().find(|g| g.key == "A").unwrap();
-        assert_eq!(group_a.x.len(), 2);
-        assert_eq!(group_a.y, vec![10.0, 20.0]);
+    fn months() -> PlotData {
+        bar_data(&[
+            ["Jan", "5", "north"],
+            ["Feb", "9", "north"],
+            ["Mar", "2", "north"],
+            ["Apr", "7", "north"],
+            ["Mar", "4", "south"],
+            ["Jan", "1", "south"],
+        ])
     }
 
     #[test]
-    fn test_transform_facet() {
-        let mut spec = make_spec();
-        spec.facet = Some(ResolvedFacet {
-            col: "cat".to_string(),
-            ncol: None,
-            scales: crate::parser::ast::FacetScales::Fixed,
-        });
+    fn test_transform_category_order_modes() {
+        let data = months();
+        let order = |scale: &str| {
+            x_categories(
+                &data,
+                &format!(r#"aes(x: x, y: y) | bar(stat: "sum") {}"#, scale),
+            )
+            .remove(0)
+        };
 
-        let csv = make_data();
-        let render_data = apply_transformations(&spec, &csv).unwrap();
+        assert_eq!(order(""), vec!["Jan", "Feb", "Mar", "Apr"]);
+        assert_eq!(
+            order(r#"| scale_x_discrete(order: "appearance")"#),
+            vec!["Jan", "Feb", "Mar", "Apr"]
+        );
+        assert_eq!(
+            order(r#"| scale_x_discrete(order: "alpha")"#),
+            vec!["Apr", "Feb", "Jan", "Mar"]
+        );
+        // Totals: Feb 9, Apr 7, Jan 6, Mar 6 (ties keep appearance order)
+        assert_eq!(
+            order(r#"| scale_x_discrete(order: "value")"#),
+            vec!["Feb", "Apr", "Jan", "Mar"]
+        );
+    }
 
-        assert_eq!(render_data.panels.len(), 2); // A and B panels
-        assert_eq!(render_data.facet_layout.panel_titles.len(), 2);
-        assert!(render_data
-            .facet_layout
-            .panel_titles
-            .contains(&"A".to_string()));
+    #[test]
+    fn test_transform_categories_align_across_layers_and_facets() {
+        let data = months();
+
+        // Grouped bars and a categorical point layer share one index per month
+        let (_, spec) = crate::parser::parse_plot_spec(
+            r#"aes(x: x, y: y, color: g) | bar(position: "stack") | point()"#,
+        )
+        .unwrap();
+        let resolved = crate::resolve::resolve_plot_aesthetics(&spec, &data).unwrap();
+        let render_data =
+            apply_transformations(&resolved, &data, &RenderOptions::default()).unwrap();
+        for layer in &render_data.panels[0].layers {
+            let south = layer.groups.iter().find(|g| g.key == "south").unwrap();
+            assert_eq!(
+                south.x_categories.as_deref().unwrap(),
+                ["Jan", "Feb", "Mar", "Apr"]
+            );
+            assert_eq!(south.x, vec![2.0, 0.0]);
+        }
+
+        // Fixed facets show every month in every panel; free x keeps only each panel's own
+        let fixed = x_categories(&data, "aes(x: x, y: y) | bar() | facet_wrap(by: g)");
+        assert_eq!(fixed[0], fixed[1]);
+        let free = x_categories(
+            &data,
+            r#"aes(x: x, y: y) | bar() | facet_wrap(by: g, scales: "free_x")"#,
+        );
+        assert_eq!(free[0], vec!["Jan", "Feb", "Mar", "Apr"]);
+        assert_eq!(free[1], vec!["Jan", "Mar"]);
     }
 }
@@ -0,0 +1,120 @@
+// Non-fatal problems found while rendering
+//
+// The library never prints: `runtime::render_with_report` and friends return these in
+// a `RenderReport`, and the CLI prints each one to stderr as "Warning: {warning}".
+
+use crate::csv_reader::RenamedHeader;
+use std::fmt;
+
+/// Something the render worked around instead of failing on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Warning {
+    /// DSL after the last component that parsed, ignored under `--lenient` (otherwise
+    /// it is a parse error)
+    UnparsedInput { input: String },
+    /// Syntax that still parses but has a newer spelling
+    Deprecated { message: String },
+    /// `head()` or `sample()` kept `kept` of `total` rows
+    RowLimit {
+        stage: String,
+        kept: usize,
+        total: usize,
+    },
+    /// Rows left out of the plot. `layer` is 1-based; `None` means a pipeline stage
+    /// dropped them before any layer saw the data.
+    SkippedRows {
+        count: usize,
+        layer: Option<usize>,
+        reason: SkipReason,
+    },
+    /// A layer's `filter:` matched no rows in any panel, so the layer draws nothing.
+    /// `layer` is 1-based.
+    EmptyLayerFilter { layer: usize },
+    /// Headers that repeat an earlier one, renamed by `csv_reader::disambiguate_headers`.
+    /// `table` is a layer's `data:` name; `None` means the plot's own data.
+    DuplicateColumns {
+        table: Option<String>,
+        renamed: Vec<RenamedHeader>,
+    },
+}
+
+/// Why rows were left out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    /// An empty, NA, NaN, null, or infinite value in a mapped column (see `--strict`)
+    MissingValues,
+    /// A filter compared a column that held non-numeric text
+    NonNumericFilter,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Warning::UnparsedInput { input } => write!(f, "unparsed input: '{}'", input),
+            Warning::Deprecated { message } => write!(f, "{}", message),
+            Warning::RowLimit { stage, kept, total } => {
+                write!(f, "{}() kept {} of {} rows", stage, kept, total)
+            }
+            Warning::SkippedRows {
+                count,
+                layer: None,
+                reason: SkipReason::NonNumericFilter,
+            } => write!(
+                f,
+                "filter() dropped {} row(s) with non-numeric values",
+                count
+            ),
+            Warning::SkippedRows {
+                count,
+                layer: None,
+                reason: SkipReason::MissingValues,
+            } => write!(f, "skipped {} row(s) with missing values", count),
+            Warning::SkippedRows {
+                count,
+                layer: Some(layer),
+                reason: SkipReason::MissingValues,
+            } => write!(
+                f,
+                "skipped {} row(s) with missing values in layer {}",
+                count, layer
+            ),
+            Warning::SkippedRows {
+                count,
+                layer: Some(layer),
+                reason: SkipReason::NonNumericFilter,
+            } => write!(
+                f,
+                "filter dropped {} row(s) with non-numeric values in layer {}",
+                count, layer
+            ),
+            Warning::EmptyLayerFilter { layer } => {
+                write!(
+                    f,
+                    "filter matched 0 rows in layer {}, so it draws nothing",
+                    layer
+                )
+            }
+            Warning::DuplicateColumns { table, renamed } => {
+                match table {
+                    Some(table) => write!(f, "data \"{}\" has duplicate column names: ", table)?,
+                    None => write!(f, "duplicate column names: ")?,
+                }
+                let described: Vec<String> = renamed
+                    .iter()
+                    .map(|header| {
+                        let repeats = if header.name == header.first {
+                            String::new()
+                        } else {
+                            format!(" repeats '{}' ignoring case and", header.first)
+                        };
+                        format!(
+                            "'{}' (column {}){} is now '{}'",
+                            header.name, header.index, repeats, header.renamed
+                        )
+                    })
+                    .collect();
+                write!(f, "{}", described.join(", "))
+            }
+        }
+    }
+}
@@ -0,0 +1,40 @@
+// Browser entry point, behind the `wasm` feature
+//
+// cargo rustc --lib --release --target wasm32-unknown-unknown --no-default-features \
+//     --features wasm --crate-type cdylib
+// wasm-bindgen --target web --out-dir pkg target/wasm32-unknown-unknown/release/gramgraph.wasm
+//
+// PNG comes from the plotters bitmap backend and the image crate's encoder. wasm32 has
+// no font rasterizer, so there PNG text is drawn as placeholder boxes
+// (`RenderOptions.placeholder_text`); ask for "svg" to get real text. SVG measures text
+// through the page's DOM, so call it from the main thread, not a worker.
+
+use crate::csv_reader::{self, CsvOptions};
+use crate::{runtime, GramGraphError, OutputFormat, RenderOptions};
+use wasm_bindgen::prelude::*;
+
+/// Render `dsl` against CSV text to PNG bytes, or to SVG bytes with `format: "svg"`.
+/// An error arrives in JavaScript as its message, e.g. "Unknown column
+/// 'x'. Available columns: a, b".
+#[wasm_bindgen]
+pub fn render(dsl: &str, csv: &str, format: Option<String>) -> Result<Vec<u8>, JsValue> {
+    let format = match format.as_deref() {
+        None | Some("png") => OutputFormat::Png,
+        Some("svg") => OutputFormat::Svg,
+        Some(other) => {
+            return Err(JsValue::from_str(&format!(
+                "Unknown format '{}'; expected png or svg",
+                other
+            )))
+        }
+    };
+    let options = RenderOptions {
+        placeholder_text: matches!(format, OutputFormat::Png) && cfg!(target_arch = "wasm32"),
+        format,
+        ..RenderOptions::default()
+    };
+    csv_reader::read_csv(csv.as_bytes(), CsvOptions::default())
+        .map_err(GramGraphError::from)
+        .and_then(|data| runtime::render(dsl, data, options))
+        .map_err(|err| JsValue::from_str(&err.to_string()))
+}
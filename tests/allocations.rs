@@ -0,0 +1,73 @@
+// Peak-memory regression checks for the transform phase
+//
+// A counting global allocator tracks live bytes, so this lives in its own test
+// binary; keep a single #[test] here so parallel tests don't skew the counts.
+
+use gramgraph::data::PlotData;
+use gramgraph::{parser, resolve, transform};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct CountingAllocator;
+
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let live = LIVE.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+        PEAK.fetch_max(live, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE.fetch_sub(layout.size(), Ordering::SeqCst);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Peak bytes allocated while transforming, as a multiple of the input's size
+fn transform_peak_ratio(dsl: &str, data: &PlotData, data_bytes: usize) -> f64 {
+    let (_, spec) = parser::parse_plot_spec(dsl).unwrap();
+    let resolved = resolve::resolve_plot_aesthetics(&spec, data).unwrap();
+
+    let base = LIVE.load(Ordering::SeqCst);
+    PEAK.store(base, Ordering::SeqCst);
+    let render_data =
+        transform::apply_transformations(&resolved, data, &gramgraph::RenderOptions::default())
+            .unwrap();
+    let peak = PEAK.load(Ordering::SeqCst) - base;
+    drop(render_data);
+
+    peak as f64 / data_bytes as f64
+}
+
+#[test]
+fn transform_does_not_copy_the_input() {
+    let before = LIVE.load(Ordering::SeqCst);
+    let rows = (0..50_000)
+        .map(|i| vec![i.to_string(), (i % 97).to_string(), format!("g{}", i % 4)])
+        .collect();
+    let data = PlotData::new(vec!["x".into(), "y".into(), "g".into()], rows);
+    let data_bytes = LIVE.load(Ordering::SeqCst) - before;
+
+    // Typed columns, per-group x strings, and the output coordinates come to about
+    // 1.2x the input; cloning the rows (or per-point boxplot padding) pushes it past 2x
+    for dsl in [
+        "aes(x: x, y: y) | line()",
+        "aes(x: x, y: y, color: g) | point()",
+        "aes(x: x, y: y) | line() | facet_wrap(by: g)",
+        r#"aes(x: g, y: y) | bar(stat: "sum")"#,
+    ] {
+        let ratio = transform_peak_ratio(dsl, &data, data_bytes);
+        assert!(
+            ratio < 1.5,
+            "{} peaked at {:.2}x the input size",
+            dsl,
+            ratio
+        );
+    }
+}
@@ -4,8 +4,34 @@ use std::process::{Command, Stdio};
 
 /// Helper function to run gramgraph with DSL and CSV input
 fn run_gramgraph(dsl: &str, csv_content: &str) -> Result<Vec<u8>, String> {
+    run_gramgraph_with_args(dsl, csv_content, &[])
+}
+
+/// Run gramgraph reading data from a file path (no stdin)
+fn run_gramgraph_file(dsl: &str, path: &str, extra_args: &[&str]) -> Result<Vec<u8>, String> {
+    let output = Command::new("cargo")
+        .args(["run", "--bin", "gramgraph", "--", dsl, "--input", path])
+        .args(extra_args)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("Failed to run process: {}", e))?;
+
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).to_string())
+    }
+}
+
+/// Run gramgraph with extra CLI arguments after the DSL
+fn run_gramgraph_with_args(
+    dsl: &str,
+    csv_content: &str,
+    extra_args: &[&str],
+) -> Result<Vec<u8>, String> {
     let mut child = Command::new("cargo")
-        .args(&["run", "--bin", "gramgraph", "--", dsl])
+        .args(["run", "--bin", "gramgraph", "--", dsl])
+        .args(extra_args)
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
@@ -33,7 +59,7 @@ fn run_gramgraph(dsl: &str, csv_content: &str) -> Result<Vec<u8>, String> {
 /// Helper function to run gramgraph and request SVG output.
 fn run_gramgraph_svg(dsl: &str, csv_content: &str) -> Result<String, String> {
     let mut child = Command::new("cargo")
-        .args(&["run", "--bin", "gramgraph", "--", dsl, "--format", "svg"])
+        .args(["run", "--bin", "gramgraph", "--", dsl, "--format", "svg"])
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
@@ -57,9 +83,37 @@ fn run_gramgraph_svg(dsl: &str, csv_content: &str) -> Result<String, String> {
     }
 }
 
+/// Run gramgraph and return its stderr on success (for warnings)
+fn run_gramgraph_stderr(dsl: &str, csv_content: &str) -> Result<String, String> {
+    let mut child = Command::new("cargo")
+        .args(["run", "--bin", "gramgraph", "--", dsl, "--format", "svg"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to spawn process: {}", e))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(csv_content.as_bytes())
+            .map_err(|e| format!("Failed to write to stdin: {}", e))?;
+    }
+
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("Failed to wait for process: {}", e))?;
+
+    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+    if output.status.success() {
+        Ok(stderr)
+    } else {
+        Err(stderr)
+    }
+}
+
 /// Check if bytes are a valid PNG
 fn is_valid_png(bytes: &[u8]) -> bool {
-    bytes.len() > 8 && &bytes[0..8] == &[137, 80, 78, 71, 13, 10, 26, 10]
+    bytes.len() > 8 && bytes[0..8] == [137, 80, 78, 71, 13, 10, 26, 10]
 }
 
 #[test]
@@ -221,6 +275,157 @@ fn test_end_to_end_theme_void() {
     assert!(result.is_ok(), "Failed: {:?}", result.err());
 }
 
+#[test]
+fn test_end_to_end_theme_background_shorthand() {
+    let csv = "x,y\n1,1\n2,4\n3,9\n";
+    let corner = |dsl: &str| {
+        let png = run_gramgraph(dsl, csv).unwrap();
+        image::load_from_memory(&png)
+            .unwrap()
+            .to_rgb8()
+            .get_pixel(2, 2)
+            .0
+    };
+    assert_eq!(corner("aes(x: x, y: y) | point()"), [255, 255, 255]);
+    assert_eq!(
+        corner(r##"aes(x: x, y: y) | point() | theme(background: "#203040")"##),
+        [0x20, 0x30, 0x40]
+    );
+    // A preset's background can be overridden in the same call
+    assert_eq!(
+        corner(r#"aes(x: x, y: y) | point() | theme(preset: "dark", background: "white")"#),
+        [255, 255, 255]
+    );
+}
+
+#[test]
+fn test_end_to_end_theme_grid_shorthand() {
+    let csv = "x,y\n1,1\n2,4\n3,9\n";
+    let grid_lines = |grid: &str| {
+        let dsl = format!(r#"aes(x: x, y: y) | point() | theme(grid: "{}")"#, grid);
+        run_gramgraph_svg(&dsl, csv)
+            .unwrap()
+            .matches("<line")
+            .count()
+    };
+    assert_eq!(grid_lines("none"), 0);
+    let major = grid_lines("major");
+    assert!(major > 0);
+    assert!(grid_lines("both") > major);
+
+    // Tick labels survive without a grid
+    let svg = run_gramgraph_svg(r#"aes(x: x, y: y) | point() | theme(grid: "none")"#, csv).unwrap();
+    assert!(svg.lines().any(|l| l.trim() == "2"));
+}
+
+#[test]
+fn test_end_to_end_count_bars_without_y() {
+    let csv = "species,island\nAdelie,Torgersen\nAdelie,Biscoe\nGentoo,Biscoe\nAdelie,Biscoe\n";
+    let svg = run_gramgraph_svg(r#"aes(x: species) | bar(stat: "count")"#, csv).unwrap();
+    assert!(svg.lines().any(|l| l.trim() == "count"));
+    assert!(svg.lines().any(|l| l.trim() == "Adelie"));
+
+    let svg = run_gramgraph_svg(
+        r#"aes(x: species, color: island) | bar(stat: "count", position: "stack")"#,
+        csv,
+    )
+    .unwrap();
+    assert!(svg.lines().any(|l| l.trim() == "Torgersen"));
+    assert!(svg.lines().any(|l| l.trim() == "3"), "stack reaches 3 rows");
+}
+
+#[test]
+fn test_end_to_end_bar_fill_position() {
+    let csv = "q,v,g\nQ1,10,a\nQ1,30,b\nQ2,5,a\nQ2,5,b\nQ3,0,a\nQ3,0,b\n";
+    let svg = run_gramgraph_svg(
+        r#"aes(x: q, y: v, color: g) | bar(position: "fill") | scale_y(format: "percent")"#,
+        csv,
+    )
+    .unwrap();
+    // Proportions, not raw totals, on the y axis
+    assert!(svg.lines().any(|l| l.trim() == "100%"));
+    assert!(!svg.lines().any(|l| l.trim() == "40"));
+    assert!(!svg.contains("NaN"));
+}
+
+#[test]
+fn test_end_to_end_point_jitter() {
+    let csv = "g,v\nA,1\nA,1\nA,1\nB,2\nB,2\n";
+    let circle_xs = |svg: &str| -> Vec<String> {
+        svg.lines()
+            .filter(|l| l.contains("<circle"))
+            .filter_map(|l| l.split("cx=\"").nth(1))
+            .map(|rest| rest.split('"').next().unwrap().to_string())
+            .collect()
+    };
+
+    // Without jitter, repeated values stack on one spot per category
+    let plain = run_gramgraph_svg("aes(x: g, y: v) | point()", csv).unwrap();
+    let mut xs = circle_xs(&plain);
+    xs.dedup();
+    assert_eq!(xs.len(), 2);
+
+    let jittered = run_gramgraph_svg("aes(x: g, y: v) | jitter(width: 0.3)", csv).unwrap();
+    let mut xs = circle_xs(&jittered);
+    assert_eq!(xs.len(), 5);
+    xs.sort();
+    xs.dedup();
+    assert_eq!(xs.len(), 5, "every point gets its own offset");
+
+    // Deterministic: the same input renders byte-for-byte the same
+    let point_form = r#"aes(x: g, y: v) | point(position: "jitter", jitter_width: 0.3)"#;
+    assert_eq!(run_gramgraph_svg(point_form, csv).unwrap(), jittered);
+
+    // Categories keep their tick labels
+    assert!(jittered.lines().any(|l| l.trim() == "A"));
+    assert!(jittered.lines().any(|l| l.trim() == "B"));
+}
+
+#[test]
+fn test_end_to_end_axis_label_formats() {
+    let csv = "city,pop\nSan Francisco Bay Area,7760000\nChicagoland,9600000\n";
+    let svg = run_gramgraph_svg(
+        r#"aes(x: city, y: pop) | bar() | scale_x(rotate: 90, max_label_len: 11) | scale_y(format: "si")"#,
+        csv,
+    )
+    .unwrap();
+    assert!(svg.lines().any(|l| l.trim() == "8M"), "SI tick labels");
+    assert!(svg.lines().any(|l| l.trim() == "San Franci…"));
+    assert!(svg.lines().any(|l| l.trim() == "Chicagoland"));
+    assert!(svg.contains("transform=\"rotate(90"));
+
+    // Facet panels format their ticks too
+    let csv = "x,y,g\n1,0.1,a\n2,0.5,a\n1,0.2,b\n2,0.4,b\n";
+    let svg = run_gramgraph_svg(
+        r#"aes(x: x, y: y) | line() | facet_wrap(by: g) | scale_y(format: "percent") | scale_x(format: "%.1f")"#,
+        csv,
+    )
+    .unwrap();
+    assert!(svg.lines().any(|l| l.trim() == "50%"));
+    assert!(svg.lines().any(|l| l.trim() == "1.5"));
+}
+
+#[test]
+fn test_end_to_end_scale_expand() {
+    let csv = "x,y,g\n3,1,a\n47,2,a\n10,1.5,b\n20,1.2,b\n";
+    let base = "aes(x: x, y: y) | point() | facet_wrap(by: g)";
+    let has_tick = |svg: &str, tick: &str| svg.lines().any(|l| l.trim() == tick);
+
+    // Nice ranges reach out to 0 and 50; expand: 0 stops at the data
+    let nice = run_gramgraph_svg(base, csv).unwrap();
+    assert!(has_tick(&nice, "50"));
+    let fitted = run_gramgraph_svg(&format!("{} | scale_x(expand: 0)", base), csv).unwrap();
+    assert!(!has_tick(&fitted, "50"));
+    assert!(has_tick(&fitted, "45"));
+
+    let err = run_gramgraph_svg(&format!("{} | scale_y(expand: -1)", base), csv).unwrap_err();
+    assert!(
+        err.contains("scale_y() expand must be at least 0"),
+        "{}",
+        err
+    );
+}
+
 #[test]
 fn test_end_to_end_linerange_and_errorbar() {
     let csv = "\
@@ -346,7 +551,7 @@ fn test_end_to_end_abline_segment_and_theme_light() {
 #[test]
 fn test_end_to_end_bar_chart() {
     let csv = fs::read_to_string("fixtures/bar_chart.csv").expect("Failed to read test CSV");
-    let result = run_gramgraph("aes(x: category, y: value1) | bar()", &csv);
+    let result = run_gramgraph("aes(x: category, y: value1) | bar(stat: \"sum\")", &csv);
     assert!(result.is_ok(), "Failed: {:?}", result.err());
     let png_bytes = result.unwrap();
     assert!(is_valid_png(&png_bytes));
@@ -368,7 +573,7 @@ fn test_end_to_end_dodge_bars() {
 fn test_end_to_end_stack_bars() {
     let csv = fs::read_to_string("fixtures/bar_chart.csv").expect("Failed to read test CSV");
     let result = run_gramgraph(
-        "aes(x: category, y: value1) | bar(position: \"stack\", color: \"blue\") | bar(y: value2, position: \"stack\", color: \"green\")",
+        "aes(x: category, y: value1) | bar(position: \"stack\", stat: \"sum\", color: \"blue\") | bar(y: value2, position: \"stack\", stat: \"sum\", color: \"green\")",
         &csv,
     );
     assert!(result.is_ok(), "Failed: {:?}", result.err());
@@ -376,6 +581,29 @@ fn test_end_to_end_stack_bars() {
     assert!(is_valid_png(&png_bytes));
 }
 
+#[test]
+fn test_end_to_end_stack_order_from_color_levels() {
+    let csv = "q,browser,share\nQ1,Other,8\nQ1,Chrome,62\nQ1,Safari,19\n";
+    let svg = run_gramgraph_svg(
+        r#"aes(x: q, y: share, color: browser) | bar(position: "stack") | scale_color(levels: "Chrome", "Safari", "Other")"#,
+        csv,
+    )
+    .unwrap();
+    let legend: Vec<&str> = svg
+        .lines()
+        .map(str::trim)
+        .filter(|l| ["Chrome", "Safari", "Other"].contains(l))
+        .collect();
+    assert_eq!(legend, vec!["Chrome", "Safari", "Other"]);
+
+    let err = run_gramgraph(
+        r#"aes(x: q, y: share, color: browser) | bar(position: "stack") | scale_color(order: "value")"#,
+        csv,
+    )
+    .unwrap_err();
+    assert!(err.contains("scale_color order"), "{}", err);
+}
+
 #[test]
 fn test_end_to_end_invalid_syntax() {
     let csv = "x,y\n1,10\n2,20\n";
@@ -384,6 +612,22 @@ fn test_end_to_end_invalid_syntax() {
     assert!(result.unwrap_err().contains("Parse error"));
 }
 
+#[test]
+fn test_end_to_end_parse_error_caret_and_lenient() {
+    let csv = "x,y\n1,10\n2,20\n";
+    let dsl = "aes(x: x, y: y) | line() | pont()";
+    let err = run_gramgraph(dsl, csv).unwrap_err();
+    let lines: Vec<&str> = err.lines().collect();
+    let source = lines.iter().position(|l| l.ends_with(dsl)).expect(&err);
+    let caret = lines[source + 1].find('^').unwrap();
+    assert_eq!(&lines[source][caret..caret + 4], "pont", "{}", err);
+
+    // --lenient draws what parsed and skips the rest
+    let lenient = run_gramgraph_with_args(dsl, csv, &["--lenient", "--format", "svg"]).unwrap();
+    let prefix = run_gramgraph_svg("aes(x: x, y: y) | line()", csv).unwrap();
+    assert_eq!(String::from_utf8(lenient).unwrap(), prefix);
+}
+
 #[test]
 fn test_end_to_end_column_not_found() {
     let csv = "a,b\n1,10\n2,20\n";
@@ -399,6 +643,257 @@ fn test_end_to_end_empty_csv() {
     assert!(result.unwrap_err().contains("at least one data row"));
 }
 
+#[test]
+fn test_end_to_end_filter_stage() {
+    let csv = "year,sales\n2019,5\n2020,7\n2021,9\n";
+    let svg = run_gramgraph_svg(
+        "aes(x: year, y: sales) | filter(year >= 2020) | point()",
+        csv,
+    )
+    .unwrap();
+    assert_eq!(svg.matches("<circle").count(), 2);
+
+    let err = run_gramgraph(
+        "aes(x: year, y: sales) | filter(year > 2030) | point()",
+        csv,
+    )
+    .unwrap_err();
+    assert!(err.contains("filter matched 0 rows"), "{}", err);
+
+    let err = run_gramgraph(
+        "aes(x: year, y: sales) | filter(yaer > 2030) | point()",
+        csv,
+    )
+    .unwrap_err();
+    assert!(err.contains("did you mean 'year'"), "{}", err);
+}
+
+#[test]
+fn test_end_to_end_summarize_stage() {
+    let csv = "day,temp\n1,10\n1,20\n2,30\n2,50\n3,5\n";
+    let svg = run_gramgraph_svg(
+        "aes(x: day, y: mean_temp) | summarize(by: day, y: mean(temp)) | point() | labs(title: \"Daily\")",
+        csv,
+    )
+    .unwrap();
+    // One point per day
+    assert_eq!(svg.matches("<circle").count(), 3);
+
+    let err = run_gramgraph(
+        "aes(x: day, y: temp) | summarize(by: dya, y: mean(temp)) | point()",
+        csv,
+    )
+    .unwrap_err();
+    assert!(err.contains("'dya' (did you mean 'day'?)"), "{}", err);
+}
+
+#[test]
+fn test_end_to_end_mutate_stage() {
+    let csv = "month,revenue,cost\nJan,100,60\nFeb,80,95\nMar,0,30\n";
+    let svg = run_gramgraph_svg(
+        "mutate(margin: revenue - cost) | aes(x: month, y: margin) | bar()",
+        csv,
+    )
+    .unwrap();
+    assert!(svg.contains("Jan") && svg.contains("Mar"));
+
+    // Division by zero leaves the cell missing, so the layer skips that row
+    let stderr = run_gramgraph_stderr(
+        "mutate(ratio: cost / revenue) | aes(x: month, y: ratio) | point()",
+        csv,
+    )
+    .unwrap();
+    assert!(
+        stderr.contains("Warning: skipped 1 row(s) with missing values in layer 1"),
+        "{}",
+        stderr
+    );
+
+    let err = run_gramgraph(
+        "mutate(margin: revenue - cots) | aes(x: month, y: margin) | bar()",
+        csv,
+    )
+    .unwrap_err();
+    assert!(err.contains("'cots' (did you mean 'cost'?)"), "{}", err);
+}
+
+#[test]
+fn test_end_to_end_pivot_longer_stage() {
+    let csv = "month,north,south,east\nJan,1,2,3\nFeb,4,5,6\n";
+    let svg = run_gramgraph_svg(
+        "pivot_longer(cols: north, south, east, names_to: region, values_to: sales) | aes(x: month, y: sales, color: region) | line() | point()",
+        csv,
+    )
+    .unwrap();
+    // One point per month and region
+    assert_eq!(svg.matches("<circle").count(), 6);
+    assert!(svg.contains("south"));
+
+    let err = run_gramgraph(
+        "pivot_longer(cols: north, sooth, names_to: region) | aes(x: month, y: value) | line()",
+        csv,
+    )
+    .unwrap_err();
+    assert!(err.contains("'sooth' (did you mean 'south'?)"), "{}", err);
+
+    let err = run_gramgraph(
+        "pivot_longer(cols: north, name_to: region) | aes(x: month, y: value) | line()",
+        csv,
+    )
+    .unwrap_err();
+    assert!(err.contains("name_to"), "{}", err);
+}
+
+#[test]
+fn test_end_to_end_rolling_mean_line() {
+    let csv: String = std::iter::once("day,v\n".to_string())
+        .chain((1..=20).map(|i| format!("{},{}\n", i, (i * 7) % 11)))
+        .collect();
+    let svg = run_gramgraph_svg(
+        r#"aes(x: day, y: v) | line(alpha: 0.3) | line(stat: "rolling_mean", window: 7, width: 2)"#,
+        &csv,
+    )
+    .unwrap();
+    let raw = run_gramgraph_svg("aes(x: day, y: v) | line(alpha: 0.3)", &csv).unwrap();
+    // One more line on the same axes
+    assert_eq!(
+        svg.matches("<polyline").count(),
+        raw.matches("<polyline").count() + 1
+    );
+
+    let err = run_gramgraph(
+        r#"aes(x: day, y: v) | line(stat: "rolling_mean", window: 30)"#,
+        &csv,
+    )
+    .unwrap_err();
+    assert!(err.contains("needs at least 30 points"), "{}", err);
+
+    let err = run_gramgraph(
+        r#"aes(x: day, y: v) | line(stat: "rolling_mean", windw: 7)"#,
+        &csv,
+    )
+    .unwrap_err();
+    assert!(err.contains("windw"), "{}", err);
+}
+
+#[test]
+fn test_end_to_end_mapped_segments_with_arrows() {
+    let csv = "team,y2019,y2023\nA,10,14\nB,12,9\nC,8,11\n";
+    let dsl = |arrow: bool| {
+        format!(
+            "mutate(start: 2019, finish: 2023) | aes(x: start, y: y2019, color: team) | segment(xend: finish, yend: y2023, arrow: {})",
+            arrow
+        )
+    };
+    let plain = run_gramgraph_svg(&dsl(false), csv).unwrap();
+    let arrows = run_gramgraph_svg(&dsl(true), csv).unwrap();
+    // One arrowhead per segment
+    assert_eq!(
+        arrows.matches("<polygon").count(),
+        plain.matches("<polygon").count() + 3
+    );
+
+    let err =
+        run_gramgraph("aes(x: team, y: y2019) | segment(xend: team, yend: 5)", csv).unwrap_err();
+    assert!(err.contains("can't mix columns and numbers"), "{}", err);
+}
+
+#[test]
+fn test_end_to_end_pie_with_other_slice() {
+    let csv = "source,share\nGas,40\nCoal,35\nWind,22\nSolar,2\nOil,1\n";
+    let svg = run_gramgraph_svg(
+        "aes(x: source, y: share) | pie(donut: 0.5, other: 0.05)",
+        csv,
+    )
+    .unwrap();
+    // Gas, Coal, Wind, and the merged Other slice; no axes means no axis lines
+    assert_eq!(svg.matches("<polygon").count(), 4);
+    assert!(svg.contains("Other"));
+    assert!(!svg.contains("Solar"));
+
+    let err = run_gramgraph("aes(x: source, y: share) | pie() | bar()", csv).unwrap_err();
+    assert!(
+        err.contains("can't be combined with other layers"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_end_to_end_color_and_shape_from_different_columns() {
+    let csv = "a,b,region,product\n1,2,EU,widget\n2,3,EU,gadget\n3,4,EU,gizmo\n\
+               1,5,US,widget\n2,6,US,gadget\n3,1,US,gizmo\n";
+    let svg = run_gramgraph_svg(
+        "aes(x: a, y: b, color: region, shape: product) | point()",
+        csv,
+    )
+    .unwrap();
+    // A legend entry per region and per product, none for the combined groups
+    for label in ["EU", "US", "widget", "gadget", "gizmo"] {
+        assert!(svg.contains(label), "missing legend entry {}", label);
+    }
+    assert!(!svg.contains("EU, widget"));
+}
+
+#[test]
+fn test_end_to_end_density_fill_toggle() {
+    let csv = "v,g\n1,a\n2,a\n2.5,a\n4,b\n5,b\n5.5,b\n";
+    let filled = run_gramgraph_svg("aes(x: v, color: g) | density()", csv).unwrap();
+    let outline = run_gramgraph_svg("aes(x: v, color: g) | density(fill: false)", csv).unwrap();
+    assert!(filled.matches("<polygon").count() >= 2);
+    assert_eq!(outline.matches("<polygon").count(), 0);
+    assert!(outline.matches("<polyline").count() >= 2);
+
+    let err = run_gramgraph("aes(x: v) | density(bw: -1)", csv).unwrap_err();
+    assert!(err.contains("bw must be greater than 0"), "{}", err);
+}
+
+#[test]
+fn test_end_to_end_sort_stage_orders_categories() {
+    let csv = "name,n\nlow,2\nhigh,10\nmid,5\n";
+    let svg =
+        run_gramgraph_svg("aes(x: name, y: n) | sort(by: n, desc: true) | bar()", csv).unwrap();
+    let position = |label: &str| {
+        svg.lines()
+            .position(|l| l.trim() == label)
+            .unwrap_or_else(|| panic!("missing tick label {}", label))
+    };
+    assert!(position("high") < position("mid"));
+    assert!(position("mid") < position("low"));
+}
+
+#[test]
+fn test_end_to_end_head_and_sample_warn_with_row_counts() {
+    let csv: String = std::iter::once("x,y\n".to_string())
+        .chain((0..200).map(|i| format!("{},{}\n", i, i % 13)))
+        .collect();
+
+    let stderr = run_gramgraph_stderr("aes(x: x, y: y) | head(n: 50) | point()", &csv).unwrap();
+    assert!(
+        stderr.contains("Warning: head() kept 50 of 200 rows"),
+        "{}",
+        stderr
+    );
+
+    let stderr =
+        run_gramgraph_stderr("aes(x: x, y: y) | sample(n: 20, seed: 3) | point()", &csv).unwrap();
+    assert!(
+        stderr.contains("Warning: sample() kept 20 of 200 rows"),
+        "{}",
+        stderr
+    );
+    let sampled =
+        run_gramgraph_svg("aes(x: x, y: y) | sample(n: 20, seed: 3) | point()", &csv).unwrap();
+    assert_eq!(sampled.matches("<circle").count(), 20);
+
+    // n past the row count is a no-op
+    let stderr = run_gramgraph_stderr("aes(x: x, y: y) | head(n: 500) | point()", &csv).unwrap();
+    assert!(!stderr.contains("kept"), "{}", stderr);
+
+    let err = run_gramgraph("aes(x: x, y: y) | head(n: 0) | point()", &csv).unwrap_err();
+    assert!(err.contains("head() n must be at least 1"), "{}", err);
+}
+
 #[test]
 fn test_end_to_end_non_numeric_data() {
     // Unified renderer is more flexible: it treats non-numeric x-data as categorical
@@ -438,6 +933,204 @@ fn test_end_to_end_unicode() {
     assert!(is_valid_png(&png_bytes));
 }
 
+#[test]
+fn test_end_to_end_quoted_column_names() {
+    let csv = fs::read_to_string("fixtures/quoted_headers.csv").expect("Failed to read test CSV");
+    // Headers with spaces, parentheses, hyphens, and non-ASCII, matched case-insensitively
+    let result = run_gramgraph(
+        r#"aes(x: `Date (UTC)`, y: "sales (usd)", color: `store-id`) | line() | point(y: "Temp °C", size: 4) | facet_wrap(by: `store-id`)"#,
+        &csv,
+    );
+    assert!(result.is_ok(), "Failed: {:?}", result.err());
+    assert!(is_valid_png(&result.unwrap()));
+
+    let err = run_gramgraph(r#"aes(x: `Date (UTC)`, y: "Sales USD") | line()"#, &csv).unwrap_err();
+    assert!(
+        err.contains("Unknown column 'Sales USD' (did you mean 'Sales (USD)'?)"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_end_to_end_column_indices() {
+    let csv = "1,10,4,a\n2,20,6,b\n3,15,5,a\n";
+    let args = ["--no-header", "--format", "svg"];
+    let by_index = run_gramgraph_with_args(
+        "aes(x: 0, y: 1, color: 3) | line() | point(y: 2)",
+        csv,
+        &args,
+    )
+    .expect("plot by index");
+    let by_name = run_gramgraph_with_args(
+        "aes(x: c0, y: c1, color: c3) | line() | point(y: c2)",
+        csv,
+        &args,
+    )
+    .expect("plot by name");
+    assert_eq!(by_index, by_name);
+
+    let err = run_gramgraph_with_args("aes(x: 0, y: 4) | line()", csv, &args).unwrap_err();
+    assert!(
+        err.contains("Column index 4 out of bounds (available columns: 4, numbered from 0)"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_end_to_end_duplicate_headers() {
+    let csv = "x,value,value\n1,2,40\n2,3,10\n3,4,20\n";
+    let stderr = run_gramgraph_stderr("aes(x: x, y: value_2) | line()", csv).expect("plot value_2");
+    assert!(
+        stderr.contains("Warning: duplicate column names: 'value' (column 2) is now 'value_2'"),
+        "{}",
+        stderr
+    );
+    let renamed = run_gramgraph_svg("aes(x: x, y: value_2) | line()", csv).unwrap();
+    let by_index = run_gramgraph_svg("aes(x: x, y: 2) | line()", csv).unwrap();
+    assert_eq!(renamed, by_index);
+    // The renamed column holds the second copy's values
+    let distinct = run_gramgraph_svg(
+        "aes(x: x, y: b) | line()",
+        "x,a,b\n1,2,40\n2,3,10\n3,4,20\n",
+    )
+    .unwrap();
+    assert_eq!(renamed, distinct);
+
+    let err = run_gramgraph("aes(x: x, y: value_3) | line()", csv).unwrap_err();
+    assert!(
+        err.contains("Available columns: x, value, value_2"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_end_to_end_list_columns_and_dry_run() {
+    let run = |args: &[&str]| {
+        let output = Command::new("cargo")
+            .args(["run", "--bin", "gramgraph", "--"])
+            .args(args)
+            .stdin(Stdio::null())
+            .output()
+            .expect("Failed to run process");
+        (
+            output.status.success(),
+            String::from_utf8_lossy(&output.stdout).to_string(),
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        )
+    };
+
+    // No DSL needed; repeated headers are listed under the names a plot uses
+    let (ok, stdout, stderr) = run(&["--list-columns", "-i", "fixtures/duplicate_headers.csv"]);
+    assert!(ok, "{}", stderr);
+    assert_eq!(
+        stdout,
+        "column   type     distinct  min  max\n\
+         value    numeric  2         1    4\n\
+         value_2  numeric  2         2    5\n\
+         other    numeric  2         3    6\n"
+    );
+    assert!(
+        stderr.contains("'value' (column 1) is now 'value_2'"),
+        "{}",
+        stderr
+    );
+
+    let (ok, stdout, stderr) = run(&[
+        "aes(x: date, y: temperature) | line() | point(color: humidity)",
+        "--dry-run",
+        "-i",
+        "fixtures/timeseries.csv",
+    ]);
+    assert!(ok, "{}", stderr);
+    assert!(stdout.starts_with("Data: "), "{}", stdout);
+    assert!(
+        stdout.contains("Layer 1: line() x = date, y = temperature\n"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains(
+            "Layer 2: point() x = date, y = temperature, color = humidity (continuous)\n"
+        ),
+        "{}",
+        stdout
+    );
+    assert!(stdout.ends_with("Facets: none\n"), "{}", stdout);
+
+    let (ok, _, stderr) = run(&[
+        "aes(x: date, y: temp) | line()",
+        "--dry-run",
+        "-i",
+        "fixtures/timeseries.csv",
+    ]);
+    assert!(!ok);
+    assert!(stderr.contains("Unknown column 'temp'"), "{}", stderr);
+
+    // Without --list-columns a DSL is still required
+    let (ok, _, stderr) = run(&["-i", "fixtures/timeseries.csv"]);
+    assert!(!ok);
+    assert!(stderr.contains("required"), "{}", stderr);
+}
+
+#[test]
+fn test_end_to_end_explain_json() {
+    let csv = "month,revenue,region,year\n\
+               1,2,a,2020\n2,3,b,2020\n3,4,a,2020\n\
+               1,5,a,2021\n2,6,b,2021\n";
+    let output = run_gramgraph_with_args(
+        "aes(x: month, y: revenue, color: region) | line() | facet_wrap(by: year)",
+        csv,
+        &["--explain", "json"],
+    )
+    .expect("explain should succeed");
+    let json: serde_json::Value = serde_json::from_slice(&output).expect("stdout is JSON");
+
+    assert_eq!(json["rows"], 5);
+    assert_eq!(json["x_axis"], "continuous");
+    let layer = &json["layers"][0];
+    assert_eq!(layer["geom"], "line");
+    assert_eq!(layer["columns"]["x"], "month");
+    assert_eq!(layer["columns"]["y"], "revenue");
+    assert_eq!(layer["grouping"]["by"]["color"], "region");
+    assert_eq!(layer["grouping"]["groups"], 2);
+    assert_eq!(json["facet"]["by"], serde_json::json!(["year"]));
+
+    let panels = json["panels"].as_array().unwrap();
+    let titles: Vec<_> = panels
+        .iter()
+        .map(|p| (p["title"].clone(), p["rows"].clone()))
+        .collect();
+    assert_eq!(
+        titles,
+        [
+            (serde_json::json!("2020"), serde_json::json!(3)),
+            (serde_json::json!("2021"), serde_json::json!(2)),
+        ]
+    );
+    for panel in panels {
+        assert_eq!(panel["x"]["min"], 1.0);
+        assert_eq!(panel["x"]["max"], 3.0);
+        assert!(panel["y"]["max"].as_f64().unwrap() >= 6.0);
+    }
+
+    // Errors are JSON on stdout, with a failing exit status
+    let output = Command::new("cargo")
+        .args(["run", "--bin", "gramgraph", "--"])
+        .args(["aes(x: date, y: temp) | line()", "--explain", "json"])
+        .args(["-i", "fixtures/timeseries.csv"])
+        .stdin(Stdio::null())
+        .output()
+        .expect("Failed to run process");
+    assert!(!output.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).expect("stdout is JSON");
+    let message = json["error"]["message"].as_str().unwrap();
+    assert!(message.contains("Unknown column 'temp'"), "{}", message);
+    assert_eq!(json["error"]["causes"][0], "Failed to plan plot");
+}
+
 #[test]
 fn test_end_to_end_mixing_bar_and_line() {
     let csv = "x,y\n1,10\n2,20\n3,30\n";
@@ -549,27 +1242,171 @@ fn test_end_to_end_facet_with_ncol() {
     assert!(is_valid_png(&png_bytes));
 }
 
-// Combined features tests
+#[test]
+fn test_end_to_end_facet_with_nrow_and_legacy_ncol() {
+    let csv =
+        fs::read_to_string("fixtures/multiregion_sales.csv").expect("Failed to read test CSV");
+    let svg = |facet: &str| {
+        run_gramgraph_svg(
+            &format!("aes(x: time, y: sales) | line() | {}", facet),
+            &csv,
+        )
+    };
+    // One row of panels is the same layout as as many columns as panels
+    let regions = csv
+        .lines()
+        .skip(1)
+        .filter_map(|l| l.split(',').nth(2))
+        .collect::<std::collections::HashSet<_>>()
+        .len();
+    assert_eq!(
+        svg("facet_wrap(by: region, nrow: 1)").unwrap(),
+        svg(&format!("facet_wrap(by: region, ncol: {})", regions)).unwrap()
+    );
+
+    let err = svg("facet_wrap(by: region, ncol: 2, nrow: 1)").unwrap_err();
+    assert!(err.contains("ncol or nrow, not both"), "{}", err);
+
+    // The old Some(n) spelling still renders, with a deprecation warning
+    let mut child = Command::new("cargo")
+        .args(["run", "--bin", "gramgraph", "--"])
+        .arg("aes(x: time, y: sales) | line() | facet_wrap(by: region, ncol: Some(2))")
+        .args(["--format", "svg"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to spawn process");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(csv.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    assert!(
+        stderr.contains("ncol: Some(2)) is deprecated"),
+        "{}",
+        stderr
+    );
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        svg("facet_wrap(by: region, ncol: 2)").unwrap()
+    );
+}
 
 #[test]
-fn test_end_to_end_facet_plus_grouping() {
+fn test_end_to_end_facet_with_only_missing_values() {
+    // Every "East" row lacks a y value; the plot still renders, with East's panel
+    // titled and marked empty
+    let csv = "time,sales,region\n1,10,North\n2,12,North\n1,NA,East\n2,,East\n1,7,West\n2,9,West\n";
+    for scales in ["fixed", "free"] {
+        let svg = run_gramgraph_svg(
+            &format!(
+                r#"aes(x: time, y: sales) | line() | point() | facet_wrap(by: region, ncol: 2, scales: "{}")"#,
+                scales
+            ),
+            csv,
+        )
+        .unwrap_or_else(|e| panic!("{}: {}", scales, e));
+        for label in [
+            "region = East",
+            "region = North",
+            "region = West",
+            "No data",
+        ] {
+            assert!(svg.contains(label), "{}: missing {}", scales, label);
+        }
+        assert_eq!(svg.matches("No data").count(), 1);
+    }
+}
+
+#[test]
+fn test_end_to_end_facet_title_sits_above_the_panels() {
     let csv =
         fs::read_to_string("fixtures/multiregion_sales.csv").expect("Failed to read test CSV");
-    let result = run_gramgraph(
-        "aes(x: time, y: sales, color: product) | line() | facet_wrap(by: region)",
+    let title = "Monthly Sales for Every Region, Product Line, and Sales Channel Since Launch";
+    let svg = run_gramgraph_svg(
+        &format!(
+            r#"aes(x: time, y: sales) | line() | facet_wrap(by: region) | labs(title: "{}")"#,
+            title
+        ),
         &csv,
+    )
+    .unwrap();
+
+    // (y, font-size) of the <text> element whose content is `label`
+    let text_at = |label: &str| -> (f64, f64) {
+        let end = svg
+            .find(&format!(">\n{}\n</text>", label))
+            .unwrap_or_else(|| panic!("no text {:?}", label));
+        let tag = &svg[svg[..end].rfind("<text").unwrap()..end];
+        let attr = |name: &str| -> f64 {
+            let start = tag.find(&format!(" {}=\"", name)).unwrap() + name.len() + 3;
+            tag[start..start + tag[start..].find('"').unwrap()]
+                .parse()
+                .unwrap()
+        };
+        (attr("y"), attr("font-size"))
+    };
+    let (title_y, title_size) = text_at(title);
+    let (panel_y, panel_size) = text_at("region = East");
+
+    // Centered over the whole grid, larger than the panel captions, and clear of them
+    assert!(svg.contains(r#"<text x="400" y="8" dy="0.76em" text-anchor="middle""#));
+    assert!(title_size > panel_size, "{} vs {}", title_size, panel_size);
+    assert!(
+        title_y + title_size * 1.3 < panel_y,
+        "title at {} ({}px) runs into panels at {}",
+        title_y,
+        title_size,
+        panel_y
     );
-    assert!(result.is_ok(), "Failed: {:?}", result.err());
-    let png_bytes = result.unwrap();
-    assert!(is_valid_png(&png_bytes));
 }
 
 #[test]
-fn test_end_to_end_multiple_layers_grouped() {
+fn test_end_to_end_facet_grid() {
+    let csv =
+        fs::read_to_string("fixtures/region_year_sales.csv").expect("Failed to read test CSV");
+    let svg = run_gramgraph_svg(
+        "aes(x: month, y: sales) | line() | point() | facet_grid(rows: region, cols: year)",
+        &csv,
+    )
+    .unwrap();
+    // A 3x2 grid (North has no 2024 rows): each column label once along the top and
+    // each row label once down the right
+    for label in [
+        "year = 2023",
+        "year = 2024",
+        "region = East",
+        "region = North",
+        "region = West",
+    ] {
+        assert_eq!(svg.matches(label).count(), 1, "{}", label);
+    }
+
+    let err = run_gramgraph_svg(
+        "aes(x: month, y: sales) | line() | facet_wrap(by: region) | facet_grid(cols: year)",
+        &csv,
+    )
+    .unwrap_err();
+    assert!(
+        err.contains("facet_wrap() or facet_grid(), not both"),
+        "{}",
+        err
+    );
+}
+
+// Combined features tests
+
+#[test]
+fn test_end_to_end_facet_plus_grouping() {
     let csv =
         fs::read_to_string("fixtures/multiregion_sales.csv").expect("Failed to read test CSV");
     let result = run_gramgraph(
-        "aes(x: time, y: sales, color: region) | line() | point()",
+        "aes(x: time, y: sales, color: product) | line() | facet_wrap(by: region)",
         &csv,
     );
     assert!(result.is_ok(), "Failed: {:?}", result.err());
@@ -578,10 +1415,1193 @@ fn test_end_to_end_multiple_layers_grouped() {
 }
 
 #[test]
-fn test_end_to_end_boxplot() {
-    let csv = fs::read_to_string("fixtures/iris.csv").expect("Failed to read test CSV");
-    let result = run_gramgraph("aes(x: species, y: sepal_length) | boxplot()", &csv);
+fn test_end_to_end_categorical_x_in_facets() {
+    let csv =
+        fs::read_to_string("fixtures/region_year_sales.csv").expect("Failed to read test CSV");
+    // Count category tick labels, which plotters writes on their own line
+    let tick_count = |svg: &str, label: &str| svg.lines().filter(|l| l.trim() == label).count();
+
+    // North has no 2024 rows, but fixed scales keep it on both panels so bars align
+    for dsl in [
+        r#"aes(x: region, y: sales) | bar(stat: "sum") | facet_wrap(by: year)"#,
+        r#"aes(x: region, y: sales, color: month) | bar(position: "dodge") | facet_wrap(by: year)"#,
+        "aes(x: region, y: sales, color: month) | line() | point() | facet_wrap(by: year)",
+    ] {
+        let svg = run_gramgraph_svg(dsl, &csv).unwrap_or_else(|e| panic!("{}: {}", dsl, e));
+        for region in ["East", "North", "West"] {
+            assert_eq!(tick_count(&svg, region), 2, "{} in {}", region, dsl);
+        }
+    }
+
+    // Free x drops the category from the panel that has no data for it
+    let svg = run_gramgraph_svg(
+        r#"aes(x: region, y: sales, color: month) | bar(position: "dodge") | facet_wrap(by: year, scales: "free_x")"#,
+        &csv,
+    )
+    .unwrap();
+    assert_eq!(tick_count(&svg, "North"), 1);
+    assert_eq!(tick_count(&svg, "West"), 2);
+}
+
+#[test]
+fn test_end_to_end_line_linetypes() {
+    let csv =
+        fs::read_to_string("fixtures/multiregion_sales.csv").expect("Failed to read test CSV");
+    let polylines = |svg: &str, color: &str| {
+        svg.lines()
+            .filter(|l| l.contains("<polyline") && l.contains(&format!("stroke=\"{}\"", color)))
+            .count()
+    };
+
+    // A solid line is one polyline plus its legend key; dashes split it into many,
+    // and dots (shorter) into more still
+    let svg = run_gramgraph_svg(
+        "aes(x: time, y: sales, color: region, linetype: region) | line()",
+        &csv,
+    )
+    .unwrap();
+    let east = polylines(&svg, "#0000FF");
+    let north = polylines(&svg, "#FFA500");
+    let south = polylines(&svg, "#008000");
+    assert_eq!(east, 2, "East is solid");
+    assert!(north > east, "North is dashed");
+    assert!(south > north, "South is dotted");
+    for region in ["East", "North", "South"] {
+        assert_eq!(svg.lines().filter(|l| l.trim() == region).count(), 1);
+    }
+
+    // A mapped linetype alone groups the series and gives each a legend entry
+    let svg = run_gramgraph_svg(
+        r#"aes(x: time, y: sales) | line(linetype: region, color: "red")"#,
+        &csv,
+    )
+    .unwrap();
+    assert!(polylines(&svg, "#FF0000") > 4);
+    assert_eq!(svg.lines().filter(|l| l.trim() == "South").count(), 1);
+
+    let solid = run_gramgraph_svg("aes(x: time, y: sales, color: region) | line()", &csv).unwrap();
+    let dashed = run_gramgraph_svg(
+        r#"aes(x: time, y: sales, color: region) | line(linetype: "dotdash")"#,
+        &csv,
+    )
+    .unwrap();
+    assert_eq!(polylines(&solid, "#0000FF"), 2);
+    assert!(polylines(&dashed, "#0000FF") > 2);
+}
+
+#[test]
+fn test_end_to_end_point_binning() {
+    let mut csv = String::from("x,y\n");
+    for i in 0..2_000 {
+        csv.push_str(&format!("{},{}\n", i % 20, (i / 20) % 30));
+    }
+    let circles = |args: &[&str], dsl: &str| {
+        let mut args = args.to_vec();
+        args.extend(["--format", "svg"]);
+        let svg = String::from_utf8(run_gramgraph_with_args(dsl, &csv, &args).unwrap()).unwrap();
+        svg.lines().filter(|l| l.starts_with("<circle")).count()
+    };
+
+    // Under the default threshold nothing changes
+    let exact = circles(&[], "aes(x: x, y: y) | point(fast: false)");
+    assert_eq!(exact, 2_000);
+    assert_eq!(circles(&[], "aes(x: x, y: y) | point()"), exact);
+
+    // Over it, each occupied pixel is drawn once
+    let binned = circles(&["--max-exact-points", "1000"], "aes(x: x, y: y) | point()");
+    assert_eq!(binned, 20 * 30);
+    let forced = circles(
+        &["--max-exact-points", "1000"],
+        "aes(x: x, y: y) | point(fast: false)",
+    );
+    assert_eq!(forced, exact);
+}
+
+#[test]
+fn test_end_to_end_line_simplify() {
+    // Far more points than pixel columns
+    let mut csv = String::from("x,y\n");
+    for i in 0..20_000 {
+        csv.push_str(&format!("{},{}\n", i, (i * 7919) % 1000));
+    }
+    let line_points = |svg: &str| {
+        svg.lines()
+            .filter(|l| l.contains("<polyline") && l.contains("stroke=\"#0000FF\""))
+            .map(|l| l.matches(',').count())
+            .max()
+            .unwrap()
+    };
+
+    let simplified = run_gramgraph_svg("aes(x: x, y: y) | line()", &csv).unwrap();
+    let full = run_gramgraph_svg("aes(x: x, y: y) | line(simplify: false)", &csv).unwrap();
+    assert_eq!(line_points(&full), 20_000);
+    // At most first, lowest, highest, and last for each column of an 800px plot
+    assert!(
+        line_points(&simplified) <= 4 * 800,
+        "{}",
+        line_points(&simplified)
+    );
+
+    // Sparse lines keep every point
+    let csv = "x,y\n1,5\n2,3\n3,8\n";
+    assert_eq!(
+        run_gramgraph_svg("aes(x: x, y: y) | line()", csv).unwrap(),
+        run_gramgraph_svg("aes(x: x, y: y) | line(simplify: false)", csv).unwrap()
+    );
+}
+
+#[test]
+fn test_end_to_end_group_without_styling() {
+    let mut csv = String::from("time,value,sensor\n");
+    for sensor in 0..50 {
+        for time in 0..5 {
+            csv.push_str(&format!("{},{},sensor{}\n", time, sensor + time, sensor));
+        }
+    }
+    let svg = run_gramgraph_svg(
+        r#"aes(x: time, y: value, group: sensor) | line(color: "red")"#,
+        &csv,
+    )
+    .unwrap();
+    let red = svg
+        .lines()
+        .filter(|l| l.contains("<polyline") && l.contains("stroke=\"#FF0000\""))
+        .count();
+    assert_eq!(red, 50);
+    assert!(!svg.contains("sensor0"), "group columns have no legend");
+
+    let ungrouped =
+        run_gramgraph_svg(r#"aes(x: time, y: value) | line(color: "red")"#, &csv).unwrap();
+    assert_eq!(
+        ungrouped
+            .lines()
+            .filter(|l| l.contains("<polyline") && l.contains("stroke=\"#FF0000\""))
+            .count(),
+        1
+    );
+}
+
+#[test]
+fn test_end_to_end_multiple_layers_grouped() {
+    let csv =
+        fs::read_to_string("fixtures/multiregion_sales.csv").expect("Failed to read test CSV");
+    let result = run_gramgraph(
+        "aes(x: time, y: sales, color: region) | line() | point()",
+        &csv,
+    );
+    assert!(result.is_ok(), "Failed: {:?}", result.err());
+    let png_bytes = result.unwrap();
+    assert!(is_valid_png(&png_bytes));
+}
+
+#[test]
+fn test_end_to_end_boxplot() {
+    let csv = fs::read_to_string("fixtures/iris.csv").expect("Failed to read test CSV");
+    let result = run_gramgraph("aes(x: species, y: sepal_length) | boxplot()", &csv);
     assert!(result.is_ok(), "Failed: {:?}", result.err());
     let png_bytes = result.unwrap();
     assert!(is_valid_png(&png_bytes));
 }
+
+#[test]
+fn test_end_to_end_text_labels_grouped() {
+    let csv = "\
+gdp,life_exp,country,continent
+2.1,52.7,Nigeria,Africa
+51.4,80.6,Germany,Europe
+89.2,83.2,A Very Long Country Name That Runs Past The Plot Edge,Europe
+";
+    let result = run_gramgraph_svg(
+        "aes(x: gdp, y: life_exp, color: continent) | point() | text(label: country, size: 10, nudge_y: 1)",
+        csv,
+    );
+    assert!(result.is_ok(), "Failed: {:?}", result.err());
+    let svg = result.unwrap();
+    assert!(svg.contains("Nigeria"), "SVG did not contain label text");
+    assert!(svg.contains("A Very Long Country Name That Runs Past The Plot Edge"));
+
+    // PNG rendering must not panic when labels extend past the canvas
+    let result = run_gramgraph(
+        "aes(x: gdp, y: life_exp) | text(label: country, nudge_x: 80)",
+        csv,
+    );
+    assert!(result.is_ok(), "Failed: {:?}", result.err());
+    assert!(is_valid_png(&result.unwrap()));
+}
+
+#[test]
+fn test_end_to_end_tile_with_colorbar() {
+    // Both axes categorical
+    let csv = "\
+hour,weekday,count
+morning,Mon,3
+evening,Mon,9
+morning,Tue,5
+evening,Tue,12
+";
+    let result = run_gramgraph_svg("aes(x: hour, y: weekday, fill: count) | tile()", csv);
+    assert!(result.is_ok(), "Failed: {:?}", result.err());
+    let svg = result.unwrap();
+    // Colorbar title plus min/max labels
+    let texts: Vec<&str> = svg.lines().map(str::trim).collect();
+    assert!(
+        texts.contains(&"count"),
+        "SVG did not contain colorbar title"
+    );
+    assert!(texts.contains(&"3") && texts.contains(&"12"));
+
+    // Numeric x with regular spacing
+    let csv = "\
+hour,weekday,count
+0,Mon,1
+6,Mon,4
+12,Mon,9
+0,Tue,2
+6,Tue,5
+12,Tue,7
+";
+    let result = run_gramgraph("aes(x: hour, y: weekday, fill: count) | tile()", csv);
+    assert!(result.is_ok(), "Failed: {:?}", result.err());
+    assert!(is_valid_png(&result.unwrap()));
+
+    // legend_position: "none" hides the colorbar
+    let result = run_gramgraph_svg(
+        r#"aes(x: hour, y: weekday, fill: count) | tile() | theme(legend_position: "none")"#,
+        csv,
+    );
+    assert!(!result.unwrap().lines().any(|line| line.trim() == "count"));
+}
+
+#[test]
+fn test_end_to_end_adversarial_category_strings() {
+    let csv =
+        fs::read_to_string("fixtures/adversarial_categories.csv").expect("Failed to read test CSV");
+    let dsl = "aes(x: x, y: y, color: name) | point(size: 6) | facet_wrap(by: region) | labs(title: \"東京 😀 Ünïcödé\")";
+
+    let result = run_gramgraph(dsl, &csv);
+    assert!(result.is_ok(), "Failed: {:?}", result.err());
+    assert!(is_valid_png(&result.unwrap()));
+
+    let result = run_gramgraph_svg(dsl, &csv);
+    assert!(result.is_ok(), "Failed: {:?}", result.err());
+    let svg = result.unwrap();
+    let texts: Vec<&str> = svg.lines().map(str::trim).collect();
+
+    // Facet titles keep commas and non-Latin scripts intact
+    assert!(texts.contains(&"region = North, East"));
+    assert!(texts.contains(&"region = 南部"));
+
+    // Exactly one legend entry per category, with XML-escaped text
+    let expected = [
+        "He said &quot;hi&quot;",
+        "Smith, Jones &amp; Co.",
+        "東京都",
+        "😀 Party 🎉",
+        "مرحبا بالعالم",
+        "&lt;tag&gt; &amp; &apos;apostrophe&apos;",
+    ];
+    for label in expected {
+        let count = texts.iter().filter(|line| **line == label).count();
+        assert_eq!(count, 1, "Expected one legend entry for {}", label);
+    }
+}
+
+#[test]
+fn test_end_to_end_continuous_color_gradient() {
+    let csv = "\
+x,y,depth
+1,2,10
+2,4,15
+3,6,20
+4,8,30
+";
+    let result = run_gramgraph_svg(
+        r#"aes(x: x, y: y, color: depth) | point(size: 5) | scale_color(low: "blue", high: "red")"#,
+        csv,
+    );
+    assert!(result.is_ok(), "Failed: {:?}", result.err());
+    let svg = result.unwrap();
+    let texts: Vec<&str> = svg.lines().map(str::trim).collect();
+
+    // A colorbar with min/mid/max labels replaces per-value legend entries
+    assert!(texts.contains(&"depth"));
+    assert!(texts.contains(&"10") && texts.contains(&"20") && texts.contains(&"30"));
+    assert!(
+        !texts.contains(&"15"),
+        "continuous values should not become legend entries"
+    );
+    // Lowest value takes the low endpoint, highest the high endpoint
+    assert!(svg.contains("fill=\"#0000FF\""));
+    assert!(svg.contains("fill=\"#FF0000\""));
+
+    let result = run_gramgraph(
+        r#"aes(x: x, y: y, color: depth) | point() | scale_color(low: "notacolor", high: "red")"#,
+        csv,
+    );
+    assert!(result.unwrap_err().contains("Unknown color 'notacolor'"));
+}
+
+/// Compare a rendered PNG against `tests/golden/<name>.png` using the same
+/// `image_diff` code path as `gramgraph diff`. Run with `UPDATE_GOLDENS=1`
+/// to rewrite the reference image.
+fn assert_matches_golden(name: &str, png: &[u8]) {
+    if let Err(message) = compare_golden(name, png) {
+        panic!("{}", message);
+    }
+}
+
+/// The golden check behind `assert_matches_golden`. A mismatch writes the new
+/// render and a difference heat image to `target/golden-failures/` for review
+fn compare_golden(name: &str, png: &[u8]) -> Result<(), String> {
+    let path = std::path::Path::new("tests/golden").join(format!("{}.png", name));
+    if std::env::var_os("UPDATE_GOLDENS").is_some() {
+        fs::create_dir_all("tests/golden").unwrap();
+        fs::write(&path, png).unwrap();
+        return Ok(());
+    }
+    let reference = fs::read(&path).map_err(|_| {
+        format!(
+            "Missing golden image {}; run with UPDATE_GOLDENS=1",
+            path.display()
+        )
+    })?;
+    let result = gramgraph::image_diff::diff_bytes(&reference, png, 8).unwrap();
+    if !result.dimensions_differ() && result.mismatch_percent() <= 0.1 {
+        return Ok(());
+    }
+    let failures = std::path::Path::new("target/golden-failures");
+    fs::create_dir_all(failures).unwrap();
+    fs::write(failures.join(format!("{}.png", name)), png).unwrap();
+    let heat = gramgraph::image_diff::encode_png(&result.diff_image).unwrap();
+    fs::write(failures.join(format!("{}.diff.png", name)), heat).unwrap();
+    Err(format!(
+        "{} differs from golden: {:.2}% of pixels changed (see target/golden-failures/)",
+        name,
+        result.mismatch_percent()
+    ))
+}
+
+/// Golden-image cases: name, fixture, DSL. They render through the library with
+/// `placeholder_text`, so text is drawn as boxes and the references hold on any
+/// machine regardless of its fonts, while still catching layout drift
+const GOLDEN_CASES: &[(&str, &str, &str)] = &[
+    (
+        "line_continuous",
+        "fixtures/timeseries.csv",
+        r##"aes(x: date, y: temperature) | line(color: "#4682b4", width: 2) | labs(title: "Temperature", x: "Day", y: "Degrees")"##,
+    ),
+    (
+        "scatter_grouped_legend",
+        "fixtures/iris.csv",
+        "aes(x: sepal_length, y: sepal_width, color: species) | point(size: 4)",
+    ),
+    (
+        "bars_dodged",
+        "fixtures/stacked_data.csv",
+        r#"aes(x: category, y: value, color: segment) | bar(position: "dodge")"#,
+    ),
+    (
+        "bars_stacked",
+        "fixtures/stacked_data.csv",
+        r#"aes(x: category, y: value, color: segment) | bar(position: "stack")"#,
+    ),
+    (
+        "facet_grid_2x2",
+        "fixtures/region_year_sales.csv",
+        "aes(x: region, y: sales) | bar() | facet_grid(rows: year, cols: month)",
+    ),
+    // A 200-character title and caption wrap inside the canvas, and twelve-digit
+    // y ticks push the axis title over rather than into them
+    (
+        "long_title_wide_ticks",
+        "fixtures/large_values.csv",
+        r#"aes(x: step, y: total) | mutate(step: x / 10000000000, total: y * 10) | line() | labs(title: "Quarterly revenue for every region and product line, shown in US dollars before tax and returns, for the fiscal years covered by the finance team's audited annual reports", y: "Revenue (USD)", caption: "Source: consolidated ledger export, adjusted for currency movements at the quarterly closing rate")"#,
+    ),
+    // Dashed, recolored major grid lines over a light minor grid
+    (
+        "grid_minor_dashed",
+        "fixtures/timeseries.csv",
+        r##"aes(x: date, y: temperature) | line(color: "#4682b4", width: 2) | theme(grid_minor: true, grid_color: "#E06666", grid_width: 1.5, grid_linetype: "dashed")"##,
+    ),
+    // Category separators in every facet panel
+    (
+        "grid_separators_facets",
+        "fixtures/stacked_data.csv",
+        r##"aes(x: category, y: value) | bar() | facet_wrap(by: segment) | theme(grid_separators: true, grid_color: "#888888")"##,
+    ),
+];
+
+#[test]
+fn test_golden_cases() {
+    let mut failures = Vec::new();
+    for (name, fixture, dsl) in GOLDEN_CASES {
+        let csv = fs::read_to_string(fixture).unwrap();
+        let data = gramgraph::csv_reader::read_csv(
+            csv.as_bytes(),
+            gramgraph::csv_reader::CsvOptions::default(),
+        )
+        .unwrap();
+        let options = gramgraph::RenderOptions {
+            width: 400,
+            height: 300,
+            placeholder_text: true,
+            ..gramgraph::RenderOptions::default()
+        };
+        let png = gramgraph::render(dsl, data, options).unwrap();
+        if let Err(message) = compare_golden(name, &png) {
+            failures.push(message);
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn test_grid_knobs_change_pixels_and_compose_with_grid_none() {
+    let render = |fixture: &str, dsl: &str| {
+        let csv = fs::read_to_string(fixture).unwrap();
+        let data = gramgraph::csv_reader::read_csv(
+            csv.as_bytes(),
+            gramgraph::csv_reader::CsvOptions::default(),
+        )
+        .unwrap();
+        let options = gramgraph::RenderOptions {
+            width: 400,
+            height: 300,
+            placeholder_text: true,
+            ..gramgraph::RenderOptions::default()
+        };
+        gramgraph::render(dsl, data, options).unwrap()
+    };
+    let mismatched = |a: &[u8], b: &[u8]| {
+        gramgraph::image_diff::diff_bytes(a, b, 0)
+            .unwrap()
+            .mismatched
+    };
+    let line = |theme: &str| {
+        render(
+            "fixtures/timeseries.csv",
+            &format!("aes(x: date, y: temperature) | line() | theme({theme})"),
+        )
+    };
+    let bars = |theme: &str| {
+        render(
+            "fixtures/stacked_data.csv",
+            &format!(r#"aes(x: category, y: value) | bar(stat: "sum") | theme({theme})"#),
+        )
+    };
+
+    // Each knob changes the render on its own
+    let base = line(r#"grid: "major""#);
+    for knob in [
+        "grid_minor: true",
+        r#"grid_color: "red""#,
+        "grid_width: 3",
+        r#"grid_linetype: "dashed""#,
+    ] {
+        let styled = line(&format!(r#"grid: "major", {knob}"#));
+        assert!(mismatched(&base, &styled) > 0, "{knob}");
+    }
+    let bars_base = bars(r#"grid: "major""#);
+    assert!(mismatched(&bars_base, &bars(r#"grid: "major", grid_separators: true"#)) > 0);
+
+    // Styling never brings back a grid that grid: "none" hid, whatever the order
+    let none = line(r#"grid: "none""#);
+    for theme in [
+        r#"grid: "none", grid_color: "red", grid_width: 3, grid_linetype: "dashed""#,
+        r#"grid_color: "red", grid_linetype: "dotted", grid: "none""#,
+    ] {
+        assert_eq!(mismatched(&none, &line(theme)), 0, "{theme}");
+    }
+    let bars_none = bars(r#"grid: "none""#);
+    assert_eq!(
+        mismatched(&bars_none, &bars(r#"grid: "none", grid_separators: true"#)),
+        0
+    );
+
+    // grid_minor: wins over grid:'s choice of minor lines wherever it is listed
+    assert_eq!(
+        mismatched(
+            &line(r#"grid: "major", grid_minor: true"#),
+            &line(r#"grid_minor: true, grid: "major""#)
+        ),
+        0
+    );
+    assert_eq!(
+        mismatched(
+            &line(r#"grid: "both", grid_minor: false"#),
+            &line(r#"grid: "major""#)
+        ),
+        0
+    );
+    // ... and brings the minor lines back alone under grid: "none"
+    assert!(mismatched(&none, &line(r#"grid: "none", grid_minor: true"#)) > 0);
+}
+
+#[test]
+fn test_golden_void_bars() {
+    // theme_void() keeps the render free of visible text, so the reference
+    // does not depend on the fonts installed on the machine. The fixture repeats
+    // category A, so "max" keeps the taller of its two bars
+    let csv = fs::read_to_string("fixtures/bar_chart.csv").unwrap();
+    let png = run_gramgraph(
+        "aes(x: category, y: value1) | bar(stat: \"max\", color: \"#4682b4\") | theme_void()",
+        &csv,
+    )
+    .unwrap();
+    assert!(is_valid_png(&png));
+    assert_matches_golden("void_bars", &png);
+}
+
+#[test]
+fn test_end_to_end_diff_subcommand() {
+    let dir = std::env::temp_dir().join(format!("gramgraph-diff-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let csv = "x,y\n1,10\n2,20\n3,30\n";
+    let old = dir.join("old.png");
+    let new = dir.join("new.png");
+    let out = dir.join("diff.png");
+    fs::write(
+        &old,
+        run_gramgraph("aes(x: x, y: y) | line()", csv).unwrap(),
+    )
+    .unwrap();
+    fs::write(
+        &new,
+        run_gramgraph("aes(x: x, y: y) | line(color: \"red\")", csv).unwrap(),
+    )
+    .unwrap();
+
+    let run_diff = |args: &[&std::path::Path], extra: &[&str]| {
+        Command::new("cargo")
+            .args(["run", "--bin", "gramgraph", "--", "diff"])
+            .args(args)
+            .args(extra)
+            .output()
+            .unwrap()
+    };
+
+    // Identical images pass
+    let same = run_diff(&[&old, &old], &[]);
+    assert!(same.status.success());
+    assert!(String::from_utf8_lossy(&same.stdout).contains("Mismatch: 0.00%"));
+
+    // Changed images fail above the threshold and write a heat image
+    let out_str = out.to_str().unwrap();
+    let changed = run_diff(&[&old, &new], &["--out", out_str]);
+    assert!(!changed.status.success());
+    assert!(String::from_utf8_lossy(&changed.stdout).contains("Mismatch: "));
+    assert!(is_valid_png(&fs::read(&out).unwrap()));
+
+    // ...but pass once the threshold allows for the change
+    let lenient = run_diff(&[&old, &new], &["--threshold", "100"]);
+    assert!(lenient.status.success());
+
+    // The subcommand shows up in the top-level help and names itself in its own
+    let help = |args: &[&str]| {
+        let output = Command::new("cargo")
+            .args(["run", "--bin", "gramgraph", "--"])
+            .args(args)
+            .output()
+            .unwrap();
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+    assert!(help(&["--help"]).contains("diff  Compare two rendered images"));
+    assert!(help(&["diff", "--help"]).contains("Usage: gramgraph diff [OPTIONS] <OLD> <NEW>"));
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_end_to_end_continuous_size_bubbles() {
+    let csv = "x,y,pop\n1,1,5\n2,2,50\n3,3,100\n4,4,75\n";
+    let result = run_gramgraph_svg(r#"aes(x: x, y: y, size: pop) | point()"#, csv);
+    assert!(result.is_ok(), "Failed: {:?}", result.err());
+    let svg = result.unwrap();
+    let texts: Vec<&str> = svg.lines().map(str::trim).collect();
+
+    // Reference bubbles at nice values replace one legend entry per distinct value
+    assert!(texts.contains(&"50") && texts.contains(&"100"));
+    assert!(
+        !texts.contains(&"75"),
+        "continuous values should not become legend entries"
+    );
+
+    // Each point is sized individually
+    let radii: std::collections::HashSet<&str> = svg
+        .split("<circle")
+        .skip(1)
+        .filter_map(|c| c.split("r=\"").nth(1)?.split('"').next())
+        .collect();
+    assert!(radii.len() >= 4, "expected distinct radii, got {:?}", radii);
+}
+
+#[test]
+fn test_end_to_end_input_file() {
+    let result = run_gramgraph_file("aes(x: one, y: two) | line()", "fixtures/basic.csv", &[]);
+    assert!(result.is_ok(), "Failed: {:?}", result.err());
+    assert!(is_valid_png(&result.unwrap()));
+
+    let result = run_gramgraph_file(
+        "aes(x: one, y: two) | line()",
+        "fixtures/basic.csv",
+        &["--format", "svg"],
+    );
+    assert!(String::from_utf8(result.unwrap())
+        .unwrap()
+        .starts_with("<svg"));
+
+    // "-" reads stdin
+    let csv = fs::read_to_string("fixtures/basic.csv").unwrap();
+    let result = run_gramgraph_with_args("aes(x: one, y: two) | line()", &csv, &["--input", "-"]);
+    assert!(is_valid_png(&result.unwrap()));
+
+    let err =
+        run_gramgraph_file("aes(x: one, y: two) | line()", "fixtures/nope.csv", &[]).unwrap_err();
+    assert!(err.contains("Failed to read input file 'fixtures/nope.csv'"));
+
+    let err = run_gramgraph_file("aes(x: one, y: two) | line()", "fixtures", &[]).unwrap_err();
+    assert!(err.contains("Failed to read input file 'fixtures'"));
+}
+
+#[test]
+fn test_end_to_end_layer_data_source() {
+    let svg = |dsl: &str, extra_args: &[&str]| {
+        let args = [&["--format", "svg"], extra_args].concat();
+        run_gramgraph_file(dsl, "fixtures/sales_2024.csv", &args)
+            .map(|bytes| String::from_utf8(bytes).unwrap())
+    };
+    let dsl = r##"aes(x: month, y: revenue) | line(color: "#0000FF") | line(data: "fixtures/sales_2023.csv", color: "#808080")"##;
+    let compared = svg(dsl, &[]).unwrap();
+    for color in ["#0000FF", "#808080"] {
+        let lines = compared
+            .lines()
+            .filter(|l| l.contains("<polyline") && l.contains(color))
+            .count();
+        assert_eq!(lines, 1, "{color}");
+    }
+    // The y axis spans both tables: last year peaked at 210, this year at 171
+    assert!(compared.lines().any(|l| l.trim() == "200"));
+
+    // --data names a file for the layer
+    let aliased = svg(
+        r##"aes(x: month, y: revenue) | line(color: "#0000FF") | line(data: "last", color: "#808080")"##,
+        &["--data", "last=fixtures/sales_2023.csv"],
+    )
+    .unwrap();
+    assert_eq!(aliased, compared);
+
+    let err = svg(
+        r#"aes(x: month, y: revenue) | line() | line(data: "fixtures/nope.csv")"#,
+        &[],
+    )
+    .unwrap_err();
+    assert!(
+        err.contains(r#"Layer 2 data: "fixtures/nope.csv""#),
+        "{err}"
+    );
+    assert!(
+        err.contains("Failed to read data file 'fixtures/nope.csv'"),
+        "{err}"
+    );
+
+    let err = svg(
+        r#"aes(x: month, y: revenue) | line() | line(data: "fixtures/basic.csv")"#,
+        &[],
+    )
+    .unwrap_err();
+    assert!(
+        err.contains(r#"Layer 2 data: "fixtures/basic.csv""#),
+        "{err}"
+    );
+    assert!(err.contains("Unknown columns 'month', 'revenue'"), "{err}");
+}
+
+#[test]
+fn test_end_to_end_annotate() {
+    let svg = |dsl: &str| {
+        run_gramgraph_file(dsl, "fixtures/sales_2024.csv", &["--format", "svg"])
+            .map(|bytes| String::from_utf8(bytes).unwrap())
+    };
+    let out = svg(
+        r#"aes(x: month, y: revenue) | line() | annotate(x: 3, y: 400, text: "policy change", color: "red") | annotate(x: 2, y: 900, text: "hidden", expand: false)"#,
+    )
+    .unwrap();
+    assert!(out.contains("policy change"));
+    assert!(!out.contains("hidden"));
+    // The y axis grew to hold the note
+    assert!(out.lines().any(|l| l.trim() == "400"));
+
+    let err = svg(
+        r#"aes(x: month, y: revenue) | line() | annotate(x: 3, y: 1, text: "a", color: "nope")"#,
+    )
+    .unwrap_err();
+    assert!(err.contains("Unknown color 'nope' in annotate()"), "{err}");
+}
+
+#[test]
+fn test_end_to_end_shade_draws_under_the_grid() {
+    let out = run_gramgraph_file(
+        r##"aes(x: month, y: revenue) | line() | shade(xmin: 3, xmax: 5, color: "#FF0000")"##,
+        "fixtures/sales_2024.csv",
+        &["--format", "svg"],
+    )
+    .map(|bytes| String::from_utf8(bytes).unwrap())
+    .unwrap();
+    let position = |needle: &str| out.lines().position(|l| l.contains(needle)).unwrap();
+    let band = position(r##"fill="#FF0000""##);
+    assert!(band < position("<line"), "{out}");
+    assert!(band < position("<polyline"), "{out}");
+}
+
+#[test]
+fn test_end_to_end_reversed_y_hangs_bars_from_the_top() {
+    // Mean height of the magenta bar pixels, as a fraction of the canvas
+    let bar_centre = |dsl: &str| {
+        let png = run_gramgraph_file(dsl, "fixtures/sales_2024.csv", &[]).unwrap();
+        let image = image::load_from_memory(&png).unwrap().to_rgb8();
+        let rows: Vec<u32> = image
+            .enumerate_pixels()
+            .filter(|(_, _, p)| p.0 == [255, 0, 255])
+            .map(|(_, y, _)| y)
+            .collect();
+        rows.iter().sum::<u32>() as f64 / rows.len() as f64 / image.height() as f64
+    };
+    let dsl = r##"aes(x: month, y: revenue) | bar(color: "#FF00FF")"##;
+    assert!(bar_centre(dsl) > 0.5);
+    assert!(bar_centre(&format!("{dsl} | scale_y(reverse: true)")) < 0.5);
+}
+
+#[test]
+fn test_end_to_end_breaks_label_exactly_the_listed_values() {
+    let csv = "x,y,f\n3,5000,a\n97,8000,a\n10,5500,b\n60,7300,b\n";
+    // Numeric labels centred under their tick: the x axis's (y labels are right-aligned)
+    let x_labels = |dsl: &str| -> Vec<f64> {
+        let svg = run_gramgraph_svg(dsl, csv).unwrap();
+        svg.split("<text")
+            .filter(|tag| tag.contains(r#"text-anchor="middle""#))
+            .filter_map(|tag| {
+                tag.split('>')
+                    .nth(1)?
+                    .split('<')
+                    .next()?
+                    .trim()
+                    .parse()
+                    .ok()
+            })
+            .collect()
+    };
+
+    // The axis reaches 0..100, so 150 is dropped
+    assert_eq!(
+        x_labels("aes(x: x, y: y) | point() | scale_x(breaks: 0, 25, 50, 75, 100, 150)"),
+        vec![0.0, 25.0, 50.0, 75.0, 100.0]
+    );
+    // Each bottom panel of a facet carries the same breaks
+    assert_eq!(
+        x_labels("aes(x: x, y: y) | point() | facet_wrap(by: f) | scale_x(breaks: 10, 90)"),
+        vec![10.0, 90.0, 10.0, 90.0]
+    );
+    // A tick count thins the automatic ticks
+    assert!(x_labels("aes(x: x, y: y) | point() | scale_x(ticks: 2)").len() <= 3);
+}
+
+#[test]
+fn test_end_to_end_unknown_arguments() {
+    let csv = fs::read_to_string("fixtures/simple_grouped.csv").unwrap();
+    let cases = [
+        (
+            r#"aes(x: time, y: value) | line(colour: "red")"#,
+            "'colour' for line()",
+            "color",
+        ),
+        (
+            "aes(x: time, y: value) | line(widht: 2)",
+            "'widht' for line()",
+            "width",
+        ),
+        (
+            "aes(x: time, y: value) | point(sise: 4)",
+            "'sise' for point()",
+            "size",
+        ),
+        (
+            "aes(x: time, y: value, colour: group) | point()",
+            "'colour' for aes()",
+            "color",
+        ),
+        (
+            "aes(x: time, y: value) | point() | facet_wrap(by: group, nclo: 2)",
+            "'nclo' for facet_wrap()",
+            "ncol",
+        ),
+    ];
+    for (dsl, unknown, suggestion) in cases {
+        let err = run_gramgraph(dsl, &csv).unwrap_err();
+        assert!(err.contains(unknown), "{}: {}", dsl, err);
+        assert!(
+            err.contains(&format!("did you mean '{}'?", suggestion)),
+            "{}: {}",
+            dsl,
+            err
+        );
+    }
+}
+
+#[test]
+fn test_end_to_end_unknown_columns() {
+    let csv = fs::read_to_string("fixtures/simple_grouped.csv").unwrap();
+    let err = run_gramgraph(
+        "aes(x: tiem, y: value, color: gruop) | line() | facet_wrap(by: region)",
+        &csv,
+    )
+    .unwrap_err();
+    assert!(
+        err.contains("Unknown columns 'tiem' (did you mean 'time'?), 'gruop' (did you mean 'group'?), 'region'."),
+        "{}",
+        err
+    );
+    assert!(
+        err.contains("Available columns: time, value, group"),
+        "{}",
+        err
+    );
+    assert!(!err.contains("Failed to extract"), "{}", err);
+
+    // Column lookup is case-insensitive
+    assert!(run_gramgraph("aes(x: TIME, y: Value, color: group) | line()", &csv).is_ok());
+}
+
+#[test]
+fn test_end_to_end_spec_json() {
+    let output = Command::new("cargo")
+        .args(["run", "--bin", "gramgraph", "--"])
+        .args(["--spec-json", "fixtures/line_spec.json"])
+        .args(["--input", "fixtures/basic.csv", "--format", "svg"])
+        .stdin(Stdio::null())
+        .output()
+        .expect("Failed to run process");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let dsl = r#"aes(x: one, y: two) | line(color: "red", width: 2) | point(size: 5) | labs(title: "From JSON")"#;
+    let expected = run_gramgraph_file(dsl, "fixtures/basic.csv", &["--format", "svg"]).unwrap();
+    assert_eq!(output.stdout, expected);
+
+    // A DSL string and --spec-json are mutually exclusive
+    let err = run_gramgraph_file(
+        dsl,
+        "fixtures/basic.csv",
+        &["--spec-json", "fixtures/line_spec.json"],
+    )
+    .unwrap_err();
+    assert!(err.contains("cannot be used with"), "{}", err);
+}
+
+#[test]
+fn test_end_to_end_json_input() {
+    let dsl = "aes(x: time, y: temp, color: sensor) | line() | point()";
+    for path in ["fixtures/readings.json", "fixtures/readings.ndjson"] {
+        let result = run_gramgraph_file(dsl, path, &["--input-format", "json", "--format", "svg"]);
+        assert!(result.is_ok(), "{}: {:?}", path, result.err());
+        let svg = String::from_utf8(result.unwrap()).unwrap();
+        let texts: Vec<&str> = svg.lines().map(str::trim).collect();
+        assert!(texts.contains(&"a") && texts.contains(&"b"), "{}", path);
+    }
+
+    let err = run_gramgraph_with_args(
+        "aes(x: time, y: temp) | line()",
+        r#"[{"time": 1, "temp": {"value": 20}}]"#,
+        &["--input-format", "json"],
+    )
+    .unwrap_err();
+    assert!(err.contains("Nested value for key 'temp'"));
+}
+
+#[test]
+fn test_end_to_end_missing_values_break_lines() {
+    let csv = fs::read_to_string("fixtures/gappy_series.csv").unwrap();
+
+    let svg = run_gramgraph_svg("aes(x: month, y: sales, color: region) | line()", &csv).unwrap();
+    let polylines = |color: &str| {
+        svg.lines()
+            .filter(|l| l.contains("<polyline") && l.contains(&format!("stroke=\"{}\"", color)))
+            .count()
+    };
+    // Each series splits into two segments around its interior gap, plus its legend swatch;
+    // gaps at the start and end of a series just shorten it
+    assert_eq!(polylines("#0000FF"), 3);
+    assert_eq!(polylines("#FFA500"), 3);
+
+    let err = run_gramgraph_with_args(
+        "aes(x: month, y: sales, color: region) | line()",
+        &csv,
+        &["--strict"],
+    )
+    .unwrap_err();
+    assert!(err.contains("Failed to parse y value 'NA'"));
+
+    // Rows missing either x or y are skipped for points too
+    let csv = fs::read_to_string("fixtures/missing_values.csv").unwrap();
+    let result = run_gramgraph("aes(x: x, y: y) | point()", &csv);
+    assert!(result.is_ok(), "Failed: {:?}", result.err());
+    assert!(is_valid_png(&result.unwrap()));
+}
+
+#[test]
+fn test_end_to_end_non_finite_values_are_skipped() {
+    let csv = "x,y\n1,10\n2,inf\n3,-inf\n4,NaN\n5,30\n";
+    let stderr = run_gramgraph_stderr("aes(x: x, y: y) | line() | point()", csv).unwrap();
+    assert!(
+        stderr.contains("Warning: skipped 3 row(s) with missing values in layer 1"),
+        "{}",
+        stderr
+    );
+    let svg = run_gramgraph_svg("aes(x: x, y: y) | point()", csv).unwrap();
+    assert!(!svg.contains("NaN") && !svg.contains("inf"));
+
+    let err = run_gramgraph_with_args("aes(x: x, y: y) | point()", csv, &["--strict"]).unwrap_err();
+    assert!(
+        err.contains("Non-finite value 'inf' in column 'y' (row 2)"),
+        "{}",
+        err
+    );
+
+    // Nothing finite to draw still renders
+    let result = run_gramgraph("aes(x: x, y: y) | point()", "x,y\n1,inf\n2,-inf\n");
+    assert!(result.is_ok(), "Failed: {:?}", result.err());
+}
+
+#[test]
+fn test_end_to_end_headerless_csv() {
+    let csv = fs::read_to_string("fixtures/headerless.csv").unwrap();
+
+    let result = run_gramgraph_with_args(
+        "aes(x: c0, y: c1, color: c2) | line() | point()",
+        &csv,
+        &["--no-header", "--format", "svg"],
+    );
+    assert!(result.is_ok(), "Failed: {:?}", result.err());
+    let svg = String::from_utf8(result.unwrap()).unwrap();
+    let texts: Vec<&str> = svg.lines().map(str::trim).collect();
+    assert!(texts.contains(&"north"));
+    assert!(texts.contains(&"south"));
+
+    // With a header row assumed, the synthesized names do not exist
+    let err = run_gramgraph("aes(x: c0, y: c1) | line()", &csv).unwrap_err();
+    assert!(err.contains("c0"));
+
+    // Short rows are reported against the synthesized names
+    let err = run_gramgraph_with_args("aes(x: c0, y: c1) | line()", "1,2\n3\n", &["--no-header"])
+        .unwrap_err();
+    assert!(err.contains("row 2 has 1 fields, expected 2 (c0, c1)"));
+}
+
+#[test]
+fn test_end_to_end_decimal_comma_numbers() {
+    // German export: semicolons between fields, comma decimals, point thousands
+    let german = "x;y\n0,5;1.234,5\n1,5;2.345,75\n2,5;1.800\n";
+    let svg = run_gramgraph_with_args(
+        "aes(x: x, y: y) | line()",
+        german,
+        &["--decimal-comma", "--delimiter", ";", "--format", "svg"],
+    )
+    .unwrap();
+    let svg = String::from_utf8(svg).unwrap();
+    let texts: Vec<&str> = svg.lines().map(str::trim).collect();
+    // Continuous axes: x ticks between the values, y ticks up past 2345.75
+    assert!(texts.contains(&"1"), "x should be numeric");
+    assert!(texts.contains(&"2400"), "y should reach 2345.75");
+    assert!(!texts.contains(&"0,5"), "x should not be categorical");
+
+    // French export: no-break space thousands in a quoted comma-separated file
+    let french = "x,y\n1,\"1\u{a0}234,5\"\n2,\"2\u{a0}000\"\n3,\"999,25\"\n";
+    let svg = run_gramgraph_with_args(
+        "aes(x: x, y: y) | line()",
+        french,
+        &["--decimal-comma", "--format", "svg"],
+    )
+    .unwrap();
+    let svg = String::from_utf8(svg).unwrap();
+    assert!(svg.lines().map(str::trim).any(|text| text == "2000"));
+
+    // Without the flag the German numbers aren't numbers
+    let err = run_gramgraph_with_args("aes(x: x, y: y) | line()", german, &["--delimiter", ";"])
+        .unwrap_err();
+    assert!(err.contains("1.234,5"), "{err}");
+
+    // A text column keeps its labels under the flag
+    let labels = "label;y\nv1.234;1\nv2;2\n1.234;3\n";
+    let svg = run_gramgraph_with_args(
+        "aes(x: label, y: y) | bar()",
+        labels,
+        &["--decimal-comma", "--delimiter", ";", "--format", "svg"],
+    )
+    .unwrap();
+    let svg = String::from_utf8(svg).unwrap();
+    let texts: Vec<&str> = svg.lines().map(str::trim).collect();
+    assert!(texts.contains(&"1.234") && texts.contains(&"v1.234"));
+}
+
+#[test]
+fn test_end_to_end_named_palette() {
+    let csv = fs::read_to_string("fixtures/multiregion_sales.csv").unwrap();
+
+    let svg = run_gramgraph_svg(
+        r#"aes(x: time, y: sales, color: region) | line() | scale_color(palette: "dark2")"#,
+        &csv,
+    )
+    .unwrap();
+    assert!(svg.contains("stroke=\"#1B9E77\""));
+    assert!(svg.contains("stroke=\"#D95F02\""));
+    assert!(!svg.contains("stroke=\"#0000FF\""));
+
+    let result = run_gramgraph(
+        r#"aes(x: time, y: sales, color: region) | line() | scale_color(palette: "rainbow")"#,
+        &csv,
+    );
+    let err = result.unwrap_err();
+    assert!(err.contains("Unknown palette 'rainbow'"));
+    assert!(err.contains("set2, dark2, pastel, tableau20, okabe_ito"));
+}
+
+#[test]
+fn test_end_to_end_manual_color_scale() {
+    let csv = fs::read_to_string("fixtures/multiregion_sales.csv").unwrap();
+
+    // Named entries pin colors; East is not covered and keeps the default palette color
+    let result = run_gramgraph_svg(
+        r##"aes(x: time, y: sales, color: region) | line() | scale_color_manual(values: "North" = "#123456", "South" = "red")"##,
+        &csv,
+    );
+    assert!(result.is_ok(), "Failed: {:?}", result.err());
+    let svg = result.unwrap();
+    assert!(svg.contains("stroke=\"#123456\""));
+    assert!(svg.contains("stroke=\"#FF0000\""));
+    assert!(svg.contains("stroke=\"#0000FF\""));
+
+    // Positional colors are assigned to groups in sorted order
+    let svg = run_gramgraph_svg(
+        r#"aes(x: time, y: sales, color: region) | line() | scale_color_manual("black", "gray", "orange")"#,
+        &csv,
+    )
+    .unwrap();
+    assert!(svg.contains("stroke=\"#000000\""));
+    assert!(svg.contains("stroke=\"#FFA500\""));
+
+    let result = run_gramgraph(
+        r#"aes(x: time, y: sales, color: region) | line() | scale_color_manual("notacolor")"#,
+        &csv,
+    );
+    assert!(result
+        .unwrap_err()
+        .contains("Unknown color 'notacolor' in scale_color_manual"));
+}
+
+#[test]
+fn test_end_to_end_layer_filter_highlight() {
+    let csv = "x,y,g\n1,1,a\n2,2,b\n3,3,a\n4,4,b\n";
+    let circles = |svg: &str, fill: &str| {
+        svg.lines()
+            .filter(|l| l.contains("<circle") && l.contains(fill))
+            .count()
+    };
+
+    let svg = run_gramgraph_svg(
+        r##"aes(x: x, y: y) | point(color: "#CCCCCC") | point(filter: g == "a", color: "#FF0000")"##,
+        csv,
+    )
+    .unwrap();
+    assert_eq!(circles(&svg, "#CCCCCC"), 4);
+    assert_eq!(circles(&svg, "#FF0000"), 2);
+
+    // A filter that matches nothing leaves its layer empty but still renders the rest
+    let svg = run_gramgraph_svg(
+        r##"aes(x: x, y: y) | point(color: "#CCCCCC") | point(filter: y > 100, color: "#FF0000")"##,
+        csv,
+    )
+    .unwrap();
+    assert_eq!(circles(&svg, "#FF0000"), 0);
+}
+
+#[test]
+fn test_end_to_end_bar_border_outlines_bars_and_legend() {
+    let csv = fs::read_to_string("fixtures/simple_grouped.csv").expect("Failed to read test CSV");
+    let plain = run_gramgraph_svg(
+        "aes(x: time, y: value, color: group) | bar(position: \"dodge\")",
+        &csv,
+    )
+    .unwrap();
+    let svg = run_gramgraph_svg(
+        "aes(x: time, y: value, color: group) | bar(position: \"dodge\", border: \"#123456\")",
+        &csv,
+    )
+    .unwrap();
+
+    // Each bar and legend key gains four outline strips in the border color
+    let fills = |svg: &str, color: &str| svg.matches(&format!("fill=\"{}\"", color)).count();
+    let filled = plain.matches("stroke=\"none\"").count() - fills(&plain, "#FFFFFF");
+    assert_eq!(fills(&plain, "#123456"), 0);
+    assert!(filled > 0);
+    assert_eq!(fills(&svg, "#123456"), 4 * filled);
+}
+
+#[test]
+fn test_end_to_end_point_fill_and_stroke_outline_points_and_legend() {
+    let csv = fs::read_to_string("fixtures/simple_grouped.csv").expect("Failed to read test CSV");
+
+    // A fixed fill puts the mapped colors on the outlines, legend keys included
+    let svg = run_gramgraph_svg(
+        "aes(x: time, y: value, color: group) | point(fill: \"#ABCDEF\", stroke: 2)",
+        &csv,
+    )
+    .unwrap();
+    assert_eq!(svg.matches("fill=\"#ABCDEF\"").count(), 10 + 2);
+    assert_eq!(svg.matches("stroke-width=\"2\"").count(), 10 + 2);
+
+    // Without a fill the mapped colors fill and the outline is black
+    let svg = run_gramgraph_svg(
+        "aes(x: time, y: value, color: group) | point(stroke: 1, shape: \"square\")",
+        &csv,
+    )
+    .unwrap();
+    assert!(svg.matches("fill=\"#0000FF\"").count() > 5);
+    let outline_quads = svg
+        .lines()
+        .filter(|l| l.starts_with("<polygon") && l.contains("fill=\"#000000\""))
+        .count();
+    assert_eq!(outline_quads, 4 * (10 + 2));
+}
+
+#[test]
+fn test_end_to_end_ascii_format() {
+    let csv = "x,y\n1,10\n2,14\n3,9\n4,20\n5,17\n";
+    let args = [
+        "--format",
+        "ascii",
+        "--ascii-width",
+        "30",
+        "--ascii-height",
+        "10",
+    ];
+    let text = String::from_utf8(
+        run_gramgraph_with_args("aes(x: x, y: y) | point()", csv, &args).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(text.lines().count(), 10);
+    assert!(text.lines().all(|line| line.chars().count() <= 30));
+    assert_eq!(text.matches('•').count(), 5);
+    assert!(text.starts_with("20│"));
+
+    // Auto color stays off when stdout is a pipe; --color always turns it on
+    assert!(!text.contains('\x1b'));
+    let colored = run_gramgraph_with_args(
+        "aes(x: x, y: y) | point()",
+        csv,
+        &[&args[..], &["--color", "always"]].concat(),
+    )
+    .unwrap();
+    assert!(String::from_utf8(colored).unwrap().contains("\x1b[38;2;"));
+}
@@ -0,0 +1,29 @@
+// The library must keep building for the browser (`wasm` feature). This needs the
+// wasm32-unknown-unknown target and the wasm-bindgen crate, so it is ignored by default:
+//
+//   rustup target add wasm32-unknown-unknown
+//   cargo test --test wasm -- --ignored
+
+use std::process::Command;
+
+#[test]
+#[ignore = "needs the wasm32-unknown-unknown target"]
+fn test_library_checks_for_wasm32() {
+    let output = Command::new(env!("CARGO"))
+        .args([
+            "check",
+            "--lib",
+            "--target",
+            "wasm32-unknown-unknown",
+            "--no-default-features",
+            "--features",
+            "wasm",
+        ])
+        .output()
+        .expect("Failed to run cargo check");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}